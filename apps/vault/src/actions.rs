@@ -200,27 +200,6 @@ impl ActionManager {
     pub(crate) fn menu_addnew(&mut self) {
         match self.mode_cache {
             VaultMode::Password => {
-                let description = match self.modals
-                    .alert_builder(t!("vault.newitem.name", xous::LANG))
-                    .field(None, Some(password_validator))
-                    .build()
-                {
-                    Ok(text) => {
-                        text.content()[0].content.as_str().unwrap_or("UTF-8 error").to_string()
-                    },
-                    _ => {log::error!("Name entry failed"); self.action_active.store(false, Ordering::SeqCst); return}
-                };
-                self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                let username = match self.modals
-                    .alert_builder(t!("vault.newitem.username", xous::LANG))
-                    .field(None, Some(password_validator))
-                    .build()
-                {
-                    Ok(text) => text.content()[0].content.as_str().unwrap_or("UTF-8 error").to_string(),
-                    _ => {log::error!("Name entry failed"); self.action_active.store(false, Ordering::SeqCst); return}
-                };
-                self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                let mut approved = false;
                 // Security note about PasswordGenerator. This is a 3rd party crate. It relies on `rand`'s implementation
                 // of ThreadRng to generate passwords. As of the version committed to the lockfile, I have evidenced the
                 // ThreadRng to request 8 bytes of entropy from our TRNG to seed its state. If the docs are to be trusted,
@@ -256,76 +235,72 @@ impl ActionManager {
                 //           - random_number::random_inclusively_with_rng()
                 //             - Uniform::new_inclusive().sample()
                 //               - dead end at Distribution Trait and UniformSampler Trait, let's hope this is correct?
-                let pg = PasswordGenerator {
-                    length: 20,
-                    numbers: true,
-                    lowercase_letters: true,
-                    uppercase_letters: true,
-                    symbols: true,
-                    spaces: false,
-                    exclude_similar_characters: true,
-                    strict: true,
-                };
-                let mut password = pg.generate_one().unwrap();
-                while !approved {
-                    let maybe_password = match self.modals
-                        .alert_builder(t!("vault.newitem.password", xous::LANG))
-                        .field(Some(password), Some(password_validator))
-                        .build()
-                    {
-                        Ok(text) => {
-                            text.content()[0].content.as_str().unwrap().to_string()
-                        },
-                        _ => {log::error!("Name entry failed"); self.action_active.store(false, Ordering::SeqCst); return}
+                let generate = |length: usize, upper: bool, number: bool, symbol: bool| {
+                    let pg = PasswordGenerator {
+                        length,
+                        numbers: number,
+                        lowercase_letters: true,
+                        uppercase_letters: upper,
+                        symbols: symbol,
+                        spaces: false,
+                        exclude_similar_characters: true,
+                        strict: true,
                     };
-                    self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                    password = if maybe_password.len() == 0 {
-                        let length = match self.modals
-                            .alert_builder(t!("vault.newitem.configure_length", xous::LANG))
-                            .field(Some("20".to_string()), Some(length_validator))
-                            .build()
-                        {
-                            Ok(entry) => entry.content()[0].content.as_str().unwrap().parse::<u32>().unwrap(),
-                            _ => {log::error!("Length entry failed"); self.action_active.store(false, Ordering::SeqCst); return}
-                        };
-                        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                        let mut upper = false;
-                        let mut number = false;
-                        let mut symbol = false;
-                        self.modals
-                            .add_list(vec![
+                    pg.generate_one().unwrap()
+                };
+                let (mut length, mut upper, mut number, mut symbol) = (20, true, true, true);
+                let mut suggestion = generate(length, upper, number, symbol);
+                // anything already entered comes back as a placeholder when the form is shown again with a new suggestion
+                let mut description = String::new();
+                let mut username = String::new();
+                let password = loop {
+                    let mut checked = Vec::new();
+                    if upper {checked.push(t!("vault.newitem.uppercase", xous::LANG));}
+                    if number {checked.push(t!("vault.newitem.numbers", xous::LANG));}
+                    if symbol {checked.push(t!("vault.newitem.symbols", xous::LANG));}
+                    let form = match self.modals
+                        .form_builder(t!("vault.newitem.form", xous::LANG))
+                        .text(t!("vault.newitem.name", xous::LANG), Some(description.as_str()), Some(password_validator))
+                        .text(t!("vault.newitem.username", xous::LANG), Some(username.as_str()), Some(password_validator))
+                        .text(t!("vault.newitem.password_label", xous::LANG), Some(suggestion.as_str()), Some(password_validator))
+                        .text(t!("vault.newitem.configure_length", xous::LANG), Some(length.to_string().as_str()), Some(length_validator))
+                        .checkboxes(t!("vault.newitem.configure_generator", xous::LANG),
+                            &[
                                 t!("vault.newitem.uppercase", xous::LANG),
                                 t!("vault.newitem.numbers", xous::LANG),
                                 t!("vault.newitem.symbols", xous::LANG),
-                            ]).expect("couldn't create configuration modal");
-                        match self.modals.get_checkbox(t!("vault.newitem.configure_generator", xous::LANG)) {
-                            Ok(options) => {
-                                for opt in options {
-                                    if opt == t!("vault.newitem.uppercase", xous::LANG) {upper = true;}
-                                    if opt == t!("vault.newitem.numbers", xous::LANG) {number = true;}
-                                    if opt == t!("vault.newitem.symbols", xous::LANG) {symbol = true;}
-                                }
-                            }
-                            _ => {log::error!("Modal selection error"); self.action_active.store(false, Ordering::SeqCst); return}
-                        }
-                        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                        let pg2 = PasswordGenerator {
-                            length: length as usize,
-                            numbers: number,
-                            lowercase_letters: true,
-                            uppercase_letters: upper,
-                            symbols: symbol,
-                            spaces: false,
-                            exclude_similar_characters: true,
-                            strict: true,
-                        };
-                        approved = false;
-                        pg2.generate_one().unwrap()
-                    } else {
-                        approved = true;
-                        maybe_password
+                            ],
+                            &checked
+                        )
+                        .build()
+                    {
+                        Ok(form) => form,
+                        _ => {log::error!("New item entry failed"); self.action_active.store(false, Ordering::SeqCst); return}
                     };
-                }
+                    self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+                    description = form.field(0).and_then(|f| f.text()).unwrap_or("").to_string();
+                    username = form.field(1).and_then(|f| f.text()).unwrap_or("").to_string();
+                    let maybe_password = form.field(2).and_then(|f| f.text()).unwrap_or("").to_string();
+                    let options = form.field(4).map(|f| f.checked()).unwrap_or_default();
+                    let new_settings = (
+                        form.field(3).and_then(|f| f.text()).and_then(|l| l.parse::<usize>().ok()).unwrap_or(length),
+                        options.contains(&t!("vault.newitem.uppercase", xous::LANG)),
+                        options.contains(&t!("vault.newitem.numbers", xous::LANG)),
+                        options.contains(&t!("vault.newitem.symbols", xous::LANG)),
+                    );
+                    // a blank password, or an accepted suggestion whose generator settings were since changed,
+                    // asks for a new suggestion
+                    if maybe_password.len() > 0
+                    && (maybe_password != suggestion || new_settings == (length, upper, number, symbol)) {
+                        break maybe_password;
+                    }
+                    let (new_length, new_upper, new_number, new_symbol) = new_settings;
+                    length = new_length;
+                    upper = new_upper;
+                    number = new_number;
+                    symbol = new_symbol;
+                    suggestion = generate(length, upper, number, symbol);
+                };
                 let record = PasswordRecord {
                     version: VAULT_PASSWORD_REC_VERSION,
                     description,
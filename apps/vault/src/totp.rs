@@ -3,7 +3,7 @@ use sha1::Sha1;
 use hmac::{Hmac, Mac, NewMac};
 use std::{
     convert::TryFrom,
-    time::{SystemTime, SystemTimeError},
+    time::{Duration, SystemTime, SystemTimeError},
 };
 use std::sync::{Arc, Mutex};
 use xous::{Message, send_message};
@@ -144,7 +144,7 @@ pub(crate) fn pumper(mode: Arc<Mutex<VaultMode>>, sid: xous::SID, main_conn: xou
     let _ = thread::spawn({
         move || {
             let tt = ticktimer_server::Ticktimer::new().unwrap();
-            let self_conn = xous::connect(sid).unwrap();
+            let mut alarm = None;
             loop {
                 let msg = xous::receive_message(sid).unwrap();
                 let opcode: Option<PumpOp> = FromPrimitive::from_usize(msg.body.id());
@@ -156,18 +156,23 @@ pub(crate) fn pumper(mode: Arc<Mutex<VaultMode>>, sid: xous::SID, main_conn: xou
                             0, 0, 0, 0)
                         ).expect("couldn't pump redraw");
                         let mode_cache = {(*mode.lock().unwrap()).clone()};
-                        { // we really want mode.lock() to be in a different scope so...
-                            if mode_cache == VaultMode::Totp {
-                                tt.sleep_ms(2000).unwrap();
-                                send_message(self_conn,
-                                    Message::new_scalar(PumpOp::Pump.to_usize().unwrap(),
-                                    0, 0, 0, 0)
-                                ).expect("couldn't restart pump");
+                        if mode_cache == VaultMode::Totp {
+                            if alarm.is_none() {
+                                alarm = tt.set_alarm(sid, PumpOp::Pump.to_usize().unwrap(), 0,
+                                    Duration::from_millis(2000), Some(Duration::from_millis(2000))
+                                ).ok();
                             }
+                        } else if let Some(id) = alarm.take() {
+                            // once we leave Totp mode, the alarm stops and the redraws stop with it
+                            tt.cancel_alarm(id);
                         }
-                        // if not in Totp mode, the restart message doesn't go through, and the redraws automatically stop.
                     },
-                    Some(PumpOp::Quit) => {break;}
+                    Some(PumpOp::Quit) => {
+                        if let Some(id) = alarm.take() {
+                            tt.cancel_alarm(id);
+                        }
+                        break;
+                    }
                     _ => log::warn!("couldn't parse message: {:?}", msg),
                 }
             }
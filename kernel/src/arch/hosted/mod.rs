@@ -19,7 +19,7 @@ use crate::services::SystemServices;
 
 use crossbeam_channel::{unbounded, Receiver, RecvError, RecvTimeoutError, Sender};
 
-use xous_kernel::{ProcessInit, ProcessKey, Result, SysCall, ThreadInit, PID, TID};
+use xous_kernel::{ProcessInit, ProcessKey, Result, Role, SysCall, ThreadInit, PID, TID};
 
 enum ThreadMessage {
     SysCall(PID, TID, SysCall),
@@ -39,12 +39,21 @@ enum ExitMessage {
 thread_local!(static NETWORK_LISTEN_ADDRESS: RefCell<SocketAddr> = RefCell::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0)));
 thread_local!(static SEND_ADDR: RefCell<Option<Sender<SocketAddr>>> = RefCell::new(None));
 thread_local!(static PID1_KEY: RefCell<[u8; 16]> = RefCell::new([0u8; 16]));
+thread_local!(static BOOT_TIME: std::time::Instant = std::time::Instant::now());
+thread_local!(static PROCESS_ROLES: RefCell<Vec<(PID, Role)>> = RefCell::new(Vec::new()));
 
 #[cfg(test)]
 pub fn set_pid1_key(new_key: [u8; 16]) {
     PID1_KEY.with(|p1k| *p1k.borrow_mut() = new_key);
 }
 
+/// Set the roles to hand out once the kernel starts. There is no loader in
+/// hosted mode, so this stands in for the one that passes roles on hardware.
+#[cfg(test)]
+pub fn set_process_roles(roles: &[(PID, Role)]) {
+    PROCESS_ROLES.with(|pr| *pr.borrow_mut() = roles.to_vec());
+}
+
 /// Set the network address for this particular thread.
 #[cfg(test)]
 pub fn set_listen_address(new_address: &SocketAddr) {
@@ -85,6 +94,11 @@ pub fn current_pid() -> PID {
     crate::arch::process::current_pid()
 }
 
+/// Milliseconds since the kernel started
+pub fn elapsed_ms() -> u64 {
+    BOOT_TIME.with(|boot_time| boot_time.elapsed().as_millis() as u64)
+}
+
 /// Each client gets its own connection and its own thread, which is handled here.
 fn handle_connection(
     conn: TcpStream,
@@ -488,6 +502,13 @@ pub fn idle() -> bool {
     let process_1 = SystemServices::with_mut(|ss| ss.create_process(pid1_init)).unwrap();
     assert_eq!(process_1.pid().get(), 1);
     let _tid1 = SystemServices::with_mut(|ss| ss.create_thread(process_1.pid(), ThreadInit {})).unwrap();
+    PROCESS_ROLES.with(|pr| {
+        SystemServices::with_mut(|ss| {
+            for (pid, role) in pr.borrow().iter() {
+                ss.assign_role(*pid, *role);
+            }
+        })
+    });
 
     let listen_addr = env::var("XOUS_LISTEN_ADDR")
         .map(|s| {
@@ -543,7 +564,27 @@ pub fn idle() -> bool {
         }
     }
 
-    while let Ok(msg) = message_receiver.recv() {
+    loop {
+        // Wake any threads whose timeouts have run out, then wait for the next
+        // message, but no longer than the next deadline.
+        let now = elapsed_ms();
+        let next_deadline = SystemServices::with_mut(|ss| {
+            ss.expire_timeouts(now);
+            ss.timeouts.next_deadline()
+        });
+        let msg = match next_deadline {
+            Some(deadline) => match message_receiver
+                .recv_timeout(std::time::Duration::from_millis(deadline.saturating_sub(now)))
+            {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match message_receiver.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+        };
         match msg {
             ThreadMessage::NewConnection(conn, access_key) => {
                 // The new process should already have a PID registered. Convert its access key
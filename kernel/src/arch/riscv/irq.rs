@@ -332,7 +332,7 @@ pub extern "C" fn trap_handler(
                     .expect("couldn't debug current process");
                 println!("Program suspended. You may inspect it using gdb.");
             } else {
-                ss.terminate_process(pid)
+                ss.terminate_process(pid, xous_kernel::ExitReason::Exception(sc.bits()))
                     .expect("couldn't terminate current process");
             };
             crate::syscall::reset_switchto_caller();
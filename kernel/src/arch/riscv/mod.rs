@@ -25,6 +25,10 @@ pub struct Wfi {
     pub base: *mut usize,
 }
 
+/// A read-only view of the ticktimer, which serves as the kernel's clock. The
+/// ticktimer server owns this device, so the page is mapped without claiming it.
+pub const TICKTIMER_KERNEL: usize = 0xffcc_0000;
+
 pub fn current_pid() -> PID {
     PID::new(satp::read().asid() as _).unwrap()
 }
@@ -45,6 +49,18 @@ pub fn init() {
     let mut wfi_kernel_csr = CSR::new(WFI_KERNEL.base as *mut u32);
     wfi_kernel_csr.wfo(utra::wfi::IGNORE_LOCKED_IGNORE_LOCKED, 1);
 
+    MemoryManager::with_mut(|memory_manager| {
+        mem::map_page_inner(
+            memory_manager,
+            PID::new(1).unwrap(),
+            utra::ticktimer::HW_TICKTIMER_BASE,
+            TICKTIMER_KERNEL,
+            MemoryFlags::R,
+            false,
+        )
+        .expect("unable to map ticktimer")
+    });
+
     unsafe {
         sie::set_ssoft();
         sie::set_sext();
@@ -52,6 +68,21 @@ pub fn init() {
     rand::init();
 }
 
+/// Milliseconds since the ticktimer was last reset, which happens once when
+/// the ticktimer server starts.
+pub fn elapsed_ms() -> u64 {
+    let ticktimer_csr = CSR::new(TICKTIMER_KERNEL as *mut u32);
+    // Read the upper half on either side of the lower half, in case the lower
+    // half rolled over in between.
+    loop {
+        let high = ticktimer_csr.r(utra::ticktimer::TIME1);
+        let low = ticktimer_csr.r(utra::ticktimer::TIME0);
+        if ticktimer_csr.r(utra::ticktimer::TIME1) == high {
+            return ((high as u64) << 32) | low as u64;
+        }
+    }
+}
+
 /// Put the core to sleep until an interrupt hits. Returns `true`
 /// to indicate the kernel should not exit.
 pub fn idle() -> bool {
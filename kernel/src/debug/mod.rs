@@ -263,20 +263,42 @@ fn process_irq_character(b: u8) {
             });
         }
         b'r' => {
-            println!("RAM usage:");
+            use xous_kernel::{Resource, RESOURCE_UNLIMITED};
+            println!("Resource usage:");
             let mut total_bytes = 0;
             crate::services::SystemServices::with(|system_services| {
                 crate::mem::MemoryManager::with(|mm| {
+                    println!(" pid |  ram   | thr | srv | conn |   ticks    | process");
                     for process in &system_services.processes {
                         if !process.free() {
                             let bytes_used = mm.ram_used_by(process.pid);
                             total_bytes += bytes_used;
+                            let usage = |resource| {
+                                system_services
+                                    .resource_usage(process.pid, resource)
+                                    .unwrap_or((0, RESOURCE_UNLIMITED))
+                            };
                             println!(
-                                "    PID {:>3}: {:>4} k {}",
+                                " {:>3} | {:>4} k | {:>3} | {:>3} | {:>4} | {:>10} | {}",
                                 process.pid,
                                 bytes_used / 1024,
+                                usage(Resource::Threads).0,
+                                usage(Resource::Servers).0,
+                                usage(Resource::Connections).0,
+                                usage(Resource::CpuTicks).0,
                                 system_services.process_name(process.pid).unwrap_or("")
                             );
+                            let limits = [
+                                ("pages", mm.page_limit(process.pid)),
+                                ("threads", usage(Resource::Threads).1),
+                                ("servers", usage(Resource::Servers).1),
+                                ("connections", usage(Resource::Connections).1),
+                            ];
+                            for (name, limit) in limits.iter() {
+                                if *limit != RESOURCE_UNLIMITED {
+                                    println!("       limit: {} {}", limit, name);
+                                }
+                            }
                         }
                     }
                 });
@@ -301,6 +323,10 @@ fn process_irq_character(b: u8) {
                 }
             });
         }
+        #[cfg(feature = "ipc-trace")]
+        b't' => {
+            crate::trace::Trace::with_mut(|trace| trace.dump());
+        }
         #[cfg(all(feature = "gdbserver", baremetal))]
         b'g' => {
             println!("Starting GDB server -- attach your debugger now");
@@ -316,8 +342,10 @@ fn process_irq_character(b: u8) {
             println!(" m  | print MMU page tables of all processes");
             println!(" p  | print all processes");
             println!(" P  | print all processes and threads");
-            println!(" r  | report RAM usage of all processes");
+            println!(" r  | report resource usage and limits of all processes");
             println!(" s  | print all allocated servers");
+            #[cfg(feature = "ipc-trace")]
+            println!(" t  | dump the IPC trace");
         }
         _ => {}
     }
@@ -24,6 +24,9 @@ mod mem;
 mod server;
 mod services;
 mod syscall;
+mod timeout;
+#[cfg(feature = "ipc-trace")]
+mod trace;
 
 use services::SystemServices;
 use xous_kernel::*;
@@ -107,39 +110,9 @@ pub unsafe extern "C" fn init(arg_offset: *const u32, init_offset: *const u32, r
 /// Loop through the SystemServices list to determine the next PID to be run.
 /// If no process is ready, return `None`.
 fn next_pid_to_run(last_pid: Option<PID>) -> Option<PID> {
-    // PIDs are 1-indexed but arrays are 0-indexed.  By not subtracting
-    // 1 from the PID when we use it as an array index, we automatically
-    // pick the next process in the list.
-    let current_pid = last_pid.unwrap_or(unsafe { PID::new_unchecked(1) }).get() as usize;
-
-    SystemServices::with(|system_services| {
-        for test_idx in current_pid..system_services.processes.len() {
-            if system_services.processes[test_idx].ppid.get() == 1 {
-                // print!("PID {} is owned by PID1... ", test_idx + 1);
-                if system_services.processes[test_idx].runnable() {
-                    // println!(" and is runnable");
-                    return match pid_from_usize(test_idx + 1) {
-                        Ok(x) => Some(x),
-                        Err(_) => None,
-                    };
-                }
-                // println!(" and is NOT RUNNABLE");
-            }
-        }
-        for test_idx in 0..current_pid {
-            if system_services.processes[test_idx].ppid.get() == 1 {
-                // print!("PID {} is owned by PID1... ", test_idx + 1);
-                if system_services.processes[test_idx].runnable() {
-                    // println!(" and is runnable");
-                    return match pid_from_usize(test_idx + 1) {
-                        Ok(x) => Some(x),
-                        Err(_) => None,
-                    };
-                }
-                // println!(" and is NOT RUNNABLE");
-            }
-        }
-        None
+    SystemServices::with_mut(|system_services| {
+        system_services.expire_timeouts(arch::elapsed_ms());
+        system_services.pick_next_process(last_pid)
     })
 }
 
@@ -4,9 +4,9 @@
 use core::fmt;
 
 pub use crate::arch::mem::{MemoryMapping, PAGE_SIZE};
-use crate::arch::process::Process;
+use crate::arch::process::{Process, MAX_PROCESS_COUNT};
 
-use xous_kernel::{MemoryFlags, MemoryRange, PID};
+use xous_kernel::{MemoryFlags, MemoryRange, PID, RESOURCE_UNLIMITED};
 
 #[derive(Debug)]
 enum ClaimReleaseMove {
@@ -47,6 +47,10 @@ pub struct MemoryManager {
     ram_name: u32,
     #[allow(dead_code)]
     last_ram_page: usize,
+    /// Number of main RAM pages owned by each process, indexed by PID - 1
+    pages_owned: [usize; MAX_PROCESS_COUNT],
+    /// Maximum number of main RAM pages each process may own, indexed by PID - 1
+    page_limits: [usize; MAX_PROCESS_COUNT],
 }
 
 impl Default for MemoryManager {
@@ -77,6 +81,8 @@ impl MemoryManager {
             ram_size: 0,
             ram_name: 0,
             last_ram_page: 0,
+            pages_owned: [0; MAX_PROCESS_COUNT],
+            page_limits: [RESOURCE_UNLIMITED; MAX_PROCESS_COUNT],
         }
     }
 
@@ -173,6 +179,16 @@ impl MemoryManager {
         unsafe {
             MEMORY_ALLOCATIONS = slice::from_raw_parts_mut(base as *mut Option<PID>, mem_size)
         };
+
+        // The loader has already handed out pages to the initial processes
+        unsafe {
+            for owner in MEMORY_ALLOCATIONS[0..self.ram_size / PAGE_SIZE]
+                .iter()
+                .flatten()
+            {
+                self.account_ram_page(None, Some(*owner));
+            }
+        }
         Ok(())
     }
 
@@ -192,6 +208,45 @@ impl MemoryManager {
         owned_bytes
     }
 
+    /// Number of main RAM pages owned by the specified process
+    pub fn pages_owned_by(&self, pid: PID) -> usize {
+        self.pages_owned[pid.get() as usize - 1]
+    }
+
+    /// Maximum number of main RAM pages the specified process may own
+    pub fn page_limit(&self, pid: PID) -> usize {
+        self.page_limits[pid.get() as usize - 1]
+    }
+
+    /// Set the maximum number of main RAM pages the specified process may own,
+    /// returning the previous limit. Pages it already owns are not taken away.
+    pub fn set_page_limit(&mut self, pid: PID, limit: usize) -> usize {
+        core::mem::replace(&mut self.page_limits[pid.get() as usize - 1], limit)
+    }
+
+    /// Return an error if the specified process may not be given another page
+    #[cfg(baremetal)]
+    fn check_page_limit(&self, pid: PID) -> Result<(), xous_kernel::Error> {
+        if self.pages_owned_by(pid) >= self.page_limit(pid) {
+            return Err(xous_kernel::Error::ResourceLimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Move the accounting of one page of main RAM from one owner to another
+    #[cfg(baremetal)]
+    fn account_ram_page(&mut self, from: Option<PID>, to: Option<PID>) {
+        if from == to {
+            return;
+        }
+        if let Some(from) = from {
+            self.pages_owned[from.get() as usize - 1] -= 1;
+        }
+        if let Some(to) = to {
+            self.pages_owned[to.get() as usize - 1] += 1;
+        }
+    }
+
     #[cfg(all(baremetal, feature = "print-debug"))]
     pub fn print_ownership(&self) {
         println!("Ownership ({} bytes in all):", unsafe {
@@ -250,6 +305,8 @@ impl MemoryManager {
     /// This function CANNOT zero the page, as it hasn't been mapped yet.
     #[cfg(baremetal)]
     pub fn alloc_page(&mut self, pid: PID) -> Result<usize, xous_kernel::Error> {
+        self.check_page_limit(pid)?;
+
         // Go through all RAM pages looking for a free page.
         // println!("Allocating page for PID {}", pid);
         unsafe {
@@ -266,6 +323,7 @@ impl MemoryManager {
                 // );
                 if allocation.is_none() {
                     *allocation = Some(pid);
+                    self.account_ram_page(None, Some(pid));
                     self.last_ram_page = index + 1;
                     // if self.last_ram_page >= end_point {
                     //     self.last_ram_page = 0;
@@ -722,7 +780,15 @@ impl MemoryManager {
         // Happy path: The address is in main RAM
         if addr >= self.ram_start && addr < self.ram_start + self.ram_size {
             offset += (addr - self.ram_start) / PAGE_SIZE;
-            return unsafe { action_inner(&mut MEMORY_ALLOCATIONS[offset], pid, action) };
+            let owner = unsafe { &mut MEMORY_ALLOCATIONS[offset] };
+            let previous_owner = *owner;
+            if let (ClaimReleaseMove::Claim, None) = (&action, previous_owner) {
+                self.check_page_limit(pid)?;
+            }
+            action_inner(&mut *owner, pid, action)?;
+            let new_owner = *owner;
+            self.account_ram_page(previous_owner, new_owner);
+            return Ok(());
         }
 
         offset += self.ram_size / PAGE_SIZE;
@@ -805,6 +871,9 @@ impl MemoryManager {
                     // Mark this page as free, which allows it to be re-allocated.
                     *owner = None;
                 }
+                if idx < self.ram_size / PAGE_SIZE {
+                    self.account_ram_page(Some(_pid), *owner);
+                }
             }
         }
     }
@@ -52,6 +52,12 @@ impl SenderID {
     pub fn new(sidx: usize, idx: usize, pid: Option<PID>) -> Self {
         SenderID { sidx, idx, pid }
     }
+
+    /// The process ID that sent this message
+    #[allow(dead_code)]
+    pub fn pid(&self) -> Option<PID> {
+        self.pid
+    }
 }
 
 impl From<usize> for SenderID {
@@ -100,6 +106,9 @@ pub enum WaitingMessage {
 
     /// This memory should be returned to the system.
     ForgetMemory(MemoryRange),
+
+    /// The sender stopped waiting for a reply, so there is nobody to give it to.
+    TimedOut,
 }
 
 /// Internal representation of a queued message for a server. This should be
@@ -238,6 +247,23 @@ enum QueuedMessage {
         u8,    /* message index */
         usize, /* server return address */
     ),
+
+    /// The sender of a blocking message stopped waiting before the Server
+    /// received it. The slot keeps its place in line so that the messages
+    /// behind it are still received in order, and is skipped when its turn comes.
+    TimedOut(
+        u16, /* client PID */
+        u8,  /* client TID */
+        u8,  /* message index */
+    ),
+
+    /// The Server received a blocking scalar message, but its sender stopped
+    /// waiting before the Server replied. The reply will be discarded.
+    WaitingTimedOut(
+        u16, /* client PID */
+        u8,  /* client TID */
+        u8,  /* message index */
+    ),
 }
 
 impl QueuedMessage {
@@ -331,7 +357,10 @@ impl Server {
                 // For `Empty` and `Scalar` messages, all we have to do is ignore them.
                 // The sending process will not be blocked. These messages will be dropped,
                 // and the server will never see them.
-                QueuedMessage::Empty | QueuedMessage::ScalarMessage(_, _, _, _, _, _, _, _, _) => {}
+                QueuedMessage::Empty
+                | QueuedMessage::ScalarMessage(_, _, _, _, _, _, _, _, _)
+                | QueuedMessage::TimedOut(_, _, _)
+                | QueuedMessage::WaitingTimedOut(_, _, _) => {}
 
                 // For `Send` messages, the Server has not yet seen these messages. Simply
                 // prevent this memory from getting mapped into the Server and free it.
@@ -479,6 +508,86 @@ impl Server {
         Ok(())
     }
 
+    /// Tear down a server whose process is exiting. Unlike `destroy()`, this also
+    /// handles messages the server has received but not yet answered, since it
+    /// never will. Every client that is still waiting is woken with a
+    /// `ServerDisconnected` error, and any memory it lent is returned to it.
+    ///
+    /// The server's process must be the active one, since lent memory is mapped
+    /// into it. The memory backing the queue goes away along with the process.
+    pub fn abandon(self, ss: &mut SystemServices) {
+        let server_pid = self.pid;
+        for entry in self.queue.iter() {
+            let (client_pid, client_tid, lent) = match *entry {
+                QueuedMessage::BlockingScalarMessage(pid, tid, _, _, _, _, _, _, _)
+                | QueuedMessage::WaitingReturnScalar(pid, tid, _, _) => (pid, tid, None),
+
+                QueuedMessage::MemoryMessageROLend(
+                    pid,
+                    tid,
+                    _idx,
+                    client_addr,
+                    _id,
+                    server_addr,
+                    buf_size,
+                    _,
+                    _,
+                )
+                | QueuedMessage::MemoryMessageRWLend(
+                    pid,
+                    tid,
+                    _idx,
+                    client_addr,
+                    _id,
+                    server_addr,
+                    buf_size,
+                    _,
+                    _,
+                ) => (pid, tid, Some((server_addr, client_addr, buf_size))),
+                QueuedMessage::WaitingReturnMemory(
+                    pid,
+                    tid,
+                    _idx,
+                    server_addr,
+                    client_addr,
+                    buf_size,
+                ) => (pid, tid, Some((server_addr, client_addr, buf_size))),
+
+                // Nobody is waiting on anything else. Moved memory and messages from
+                // processes that have already exited are freed along with this process.
+                _ => continue,
+            };
+
+            // Messages this process sent to itself have nobody left to answer to.
+            let client_pid = match PID::new(client_pid as _) {
+                Some(pid) if pid != server_pid => pid,
+                _ => continue,
+            };
+            let client_tid = client_tid as _;
+
+            if let Some((server_addr, client_addr, buf_size)) = lent {
+                ss.return_memory(
+                    server_addr as *mut usize,
+                    client_pid,
+                    client_tid,
+                    client_addr as _,
+                    buf_size,
+                )
+                .ok();
+            }
+            ss.timeouts.cancel(client_pid, client_tid);
+            if cfg!(baremetal) {
+                ss.ready_thread(client_pid, client_tid).ok();
+            }
+            ss.set_thread_result(
+                client_pid,
+                client_tid,
+                xous_kernel::Result::Error(xous_kernel::Error::ServerDisconnected),
+            )
+            .ok();
+        }
+    }
+
     // pub fn print_queue(&self) {
     //     println!("    Q Queue Head: {}", self.queue_head);
     //     println!("    Q Queue Tail: {}", self.queue_tail);
@@ -584,8 +693,10 @@ impl Server {
             QueuedMessage::WaitingReturnScalar(pid, tid, idx, return_address) => {
                 (pid, tid, idx, return_address, 0, 0, true, false)
             }
+            QueuedMessage::WaitingTimedOut(pid, tid, idx) => (pid, tid, idx, 0, 0, 0, true, false),
             _ => return Ok(WaitingMessage::None),
         };
+        let timed_out = matches!(*current_val, QueuedMessage::WaitingTimedOut(_, _, _));
 
         // Sanity check the specified address was correct, and matches what we
         // had cached.
@@ -620,6 +731,10 @@ impl Server {
         //     tid
         // );
 
+        if timed_out {
+            return Ok(WaitingMessage::TimedOut);
+        }
+
         if !is_memory {
             return Ok(WaitingMessage::ScalarMessage(
                 PID::new(pid as _).unwrap(),
@@ -938,6 +1053,23 @@ impl Server {
                     self.head_generation = self.head_generation.wrapping_add(1);
                     return Some(msg);
                 }
+                // Nobody is waiting on this message anymore, so drop it and look for
+                // the next one.
+                QueuedMessage::TimedOut(_, _, idx) if idx == self.head_generation => {
+                    self.queue[queue_idx] = QueuedMessage::Empty;
+                    if queue_idx == self.queue_tail {
+                        self.queue_tail += 1;
+                        if self.queue_tail >= self.queue.len() {
+                            self.queue_tail = 0;
+                        }
+                    }
+                    self.head_generation = self.head_generation.wrapping_add(1);
+                    if self.tail_generation == self.head_generation {
+                        return None;
+                    }
+                    queue_idx = self.queue_tail;
+                    continue;
+                }
                 _ => {
                     queue_idx += 1;
                     if queue_idx >= self.queue.len() {
@@ -963,6 +1095,127 @@ impl Server {
         }
     }
 
+    /// Withdraw a blocking message that the Server has not received yet, because
+    /// its sender has given up waiting. The slot becomes a `TimedOut` placeholder.
+    ///
+    /// Returns what needs to be handed back to the sender, or `WaitingMessage::None`
+    /// if the slot does not hold a queued blocking message from this sender.
+    pub fn cancel_queued_message(
+        &mut self,
+        message_index: usize,
+        pid: PID,
+        tid: TID,
+    ) -> WaitingMessage {
+        let entry = match self.queue.get_mut(message_index) {
+            Some(entry) => entry,
+            None => return WaitingMessage::None,
+        };
+        let (msg_pid, msg_tid, idx, waiting) = match *entry {
+            QueuedMessage::BlockingScalarMessage(msg_pid, msg_tid, idx, _, _, _, _, _, _) => (
+                msg_pid,
+                msg_tid,
+                idx,
+                WaitingMessage::ScalarMessage(pid, tid),
+            ),
+            QueuedMessage::MemoryMessageROLend(
+                msg_pid,
+                msg_tid,
+                idx,
+                client_addr,
+                _,
+                server_addr,
+                buf_size,
+                _,
+                _,
+            )
+            | QueuedMessage::MemoryMessageRWLend(
+                msg_pid,
+                msg_tid,
+                idx,
+                client_addr,
+                _,
+                server_addr,
+                buf_size,
+                _,
+                _,
+            ) => {
+                let waiting = match (
+                    MemoryAddress::new(server_addr),
+                    MemoryAddress::new(client_addr),
+                    MemorySize::new(buf_size),
+                ) {
+                    (Some(server_addr), Some(client_addr), Some(len)) => {
+                        WaitingMessage::BorrowedMemory(pid, tid, server_addr, client_addr, len)
+                    }
+                    _ => return WaitingMessage::None,
+                };
+                (msg_pid, msg_tid, idx, waiting)
+            }
+            _ => return WaitingMessage::None,
+        };
+        if msg_pid != pid.get() as u16 || msg_tid != tid as u8 {
+            return WaitingMessage::None;
+        }
+        *entry = QueuedMessage::TimedOut(msg_pid, msg_tid, idx);
+        waiting
+    }
+
+    /// Stop waiting for the reply to a blocking message that the Server has
+    /// already received, because its sender has given up. The Server may still
+    /// reply later: a scalar reply is discarded, and lent memory is forgotten
+    /// rather than returned, since the sender gets it back right away.
+    ///
+    /// Returns what needs to be handed back to the sender, or `WaitingMessage::None`
+    /// if the slot does not hold a received message from this sender.
+    pub fn abandon_waiting_message(
+        &mut self,
+        message_index: usize,
+        pid: PID,
+        tid: TID,
+    ) -> WaitingMessage {
+        let entry = match self.queue.get_mut(message_index) {
+            Some(entry) => entry,
+            None => return WaitingMessage::None,
+        };
+        match *entry {
+            QueuedMessage::WaitingReturnScalar(msg_pid, msg_tid, idx, _)
+                if msg_pid == pid.get() as u16 && msg_tid == tid as u8 =>
+            {
+                *entry = QueuedMessage::WaitingTimedOut(msg_pid, msg_tid, idx);
+                WaitingMessage::ScalarMessage(pid, tid)
+            }
+            QueuedMessage::WaitingReturnMemory(
+                msg_pid,
+                msg_tid,
+                idx,
+                server_addr,
+                client_addr,
+                len,
+            ) if msg_pid == pid.get() as u16 && msg_tid == tid as u8 => {
+                let waiting = match (
+                    MemoryAddress::new(server_addr),
+                    MemoryAddress::new(client_addr),
+                    MemorySize::new(len),
+                ) {
+                    (Some(server_addr), Some(client_addr), Some(len)) => {
+                        WaitingMessage::BorrowedMemory(pid, tid, server_addr, client_addr, len)
+                    }
+                    _ => return WaitingMessage::None,
+                };
+                *entry = QueuedMessage::WaitingForget(
+                    msg_pid,
+                    msg_tid,
+                    idx,
+                    server_addr,
+                    client_addr,
+                    len,
+                );
+                waiting
+            }
+            _ => WaitingMessage::None,
+        }
+    }
+
     /// Add the given message to this server's queue.
     ///
     /// # Errors
@@ -1195,6 +1448,16 @@ impl Server {
         self.ready_threads |= 1 << tid;
     }
 
+    /// Remove the given thread from the list of threads waiting for a message.
+    /// Returns `false` if the thread was not waiting.
+    pub fn unpark_thread(&mut self, tid: TID) -> bool {
+        if self.ready_threads & (1 << tid) == 0 {
+            return false;
+        }
+        self.ready_threads &= !(1 << tid);
+        true
+    }
+
     /// Add the given context to the list of ready and waiting contexts.
     pub fn park_thread(&mut self, tid: TID) {
         klog!("parking thread {}", tid);
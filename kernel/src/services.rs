@@ -11,14 +11,22 @@ use xous_kernel::MemoryRange;
 use core::num::NonZeroU8;
 
 use crate::filled_array;
-use crate::server::Server;
+use crate::server::{SenderID, Server, WaitingMessage};
+use crate::timeout::{Timeout, Timeouts, Wait};
 // use core::mem;
 use xous_kernel::{
-    pid_from_usize, Error, MemoryAddress, Message, ProcessInit, ThreadInit, CID, PID, SID, TID,
+    pid_from_usize, Error, ExitReason, MemoryAddress, Message, MessageEnvelope, ProcessExit,
+    ProcessInit, Resource, Role, ThreadInit, ThreadPriority, CID, PID, RESOURCE_UNLIMITED, ROLE_COUNT,
+    SID, TID,
 };
 
 const MAX_SERVER_COUNT: usize = 128;
 
+/// A ready thread (or process) that has been passed over this many times in favour of
+/// others is treated as if it had one priority level more. This is what keeps a busy high
+/// priority thread from starving everything else.
+pub const STARVATION_LIMIT: u8 = 8;
+
 pub use crate::arch::process::{INITIAL_TID, MAX_PROCESS_COUNT};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -44,6 +52,19 @@ pub struct SystemServices {
 
     /// A table of all servers in the system
     pub servers: [Option<Server>; MAX_SERVER_COUNT],
+
+    /// Threads that are blocked with a deadline
+    pub timeouts: Timeouts,
+
+    /// The process that is told when other processes exit, and the server
+    /// it wants to hear about it on
+    supervisor: Option<(PID, SID)>,
+
+    /// The process that holds each role, indexed by `Role as usize - 1`
+    roles: [Option<PID>; ROLE_COUNT],
+
+    /// When the CPU last passed from one process to another, in ms since boot
+    switched_at_ms: u64,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -109,6 +130,188 @@ impl Default for ProcessState {
     }
 }
 
+/// Number of thread slots with scheduling state. Hosted TIDs are 1-based, so
+/// they go up to `MAX_THREAD + 1`.
+const THREAD_SLOTS: usize = arch::process::MAX_THREAD + 2;
+
+/// The bits of a thread's scheduling state that count how often it was passed over.
+/// The `ThreadPriority` is in the remaining top two bits.
+const PASSED_OVER_MASK: u8 = 0x3f;
+
+/// Per-process scheduling state: the priority of each thread, and how many times each
+/// thread and the process as a whole were ready to run but passed over.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Schedule {
+    /// Per thread, the priority and the count of scheduling decisions that went to
+    /// some other thread, packed into a byte
+    threads: [u8; THREAD_SLOTS],
+
+    /// Count of scheduling decisions that went to some other process
+    process_passed_over: u8,
+}
+
+fn is_ready(ready: usize, tid: TID) -> bool {
+    tid < usize::BITS as usize && ready & (1 << tid) != 0
+}
+
+impl Schedule {
+    pub const fn new() -> Self {
+        Schedule {
+            threads: [(ThreadPriority::Normal as u8) << 6; THREAD_SLOTS],
+            process_passed_over: 0,
+        }
+    }
+
+    pub fn priority(&self, tid: TID) -> ThreadPriority {
+        ThreadPriority::from_usize((self.threads[tid] >> 6) as usize).unwrap()
+    }
+
+    /// Set the priority of the given thread, returning its previous priority
+    pub fn set_priority(&mut self, tid: TID, priority: ThreadPriority) -> ThreadPriority {
+        let previous = self.priority(tid);
+        self.threads[tid] = ((priority as u8) << 6) | self.passed_over(tid);
+        previous
+    }
+
+    fn passed_over(&self, tid: TID) -> u8 {
+        self.threads[tid] & PASSED_OVER_MASK
+    }
+
+    fn set_passed_over(&mut self, tid: TID, passed_over: u8) {
+        self.threads[tid] =
+            (self.threads[tid] & !PASSED_OVER_MASK) | passed_over.min(PASSED_OVER_MASK);
+    }
+
+    /// Return a thread slot to its initial state, for when a new thread is created in it
+    pub fn reset_thread(&mut self, tid: TID) {
+        self.threads[tid] = (ThreadPriority::default() as u8) << 6;
+    }
+
+    /// The priority of the thread, promoted by one level for every `STARVATION_LIMIT`
+    /// times it was passed over
+    pub fn effective_priority(&self, tid: TID) -> u8 {
+        Self::promote(self.priority(tid) as u8, self.passed_over(tid))
+    }
+
+    fn promote(priority: u8, passed_over: u8) -> u8 {
+        (priority + passed_over / STARVATION_LIMIT).min(ThreadPriority::High as u8)
+    }
+
+    /// Choose which of the `ready` threads to run, and age the ones that weren't chosen.
+    /// The thread with the highest effective priority wins; of those, the one that was
+    /// passed over the most. Remaining ties go to the first thread after `last_tid`, so
+    /// that equals take turns.
+    pub fn pick_thread(&mut self, ready: usize, last_tid: TID) -> Option<TID> {
+        let mut best: Option<(TID, (u8, u8))> = None;
+        for offset in 1..=THREAD_SLOTS {
+            let tid = (last_tid + offset) % THREAD_SLOTS;
+            if !is_ready(ready, tid) {
+                continue;
+            }
+            let key = (self.effective_priority(tid), self.passed_over(tid));
+            if best.map_or(true, |(_, best_key)| key > best_key) {
+                best = Some((tid, key));
+            }
+        }
+        let (chosen, _) = best?;
+        for tid in 0..THREAD_SLOTS {
+            if tid == chosen {
+                self.set_passed_over(tid, 0);
+            } else if is_ready(ready, tid) {
+                self.set_passed_over(tid, self.passed_over(tid) + 1);
+            }
+        }
+        Some(chosen)
+    }
+
+    /// The key by which this process competes with other processes: the best effective
+    /// priority of its `ready` threads, promoted for the times the process was passed
+    /// over, and then the number of times it was passed over.
+    pub fn process_key(&self, ready: usize) -> (u8, u8) {
+        let best = (0..THREAD_SLOTS)
+            .filter(|&tid| is_ready(ready, tid))
+            .map(|tid| self.effective_priority(tid))
+            .max()
+            .unwrap_or(ThreadPriority::Idle as u8);
+        (
+            Self::promote(best, self.process_passed_over),
+            self.process_passed_over,
+        )
+    }
+
+    /// Record the outcome of a scheduling decision between processes
+    pub fn process_scheduled(&mut self, chosen: bool) {
+        self.process_passed_over = if chosen {
+            0
+        } else {
+            self.process_passed_over.saturating_add(1)
+        };
+    }
+
+    /// Whether the given thread ID has scheduling state
+    pub fn valid_tid(tid: TID) -> bool {
+        tid < THREAD_SLOTS
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::new()
+    }
+}
+
+/// Per-process resource counters and limits. Memory pages are accounted for by
+/// the `MemoryManager`, and servers are counted from the server table.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Resources {
+    /// Number of threads that exist in this process
+    pub threads: usize,
+
+    /// Number of connections this process holds to servers
+    pub connections: usize,
+
+    /// Milliseconds this process has spent on the CPU
+    pub cpu_ticks: usize,
+
+    thread_limit: usize,
+    server_limit: usize,
+    connection_limit: usize,
+
+    /// The process that created this one, which may change its limits
+    creator: Option<PID>,
+}
+
+impl Resources {
+    pub const fn new(creator: Option<PID>) -> Self {
+        Resources {
+            threads: 0,
+            connections: 0,
+            cpu_ticks: 0,
+            thread_limit: RESOURCE_UNLIMITED,
+            server_limit: RESOURCE_UNLIMITED,
+            connection_limit: RESOURCE_UNLIMITED,
+            creator,
+        }
+    }
+
+    /// The limit that is stored here, or `None` if this resource is either unlimited
+    /// or kept elsewhere
+    fn limit_mut(&mut self, resource: Resource) -> Option<&mut usize> {
+        match resource {
+            Resource::Threads => Some(&mut self.thread_limit),
+            Resource::Servers => Some(&mut self.server_limit),
+            Resource::Connections => Some(&mut self.connection_limit),
+            Resource::MemoryPages | Resource::CpuTicks => None,
+        }
+    }
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Resources::new(None)
+    }
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub struct Process {
     /// The absolute MMU address.  If 0, then this process is free.  This needs
@@ -135,6 +338,12 @@ pub struct Process {
 
     /// When an exception is hit, the kernel will switch to this Thread.
     exception_handler: Option<ExceptionHandler>,
+
+    /// Thread priorities, and the bookkeeping that keeps them from starving anyone
+    pub schedule: Schedule,
+
+    /// What this process is using, and how much it may use
+    pub resources: Resources,
 }
 
 impl Default for Process {
@@ -147,6 +356,8 @@ impl Default for Process {
             previous_thread: 0,
             exception_handler: None,
             mapping: Default::default(),
+            schedule: Schedule::new(),
+            resources: Resources::new(None),
         }
     }
 }
@@ -215,6 +426,18 @@ impl Process {
         )
     }
 
+    /// The key by which this process competes with other runnable processes for
+    /// the CPU. Higher keys go first.
+    pub fn scheduling_key(&self) -> (u8, u8) {
+        match self.state {
+            ProcessState::Ready(x) => self.schedule.process_key(x),
+            ProcessState::Setup(_) => self.schedule.process_key(1 << INITIAL_TID),
+            // Exceptions jump the queue
+            ProcessState::Exception(_) => (ThreadPriority::High as u8, u8::MAX),
+            _ => (ThreadPriority::Idle as u8, 0),
+        }
+    }
+
     /// This process slot is unallocated and may be turn into a process
     pub fn free(&self) -> bool {
         matches!(self.state, ProcessState::Free)
@@ -257,10 +480,16 @@ std::thread_local!(static SYSTEM_SERVICES: core::cell::RefCell<SystemServices> =
         current_thread: 0_usize,
         previous_thread: INITIAL_TID as TID,
         exception_handler: None,
+        schedule: Schedule::new(),
+        resources: Resources::new(None),
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
     servers: filled_array![None; 128],
+    timeouts: Timeouts::new(),
+    supervisor: None,
+    roles: [None; ROLE_COUNT],
+    switched_at_ms: 0,
 }));
 
 #[cfg(baremetal)]
@@ -273,10 +502,16 @@ static mut SYSTEM_SERVICES: SystemServices = SystemServices {
         current_thread: 0_usize,
         previous_thread: INITIAL_TID as TID,
         exception_handler: None,
+        schedule: Schedule::new(),
+        resources: Resources::new(None),
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
     servers: filled_array![None; 128],
+    timeouts: Timeouts::new(),
+    supervisor: None,
+    roles: [None; ROLE_COUNT],
+    switched_at_ms: 0,
 };
 
 impl core::fmt::Debug for Process {
@@ -359,6 +594,8 @@ impl SystemServices {
                 process.ppid = PID::new_unchecked(1);
                 process.pid = PID::new(pid as _).unwrap();
             };
+            process.resources = Resources::new(None);
+            process.resources.threads = 1;
             // let old_state = process.state;
             if pid == 1 {
                 process.state = ProcessState::Running(0);
@@ -386,6 +623,42 @@ impl SystemServices {
         // to make sure other fields such as "thread number" are all valid.
         ArchProcess::setup_process(PID::new(1).unwrap(), ThreadInit::default())
             .expect("couldn't setup process");
+
+        // Apply any resource limits the loader passed along. Each entry is a
+        // (PID, resource, limit) triple.
+        for arg in args.iter() {
+            if arg.name != u32::from_le_bytes(*b"PLim") {
+                continue;
+            }
+            for entry in arg.data.chunks_exact(3) {
+                let limit = if entry[2] == u32::MAX {
+                    RESOURCE_UNLIMITED
+                } else {
+                    entry[2] as usize
+                };
+                if let (Ok(pid), Some(resource)) = (
+                    pid_from_usize(entry[0] as usize),
+                    Resource::from_usize(entry[1] as usize),
+                ) {
+                    self.apply_resource_limit(pid, resource, limit).ok();
+                }
+            }
+        }
+
+        // Hand out the roles the loader assigned. Each entry is a (PID, role) pair.
+        for arg in args.iter() {
+            if arg.name != u32::from_le_bytes(*b"PRol") {
+                continue;
+            }
+            for entry in arg.data.chunks_exact(2) {
+                if let (Ok(pid), Some(role)) = (
+                    pid_from_usize(entry[0] as usize),
+                    Role::from_usize(entry[1] as usize),
+                ) {
+                    self.assign_role(pid, role);
+                }
+            }
+        }
     }
 
     /// Add a new entry to the process table. This results in a new address space
@@ -407,6 +680,11 @@ impl SystemServices {
             entry.pid = new_pid.unwrap();
             entry.ppid = PID::new(1).unwrap();
             entry.state = ProcessState::Allocated;
+            entry.schedule = Schedule::new();
+            entry.resources = Resources::new(Some(_ppid));
+            crate::mem::MemoryManager::with_mut(|mm| {
+                mm.set_page_limit(new_pid.unwrap(), RESOURCE_UNLIMITED)
+            });
             unsafe {
                 entry
                     .mapping
@@ -429,6 +707,7 @@ impl SystemServices {
             // the state to `ProcessState::Allocated` and we can go straight to running
             // this process.
             entry.state = ProcessState::Ready(1 << INITIAL_TID);
+            entry.resources.threads = 1;
         }
         // entry.ppid = _ppid;
         klog!("created new process for PID {} with PPID {}", new_pid, _ppid);
@@ -647,6 +926,159 @@ impl SystemServices {
         Ok(())
     }
 
+    /// Pick the next process to run out of the runnable children of PID 1, based
+    /// on the priorities of their ready threads. Processes that are passed over
+    /// age, so they get their turn eventually. Ties are broken round-robin,
+    /// starting after `last_pid`. If no process is ready, return `None`.
+    pub fn pick_next_process(&mut self, last_pid: Option<PID>) -> Option<PID> {
+        // PIDs are 1-indexed but arrays are 0-indexed.  By not subtracting
+        // 1 from the PID when we use it as an array index, we automatically
+        // start with the next process in the list.
+        let start = last_pid.map(|pid| pid.get() as usize).unwrap_or(1);
+        let count = self.processes.len();
+        let candidate = |process: &Process| process.ppid.get() == 1 && process.runnable();
+
+        let mut best: Option<(usize, (u8, u8))> = None;
+        for offset in 0..count {
+            let idx = (start + offset) % count;
+            let process = &self.processes[idx];
+            if !candidate(process) {
+                continue;
+            }
+            let key = process.scheduling_key();
+            if best.map_or(true, |(_, best_key)| key > best_key) {
+                best = Some((idx, key));
+            }
+        }
+        let (chosen, _) = best?;
+        for (idx, process) in self.processes.iter_mut().enumerate() {
+            if candidate(process) {
+                process.schedule.process_scheduled(idx == chosen);
+            }
+        }
+        #[cfg(feature = "ipc-trace")]
+        crate::trace::tick();
+        pid_from_usize(chosen + 1).ok()
+    }
+
+    /// Charge the time since the CPU last changed hands to `from`, which is giving
+    /// it up to `to`. Switches between threads of one process aren't counted, as
+    /// the time goes to the same process either way.
+    fn account_cpu_time(&mut self, from: PID, to: PID) {
+        if from == to {
+            return;
+        }
+        let now = crate::arch::elapsed_ms();
+        // The clock goes back once, when the ticktimer server resets it at boot
+        let elapsed = now.saturating_sub(self.switched_at_ms);
+        self.switched_at_ms = now;
+        if let Ok(process) = self.get_process_mut(from) {
+            process.resources.cpu_ticks =
+                process.resources.cpu_ticks.wrapping_add(elapsed as usize);
+        }
+    }
+
+    /// Return `(usage, limit)` of a resource of the given process
+    pub fn resource_usage(
+        &self,
+        pid: PID,
+        resource: Resource,
+    ) -> Result<(usize, usize), xous_kernel::Error> {
+        let process = self.get_process(pid)?;
+        if process.free() {
+            return Err(xous_kernel::Error::ProcessNotFound);
+        }
+        let resources = &process.resources;
+        Ok(match resource {
+            Resource::MemoryPages => crate::mem::MemoryManager::with_mut(|mm| {
+                (mm.pages_owned_by(pid), mm.page_limit(pid))
+            }),
+            Resource::Threads => (resources.threads, resources.thread_limit),
+            Resource::Servers => (
+                self.servers
+                    .iter()
+                    .flatten()
+                    .filter(|server| server.pid == pid)
+                    .count(),
+                resources.server_limit,
+            ),
+            Resource::Connections => (resources.connections, resources.connection_limit),
+            Resource::CpuTicks => (resources.cpu_ticks, RESOURCE_UNLIMITED),
+        })
+    }
+
+    /// Return `ResourceLimitExceeded` if the given process may not have another one
+    /// of `resource`
+    fn check_resource_limit(&self, pid: PID, resource: Resource) -> Result<(), xous_kernel::Error> {
+        let (usage, limit) = self.resource_usage(pid, resource)?;
+        if usage >= limit {
+            klog!("PID {} is at its {:?} limit of {}", pid, resource, limit);
+            return Err(xous_kernel::Error::ResourceLimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Set the limit on a resource of `pid` on behalf of `caller`, returning the previous
+    /// limit. A process may lower its own limits, and its creator may change them freely.
+    pub fn set_resource_limit(
+        &mut self,
+        caller: PID,
+        pid: PID,
+        resource: Resource,
+        limit: usize,
+    ) -> Result<usize, xous_kernel::Error> {
+        let (_, current) = self.resource_usage(pid, resource)?;
+        let process = self.get_process(pid)?;
+        let allowed =
+            process.resources.creator == Some(caller) || (caller == pid && limit <= current);
+        if !allowed {
+            return Err(xous_kernel::Error::AccessDenied);
+        }
+        self.apply_resource_limit(pid, resource, limit)
+    }
+
+    /// Set the limit on a resource of the given process without checking who is asking,
+    /// returning the previous limit
+    fn apply_resource_limit(
+        &mut self,
+        pid: PID,
+        resource: Resource,
+        limit: usize,
+    ) -> Result<usize, xous_kernel::Error> {
+        if resource == Resource::MemoryPages {
+            self.get_process(pid)?;
+            return Ok(crate::mem::MemoryManager::with_mut(|mm| {
+                mm.set_page_limit(pid, limit)
+            }));
+        }
+        let process = self.get_process_mut(pid)?;
+        let stored = process
+            .resources
+            .limit_mut(resource)
+            .ok_or(xous_kernel::Error::InvalidLimit)?;
+        Ok(core::mem::replace(stored, limit))
+    }
+
+    /// Set the priority of a thread in the given process, returning its previous priority.
+    /// Threads start out at `ThreadPriority::default()`, and only the supervisor may raise
+    /// one above that, so that a process can't starve the rest of the system.
+    pub fn set_thread_priority(
+        &mut self,
+        pid: PID,
+        tid: TID,
+        priority: ThreadPriority,
+    ) -> Result<ThreadPriority, xous_kernel::Error> {
+        if !Schedule::valid_tid(tid) {
+            return Err(xous_kernel::Error::InvalidThread);
+        }
+        if priority > ThreadPriority::default() && self.role_holder(Role::Supervisor) != Some(pid) {
+            return Err(xous_kernel::Error::AccessDenied);
+        }
+        let process = self.get_process_mut(pid)?;
+        klog!("Setting priority of ({}:{}) to {:?}", pid, tid, priority);
+        Ok(process.schedule.set_priority(tid, priority))
+    }
+
     pub fn runnable(&self, pid: PID, tid: Option<TID>) -> Result<bool, xous_kernel::Error> {
         let process = self.get_process(pid)?;
         if let Some(tid) = tid {
@@ -703,6 +1135,7 @@ impl SystemServices {
         pid: PID,
         tid: Option<TID>,
     ) -> Result<(), xous_kernel::Error> {
+        self.account_cpu_time(crate::arch::process::current_pid(), pid);
         let process = self.get_process_mut(pid)?;
         // klog!(
         //     "switch_to_thread({}:{:?}): Old state was {:?}",
@@ -753,17 +1186,10 @@ impl SystemServices {
             }
             ProcessState::Ready(x) => {
                 let new_thread = match tid {
-                    None => {
-                        let mut new_context = 0;
-
-                        while x & (1 << new_context) == 0 {
-                            new_context += 1;
-                            if new_context > arch::process::MAX_THREAD {
-                                new_context = 0;
-                            }
-                        }
-                        new_context
-                    }
+                    None => process
+                        .schedule
+                        .pick_thread(x, process.current_thread)
+                        .expect("no thread was ready"),
                     Some(ctx) => {
                         // Ensure the specified context is ready to run
                         if x & (1 << ctx) == 0 {
@@ -794,17 +1220,10 @@ impl SystemServices {
                 let mut p = ArchProcess::current();
                 // let current_thread = p.current_thread();
                 let new_thread = match tid {
-                    None => {
-                        let mut new_thread = 0;
-
-                        while ready_threads & (1 << new_thread) == 0 {
-                            new_thread += 1;
-                            if new_thread > arch::process::MAX_THREAD {
-                                new_thread = 0;
-                            }
-                        }
-                        new_thread
-                    }
+                    None => process
+                        .schedule
+                        .pick_thread(ready_threads, process.current_thread)
+                        .expect("no thread was ready"),
                     Some(tid) => {
                         // Ensure the specified context is ready to run, or is
                         // currently running.
@@ -951,6 +1370,7 @@ impl SystemServices {
 
         // Save state if the PID has changed.  This will activate the new memory
         // space.
+        self.account_cpu_time(previous_pid, new_pid);
         let new = self.get_process_mut(new_pid)?;
         if new_pid != previous_pid {
             klog!("New process original state: {:?}", new.state);
@@ -967,26 +1387,18 @@ impl SystemServices {
                     // new.current_thread = new_tid;
                 }
                 ProcessState::Running(x) | ProcessState::Ready(x) => {
-                    // If no new context is specified, take the previous
-                    // context.  If that is not runnable, do a round-robin
-                    // search for the next available context.
+                    // If no new context is specified, pick one of the ready
+                    // contexts by priority.
                     assert!(
                         x != 0,
                         "process was {:?} but had no free contexts",
                         new.state
                     );
                     if new_tid == 0 {
-                        new_tid = (new.current_thread + 1) as usize;
-                        while x & (1 << new_tid) == 0 {
-                            new_tid += 1;
-                            if new_tid > arch::process::MAX_THREAD {
-                                new_tid = 0;
-                            } else if new_tid == (new.current_thread + 1) as usize {
-                                // If we've looped around, return an error.
-                                // println!("Looked through all contexts and couldn't find one that was ready");
-                                return Err(xous_kernel::Error::ProcessNotFound);
-                            }
-                        }
+                        new_tid = new
+                            .schedule
+                            .pick_thread(x, new.current_thread)
+                            .ok_or(xous_kernel::Error::ProcessNotFound)?;
                         new.current_thread = new_tid as _;
                         klog!("picked thread ID {}", new_tid);
                     } else if x & (1 << new_tid) == 0 {
@@ -1544,6 +1956,7 @@ impl SystemServices {
         pid: PID,
         thread_init: ThreadInit,
     ) -> Result<TID, xous_kernel::Error> {
+        self.check_resource_limit(pid, Resource::Threads)?;
         let mut process = self.get_process_mut(pid)?;
         process.activate()?;
 
@@ -1553,6 +1966,8 @@ impl SystemServices {
             .ok_or(xous_kernel::Error::ThreadNotAvailable)?;
 
         arch_process.setup_thread(new_tid, thread_init)?;
+        process.schedule.reset_thread(new_tid);
+        process.resources.threads += 1;
 
         // println!("KERNEL({}): Created new thread {}", pid, new_tid);
 
@@ -1594,6 +2009,8 @@ impl SystemServices {
         // Destroy the thread at a hardware level
         let mut arch_process = ArchProcess::current();
         let return_value = arch_process.destroy_thread(tid).unwrap_or_default();
+        let resources = &mut self.get_process_mut(pid)?.resources;
+        resources.threads = resources.threads.saturating_sub(1);
 
         // If there's another thread waiting on the return value of this thread,
         // wake it up and set its return value.
@@ -1682,6 +2099,10 @@ impl SystemServices {
 
         // TODO: Come up with a way to randomize the server ID
         let ppid = self.get_process(pid)?.ppid.get();
+        self.check_resource_limit(pid, Resource::Servers)?;
+        if connect {
+            self.check_resource_limit(pid, Resource::Connections)?;
+        }
         if ppid != 1 {
             panic!(
                 "KERNEL({}): Non-PID1 processes cannot start servers yet",
@@ -1779,7 +2200,8 @@ impl SystemServices {
         for process in self.processes.iter_mut() {
             if !process.free() {
                 process.activate().unwrap();
-                ArchProcess::with_inner_mut(|process_inner| {
+                let disconnected = ArchProcess::with_inner_mut(|process_inner| {
+                    let mut disconnected = 0;
                     // Look through the connection map for (1) a free slot, and (2) an
                     // existing connection
                     #[allow(clippy::manual_flatten)]
@@ -1787,11 +2209,15 @@ impl SystemServices {
                         if let Some(client_server_idx) = server_idx_opt {
                             if client_server_idx.get() == (server_idx + 2) as _ {
                                 *server_idx_opt = None;
+                                disconnected += 1;
                                 continue;
                             }
                         }
                     }
+                    disconnected
                 });
+                process.resources.connections =
+                    process.resources.connections.saturating_sub(disconnected);
             }
         }
 
@@ -1826,7 +2252,8 @@ impl SystemServices {
         // yet connected.
 
         let pid = crate::arch::process::current_pid();
-        ArchProcess::with_inner_mut(|process_inner| {
+        let (connections, connection_limit) = self.resource_usage(pid, Resource::Connections)?;
+        let (cid, new_connection) = ArchProcess::with_inner_mut(|process_inner| {
             assert_eq!(pid, process_inner.pid);
             let mut slot_idx = None;
             // Look through the connection map for (1) a free slot, and (2) an
@@ -1850,11 +2277,19 @@ impl SystemServices {
                         //     (connection_idx as CID) + 2,
                         //     process_inner.connection_map,
                         // );
-                        return Ok((connection_idx as CID) + 2);
+                        return Ok(((connection_idx as CID) + 2, false));
                     }
                 }
             }
             let slot_idx = slot_idx.ok_or(Error::OutOfMemory)?;
+            if connections >= connection_limit {
+                klog!(
+                    "PID {} is at its connection limit of {}",
+                    pid,
+                    connection_limit
+                );
+                return Err(xous_kernel::Error::ResourceLimitExceeded);
+            }
 
             // Look through all servers for one whose SID matches.
             for (server_idx, server) in self.servers.iter().enumerate() {
@@ -1869,12 +2304,16 @@ impl SystemServices {
                         //     slot_idx + 2,
                         //     process_inner.connection_map
                         // );
-                        return Ok((slot_idx as CID) + 2);
+                        return Ok(((slot_idx as CID) + 2, true));
                     }
                 }
             }
             Err(xous_kernel::Error::ServerNotFound) // May also be OutOfMemory if the table is full
-        })
+        })?;
+        if new_connection {
+            self.get_process_mut(pid)?.resources.connections += 1;
+        }
+        Ok(cid)
     }
 
     /// Invalidate the provided connection ID.
@@ -1910,7 +2349,30 @@ impl SystemServices {
             *idx = None;
             klog!("Removing server from table");
             Ok(())
-        })
+        })?;
+        let resources = &mut self.get_process_mut(pid)?.resources;
+        resources.connections = resources.connections.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Return the PID of the process that created the server with the given SID
+    pub fn server_owner(&self, sid: SID) -> Option<PID> {
+        self.servers
+            .iter()
+            .flatten()
+            .find(|server| server.sid == sid)
+            .map(|server| server.pid)
+    }
+
+    /// Give `role` to `pid`. This happens only at boot, from the roles the loader
+    /// passed along, or from the test harness in hosted mode.
+    pub fn assign_role(&mut self, pid: PID, role: Role) {
+        self.roles[role as usize - 1] = Some(pid);
+    }
+
+    /// Return the PID of the process that holds `role`, if it is still running
+    pub fn role_holder(&self, role: Role) -> Option<PID> {
+        self.roles[role as usize - 1]
     }
 
     /// Retrieve the server ID index from the specified SID.
@@ -1983,6 +2445,20 @@ impl SystemServices {
         })
     }
 
+    /// Returns `true` if `cid` was connected to a server whose process has
+    /// since exited, in which case the connection holds a tombstone.
+    pub fn cid_is_disconnected(&self, cid: CID) -> bool {
+        if cid < 2 {
+            return false;
+        }
+        ArchProcess::with_inner(|process_inner| {
+            matches!(
+                process_inner.connection_map.get(cid as usize - 2),
+                Some(Some(mapping)) if mapping.get() == 1
+            )
+        })
+    }
+
     /// Switch to the server's memory space and add the message to its server
     /// queue
     pub fn queue_server_message(
@@ -2059,8 +2535,199 @@ impl SystemServices {
     //     None
     // }
 
+    /// Wake every thread whose timeout has passed, giving it a `Timeout` error.
+    /// `now` is in milliseconds since boot.
+    pub fn expire_timeouts(&mut self, now: u64) {
+        let mut expired = false;
+        while let Some(timeout) = self.timeouts.take_expired(now) {
+            // The thread may have been woken by other means in the meantime,
+            // in which case there is nothing left to do.
+            self.expire_timeout(timeout).ok();
+            expired = true;
+        }
+        if expired {
+            self.request_timeout_wakeup();
+        }
+    }
+
+    /// Make sure the kernel gets to run by the time the next timeout runs out.
+    /// An idle system sits in `wfi` until an interrupt arrives, so ask the
+    /// ticktimer to raise one at the next deadline. Hosted mode waits on its
+    /// message queue with a timeout instead, and doesn't need this.
+    pub fn request_timeout_wakeup(&mut self) {
+        if !cfg!(baremetal) {
+            return;
+        }
+        let deadline = match self.timeouts.wakeup_needed(arch::elapsed_ms()) {
+            Some(deadline) => deadline,
+            None => return,
+        };
+        let sid = SID::from_bytes(b"ticktimer-server").unwrap();
+        let ticktimer_pid = match self.server_owner(sid) {
+            Some(pid) => pid,
+            None => return,
+        };
+        let sidx = self
+            .sidx_from_sid(sid, ticktimer_pid)
+            .expect("couldn't re-discover server index");
+        let message = xous_kernel::KernelWakeup { deadline }.to_message();
+        if self
+            .post_kernel_message(ticktimer_pid, sidx, message)
+            .is_ok()
+        {
+            self.timeouts.wakeup_requested(deadline);
+        }
+    }
+
+    fn expire_timeout(&mut self, timeout: Timeout) -> Result<(), xous_kernel::Error> {
+        let Timeout { pid, tid, wait, .. } = timeout;
+        match wait {
+            Wait::Receive(sidx) => {
+                let server = self
+                    .server_from_sidx_mut(sidx)
+                    .ok_or(xous_kernel::Error::ServerNotFound)?;
+                if server.pid != pid || !server.unpark_thread(tid) {
+                    return Err(xous_kernel::Error::ThreadNotAvailable);
+                }
+            }
+            Wait::Send(sidx, queue_idx) => {
+                let server = self
+                    .server_from_sidx_mut(sidx)
+                    .ok_or(xous_kernel::Error::ServerNotFound)?;
+                let server_pid = server.pid;
+                // The message is either still in the queue, or the server has
+                // received it and not replied yet.
+                let (waiting, received) = match server.cancel_queued_message(queue_idx, pid, tid) {
+                    WaitingMessage::None => {
+                        (server.abandon_waiting_message(queue_idx, pid, tid), true)
+                    }
+                    waiting => (waiting, false),
+                };
+                match waiting {
+                    WaitingMessage::ScalarMessage(_, _) => {}
+                    WaitingMessage::BorrowedMemory(_, _, server_addr, client_addr, len) => {
+                        // The lent memory lives in the server's address space
+                        // until it is returned.
+                        let current_pid = self.current_pid();
+                        self.get_process(server_pid)?.activate()?;
+                        let mut result = self
+                            .return_memory(
+                                server_addr.get() as _,
+                                pid,
+                                tid,
+                                client_addr.get() as _,
+                                len.get(),
+                            )
+                            .map(|_| ());
+                        // A server that is still working on the message keeps
+                        // using the range, so back it with fresh pages until it
+                        // replies and the range is forgotten.
+                        if cfg!(baremetal) && received && result.is_ok() {
+                            result = crate::mem::MemoryManager::with_mut(|mm| {
+                                mm.reserve_range(
+                                    server_addr.get() as *mut u8,
+                                    len.get(),
+                                    xous_kernel::MemoryFlags::R | xous_kernel::MemoryFlags::W,
+                                )
+                                .map(|_| ())
+                            });
+                        }
+                        self.get_process(current_pid)?.activate()?;
+                        result?;
+                    }
+                    _ => return Err(xous_kernel::Error::ThreadNotAvailable),
+                }
+            }
+        }
+
+        if cfg!(baremetal) {
+            self.ready_thread(pid, tid)?;
+        }
+        self.set_thread_result(
+            pid,
+            tid,
+            xous_kernel::Result::Error(xous_kernel::Error::Timeout),
+        )
+    }
+
+    /// Have `pid`, which must hold `Role::Supervisor`, sent a `ProcessExit`
+    /// message on the given server whenever another process exits.
+    pub fn set_supervisor(&mut self, pid: PID, sid: SID) -> Result<(), xous_kernel::Error> {
+        if self.role_holder(Role::Supervisor) != Some(pid) {
+            return Err(xous_kernel::Error::AccessDenied);
+        }
+        self.sidx_from_sid(sid, pid)
+            .ok_or(xous_kernel::Error::ServerNotFound)?;
+        self.supervisor = Some((pid, sid));
+        Ok(())
+    }
+
+    /// Tell the supervisor, if there is one, that a process has exited. The
+    /// message comes from PID 1, and is dropped if the supervisor's queue is full.
+    fn notify_supervisor(&mut self, exit: ProcessExit) {
+        let (supervisor_pid, sid) = match self.supervisor {
+            Some(supervisor) => supervisor,
+            None => return,
+        };
+        if supervisor_pid == exit.pid {
+            self.supervisor = None;
+            return;
+        }
+        let sidx = match self.sidx_from_sid(sid, supervisor_pid) {
+            Some(sidx) => sidx,
+            None => return,
+        };
+        self.post_kernel_message(supervisor_pid, sidx, exit.to_message())
+            .ok();
+    }
+
+    /// Send a non-blocking message from PID 1 to the server with index `sidx`,
+    /// which belongs to `server_pid`.
+    ///
+    /// # Errors
+    ///
+    /// * **ServerQueueFull**: The server is busy and its queue is full
+    fn post_kernel_message(
+        &mut self,
+        server_pid: PID,
+        sidx: usize,
+        message: Message,
+    ) -> Result<(), xous_kernel::Error> {
+        let kernel_pid = PID::new(1).unwrap();
+        let server = self
+            .server_from_sidx_mut(sidx)
+            .expect("couldn't re-discover server index");
+        if let Some(server_tid) = server.take_available_thread() {
+            let envelope = MessageEnvelope {
+                sender: SenderID::new(sidx, 0, Some(kernel_pid)).into(),
+                body: message,
+            };
+            if let Err(e) = self.ready_thread(server_pid, server_tid) {
+                self.server_from_sidx_mut(sidx)
+                    .expect("couldn't re-discover server index")
+                    .return_available_thread(server_tid);
+                return Err(e);
+            }
+            if !cfg!(baremetal) {
+                self.switch_to_thread(server_pid, Some(server_tid)).ok();
+            }
+            self.set_thread_result(
+                server_pid,
+                server_tid,
+                xous_kernel::Result::Message(envelope),
+            )
+        } else {
+            self.queue_server_message(sidx, kernel_pid, 0, message, None)
+                .map(|_| ())
+        }
+    }
+
     /// Terminate the given process. Returns the process' parent PID.
-    pub fn terminate_process(&mut self, target_pid: PID) -> Result<PID, xous_kernel::Error> {
+    pub fn terminate_process(
+        &mut self,
+        target_pid: PID,
+        reason: ExitReason,
+    ) -> Result<PID, xous_kernel::Error> {
         // To terminate a process, we must perform the following:
         //
         // 1. If we have any client connections, remove them.
@@ -2103,12 +2770,22 @@ impl SystemServices {
         }
 
         // Now that the server has been "Disconnected", free the server entry.
-        #[allow(clippy::manual_flatten)]
-        for server in self.servers.iter_mut() {
-            if let Some(server_inner) = server {
-                if server_inner.pid == target_pid {
-                    *server = None;
-                }
+        // Clients still waiting on it would otherwise block forever, so wake
+        // them with an error.
+        self.get_process(target_pid)?.activate()?;
+        for sidx in 0..self.servers.len() {
+            if matches!(&self.servers[sidx], Some(server) if server.pid == target_pid) {
+                let server = self.servers[sidx].take().unwrap();
+                server.abandon(self);
+            }
+        }
+
+        self.timeouts.cancel_pid(target_pid);
+
+        // Roles aren't passed on to whichever process gets this PID next
+        for holder in self.roles.iter_mut() {
+            if *holder == Some(target_pid) {
+                *holder = None;
             }
         }
 
@@ -2117,6 +2794,11 @@ impl SystemServices {
         let parent_pid = process.ppid;
         process.terminate()?;
 
+        self.notify_supervisor(ProcessExit {
+            pid: target_pid,
+            reason,
+        });
+
         self.switch_to_thread(parent_pid, None).unwrap();
 
         Ok(parent_pid)
@@ -7,6 +7,7 @@ use crate::irq::interrupt_claim;
 use crate::mem::{MemoryManager, PAGE_SIZE};
 use crate::server::{SenderID, WaitingMessage};
 use crate::services::SystemServices;
+use crate::timeout::{Timeout, Wait};
 use core::mem;
 use xous_kernel::*;
 
@@ -28,6 +29,8 @@ static mut SWITCHTO_CALLER: Option<(PID, TID)> = None;
 enum ExecutionType {
     Blocking,
     NonBlocking,
+    /// Block for at most this many milliseconds
+    Timeout(usize),
 }
 
 #[cfg(baremetal)]
@@ -64,11 +67,26 @@ fn do_yield(_pid: PID, tid: TID) -> SysCallResult {
     })
 }
 
-fn send_message(pid: PID, thread: TID, cid: CID, message: Message) -> SysCallResult {
+fn send_message(
+    pid: PID,
+    thread: TID,
+    cid: CID,
+    message: Message,
+    timeout: Option<usize>,
+) -> SysCallResult {
     SystemServices::with_mut(|ss| {
-        let sidx = ss
-            .sidx_from_cid(cid)
-            .ok_or(xous_kernel::Error::ServerNotFound)?;
+        let sidx = ss.sidx_from_cid(cid).ok_or_else(|| {
+            if ss.cid_is_disconnected(cid) {
+                xous_kernel::Error::ServerDisconnected
+            } else {
+                xous_kernel::Error::ServerNotFound
+            }
+        })?;
+
+        // Make sure the timeout can be recorded before anything is lent out.
+        if timeout.is_some() && ss.timeouts.is_full() {
+            return Err(xous_kernel::Error::OutOfMemory);
+        }
 
         let server_pid = ss
             .server_from_sidx(sidx)
@@ -166,6 +184,17 @@ fn send_message(pid: PID, thread: TID, cid: CID, message: Message) -> SysCallRes
             } else {
                 0
             };
+            // A blocking sender waits for the reply, so that is what the timeout
+            // covers. There is room for it, since that was checked above.
+            if let (true, Some(ms)) = (blocking, timeout) {
+                ss.timeouts.insert(Timeout {
+                    pid,
+                    tid: thread,
+                    deadline: arch::elapsed_ms().saturating_add(ms as u64),
+                    wait: Wait::Send(sidx, sender_idx),
+                })?;
+                ss.request_timeout_wakeup();
+            }
             let sender = SenderID::new(sidx, sender_idx, Some(pid));
             klog!(
                 "server connection data: sidx: {}, idx: {}, server pid: {}",
@@ -253,12 +282,23 @@ fn send_message(pid: PID, thread: TID, cid: CID, message: Message) -> SysCallRes
         );
         // Add this message to the queue.  If the queue is full, this
         // returns an error.
-        let _queue_idx = ss.queue_server_message(sidx, pid, thread, message, client_address)?;
-        klog!("queued into index {:x}", _queue_idx);
+        let queue_idx = ss.queue_server_message(sidx, pid, thread, message, client_address)?;
+        klog!("queued into index {:x}", queue_idx);
 
         // Park this context if it's blocking.  This is roughly
         // equivalent to a "Yield".
         if blocking {
+            // The timeout covers both the time spent in the queue and the wait
+            // for the server's reply, since the slot stays put once received.
+            if let Some(ms) = timeout {
+                ss.timeouts.insert(Timeout {
+                    pid,
+                    tid: thread,
+                    deadline: arch::elapsed_ms().saturating_add(ms as u64),
+                    wait: Wait::Send(sidx, queue_idx),
+                })?;
+                ss.request_timeout_wakeup();
+            }
             if cfg!(baremetal) {
                 // println!("Returning to parent");
                 let process = ss.get_process(pid).expect("Can't get current process");
@@ -306,7 +346,7 @@ fn return_memory(
                 client_addr,
                 len,
             ) => (client_pid, client_ctx, server_addr, client_addr, len),
-            WaitingMessage::MovedMemory => {
+            WaitingMessage::MovedMemory | WaitingMessage::TimedOut => {
                 return Ok(xous_kernel::Result::Ok);
             }
             WaitingMessage::ForgetMemory(range) => {
@@ -337,6 +377,7 @@ fn return_memory(
                 return Err(xous_kernel::Error::ProcessNotFound);
             }
         };
+        ss.timeouts.cancel(client_pid, client_tid);
         // println!(
         //     "KERNEL({}): Returning {} bytes from {:08x} in PID {} to {:08x} in PID {} in context {}",
         //     pid,
@@ -411,6 +452,8 @@ fn return_scalar(
         let result = server.take_waiting_message(sender.idx, None)?;
         let (client_pid, client_tid) = match result {
             WaitingMessage::ScalarMessage(pid, tid) => (pid, tid),
+            // The client gave up waiting, so the reply goes nowhere.
+            WaitingMessage::TimedOut => return Ok(xous_kernel::Result::Ok),
             WaitingMessage::ForgetMemory(_) => {
                 println!(
                     "WARNING: Tried to wait on a scalar message that was actually forgettingmemory"
@@ -435,6 +478,7 @@ fn return_scalar(
             }
         };
 
+        ss.timeouts.cancel(client_pid, client_tid);
         let client_is_runnable = ss.runnable(client_pid, Some(client_tid))?;
 
         if !cfg!(baremetal) || in_irq || !client_is_runnable {
@@ -484,6 +528,8 @@ fn return_scalar2(
         let result = server.take_waiting_message(sender.idx, None)?;
         let (client_pid, client_tid) = match result {
             WaitingMessage::ScalarMessage(pid, tid) => (pid, tid),
+            // The client gave up waiting, so the reply goes nowhere.
+            WaitingMessage::TimedOut => return Ok(xous_kernel::Result::Ok),
             WaitingMessage::ForgetMemory(_) => {
                 println!("WARNING: Tried to wait on a scalar message that was actually forgetting memory");
                 return Err(xous_kernel::Error::ProcessNotFound);
@@ -506,6 +552,7 @@ fn return_scalar2(
             }
         };
 
+        ss.timeouts.cancel(client_pid, client_tid);
         let client_is_runnable = ss.runnable(client_pid, Some(client_tid))?;
 
         if !cfg!(baremetal) || in_irq || !client_is_runnable {
@@ -569,6 +616,19 @@ fn receive_message(pid: PID, tid: TID, sid: SID, blocking: ExecutionType) -> Sys
             return Ok(xous_kernel::Result::None);
         }
 
+        if let ExecutionType::Timeout(ms) = blocking {
+            ss.timeouts.insert(Timeout {
+                pid,
+                tid,
+                deadline: arch::elapsed_ms().saturating_add(ms as u64),
+                wait: Wait::Receive(sidx),
+            })?;
+            ss.request_timeout_wakeup();
+        }
+        let server = ss
+            .server_from_sidx_mut(sidx)
+            .ok_or(xous_kernel::Error::ServerNotFound)?;
+
         // There is no pending message, so return control to the parent
         // process and mark ourselves as awaiting an event.  When a message
         // arrives, our return value will already be set to the
@@ -606,6 +666,11 @@ pub fn handle(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallResult
     let result = if in_irq && !call.can_call_from_interrupt() {
         Err(xous_kernel::Error::InvalidSyscall)
     } else {
+        #[cfg(feature = "ipc-trace")]
+        crate::trace::record(pid, tid, &call);
+        // A thread that is making a syscall is not blocked, so any timeout it
+        // had left over no longer applies.
+        SystemServices::with_mut(|ss| ss.timeouts.cancel(pid, tid));
         handle_inner(pid, tid, in_irq, call)
     };
 
@@ -807,6 +872,9 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
         SysCall::TryReceiveMessage(sid) => {
             receive_message(pid, tid, sid, ExecutionType::NonBlocking)
         }
+        SysCall::ReceiveMessageTimeout(sid, ms) => {
+            receive_message(pid, tid, sid, ExecutionType::Timeout(ms))
+        }
         SysCall::WaitEvent => SystemServices::with_mut(|ss| {
             let process = ss.get_process(pid).expect("Can't get current process");
             let ppid = process.ppid;
@@ -855,10 +923,10 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
         SysCall::ReturnScalar2(sender, arg1, arg2) => {
             return_scalar2(pid, tid, in_irq, sender, arg1, arg2)
         }
-        SysCall::TrySendMessage(cid, message) => send_message(pid, tid, cid, message),
-        SysCall::TerminateProcess(_ret) => SystemServices::with_mut(|ss| {
+        SysCall::TrySendMessage(cid, message) => send_message(pid, tid, cid, message, None),
+        SysCall::TerminateProcess(ret) => SystemServices::with_mut(|ss| {
             ss.unschedule_thread(pid, tid)?;
-            ss.terminate_process(pid)?;
+            ss.terminate_process(pid, ExitReason::Exited(ret))?;
             // Clear out `SWITCHTO_CALLER` since we're resuming the parent process.
             unsafe { SWITCHTO_CALLER = None };
             Ok(xous_kernel::Result::ResumeProcess)
@@ -892,13 +960,29 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
             }
         }
         SysCall::SendMessage(cid, message) => {
-            let result = send_message(pid, tid, cid, message);
+            let result = send_message(pid, tid, cid, message, None);
             match result {
                 Ok(o) => Ok(o),
                 Err(xous_kernel::Error::ServerQueueFull) => retry_syscall(pid, tid),
                 Err(e) => Err(e),
             }
         }
+        SysCall::SendMessageTimeout(cid, message, ms) => {
+            send_message(pid, tid, cid, message, Some(ms))
+        }
+        SysCall::SetSupervisor(sid) => SystemServices::with_mut(|ss| {
+            ss.set_supervisor(pid, sid).map(|_| xous_kernel::Result::Ok)
+        }),
+        SysCall::GetServerOwner(sid) => SystemServices::with(|ss| {
+            ss.server_owner(sid)
+                .map(xous_kernel::Result::ProcessID)
+                .ok_or(xous_kernel::Error::ServerNotFound)
+        }),
+        SysCall::GetRoleHolder(role) => SystemServices::with(|ss| {
+            ss.role_holder(role)
+                .map(xous_kernel::Result::ProcessID)
+                .ok_or(xous_kernel::Error::ProcessNotFound)
+        }),
         SysCall::Disconnect(cid) => SystemServices::with_mut(|ss| {
             ss.disconnect_from_server(cid)
                 .and(Ok(xous_kernel::Result::Ok))
@@ -936,6 +1020,32 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
             }),
             _ => Err(xous_kernel::Error::InvalidLimit),
         },
+        SysCall::SetThreadPriority(target_tid, priority) => SystemServices::with_mut(|ss| {
+            ss.set_thread_priority(pid, target_tid, priority)
+                .map(|previous| xous_kernel::Result::Scalar1(previous as usize))
+        }),
+        SysCall::GetResourceUsage(target_pid, resource) => SystemServices::with(|ss| {
+            ss.resource_usage(target_pid, resource)
+                .map(|(usage, limit)| xous_kernel::Result::Scalar2(usage, limit))
+        }),
+        #[cfg(feature = "ipc-trace")]
+        SysCall::ReadIpcTrace(seq) => {
+            let reader = SystemServices::with(|ss| ss.role_holder(xous_kernel::Role::TraceReader));
+            if reader != Some(pid) {
+                return Err(xous_kernel::Error::AccessDenied);
+            }
+            crate::trace::Trace::with_mut(|trace| match trace.event_from(seq) {
+                Some(event) => {
+                    let [a, b, c, d, e] = event.to_args();
+                    Ok(xous_kernel::Result::Scalar5(a, b, c, d, e))
+                }
+                None => Ok(xous_kernel::Result::None),
+            })
+        }
+        SysCall::SetResourceLimit(target_pid, resource, limit) => SystemServices::with_mut(|ss| {
+            ss.set_resource_limit(pid, target_pid, resource, limit)
+                .map(xous_kernel::Result::Scalar1)
+        }),
         /* https://github.com/betrusted-io/xous-core/issues/90
         SysCall::SetExceptionHandler(pc, sp) => SystemServices::with_mut(|ss| {
             ss.set_exception_handler(pid, pc, sp)
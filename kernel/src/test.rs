@@ -20,6 +20,12 @@ use core::sync::atomic::{AtomicU64, Ordering};
 static RNG_LOCAL_STATE: AtomicU64 = AtomicU64::new(1);
 
 fn start_kernel(server_spec: &str) -> JoinHandle<()> {
+    start_kernel_with_roles(server_spec, &[])
+}
+
+/// Start the kernel with `roles` already handed out, as the loader would on
+/// hardware. Processes are numbered in the order they're created, from PID 2.
+fn start_kernel_with_roles(server_spec: &str, roles: &[(u32, xous_kernel::Role)]) -> JoinHandle<()> {
     assert!(
         std::env::var("XOUS_LISTEN_ADDR").is_err(),
         "XOUS_LISTEN_ADDR environment variable must be unset to run tests"
@@ -52,6 +58,10 @@ fn start_kernel(server_spec: &str) -> JoinHandle<()> {
     // drop(temp_server);
 
     let (send_addr, recv_addr) = unbounded();
+    let roles: Vec<(xous_kernel::PID, xous_kernel::Role)> = roles
+        .iter()
+        .map(|(pid, role)| (xous_kernel::PID::new(*pid as _).unwrap(), *role))
+        .collect();
 
     // Launch the main thread. We pass a `send_addr` channel so that the
     // server can notify us when it's ready to listen.
@@ -62,6 +72,7 @@ fn start_kernel(server_spec: &str) -> JoinHandle<()> {
             crate::arch::set_pid1_key(pid1_key);
             crate::arch::set_send_addr(send_addr);
             crate::arch::set_listen_address(&server_spec_server);
+            crate::arch::set_process_roles(&roles);
             kmain()
         })
         .expect("couldn't start kernel thread");
@@ -406,6 +417,258 @@ fn try_receive_message() {
     main_thread.join().expect("couldn't join kernel process");
 }
 
+#[test]
+fn receive_message_timeout() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "receive_message_timeout server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+
+            // Nobody knows about this server yet, so this must time out
+            assert_eq!(
+                xous_kernel::receive_message_timeout(sid, 50),
+                Err(xous_kernel::Error::Timeout)
+            );
+
+            server_addr_send.send(sid).unwrap();
+            let envelope = xous_kernel::receive_message_timeout(sid, 10_000)
+                .expect("couldn't receive message");
+            assert_eq!(
+                envelope.body,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 11,
+                    arg1: 12,
+                    arg2: 13,
+                    arg3: 14,
+                    arg4: 15
+                })
+            );
+        },
+    ))
+    .expect("couldn't spawn server process");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "receive_message_timeout client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::try_connect(sid).expect("couldn't connect to server");
+            xous_kernel::try_send_message(
+                conn,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 11,
+                    arg1: 12,
+                    arg2: 13,
+                    arg3: 14,
+                    arg4: 15,
+                }),
+            )
+            .expect("couldn't send message");
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn send_message_timeout() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+    let (client_done_send, client_done_recv) = unbounded();
+    let test_bytes = b"Hello, world!";
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "send_message_timeout server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+
+            // Don't receive anything until the client has given up on its
+            // blocking messages. Only the message sent after that should arrive.
+            client_done_recv.recv().unwrap();
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive message");
+            assert_eq!(
+                envelope.body,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 3,
+                    arg1: 0,
+                    arg2: 0,
+                    arg3: 0,
+                    arg4: 0
+                })
+            );
+            assert!(xous_kernel::try_receive_message(sid)
+                .expect("couldn't receive message")
+                .is_none());
+        },
+    ))
+    .expect("couldn't spawn server process");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "send_message_timeout client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+
+            assert_eq!(
+                xous_kernel::send_message_timeout(
+                    conn,
+                    xous_kernel::Message::BlockingScalar(xous_kernel::ScalarMessage {
+                        id: 1,
+                        arg1: 0,
+                        arg2: 0,
+                        arg3: 0,
+                        arg4: 0,
+                    }),
+                    50,
+                ),
+                Err(xous_kernel::Error::Timeout)
+            );
+
+            // A lend that times out must give the memory back untouched
+            let carton = xous_kernel::carton::Carton::from_bytes(test_bytes);
+            let range: &xous_kernel::MemoryRange = carton.as_ref();
+            let msg = xous_kernel::MemoryMessage {
+                id: 2,
+                buf: *range,
+                offset: None,
+                valid: None,
+            };
+            assert_eq!(
+                xous_kernel::send_message_timeout(
+                    conn,
+                    xous_kernel::Message::MutableBorrow(msg),
+                    50
+                ),
+                Err(xous_kernel::Error::Timeout)
+            );
+            let returned: &[u8] = carton.as_ref();
+            assert_eq!(returned, test_bytes);
+
+            xous_kernel::try_send_message(
+                conn,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 3,
+                    arg1: 0,
+                    arg2: 0,
+                    arg3: 0,
+                    arg4: 0,
+                }),
+            )
+            .expect("couldn't send message");
+            client_done_send.send(()).unwrap();
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn send_message_timeout_without_reply() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+    let (timed_out_send, timed_out_recv) = unbounded();
+    let test_bytes = b"Hello, world!";
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "send_message_timeout_without_reply server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+
+            // Receive each message, but only reply once the client has given up.
+            // The late replies go nowhere, and must not upset the server.
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive message");
+            timed_out_recv.recv().unwrap();
+            assert_eq!(
+                xous_kernel::return_scalar(envelope.sender, 5),
+                Ok(())
+            );
+
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive message");
+            if let xous_kernel::Message::MutableBorrow(m) = envelope.body {
+                let bt =
+                    unsafe { core::slice::from_raw_parts_mut(m.buf.as_mut_ptr(), m.buf.len()) };
+                for letter in bt.iter_mut() {
+                    *letter += 1;
+                }
+                timed_out_recv.recv().unwrap();
+                xous_kernel::return_memory(envelope.sender, m.buf)
+                    .expect("couldn't return memory");
+            } else {
+                panic!("unexpected message type");
+            }
+        },
+    ))
+    .expect("couldn't spawn server process");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "send_message_timeout_without_reply client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+
+            assert_eq!(
+                xous_kernel::send_message_timeout(
+                    conn,
+                    xous_kernel::Message::BlockingScalar(xous_kernel::ScalarMessage {
+                        id: 1,
+                        arg1: 0,
+                        arg2: 0,
+                        arg3: 0,
+                        arg4: 0,
+                    }),
+                    50,
+                ),
+                Err(xous_kernel::Error::Timeout)
+            );
+            timed_out_send.send(()).unwrap();
+
+            let carton = xous_kernel::carton::Carton::from_bytes(test_bytes);
+            let range: &xous_kernel::MemoryRange = carton.as_ref();
+            let msg = xous_kernel::MemoryMessage {
+                id: 2,
+                buf: *range,
+                offset: None,
+                valid: None,
+            };
+            assert_eq!(
+                xous_kernel::send_message_timeout(
+                    conn,
+                    xous_kernel::Message::MutableBorrow(msg),
+                    50
+                ),
+                Err(xous_kernel::Error::Timeout)
+            );
+            let returned: &[u8] = carton.as_ref();
+            assert_eq!(returned, test_bytes);
+            timed_out_send.send(()).unwrap();
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
 #[test]
 fn send_blocking_scalar_message() {
     // Start the server in another thread
@@ -1177,3 +1440,546 @@ fn process_restart_server() {
 
     main_thread.join().expect("couldn't join kernel process");
 }
+
+#[test]
+fn thread_priority() {
+    use xous_kernel::ThreadPriority;
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let xous_process = xous_kernel::create_process_as_thread(
+        xous_kernel::ProcessArgsAsThread::new("thread_priority process", move || {
+            let tid = xous_kernel::current_tid().expect("couldn't get thread id");
+            // Only the supervisor may go above the priority that threads start at
+            assert_eq!(
+                xous_kernel::set_thread_priority(tid, ThreadPriority::High),
+                Err(xous_kernel::Error::AccessDenied)
+            );
+            assert_eq!(
+                xous_kernel::set_thread_priority(tid, ThreadPriority::Idle),
+                Ok(ThreadPriority::Normal)
+            );
+            assert_eq!(
+                xous_kernel::set_thread_priority(tid, ThreadPriority::Normal),
+                Ok(ThreadPriority::Idle)
+            );
+
+            // New threads start out at the default priority
+            let (previous_send, previous_recv) = unbounded();
+            let thr = xous_kernel::create_thread(move || {
+                let tid = xous_kernel::current_tid().expect("couldn't get thread id");
+                previous_send
+                    .send(xous_kernel::set_thread_priority(tid, ThreadPriority::Low))
+                    .unwrap();
+            })
+            .expect("couldn't create thread");
+            xous_kernel::wait_thread(thr).expect("couldn't wait for thread");
+            assert_eq!(previous_recv.recv().unwrap(), Ok(ThreadPriority::Normal));
+
+            assert_eq!(
+                xous_kernel::set_thread_priority(1000, ThreadPriority::High),
+                Err(xous_kernel::Error::InvalidThread)
+            );
+        }),
+    )
+    .expect("couldn't spawn priority process");
+
+    xous_kernel::wait_process_as_thread(xous_process).expect("couldn't join priority process");
+
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn supervisor_thread_priority() {
+    use xous_kernel::ThreadPriority;
+    let main_thread = start_kernel_with_roles(SERVER_SPEC, &[(2, xous_kernel::Role::Supervisor)]);
+
+    let supervisor = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "supervisor_thread_priority process",
+        move || {
+            let tid = xous_kernel::current_tid().expect("couldn't get thread id");
+            assert_eq!(
+                xous_kernel::set_thread_priority(tid, ThreadPriority::High),
+                Ok(ThreadPriority::Normal)
+            );
+            assert_eq!(
+                xous_kernel::set_thread_priority(tid, ThreadPriority::Normal),
+                Ok(ThreadPriority::High)
+            );
+        },
+    ))
+    .expect("couldn't spawn supervisor process");
+    xous_kernel::wait_process_as_thread(supervisor).expect("couldn't join supervisor process");
+
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn resource_limits() {
+    use xous_kernel::{Error, Resource, RESOURCE_UNLIMITED};
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let xous_process = xous_kernel::create_process_as_thread(
+        xous_kernel::ProcessArgsAsThread::new("resource_limits process", move || {
+            let pid = xous_kernel::current_pid().expect("couldn't get pid");
+
+            // A process may lower its own thread limit, but not raise it again
+            let (threads, limit) = xous_kernel::resource_usage(pid, Resource::Threads).unwrap();
+            assert!(threads >= 1);
+            assert_eq!(limit, RESOURCE_UNLIMITED);
+            assert_eq!(
+                xous_kernel::set_resource_limit(pid, Resource::Threads, threads),
+                Ok(RESOURCE_UNLIMITED)
+            );
+            assert_eq!(
+                xous_kernel::create_thread(|| {}).map(|_| ()),
+                Err(Error::ResourceLimitExceeded)
+            );
+            assert_eq!(
+                xous_kernel::set_resource_limit(pid, Resource::Threads, threads + 1),
+                Err(Error::AccessDenied)
+            );
+
+            // Servers are counted, and limited. Creating a server also connects to it.
+            let (connections, _) = xous_kernel::resource_usage(pid, Resource::Connections).unwrap();
+            let (sid1, cid1) = xous_kernel::create_server()
+                .and_then(|sid| Ok((sid, xous_kernel::connect(sid)?)))
+                .expect("couldn't create test server");
+            let (sid2, cid2) = xous_kernel::create_server()
+                .and_then(|sid| Ok((sid, xous_kernel::connect(sid)?)))
+                .expect("couldn't create test server");
+            assert_eq!(
+                xous_kernel::resource_usage(pid, Resource::Servers),
+                Ok((2, RESOURCE_UNLIMITED))
+            );
+            assert_eq!(
+                xous_kernel::resource_usage(pid, Resource::Connections),
+                Ok((connections + 2, RESOURCE_UNLIMITED))
+            );
+            xous_kernel::set_resource_limit(pid, Resource::Servers, 2).unwrap();
+            assert_eq!(
+                xous_kernel::create_server(),
+                Err(Error::ResourceLimitExceeded)
+            );
+
+            // Connections are limited, but reusing a connection is always allowed
+            unsafe { xous_kernel::disconnect(cid2).expect("couldn't disconnect") };
+            xous_kernel::set_resource_limit(pid, Resource::Connections, connections + 1).unwrap();
+            assert_eq!(
+                xous_kernel::connect(sid2),
+                Err(Error::ResourceLimitExceeded)
+            );
+            assert_eq!(xous_kernel::connect(sid1), Ok(cid1));
+
+            // CPU ticks are counted but cannot be limited
+            assert_eq!(
+                xous_kernel::set_resource_limit(pid, Resource::CpuTicks, 0),
+                Err(Error::InvalidLimit)
+            );
+            let usage = xous_kernel::process_usage(pid).unwrap();
+            assert_eq!(usage.servers, 2);
+            assert_eq!(usage.threads, threads);
+        }),
+    )
+    .expect("couldn't spawn resource limits process");
+
+    xous_kernel::wait_process_as_thread(xous_process)
+        .expect("couldn't join resource limits process");
+
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn resource_limits_at_creation() {
+    use xous_kernel::{Error, Resource};
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+
+    // Limits passed at creation are in place before the process first runs
+    let xous_server = xous_kernel::create_process_as_thread_with_limits(
+        xous_kernel::ProcessArgsAsThread::new("resource_limits_at_creation server", move || {
+            let pid = xous_kernel::current_pid().expect("couldn't get pid");
+            assert_eq!(
+                xous_kernel::resource_usage(pid, Resource::Servers),
+                Ok((0, 1))
+            );
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            assert_eq!(
+                xous_kernel::create_server(),
+                Err(Error::ResourceLimitExceeded)
+            );
+            server_addr_send.send(sid).unwrap();
+
+            // The client was on the CPU until it sent this message, and was
+            // charged for that time when it handed the CPU over
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive message");
+            let client = if let xous_kernel::Message::BlockingScalar(scalar) = envelope.body {
+                xous_kernel::pid_from_usize(scalar.arg1).unwrap()
+            } else {
+                panic!("unexpected message {:?}", envelope.body);
+            };
+            let (cpu_ticks, _) = xous_kernel::resource_usage(client, Resource::CpuTicks).unwrap();
+            assert!(cpu_ticks > 0, "client was not charged for its CPU time");
+            xous_kernel::return_scalar(envelope.sender, 0).expect("couldn't return scalar");
+        }),
+        &[(Resource::Servers, 1)],
+    )
+    .expect("couldn't spawn server process");
+
+    // Resources that cannot be limited are rejected before anything is created
+    assert_eq!(
+        xous_kernel::create_process_as_thread_with_limits(
+            xous_kernel::ProcessArgsAsThread::new("resource_limits_at_creation invalid", || {}),
+            &[(Resource::CpuTicks, 1)],
+        )
+        .map(|_| ()),
+        Err(Error::InvalidLimit)
+    );
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "resource_limits_at_creation client",
+        move || {
+            let pid = xous_kernel::current_pid().expect("couldn't get pid");
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+            let busy_until = std::time::Instant::now() + std::time::Duration::from_millis(20);
+            while std::time::Instant::now() < busy_until {}
+            xous_kernel::send_message(
+                conn,
+                xous_kernel::Message::BlockingScalar(xous_kernel::ScalarMessage {
+                    id: 1,
+                    arg1: pid.get() as usize,
+                    arg2: 0,
+                    arg3: 0,
+                    arg4: 0,
+                }),
+            )
+            .expect("couldn't send message");
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[cfg(feature = "ipc-trace")]
+#[test]
+fn ipc_trace() {
+    use xous_kernel::{IpcTraceKind, Message, ScalarMessage};
+    // The server is the first process to start
+    let main_thread = start_kernel_with_roles(SERVER_SPEC, &[(2, xous_kernel::Role::TraceReader)]);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+    let (client_pid_send, client_pid_recv) = unbounded();
+    let (traced_send, traced_recv) = unbounded();
+    let (checked_send, checked_recv) = unbounded();
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "ipc_trace server",
+        move || {
+            let pid = xous_kernel::current_pid().expect("couldn't get pid");
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive messages");
+            xous_kernel::return_scalar(envelope.sender, 42).expect("couldn't return scalar");
+            let client_pid = client_pid_recv.recv().unwrap();
+
+            let mut events = vec![];
+            while let Some(event) = xous_kernel::read_ipc_trace(
+                events.last().map(|e: &xous_kernel::IpcTraceEvent| e.seq + 1).unwrap_or(0),
+            )
+            .expect("couldn't read trace")
+            {
+                events.push(event);
+            }
+            let send = events
+                .iter()
+                .find(|e| e.kind == IpcTraceKind::SendMessage && e.pid == client_pid)
+                .expect("send was not traced");
+            assert_eq!(send.peer, Some(pid));
+            assert_eq!(send.arg, 7);
+            let reply = events
+                .iter()
+                .find(|e| e.kind == IpcTraceKind::ReturnScalar1 && e.pid == pid)
+                .expect("reply was not traced");
+            assert_eq!(reply.peer, Some(client_pid));
+            assert_eq!(reply.sidx, send.sidx);
+            assert_eq!(reply.arg, 42);
+            assert!(reply.seq > send.seq);
+            assert!(reply.timestamp >= send.timestamp);
+
+            // Stay alive until the client has tried to read
+            traced_send.send(()).unwrap();
+            checked_recv.recv().unwrap();
+        },
+    ))
+    .expect("couldn't spawn server process");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "ipc_trace client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+            let result = xous_kernel::send_message(
+                conn,
+                Message::BlockingScalar(ScalarMessage {
+                    id: 7,
+                    arg1: 0,
+                    arg2: 0,
+                    arg3: 0,
+                    arg4: 0,
+                }),
+            )
+            .expect("couldn't send message");
+            assert_eq!(result, xous_kernel::Result::Scalar1(42));
+            client_pid_send
+                .send(xous_kernel::current_pid().expect("couldn't get pid"))
+                .unwrap();
+
+            // Only the process that was given the role may read the trace,
+            // whether or not it is reading at the time
+            assert_eq!(
+                xous_kernel::read_ipc_trace(0),
+                Err(xous_kernel::Error::AccessDenied)
+            );
+            traced_recv.recv().unwrap();
+            assert_eq!(
+                xous_kernel::read_ipc_trace(0),
+                Err(xous_kernel::Error::AccessDenied)
+            );
+            checked_send.send(()).unwrap();
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    xous_kernel::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    xous_kernel::wait_process_as_thread(xous_server).expect("couldn't join server process");
+
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn priority_scheduling() {
+    use crate::services::{Schedule, STARVATION_LIMIT};
+    use xous_kernel::ThreadPriority;
+
+    // Equal priorities take turns, starting after the last thread to run
+    let mut schedule = Schedule::new();
+    let ready = (1 << 2) | (1 << 3) | (1 << 5);
+    assert_eq!(schedule.pick_thread(ready, 2), Some(3));
+    assert_eq!(schedule.pick_thread(ready, 3), Some(5));
+    assert_eq!(schedule.pick_thread(ready, 5), Some(2));
+    assert_eq!(schedule.pick_thread(0, 5), None);
+
+    // A higher priority thread goes first, no matter the order
+    let mut schedule = Schedule::new();
+    assert_eq!(
+        schedule.set_priority(5, ThreadPriority::High),
+        ThreadPriority::Normal
+    );
+    schedule.set_priority(3, ThreadPriority::Idle);
+    assert_eq!(schedule.pick_thread(ready, 2), Some(5));
+    assert_eq!(schedule.pick_thread(ready, 5), Some(5));
+
+    // ...but a thread that is always ready can't starve the others
+    let mut runs = [0usize; 6];
+    let mut last = 5;
+    for _ in 0..(STARVATION_LIMIT as usize * 16) {
+        last = schedule.pick_thread(ready, last).unwrap();
+        runs[last] += 1;
+    }
+    assert!(runs[5] > runs[2], "high priority ran less: {:?}", runs);
+    assert!(runs[2] > runs[3], "normal priority ran less than idle: {:?}", runs);
+    assert!(runs[3] > 0, "idle thread starved: {:?}", runs);
+
+    // Recycling a thread slot resets it
+    schedule.reset_thread(5);
+    assert_eq!(schedule.priority(5), ThreadPriority::Normal);
+
+    // Processes compete with the best of their ready threads, and age as well
+    let busy = Schedule::new();
+    let mut background = Schedule::new();
+    background.set_priority(2, ThreadPriority::Low);
+    assert!(busy.process_key(1 << 2) > background.process_key(1 << 2));
+    for _ in 0..STARVATION_LIMIT {
+        background.process_scheduled(false);
+    }
+    assert!(busy.process_key(1 << 2) < background.process_key(1 << 2));
+    background.process_scheduled(true);
+    assert!(busy.process_key(1 << 2) > background.process_key(1 << 2));
+}
+
+#[test]
+fn supervisor_restart() {
+    // The supervisor is the first process to start
+    let main_thread = start_kernel_with_roles(SERVER_SPEC, &[(2, xous_kernel::Role::Supervisor)]);
+
+    let (supervisor_ready_send, supervisor_ready_recv) = unbounded();
+    let (service_pid_send, service_pid_recv) = unbounded();
+    let (exit_send, exit_recv) = unbounded();
+    let (restarted_send, restarted_recv) = unbounded();
+    let (service_ready_send, service_ready_recv) = unbounded();
+    let (service_exit_send, service_exit_recv) = unbounded();
+
+    let supervisor = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "supervisor_restart supervisor",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create supervisor server");
+            xous_kernel::set_supervisor(sid).expect("couldn't become the supervisor");
+            assert_eq!(
+                xous_kernel::role_holder(xous_kernel::Role::Supervisor),
+                xous_kernel::current_pid()
+            );
+            supervisor_ready_send.send(()).unwrap();
+
+            let service_pid = service_pid_recv.recv().unwrap();
+            loop {
+                let envelope = xous_kernel::receive_message(sid).expect("couldn't receive message");
+                assert_eq!(envelope.sender.pid().map(|pid| pid.get()), Some(1));
+                let exit = xous_kernel::ProcessExit::from_message(&envelope.body)
+                    .expect("not a death notification");
+                if exit.pid == service_pid {
+                    exit_send.send(exit).unwrap();
+                    break;
+                }
+            }
+
+            // Restart the service
+            let service = xous_kernel::create_process_as_thread(
+                xous_kernel::ProcessArgsAsThread::new(
+                    "supervisor_restart service again",
+                    move || {
+                        let sid = xous_kernel::create_server_with_address(b"supervised srv  ")
+                            .expect("couldn't create server again");
+                        let envelope =
+                            xous_kernel::receive_message(sid).expect("couldn't receive message");
+                        xous_kernel::return_scalar(envelope.sender, 42).expect("couldn't answer");
+                    },
+                ),
+            )
+            .expect("couldn't restart service");
+            restarted_send.send(service).unwrap();
+        },
+    ))
+    .expect("couldn't start supervisor");
+    supervisor_ready_recv.recv().unwrap();
+
+    // The first instance of the service receives a blocking message, then exits
+    // without answering it.
+    let service = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "supervisor_restart service",
+        move || {
+            let sid = xous_kernel::create_server_with_address(b"supervised srv  ")
+                .expect("couldn't create server");
+
+            // Only the process that was given the role may supervise
+            assert_eq!(
+                xous_kernel::set_supervisor(sid),
+                Err(xous_kernel::Error::AccessDenied)
+            );
+
+            service_pid_send
+                .send(xous_kernel::current_pid().unwrap())
+                .unwrap();
+            service_ready_send.send(()).unwrap();
+            xous_kernel::receive_message(sid).expect("couldn't receive message");
+            service_exit_recv.recv().unwrap();
+        },
+    ))
+    .expect("couldn't start service");
+    service_ready_recv.recv().unwrap();
+
+    let client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "supervisor_restart client",
+        move || {
+            let sid = xous_kernel::SID::from_bytes(b"supervised srv  ").unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+            let message = || xous_kernel::Message::new_blocking_scalar(1, 2, 3, 4, 5);
+            service_exit_send.send(()).unwrap();
+
+            // The service goes away while this is waiting for an answer
+            assert_eq!(
+                xous_kernel::send_message(conn, message()),
+                Err(xous_kernel::Error::ServerDisconnected)
+            );
+            assert_eq!(
+                xous_kernel::send_message(conn, message()),
+                Err(xous_kernel::Error::ServerDisconnected)
+            );
+
+            // Once the supervisor has brought the service back, a fresh
+            // connection works again
+            unsafe { xous_kernel::disconnect(conn).expect("couldn't disconnect") };
+            let conn = xous_kernel::connect(sid).expect("couldn't reconnect to server");
+            assert_eq!(
+                xous_kernel::send_message(conn, message()),
+                Ok(xous_kernel::Result::Scalar1(42))
+            );
+        },
+    ))
+    .expect("couldn't start client");
+
+    let exit = exit_recv.recv().unwrap();
+    assert_eq!(exit.reason, xous_kernel::ExitReason::Exited(0));
+    crate::wait_process_as_thread(service).expect("couldn't join service");
+    let service = restarted_recv.recv().unwrap();
+    crate::wait_process_as_thread(supervisor).expect("couldn't join supervisor");
+
+    crate::wait_process_as_thread(service).expect("couldn't join restarted service");
+    crate::wait_process_as_thread(client).expect("couldn't join client");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn server_owner() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_send, server_recv) = unbounded();
+    let (done_send, done_recv) = unbounded();
+    let server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "server_owner server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create server");
+            assert_eq!(
+                xous_kernel::server_owner(sid),
+                Ok(xous_kernel::current_pid().unwrap())
+            );
+            server_send
+                .send((sid, xous_kernel::current_pid().unwrap()))
+                .unwrap();
+            done_recv.recv().unwrap();
+        },
+    ))
+    .expect("couldn't start server");
+    let (sid, server_pid) = server_recv.recv().unwrap();
+
+    let client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "server_owner client",
+        move || {
+            assert_ne!(xous_kernel::current_pid().unwrap(), server_pid);
+            assert_eq!(xous_kernel::server_owner(sid), Ok(server_pid));
+            assert_eq!(
+                xous_kernel::server_owner(xous_kernel::SID::from_u32(1, 2, 3, 4)),
+                Err(xous_kernel::Error::ServerNotFound)
+            );
+            done_send.send(()).unwrap();
+        },
+    ))
+    .expect("couldn't start client");
+
+    crate::wait_process_as_thread(client).expect("couldn't join client");
+    crate::wait_process_as_thread(server).expect("couldn't join server");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
@@ -0,0 +1,140 @@
+//! Deadlines for threads that are blocked in `ReceiveMessageTimeout` or
+//! `SendMessageTimeout`. The table only records what each thread is waiting on;
+//! `SystemServices::expire_timeouts()` is what wakes threads whose time is up.
+//!
+//! On hardware, deadlines are checked whenever the kernel picks the next process
+//! to run. So that this happens even on an otherwise idle system, the kernel asks
+//! the ticktimer server for an interrupt at the next deadline.
+
+use xous_kernel::{PID, TID};
+
+/// The number of threads that may be waiting with a timeout at once
+pub const MAX_TIMEOUTS: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Wait {
+    /// Parked on the server with this index, waiting for a message
+    Receive(usize /* sidx */),
+
+    /// Waiting for the server with this index to receive and reply to the
+    /// message in this slot of its queue
+    Send(usize /* sidx */, usize /* queue index */),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Timeout {
+    pub pid: PID,
+    pub tid: TID,
+
+    /// When the thread should give up, in milliseconds since boot
+    pub deadline: u64,
+
+    pub wait: Wait,
+}
+
+pub struct Timeouts {
+    entries: [Option<Timeout>; MAX_TIMEOUTS],
+
+    /// How many entries are in use, so the common case of nobody waiting is cheap
+    active: usize,
+
+    /// The time the ticktimer was last asked to wake the kernel at
+    wakeup: Option<u64>,
+}
+
+impl Timeouts {
+    pub const fn new() -> Self {
+        Timeouts {
+            entries: [None; MAX_TIMEOUTS],
+            active: 0,
+            wakeup: None,
+        }
+    }
+
+    /// Start a timeout. A thread can only wait on one thing at a time, so this
+    /// replaces any timeout the thread already had.
+    ///
+    /// # Errors
+    ///
+    /// * **OutOfMemory**: Every slot is already in use
+    pub fn insert(&mut self, timeout: Timeout) -> Result<(), xous_kernel::Error> {
+        self.cancel(timeout.pid, timeout.tid);
+        let slot = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.is_none())
+            .ok_or(xous_kernel::Error::OutOfMemory)?;
+        *slot = Some(timeout);
+        self.active += 1;
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.active >= MAX_TIMEOUTS
+    }
+
+    /// Forget the timeout for the given thread, if it has one
+    pub fn cancel(&mut self, pid: PID, tid: TID) {
+        self.retain(|timeout| timeout.pid != pid || timeout.tid != tid);
+    }
+
+    /// Forget every timeout belonging to the given process
+    pub fn cancel_pid(&mut self, pid: PID) {
+        self.retain(|timeout| timeout.pid != pid);
+    }
+
+    fn retain<F: Fn(&Timeout) -> bool>(&mut self, keep: F) {
+        if self.active == 0 {
+            return;
+        }
+        for entry in self.entries.iter_mut() {
+            if let Some(timeout) = entry {
+                if !keep(timeout) {
+                    *entry = None;
+                    self.active -= 1;
+                }
+            }
+        }
+    }
+
+    /// The earliest deadline of any waiting thread
+    pub fn next_deadline(&self) -> Option<u64> {
+        if self.active == 0 {
+            return None;
+        }
+        self.entries
+            .iter()
+            .flatten()
+            .map(|timeout| timeout.deadline)
+            .min()
+    }
+
+    /// The time to ask for a wakeup at, or `None` if nobody is waiting or a
+    /// wakeup that is still to come already covers the next deadline
+    pub fn wakeup_needed(&self, now: u64) -> Option<u64> {
+        let deadline = self.next_deadline()?;
+        match self.wakeup {
+            Some(wakeup) if wakeup > now && wakeup <= deadline => None,
+            _ => Some(deadline),
+        }
+    }
+
+    /// Note that a wakeup has been asked for at `deadline`
+    pub fn wakeup_requested(&mut self, deadline: u64) {
+        self.wakeup = Some(deadline);
+    }
+
+    /// Remove and return a timeout whose deadline is at or before `now`
+    pub fn take_expired(&mut self, now: u64) -> Option<Timeout> {
+        if self.active == 0 {
+            return None;
+        }
+        for entry in self.entries.iter_mut() {
+            if matches!(entry, Some(timeout) if timeout.deadline <= now) {
+                self.active -= 1;
+                return entry.take();
+            }
+        }
+        None
+    }
+}
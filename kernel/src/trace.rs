@@ -0,0 +1,171 @@
+//! IPC flight recorder. When the `ipc-trace` feature is enabled, every message
+//! send and every reply is recorded into a ring buffer, which can be dumped from
+//! the debug console or read through `ReadIpcTrace` by the one process that the
+//! loader gave `Role::TraceReader`.
+
+use crate::services::SystemServices;
+use xous_kernel::{IpcTraceEvent, IpcTraceKind, SysCall, PID, TID};
+
+/// Number of events kept in the ring buffer
+pub const TRACE_LEN: usize = 256;
+
+pub struct Trace {
+    events: [Option<IpcTraceEvent>; TRACE_LEN],
+
+    /// Sequence number of the next event to be recorded
+    next_seq: usize,
+
+    /// Number of times the scheduler has run, which stands in for a clock on hardware
+    #[allow(dead_code)]
+    ticks: usize,
+
+    #[cfg(not(baremetal))]
+    start: Option<std::time::Instant>,
+}
+
+#[cfg(not(baremetal))]
+std::thread_local!(static TRACE: core::cell::RefCell<Trace> = core::cell::RefCell::new(Trace::new()));
+
+#[cfg(baremetal)]
+static mut TRACE: Trace = Trace::new();
+
+impl Trace {
+    const fn new() -> Self {
+        Trace {
+            events: [None; TRACE_LEN],
+            next_seq: 0,
+            ticks: 0,
+            #[cfg(not(baremetal))]
+            start: None,
+        }
+    }
+
+    pub fn with_mut<F, R>(f: F) -> R
+    where
+        F: FnOnce(&mut Trace) -> R,
+    {
+        #[cfg(baremetal)]
+        unsafe {
+            f(&mut TRACE)
+        }
+
+        #[cfg(not(baremetal))]
+        TRACE.with(|trace| f(&mut trace.borrow_mut()))
+    }
+
+    #[cfg(baremetal)]
+    fn timestamp(&mut self) -> usize {
+        self.ticks
+    }
+
+    #[cfg(not(baremetal))]
+    fn timestamp(&mut self) -> usize {
+        self.start
+            .get_or_insert_with(std::time::Instant::now)
+            .elapsed()
+            .as_micros() as usize
+    }
+
+    fn push(&mut self, event: IpcTraceEvent) {
+        self.events[event.seq % TRACE_LEN] = Some(event);
+        self.next_seq += 1;
+    }
+
+    /// The oldest event whose sequence number is at least `seq`
+    pub fn event_from(&self, seq: usize) -> Option<IpcTraceEvent> {
+        let oldest = self.next_seq.saturating_sub(TRACE_LEN);
+        let seq = seq.max(oldest);
+        if seq >= self.next_seq {
+            return None;
+        }
+        self.events[seq % TRACE_LEN]
+    }
+
+    /// Print the contents of the ring buffer, oldest first
+    #[allow(dead_code)]
+    pub fn dump(&self) {
+        println!("IPC trace ({} events recorded):", self.next_seq);
+        println!("   seq |  timestamp | kind     |   from  |  to | sidx | arg");
+        let mut seq = 0;
+        while let Some(event) = self.event_from(seq) {
+            let kind = match event.kind {
+                IpcTraceKind::SendMessage => "send",
+                IpcTraceKind::TrySendMessage => "try_send",
+                IpcTraceKind::ReturnMemory => "ret_mem",
+                IpcTraceKind::ReturnScalar1 => "ret_s1",
+                IpcTraceKind::ReturnScalar2 => "ret_s2",
+            };
+            println!(
+                " {:>5} | {:>10} | {:8} | {:>3}:{:<3} | {:>3} | {:>4} | {:08x}",
+                event.seq,
+                event.timestamp,
+                kind,
+                event.pid,
+                event.tid,
+                event.peer.map(|p| p.get()).unwrap_or(0),
+                event.sidx,
+                event.arg
+            );
+            seq = event.seq + 1;
+        }
+    }
+}
+
+/// Note that the scheduler has run once more
+#[allow(dead_code)]
+pub fn tick() {
+    Trace::with_mut(|trace| trace.ticks = trace.ticks.wrapping_add(1));
+}
+
+/// Record `call` if it is one of the IPC operations that are traced
+pub fn record(pid: PID, tid: TID, call: &SysCall) {
+    let (kind, peer, sidx, arg) = match call {
+        SysCall::SendMessage(cid, message)
+        | SysCall::SendMessageTimeout(cid, message, _)
+        | SysCall::TrySendMessage(cid, message) => {
+            let kind = if let SysCall::TrySendMessage(..) = call {
+                IpcTraceKind::TrySendMessage
+            } else {
+                IpcTraceKind::SendMessage
+            };
+            let (peer, sidx) = SystemServices::with(|ss| {
+                ss.sidx_from_cid(*cid)
+                    .map(|sidx| (ss.server_from_sidx(sidx).map(|s| s.pid), sidx))
+                    .unwrap_or((None, 0))
+            });
+            (kind, peer, sidx, message.id())
+        }
+        SysCall::ReturnMemory(sender, _, offset, _) => {
+            let sender = crate::server::SenderID::from(*sender);
+            (
+                IpcTraceKind::ReturnMemory,
+                sender.pid(),
+                sender.sidx,
+                offset.map(|o| o.get()).unwrap_or(0),
+            )
+        }
+        SysCall::ReturnScalar1(sender, arg) => {
+            let sender = crate::server::SenderID::from(*sender);
+            (IpcTraceKind::ReturnScalar1, sender.pid(), sender.sidx, *arg)
+        }
+        SysCall::ReturnScalar2(sender, arg, _) => {
+            let sender = crate::server::SenderID::from(*sender);
+            (IpcTraceKind::ReturnScalar2, sender.pid(), sender.sidx, *arg)
+        }
+        _ => return,
+    };
+
+    Trace::with_mut(|trace| {
+        let event = IpcTraceEvent {
+            seq: trace.next_seq,
+            timestamp: trace.timestamp(),
+            kind,
+            pid,
+            tid,
+            peer,
+            sidx,
+            arg,
+        };
+        trace.push(event);
+    });
+}
@@ -1,6 +1,8 @@
-#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
-pub struct TestStruct {
-    pub challenge: [u32; 8],
+xous_ipc::payload! {
+    #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+    pub struct TestStruct {
+        pub challenge: [u32; 8],
+    }
 }
 impl TestStruct {
     pub fn new() -> Self {
@@ -10,10 +12,16 @@ impl TestStruct {
 
 pub const SERVER_NAME_BENCHMARK: &str = "_Benchmark target_";
 
-#[allow(dead_code)]
-#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
-pub enum Opcode {
-    TestScalar, //(u32),
-    TestMemory, //(TestStruct),
-    TestMemorySend,
+xous_ipc::api! {
+    /// Round-trip targets for the IPC benchmark
+    pub mod bench_api {
+        version: (1, 0),
+
+        /// Return `value + 1`
+        blocking_scalar TestScalar = 0 => fn test_scalar(value);
+        /// Return a `TestStruct` whose first challenge word is one more than the one lent
+        lend_mut TestMemory = 1 => fn test_memory(TestStruct);
+        /// Add the first challenge word to the server's running total
+        send TestMemorySend = 2 => fn test_memory_send(TestStruct);
+    }
 }
@@ -3,12 +3,26 @@
 
 mod api;
 use api::*;
-use xous::msg_blocking_scalar_unpack;
 
 use log::{error, info};
 
-use num_traits::FromPrimitive;
-use xous_ipc::Buffer;
+struct BenchTarget {
+    state: u32,
+}
+
+impl bench_api::Handler for BenchTarget {
+    fn test_scalar(&mut self, _sender: xous::MessageSender, value: usize) -> usize {
+        value + 1
+    }
+    fn test_memory(&mut self, _sender: xous::MessageSender, reg: TestStruct) -> TestStruct {
+        let mut ret = TestStruct::new();
+        ret.challenge[0] = reg.challenge[0] + 1;
+        ret
+    }
+    fn test_memory_send(&mut self, _sender: xous::MessageSender, reg: TestStruct) {
+        self.state += reg.challenge[0];
+    }
+}
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
@@ -20,33 +34,16 @@ fn main() -> ! {
         .expect("BENCHTARGET: can't register server");
     info!("BENCHTARGET: registered with NS -- {:?}", bench_sid);
 
-    let mut state: u32 = 0;
+    let mut target = BenchTarget { state: 0 };
     loop {
-        let mut envelope = xous::receive_message(bench_sid).unwrap();
-        match FromPrimitive::from_usize(envelope.body.id()) {
-            Some(Opcode::TestScalar) => msg_blocking_scalar_unpack!(envelope, val, _, _, _, {
-                xous::return_scalar(envelope.sender, (val + 1) as usize)
-                    .expect("BENCHTARGET: couldn't return TestScalar request");
-            }),
-            Some(Opcode::TestMemory) => {
-                let mut buffer = unsafe {
-                    Buffer::from_memory_message_mut(envelope.body.memory_message_mut().unwrap())
-                };
-                let reg = buffer.to_original::<TestStruct, _>().unwrap();
-
-                let mut ret = TestStruct::new();
-                ret.challenge[0] = reg.challenge[0] + 1;
-                buffer.replace(ret).unwrap();
-            }
-            Some(Opcode::TestMemorySend) => {
-                let mut buffer = unsafe {
-                    Buffer::from_memory_message_mut(envelope.body.memory_message_mut().unwrap())
-                };
-                let reg = buffer.to_original::<TestStruct, _>().unwrap();
-                state += reg.challenge[0];
+        let envelope = xous::receive_message(bench_sid).unwrap();
+        match bench_api::dispatch(&mut target, envelope) {
+            Ok(()) => (),
+            Err(xous_ipc::DispatchError::BadPayload(envelope)) => {
+                error!("BENCHTARGET: couldn't decode payload: {:?}", envelope);
             }
-            None => {
-                error!("BENCHTARGET: couldn't convert opcode");
+            Err(xous_ipc::DispatchError::Unhandled(envelope)) => {
+                error!("BENCHTARGET: couldn't convert opcode: {:?}", envelope);
             }
         }
     }
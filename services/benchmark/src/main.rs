@@ -89,6 +89,8 @@ fn main() -> ! {
     let target_conn = xns
         .request_connection_blocking(benchmark_target::api::SERVER_NAME_BENCHMARK)
         .expect("BENCHMARK: can't connect to COM");
+    let target = benchmark_target::Client::new(target_conn)
+        .expect("BENCHMARK: benchmark target speaks an incompatible API");
 
     xous::create_thread_0(stopwatch_thread).unwrap();
     info!("BENCHMARK: stopwatch thread started");
@@ -166,7 +168,7 @@ fn main() -> ! {
             // xous v0.8
             // 29729 per 10s = 2972.9/s (hardware)
             // 485 per 10s = 48.5/s (hosted)
-            count = benchmark_target::test_scalar(target_conn, count)
+            count = benchmark_target::test_scalar(&target, count)
                 .expect("BENCHMARK: couldn't send test message");
             check_count = check_count + 1;
         } else {
@@ -177,12 +179,12 @@ fn main() -> ! {
                 // xous v0.8
                 // 9,928 per 10s = 992.8/s (hardware)
                 // 243 per 10s = 24.3/s (hosted)
-                count = benchmark_target::test_memory(target_conn, count)
+                count = benchmark_target::test_memory(&target, count)
                     .expect("BENCHMARK: couldn't send test message");
                 check_count = check_count + 1;
             } else {
                 // simple send benchmark, instead of lend
-                count = benchmark_target::test_memory_send(target_conn, count)
+                count = benchmark_target::test_memory_send(&target, count)
                     .expect("BENCHMARK: couldn't send test message");
                 check_count = check_count + 1;
             }
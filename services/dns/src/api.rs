@@ -16,7 +16,7 @@ pub(crate) enum Opcode {
     Lookup = 0,
     Flush = 1,
 
-    /// used internally to update the TTL field, and eventually expire the cache (unless cache is frozen)
+    /// used internally to periodically drop cache entries that have outlived their TTL (unless cache is frozen)
     UpdateTtl = 2,
 
     /// issuing this opcode causes all future attempts to change the DNS server configs to be ignored. This also freezes the cache.
@@ -49,6 +49,10 @@ pub(crate) enum Opcode {
     ///     * 4: Ipv4 Address -- 4 octets follow, for a total of 5 bytes
     ///     * 6: Ipv6 Address -- 16 octets follow, for a total of 17 bytes
     RawLookup = 6,
+
+    /// Returns a page of the cache contents, as a `DnsCachePage`. The caller sets `start`
+    /// to the index of the first entry it wants.
+    CacheEntries = 7,
 }
 
 #[derive(
@@ -60,6 +64,8 @@ pub(crate) enum Opcode {
     Deserialize,
     Copy,
     Clone,
+    PartialEq,
+    Eq,
 )]
 #[repr(u16)]
 pub enum DnsResponseCode {
@@ -80,3 +86,27 @@ pub struct DnsResponse {
     pub addr: Option<NetIpAddr>,
     pub code: DnsResponseCode,
 }
+
+pub const DNS_CACHE_PAGE_LEN: usize = 8;
+
+/// One entry of the DNS cache, as seen from the outside
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone)]
+pub struct DnsCacheRecord {
+    pub name: xous_ipc::String<DNS_NAME_LENGTH_LIMIT>,
+    /// the first of the cached addresses; IPv4 addresses come first
+    pub addr: Option<NetIpAddr>,
+    /// total number of cached addresses, of both families
+    pub addr_count: u32,
+    /// seconds until (part of) the entry expires
+    pub ttl: u32,
+    /// set if the entry records that the name does not exist
+    pub negative: bool,
+}
+
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone)]
+pub struct DnsCachePage {
+    pub start: u32,
+    /// total number of entries in the cache
+    pub total: u32,
+    pub records: [Option<DnsCacheRecord>; DNS_CACHE_PAGE_LEN],
+}
@@ -0,0 +1,325 @@
+use crate::api::DnsResponseCode;
+use crate::message::QueryType;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Upper bound on how long anything stays in the cache, regardless of the TTL the server hands out
+pub(crate) const MAX_TTL_SECS: u32 = 86400;
+/// Upper bound for negative answers. RFC 2308 suggests one to three hours.
+pub(crate) const MAX_NEGATIVE_TTL_SECS: u32 = 3600;
+/// Negative answers without an SOA record are still cached for a little while, so that e.g. a
+/// name without IPv6 addresses doesn't trigger an AAAA query on every lookup.
+pub(crate) const DEFAULT_NEGATIVE_TTL_SECS: u32 = 30;
+/// When the cache is full, the entry closest to expiring is evicted
+const MAX_CACHE_ENTRIES: usize = 256;
+
+/// The records of one type for a name. An empty set is a cached "no records of this type".
+#[derive(Debug)]
+struct RecordSet {
+    addrs: Vec<IpAddr>,
+    expiry_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntry {
+    a: Option<RecordSet>,
+    aaaa: Option<RecordSet>,
+    /// set if the name doesn't exist at all
+    nxdomain: Option<u64>,
+}
+impl CacheEntry {
+    /// The earliest time at which something in this entry expires
+    fn expiry_ms(&self) -> u64 {
+        self.a
+            .iter()
+            .chain(self.aaaa.iter())
+            .map(|set| set.expiry_ms)
+            .chain(self.nxdomain.iter().copied())
+            .min()
+            .unwrap_or(0)
+    }
+    /// Drops whatever has expired, returning `true` if nothing is left
+    fn purge(&mut self, now_ms: u64) -> bool {
+        if self.a.as_ref().map_or(false, |set| set.expiry_ms <= now_ms) {
+            self.a = None;
+        }
+        if self.aaaa.as_ref().map_or(false, |set| set.expiry_ms <= now_ms) {
+            self.aaaa = None;
+        }
+        if self.nxdomain.map_or(false, |expiry| expiry <= now_ms) {
+            self.nxdomain = None;
+        }
+        self.a.is_none() && self.aaaa.is_none() && self.nxdomain.is_none()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum CacheLookup {
+    /// IPv4 addresses come first
+    Hit(Vec<IpAddr>),
+    /// Only one address family is still cached; the other one, given here, has expired and should be queried again
+    Partial(Vec<IpAddr>, QueryType),
+    /// The name is known not to resolve; the code is what the original response reported
+    Negative(DnsResponseCode),
+    Miss,
+}
+
+/// A summary of one cache entry, for inspection
+#[derive(Debug)]
+pub(crate) struct CacheInfo {
+    pub name: String,
+    pub addrs: Vec<IpAddr>,
+    /// seconds until the first part of the entry expires
+    pub ttl_secs: u32,
+    pub negative: bool,
+}
+
+/// DNS cache that honors the TTLs of the records it holds, and remembers negative answers.
+///
+/// Times are in milliseconds, as reported by the ticktimer; they are passed in by the caller
+/// so the cache doesn't need to know where they come from. While the cache is frozen, nothing expires.
+#[derive(Debug, Default)]
+pub(crate) struct DnsCache {
+    entries: HashMap<String, CacheEntry>,
+    frozen: bool,
+}
+
+impl DnsCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// `name` must already be normalized (see [`crate::message::normalize_name`])
+    pub fn lookup(&mut self, name: &str, now_ms: u64) -> CacheLookup {
+        let frozen = self.frozen;
+        let entry = match self.entries.get_mut(name) {
+            Some(entry) => entry,
+            None => return CacheLookup::Miss,
+        };
+        if !frozen && entry.purge(now_ms) {
+            self.entries.remove(name);
+            return CacheLookup::Miss;
+        }
+        if entry.nxdomain.is_some() {
+            return CacheLookup::Negative(DnsResponseCode::NameError);
+        }
+        let addrs: Vec<IpAddr> = entry
+            .a
+            .iter()
+            .chain(entry.aaaa.iter())
+            .flat_map(|set| set.addrs.iter().copied())
+            .collect();
+        if !addrs.is_empty() {
+            match (&entry.a, &entry.aaaa) {
+                (Some(_), None) => CacheLookup::Partial(addrs, QueryType::AAAA),
+                (None, Some(_)) => CacheLookup::Partial(addrs, QueryType::A),
+                _ => CacheLookup::Hit(addrs),
+            }
+        } else if entry.a.is_some() && entry.aaaa.is_some() {
+            // the name exists, but has no addresses of either family
+            CacheLookup::Negative(DnsResponseCode::NameError)
+        } else {
+            CacheLookup::Miss
+        }
+    }
+
+    /// Caches the outcome of a query of type `qtype`. An empty `addrs` is a negative answer, in which case
+    /// `ttl_secs` should come from the SOA record of the response, if it had one.
+    pub fn insert(&mut self, name: &str, qtype: QueryType, addrs: Vec<IpAddr>, ttl_secs: Option<u32>, now_ms: u64) {
+        let ttl = if addrs.is_empty() {
+            ttl_secs.unwrap_or(DEFAULT_NEGATIVE_TTL_SECS).min(MAX_NEGATIVE_TTL_SECS)
+        } else {
+            ttl_secs.unwrap_or(0).min(MAX_TTL_SECS)
+        };
+        if ttl == 0 {
+            // zero TTL means "use this answer, but don't cache it"
+            return;
+        }
+        let set = RecordSet {
+            addrs,
+            expiry_ms: now_ms + ttl as u64 * 1000,
+        };
+        self.make_room(name);
+        let entry = self.entries.entry(name.to_string()).or_default();
+        entry.nxdomain = None;
+        match qtype {
+            QueryType::A => entry.a = Some(set),
+            QueryType::AAAA => entry.aaaa = Some(set),
+            _ => log::warn!("not caching records of type {:?}", qtype),
+        }
+    }
+
+    /// Records that `name` doesn't exist, for `ttl_secs` from the SOA record of the response, if it had one
+    pub fn insert_nxdomain(&mut self, name: &str, ttl_secs: Option<u32>, now_ms: u64) {
+        let ttl = ttl_secs.unwrap_or(DEFAULT_NEGATIVE_TTL_SECS).min(MAX_NEGATIVE_TTL_SECS);
+        if ttl == 0 {
+            return;
+        }
+        self.make_room(name);
+        let entry = self.entries.entry(name.to_string()).or_default();
+        entry.a = None;
+        entry.aaaa = None;
+        entry.nxdomain = Some(now_ms + ttl as u64 * 1000);
+    }
+
+    /// Removes everything that has expired, unless the cache is frozen
+    pub fn purge(&mut self, now_ms: u64) {
+        if self.frozen {
+            return;
+        }
+        self.entries.retain(|name, entry| {
+            let expired = entry.purge(now_ms);
+            if expired {
+                log::debug!("DNS cache removing {}", name);
+            }
+            !expired
+        });
+    }
+
+    /// Lists the cache contents, sorted by name
+    pub fn info(&self, now_ms: u64) -> Vec<CacheInfo> {
+        let mut info: Vec<CacheInfo> = self
+            .entries
+            .iter()
+            .map(|(name, entry)| CacheInfo {
+                name: name.to_string(),
+                addrs: entry
+                    .a
+                    .iter()
+                    .chain(entry.aaaa.iter())
+                    .flat_map(|set| set.addrs.iter().copied())
+                    .collect(),
+                ttl_secs: (entry.expiry_ms().saturating_sub(now_ms) / 1000) as u32,
+                negative: entry.nxdomain.is_some(),
+            })
+            .collect();
+        info.sort_by(|a, b| a.name.cmp(&b.name));
+        info
+    }
+
+    fn make_room(&mut self, name: &str) {
+        if self.entries.len() < MAX_CACHE_ENTRIES || self.entries.contains_key(name) {
+            return;
+        }
+        if let Some(victim) = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.expiry_ms())
+            .map(|(name, _)| name.to_string())
+        {
+            log::debug!("DNS cache full, evicting {}", victim);
+            self.entries.remove(&victim);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn v4(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+    fn v6(last: u16) -> IpAddr {
+        IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, last))
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let mut cache = DnsCache::new();
+        assert_eq!(cache.lookup("example.com", 0), CacheLookup::Miss);
+        cache.insert("example.com", QueryType::A, vec![v4(1)], Some(60), 1000);
+        cache.insert("example.com", QueryType::AAAA, vec![v6(1)], Some(10), 1000);
+        assert_eq!(cache.lookup("example.com", 1000), CacheLookup::Hit(vec![v4(1), v6(1)]));
+        // the AAAA record expires first, the A record keeps going until AAAA is queried again
+        assert_eq!(cache.lookup("example.com", 11_000), CacheLookup::Partial(vec![v4(1)], QueryType::AAAA));
+        cache.insert("example.com", QueryType::AAAA, vec![v6(2)], Some(10), 11_000);
+        assert_eq!(cache.lookup("example.com", 11_000), CacheLookup::Hit(vec![v4(1), v6(2)]));
+        assert_eq!(cache.lookup("example.com", 21_000), CacheLookup::Partial(vec![v4(1)], QueryType::AAAA));
+        assert_eq!(cache.lookup("example.com", 61_000), CacheLookup::Miss);
+        assert_eq!(cache.len(), 0);
+
+        // zero TTL isn't cached at all, and huge TTLs are clamped
+        cache.insert("zero.com", QueryType::A, vec![v4(2)], Some(0), 0);
+        assert_eq!(cache.lookup("zero.com", 0), CacheLookup::Miss);
+        cache.insert("long.com", QueryType::A, vec![v4(3)], Some(u32::MAX), 0);
+        assert_eq!(cache.info(0)[0].ttl_secs, MAX_TTL_SECS);
+    }
+
+    #[test]
+    fn test_negative() {
+        let mut cache = DnsCache::new();
+        cache.insert_nxdomain("nope.com", Some(300), 0);
+        assert_eq!(cache.lookup("nope.com", 299_000), CacheLookup::Negative(DnsResponseCode::NameError));
+        assert_eq!(cache.lookup("nope.com", 300_000), CacheLookup::Miss);
+
+        // no SOA: default negative TTL; SOA with a huge TTL: clamped
+        cache.insert_nxdomain("nope.com", None, 0);
+        assert_eq!(cache.info(0)[0].ttl_secs, DEFAULT_NEGATIVE_TTL_SECS);
+        cache.insert_nxdomain("nope.com", Some(u32::MAX), 0);
+        assert_eq!(cache.info(0)[0].ttl_secs, MAX_NEGATIVE_TTL_SECS);
+        assert!(cache.info(0)[0].negative);
+
+        // no records of one type isn't negative for the name as a whole
+        cache.insert("v4only.com", QueryType::A, vec![v4(1)], Some(60), 0);
+        cache.insert("v4only.com", QueryType::AAAA, vec![], None, 0);
+        assert_eq!(cache.lookup("v4only.com", 0), CacheLookup::Hit(vec![v4(1)]));
+        // but no records of either type is
+        cache.insert("empty.com", QueryType::A, vec![], Some(60), 0);
+        assert_eq!(cache.lookup("empty.com", 0), CacheLookup::Miss);
+        cache.insert("empty.com", QueryType::AAAA, vec![], Some(60), 0);
+        assert_eq!(cache.lookup("empty.com", 0), CacheLookup::Negative(DnsResponseCode::NameError));
+
+        // a positive answer replaces a negative one
+        cache.insert("nope.com", QueryType::A, vec![v4(4)], Some(60), 0);
+        assert_eq!(cache.lookup("nope.com", 0), CacheLookup::Partial(vec![v4(4)], QueryType::AAAA));
+        // and a name that only has AAAA records asks for A again once that has expired
+        cache.insert("v6only.com", QueryType::A, vec![], Some(5), 0);
+        cache.insert("v6only.com", QueryType::AAAA, vec![v6(1)], Some(60), 0);
+        assert_eq!(cache.lookup("v6only.com", 0), CacheLookup::Hit(vec![v6(1)]));
+        assert_eq!(cache.lookup("v6only.com", 5_000), CacheLookup::Partial(vec![v6(1)], QueryType::A));
+    }
+
+    #[test]
+    fn test_frozen_and_purge() {
+        let mut cache = DnsCache::new();
+        cache.insert("a.com", QueryType::A, vec![v4(1)], Some(1), 0);
+        cache.insert("b.com", QueryType::A, vec![v4(2)], Some(100), 0);
+        cache.set_frozen(true);
+        cache.purge(50_000);
+        assert_eq!(cache.lookup("a.com", 50_000), CacheLookup::Partial(vec![v4(1)], QueryType::AAAA));
+        cache.set_frozen(false);
+        cache.purge(50_000);
+        assert_eq!(cache.len(), 1);
+        let info = cache.info(50_000);
+        assert_eq!(info[0].name, "b.com");
+        assert_eq!(info[0].ttl_secs, 50);
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = DnsCache::new();
+        for i in 0..MAX_CACHE_ENTRIES {
+            cache.insert(&format!("host{}.com", i), QueryType::A, vec![v4(1)], Some(100 + i as u32), 0);
+        }
+        assert_eq!(cache.len(), MAX_CACHE_ENTRIES);
+        cache.insert("new.com", QueryType::A, vec![v4(2)], Some(60), 0);
+        assert_eq!(cache.len(), MAX_CACHE_ENTRIES);
+        // the one closest to expiry made way
+        assert_eq!(cache.lookup("host0.com", 0), CacheLookup::Miss);
+        assert_eq!(cache.lookup("new.com", 0), CacheLookup::Partial(vec![v4(2)], QueryType::AAAA));
+    }
+}
@@ -1,6 +1,6 @@
 use net::NetIpAddr;
 use std::net::ToSocketAddrs;
-use crate::DnsResponseCode;
+use crate::{DnsCacheRecord, DnsResponseCode};
 
 #[derive(Debug)]
 pub struct Dns {
@@ -38,4 +38,8 @@ impl Dns {
         log::warn!("DNS cache flush not implemented in hosted mode!");
         Ok(())
     }
+    pub fn cache_entries(&self) -> Result<Vec<DnsCacheRecord>, xous::Error> {
+        log::warn!("DNS cache is not used in hosted mode!");
+        Ok(Vec::new())
+    }
 }
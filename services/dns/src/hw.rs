@@ -45,6 +45,27 @@ impl Dns {
             xous::Message::new_scalar(Opcode::Flush.to_usize().unwrap(), 0, 0, 0, 0)
         ).map(|_| ())
     }
+    /// Returns a snapshot of the resolver's cache, sorted by name
+    pub fn cache_entries(&self) -> Result<Vec<DnsCacheRecord>, xous::Error> {
+        let mut entries = Vec::new();
+        loop {
+            let page = DnsCachePage {
+                start: entries.len() as u32,
+                total: 0,
+                records: [None; DNS_CACHE_PAGE_LEN],
+            };
+            let mut buf = Buffer::into_buf(page).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.conn, Opcode::CacheEntries.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            let page = buf.to_original::<DnsCachePage, _>().or(Err(xous::Error::InternalError))?;
+            let count = page.records.iter().flatten().count();
+            entries.extend(page.records.iter().flatten().copied());
+            if count < DNS_CACHE_PAGE_LEN || entries.len() >= page.total as usize {
+                break;
+            }
+        }
+        Ok(entries)
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -3,306 +3,25 @@
 
 mod api;
 use api::*;
+mod cache;
+use cache::{CacheLookup, DnsCache};
+mod message;
+use message::{normalize_name, Message, QueryClass, QueryType, MAX_CNAME_CHAIN};
 
 use net::NetIpAddr;
 use num_traits::*;
-use xous::msg_scalar_unpack;
 
-use std::collections::HashMap;
 use std::convert::TryInto;
-use std::io::ErrorKind;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
 use std::time::Duration;
 use std::thread;
 use xous_ipc::{Buffer, String};
 
-// KISS DNS
-
-// The DNS implementation here is based on https://github.com/vinc/moros/blob/43ac7cdc8ccc860dc1b6f0f060b5dbcd01424c03/src/usr/host.rs
-// MOROS is MIT licensed.
-// See RFC 1035 for implementation details
-
-#[repr(u16)]
-enum QueryType {
-    A = 1,
-    // NS = 2,
-    // MD = 3,
-    // MF = 4,
-    // CNAME = 5,
-    // SOA = 6,
-    // MX = 15,
-    // TXT = 16,
-}
-
-#[repr(u16)]
-enum QueryClass {
-    IN = 1,
-}
-
-struct Message {
-    pub datagram: Vec<u8>,
-}
-
-const FLAG_RD: u16 = 0x0100; // Recursion desired
-
-impl Message {
-    pub fn from(datagram: &[u8]) -> Self {
-        Self {
-            datagram: Vec::from(datagram),
-        }
-    }
-
-    pub fn query(qname: &str, qtype: QueryType, qclass: QueryClass, id: u16) -> Self {
-        let mut datagram = Vec::new();
-
-        for b in id.to_be_bytes().iter() {
-            datagram.push(*b); // Transaction ID
-        }
-        for b in FLAG_RD.to_be_bytes().iter() {
-            datagram.push(*b); // Flags
-        }
-        for b in (1 as u16).to_be_bytes().iter() {
-            datagram.push(*b); // Questions
-        }
-        for _ in 0..6 {
-            datagram.push(0); // Answer + Authority + Additional
-        }
-        for label in qname.split('.') {
-            datagram.push(label.len() as u8); // QNAME label length
-            for b in label.bytes() {
-                datagram.push(b); // QNAME label bytes
-            }
-        }
-        datagram.push(0); // Root null label
-        for b in (qtype as u16).to_be_bytes().iter() {
-            datagram.push(*b); // QTYPE
-        }
-        for b in (qclass as u16).to_be_bytes().iter() {
-            datagram.push(*b); // QCLASS
-        }
-
-        Self { datagram }
-    }
-
-    pub fn id(&self) -> u16 {
-        u16::from_be_bytes(self.datagram[0..2].try_into().unwrap())
-    }
-
-    pub fn header(&self) -> u16 {
-        u16::from_be_bytes(self.datagram[2..4].try_into().unwrap())
-    }
-
-    pub fn is_response(&self) -> bool {
-        if (self.header() & (1 << 15)) == 0 {
-            false
-        } else {
-            true
-        }
-    }
-
-    fn fast_foward_name(&self, start: usize) -> Result<usize, DnsResponseCode> {
-        use DnsResponseCode::FormatError;
-        let mut index = start;
-        loop {
-            log::trace!("cname index: {}", index);
-            if *(self.datagram.get(index).ok_or(FormatError)?) == 0 {
-                index += 1;
-                break;
-            } else {
-                index += *(self.datagram.get(index).ok_or(FormatError)?) as usize;
-                index += 1;
-            }
-        }
-        Ok(index)
-    }
-
-    pub fn parse_response(&self) -> Result<HashMap<IpAddr, u32>, DnsResponseCode> {
-        use DnsResponseCode::FormatError;
-        log::trace!("parsing packet: {:?}", self.datagram);
-
-        let mut map = HashMap::<IpAddr, u32>::new();
-        // ASSUME: the query ID and response bit fields have already been checked
-        // and that the rcode is valid
-        let qdcount = u16::from_be_bytes(self.datagram[4..6].try_into().unwrap());
-        let ancount = u16::from_be_bytes(self.datagram[6..8].try_into().unwrap());
-
-        let mut index = 12;
-        // fast forward past the qname
-        for queries in 0..qdcount {
-            log::trace!("parsing query{}, index {}", queries, index);
-            index = self.fast_foward_name(index)?;
-            log::trace!("fast forward through qname to {}", index);
-            // index is now at qtype
-            let qtype = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            // A = 1, AAAA = 28
-            if qtype != 1 && qtype != 28 {
-                log::error!("Problem parsing qname, qtype is not 1 or 28: {}", qtype);
-                return Err(FormatError);
-            }
-            index += 2;
-            let qclass = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            if qclass != 1 {
-                log::error!("Problem parsing qname, qclass is not 1: {}", qclass);
-                return Err(FormatError);
-            }
-            index += 2;
-        }
-        // index is now at the aname section
-        for aname in 0..ancount {
-            log::trace!("parsing aname{}, index {}", aname, index);
-            // first check to see if we're dealing with a pointer or a name
-            if self.datagram[index] >= 0xc0 {
-                // pointer
-                index += 1;
-                if self.datagram[index] != 0xc {
-                    log::error!(
-                        "Found aname pointer, but value does not conform to length of aname header"
-                    );
-                    return Err(FormatError);
-                }
-                index += 1;
-            } else {
-                // name, fast forward past the name
-                index = self.fast_foward_name(index)?;
-                log::trace!("fast forward aname to {}", index);
-            }
-            // index is now at type
-            let atype = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            // A = 1, AAAA = 28
-            if atype != 1 && atype != 28 {
-                log::error!("Problem parsing aname, type is not 1 or 28: {}", atype);
-                return Err(FormatError);
-            }
-            index += 2;
-            let aclass = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            if aclass != 1 {
-                log::error!("Problem parsing aname, aclass is not 1: {}", aclass);
-                return Err(FormatError);
-            }
-            index += 2;
-            // this is our TTL
-            let ttl = u32::from_be_bytes(self.datagram[index..index + 4].try_into().unwrap());
-            log::trace!("got ttl: {}", ttl);
-            index += 4;
-            // this is the payload length
-            let addr_len = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            index += 2;
-            match addr_len {
-                // ipv4
-                4 => {
-                    if atype != 1 {
-                        log::error!("Got a 4-byte address, but ATYPE != A (1)");
-                        return Err(FormatError);
-                    }
-                    // this copy happens because I can't figure out how to get Ipv4Addr::from() to realize it's casting from a [u8;4]
-                    let mut rdata: [u8; 4] = [0; 4];
-                    for (&src, dst) in self.datagram[index..index + 4].iter().zip(rdata.iter_mut())
-                    {
-                        *dst = src;
-                    }
-                    let addr = IpAddr::V4(Ipv4Addr::from(rdata));
-                    index += 4;
-                    map.insert(addr, ttl);
-                }
-                // ipv6
-                16 => {
-                    if atype != 28 {
-                        log::error!("Got a 16-byte address, but ATYPE != AAAA (28)");
-                        return Err(FormatError);
-                    }
-                    // this copy happens because I can't figure out how to get Ipv6Addr::from() to realize it's casting from a [u8;4]
-                    let mut rdata: [u8; 16] = [0; 16];
-                    for (&src, dst) in self.datagram[index..index + 16]
-                        .iter()
-                        .zip(rdata.iter_mut())
-                    {
-                        *dst = src;
-                    }
-                    let addr = IpAddr::V6(Ipv6Addr::from(rdata));
-                    index += 16;
-                    map.insert(addr, ttl);
-                }
-                _ => {
-                    log::error!("Length field does not match a known record type");
-                    return Err(FormatError);
-                }
-            }
-        }
-
-        Ok(map)
-    }
-
-    /*
-         example response for: betrusted.io->185.199.111.153
-    Header:
-          61, ca,   id
-          81, 80,   header
-          0, 1,     qdcount
-          0, 4,     ancount
-          0, 0,     nscount
-          0, 0,     arcount
-    qname:
-          9,        length 9
-          62, 65, 74, 72, 75, 73, 74, 65, 64,    "betrusted"
-          2,        length 2
-          69, 6f,   "io"
-          0,        end of name
-    qtype:
-          0, 1,     type A
-    qclass:
-          0, 1,     type IN
-    aname0:
-          c0,       name is a pointer (any value > 192 is a pointer)
-          c,        offset of 12 from start of aname0
-          0, 1,     type A
-          0, 1,     class IN
-          0, 0, e, 10,   0xe10 = 3600 seconds TTL
-          0, 4,     4 bytes address
-          b9, c7, 6c, 99,  address
-    aname1:
-          c0,       name is a pointer
-          c,
-          0, 1,     type A
-          0, 1,     class IN
-          0, 0, e, 10,  TTL
-          0, 4,     4 byte address
-          b9, c7, 6d, 99,  address
-    aname2:
-          c0,
-          c,
-          0, 1,
-          0, 1,
-          0, 0, e, 10,
-          0, 4,
-          b9, c7, 6e, 99,
-    aname3:
-          c0,
-          c,
-          0, 1,
-          0, 1,
-          0, 0, e, 10,
-          0, 4,
-          b9, c7, 6f, 99
-         */
-
-    /*
-    pub fn is_query(&self) -> bool {
-        !self.is_response()
-    }
-    */
-
-    pub fn rcode(&self) -> DnsResponseCode {
-        match (self.header() >> 11) & 0xF {
-            0 => DnsResponseCode::NoError,
-            1 => DnsResponseCode::FormatError,
-            2 => DnsResponseCode::ServerFailure,
-            3 => DnsResponseCode::NameError,
-            4 => DnsResponseCode::NotImplemented,
-            5 => DnsResponseCode::Refused,
-            _ => DnsResponseCode::UnknownError,
-        }
-    }
-}
+/// how long to wait for the first answer to a round of queries
+const QUERY_TIMEOUT_MS: u64 = 10_000;
+/// once one query of a round has been answered, how much longer to wait for the others. Also how long to wait
+/// when refreshing one address family while the other one is still cached.
+const FOLLOWUP_TIMEOUT_MS: u64 = 1_000;
 
 pub struct Resolver {
     /// DnsServerManager is a service of the Net crate that automatically updates the DNS server list
@@ -320,7 +39,7 @@ impl Resolver {
             format!("0.0.0.0:{}", local_port),
         )
         .expect("couldn't create socket for DNS resolver");
-        let timeout = Duration::from_millis(10_000); // 10 seconds for DNS to resolve by default
+        let timeout = Duration::from_millis(QUERY_TIMEOUT_MS); // 10 seconds for DNS to resolve by default
         socket.set_read_timeout(Some(timeout)).unwrap();
         socket.set_nonblocking(false).unwrap(); // we want this to block.
                                                 // we /could/ do a non-blocking DNS resolver, but...what would you do in the meantime??
@@ -355,39 +74,199 @@ impl Resolver {
     pub fn trng_u32(&self) -> u32 {
         self.trng.get_u32().unwrap()
     }
-    pub fn resolve(&mut self, name: &str) -> Result<HashMap<IpAddr, u32>, DnsResponseCode> {
-        if let Some(dns_address) = self.mgr.get_random() {
-            let dns_port = 53;
-            let server = SocketAddr::new(dns_address, dns_port);
-
-            let qname = name;
-            let qtype = QueryType::A;
-            let qclass = QueryClass::IN;
-            let query = Message::query(qname, qtype, qclass, self.trng.get_u32().unwrap() as u16);
-
+    /// Sends all the queries at once, and collects the responses in the same order. Waits up to `timeout`
+    /// for the first response, and then up to FOLLOWUP_TIMEOUT_MS for each of the rest, so that one dropped
+    /// reply doesn't hold up the ones that did arrive. Queries that aren't answered in time have no response.
+    fn exchange(
+        &mut self,
+        server: &SocketAddr,
+        queries: &[Message],
+        timeout: Duration,
+    ) -> Result<Vec<Option<Message>>, DnsResponseCode> {
+        for query in queries.iter() {
             self.socket
-                .send_to(&query.datagram, &server)
+                .send_to(&query.datagram, server)
                 .map_err(|_| DnsResponseCode::NetworkError)?;
-
-            match self.socket.recv(&mut self.buf) {
-                Ok(len) => {
+        }
+        self.socket.set_read_timeout(Some(timeout)).map_err(|_| DnsResponseCode::NetworkError)?;
+        let mut responses: Vec<Option<Message>> = queries.iter().map(|_| None).collect();
+        let mut outstanding = queries.len();
+        // stray packets (e.g. late responses to an earlier lookup) are skipped, within reason
+        let mut attempts = queries.len() * 2;
+        while outstanding > 0 && attempts > 0 {
+            attempts -= 1;
+            match self.socket.recv_from(&mut self.buf) {
+                Ok((len, src)) => {
+                    if src.ip() != server.ip() || len < 12 {
+                        continue;
+                    }
                     let message = Message::from(&self.buf[..len]);
-                    if message.id() == query.id() && message.is_response() {
-                        return match message.rcode() {
-                            DnsResponseCode::NoError => message.parse_response(),
-                            rcode => Err(rcode),
-                        };
-                    } else {
-                        Err(DnsResponseCode::NetworkError)
+                    if !message.is_response() {
+                        continue;
                     }
+                    if let Some(index) = queries.iter().position(|q| q.id() == message.id()) {
+                        if responses[index].is_none() {
+                            responses[index] = Some(message);
+                            if outstanding == queries.len() {
+                                // the first answer is in, don't hold it up for long waiting on the rest
+                                let followup = timeout.min(Duration::from_millis(FOLLOWUP_TIMEOUT_MS));
+                                self.socket.set_read_timeout(Some(followup)).ok();
+                            }
+                            outstanding -= 1;
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::debug!("DNS receive ended: {:?}", e);
+                    break;
                 }
-                Err(e) => match e.kind() {
-                    ErrorKind::WouldBlock => Err(DnsResponseCode::NetworkError),
-                    _ => Err(DnsResponseCode::UnknownError),
-                },
             }
+        }
+        if outstanding == queries.len() {
+            Err(DnsResponseCode::NetworkError)
         } else {
-            Err(DnsResponseCode::NoServerSpecified)
+            Ok(responses)
+        }
+    }
+    /// Queries `name` for records of each of `qtypes` (A and/or AAAA), chasing CNAMEs that the server didn't
+    /// resolve on its own. `name` should already be normalized; `timeout` is how long to wait for a first answer.
+    pub(crate) fn resolve(
+        &mut self,
+        name: &str,
+        qtypes: &[QueryType],
+        timeout: Duration,
+    ) -> Result<Resolution, DnsResponseCode> {
+        let dns_address = self.mgr.get_random().ok_or(DnsResponseCode::NoServerSpecified)?;
+        let dns_port = 53;
+        let server = SocketAddr::new(dns_address, dns_port);
+
+        let mut records = Vec::new();
+        // queries still to be made: the name, the type, and the TTL limit from the CNAMEs that led to the name
+        let mut pending: Vec<(std::string::String, QueryType, Option<u32>)> =
+            qtypes.iter().map(|&qtype| (name.to_string(), qtype, None)).collect();
+        for _ in 0..MAX_CNAME_CHAIN {
+            if pending.is_empty() {
+                break;
+            }
+            let queries: Vec<Message> = pending
+                .iter()
+                .map(|(qname, qtype, _)| {
+                    Message::query(qname, *qtype, QueryClass::IN, self.trng.get_u32().unwrap() as u16)
+                })
+                .collect();
+            let responses = self.exchange(&server, &queries, timeout)?;
+            let mut next = Vec::new();
+            for ((qname, qtype, chain_ttl), response) in pending.into_iter().zip(responses.into_iter()) {
+                let response = match response {
+                    Some(response) => response,
+                    None => {
+                        log::debug!("no response for {} {:?}", qname, qtype);
+                        continue;
+                    }
+                };
+                match response.rcode() {
+                    DnsResponseCode::NoError => {
+                        let answer = response.parse_response(&qname, qtype)?;
+                        if let Some(cname) = answer.cname {
+                            // the server only gave us the alias; ask for the canonical name
+                            next.push((cname, qtype, min_ttl(chain_ttl, answer.ttl)));
+                        } else if answer.addrs.is_empty() {
+                            records.push((qtype, answer.addrs, min_ttl(chain_ttl, response.negative_ttl())));
+                        } else {
+                            records.push((qtype, answer.addrs, min_ttl(chain_ttl, answer.ttl)));
+                        }
+                    }
+                    DnsResponseCode::NameError if qname == name => {
+                        return Ok(Resolution::NxDomain(response.negative_ttl()));
+                    }
+                    DnsResponseCode::NameError => {
+                        // the target of a CNAME doesn't exist, so the alias has no addresses
+                        records.push((qtype, Vec::new(), min_ttl(chain_ttl, response.negative_ttl())));
+                    }
+                    rcode => return Err(rcode),
+                }
+            }
+            pending = next;
+        }
+        if !pending.is_empty() {
+            log::warn!("CNAME chain for {} is too long, giving up", name);
+        }
+        if records.is_empty() {
+            Err(DnsResponseCode::NetworkError)
+        } else {
+            Ok(Resolution::Records(records))
+        }
+    }
+}
+
+/// The outcome of resolving a name
+pub(crate) enum Resolution {
+    /// The addresses found for each query type, and how long they can be cached for.
+    /// A query type is missing if its query went unanswered.
+    Records(Vec<(QueryType, Vec<IpAddr>, Option<u32>)>),
+    /// The name does not exist; the TTL is how long that can be cached for
+    NxDomain(Option<u32>),
+}
+
+fn min_ttl(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Looks `name` up in the cache, and failing that, resolves it and caches the outcome.
+/// IPv4 addresses come first in the result.
+fn lookup(
+    resolver: &mut Resolver,
+    dns_cache: &mut DnsCache,
+    tt: &ticktimer_server::Ticktimer,
+    name: &str,
+) -> Result<Vec<IpAddr>, DnsResponseCode> {
+    let name = normalize_name(name);
+    if name.is_empty() {
+        return Err(DnsResponseCode::FormatError);
+    }
+    let (cached, qtypes, timeout) = match dns_cache.lookup(&name, tt.elapsed_ms()) {
+        CacheLookup::Hit(addrs) => {
+            log::debug!("DNS cached: {}->{:?}", name, addrs);
+            return Ok(addrs);
+        }
+        CacheLookup::Negative(code) => {
+            log::debug!("DNS cached negative: {}", name);
+            return Err(code);
+        }
+        // one address family expired before the other: only ask for that one again, and don't wait long for it,
+        // as the cached addresses will do if it doesn't come
+        CacheLookup::Partial(addrs, missing) => (addrs, vec![missing], FOLLOWUP_TIMEOUT_MS),
+        CacheLookup::Miss => (Vec::new(), vec![QueryType::A, QueryType::AAAA], QUERY_TIMEOUT_MS),
+    };
+    let resolution = match resolver.resolve(&name, &qtypes, Duration::from_millis(timeout)) {
+        Ok(resolution) => resolution,
+        Err(code) if !cached.is_empty() => {
+            log::debug!("DNS refresh of {} failed ({:?}), using cached: {:?}", name, code, cached);
+            return Ok(cached);
+        }
+        Err(code) => return Err(code),
+    };
+    match resolution {
+        Resolution::Records(records) => {
+            let now = tt.elapsed_ms();
+            let mut addrs = cached;
+            for (qtype, found, ttl) in records {
+                addrs.extend(found.iter().copied());
+                dns_cache.insert(&name, qtype, found, ttl, now);
+            }
+            addrs.sort_by_key(|addr| addr.is_ipv6());
+            if addrs.is_empty() {
+                Err(DnsResponseCode::NameError)
+            } else {
+                Ok(addrs)
+            }
+        }
+        Resolution::NxDomain(ttl) => {
+            dns_cache.insert_nxdomain(&name, ttl, tt.elapsed_ms());
+            Err(DnsResponseCode::NameError)
         }
     }
 }
@@ -423,7 +302,7 @@ fn name_from_msg(env: &xous::MessageEnvelope) -> Result<&str, NameConversionErro
     Ok(name_string)
 }
 
-fn fill_response(mut env: xous::MessageEnvelope, entries: &HashMap<IpAddr, u32>) -> Option<()> {
+fn fill_response(mut env: xous::MessageEnvelope, entries: &[IpAddr]) -> Option<()> {
     let mem = env.body.memory_message_mut()?;
 
     let s: &mut [u8] = mem.buf.as_slice_mut();
@@ -442,7 +321,7 @@ fn fill_response(mut env: xous::MessageEnvelope, entries: &HashMap<IpAddr, u32>)
     *i.next()? = entry_count.try_into().ok()?;
 
     // Start filling in the addreses
-    for addr in entries.keys() {
+    for addr in entries.iter().take(entry_count) {
         match addr {
             &IpAddr::V4(a) => {
                 // IPv4
@@ -453,10 +332,10 @@ fn fill_response(mut env: xous::MessageEnvelope, entries: &HashMap<IpAddr, u32>)
             }
             &IpAddr::V6(a) => {
                 // IPv6
+                *i.next()? = 6;
                 for entry in a.octets() {
                     *i.next()? = entry;
                 }
-                *i.next()? = 6;
             }
         }
     }
@@ -495,9 +374,11 @@ fn main() -> ! {
     // if you wanted to force a server into the initial config, you can do it here, for example:
     // resolver.add_server(IpAddr::V4(Ipv4Addr::new(1,1,1,1)));
 
-    let mut dns_cache = HashMap::<std::string::String, HashMap<IpAddr, u32>>::new();
+    let mut dns_cache = DnsCache::new();
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
 
-    // build a thread that pings the UpdateTtl function once every few minutes to expire the DNS cache
+    // build a thread that pings the UpdateTtl function once every few minutes to expire the DNS cache.
+    // Lookups skip over expired entries anyways, this is just so they don't pile up.
     thread::spawn({
         let local_cid = xous::connect(dns_sid).unwrap();
         move || {
@@ -515,7 +396,7 @@ fn main() -> ! {
                         0,
                     ),
                 )
-                .expect("couldn't increment DNS cache");
+                .expect("couldn't expire DNS cache");
             }
         }
     });
@@ -528,17 +409,9 @@ fn main() -> ! {
                 match name_from_msg(&msg).map(|s| s.to_owned()) {
                     Ok(owned_name) => {
                         log::trace!("performing a lookup of {}", owned_name);
-                        // Try to get the result out of the DNS cache
-                        if let Some(entries) = dns_cache.get(&owned_name) {
-                            fill_response(msg, entries);
-                            continue;
-                        }
-
-                        // This entry is not in the cache, so perform a lookup
-                        match resolver.resolve(&owned_name) {
-                            Ok(cache_entry) => {
-                                fill_response(msg, &cache_entry);
-                                dns_cache.insert(owned_name, cache_entry);
+                        match lookup(&mut resolver, &mut dns_cache, &tt, &owned_name) {
+                            Ok(addrs) => {
+                                fill_response(msg, &addrs);
                                 continue;
                             }
                             Err(e) => {
@@ -561,110 +434,62 @@ fn main() -> ! {
                 let name = buf
                     .to_original::<String<DNS_NAME_LENGTH_LIMIT>, _>()
                     .unwrap();
-                let name_std = std::string::String::from(name.as_str().unwrap());
-                if let Some(cache_entry) = dns_cache.get(&name_std) {
-                    // pick a random entry
-                    let rand = resolver.trng_u32() as usize % cache_entry.len();
-                    for (index, (ip_addr, _)) in cache_entry.iter().enumerate() {
-                        if rand == index {
-                            log::debug!("DNS cached: {}->{:?}", name, ip_addr);
-                            let response = DnsResponse {
-                                addr: Some(NetIpAddr::from(*ip_addr)),
-                                code: DnsResponseCode::NoError,
-                            };
-                            buf.replace(response).unwrap();
-                            break;
+                let response = match lookup(&mut resolver, &mut dns_cache, &tt, name.as_str().unwrap()) {
+                    Ok(addrs) => {
+                        // prefer IPv4, as not every network we end up on routes IPv6.
+                        // pick a random entry of the preferred family.
+                        let ipv4_count = addrs.iter().filter(|addr| addr.is_ipv4()).count();
+                        let candidates = if ipv4_count > 0 { ipv4_count } else { addrs.len() };
+                        let ip_addr = addrs[resolver.trng_u32() as usize % candidates];
+                        log::debug!("DNS resolved: {}->{:?}", name, ip_addr);
+                        DnsResponse {
+                            addr: Some(NetIpAddr::from(ip_addr)),
+                            code: DnsResponseCode::NoError,
                         }
                     }
-                } else {
-                    match resolver.resolve(name.as_str().unwrap()) {
-                        Ok(cache_entry) => {
-                            if cache_entry.len() > 0 {
-                                dns_cache.insert(name_std, cache_entry);
-
-                                // now pick the entry back out again, as it was consumed...
-                                let name_std = std::string::String::from(name.as_str().unwrap());
-                                let cache_entry = dns_cache.get(&name_std).unwrap();
-
-                                // pick a random entry from the query response
-                                let rand = resolver.trng_u32() as usize % cache_entry.len();
-                                for (index, (ip_addr, _)) in cache_entry.iter().enumerate() {
-                                    if rand == index {
-                                        let response = DnsResponse {
-                                            addr: Some(NetIpAddr::from(*ip_addr)),
-                                            code: DnsResponseCode::NoError,
-                                        };
-                                        buf.replace(response).unwrap();
-                                        break;
-                                    }
-                                }
-                            } else {
-                                // no names found
-                                let response = DnsResponse {
-                                    addr: None,
-                                    code: DnsResponseCode::NameError,
-                                };
-                                buf.replace(response).unwrap();
-                            }
-                        }
-                        Err(e) => {
-                            log::debug!("DNS query failed: {}->{:?}", name, e);
-                            let response = DnsResponse {
-                                addr: None,
-                                code: e,
-                            };
-                            buf.replace(response).unwrap();
+                    Err(e) => {
+                        log::debug!("DNS query failed: {}->{:?}", name, e);
+                        DnsResponse {
+                            addr: None,
+                            code: e,
                         }
                     }
-                }
+                };
+                buf.replace(response).unwrap();
             }
-            Some(Opcode::UpdateTtl) => msg_scalar_unpack!(msg, incr_secs, _, _, _, {
-                let increment = if incr_secs < u32::MAX as usize {
-                    incr_secs as u32
-                } else {
-                    u32::MAX
+            Some(Opcode::UpdateTtl) => {
+                dns_cache.purge(tt.elapsed_ms());
+                log::debug!("DNS cache has {} entries", dns_cache.len());
+            }
+            Some(Opcode::CacheEntries) => {
+                let mut buf = unsafe {
+                    Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
                 };
-                if !resolver.get_freeze() {
-                    let mut expired_names = Vec::<std::string::String>::new();
-                    for (name, cache_map) in dns_cache.iter_mut() {
-                        // each entry can have multiple names with a different TTL
-                        // decrement the TTL, and note which go to zero
-                        let mut expired_entries = Vec::<IpAddr>::new();
-                        for (entry, ttl) in cache_map.iter_mut() {
-                            log::debug!("entry: {:?}, ttl: {}, incr: {}", entry, ttl, increment);
-                            if *ttl < increment {
-                                *ttl = 0;
-                                expired_entries.push(*entry);
-                            } else {
-                                *ttl = *ttl - increment as u32;
-                            }
-                        }
-                        // remove the entries that are 0
-                        for entry in expired_entries {
-                            log::debug!("DNS cache expiring {:?}", entry);
-                            cache_map.remove(&entry);
-                        }
-                        // if all the entries are removed, mark for removal from the cache entirely
-                        if cache_map.len() == 0 {
-                            // have to copy the name to a new object to track it
-                            let name = std::string::String::from(name.as_str());
-                            expired_names.push(name);
-                        }
-                    }
-                    for name in expired_names {
-                        log::debug!("DNS cache removing {}", &name);
-                        dns_cache.remove(&name);
-                    }
+                let mut page = buf.to_original::<DnsCachePage, _>().unwrap();
+                let info = dns_cache.info(tt.elapsed_ms());
+                page.total = info.len() as u32;
+                let mut entries = info.iter().skip(page.start as usize);
+                for record in page.records.iter_mut() {
+                    *record = entries.next().map(|entry| DnsCacheRecord {
+                        name: String::<DNS_NAME_LENGTH_LIMIT>::from_str(&entry.name),
+                        addr: entry.addrs.first().map(|addr| NetIpAddr::from(*addr)),
+                        addr_count: entry.addrs.len() as u32,
+                        ttl: entry.ttl_secs,
+                        negative: entry.negative,
+                    });
                 }
-            }),
+                buf.replace(page).unwrap();
+            }
             Some(Opcode::Flush) => {
                 dns_cache.clear();
             }
             Some(Opcode::FreezeConfig) => {
                 resolver.set_freeze_config(true);
+                dns_cache.set_frozen(resolver.get_freeze());
             }
             Some(Opcode::ThawConfig) => {
                 resolver.set_freeze_config(false);
+                dns_cache.set_frozen(resolver.get_freeze());
             }
             Some(Opcode::Quit) => {
                 log::warn!("got quit!");
@@ -0,0 +1,513 @@
+use crate::api::DnsResponseCode;
+
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// KISS DNS
+
+// The DNS implementation here is based on https://github.com/vinc/moros/blob/43ac7cdc8ccc860dc1b6f0f060b5dbcd01424c03/src/usr/host.rs
+// MOROS is MIT licensed.
+// See RFC 1035 for implementation details
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u16)]
+pub(crate) enum QueryType {
+    A = 1,
+    // NS = 2,
+    // MD = 3,
+    // MF = 4,
+    CNAME = 5,
+    SOA = 6,
+    // MX = 15,
+    // TXT = 16,
+    AAAA = 28,
+}
+
+#[repr(u16)]
+pub(crate) enum QueryClass {
+    IN = 1,
+}
+
+/// A CNAME chain longer than this is treated as a loop
+pub(crate) const MAX_CNAME_CHAIN: usize = 8;
+/// Bound on the number of labels (and compression pointers) followed when decoding a single name
+const MAX_NAME_LABELS: usize = 128;
+
+/// The addresses a response holds for a query, after following any CNAME records in it
+#[derive(Debug, PartialEq)]
+pub(crate) struct Answer {
+    pub addrs: Vec<IpAddr>,
+    /// The smallest TTL of the address records, and of the CNAME records that led to them.
+    /// `None` if there were no records to take a TTL from.
+    pub ttl: Option<u32>,
+    /// If the CNAME chain ends on a name that has no records in this response, this is that name,
+    /// and it has to be queried separately.
+    pub cname: Option<String>,
+}
+
+/// A resource record, with its name decoded but the data left in place
+struct Record {
+    name: String,
+    rtype: u16,
+    class: u16,
+    ttl: u32,
+    /// offset and length of the RDATA in the datagram
+    rdata: (usize, usize),
+}
+
+pub(crate) struct Message {
+    pub datagram: Vec<u8>,
+}
+
+const FLAG_RD: u16 = 0x0100; // Recursion desired
+
+/// Names are compared case-insensitively, and without the trailing root label
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+impl Message {
+    pub fn from(datagram: &[u8]) -> Self {
+        Self {
+            datagram: Vec::from(datagram),
+        }
+    }
+
+    pub fn query(qname: &str, qtype: QueryType, qclass: QueryClass, id: u16) -> Self {
+        let mut datagram = Vec::new();
+
+        for b in id.to_be_bytes().iter() {
+            datagram.push(*b); // Transaction ID
+        }
+        for b in FLAG_RD.to_be_bytes().iter() {
+            datagram.push(*b); // Flags
+        }
+        for b in (1 as u16).to_be_bytes().iter() {
+            datagram.push(*b); // Questions
+        }
+        for _ in 0..6 {
+            datagram.push(0); // Answer + Authority + Additional
+        }
+        for label in qname.trim_end_matches('.').split('.') {
+            datagram.push(label.len() as u8); // QNAME label length
+            for b in label.bytes() {
+                datagram.push(b); // QNAME label bytes
+            }
+        }
+        datagram.push(0); // Root null label
+        for b in (qtype as u16).to_be_bytes().iter() {
+            datagram.push(*b); // QTYPE
+        }
+        for b in (qclass as u16).to_be_bytes().iter() {
+            datagram.push(*b); // QCLASS
+        }
+
+        Self { datagram }
+    }
+
+    pub fn id(&self) -> u16 {
+        u16::from_be_bytes(self.datagram[0..2].try_into().unwrap())
+    }
+
+    pub fn header(&self) -> u16 {
+        u16::from_be_bytes(self.datagram[2..4].try_into().unwrap())
+    }
+
+    pub fn is_response(&self) -> bool {
+        if (self.header() & (1 << 15)) == 0 {
+            false
+        } else {
+            true
+        }
+    }
+
+    fn read_u16(&self, index: usize) -> Result<u16, DnsResponseCode> {
+        self.datagram
+            .get(index..index + 2)
+            .map(|b| u16::from_be_bytes(b.try_into().unwrap()))
+            .ok_or(DnsResponseCode::FormatError)
+    }
+
+    fn read_u32(&self, index: usize) -> Result<u32, DnsResponseCode> {
+        self.datagram
+            .get(index..index + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .ok_or(DnsResponseCode::FormatError)
+    }
+
+    /// Decodes the name at `start`, following compression pointers. Returns the name, and the index
+    /// just past the name as it sits at `start`.
+    fn read_name(&self, start: usize) -> Result<(String, usize), DnsResponseCode> {
+        use DnsResponseCode::FormatError;
+        let mut name = String::new();
+        let mut index = start;
+        // where parsing continues once the name is done; set by the first pointer encountered
+        let mut end: Option<usize> = None;
+        for _ in 0..MAX_NAME_LABELS {
+            let len = *self.datagram.get(index).ok_or(FormatError)? as usize;
+            if len == 0 {
+                return Ok((name, end.unwrap_or(index + 1)));
+            } else if len >= 0xc0 {
+                // pointer: the remaining 14 bits are an offset from the start of the message
+                let offset = (self.read_u16(index)? & 0x3fff) as usize;
+                if end.is_none() {
+                    end = Some(index + 2);
+                }
+                index = offset;
+            } else if len >= 0x40 {
+                log::error!("Unsupported label type: {:x}", len);
+                return Err(FormatError);
+            } else {
+                let label = self.datagram.get(index + 1..index + 1 + len).ok_or(FormatError)?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                for &b in label {
+                    name.push(b.to_ascii_lowercase() as char);
+                }
+                index += 1 + len;
+            }
+        }
+        log::error!("Name at {} has too many labels, or a pointer loop", start);
+        Err(FormatError)
+    }
+
+    fn read_record(&self, start: usize) -> Result<(Record, usize), DnsResponseCode> {
+        let (name, mut index) = self.read_name(start)?;
+        let rtype = self.read_u16(index)?;
+        let class = self.read_u16(index + 2)?;
+        let ttl = self.read_u32(index + 4)?;
+        let rdlength = self.read_u16(index + 8)? as usize;
+        index += 10;
+        if index + rdlength > self.datagram.len() {
+            log::error!("Record at {} runs past the end of the packet", start);
+            return Err(DnsResponseCode::FormatError);
+        }
+        Ok((
+            Record {
+                name,
+                rtype,
+                class,
+                ttl,
+                rdata: (index, rdlength),
+            },
+            index + rdlength,
+        ))
+    }
+
+    /// Skips the question section, returning the index of the first answer record
+    fn skip_questions(&self) -> Result<usize, DnsResponseCode> {
+        let qdcount = self.read_u16(4)?;
+        let mut index = 12;
+        for _ in 0..qdcount {
+            let (_qname, next) = self.read_name(index)?;
+            let qclass = self.read_u16(next + 2)?;
+            if qclass != QueryClass::IN as u16 {
+                log::error!("Problem parsing qname, qclass is not 1: {}", qclass);
+                return Err(DnsResponseCode::FormatError);
+            }
+            index = next + 4;
+        }
+        Ok(index)
+    }
+
+    /// Returns the answer records, followed by the authority records
+    fn records(&self) -> Result<(Vec<Record>, Vec<Record>), DnsResponseCode> {
+        let ancount = self.read_u16(6)?;
+        let nscount = self.read_u16(8)?;
+        let mut index = self.skip_questions()?;
+        let mut answers = Vec::new();
+        for _ in 0..ancount {
+            let (record, next) = self.read_record(index)?;
+            answers.push(record);
+            index = next;
+        }
+        let mut authority = Vec::new();
+        for _ in 0..nscount {
+            let (record, next) = self.read_record(index)?;
+            authority.push(record);
+            index = next;
+        }
+        Ok((answers, authority))
+    }
+
+    /// Extracts the addresses of type `qtype` for `qname`, following the CNAME chain in the answer section.
+    /// Records that are not part of the chain, or that are of other types (e.g. RRSIG), are ignored.
+    pub fn parse_response(&self, qname: &str, qtype: QueryType) -> Result<Answer, DnsResponseCode> {
+        use DnsResponseCode::FormatError;
+        log::trace!("parsing packet: {:?}", self.datagram);
+        // ASSUME: the query ID and response bit fields have already been checked
+        // and that the rcode is valid
+        let (answers, _) = self.records()?;
+
+        let mut target = normalize_name(qname);
+        let mut ttl: Option<u32> = None;
+        for _ in 0..=MAX_CNAME_CHAIN {
+            let mut addrs = Vec::new();
+            for record in answers
+                .iter()
+                .filter(|r| r.class == QueryClass::IN as u16 && r.name == target && r.rtype == qtype as u16)
+            {
+                let (start, len) = record.rdata;
+                let rdata = &self.datagram[start..start + len];
+                let addr = match (qtype, len) {
+                    (QueryType::A, 4) => {
+                        let octets: [u8; 4] = rdata.try_into().unwrap();
+                        IpAddr::V4(Ipv4Addr::from(octets))
+                    }
+                    (QueryType::AAAA, 16) => {
+                        let octets: [u8; 16] = rdata.try_into().unwrap();
+                        IpAddr::V6(Ipv6Addr::from(octets))
+                    }
+                    _ => {
+                        log::error!("Length field {} does not match record type {:?}", len, qtype);
+                        return Err(FormatError);
+                    }
+                };
+                ttl = Some(ttl.map_or(record.ttl, |t| t.min(record.ttl)));
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+            if !addrs.is_empty() {
+                return Ok(Answer { addrs, ttl, cname: None });
+            }
+            // no addresses for this name: see if it's an alias
+            match answers.iter().find(|r| {
+                r.class == QueryClass::IN as u16 && r.name == target && r.rtype == QueryType::CNAME as u16
+            }) {
+                Some(record) => {
+                    let (canonical, _) = self.read_name(record.rdata.0)?;
+                    log::debug!("following CNAME {} -> {}", target, canonical);
+                    ttl = Some(ttl.map_or(record.ttl, |t| t.min(record.ttl)));
+                    target = canonical;
+                }
+                None => {
+                    let cname = if target != normalize_name(qname) { Some(target) } else { None };
+                    return Ok(Answer { addrs, ttl, cname });
+                }
+            }
+        }
+        log::error!("CNAME chain for {} is too long", qname);
+        Err(FormatError)
+    }
+
+    /// How long a negative response (NXDOMAIN, or no records of the requested type) may be cached for.
+    /// Per RFC 2308, this is the lesser of the TTL of the SOA record in the authority section and its
+    /// MINIMUM field. `None` if the response carries no SOA record.
+    pub fn negative_ttl(&self) -> Option<u32> {
+        let (_, authority) = self.records().ok()?;
+        let soa = authority.iter().find(|r| r.rtype == QueryType::SOA as u16)?;
+        let (start, len) = soa.rdata;
+        // MNAME and RNAME, followed by five 32-bit fields, the last of which is MINIMUM
+        let (_mname, index) = self.read_name(start).ok()?;
+        let (_rname, index) = self.read_name(index).ok()?;
+        if index + 20 > start + len {
+            return None;
+        }
+        let minimum = self.read_u32(index + 16).ok()?;
+        Some(soa.ttl.min(minimum))
+    }
+
+    /*
+         example response for: betrusted.io->185.199.111.153
+    Header:
+          61, ca,   id
+          81, 80,   header
+          0, 1,     qdcount
+          0, 4,     ancount
+          0, 0,     nscount
+          0, 0,     arcount
+    qname:
+          9,        length 9
+          62, 65, 74, 72, 75, 73, 74, 65, 64,    "betrusted"
+          2,        length 2
+          69, 6f,   "io"
+          0,        end of name
+    qtype:
+          0, 1,     type A
+    qclass:
+          0, 1,     type IN
+    aname0:
+          c0,       name is a pointer (any value > 192 is a pointer)
+          c,        offset of 12 from start of aname0
+          0, 1,     type A
+          0, 1,     class IN
+          0, 0, e, 10,   0xe10 = 3600 seconds TTL
+          0, 4,     4 bytes address
+          b9, c7, 6c, 99,  address
+    aname1:
+          c0,       name is a pointer
+          c,
+          0, 1,     type A
+          0, 1,     class IN
+          0, 0, e, 10,  TTL
+          0, 4,     4 byte address
+          b9, c7, 6d, 99,  address
+    aname2:
+          c0,
+          c,
+          0, 1,
+          0, 1,
+          0, 0, e, 10,
+          0, 4,
+          b9, c7, 6e, 99,
+    aname3:
+          c0,
+          c,
+          0, 1,
+          0, 1,
+          0, 0, e, 10,
+          0, 4,
+          b9, c7, 6f, 99
+         */
+
+    /*
+    pub fn is_query(&self) -> bool {
+        !self.is_response()
+    }
+    */
+
+    pub fn rcode(&self) -> DnsResponseCode {
+        match self.header() & 0xF {
+            0 => DnsResponseCode::NoError,
+            1 => DnsResponseCode::FormatError,
+            2 => DnsResponseCode::ServerFailure,
+            3 => DnsResponseCode::NameError,
+            4 => DnsResponseCode::NotImplemented,
+            5 => DnsResponseCode::Refused,
+            _ => DnsResponseCode::UnknownError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The response from the comment above, for betrusted.io
+    const BETRUSTED_A: &[u8] = &[
+        0x61, 0xca, 0x81, 0x80, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+        0x09, 0x62, 0x65, 0x74, 0x72, 0x75, 0x73, 0x74, 0x65, 0x64, 0x02, 0x69, 0x6f, 0x00,
+        0x00, 0x01, 0x00, 0x01,
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, 0xb9, 0xc7, 0x6c, 0x99,
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, 0xb9, 0xc7, 0x6d, 0x99,
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, 0xb9, 0xc7, 0x6e, 0x99,
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, 0xb9, 0xc7, 0x6f, 0x99,
+    ];
+
+    /// www.example.com AAAA: www.example.com CNAME web.example.com (ttl 300),
+    /// web.example.com AAAA 2001:db8::1 (ttl 60), plus an unrelated RRSIG-type record
+    const CNAME_AAAA: &[u8] = &[
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00,
+        // question: www.example.com AAAA IN
+        0x03, b'w', b'w', b'w', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o', b'm', 0x00,
+        0x00, 0x1c, 0x00, 0x01,
+        // answer 0 (offset 33): www.example.com CNAME web.example.com
+        0xc0, 0x0c, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x01, 0x2c, 0x00, 0x06,
+        0x03, b'w', b'e', b'b', 0xc0, 0x10,
+        // answer 1 (offset 51): RRSIG (type 46) on www, which must be skipped
+        0xc0, 0x0c, 0x00, 0x2e, 0x00, 0x01, 0x00, 0x00, 0x01, 0x2c, 0x00, 0x02, 0xaa, 0xbb,
+        // answer 2: web.example.com AAAA 2001:db8::1, the name is a pointer into answer 0's rdata
+        0xc0, 0x2d, 0x00, 0x1c, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x10,
+        0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01,
+    ];
+
+    /// nope.example.com A, NXDOMAIN with an SOA in the authority section: ttl 900, minimum 300
+    const NXDOMAIN: &[u8] = &[
+        0xab, 0xcd, 0x81, 0x83, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x04, b'n', b'o', b'p', b'e', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o', b'm', 0x00,
+        0x00, 0x01, 0x00, 0x01,
+        // authority: example.com SOA
+        0xc0, 0x11, 0x00, 0x06, 0x00, 0x01, 0x00, 0x00, 0x03, 0x84, 0x00, 0x1a,
+        0x02, b'n', b's', 0xc0, 0x11, // mname ns.example.com
+        0x00, // rname: root, to keep it short
+        0x00, 0x00, 0x00, 0x01, // serial
+        0x00, 0x00, 0x0e, 0x10, // refresh
+        0x00, 0x00, 0x02, 0x58, // retry
+        0x00, 0x09, 0x3a, 0x80, // expire
+        0x00, 0x00, 0x01, 0x2c, // minimum: 300
+    ];
+
+    #[test]
+    fn test_query() {
+        let query = Message::query("betrusted.io.", QueryType::AAAA, QueryClass::IN, 0x61ca);
+        assert_eq!(
+            query.datagram,
+            vec![
+                0x61, 0xca, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0,
+                0x09, b'b', b'e', b't', b'r', b'u', b's', b't', b'e', b'd', 0x02, b'i', b'o', 0x00,
+                0x00, 0x1c, 0x00, 0x01,
+            ]
+        );
+        assert_eq!(query.id(), 0x61ca);
+        assert!(!query.is_response());
+    }
+
+    #[test]
+    fn test_parse_a() {
+        let msg = Message::from(BETRUSTED_A);
+        assert!(msg.is_response());
+        assert!(matches!(msg.rcode(), DnsResponseCode::NoError));
+        let answer = msg.parse_response("Betrusted.IO", QueryType::A).unwrap();
+        assert_eq!(answer.ttl, Some(3600));
+        assert_eq!(answer.cname, None);
+        assert_eq!(answer.addrs.len(), 4);
+        assert_eq!(answer.addrs[0], IpAddr::V4(Ipv4Addr::new(185, 199, 108, 153)));
+        assert_eq!(answer.addrs[3], IpAddr::V4(Ipv4Addr::new(185, 199, 111, 153)));
+        // the same response has nothing for an AAAA query
+        let answer = msg.parse_response("betrusted.io", QueryType::AAAA).unwrap();
+        assert!(answer.addrs.is_empty());
+        assert_eq!(answer.ttl, None);
+        assert_eq!(msg.negative_ttl(), None);
+    }
+
+    #[test]
+    fn test_parse_cname() {
+        let msg = Message::from(CNAME_AAAA);
+        let answer = msg.parse_response("www.example.com", QueryType::AAAA).unwrap();
+        assert_eq!(answer.addrs, vec![IpAddr::V6("2001:db8::1".parse().unwrap())]);
+        // limited by the AAAA record
+        assert_eq!(answer.ttl, Some(60));
+        assert_eq!(answer.cname, None);
+
+        // an A query answered with only the CNAME has to be chased with another query
+        let answer = msg.parse_response("www.example.com", QueryType::A).unwrap();
+        assert!(answer.addrs.is_empty());
+        assert_eq!(answer.ttl, Some(300));
+        assert_eq!(answer.cname, Some("web.example.com".to_string()));
+    }
+
+    #[test]
+    fn test_cname_loop() {
+        let mut pkt = CNAME_AAAA.to_vec();
+        // point web.example.com's CNAME target back at www: turn the AAAA record into a CNAME to www
+        let aaaa = 65;
+        pkt.truncate(aaaa);
+        pkt.extend_from_slice(&[0xc0, 0x2d, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x02, 0xc0, 0x0c]);
+        let msg = Message::from(&pkt);
+        assert!(matches!(msg.parse_response("www.example.com", QueryType::AAAA), Err(DnsResponseCode::FormatError)));
+    }
+
+    #[test]
+    fn test_nxdomain() {
+        let msg = Message::from(NXDOMAIN);
+        assert!(matches!(msg.rcode(), DnsResponseCode::NameError));
+        assert_eq!(msg.negative_ttl(), Some(300));
+        let answer = msg.parse_response("nope.example.com", QueryType::A).unwrap();
+        assert!(answer.addrs.is_empty());
+    }
+
+    #[test]
+    fn test_malformed() {
+        // truncated in the middle of an answer
+        let msg = Message::from(&BETRUSTED_A[..40]);
+        assert!(matches!(msg.parse_response("betrusted.io", QueryType::A), Err(DnsResponseCode::FormatError)));
+        // a name that points at itself
+        let mut pkt = BETRUSTED_A.to_vec();
+        pkt[30] = 0xc0;
+        pkt[31] = 30;
+        let msg = Message::from(&pkt);
+        assert!(matches!(msg.parse_response("betrusted.io", QueryType::A), Err(DnsResponseCode::FormatError)));
+    }
+}
@@ -78,12 +78,25 @@ pub struct SwitchToApp {
     pub app_name: String::<128>,
 }
 
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct SplitRequest {
+    /// app token of the App that the pane is docked to
+    pub token: [u32; 4],
+    /// name of the pane to dock; `None` undocks the current pane
+    pub pane_name: Option<String::<128>>,
+    pub result: Option<ActivationResult>,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub enum UxType {
     Chat,
     Menu,
     Modal,
     Framebuffer,
+    /// a persistent strip under the status bar; only one can be registered, and it never gets keyboard input
+    Widget,
+    /// a secondary content area that an App can dock above itself with `Gam::set_split()`
+    Pane,
 }
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub struct UxRegistration {
@@ -205,6 +218,9 @@ pub(crate) enum Opcode {
     Bip39toBytes = 30,
     BytestoBip39 = 31,
     Bip39Suggestions = 32,
+
+    /// docks a pane above the calling App, or undocks it
+    SetSplit = 33,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -278,6 +294,8 @@ pub enum CanvasType {
     Modal,
     Menu,
     Status,
+    Widget,
+    PaneContent,
 }
 impl CanvasType {
     pub fn is_content(&self) -> bool {
@@ -286,7 +304,9 @@ impl CanvasType {
             CanvasType::Framebuffer |
             CanvasType::Modal |
             CanvasType::Menu |
-            CanvasType::Status => true,
+            CanvasType::Status |
+            CanvasType::Widget |
+            CanvasType::PaneContent => true,
             _ => false,
         }
     }
@@ -14,12 +14,16 @@ use enum_dispatch::enum_dispatch;
 
 pub (crate) const MISC_CONTEXT_DEFAULT_TRUST: u8 = 127;
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub(crate) enum LayoutBehavior {
     /// a layout that can render over others, takes focus, and only dismissed if explicitly dismissed
     Alert,
     /// a layout that assumes it has the full screen and is the primary content when visible
     App,
+    /// a strip under the status bar that stays on screen no matter which App has focus; it never takes focus
+    Widget,
+    /// a secondary content area that is only shown docked above the App that asked for it
+    Pane,
 }
 
 #[enum_dispatch]
@@ -36,6 +40,11 @@ pub(crate) trait LayoutApi {
     // note that this visibility state is an independent variable from the trust level draw-ability
     fn set_visibility_state(&mut self, onscreen: bool, canvases: &mut HashMap<Gid, Canvas>);
     fn behavior(&self) -> LayoutBehavior;
+    // moves the layout's canvases so they start below `header`, the region claimed by the status bar, the widget strip
+    // and any docked pane. Layouts that float over the screen (menus, modals) ignore this.
+    fn reflow(&mut self, _header: &Rectangle, _canvases: &mut HashMap<Gid, Canvas>) -> Result<(), xous::Error> {
+        Ok(())
+    }
 }
 
 #[enum_dispatch(LayoutApi)]
@@ -45,6 +54,8 @@ pub(crate) enum UxLayout {
     MenuLayout,
     ModalLayout,
     Framebuffer,
+    WidgetLayout,
+    PaneLayout,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -80,6 +91,20 @@ pub(crate) struct UxContext {
 }
 pub(crate) const BOOT_CONTEXT_TRUSTLEVEL: u8 = 254;
 
+/// A Pane context docked above an App context. Pressing the menu key together with '↑' or '↓' while the
+/// App has focus moves the keyboard between the two.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Split {
+    /// app_token of the App that docked the pane
+    host: [u32; 4],
+    /// app_token of the docked Pane
+    pane: [u32; 4],
+    /// set when keystrokes are routed to the pane instead of the host
+    pane_focused: bool,
+    /// tracks the host's visibility: the pane is only on screen while its host is
+    shown: bool,
+}
+
 /*
   For now, app focus from menus is cooperative (menu items must relinquish focus).
   However, later on, I think it would be good to implement a press-hold to feature to
@@ -97,6 +122,8 @@ pub(crate) struct ContextManager {
     imef_active: bool,
     kbd: keyboard::Keyboard,
     main_menu_app_token: Option<[u32; 4]>, // app_token of the main menu, if it has been registered
+    widget: Option<[u32; 4]>, // app_token of the widget strip, if one has been registered
+    split: Option<Split>, // the docked pane, if any
     /// for internal generation of deface states
     pub trng: trng::Trng,
     tt: ticktimer_server::Ticktimer,
@@ -120,6 +147,8 @@ impl ContextManager {
             imef_active: false,
             kbd,
             main_menu_app_token: None,
+            widget: None,
+            split: None,
             trng: trng::Trng::new(&xns).expect("couldn't connect to trng"),
             tt: ticktimer_server::Ticktimer::new().unwrap(),
         }
@@ -247,6 +276,70 @@ impl ContextManager {
                     };
                     self.contexts.insert(token, ux_context);
                 }
+                UxType::Widget => {
+                    if self.widget.is_some() {
+                        log::error!("attempt to register a second widget strip, ignoring {}", registration.app_name);
+                        return None;
+                    }
+                    let widget = WidgetLayout::init(&gfx, &trng,
+                        &status_cliprect, canvases).expect("couldn't create widget layout");
+                    log::debug!("debug widget layout: {:?}", widget);
+                    let ux_context = UxContext {
+                        layout: UxLayout::WidgetLayout(widget),
+                        predictor: None,
+                        app_token: token,
+                        gam_token: [trng.get_u32().unwrap(), trng.get_u32().unwrap(), trng.get_u32().unwrap(), trng.get_u32().unwrap(), ],
+                        listener: xous::connect(xous::SID::from_array(registration.listener)).unwrap(),
+                        redraw_id: registration.redraw_id,
+                        gotinput_id: None,
+                        audioframe_id: None,
+                        focuschange_id: None,
+                        rawkeys_id: None,
+                        vibe: false,
+                        imef_menu_mode: false,
+                        pred_token: None,
+                    };
+                    self.contexts.insert(token, ux_context);
+                    self.widget = Some(token);
+                    // the strip is persistent, so unlike other layouts it goes on-screen right away -- unless a menu or modal is up
+                    self.sync_widget(self.focused_context, canvases);
+                }
+                UxType::Pane => {
+                    let header = self.header_for(token, status_cliprect, canvases);
+                    let mut pane = PaneLayout::init(&gfx, &trng,
+                        &header, canvases).expect("couldn't create pane layout");
+                    // panes are only shown when an App docks them
+                    pane.set_visibility_state(false, canvases);
+                    log::debug!("debug pane layout: {:?}", pane);
+                    let ux_context = UxContext {
+                        layout: UxLayout::PaneLayout(pane),
+                        predictor: None,
+                        app_token: token,
+                        gam_token: [trng.get_u32().unwrap(), trng.get_u32().unwrap(), trng.get_u32().unwrap(), trng.get_u32().unwrap(), ],
+                        listener: xous::connect(xous::SID::from_array(registration.listener)).unwrap(),
+                        redraw_id: registration.redraw_id,
+                        gotinput_id: None,
+                        audioframe_id: None,
+                        focuschange_id: registration.focuschange_id,
+                        rawkeys_id: registration.rawkeys_id,
+                        vibe: false,
+                        imef_menu_mode: false,
+                        pred_token: None,
+                    };
+                    self.contexts.insert(token, ux_context);
+                }
+            }
+            // layouts are created assuming only the status bar is above them; fit them around the widget strip
+            self.reflow(status_cliprect, canvases);
+            if self.widget == maybe_token {
+                // the strip just pushed the focused App down
+                if let Some(context) = self.focused_context() {
+                    context.layout.clear(gfx, canvases).expect("couldn't clear after adding the widget strip");
+                }
+                if let Some(context) = self.get_context_by_token(token) {
+                    context.layout.clear(gfx, canvases).expect("couldn't clear the widget strip");
+                }
+                self.redraw().ok();
             }
         } else {
             // at the moment, we don't allow contexts that are not part of the boot set.
@@ -273,6 +366,132 @@ impl ContextManager {
             false
         }
     }
+    fn content_rect(&self, token: [u32; 4], canvases: &HashMap<Gid, Canvas>) -> Option<Rectangle> {
+        self.get_content_canvas(token).and_then(|gid| canvases.get(&gid)).map(|c| c.clip_rect())
+    }
+    /// the region at the top of the screen that the context with `token` has to lay itself out below: the
+    /// status bar, the widget strip, and for the host of a split, the docked pane and its divider.
+    fn header_for(&self, token: [u32; 4], status_cliprect: &Rectangle, canvases: &HashMap<Gid, Canvas>) -> Rectangle {
+        if self.widget == Some(token) {
+            return *status_cliprect;
+        }
+        let strip = self.widget.and_then(|widget| self.content_rect(widget, canvases));
+        let pane = self.split
+            .filter(|split| split.host == token)
+            .and_then(|split| self.content_rect(split.pane, canvases));
+        stack_header(status_cliprect, strip, pane)
+    }
+    /// re-fits every layout below its header; the widget strip goes first, then the panes, as the others depend on them.
+    fn reflow(&mut self, status_cliprect: &Rectangle, canvases: &mut HashMap<Gid, Canvas>) {
+        let mut tokens: Vec<[u32; 4]> = self.contexts.keys().cloned().collect();
+        tokens.sort_by_key(|token| reflow_rank(self.contexts[token].layout.behavior()));
+        for token in tokens {
+            let header = self.header_for(token, status_cliprect, canvases);
+            if let Some(context) = self.contexts.get_mut(&token) {
+                context.layout.reflow(&header, canvases).expect("couldn't reflow layout");
+            }
+        }
+        recompute_canvases(canvases);
+    }
+    /// shows or hides the widget strip to match the context that has (or is about to get) focus
+    fn sync_widget(&mut self, focus: Option<[u32; 4]>, canvases: &mut HashMap<Gid, Canvas>) {
+        let shown = widget_shown(focus.and_then(|token| self.contexts.get(&token)).map(|context| context.layout.behavior()));
+        if let Some(widget) = self.widget {
+            if let Some(context) = self.contexts.get_mut(&widget) {
+                context.layout.set_visibility_state(shown, canvases);
+            }
+        }
+    }
+    /// shows or hides the docked pane to match its host, and tells the pane about it
+    fn sync_split(&mut self, canvases: &mut HashMap<Gid, Canvas>) {
+        if let Some(mut split) = self.split {
+            let shown = if let Some(gid) = self.get_content_canvas(split.host) {
+                canvases.get(&gid).map(|c| c.is_onscreen()).unwrap_or(false)
+            } else {
+                false
+            };
+            if shown != split.shown {
+                if let Some(pane) = self.contexts.get_mut(&split.pane) {
+                    pane.layout.set_visibility_state(shown, canvases);
+                    if let Some(focuschange_id) = pane.focuschange_id {
+                        let state = if shown {gam::FocusState::Foreground} else {gam::FocusState::Background};
+                        xous::send_message(pane.listener,
+                            xous::Message::new_scalar(focuschange_id as usize, state as usize, 0, 0, 0)
+                        ).ok();
+                    }
+                }
+                split.shown = shown;
+                self.split = Some(split);
+            }
+        }
+    }
+    /// draws the rule between the docked pane and its host. This area belongs to no canvas; only call it while the host has focus.
+    fn draw_divider(&self, gfx: &graphics_server::Gfx, canvases: &HashMap<Gid, Canvas>) {
+        if let Some(split) = self.split {
+            if let Some(pane) = self.content_rect(split.pane, canvases) {
+                let mut divider = Rectangle::new_v_stack(pane, PANE_DIVIDER_HEIGHT);
+                divider.style = DrawStyle {fill_color: Some(PixelColor::Light), stroke_color: None, stroke_width: 0,};
+                gfx.draw_rectangle(divider).expect("can't clear divider");
+                // a hairline while the host has the keyboard, a solid bar while the pane has it
+                if !split.pane_focused {
+                    divider.tl.y = divider.br.y - PANE_DIVIDER_HEIGHT / 2;
+                    divider.br.y = divider.tl.y;
+                }
+                divider.style = DrawStyle {fill_color: Some(PixelColor::Dark), stroke_color: None, stroke_width: 0,};
+                gfx.draw_rectangle(divider).expect("can't draw divider");
+            }
+        }
+    }
+    /// docks the Pane named `pane_name` above the App `host`, or undocks the current pane if `pane_name` is None.
+    /// Only one pane is docked at a time, and only an App may host it.
+    pub(crate) fn set_split(&mut self,
+        gfx: &graphics_server::Gfx,
+        status_cliprect: &Rectangle,
+        canvases: &mut HashMap<Gid, Canvas>,
+        host: [u32; 4],
+        pane_name: Option<&str>,
+    ) -> Result<(), xous::Error> {
+        match self.get_context_by_token(host) {
+            Some(context) if context.layout.behavior() == LayoutBehavior::App => (),
+            _ => return Err(xous::Error::AccessDenied),
+        }
+        let pane = if let Some(name) = pane_name {
+            let token = self.find_app_token_by_name(name).ok_or(xous::Error::ProcessNotFound)?;
+            match self.get_context_by_token(token) {
+                Some(context) if context.layout.behavior() == LayoutBehavior::Pane => Some(token),
+                _ => return Err(xous::Error::ProcessNotFound),
+            }
+        } else {
+            None
+        };
+
+        if let Some(old) = self.split.take() {
+            if old.shown {
+                if let Some(context) = self.contexts.get_mut(&old.pane) {
+                    context.layout.set_visibility_state(false, canvases);
+                    if let Some(focuschange_id) = context.focuschange_id {
+                        xous::send_message(context.listener,
+                            xous::Message::new_scalar(focuschange_id as usize, gam::FocusState::Background as usize, 0, 0, 0)
+                        ).ok();
+                    }
+                }
+            }
+        }
+        self.split = pane.map(|pane| Split { host, pane, pane_focused: false, shown: false });
+        self.sync_split(canvases);
+        self.reflow(status_cliprect, canvases);
+
+        if self.focused_app() == Some(host) {
+            // the host's canvases moved, so wipe everything below the widget strip: that covers the host, and the area the pane vacated or now occupies
+            let top = self.widget.and_then(|widget| self.content_rect(widget, canvases)).unwrap_or(*status_cliprect);
+            let mut body = Rectangle::new(Point::new(0, top.br.y + 1), gfx.screen_size().expect("Couldn't get screen size"));
+            body.style = DrawStyle {fill_color: Some(PixelColor::Light), stroke_color: None, stroke_width: 0,};
+            gfx.draw_rectangle(body).expect("can't clear screen body");
+            self.draw_divider(gfx, canvases);
+            self.redraw().ok();
+        }
+        Ok(())
+    }
     pub(crate) fn get_content_canvas(&self, token: [u32; 4]) -> Option<Gid> {
         if let Some(context) = self.contexts.get(&token) {
             let gids = context.layout.get_gids();
@@ -292,13 +511,12 @@ impl ContextManager {
         status_cliprect: &Rectangle,
         canvases: &mut HashMap<Gid, Canvas>) -> Option<Point> {
 
-        for context in self.contexts.values_mut() {
-            if context.gam_token == gam_token {
-                let result = context.layout.resize_height(gfx, new_height, status_cliprect, canvases).expect("couldn't adjust height of active Ux context");
-                return Some(result)
-            }
+        let app_token = self.contexts.values().find(|context| context.gam_token == gam_token).map(|context| context.app_token);
+        if let Some(token) = app_token {
+            self.set_canvas_height_app_token(gfx, token, new_height, status_cliprect, canvases)
+        } else {
+            None
         }
-        None
     }
     // hmmm...feels wrong to have basically a dupe of the above. Maybe this abstraction needs to be cleaned up a bit.
     pub(crate) fn set_canvas_height_app_token(&mut self,
@@ -308,8 +526,23 @@ impl ContextManager {
         status_cliprect: &Rectangle,
         canvases: &mut HashMap<Gid, Canvas>) -> Option<Point> {
 
+        let header = self.header_for(app_token, status_cliprect, canvases);
         if let Some(context) = self.contexts.get_mut(&app_token) {
-            let result = context.layout.resize_height(gfx, new_height, status_cliprect, canvases).expect("couldn't adjust height of active Ux context");
+            let result = context.layout.resize_height(gfx, new_height, &header, canvases).expect("couldn't adjust height of active Ux context");
+            match context.layout.behavior() {
+                // everything below the strip or the pane has to move with it
+                LayoutBehavior::Widget | LayoutBehavior::Pane => {
+                    self.reflow(status_cliprect, canvases);
+                    if let Some(context) = self.focused_context() {
+                        context.layout.clear(gfx, canvases).expect("couldn't clear after a resize");
+                    }
+                    if self.split.map(|split| split.shown).unwrap_or(false) && self.focused_app() == self.split.map(|split| split.host) {
+                        self.draw_divider(gfx, canvases);
+                    }
+                    self.redraw().ok();
+                }
+                _ => (),
+            }
             Some(result)
         } else {
             None
@@ -327,6 +560,12 @@ impl ContextManager {
         token: [u32; 4],
         clear: bool,
     ) -> Result<(), xous::Error> {
+        if let Some(context) = self.get_context_by_token(token) {
+            // the widget strip and panes ride along with an App; they are never focused on their own
+            if context.layout.behavior() == LayoutBehavior::Widget || context.layout.behavior() == LayoutBehavior::Pane {
+                return Err(xous::Error::AccessDenied);
+            }
+        }
         self.notify_app_switch(token).ok();
 
         let mut leaving_visibility: bool = false;
@@ -395,6 +634,10 @@ impl ContextManager {
                     old_context.layout.set_visibility_state(leaving_visibility, canvases);
                 }
             }
+            // a docked pane follows its host on and off the screen
+            self.sync_split(canvases);
+            // the strip stays up under Apps, but gives way to menus and modals
+            self.sync_widget(Some(token), canvases);
         }
         log::trace!("rewiring IMEF and recomputing canvases");
         {
@@ -458,6 +701,9 @@ impl ContextManager {
                 self.last_context = self.focused_context;
                 self.focused_context = Some(last_token);
             }
+            if self.split.map(|split| split.host) == Some(token) {
+                self.draw_divider(gfx, canvases);
+            }
             log::trace!("context stack: {:x?}", self.context_stack);
             if self.context_stack.len() > 1 { // we've now got a stack of contexts, start stashing copies
                 log::trace!("stashing");
@@ -529,20 +775,33 @@ impl ContextManager {
         }
         Ok(())
     }
-    pub(crate) fn redraw(&self) -> Result<(), xous::Error> { // redraws the currently focused context
+    fn send_redraw(context: &UxContext) -> Result<(), xous::Error> {
+        log::debug!("redraw msg to {}, id {}", context.listener, context.redraw_id);
+        match xous::try_send_message(context.listener,
+            xous::Message::new_scalar(context.redraw_id as usize, 0, 0, 0, 0)
+        ) {
+            Err(xous::Error::ServerQueueFull) => {
+                log::warn!("server queue full, redraw skipped");
+                Ok(())
+            },
+            Ok(_r) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+    pub(crate) fn redraw(&self) -> Result<(), xous::Error> { // redraws the currently focused context, plus the widget strip and any docked pane on screen
         if let Some(token) = self.focused_app() {
             if let Some(context) = self.contexts.get(&token) {
-                log::debug!("redraw msg to {}, id {}", context.listener, context.redraw_id);
-                let ret = match xous::try_send_message(context.listener,
-                    xous::Message::new_scalar(context.redraw_id as usize, 0, 0, 0, 0)
-                ) {
-                    Err(xous::Error::ServerQueueFull) => {
-                        log::warn!("server queue full, redraw skipped");
-                        Ok(())
-                    },
-                    Ok(_r) => Ok(()),
-                    Err(e) => Err(e),
-                };
+                if let Some(widget) = self.widget.and_then(|widget| self.contexts.get(&widget)) {
+                    ContextManager::send_redraw(widget).ok();
+                }
+                if let Some(split) = self.split {
+                    if split.shown {
+                        if let Some(pane) = self.contexts.get(&split.pane) {
+                            ContextManager::send_redraw(pane).ok();
+                        }
+                    }
+                }
+                let ret = ContextManager::send_redraw(context);
                 // this delay helps ensure that the previously requested UX redraw has time to complete
                 // in particular, this helps sequence the case where one modal is erased, and the next one is
                 // raised, in quick succession.
@@ -586,6 +845,34 @@ impl ContextManager {
         gfx: &graphics_server::Gfx,
         canvases: &mut HashMap<Gid, Canvas>,
     ) {
+        if let Some(mut split) = self.split {
+            if self.focused_context == Some(split.host) {
+                // the menu key chorded with an up/down arrow moves the keyboard between the host and its pane
+                if keys.contains(&'∴') && keys.iter().any(|&k| k == '↑' || k == '↓') {
+                    split.pane_focused = !split.pane_focused;
+                    self.split = Some(split);
+                    self.draw_divider(gfx, canvases);
+                    // the apps' redraws flush the new divider to the screen
+                    self.redraw().ok();
+                    return;
+                }
+                // the menu key on its own still raises the main menu
+                if split.pane_focused && keys[0] != '∴' {
+                    if let Some(pane) = self.get_context_by_token(split.pane) {
+                        if let Some(rawkeys_id) = pane.rawkeys_id {
+                            xous::send_message(pane.listener,
+                                xous::Message::new_scalar(rawkeys_id as usize,
+                                keys[0] as u32 as usize,
+                                keys[1] as u32 as usize,
+                                keys[2] as u32 as usize,
+                                keys[3] as u32 as usize,
+                            )).expect("couldn't forward raw keys onto pane listener");
+                        }
+                    }
+                    return;
+                }
+            }
+        }
         // only pop up the menu if the primary key hit is the menu key (search just the first entry of keys); reject multi-key hits
         // only pop up the menu if it isn't already popped up
         if keys[0] == '∴' {
@@ -678,3 +965,94 @@ impl ContextManager {
         Err(xous::Error::ProcessNotFound)
     }
 }
+
+/// the region a layout has to start below: the status bar, then the widget strip if there is one, then for the
+/// host of a split, the docked pane and its divider.
+fn stack_header(status_cliprect: &Rectangle, strip: Option<Rectangle>, pane: Option<Rectangle>) -> Rectangle {
+    let mut header = *status_cliprect;
+    if let Some(strip) = strip {
+        header.br.y = strip.br.y;
+    }
+    if let Some(pane) = pane {
+        header.br.y = pane.br.y + PANE_DIVIDER_HEIGHT;
+    }
+    header
+}
+/// layouts are reflowed from the top down, as each one's header depends on the ones above it
+fn reflow_rank(behavior: LayoutBehavior) -> u8 {
+    match behavior {
+        LayoutBehavior::Widget => 0,
+        LayoutBehavior::Pane => 1,
+        _ => 2,
+    }
+}
+/// the widget strip is drawn by an app at content-level trust, so it is only on screen while an App has focus:
+/// otherwise it could keep drawing next to a menu or a modal, such as a password prompt.
+fn widget_shown(focus: Option<LayoutBehavior>) -> bool {
+    match focus {
+        Some(behavior) => behavior == LayoutBehavior::App,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> Rectangle {
+        Rectangle::new_coords(0, 0, 335, 31)
+    }
+    fn corners(r: Rectangle) -> (Point, Point) {
+        (r.tl, r.br)
+    }
+
+    #[test]
+    fn header_stacks_strip_and_pane() {
+        let status = status();
+        assert_eq!(corners(stack_header(&status, None, None)), corners(status));
+
+        let strip = Rectangle::new_v_stack(status, 20);
+        let header = stack_header(&status, Some(strip), None);
+        assert_eq!(corners(header), (status.tl, Point::new(status.br.x, strip.br.y)));
+
+        let pane = Rectangle::new_v_stack(header, 100);
+        let host_header = stack_header(&status, Some(strip), Some(pane));
+        assert_eq!(host_header.br.y, pane.br.y + PANE_DIVIDER_HEIGHT);
+        // the divider sits between the pane and the host, so neither can draw on it
+        let host = Rectangle::new_v_stack(host_header, 200);
+        assert_eq!(host.tl.y - pane.br.y - 1, PANE_DIVIDER_HEIGHT);
+
+        // without a strip, the pane goes right under the status bar
+        let pane = Rectangle::new_v_stack(status, 100);
+        assert_eq!(stack_header(&status, None, Some(pane)).br.y, status.br.y + 100 + PANE_DIVIDER_HEIGHT);
+    }
+
+    #[test]
+    fn reflow_moves_the_strip_without_resizing_it() {
+        let status = status();
+        let strip = Rectangle::new_v_stack(status, 24);
+        // reflowing under the same header is a no-op
+        assert_eq!(corners(WidgetLayout::stack_below(&status, &strip)), corners(strip));
+        // a taller status bar pushes the strip down, keeping its height
+        let taller = Rectangle::new_coords(0, 0, 335, 39);
+        let moved = WidgetLayout::stack_below(&taller, &strip);
+        assert_eq!(moved.tl.y, taller.br.y + 1);
+        assert_eq!(moved.br.y - moved.tl.y, strip.br.y - strip.tl.y);
+        // and everything below follows it
+        assert_eq!(stack_header(&taller, Some(moved), None).br.y, moved.br.y);
+    }
+
+    #[test]
+    fn reflow_goes_top_down() {
+        let mut order = [LayoutBehavior::App, LayoutBehavior::Pane, LayoutBehavior::Alert, LayoutBehavior::Widget];
+        order.sort_by_key(|&behavior| reflow_rank(behavior));
+        assert_eq!(&order[..2], &[LayoutBehavior::Widget, LayoutBehavior::Pane]);
+    }
+
+    #[test]
+    fn widget_gives_way_to_menus_and_modals() {
+        assert!(widget_shown(None));
+        assert!(widget_shown(Some(LayoutBehavior::App)));
+        assert!(!widget_shown(Some(LayoutBehavior::Alert)));
+    }
+}
@@ -5,4 +5,8 @@ pub(crate) use chat::*;
 mod modal;
 pub(crate) use modal::*;
 mod framebuffer;
-pub(crate) use framebuffer::*;
\ No newline at end of file
+pub(crate) use framebuffer::*;
+mod widget;
+pub(crate) use widget::*;
+mod pane;
+pub(crate) use pane::*;
@@ -152,4 +152,12 @@ impl LayoutApi for ChatLayout {
         let predictive_canvas = canvases.get_mut(&self.predictive).expect("couldn't find predictive canvas");
         predictive_canvas.set_onscreen(onscreen);
     }
+    fn reflow(&mut self, header: &Rectangle, canvases: &mut HashMap<Gid, Canvas>) -> Result<(), xous::Error> {
+        let input_canvas = canvases.get(&self.input).expect("couldn't find input canvas");
+        let new_content_rect = Rectangle::new_v_span(*header, input_canvas.clip_rect());
+
+        let content_canvas = canvases.get_mut(&self.content).expect("couldn't find content canvas");
+        content_canvas.set_clip(new_content_rect);
+        Ok(())
+    }
 }
\ No newline at end of file
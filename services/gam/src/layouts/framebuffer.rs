@@ -66,4 +66,11 @@ impl LayoutApi for Framebuffer {
         log::debug!("raw fb entering set_visibilty_state, {}->{}", fb_canvas.is_onscreen(), onscreen);
         fb_canvas.set_onscreen(onscreen);
     }
+    fn reflow(&mut self, header: &Rectangle, canvases: &mut HashMap<Gid, Canvas>) -> Result<(), xous::Error> {
+        let fb_canvas = canvases.get_mut(&self.gid).expect("couldn't find my canvas");
+        let orig_rect = fb_canvas.clip_rect();
+
+        fb_canvas.set_clip(Rectangle::new_coords(orig_rect.tl().x, header.br().y + 1, orig_rect.br().x, orig_rect.br().y));
+        Ok(())
+    }
 }
\ No newline at end of file
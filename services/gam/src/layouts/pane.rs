@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use graphics_server::*;
+
+use crate::api::CanvasType;
+use crate::{Canvas, GlyphStyle, LayoutApi, LayoutBehavior};
+use crate::contexts::MISC_CONTEXT_DEFAULT_TRUST;
+const TRUST_OFFSET: u8 = 2;
+/// height of the rule the GAM draws between a docked pane and the App below it. It belongs to no canvas,
+/// so neither app can draw on it; it thickens while the pane has keyboard focus.
+pub(crate) const PANE_DIVIDER_HEIGHT: i16 = 4;
+
+/// A content canvas that is shown above an App context when that App docks it, splitting the screen
+/// between the two. The App's own layout is reflowed to start below the pane.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct PaneLayout {
+    pub gid: Gid,
+    min_height: i16,
+    max_height: i16,
+}
+impl PaneLayout {
+    pub fn init(
+        gfx: &graphics_server::Gfx,
+        trng: &trng::Trng,
+        header: &Rectangle,
+        canvases: &mut HashMap<Gid, Canvas>
+    ) -> Result<PaneLayout, xous::Error> {
+        let screensize = gfx.screen_size().expect("Couldn't get screen size");
+        let regular_height: i16 = gfx.glyph_height_hint(GlyphStyle::Regular).expect("couldn't get glyph height") as i16;
+        let margin = 4;
+
+        // start at a third of the screen; the pane can grow to at most half of it
+        let body_height = screensize.y - header.br().y;
+        // same trust as the chat content canvas, so a pane never outranks the chrome of the App it is docked to
+        let pane_canvas = Canvas::new(
+            Rectangle::new_v_stack(*header, body_height / 3),
+            (MISC_CONTEXT_DEFAULT_TRUST - TRUST_OFFSET) / 2, &trng, None, CanvasType::PaneContent
+        ).expect("couldn't create pane canvas");
+        let pane_gid = pane_canvas.gid();
+        canvases.insert(pane_canvas.gid(), pane_canvas);
+
+        Ok(PaneLayout {
+            gid: pane_gid,
+            min_height: regular_height + margin * 2,
+            max_height: body_height / 2,
+        })
+    }
+}
+impl LayoutApi for PaneLayout {
+    fn behavior(&self) -> LayoutBehavior {
+        LayoutBehavior::Pane
+    }
+    fn clear(&self, gfx: &graphics_server::Gfx, canvases: &mut HashMap<Gid, Canvas>) -> Result<(), xous::Error> {
+        let pane_canvas = canvases.get(&self.gid).expect("couldn't find pane canvas");
+
+        let mut rect = pane_canvas.clip_rect();
+        rect.style = DrawStyle {fill_color: Some(PixelColor::Light), stroke_color: None, stroke_width: 0,};
+        gfx.draw_rectangle(rect)
+    }
+    fn resize_height(&mut self, _gfx: &graphics_server::Gfx, new_height: i16, status_canvas: &Rectangle, canvases: &mut HashMap<Gid, Canvas>) -> Result<Point, xous::Error> {
+        let pane_canvas = canvases.get_mut(&self.gid).expect("couldn't find pane canvas");
+
+        let height = new_height.max(self.min_height).min(self.max_height);
+        let pane_clip_rect = Rectangle::new_v_stack(*status_canvas, height);
+        pane_canvas.set_clip(pane_clip_rect);
+        Ok(pane_clip_rect.br)
+    }
+    fn get_gids(&self) ->Vec<crate::api::GidRecord> {
+        vec![
+            crate::api::GidRecord {
+                gid: self.gid,
+                canvas_type: CanvasType::PaneContent
+            }
+        ]
+    }
+    fn set_visibility_state(&mut self, onscreen: bool, canvases: &mut HashMap<Gid, Canvas>) {
+        let pane_canvas = canvases.get_mut(&self.gid).expect("couldn't find pane canvas");
+        log::debug!("pane entering set_visibilty_state, {}->{}", pane_canvas.is_onscreen(), onscreen);
+        pane_canvas.set_onscreen(onscreen);
+    }
+    fn reflow(&mut self, header: &Rectangle, canvases: &mut HashMap<Gid, Canvas>) -> Result<(), xous::Error> {
+        let pane_canvas = canvases.get_mut(&self.gid).expect("couldn't find pane canvas");
+        let orig_rect = pane_canvas.clip_rect();
+
+        pane_canvas.set_clip(Rectangle::new_v_stack(*header, orig_rect.br().y - orig_rect.tl().y + 1));
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use graphics_server::*;
+
+use crate::api::CanvasType;
+use crate::{Canvas, GlyphStyle, LayoutApi, LayoutBehavior};
+use crate::contexts::MISC_CONTEXT_DEFAULT_TRUST;
+const TRUST_OFFSET: u8 = 3;
+/// the strip can grow to at most this many lines of small text
+const MAX_LINES: i16 = 3;
+
+/// A thin strip directly below the status bar that an app can keep on screen while other apps have focus.
+/// It never receives keystrokes, and App layouts are reflowed to start below it.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct WidgetLayout {
+    pub gid: Gid,
+    min_height: i16,
+    max_height: i16,
+}
+impl WidgetLayout {
+    pub fn init(
+        gfx: &graphics_server::Gfx,
+        trng: &trng::Trng,
+        status_cliprect: &Rectangle,
+        canvases: &mut HashMap<Gid, Canvas>
+    ) -> Result<WidgetLayout, xous::Error> {
+        let small_height: i16 = gfx.glyph_height_hint(GlyphStyle::Small).expect("couldn't get glyph height") as i16;
+        let margin = 2;
+
+        // content-level trust: the strip is app-drawn, so menus, modals and app chrome all ride on top of it
+        let widget_canvas = Canvas::new(
+            Rectangle::new_v_stack(*status_cliprect, small_height + margin * 2),
+            (MISC_CONTEXT_DEFAULT_TRUST - TRUST_OFFSET) / 2, &trng, None, CanvasType::Widget
+        ).expect("couldn't create widget canvas");
+        let widget_gid = widget_canvas.gid();
+        canvases.insert(widget_canvas.gid(), widget_canvas);
+
+        Ok(WidgetLayout {
+            gid: widget_gid,
+            min_height: small_height + margin * 2,
+            max_height: small_height * MAX_LINES + margin * 2,
+        })
+    }
+    /// where the strip `strip` goes when the region above it changes to `header`: directly below it, at the same height
+    pub(crate) fn stack_below(header: &Rectangle, strip: &Rectangle) -> Rectangle {
+        Rectangle::new_v_stack(*header, strip.br.y - strip.tl.y + 1)
+    }
+}
+impl LayoutApi for WidgetLayout {
+    fn behavior(&self) -> LayoutBehavior {
+        LayoutBehavior::Widget
+    }
+    fn clear(&self, gfx: &graphics_server::Gfx, canvases: &mut HashMap<Gid, Canvas>) -> Result<(), xous::Error> {
+        let widget_canvas = canvases.get(&self.gid).expect("couldn't find widget canvas");
+
+        let mut rect = widget_canvas.clip_rect();
+        rect.style = DrawStyle {fill_color: Some(PixelColor::Light), stroke_color: None, stroke_width: 0,};
+        gfx.draw_rectangle(rect)
+    }
+    fn resize_height(&mut self, _gfx: &graphics_server::Gfx, new_height: i16, status_canvas: &Rectangle, canvases: &mut HashMap<Gid, Canvas>) -> Result<Point, xous::Error> {
+        let widget_canvas = canvases.get_mut(&self.gid).expect("couldn't find widget canvas");
+
+        let height = new_height.max(self.min_height).min(self.max_height);
+        let widget_clip_rect = Rectangle::new_v_stack(*status_canvas, height);
+        widget_canvas.set_clip(widget_clip_rect);
+        Ok(widget_clip_rect.br)
+    }
+    fn get_gids(&self) ->Vec<crate::api::GidRecord> {
+        vec![
+            crate::api::GidRecord {
+                gid: self.gid,
+                canvas_type: CanvasType::Widget
+            }
+        ]
+    }
+    fn set_visibility_state(&mut self, onscreen: bool, canvases: &mut HashMap<Gid, Canvas>) {
+        let widget_canvas = canvases.get_mut(&self.gid).expect("couldn't find widget canvas");
+        log::debug!("widget strip entering set_visibilty_state, {}->{}", widget_canvas.is_onscreen(), onscreen);
+        widget_canvas.set_onscreen(onscreen);
+    }
+    fn reflow(&mut self, header: &Rectangle, canvases: &mut HashMap<Gid, Canvas>) -> Result<(), xous::Error> {
+        let widget_canvas = canvases.get_mut(&self.gid).expect("couldn't find widget canvas");
+        let orig_rect = widget_canvas.clip_rect();
+
+        widget_canvas.set_clip(WidgetLayout::stack_below(header, &orig_rect));
+        Ok(())
+    }
+}
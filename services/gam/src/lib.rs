@@ -10,6 +10,8 @@ pub mod menu;
 pub use menu::*;
 pub mod apps;
 pub use apps::*;
+pub mod textviewer;
+pub use textviewer::*;
 #[cfg(feature="ditherpunk")]
 pub mod bitmap;
 #[cfg(feature="ditherpunk")]
@@ -151,6 +153,30 @@ impl Gam {
         tv.set_op(TextOp::Nop);
         Ok(())
     }
+    /// Same as `post_textview`, but nothing is drawn outside of `area`, which is a rectangle in the coordinates of
+    /// the TextView's canvas. Text that runs off the edges of the area is cut off, so it can be used to scroll text
+    /// that is taller than the area (see `TextViewer`). The area can't extend the drawing beyond the canvas.
+    pub fn post_textview_clipped(&self, tv: &mut TextView, area: Rectangle) -> Result<(), xous::Error> {
+        tv.set_op(TextOp::Render);
+        // the GAM interprets a clip_rect on a render request as an area within the canvas
+        tv.clip_rect = Some(area);
+        let mut buf = Buffer::into_buf(tv.clone()).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::RenderTextView.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::RenderReturn(tvr) => {
+                tv.bounds_computed = tvr.bounds_computed;
+                tv.cursor = tvr.cursor;
+            }
+            api::Return::NotCurrentlyDrawable => {
+                tv.bounds_computed = None;
+            }
+            _ => panic!("GAM_API: post_textview_clipped got a return value from the server that isn't expected or handled")
+        }
+        tv.clip_rect = None;
+        tv.set_op(TextOp::Nop);
+        Ok(())
+    }
     /// Bounds computation does no checks on security since it's a non-drawing operation. While normal drawing always
     /// takes the bounds from the canvas, the caller can specify a clip_rect in this tv, instead of drawing the
     /// clip_rect from the Canvas associated with the tv.
@@ -415,6 +441,24 @@ impl Gam {
     pub fn raise_modal(&self, modal_name: &str) -> Result<(), xous::Error> {
         self.raise_menu(modal_name)
     }
+    /// splits the screen of the App holding `token` by docking the `UxType::Pane` registered as `pane_name`
+    /// above it, or undocks the current pane if `pane_name` is `None`. One pane is docked at a time; docking
+    /// a new one undocks the previous. While the App has focus, the menu key chorded with '↑' or '↓' moves
+    /// keyboard input between the App and the pane.
+    pub fn set_split(&self, token: [u32; 4], pane_name: Option<&str>) -> Result<(), xous::Error> {
+        let split = SplitRequest {
+            token,
+            pane_name: pane_name.map(|name| String::<128>::from_str(name)),
+            result: None,
+        };
+        let mut buf = Buffer::into_buf(split).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::SetSplit.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        match buf.to_original::<SplitRequest, _>().unwrap().result {
+            Some(ActivationResult::Success) => Ok(()),
+            Some(ActivationResult::Failure) => Err(xous::Error::AccessDenied),
+            None => Err(xous::Error::InternalError),
+        }
+    }
     /// this is a one-way door, once you've set it, you can't unset it.
     pub fn set_devboot(&self, enable: bool) -> Result<(), xous::Error> {
         let ena =
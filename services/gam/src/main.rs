@@ -233,7 +233,25 @@ fn wrapped_main() -> ! {
                             if canvas.is_drawable() {
                                 // set the clip rectangle according to the canvas' location
                                 let base_clip_rect = canvas.clip_rect();
-                                tv.clip_rect = Some(base_clip_rect.into());
+                                if let Some(area) = tv.clip_rect {
+                                    // the client asked to only draw within an area of its canvas (see Gam::post_textview_clipped()).
+                                    // The area can only narrow the canvas' clip rectangle, never widen it. The graphics server
+                                    // places text relative to the top left of the clip rectangle, so shift the bounds to match.
+                                    let mut screen_area = area;
+                                    screen_area.translate(base_clip_rect.tl);
+                                    if let Some(clip) = screen_area.clip_with(base_clip_rect) {
+                                        tv.bounds_hint = tv.bounds_hint.translate(base_clip_rect.tl - clip.tl);
+                                        tv.clip_rect = Some(clip);
+                                    } else {
+                                        // the area is entirely outside of the canvas, so there's nothing to draw
+                                        tv.bounds_computed = None;
+                                        let ret = api::Return::RenderReturn(tv);
+                                        buffer.replace(ret).unwrap();
+                                        continue;
+                                    }
+                                } else {
+                                    tv.clip_rect = Some(base_clip_rect.into());
+                                }
 
                                 // you have to clone the tv object, because if you don't the same block of
                                 // memory gets passed on to the graphics_server(). Which is efficient, but,
@@ -599,6 +617,22 @@ fn wrapped_main() -> ! {
                 });
                 buffer.replace(activation).unwrap();
             },
+            Some(Opcode::SetSplit) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut split = buffer.to_original::<SplitRequest, _>().unwrap();
+                let pane_name = split.pane_name.map(|name| name.as_str().unwrap_or("UTF-8 error").to_string());
+                log::debug!("got request to dock pane {:?}", pane_name);
+                let result = context_mgr.set_split(&gfx, &status_cliprect, &mut canvases, split.token, pane_name.as_deref());
+                split.result = Some(
+                    match result {
+                        Ok(_) => ActivationResult::Success,
+                        Err(e) => {
+                            log::warn!("couldn't dock pane {:?}: {:?}", pane_name, e);
+                            ActivationResult::Failure
+                        }
+                });
+                buffer.replace(split).unwrap();
+            },
             Some(Opcode::Devboot) => msg_scalar_unpack!(msg, ena, _,  _,  _, {
                 if ena != 0 { gfx.set_devboot(true).expect("couldn't send devboot message"); }
                 else { gfx.set_devboot(false).expect("couldn't send devboot message"); }
@@ -20,6 +20,10 @@ mod image;
 pub use image::*;
 mod bip39entry;
 pub use bip39entry::*;
+mod scrollingtext;
+pub use scrollingtext::*;
+mod form;
+pub use form::*;
 
 use enum_dispatch::enum_dispatch;
 
@@ -35,6 +39,7 @@ use num_traits::*;
 use core::fmt::Write;
 
 pub const MAX_ITEMS: usize = 8;
+pub const MAX_FORM_FIELDS: usize = 8;
 
 #[enum_dispatch(ActionApi)]
 pub enum ActionType {
@@ -46,7 +51,9 @@ pub enum ActionType {
     Notification,
     #[cfg(feature="ditherpunk")]
     Image,
-    ConsoleInput
+    ConsoleInput,
+    ScrollingText,
+    Form,
 }
 
 #[enum_dispatch]
@@ -172,6 +179,101 @@ impl CheckBoxPayload {
     }
 }
 
+/// One labelled field of a `Form`. The value the user settles on is written back into `kind`.
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum FormFieldKind {
+    /// free text entry; an untouched, empty field takes on its placeholder when the form is submitted
+    Text(TextEntryPayload),
+    /// exactly one of `items`; `selected` is the index of the chosen item
+    Radio { items: [Option<ItemName>; MAX_ITEMS], selected: u32 },
+    /// any number of `items`; bit `n` of `checked` is set if item `n` is checked
+    CheckBoxes { items: [Option<ItemName>; MAX_ITEMS], checked: u32 },
+    Slider { min: u32, max: u32, step: u32, value: u32 },
+}
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct FormField {
+    pub label: ItemName,
+    pub kind: FormFieldKind,
+}
+impl FormField {
+    /// the entered text, if this is a text field
+    pub fn text(&self) -> Option<&str> {
+        match &self.kind {
+            FormFieldKind::Text(payload) => Some(payload.as_str()),
+            _ => None,
+        }
+    }
+    /// the name of the chosen item, if this is a radio field
+    pub fn selected(&self) -> Option<&str> {
+        match &self.kind {
+            FormFieldKind::Radio { items, selected } => items.get(*selected as usize).and_then(|i| i.as_ref()).map(|i| i.as_str()),
+            _ => None,
+        }
+    }
+    /// the names of the checked items; empty if this is not a checkbox field
+    pub fn checked(&self) -> Vec<&str> {
+        let mut ret = Vec::new();
+        if let FormFieldKind::CheckBoxes { items, checked } = &self.kind {
+            for (index, maybe_item) in items.iter().enumerate() {
+                if let Some(item) = maybe_item {
+                    if checked & (1 << index) != 0 {
+                        ret.push(item.as_str());
+                    }
+                }
+            }
+        }
+        ret
+    }
+    /// the slider setting, if this is a slider field
+    pub fn value(&self) -> Option<u32> {
+        match &self.kind {
+            FormFieldKind::Slider { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+}
+/// The fields of a `Form`, in display order. This is both the specification sent to the modal,
+/// and the result sent back when the form is submitted.
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct FormPayload {
+    pub fields: [Option<FormField>; MAX_FORM_FIELDS],
+    /// index of the field that has the cursor when the form is shown
+    pub focus: u32,
+}
+impl FormPayload {
+    pub fn new() -> Self {
+        FormPayload { fields: [None; MAX_FORM_FIELDS], focus: 0 }
+    }
+    /// returns false if the form is already full
+    pub fn add(&mut self, label: &str, kind: FormFieldKind) -> bool {
+        for maybe_field in self.fields.iter_mut() {
+            if maybe_field.is_none() {
+                *maybe_field = Some(FormField { label: ItemName::new(label), kind });
+                return true;
+            }
+        }
+        false
+    }
+    pub fn len(&self) -> usize {
+        self.fields.iter().filter(|f| f.is_some()).count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.fields[0].is_none()
+    }
+    /// fields are numbered in the order they were added
+    pub fn field(&self, index: usize) -> Option<&FormField> {
+        self.fields.get(index).and_then(|f| f.as_ref())
+    }
+    /// Ensures that 0's are written to the storage of the text fields, and not optimized out.
+    pub fn volatile_clear(&mut self) {
+        for maybe_field in self.fields.iter_mut() {
+            if let Some(FormField { kind: FormFieldKind::Text(payload), .. }) = maybe_field {
+                payload.volatile_clear();
+            }
+        }
+    }
+}
+
 //#[derive(Debug)]
 pub struct Modal<'a> {
     pub sid: xous::SID,
@@ -0,0 +1,462 @@
+use crate::*;
+
+use graphics_server::api::*;
+
+use xous_ipc::{String, Buffer};
+
+use core::fmt::Write;
+use locales::t;
+#[cfg(feature="tts")]
+use tts_frontend::TtsFrontend;
+
+/// extra room under a text field's value line for the entry underline
+const TEXT_SLOP: i16 = 6;
+
+/// Several labelled fields of mixed types in a single modal. ↑/↓ (or tab) move the cursor between rows,
+/// where a checkbox field has one row per item and every other field has a single row. The form is
+/// submitted from the "OK" row at the bottom, and the whole `FormPayload` is returned to the action target.
+#[derive(Debug)]
+pub struct Form {
+    pub action_conn: xous::CID,
+    pub action_opcode: u32,
+    pub action_payload: FormPayload,
+    pub select_index: i16,
+    /// like `TextEntry`, tracks if keys were hit in a text field so that a cleared field stays empty
+    keys_hit: [bool; MAX_FORM_FIELDS],
+    #[cfg(feature = "tts")]
+    pub tts: TtsFrontend,
+}
+impl Form {
+    pub fn new(action_conn: xous::CID, action_opcode: u32) -> Self {
+        #[cfg(feature="tts")]
+        let tts = TtsFrontend::new(&xous_names::XousNames::new().unwrap()).unwrap();
+        Form {
+            action_conn,
+            action_opcode,
+            action_payload: FormPayload::new(),
+            select_index: 0,
+            keys_hit: [false; MAX_FORM_FIELDS],
+            #[cfg(feature="tts")]
+            tts,
+        }
+    }
+    /// loads a new set of fields, placing the cursor on the first row of the payload's `focus` field
+    pub fn set_payload(&mut self, payload: FormPayload) {
+        self.action_payload = payload;
+        self.keys_hit = [false; MAX_FORM_FIELDS];
+        self.select_index = self.first_row(payload.focus as usize) as i16;
+    }
+    fn rows(field: &FormField) -> usize {
+        match &field.kind {
+            FormFieldKind::CheckBoxes { items, .. } => items.iter().filter(|i| i.is_some()).count(),
+            _ => 1,
+        }
+    }
+    /// total number of cursor rows, including the "OK" row
+    fn row_count(&self) -> usize {
+        self.action_payload.fields.iter().flatten().map(Self::rows).sum::<usize>() + 1
+    }
+    fn first_row(&self, field_index: usize) -> usize {
+        self.action_payload.fields.iter().take(field_index).flatten().map(Self::rows).sum()
+    }
+    /// maps a cursor row onto (field index, item within the field); `None` is the "OK" row
+    fn row_target(&self, row: usize) -> Option<(usize, usize)> {
+        let mut cur = 0;
+        for (index, maybe_field) in self.action_payload.fields.iter().enumerate() {
+            if let Some(field) = maybe_field {
+                let rows = Self::rows(field);
+                if row < cur + rows {
+                    return Some((index, row - cur));
+                }
+                cur += rows;
+            }
+        }
+        None
+    }
+    fn submit(&mut self) {
+        // untouched, empty text fields take on their placeholder, as they do in a `TextEntry`
+        for (index, maybe_field) in self.action_payload.fields.iter_mut().enumerate() {
+            if let Some(FormField { kind: FormFieldKind::Text(payload), .. }) = maybe_field {
+                if payload.content.len() == 0 && !self.keys_hit[index] {
+                    if let Some(placeholder) = payload.placeholder {
+                        payload.content.append(placeholder.to_str()).ok();
+                    }
+                }
+            }
+        }
+        let buf = Buffer::into_buf(self.action_payload).expect("couldn't convert message to payload");
+        buf.send(self.action_conn, self.action_opcode).map(|_| ()).expect("couldn't send action message");
+
+        self.action_payload.volatile_clear();
+        self.keys_hit = [false; MAX_FORM_FIELDS];
+    }
+}
+impl ActionApi for Form {
+    fn set_action_opcode(&mut self, op: u32) {self.action_opcode = op}
+    fn height(&self, glyph_height: i16, margin: i16) -> i16 {
+        /*
+            label
+            ▶  value              <- one line per row, text values are underlined
+            label
+               × item
+               × item
+
+            ▶  OK
+        */
+        let mut lines = 2; // the blank line and the "OK" line
+        let mut slop = 0;
+        for field in self.action_payload.fields.iter().flatten() {
+            lines += 1 + Self::rows(field) as i16;
+            if let FormFieldKind::Text(_) = field.kind {
+                slop += TEXT_SLOP;
+            }
+        }
+        lines * glyph_height + slop + margin * 2 + 5 // some slop needed because of the prompt character
+    }
+    fn redraw(&self, at_height: i16, modal: &Modal) {
+        // prime a textview with the correct general style parameters
+        let mut tv = TextView::new(
+            modal.canvas,
+            TextBounds::BoundingBox(Rectangle::new_coords(0, 0, 1, 1))
+        );
+        tv.ellipsis = true;
+        tv.style = modal.style;
+        tv.invert = false;
+        tv.draw_border= false;
+        tv.margin = Point::new(0, 0,);
+        tv.insertion = None;
+
+        let cursor_x = modal.margin;
+        let select_x = modal.margin + 20;
+        let text_x = modal.margin + 20 + 20;
+
+        let emoji_slop = 2; // tweaked for a non-emoji glyph
+
+        let mut cur_y = at_height;
+        let mut row = 0;
+        let mut do_okay = true;
+        for (index, field) in self.action_payload.fields.iter().enumerate().filter_map(|(i, f)| f.as_ref().map(|f| (i, f))) {
+            // the label
+            tv.text.clear();
+            tv.bounds_computed = None;
+            tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                Point::new(cursor_x, cur_y), Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height)
+            ));
+            write!(tv, "{}", field.label.as_str()).unwrap();
+            modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+            cur_y += modal.line_height;
+
+            for item in 0..Self::rows(field) {
+                if row == self.select_index {
+                    #[cfg(feature="tts")]
+                    {
+                        self.tts.tts_simple(field.label.as_str()).unwrap();
+                    }
+                    // draw the cursor
+                    tv.text.clear();
+                    tv.bounds_computed = None;
+                    tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                        Point::new(cursor_x, cur_y - emoji_slop), Point::new(cursor_x + 36, cur_y - emoji_slop + 36)
+                    ));
+                    write!(tv, "\u{25B6}").unwrap(); // right arrow
+                    modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+                    do_okay = false;
+                }
+                let mut line_height = modal.line_height;
+                tv.text.clear();
+                tv.bounds_computed = None;
+                match &field.kind {
+                    FormFieldKind::Text(payload) => {
+                        if payload.content.len() == 0 && !self.keys_hit[index] {
+                            if let Some(placeholder) = payload.placeholder {
+                                write!(tv, "{}", placeholder.as_str().unwrap_or("")).unwrap();
+                            }
+                        } else {
+                            write!(tv, "{}", payload.as_str()).unwrap();
+                        }
+                        line_height += TEXT_SLOP;
+                        // draw a line for where text gets entered
+                        modal.gam.draw_line(modal.canvas, Line::new_with_style(
+                            Point::new(text_x, cur_y + modal.line_height + 3),
+                            Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height + 3),
+                            DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 1))
+                            ).expect("couldn't draw entry line");
+                    }
+                    FormFieldKind::Radio { .. } => {
+                        write!(tv, "< {} >", field.selected().unwrap_or("")).unwrap();
+                    }
+                    FormFieldKind::Slider { value, .. } => {
+                        write!(tv, "< {} >", value).unwrap();
+                    }
+                    FormFieldKind::CheckBoxes { items, checked } => {
+                        if checked & (1 << item) != 0 {
+                            // draw the check mark
+                            let mut check_tv = TextView::new(modal.canvas, TextBounds::BoundingBox(Rectangle::new(
+                                Point::new(select_x, cur_y - emoji_slop), Point::new(select_x + 36, cur_y + modal.line_height)
+                            )));
+                            check_tv.style = modal.style;
+                            check_tv.draw_border = false;
+                            check_tv.margin = Point::new(0, 0);
+                            write!(check_tv, "\u{d7}").unwrap(); // multiplication sign
+                            modal.gam.post_textview(&mut check_tv).expect("couldn't post tv");
+                        }
+                        // only the `Some` items have rows, and they are packed to the front by the builder
+                        write!(tv, "{}", items[item].as_ref().map(|i| i.as_str()).unwrap_or("")).unwrap();
+                    }
+                }
+                tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                    Point::new(text_x, cur_y), Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height)
+                ));
+                modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+
+                cur_y += line_height;
+                row += 1;
+            }
+        }
+        cur_y += modal.line_height;
+        if do_okay {
+            tv.text.clear();
+            tv.bounds_computed = None;
+            tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                Point::new(cursor_x, cur_y - emoji_slop), Point::new(cursor_x + 36, cur_y - emoji_slop + 36)
+            ));
+            write!(tv, "\u{25B6}").unwrap(); // right arrow emoji. use unicode numbers, because text editors do funny shit with emojis
+            modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+            #[cfg(feature="tts")]
+            {
+                self.tts.tts_blocking(t!("checkbox.select_and_close_tts", xous::LANG)).unwrap();
+            }
+        }
+        // draw the "OK" line
+        tv.text.clear();
+        tv.bounds_computed = None;
+        tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+            Point::new(text_x, cur_y), Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height)
+        ));
+        write!(tv, "{}", t!("radio.select_and_close", xous::LANG)).unwrap();
+        modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+
+        // divider lines
+        modal.gam.draw_line(modal.canvas, Line::new_with_style(
+            Point::new(modal.margin, at_height),
+            Point::new(modal.canvas_width - modal.margin, at_height),
+            DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 1))
+            ).expect("couldn't draw entry line");
+    }
+    fn key_action(&mut self, k: char) -> (Option<ValidatorErr>, bool) {
+        log::trace!("key_action: {}", k);
+        let target = self.row_target(self.select_index as usize);
+        match k {
+            '↑' => {
+                if self.select_index > 0 {
+                    self.select_index -= 1;
+                }
+            }
+            '↓' | '\t' => {
+                if (self.select_index as usize) < self.row_count() - 1 {
+                    self.select_index += 1;
+                }
+            }
+            '∴' | '\u{d}' | '←' | '→' => {
+                let (index, item) = match target {
+                    Some(t) => t,
+                    None => {
+                        if k == '∴' || k == '\u{d}' {
+                            self.submit();
+                            return (None, true)
+                        }
+                        return (None, false)
+                    }
+                };
+                let field = self.action_payload.fields[index].as_mut().expect("cursor row should map to a field");
+                match &mut field.kind {
+                    FormFieldKind::CheckBoxes { checked, .. } => {
+                        *checked ^= 1 << item;
+                        #[cfg(feature="tts")]
+                        {
+                            if *checked & (1 << item) != 0 {
+                                self.tts.tts_blocking(t!("checkbox.check", xous::LANG)).unwrap();
+                            } else {
+                                self.tts.tts_blocking(t!("checkbox.uncheck", xous::LANG)).unwrap();
+                            }
+                        }
+                    }
+                    _ if k == '∴' || k == '\u{d}' => {
+                        // everything other than a checkbox is "entered" by moving on to the next row
+                        self.select_index += 1;
+                    }
+                    FormFieldKind::Text(payload) => {
+                        if payload.content.len() == 0 {
+                            if let Some(placeholder) = payload.placeholder {
+                                payload.content.append(placeholder.to_str()).ok();
+                            }
+                        }
+                    }
+                    FormFieldKind::Radio { items, selected } => {
+                        let count = items.iter().filter(|i| i.is_some()).count() as u32;
+                        if count > 0 {
+                            *selected = if k == '←' {
+                                (*selected + count - 1) % count
+                            } else {
+                                (*selected + 1) % count
+                            };
+                        }
+                    }
+                    FormFieldKind::Slider { min, max, step, value } => {
+                        *value = if k == '←' {
+                            value.saturating_sub(*step).max(*min)
+                        } else {
+                            value.saturating_add(*step).min(*max)
+                        };
+                    }
+                }
+            }
+            '\u{0}' | '\u{f701}' | '\u{f700}' => {
+                // ignore null messages and unhandled navigation keys
+            }
+            _ => {
+                // text entry only applies to text fields
+                if let Some((index, _)) = target {
+                    if let Some(FormField { kind: FormFieldKind::Text(payload), .. }) = self.action_payload.fields[index].as_mut() {
+                        self.keys_hit[index] = true;
+                        #[cfg(feature="tts")]
+                        {
+                            if k == '\u{8}' {
+                                self.tts.tts_blocking(t!("input.delete-tts", xous::LANG)).unwrap();
+                            } else {
+                                self.tts.tts_blocking(&k.to_string()).unwrap();
+                            }
+                        }
+                        if k == '\u{8}' { // backspace
+                            // coded in a conservative manner to avoid temporary allocations that can leave the plaintext on the stack
+                            if payload.content.len() > 0 {
+                                let mut temp_str = String::<256>::from_str(payload.content.as_str().unwrap());
+                                let cur_len = temp_str.as_str().unwrap().chars().count();
+                                let mut c_iter = temp_str.as_str().unwrap().chars();
+                                payload.content.clear();
+                                for _ in 0..cur_len-1 {
+                                    payload.content.push(c_iter.next().unwrap()).unwrap();
+                                }
+                                temp_str.volatile_clear();
+                            }
+                        } else {
+                            payload.content.push(k).ok();
+                        }
+                    }
+                }
+            }
+        }
+        (None, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(names: &[&str]) -> [Option<ItemName>; MAX_ITEMS] {
+        let mut items = [None; MAX_ITEMS];
+        for (item, name) in items.iter_mut().zip(names.iter()) {
+            *item = Some(ItemName::new(name));
+        }
+        items
+    }
+    /// a text field, three checkboxes, a radio and a slider: rows 0, 1..=3, 4, 5, and "OK" on 6
+    fn form() -> Form {
+        let mut payload = FormPayload::new();
+        payload.add("Name", FormFieldKind::Text(TextEntryPayload::new()));
+        payload.add("Options", FormFieldKind::CheckBoxes { items: items(&["upper", "numbers", "symbols"]), checked: 0b001 });
+        payload.add("Kind", FormFieldKind::Radio { items: items(&["login", "note"]), selected: 0 });
+        payload.add("Length", FormFieldKind::Slider { min: 4, max: 10, step: 4, value: 8 });
+        let mut form = Form::new(0, 0);
+        form.set_payload(payload);
+        form
+    }
+
+    #[test]
+    fn rows_map_onto_fields() {
+        let form = form();
+        assert_eq!(form.row_count(), 7);
+        assert_eq!(form.first_row(0), 0);
+        assert_eq!(form.first_row(1), 1);
+        assert_eq!(form.first_row(2), 4);
+        assert_eq!(form.first_row(3), 5);
+        assert_eq!(form.row_target(0), Some((0, 0)));
+        assert_eq!(form.row_target(1), Some((1, 0)));
+        assert_eq!(form.row_target(3), Some((1, 2)));
+        assert_eq!(form.row_target(4), Some((2, 0)));
+        assert_eq!(form.row_target(5), Some((3, 0)));
+        assert_eq!(form.row_target(6), None);
+    }
+
+    #[test]
+    fn focus_places_the_cursor() {
+        let mut form = form();
+        let mut payload = form.action_payload;
+        payload.focus = 2;
+        form.set_payload(payload);
+        assert_eq!(form.select_index, 4);
+    }
+
+    #[test]
+    fn cursor_stays_within_the_rows() {
+        let mut form = form();
+        assert_eq!(form.key_action('↑'), (None, false));
+        assert_eq!(form.select_index, 0);
+        for _ in 0..10 {
+            form.key_action('↓');
+        }
+        assert_eq!(form.select_index, 6);
+        form.key_action('↑');
+        form.key_action('\t');
+        assert_eq!(form.select_index, 6);
+    }
+
+    #[test]
+    fn checkbox_rows_toggle_their_own_item() {
+        let mut form = form();
+        form.select_index = 2;
+        form.key_action('∴');
+        form.select_index = 1;
+        form.key_action('→');
+        assert_eq!(form.action_payload.field(1).unwrap().checked(), vec!["numbers"]);
+        // a checkbox is toggled in place, rather than moving on to the next row
+        assert_eq!(form.select_index, 1);
+    }
+
+    #[test]
+    fn radio_rows_wrap_around() {
+        let mut form = form();
+        form.select_index = 4;
+        form.key_action('←');
+        assert_eq!(form.action_payload.field(2).unwrap().selected(), Some("note"));
+        form.key_action('→');
+        assert_eq!(form.action_payload.field(2).unwrap().selected(), Some("login"));
+        form.key_action('∴');
+        assert_eq!(form.select_index, 5);
+    }
+
+    #[test]
+    fn slider_rows_clamp_to_their_range() {
+        let mut form = form();
+        form.select_index = 5;
+        form.key_action('→');
+        assert_eq!(form.action_payload.field(3).unwrap().value(), Some(10));
+        form.key_action('←');
+        assert_eq!(form.action_payload.field(3).unwrap().value(), Some(6));
+        form.key_action('←');
+        assert_eq!(form.action_payload.field(3).unwrap().value(), Some(4));
+    }
+
+    #[test]
+    fn typing_only_reaches_text_fields() {
+        let mut form = form();
+        for k in "ab\u{8}c".chars() {
+            form.key_action(k);
+        }
+        assert_eq!(form.action_payload.field(0).unwrap().text(), Some("ac"));
+        form.select_index = 4;
+        form.key_action('x');
+        assert_eq!(form.action_payload.field(2).unwrap().selected(), Some("login"));
+    }
+}
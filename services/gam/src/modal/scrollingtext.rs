@@ -0,0 +1,81 @@
+use crate::*;
+
+use graphics_server::api::*;
+
+use std::cell::RefCell;
+
+/// A modal action that shows a long document in a scrolling `TextViewer`. '↑' and '↓' scroll by a line,
+/// '←' and '→' by a page, and any other key closes the modal.
+#[derive(Debug)]
+pub struct ScrollingText {
+    pub action_conn: xous::CID,
+    pub action_opcode: u32,
+    /// number of lines of text in view at once
+    pub lines: i16,
+    text: std::string::String,
+    /// created on the first redraw, once the modal's canvas is known. `ActionApi::redraw()` only gets a shared
+    /// reference, but drawing lays out text that hasn't been seen yet and caches its height.
+    viewer: RefCell<Option<TextViewer>>,
+    /// navigation keys that arrived since the last redraw; scrolling needs the GAM, which is only at hand there
+    pending: RefCell<Vec<char>>,
+}
+impl ScrollingText {
+    pub fn new(action_conn: xous::CID, action_opcode: u32, text: &str, lines: i16) -> Self {
+        ScrollingText {
+            action_conn,
+            action_opcode,
+            lines,
+            text: text.to_string(),
+            viewer: RefCell::new(None),
+            pending: RefCell::new(Vec::new()),
+        }
+    }
+}
+impl ActionApi for ScrollingText {
+    fn set_action_opcode(&mut self, op: u32) {
+        self.action_opcode = op
+    }
+    fn height(&self, glyph_height: i16, margin: i16) -> i16 {
+        glyph_height * self.lines + margin * 2
+    }
+    fn redraw(&self, at_height: i16, modal: &Modal) {
+        let area = Rectangle::new_coords(
+            modal.margin,
+            at_height + modal.margin,
+            modal.canvas_width - modal.margin,
+            at_height + modal.margin + modal.line_height * self.lines,
+        );
+        let mut viewer = self.viewer.borrow_mut();
+        let viewer = viewer.get_or_insert_with(|| {
+            let mut viewer = TextViewer::new(modal.canvas, area, modal.style);
+            viewer.set_text(&self.text);
+            viewer
+        });
+        viewer.set_area(area);
+        viewer.set_style(modal.style);
+        for key in self.pending.borrow_mut().drain(..) {
+            viewer.key_action(&modal.gam, key).expect("couldn't scroll text");
+        }
+        viewer.redraw(&modal.gam).expect("couldn't draw text");
+    }
+    fn key_action(&mut self, k: char) -> (Option<ValidatorErr>, bool) {
+        log::trace!("key_action: {}", k);
+        match k {
+            '\u{0}' => {
+                // ignore null messages
+            }
+            '↑' | '↓' | '←' | '→' => {
+                self.pending.get_mut().push(k);
+            }
+            _ => {
+                send_message(
+                    self.action_conn,
+                    xous::Message::new_scalar(self.action_opcode as usize, k as u32 as usize, 0, 0, 0),
+                )
+                .expect("couldn't pass on dismissal");
+                return (None, true);
+            }
+        }
+        (None, false)
+    }
+}
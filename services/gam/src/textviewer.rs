@@ -0,0 +1,586 @@
+//! A scrolling viewer for documents that are too long to fit in a single `TextView`, such as release notes,
+//! a transcript, or a certificate dump.
+//!
+//! The document is broken into blocks -- one per paragraph, with long paragraphs split at a space -- and each
+//! block is laid out by the graphics server the first time it comes into view. The height of each block is
+//! remembered, so scrolling, redrawing and appending to the document only ever lay out the blocks that
+//! haven't been seen before. The heights are thrown away if the width or the style of the text changes.
+//!
+//! The viewer draws into a rectangular area of a canvas that the caller owns, so it can be used by an app on
+//! its content canvas, or by a modal (see `modal::ScrollingText`).
+
+use crate::Gam;
+use graphics_server::api::*;
+
+use core::fmt::Write;
+
+/// Longest block of text handed to the graphics server in one go. Anything longer is split at a space, which
+/// keeps each layout cheap, and well within the capacity of a `TextView`.
+const MAX_BLOCK_LEN: usize = 512;
+/// Space on the left of the text, where the match of the latest search is marked
+const GUTTER_WIDTH: i16 = 4;
+/// Space on the right of the text, where the scroll position is shown
+const SCROLLBAR_WIDTH: i16 = 3;
+/// Height of the layout area used to measure blocks. Blocks are never anywhere near this tall.
+const MEASURE_HEIGHT: i16 = i16::MAX;
+
+/// A run of text that is laid out in one piece. `start..end` is a range of bytes in the document, and excludes
+/// any line break that ends the paragraph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Block {
+    start: usize,
+    end: usize,
+    /// height in pixels, once the block has been laid out at the current width and style
+    height: Option<i32>,
+}
+impl Block {
+    fn new(start: usize, end: usize) -> Self {
+        Block { start, end, height: None }
+    }
+}
+
+/// Lays out text to find out how tall it is. The GAM asks the graphics server; anything else that lays text
+/// out the same way (such as a stand-in font in a test) can take its place.
+pub trait TextMeasure {
+    /// height in pixels of one line of text in `style`
+    fn line_height(&self, style: GlyphStyle) -> Result<i32, xous::Error>;
+    /// height in pixels of `text` in `style` when it is wrapped to `width`, or `None` if it couldn't be laid out
+    fn text_height(&self, canvas: Gid, style: GlyphStyle, width: i16, text: &str) -> Result<Option<i32>, xous::Error>;
+}
+impl TextMeasure for Gam {
+    fn line_height(&self, style: GlyphStyle) -> Result<i32, xous::Error> {
+        Ok(self.glyph_height_hint(style)? as i32)
+    }
+    fn text_height(&self, canvas: Gid, style: GlyphStyle, width: i16, text: &str) -> Result<Option<i32>, xous::Error> {
+        let mut tv = TextView::new(canvas, TextBounds::GrowableFromTl(Point::new(0, 0), width as u16));
+        tv.style = style;
+        tv.margin = Point::new(0, 0);
+        tv.draw_border = false;
+        tv.ellipsis = false;
+        // lay out against a tall area of our own, rather than the canvas, so the height isn't limited by the screen
+        tv.clip_rect = Some(Rectangle::new(Point::new(0, 0), Point::new(width, MEASURE_HEIGHT)));
+        write!(tv, "{}", text).unwrap();
+        self.bounds_compute_textview(&mut tv)?;
+        Ok(tv.bounds_computed.map(|bounds| (bounds.br.y - bounds.tl.y) as i32))
+    }
+}
+
+/// Where the latest search matched
+#[derive(Debug, Copy, Clone)]
+struct Found {
+    /// byte offset of the match in the document
+    pos: usize,
+    /// distance in pixels from the top of its block to the line holding the match
+    offset: i32,
+}
+
+/// Break `text[from..]` into blocks of at most `max_len` bytes. A line break ends a paragraph; a line break at the
+/// very end of the text does not start another, empty, paragraph.
+fn split_blocks(text: &str, from: usize, max_len: usize) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut start = from;
+    while start < text.len() {
+        let para_end = match text[start..].find('\n') {
+            Some(offset) => start + offset,
+            None => text.len(),
+        };
+        while para_end - start > max_len {
+            let mut cut = start + max_len;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            // break at the last space, and leave the space out, so the next block doesn't start with it
+            let (end, next) = match text[start..cut].rfind(' ') {
+                Some(offset) if offset > 0 => (start + offset, start + offset + 1),
+                _ => (cut, cut),
+            };
+            blocks.push(Block::new(start, end));
+            start = next;
+        }
+        blocks.push(Block::new(start, para_end));
+        start = para_end + 1;
+    }
+    blocks
+}
+
+#[derive(Debug)]
+pub struct TextViewer {
+    canvas: Gid,
+    /// where the document is drawn, in the canvas' coordinates
+    area: Rectangle,
+    style: GlyphStyle,
+    text: std::string::String,
+    blocks: Vec<Block>,
+    line_height: Option<i32>,
+    /// index of the block at the top of the view
+    top: usize,
+    /// how many pixels of the top block are scrolled off the top of the view
+    offset: i32,
+    found: Option<Found>,
+}
+
+impl TextViewer {
+    pub fn new(canvas: Gid, area: Rectangle, style: GlyphStyle) -> Self {
+        TextViewer {
+            canvas,
+            area,
+            style,
+            text: std::string::String::new(),
+            blocks: Vec::new(),
+            line_height: None,
+            top: 0,
+            offset: 0,
+            found: None,
+        }
+    }
+    /// Replace the document, and go back to its start
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        self.text.push_str(text);
+        self.blocks = split_blocks(&self.text, 0, MAX_BLOCK_LEN);
+        self.top = 0;
+        self.offset = 0;
+        self.found = None;
+    }
+    /// Add to the end of the document. Only the last paragraph of the existing text is laid out again; the view
+    /// stays where it is (call `end()` to follow the new text).
+    pub fn append(&mut self, text: &str) {
+        let from = match self.blocks.pop() {
+            Some(last) => last.start,
+            None => 0,
+        };
+        self.text.push_str(text);
+        self.blocks.extend(split_blocks(&self.text, from, MAX_BLOCK_LEN));
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    /// Move or resize the area the document is drawn in. The text is laid out again if the width changes.
+    pub fn set_area(&mut self, area: Rectangle) {
+        if area.width() != self.area.width() {
+            self.invalidate_layout();
+        }
+        self.area = area;
+    }
+    pub fn set_style(&mut self, style: GlyphStyle) {
+        if style != self.style {
+            self.style = style;
+            self.line_height = None;
+            self.invalidate_layout();
+        }
+    }
+    fn invalidate_layout(&mut self) {
+        for block in self.blocks.iter_mut() {
+            block.height = None;
+        }
+        // pixel offsets mean nothing once the text is wrapped differently, so keep the top block in view
+        self.offset = 0;
+        if let Some(found) = self.found.as_mut() {
+            found.offset = 0;
+        }
+    }
+
+    fn text_width(&self) -> i16 {
+        self.area.br.x - self.area.tl.x - GUTTER_WIDTH - SCROLLBAR_WIDTH
+    }
+    fn view_height(&self) -> i32 {
+        (self.area.br.y - self.area.tl.y) as i32
+    }
+    fn line_height<M: TextMeasure>(&mut self, gam: &M) -> Result<i32, xous::Error> {
+        if let Some(h) = self.line_height {
+            return Ok(h);
+        }
+        let h = gam.line_height(self.style)?;
+        self.line_height = Some(h);
+        Ok(h)
+    }
+    /// Height in pixels of `text` when it is wrapped to the width of the view
+    fn measure<M: TextMeasure>(&mut self, gam: &M, text: &str) -> Result<i32, xous::Error> {
+        let line_height = self.line_height(gam)?;
+        if text.trim().is_empty() {
+            return Ok(line_height);
+        }
+        match gam.text_height(self.canvas, self.style, self.text_width(), text)? {
+            Some(h) => Ok(h.max(line_height)),
+            None => {
+                log::warn!("couldn't compute the height of a block of text, assuming one line");
+                Ok(line_height)
+            }
+        }
+    }
+    fn block_height<M: TextMeasure>(&mut self, gam: &M, index: usize) -> Result<i32, xous::Error> {
+        if let Some(h) = self.blocks[index].height {
+            return Ok(h);
+        }
+        let block = self.blocks[index];
+        // the text is copied out so that `measure()` can borrow self mutably
+        let text = self.text[block.start..block.end].to_string();
+        let h = self.measure(gam, &text)?;
+        self.blocks[index].height = Some(h);
+        Ok(h)
+    }
+
+    /// How far the view can scroll down, up to `limit` pixels, before the end of the document reaches the bottom
+    fn room_below<M: TextMeasure>(&mut self, gam: &M, limit: i32) -> Result<i32, xous::Error> {
+        let wanted = self.view_height() + limit;
+        let mut content = -self.offset;
+        let mut index = self.top;
+        while content < wanted && index < self.blocks.len() {
+            content += self.block_height(gam, index)?;
+            index += 1;
+        }
+        Ok((content - self.view_height()).max(0).min(limit))
+    }
+    /// Scroll by `dy` pixels: positive values move further into the document. Scrolling stops at either end.
+    pub fn scroll_by<M: TextMeasure>(&mut self, gam: &M, dy: i32) -> Result<(), xous::Error> {
+        if self.blocks.is_empty() {
+            return Ok(());
+        }
+        if dy > 0 {
+            self.offset += self.room_below(gam, dy)?;
+            while self.top + 1 < self.blocks.len() {
+                let h = self.block_height(gam, self.top)?;
+                if self.offset < h {
+                    break;
+                }
+                self.offset -= h;
+                self.top += 1;
+            }
+        } else {
+            self.offset += dy;
+            while self.offset < 0 && self.top > 0 {
+                self.top -= 1;
+                self.offset += self.block_height(gam, self.top)?;
+            }
+            self.offset = self.offset.max(0);
+        }
+        Ok(())
+    }
+    /// Scroll back if there isn't enough of the document below the top of the view to fill it, as can happen
+    /// after jumping to a search match near the end, or after the view is made taller.
+    fn settle<M: TextMeasure>(&mut self, gam: &M) -> Result<(), xous::Error> {
+        let view_height = self.view_height();
+        let mut content = -self.offset;
+        let mut index = self.top;
+        while content < view_height && index < self.blocks.len() {
+            content += self.block_height(gam, index)?;
+            index += 1;
+        }
+        if content < view_height {
+            self.scroll_by(gam, content - view_height)?;
+        }
+        Ok(())
+    }
+    pub fn line_down<M: TextMeasure>(&mut self, gam: &M) -> Result<(), xous::Error> {
+        let dy = self.line_height(gam)?;
+        self.scroll_by(gam, dy)
+    }
+    pub fn line_up<M: TextMeasure>(&mut self, gam: &M) -> Result<(), xous::Error> {
+        let dy = self.line_height(gam)?;
+        self.scroll_by(gam, -dy)
+    }
+    /// Scroll down by the height of the view, keeping one line of the previous page in view
+    pub fn page_down<M: TextMeasure>(&mut self, gam: &M) -> Result<(), xous::Error> {
+        let dy = (self.view_height() - self.line_height(gam)?).max(1);
+        self.scroll_by(gam, dy)
+    }
+    pub fn page_up<M: TextMeasure>(&mut self, gam: &M) -> Result<(), xous::Error> {
+        let dy = (self.view_height() - self.line_height(gam)?).max(1);
+        self.scroll_by(gam, -dy)
+    }
+    pub fn home(&mut self) {
+        self.top = 0;
+        self.offset = 0;
+    }
+    /// Scroll to the end of the document. Only the blocks that end up in view are laid out.
+    pub fn end<M: TextMeasure>(&mut self, gam: &M) -> Result<(), xous::Error> {
+        if self.blocks.is_empty() {
+            return Ok(());
+        }
+        self.top = self.blocks.len() - 1;
+        self.offset = self.block_height(gam, self.top)?;
+        let dy = self.view_height();
+        self.scroll_by(gam, -dy)
+    }
+    /// Returns true if the document is scrolled all the way to the end
+    pub fn at_end<M: TextMeasure>(&mut self, gam: &M) -> Result<bool, xous::Error> {
+        Ok(self.room_below(gam, 1)? == 0)
+    }
+
+    /// Search for the next occurrence of `needle`, and scroll the line holding it to the top of the view. The
+    /// search starts after the previous match (or at the top of the view), and wraps around to the start of the
+    /// document. Returns false if there is no match anywhere.
+    pub fn find<M: TextMeasure>(&mut self, gam: &M, needle: &str) -> Result<bool, xous::Error> {
+        if needle.is_empty() || self.blocks.is_empty() {
+            return Ok(false);
+        }
+        let from = match self.found {
+            Some(found) => match self.text[found.pos..].chars().next() {
+                Some(c) => found.pos + c.len_utf8(),
+                None => self.text.len(),
+            },
+            None => self.blocks[self.top].start,
+        };
+        let pos = match self.text[from..].find(needle) {
+            Some(offset) => from + offset,
+            None => match self.text.find(needle) {
+                Some(pos) => pos,
+                None => {
+                    self.found = None;
+                    return Ok(false);
+                }
+            },
+        };
+        // the last block that starts at or before the match holds it
+        let index = self.blocks.iter().rposition(|b| b.start <= pos).unwrap_or(0);
+        // measure the block up to the end of the matched word, to find out which line the match wraps onto
+        let block = self.blocks[index];
+        let offset = if pos > block.start {
+            let word_end = match self.text[pos..block.end].find(char::is_whitespace) {
+                Some(offset) => pos + offset,
+                None => block.end,
+            };
+            let prefix = self.text[block.start..word_end].to_string();
+            self.measure(gam, &prefix)? - self.line_height(gam)?
+        } else {
+            0
+        };
+        self.found = Some(Found { pos, offset });
+        self.top = index;
+        self.offset = 0;
+        self.scroll_by(gam, offset)?;
+        self.settle(gam)?;
+        Ok(true)
+    }
+    /// Forget the latest search, so the next one starts from the top of the view
+    pub fn clear_find(&mut self) {
+        self.found = None;
+    }
+
+    /// Handle the navigation keys: '↑' and '↓' scroll by a line, '←' and '→' by a page. Returns false for any other
+    /// key, so the caller can handle it. Call `redraw()` afterwards to show the result.
+    pub fn key_action<M: TextMeasure>(&mut self, gam: &M, key: char) -> Result<bool, xous::Error> {
+        match key {
+            '↑' => self.line_up(gam)?,
+            '↓' => self.line_down(gam)?,
+            '←' => self.page_up(gam)?,
+            '→' => self.page_down(gam)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Draw the part of the document that is in view. Like any other drawing, it only shows up on the screen
+    /// once the caller asks the GAM to `redraw()`.
+    pub fn redraw(&mut self, gam: &Gam) -> Result<(), xous::Error> {
+        self.settle(gam)?;
+        let light = DrawStyle::new(PixelColor::Light, PixelColor::Light, 0);
+        let dark = DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 0);
+        gam.draw_rectangle(self.canvas, Rectangle::new_with_style(self.area.tl, self.area.br, light))?;
+        let view_height = self.view_height();
+        let line_height = self.line_height(gam)?;
+        let left = self.area.tl.x + GUTTER_WIDTH;
+        let width = self.text_width();
+
+        // `y` is the top of the current block, relative to the top of the view
+        let mut y = -self.offset;
+        let mut index = self.top;
+        while y < view_height && index < self.blocks.len() {
+            let h = self.block_height(gam, index)?;
+            let block = self.blocks[index];
+            if !self.text[block.start..block.end].trim().is_empty() {
+                let mut tv = TextView::new(self.canvas,
+                    TextBounds::GrowableFromTl(Point::new(left, self.area.tl.y + y as i16), width as u16));
+                tv.style = self.style;
+                tv.margin = Point::new(0, 0);
+                tv.draw_border = false;
+                tv.clear_area = false; // the whole area was cleared above
+                tv.ellipsis = false;
+                write!(tv, "{}", &self.text[block.start..block.end]).unwrap();
+                gam.post_textview_clipped(&mut tv, self.area)?;
+            }
+            if let Some(found) = self.found {
+                let mark = y + found.offset;
+                if block.start <= found.pos && found.pos <= block.end && mark >= 0 && mark + line_height <= view_height {
+                    let top = self.area.tl.y + mark as i16;
+                    gam.draw_rectangle(self.canvas, Rectangle::new_with_style(
+                        Point::new(self.area.tl.x, top),
+                        Point::new(self.area.tl.x + GUTTER_WIDTH / 2, top + line_height as i16 - 1),
+                        dark))?;
+                }
+            }
+            y += h;
+            index += 1;
+        }
+
+        // show where the view is in the document, unless all of it is in view
+        let everything_shown = self.top == 0 && self.offset == 0 && index == self.blocks.len() && y <= view_height;
+        if !everything_shown {
+            let total = self.text.len().max(1);
+            let first = self.blocks[self.top].start;
+            let last = self.blocks[index - 1].end;
+            let min_thumb = (line_height / 2).max(2);
+            let thumb_top = (view_height as usize * first / total) as i32;
+            let thumb_bottom = ((view_height as usize * last / total) as i32).max(thumb_top + min_thumb).min(view_height);
+            gam.draw_rectangle(self.canvas, Rectangle::new_with_style(
+                Point::new(self.area.br.x - SCROLLBAR_WIDTH + 1, self.area.tl.y + thumb_top as i16),
+                Point::new(self.area.br.x, self.area.tl.y + thumb_bottom as i16),
+                dark))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A font where every character is `CHAR_WIDTH` wide and lines are `LINE_HEIGHT` tall; wraps at any character
+    const CHAR_WIDTH: usize = 10;
+    const LINE_HEIGHT: i32 = 10;
+    #[derive(Default)]
+    struct Mono {
+        /// number of blocks laid out so far
+        measured: core::cell::Cell<usize>,
+    }
+    impl TextMeasure for Mono {
+        fn line_height(&self, _style: GlyphStyle) -> Result<i32, xous::Error> {
+            Ok(LINE_HEIGHT)
+        }
+        fn text_height(&self, _canvas: Gid, _style: GlyphStyle, width: i16, text: &str) -> Result<Option<i32>, xous::Error> {
+            self.measured.set(self.measured.get() + 1);
+            let per_line = width as usize / CHAR_WIDTH;
+            Ok(Some(((text.chars().count() + per_line - 1) / per_line) as i32 * LINE_HEIGHT))
+        }
+    }
+    /// A view 100 pixels tall with room for 10 characters per line
+    fn viewer(text: &str) -> TextViewer {
+        let mut viewer = TextViewer::new(Gid::new([0; 4]), area(100), GlyphStyle::Regular);
+        viewer.set_text(text);
+        viewer
+    }
+    fn area(height: i16) -> Rectangle {
+        Rectangle::new_coords(0, 0, 10 * CHAR_WIDTH as i16 + GUTTER_WIDTH + SCROLLBAR_WIDTH, height)
+    }
+    /// `count` paragraphs of three lines (30 pixels) each
+    fn paragraphs(count: usize) -> std::string::String {
+        vec!["x".repeat(25); count].join("\n")
+    }
+    /// distance in pixels from the top of the document to the top of the view
+    fn position(viewer: &TextViewer) -> i32 {
+        viewer.top as i32 * 3 * LINE_HEIGHT + viewer.offset
+    }
+
+    #[test]
+    fn scrolling_stops_at_either_end() {
+        let mono = Mono::default();
+        let mut viewer = viewer(&paragraphs(10));
+        assert_eq!(viewer.room_below(&mono, 50).unwrap(), 50);
+        assert_eq!(viewer.room_below(&mono, 1000).unwrap(), 200);
+        viewer.scroll_by(&mono, 45).unwrap();
+        assert_eq!((viewer.top, viewer.offset), (1, 15));
+        viewer.scroll_by(&mono, 1000).unwrap();
+        assert_eq!((viewer.top, viewer.offset), (6, 20));
+        assert_eq!(viewer.room_below(&mono, 1000).unwrap(), 0);
+        assert!(viewer.at_end(&mono).unwrap());
+        viewer.scroll_by(&mono, -25).unwrap();
+        assert_eq!((viewer.top, viewer.offset), (5, 25));
+        assert!(!viewer.at_end(&mono).unwrap());
+        viewer.scroll_by(&mono, -1000).unwrap();
+        assert_eq!((viewer.top, viewer.offset), (0, 0));
+    }
+
+    #[test]
+    fn short_documents_dont_scroll() {
+        let mono = Mono::default();
+        let mut viewer = viewer(&paragraphs(2));
+        viewer.scroll_by(&mono, 30).unwrap();
+        assert_eq!((viewer.top, viewer.offset), (0, 0));
+        assert!(viewer.at_end(&mono).unwrap());
+        viewer.end(&mono).unwrap();
+        assert_eq!((viewer.top, viewer.offset), (0, 0));
+    }
+
+    #[test]
+    fn end_only_lays_out_the_blocks_in_view() {
+        let mono = Mono::default();
+        let mut viewer = viewer(&paragraphs(100));
+        viewer.end(&mono).unwrap();
+        assert_eq!(position(&viewer), 100 * 30 - 100);
+        assert_eq!(mono.measured.get(), 4);
+        assert!(viewer.at_end(&mono).unwrap());
+    }
+
+    #[test]
+    fn settle_fills_a_view_that_grew() {
+        let mono = Mono::default();
+        let mut viewer = viewer(&paragraphs(10));
+        viewer.end(&mono).unwrap();
+        viewer.set_area(area(200));
+        viewer.settle(&mono).unwrap();
+        assert_eq!(position(&viewer), 300 - 200);
+        // there's nothing to do if the view is already full
+        viewer.settle(&mono).unwrap();
+        assert_eq!(position(&viewer), 300 - 200);
+    }
+
+    #[test]
+    fn find_scrolls_to_the_line_of_the_match() {
+        let mono = Mono::default();
+        // the first paragraph wraps as "aaaa bbbb ", "cccc dddd ", "eeee"
+        let mut viewer = viewer(&format!("aaaa bbbb cccc dddd eeee\n{}\ndddd", paragraphs(10)));
+        assert!(viewer.find(&mono, "dddd").unwrap());
+        assert_eq!(viewer.found.unwrap().pos, 15);
+        assert_eq!(viewer.found.unwrap().offset, LINE_HEIGHT);
+        assert_eq!((viewer.top, viewer.offset), (0, LINE_HEIGHT));
+
+        // the next search starts after the previous match; a match at the start of a block is on its first line
+        assert!(viewer.find(&mono, "dddd").unwrap());
+        assert_eq!(viewer.found.unwrap().pos, viewer.text().len() - 4);
+        assert_eq!(viewer.found.unwrap().offset, 0);
+        // it's in the last block, so the view is settled back to keep it full
+        assert_eq!((viewer.top, viewer.offset), (8, 0));
+        assert!(viewer.at_end(&mono).unwrap());
+
+        // and then it wraps around to the start
+        assert!(viewer.find(&mono, "dddd").unwrap());
+        assert_eq!(viewer.found.unwrap().pos, 15);
+
+        assert!(!viewer.find(&mono, "zzzz").unwrap());
+        assert!(viewer.found.is_none());
+    }
+
+    fn ranges(text: &str, max_len: usize) -> Vec<&str> {
+        split_blocks(text, 0, max_len).iter().map(|b| &text[b.start..b.end]).collect()
+    }
+
+    #[test]
+    fn paragraphs_become_blocks() {
+        assert_eq!(ranges("one\ntwo\n\nthree", 100), vec!["one", "two", "", "three"]);
+        // a final line break doesn't start an empty paragraph
+        assert_eq!(ranges("one\ntwo\n", 100), vec!["one", "two"]);
+        assert_eq!(ranges("\n\n", 100), vec!["", ""]);
+        assert!(ranges("", 100).is_empty());
+    }
+
+    #[test]
+    fn long_paragraphs_split_at_spaces() {
+        assert_eq!(ranges("aaa bbb ccc ddd", 8), vec!["aaa bbb", "ccc ddd"]);
+        // with no space to break at, the paragraph is cut at a character boundary
+        assert_eq!(ranges("aaaaaaaaaa", 4), vec!["aaaa", "aaaa", "aa"]);
+        assert_eq!(ranges("ééééé", 3), vec!["é", "é", "é", "é", "é"]);
+    }
+
+    #[test]
+    fn appending_only_resplits_the_last_paragraph() {
+        let mut viewer = TextViewer::new(Gid::new([0; 4]), Rectangle::new_coords(0, 0, 100, 100), GlyphStyle::Regular);
+        viewer.set_text("first\nsecond half");
+        for block in viewer.blocks.iter_mut() {
+            block.height = Some(10);
+        }
+        viewer.append(" and more\nthird");
+        let text = viewer.text();
+        let blocks: Vec<(&str, Option<i32>)> = viewer.blocks.iter().map(|b| (&text[b.start..b.end], b.height)).collect();
+        assert_eq!(blocks, vec![("first", Some(10)), ("second half and more", None), ("third", None)]);
+    }
+}
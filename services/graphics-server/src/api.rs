@@ -116,6 +116,9 @@ pub(crate) enum Opcode {
     /// generates a test pattern
     TestPattern,
 
+    /// saves the current frame as a PNG on the host; only available in hosted mode
+    Screenshot, //(Screenshot),
+
     /// SuspendResume callback
     SuspendResume,
 
@@ -164,6 +167,14 @@ impl ClipObjectList {
     }
 }
 
+/// A request to save the current frame to `path` on the host. `saved` is filled in
+/// by the server.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct Screenshot {
+    pub path: xous_ipc::String<256>,
+    pub saved: bool,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub struct TokenClaim {
     pub token: Option<[u32; 4]>,
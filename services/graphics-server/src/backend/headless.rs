@@ -0,0 +1,65 @@
+#![cfg_attr(not(target_os = "none"), allow(dead_code))]
+
+use crate::api::Point;
+use crate::api::{LINES, WIDTH};
+
+/// Width of the screen in 32-bit words
+const WIDTH_WORDS: usize = 11;
+pub const FB_WIDTH_WORDS: usize = WIDTH_WORDS;
+pub const FB_WIDTH_PIXELS: usize = WIDTH as usize;
+pub const FB_LINES: usize = LINES as usize;
+pub const FB_SIZE: usize = WIDTH_WORDS * LINES as usize; // 44 bytes by 536 lines
+
+/// A display that only exists in memory. It draws exactly what the hardware would, but
+/// never opens a window, so hosted mode can run on machines without one. What has been
+/// drawn can be inspected with `Gfx::screenshot()`.
+pub struct XousDisplay {
+    fb: [u32; FB_SIZE],
+    srfb: [u32; FB_SIZE],
+}
+
+impl XousDisplay {
+    pub fn new() -> XousDisplay {
+        log::info!("running headless: nothing will be shown on screen");
+        XousDisplay {
+            fb: [0xFFFF_FFFF; FB_SIZE],
+            srfb: [0u32; FB_SIZE],
+        }
+    }
+    pub fn set_devboot(&mut self, _ena: bool) {}
+    pub fn suspend(&self) {}
+    pub fn resume(&self) {}
+
+    pub fn stash(&mut self) {
+        self.srfb.copy_from_slice(&self.fb);
+    }
+    pub fn pop(&mut self) {
+        self.fb[FB_WIDTH_WORDS * 32..].copy_from_slice(&self.srfb[FB_WIDTH_WORDS * 32..]);
+    }
+
+    pub fn screen_size(&self) -> Point {
+        Point::new(WIDTH, LINES)
+    }
+
+    pub fn blit_screen(&mut self, bmp: &[u32]) {
+        for (dest, src) in self.fb.iter_mut().zip(bmp.iter()) {
+            *dest = *src;
+        }
+    }
+    pub fn as_slice(&self) -> &[u32] {
+        &self.fb
+    }
+
+    pub fn native_buffer(&mut self) -> &mut [u32; FB_SIZE] {
+        &mut self.fb
+    }
+
+    pub fn redraw(&mut self) {
+        // there is no panel to send dirty lines to, so just forget which ones were dirty
+        for line in self.fb.chunks_mut(FB_WIDTH_WORDS) {
+            line[FB_WIDTH_WORDS - 1] &= 0x0000_FFFF;
+        }
+    }
+
+    pub fn update(&mut self) {}
+}
@@ -1,8 +1,18 @@
-#[cfg(any(windows, unix))]
+#[cfg(all(any(windows, unix), not(feature = "headless")))]
 mod minifb;
-#[cfg(any(windows, unix))]
+#[cfg(all(any(windows, unix), not(feature = "headless")))]
 pub use crate::backend::minifb::*;
 
+#[cfg(all(any(windows, unix), feature = "headless"))]
+mod headless;
+#[cfg(all(any(windows, unix), feature = "headless"))]
+pub use crate::backend::headless::*;
+
+#[cfg(any(windows, unix))]
+mod screenshot;
+#[cfg(any(windows, unix))]
+pub use crate::backend::screenshot::*;
+
 #[cfg(any(target_os = "none", target_os = "xous"))]
 mod betrusted;
 #[cfg(any(target_os = "none", target_os = "xous"))]
@@ -0,0 +1,66 @@
+use super::{FB_LINES, FB_WIDTH_PIXELS, FB_WIDTH_WORDS};
+
+/// Grey level of a pixel whose bit is set in the frame buffer
+const LIGHT_GREY: u8 = 0xFF;
+/// Grey level of a pixel whose bit is clear in the frame buffer
+const DARK_GREY: u8 = 0x00;
+
+/// Convert a 1-bit frame buffer into 8-bit grey levels, one byte per pixel, row by row.
+/// The bits past the right edge of each line hold the dirty flags, and are skipped.
+pub fn frame_to_grey(fb: &[u32]) -> Vec<u8> {
+    let mut grey = Vec::with_capacity(FB_WIDTH_PIXELS * FB_LINES);
+    for line in fb.chunks(FB_WIDTH_WORDS).take(FB_LINES) {
+        for x in 0..FB_WIDTH_PIXELS {
+            grey.push(if line[x / 32] & (1 << (x % 32)) != 0 {
+                LIGHT_GREY
+            } else {
+                DARK_GREY
+            });
+        }
+    }
+    grey
+}
+
+/// Save a frame buffer as a greyscale PNG on the host
+pub fn save_png(path: &str, fb: &[u32]) -> std::io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(
+        std::io::BufWriter::new(file),
+        FB_WIDTH_PIXELS as u32,
+        FB_LINES as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    writer
+        .write_image_data(&frame_to_grey(fb))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FB_SIZE;
+
+    #[test]
+    fn grey_frame_follows_the_bits_and_skips_dirty_flags() {
+        let mut fb = [0u32; FB_SIZE];
+        // light up the first pixel of line 0 and the last pixel of line 1
+        fb[0] = 1;
+        let last = FB_WIDTH_PIXELS - 1;
+        fb[FB_WIDTH_WORDS + last / 32] |= 1 << (last % 32);
+        // dirty flag for line 2, which is not a pixel
+        fb[2 * FB_WIDTH_WORDS + FB_WIDTH_WORDS - 1] |= 0x1_0000;
+
+        let grey = frame_to_grey(&fb);
+        assert_eq!(grey.len(), FB_WIDTH_PIXELS * FB_LINES);
+        assert_eq!(grey[0], LIGHT_GREY);
+        assert_eq!(grey[1], DARK_GREY);
+        assert_eq!(grey[2 * FB_WIDTH_PIXELS - 1], LIGHT_GREY);
+        assert!(grey[2 * FB_WIDTH_PIXELS..3 * FB_WIDTH_PIXELS]
+            .iter()
+            .all(|&p| p == DARK_GREY));
+    }
+}
@@ -269,6 +269,29 @@ impl Gfx {
         .expect("couldn't self test");
     }
 
+    /// Save what is currently on the screen as a greyscale PNG at `path` on the host.
+    /// Only hosted builds can do this; on hardware it returns `UnhandledSyscall`.
+    pub fn screenshot(&self, path: &str) -> Result<(), xous::Error> {
+        let request = api::Screenshot {
+            path: xous_ipc::String::from_str(path),
+            saved: false,
+        };
+        if request.path.as_str().unwrap_or("") != path {
+            return Err(xous::Error::InvalidString);
+        }
+        let mut buf = Buffer::into_buf(request).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Screenshot.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<api::Screenshot, _>().unwrap();
+        if response.saved {
+            Ok(())
+        } else if cfg!(any(target_os = "none", target_os = "xous")) {
+            Err(xous::Error::UnhandledSyscall)
+        } else {
+            Err(xous::Error::InternalError)
+        }
+    }
+
     pub fn stash(&self, blocking: bool) {
         if blocking {
             send_message(
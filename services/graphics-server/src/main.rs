@@ -134,10 +134,15 @@ fn wrapped_main() -> ! {
     let sid = xns
         .register_name(api::SERVER_NAME_GFX, Some(2))
         .expect("can't register server");
-    #[cfg(not(any(target_os = "none", target_os = "xous")))]
+    #[cfg(all(not(any(target_os = "none", target_os = "xous")), not(feature = "headless")))]
     let sid = xns
         .register_name(api::SERVER_NAME_GFX, Some(1))
         .expect("can't register server");
+    // headless mode leaves room for a test harness to connect and take screenshots
+    #[cfg(all(not(any(target_os = "none", target_os = "xous")), feature = "headless"))]
+    let sid = xns
+        .register_name(api::SERVER_NAME_GFX, Some(2))
+        .expect("can't register server");
 
     draw_boot_logo(&mut display);
 
@@ -610,6 +615,26 @@ fn wrapped_main() -> ! {
 
                     xous::return_scalar(msg.sender, duration).expect("couldn't ack test pattern");
                 }),
+                Some(Opcode::Screenshot) => {
+                    let mut buffer = unsafe {
+                        Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
+                    };
+                    let mut request = buffer.to_original::<api::Screenshot, _>().unwrap();
+                    #[cfg(not(any(target_os = "none", target_os = "xous")))]
+                    {
+                        let path = request.path.as_str().unwrap_or("");
+                        match backend::save_png(path, display.as_slice()) {
+                            Ok(()) => request.saved = true,
+                            Err(e) => log::error!("couldn't save screenshot to {}: {:?}", path, e),
+                        }
+                    }
+                    #[cfg(any(target_os = "none", target_os = "xous"))]
+                    {
+                        log::warn!("screenshots are only available in hosted mode");
+                        request.saved = false;
+                    }
+                    buffer.replace(request).unwrap();
+                }
                 Some(Opcode::Stash) => {
                     display.stash();
                     match msg.body { // ack the message if it's a blocking scalar
@@ -16,6 +16,23 @@ impl Default for LogRecord {
     }
 }
 
+/// The message ID of a `PanicReport` sent to the panic listener
+pub const PANIC_REPORT_ID: usize = 0xffff_0001;
+
+/// The message ID of the scalar sent to the spill listener when records should be
+/// read out before they are evicted. `arg1` and `arg2` are the low and high words
+/// of the newest record's sequence number.
+pub const SPILL_READY_ID: usize = 0xffff_0002;
+
+/// The text of one panic, forwarded to the panic listener in a page of memory
+/// that is moved to it. The text may have been cut short.
+#[repr(C, align(4096))]
+pub struct PanicReport {
+    pub pid: u32,
+    pub length: u32,
+    pub text: [u8; 4088],
+}
+
 #[derive(Debug, PartialEq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub enum Opcode {
     /// A `LogRecord` message, delivering structured log output
@@ -73,4 +90,139 @@ pub enum Opcode {
 
     /// Enable receiving messages when the system is resumed from sleep.
     EnableRx = 2000,
+
+    /// Forward the text of every panic to the server whose SID is in the four
+    /// scalar arguments, as a `PanicReport`. This replaces any earlier listener.
+    /// Only the supervisor may send this, and the server must be its own.
+    SetPanicListener = 3000,
+
+    /// Change which records are printed and stored. `arg1` is a PID, or 0 to set
+    /// the default for every process without its own filter, and `arg2` is a
+    /// `log::LevelFilter` as a `usize`. Only the log admin may send this, as a
+    /// blocking scalar that returns 0 on success and 1 if it was refused.
+    SetLevelFilter = 4,
+
+    /// Read stored records into a `RecordPage` that is lent mutably. The log admin
+    /// may read every process's records; anyone else only gets their own.
+    ReadRecords = 5,
+
+    /// Throw away every stored record. Only the log admin may send this, as a
+    /// blocking scalar that returns 0 on success and 1 if it was refused.
+    ClearRecords = 6,
+
+    /// Send `SPILL_READY_ID` to the server whose SID is in the four scalar
+    /// arguments whenever records should be spilled, starting right away. This
+    /// replaces any earlier listener. Only the log admin may send this, and the
+    /// server must be its own.
+    SetSpillListener = 7,
+
+    /// The ms since boot, as the low and high words in `arg1` and `arg2`. Sent by
+    /// the log server's own clock thread, so that storing a record never has to
+    /// wait on the ticktimer.
+    ClockTick = 8,
+}
+
+/// Number of bytes of encoded records that fit in a `RecordPage`
+pub const RECORD_PAGE_LEN: usize = 4056;
+
+/// One page of stored records. The caller fills in the query, and the log server
+/// fills `data` with as many matching records as fit, oldest first, each encoded
+/// with `StoredRecord::encode()`.
+#[repr(C, align(4096))]
+pub struct RecordPage {
+    /// Only return records whose sequence number is greater than this
+    pub after_seq: u64,
+    /// Only return records logged at or after this many ms since boot
+    pub since_ms: u64,
+    /// Only return records from this PID, or from every process if 0
+    pub pid: u32,
+    /// Only return records at least as severe as this `log::Level`, or any level if 0
+    pub level: u32,
+
+    /// Set by the server: the number of records in `data`
+    pub count: u32,
+    /// Set by the server: the number of records that have been evicted to make room
+    pub dropped: u32,
+    /// Set by the server: pass this as `after_seq` to read the next page. It is
+    /// unchanged once there are no more matching records.
+    pub last_seq: u64,
+    pub data: [u8; RECORD_PAGE_LEN],
+}
+
+/// A log record kept by the log server
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredRecord {
+    /// Increases by one for every record stored since boot
+    pub seq: u64,
+    /// When the record arrived, in ms since boot. Records that arrive before the
+    /// ticktimer is running are stamped 0.
+    pub timestamp_ms: u64,
+    pub pid: u8,
+    /// A `log::Level` as a `u32`, as in `LogRecord`
+    pub level: u32,
+    pub module: String,
+    pub args: String,
+}
+
+impl StoredRecord {
+    const HEADER_LEN: usize = 22;
+
+    /// The number of bytes `encode()` needs for this record
+    pub fn encoded_len(&self) -> usize {
+        Self::HEADER_LEN + self.module.len() + self.args.len()
+    }
+
+    /// Write this record to the start of `buf`, returning the number of bytes used,
+    /// or `None` if it doesn't fit.
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let len = self.encoded_len();
+        if len > buf.len()
+            || self.module.len() > u16::MAX as usize
+            || self.args.len() > u16::MAX as usize
+        {
+            return None;
+        }
+        buf[0..8].copy_from_slice(&self.seq.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.timestamp_ms.to_le_bytes());
+        buf[16] = self.pid;
+        buf[17] = self.level as u8;
+        buf[18..20].copy_from_slice(&(self.module.len() as u16).to_le_bytes());
+        buf[20..22].copy_from_slice(&(self.args.len() as u16).to_le_bytes());
+        let args_start = Self::HEADER_LEN + self.module.len();
+        buf[Self::HEADER_LEN..args_start].copy_from_slice(self.module.as_bytes());
+        buf[args_start..len].copy_from_slice(self.args.as_bytes());
+        Some(len)
+    }
+
+    /// Read a record from the start of `buf`, returning it along with the number of
+    /// bytes it took up.
+    #[allow(dead_code)] // the log server only encodes
+    pub fn decode(buf: &[u8]) -> Option<(StoredRecord, usize)> {
+        if buf.len() < Self::HEADER_LEN {
+            return None;
+        }
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&buf[0..8]);
+        let seq = u64::from_le_bytes(word);
+        word.copy_from_slice(&buf[8..16]);
+        let timestamp_ms = u64::from_le_bytes(word);
+        let module_len = u16::from_le_bytes([buf[18], buf[19]]) as usize;
+        let args_len = u16::from_le_bytes([buf[20], buf[21]]) as usize;
+        let args_start = Self::HEADER_LEN + module_len;
+        let len = args_start + args_len;
+        if len > buf.len() {
+            return None;
+        }
+        Some((
+            StoredRecord {
+                seq,
+                timestamp_ms,
+                pid: buf[16],
+                level: buf[17] as u32,
+                module: String::from_utf8_lossy(&buf[Self::HEADER_LEN..args_start]).into_owned(),
+                args: String::from_utf8_lossy(&buf[args_start..len]).into_owned(),
+            },
+            len,
+        ))
+    }
 }
@@ -108,3 +108,167 @@ pub fn init_wait() -> Result<(), ()> {
 pub fn resume() {
     XOUS_LOGGER.resume();
 }
+
+/// Ask the log server to forward the text of every panic to `sid`, as a
+/// `PanicReport` with the ID `api::PANIC_REPORT_ID`. `init()` must be called first.
+/// The request is ignored unless this process is the supervisor and owns `sid`.
+pub fn set_panic_listener(sid: xous::SID) -> Result<(), xous::Error> {
+    let sid = sid.to_u32();
+    xous::send_message(
+        XOUS_LOGGER_CONNECTION.load(Ordering::Relaxed),
+        xous::Message::new_scalar(
+            api::Opcode::SetPanicListener.to_usize().unwrap(),
+            sid.0 as _,
+            sid.1 as _,
+            sid.2 as _,
+            sid.3 as _,
+        ),
+    )
+    .map(|_| ())
+}
+
+/// Change which log records from `pid` are printed and stored, or the default for
+/// processes without their own filter if `pid` is `None`. Processes only send
+/// records up to their own `log::max_level()`, so this can hide records but not
+/// bring back ones a process doesn't send. Fails with `AccessDenied` unless this
+/// process holds `xous::Role::LogAdmin`. `init()` must be called first.
+pub fn set_level_filter(
+    pid: Option<xous::PID>,
+    filter: log::LevelFilter,
+) -> Result<(), xous::Error> {
+    admin_request(xous::Message::new_blocking_scalar(
+        api::Opcode::SetLevelFilter.to_usize().unwrap(),
+        pid.map(|p| p.get() as usize).unwrap_or(0),
+        filter as usize,
+        0,
+        0,
+    ))
+}
+
+/// Throw away every stored log record. Fails with `AccessDenied` unless this
+/// process holds `xous::Role::LogAdmin`. `init()` must be called first.
+pub fn clear_records() -> Result<(), xous::Error> {
+    admin_request(xous::Message::new_blocking_scalar(
+        api::Opcode::ClearRecords.to_usize().unwrap(),
+        0,
+        0,
+        0,
+        0,
+    ))
+}
+
+fn admin_request(message: xous::Message) -> Result<(), xous::Error> {
+    match xous::send_message(XOUS_LOGGER_CONNECTION.load(Ordering::Relaxed), message)? {
+        xous::Result::Scalar1(0) => Ok(()),
+        xous::Result::Scalar1(_) => Err(xous::Error::AccessDenied),
+        _ => Err(xous::Error::InternalError),
+    }
+}
+
+/// Ask the log server to send `api::SPILL_READY_ID` to `sid` whenever stored records
+/// should be read out before they are evicted, and once right away. `init()` must be
+/// called first. The request is ignored unless this process holds
+/// `xous::Role::LogAdmin` and owns `sid`.
+pub fn set_spill_listener(sid: xous::SID) -> Result<(), xous::Error> {
+    let sid = sid.to_u32();
+    xous::send_message(
+        XOUS_LOGGER_CONNECTION.load(Ordering::Relaxed),
+        xous::Message::new_scalar(
+            api::Opcode::SetSpillListener.to_usize().unwrap(),
+            sid.0 as _,
+            sid.1 as _,
+            sid.2 as _,
+            sid.3 as _,
+        ),
+    )
+    .map(|_| ())
+}
+
+/// Which stored records `read_records()` returns. The default returns everything.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RecordQuery {
+    /// Only return records whose sequence number is greater than this, which
+    /// allows reading only the records that arrived since the last read
+    pub after_seq: u64,
+    /// Only return records logged at or after this many ms since boot
+    pub since_ms: u64,
+    /// Only return records from this process
+    pub pid: Option<xous::PID>,
+    /// Only return records at least as severe as this
+    pub level: Option<log::Level>,
+}
+
+/// Read the log records kept by the log server that match `query`, oldest first.
+/// Also returns the number of records that have been thrown away to make room
+/// since boot. Only the process holding `Role::LogAdmin` can read other processes'
+/// records; for anyone else, `query.pid` is ignored and only their own are returned.
+/// `init()` must be called first.
+pub fn read_records(query: &RecordQuery) -> Result<(Vec<api::StoredRecord>, u32), xous::Error> {
+    assert_eq!(core::mem::size_of::<api::RecordPage>(), 4096);
+    let page = xous::map_memory(
+        None,
+        None,
+        core::mem::size_of::<api::RecordPage>(),
+        xous::MemoryFlags::R | xous::MemoryFlags::W,
+    )?;
+    let mut records = Vec::new();
+    let mut after_seq = query.after_seq;
+    let mut dropped = 0;
+    let result = loop {
+        // Safe because the page was freshly mapped and is large enough, and any bit
+        // pattern is a valid `RecordPage`.
+        let request = unsafe { &mut *(page.as_mut_ptr() as *mut api::RecordPage) };
+        request.after_seq = after_seq;
+        request.since_ms = query.since_ms;
+        request.pid = query.pid.map(|p| p.get() as u32).unwrap_or(0);
+        request.level = query.level.map(|l| l as u32).unwrap_or(0);
+        request.count = 0;
+        request.last_seq = after_seq;
+
+        if let Err(e) = xous::send_message(
+            XOUS_LOGGER_CONNECTION.load(Ordering::Relaxed),
+            xous::Message::new_lend_mut(
+                api::Opcode::ReadRecords.to_usize().unwrap(),
+                page,
+                None,
+                None,
+            ),
+        ) {
+            break Err(e);
+        }
+
+        let response = unsafe { &*(page.as_ptr() as *const api::RecordPage) };
+        dropped = response.dropped;
+        let mut offset = 0;
+        for _ in 0..response.count {
+            match api::StoredRecord::decode(&response.data[offset..]) {
+                Some((record, len)) => {
+                    records.push(record);
+                    offset += len;
+                }
+                None => break,
+            }
+        }
+        // A page can come back empty if its only record was too large to send
+        if response.last_seq == after_seq {
+            break Ok(());
+        }
+        after_seq = response.last_seq;
+    };
+    xous::unmap_memory(page).ok();
+    result.map(|_| (records, dropped))
+}
+
+impl api::StoredRecord {
+    /// The level the record was logged at
+    pub fn log_level(&self) -> Option<log::Level> {
+        match self.level {
+            1 => Some(log::Level::Error),
+            2 => Some(log::Level::Warn),
+            3 => Some(log::Level::Info),
+            4 => Some(log::Level::Debug),
+            5 => Some(log::Level::Trace),
+            _ => None,
+        }
+    }
+}
@@ -3,6 +3,7 @@
 
 mod api;
 use api::*;
+mod store;
 
 #[cfg(any(target_os = "none", target_os = "xous"))]
 #[macro_use]
@@ -109,6 +110,27 @@ mod implementation {
             Ok(())
         }
     }
+
+    /// Timestamps for stored records
+    pub struct Clock {
+        start: std::time::Instant,
+    }
+
+    impl Clock {
+        pub fn new() -> Clock {
+            Clock {
+                start: std::time::Instant::now(),
+            }
+        }
+
+        pub fn elapsed_ms(&mut self) -> u64 {
+            self.start.elapsed().as_millis() as u64
+        }
+
+        /// Only hardware builds send clock ticks, as hosted builds can read the
+        /// host's clock without waiting on anything
+        pub fn set(&mut self, _ms: u64) {}
+    }
 }
 
 #[cfg(any(target_os = "none", target_os = "xous"))]
@@ -247,16 +269,262 @@ mod implementation {
             Ok(())
         }
     }
+
+    /// How often the clock thread reads the ticktimer, which is also how far
+    /// behind a record's timestamp may be
+    const CLOCK_TICK_MS: usize = 100;
+
+    /// Timestamps for stored records. The ticktimer logs through us too, so the
+    /// server thread never waits on it: a separate thread reads the time and sends
+    /// it to the server as a `ClockTick`, and records are stamped with the last
+    /// time that arrived.
+    pub struct Clock {
+        last_ms: u64,
+    }
+
+    impl Clock {
+        pub fn new() -> Clock {
+            xous::create_thread_0(clock_thread).expect("couldn't start the clock thread");
+            Clock { last_ms: 0 }
+        }
+
+        pub fn elapsed_ms(&mut self) -> u64 {
+            self.last_ms
+        }
+
+        pub fn set(&mut self, ms: u64) {
+            self.last_ms = ms;
+        }
+    }
+
+    fn clock_thread() {
+        let log = xous::connect(xous::SID::from_bytes(b"xous-log-server ").unwrap())
+            .expect("couldn't connect to the log server");
+        // The ticktimer starts after us, so this waits for it
+        let ticktimer = xous::connect(xous::SID::from_bytes(b"ticktimer-server").unwrap())
+            .expect("couldn't connect to the ticktimer");
+        loop {
+            // Opcode 0 is the ticktimer's `ElapsedMs`
+            if let Ok(xous::Result::Scalar2(low, high)) =
+                xous::send_message(ticktimer, xous::Message::new_blocking_scalar(0, 0, 0, 0, 0))
+            {
+                xous::send_message(
+                    log,
+                    xous::Message::new_scalar(
+                        crate::api::Opcode::ClockTick as usize,
+                        low,
+                        high,
+                        0,
+                        0,
+                    ),
+                )
+                .ok();
+            }
+            // Opcode 1 is the ticktimer's `SleepMs`
+            xous::send_message(
+                ticktimer,
+                xous::Message::new_blocking_scalar(1, CLOCK_TICK_MS, 0, 0, 0),
+            )
+            .ok();
+        }
+    }
+}
+
+/// The longest panic text that is kept for forwarding
+const PANIC_TEXT_LEN: usize = 1024;
+
+/// A panic whose text is still arriving
+struct PendingPanic {
+    pid: xous::PID,
+    length: usize,
+    text: [u8; PANIC_TEXT_LEN],
+}
+
+/// Collects the text of each panic so it can be stored and forwarded to the
+/// panic listener once the panic is finished.
+struct PanicForwarder {
+    listener: Option<xous::CID>,
+
+    /// More than one process may be panicking at once
+    pending: [Option<PendingPanic>; 2],
+}
+
+impl PanicForwarder {
+    fn new() -> Self {
+        PanicForwarder {
+            listener: None,
+            pending: [None, None],
+        }
+    }
+
+    fn set_listener(&mut self, sid: xous::SID) {
+        self.listener = xous::try_connect(sid).ok();
+    }
+
+    fn start(&mut self, pid: xous::PID) {
+        // Reuse the slot of an unfinished panic from the same process, if any
+        let slot = self
+            .pending
+            .iter()
+            .position(|p| matches!(p, Some(p) if p.pid == pid))
+            .or_else(|| self.pending.iter().position(|p| p.is_none()));
+        if let Some(slot) = slot {
+            self.pending[slot] = Some(PendingPanic {
+                pid,
+                length: 0,
+                text: [0u8; PANIC_TEXT_LEN],
+            });
+        }
+    }
+
+    fn append(&mut self, pid: xous::PID, bytes: &[u8]) {
+        if let Some(pending) = self.pending.iter_mut().flatten().find(|p| p.pid == pid) {
+            let count = bytes.len().min(PANIC_TEXT_LEN - pending.length);
+            pending.text[pending.length..pending.length + count].copy_from_slice(&bytes[..count]);
+            pending.length += count;
+        }
+    }
+
+    /// Stop collecting the panic from `pid`, and return its text
+    fn finish(&mut self, pid: xous::PID) -> Option<PendingPanic> {
+        self.pending
+            .iter_mut()
+            .find(|p| matches!(p, Some(p) if p.pid == pid))
+            .and_then(|p| p.take())
+    }
+
+    fn forward(&self, pending: &PendingPanic) {
+        let listener = match self.listener {
+            Some(listener) => listener,
+            None => return,
+        };
+        let page = match xous::map_memory(
+            None,
+            None,
+            core::mem::size_of::<PanicReport>(),
+            xous::MemoryFlags::R | xous::MemoryFlags::W,
+        ) {
+            Ok(page) => page,
+            Err(_) => return,
+        };
+        // Safe because the page is freshly mapped and large enough, and any bit
+        // pattern is a valid `PanicReport`.
+        let report = unsafe { &mut *(page.as_mut_ptr() as *mut PanicReport) };
+        report.pid = pending.pid.get() as u32;
+        report.length = pending.length as u32;
+        report.text[..pending.length].copy_from_slice(&pending.text[..pending.length]);
+
+        // Move the page rather than lending it, so a slow listener can't stall logging
+        let message = xous::Message::Move(xous::MemoryMessage {
+            id: PANIC_REPORT_ID,
+            buf: page,
+            offset: None,
+            valid: None,
+        });
+        if xous::try_send_message(listener, message).is_err() {
+            xous::unmap_memory(page).ok();
+        }
+    }
+}
+
+/// Log records that are kept in RAM so they can be read back later, and the
+/// filters that decide which records are kept
+struct Records {
+    store: store::LogStore,
+    filters: store::LevelFilters,
+    clock: implementation::Clock,
+    /// Told when records should be read out and written to the PDDB
+    spill_listener: Option<xous::CID>,
+}
+
+impl Records {
+    fn new() -> Self {
+        Records {
+            store: store::LogStore::new(store::STORE_CAPACITY),
+            filters: store::LevelFilters::new(),
+            clock: implementation::Clock::new(),
+            spill_listener: None,
+        }
+    }
+
+    fn push(&mut self, record: &StoredRecord) {
+        let timestamp_ms = self.clock.elapsed_ms();
+        self.store.push(
+            timestamp_ms,
+            record.pid,
+            record.level,
+            &record.module,
+            &record.args,
+        );
+        if self.store.needs_spill() {
+            self.request_spill();
+        }
+    }
+
+    fn set_spill_listener(&mut self, sid: xous::SID) {
+        self.spill_listener = xous::try_connect(sid).ok();
+        // Have it spill what arrived before it was listening
+        self.request_spill();
+    }
+
+    fn request_spill(&mut self) {
+        let listener = match self.spill_listener {
+            Some(listener) => listener,
+            None => return,
+        };
+        let seq = self.store.newest_seq();
+        // Don't wait on a busy listener; it is asked again after the next record
+        if xous::try_send_message(
+            listener,
+            xous::Message::new_scalar(
+                SPILL_READY_ID,
+                seq as u32 as usize,
+                (seq >> 32) as usize,
+                0,
+                0,
+            ),
+        )
+        .is_ok()
+        {
+            self.store.mark_spilled();
+        }
+    }
+
+    fn read(&self, page: &mut RecordPage) {
+        let query = store::Query {
+            after_seq: page.after_seq,
+            since_ms: page.since_ms,
+            pid: if page.pid == 0 {
+                None
+            } else {
+                Some(page.pid as u8)
+            },
+            level: if page.level == 0 {
+                None
+            } else {
+                Some(page.level)
+            },
+        };
+        let (count, last_seq) = self.store.read(&query, &mut page.data);
+        page.count = count as u32;
+        page.last_seq = last_seq;
+        page.dropped = self.store.dropped() as u32;
+    }
 }
 
 fn handle_scalar(
     output: &mut implementation::OutputWriter,
+    panics: &mut PanicForwarder,
+    records: &mut Records,
     sender: xous::MessageSender,
     msg: &xous::ScalarMessage,
     sender_pid: xous::PID,
 ) {
     match msg.id {
-        1000 => writeln!(output, "PANIC in PID {}:", sender_pid).unwrap(),
+        1000 => {
+            writeln!(output, "PANIC in PID {}:", sender_pid).unwrap();
+            panics.start(sender_pid);
+        }
         1100 => (),
         1101..=1132 => {
             let mut output_bfr = [0u8; core::mem::size_of::<usize>() * 4];
@@ -284,13 +552,82 @@ fn handle_scalar(
                 }
                 output.putc(*c);
             }
+            panics.append(sender_pid, &output_bfr[..total_chars.min(output_bfr.len())]);
+        }
+        1200 => {
+            writeln!(output, "Terminating process").unwrap();
+            if let Some(pending) = panics.finish(sender_pid) {
+                panics.forward(&pending);
+                // The text itself only goes to the supervisor, since it can hold secrets
+                // and the store can be read back and spilled
+                records.push(&StoredRecord {
+                    seq: 0,
+                    timestamp_ms: 0,
+                    pid: sender_pid.get(),
+                    level: log::Level::Error as u32,
+                    module: "panic".to_owned(),
+                    args: "process panicked".to_owned(),
+                });
+            }
         }
-        1200 => writeln!(output, "Terminating process").unwrap(),
         2000 => {
             #[cfg(any(target_os = "none", target_os = "xous"))]
             crate::debug::DEFAULT.enable_rx();
             writeln!(output, "Resuming logger").unwrap();
         }
+        3000 => {
+            // Panic text can hold secrets, so only the supervisor may have it,
+            // and only on a server of its own
+            let sid =
+                xous::SID::from_u32(msg.arg1 as _, msg.arg2 as _, msg.arg3 as _, msg.arg4 as _);
+            if xous::role_holder(xous::Role::Supervisor) == Ok(sender_pid)
+                && xous::server_owner(sid) == Ok(sender_pid)
+            {
+                panics.set_listener(sid);
+            } else {
+                writeln!(output, "PID {} may not listen for panics", sender_pid).unwrap();
+            }
+        }
+        4 | 6 => {
+            // Filters and clearing decide what survives for a post-mortem, so they
+            // belong to the log admin
+            if xous::role_holder(xous::Role::LogAdmin) != Ok(sender_pid) {
+                writeln!(output, "PID {} may not change stored records", sender_pid).unwrap();
+                xous::return_scalar(sender, 1).ok();
+                return;
+            }
+            if msg.id == 4 {
+                if let Some(filter) = store::level_filter_from_usize(msg.arg2) {
+                    let pid = if msg.arg1 == 0 {
+                        None
+                    } else {
+                        Some(msg.arg1 as u8)
+                    };
+                    records.filters.set(pid, filter);
+                }
+            } else {
+                records.store.clear();
+            }
+            xous::return_scalar(sender, 0).ok();
+        }
+        7 => {
+            let sid =
+                xous::SID::from_u32(msg.arg1 as _, msg.arg2 as _, msg.arg3 as _, msg.arg4 as _);
+            if xous::role_holder(xous::Role::LogAdmin) == Ok(sender_pid)
+                && xous::server_owner(sid) == Ok(sender_pid)
+            {
+                records.set_spill_listener(sid);
+            } else {
+                writeln!(output, "PID {} may not spill the log", sender_pid).unwrap();
+            }
+        }
+        8 => {
+            if sender_pid.get() as u32 == xous::process::id() {
+                records
+                    .clock
+                    .set(msg.arg1 as u32 as u64 | ((msg.arg2 as u64) << 32));
+            }
+        }
         _ => writeln!(
             output,
             "Unrecognized scalar message from {}: {:#?}",
@@ -300,18 +637,47 @@ fn handle_scalar(
     }
 }
 
+/// Handle a message. Log records that should be stored are returned rather than
+/// stored right away, so the sender's memory can be returned to it first.
 fn handle_opcode(
     output: &mut implementation::OutputWriter,
+    panics: &mut PanicForwarder,
+    records: &mut Records,
     sender: xous::MessageSender,
     opcode: api::Opcode,
-    message: &xous::Message,
-) {
+    message: &mut xous::Message,
+) -> Option<StoredRecord> {
+    if opcode == api::Opcode::ReadRecords {
+        if let Some(mem) = message.memory_message_mut() {
+            if mem.buf.len() >= core::mem::size_of::<RecordPage>() {
+                // Safe because the buffer is large enough, and any bit pattern is a
+                // valid `RecordPage`.
+                let page = unsafe { &mut *(mem.buf.as_mut_ptr() as *mut RecordPage) };
+                // Records can hold anything a process logged, so only the log admin may
+                // read everyone's; other processes only get their own
+                match sender.pid() {
+                    Some(pid) => {
+                        if xous::role_holder(xous::Role::LogAdmin) != Ok(pid) {
+                            page.pid = pid.get() as u32;
+                        }
+                        records.read(page);
+                    }
+                    None => page.count = 0,
+                }
+            }
+        }
+        return None;
+    }
     if let Some(mem) = message.memory_message() {
         match opcode {
             api::Opcode::LogRecord => {
                 // This transmute is safe because even if the resulting buffer is garbage,
                 // there are no invalid values in the resulting struct.
                 let lr = unsafe { &*(mem.buf.as_ptr() as *const LogRecord) };
+                let sender_pid = sender.pid().map(|p| p.get()).unwrap_or_default();
+                if !records.filters.allows(sender_pid, lr.level) {
+                    return None;
+                }
                 let level = if log::Level::Error as u32 == lr.level {
                     "ERR "
                 } else if log::Level::Warn as u32 == lr.level {
@@ -326,13 +692,13 @@ fn handle_opcode(
                     "UNKNOWN"
                 };
                 if lr.file_length as usize > lr.file.len() {
-                    return;
+                    return None;
                 }
                 if lr.args_length as usize > lr.args.len() {
-                    return;
+                    return None;
                 }
                 if lr.module_length as usize > lr.module.len() {
-                    return;
+                    return None;
                 }
 
                 let file_slice = &lr.file[0..lr.file_length as usize];
@@ -358,6 +724,15 @@ fn handle_opcode(
                     write!(output, ":{}", line.get()).ok();
                 }
                 writeln!(output, ")").ok();
+
+                return Some(StoredRecord {
+                    seq: 0,
+                    timestamp_ms: 0,
+                    pid: sender_pid,
+                    level: lr.level,
+                    module: String::from_utf8_lossy(module_slice).into_owned(),
+                    args: String::from_utf8_lossy(args_slice).into_owned(),
+                });
             }
             api::Opcode::StandardOutput | api::Opcode::StandardError => {
                 // let mut buffer_start_offset = mem.offset.map(|o| o.get()).unwrap_or(0);
@@ -390,8 +765,16 @@ fn handle_opcode(
         }
     } else if let Some(scalar) = message.scalar_message() {
         // Scalar message
-        handle_scalar(output, sender, scalar, sender.pid().unwrap());
+        handle_scalar(
+            output,
+            panics,
+            records,
+            sender,
+            scalar,
+            sender.pid().unwrap(),
+        );
     }
+    None
 }
 
 fn reader_thread(arg: usize) {
@@ -401,6 +784,8 @@ fn reader_thread(arg: usize) {
     writeln!(output, "LOG: Server listening on address {:?}", server_addr).unwrap();
 
     println!("LOG: my PID is {}", xous::process::id());
+    let mut panics = PanicForwarder::new();
+    let mut records = Records::new();
     let mut counter: usize = 0;
     loop {
         if counter.trailing_zeros() >= 12 {
@@ -408,10 +793,24 @@ fn reader_thread(arg: usize) {
         }
         counter += 1;
         // writeln!(output, "LOG: Waiting for an event...").unwrap();
-        let envelope = xous::syscall::receive_message(server_addr).expect("couldn't get address");
+        let mut envelope =
+            xous::syscall::receive_message(server_addr).expect("couldn't get address");
         let sender = envelope.sender;
         if let Some(opcode) = FromPrimitive::from_usize(envelope.body.id()) {
-            handle_opcode(output, sender, opcode, &envelope.body);
+            let record = handle_opcode(
+                output,
+                &mut panics,
+                &mut records,
+                sender,
+                opcode,
+                &mut envelope.body,
+            );
+            // Return the sender's memory before storing the record, which may
+            // involve asking for a spill
+            drop(envelope);
+            if let Some(record) = record {
+                records.push(&record);
+            }
         } else {
             writeln!(
                 output,
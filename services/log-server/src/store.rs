@@ -0,0 +1,394 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::api::{StoredRecord, RECORD_PAGE_LEN};
+
+/// Bytes of encoded records kept in RAM. Once this is used up, the oldest
+/// records are thrown away to make room.
+pub const STORE_CAPACITY: usize = 64 * 1024;
+
+/// Which records `LogStore::read()` should return
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Query {
+    pub after_seq: u64,
+    pub since_ms: u64,
+    pub pid: Option<u8>,
+    /// The least severe `log::Level` to return
+    pub level: Option<u32>,
+}
+
+impl Query {
+    fn matches(&self, record: &StoredRecord) -> bool {
+        record.timestamp_ms >= self.since_ms
+            && self.pid.iter().all(|&pid| record.pid == pid)
+            && self.level.iter().all(|&level| record.level <= level)
+    }
+}
+
+/// A ring of the most recent log records, bounded by the space they take up
+/// when encoded rather than by the number of records.
+pub struct LogStore {
+    records: VecDeque<StoredRecord>,
+    capacity: usize,
+    used: usize,
+    next_seq: u64,
+    dropped: u64,
+    /// Bytes of records stored since the spill listener was last told about them
+    unspilled: usize,
+}
+
+impl LogStore {
+    pub fn new(capacity: usize) -> Self {
+        LogStore {
+            records: VecDeque::new(),
+            capacity,
+            used: 0,
+            next_seq: 1,
+            dropped: 0,
+            unspilled: 0,
+        }
+    }
+
+    /// Add a record, evicting old ones if there isn't room. Text that would not fit
+    /// in an empty store or in a `RecordPage` is cut short. Returns the record's
+    /// sequence number.
+    pub fn push(
+        &mut self,
+        timestamp_ms: u64,
+        pid: u8,
+        level: u32,
+        module: &str,
+        args: &str,
+    ) -> u64 {
+        let mut record = StoredRecord {
+            seq: self.next_seq,
+            timestamp_ms,
+            pid,
+            level,
+            module: module.to_owned(),
+            args: args.to_owned(),
+        };
+        let limit = self.capacity.min(RECORD_PAGE_LEN);
+        while record.encoded_len() > limit && !record.args.is_empty() {
+            let excess = record.encoded_len() - limit;
+            let mut cut = record.args.len().saturating_sub(excess);
+            while !record.args.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            record.args.truncate(cut);
+        }
+        if record.encoded_len() > limit {
+            record.module.clear();
+        }
+
+        let len = record.encoded_len();
+        while self.used + len > self.capacity {
+            match self.records.pop_front() {
+                Some(old) => {
+                    self.used -= old.encoded_len();
+                    self.dropped += 1;
+                }
+                None => break,
+            }
+        }
+        self.used += len;
+        self.unspilled += len;
+        self.next_seq += 1;
+        self.records.push_back(record);
+        self.next_seq - 1
+    }
+
+    /// Encode records matching `query` into `buf`, oldest first, until `buf` is full.
+    /// Returns the number of records written and the sequence number to continue
+    /// from on the next call. A record too large for even an empty `buf` is skipped,
+    /// so that it can't hold up the records after it.
+    pub fn read(&self, query: &Query, buf: &mut [u8]) -> (usize, u64) {
+        let start = self.records.partition_point(|r| r.seq <= query.after_seq);
+        let mut offset = 0;
+        let mut count = 0;
+        let mut last_seq = query.after_seq;
+        for record in self.records.range(start..) {
+            if query.matches(record) {
+                match record.encode(&mut buf[offset..]) {
+                    Some(len) => {
+                        offset += len;
+                        count += 1;
+                    }
+                    None if offset == 0 => (),
+                    None => break,
+                }
+            }
+            last_seq = record.seq;
+        }
+        (count, last_seq)
+    }
+
+    /// The sequence number of the most recently stored record, or 0 if none has been
+    /// stored yet
+    pub fn newest_seq(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    /// Records that have been evicted to make room for newer ones
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Whether enough records have arrived since `mark_spilled()` that they should
+    /// be spilled before they are evicted. This leaves the spill listener the
+    /// other half of the store's capacity to catch up in.
+    pub fn needs_spill(&self) -> bool {
+        self.unspilled >= self.capacity / 2
+    }
+
+    /// The spill listener has been told about every record stored so far
+    pub fn mark_spilled(&mut self) {
+        self.unspilled = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.used = 0;
+        self.unspilled = 0;
+    }
+}
+
+/// Per-process log levels, which can be changed while the system is running
+pub struct LevelFilters {
+    default: log::LevelFilter,
+    processes: HashMap<u8, log::LevelFilter>,
+}
+
+impl LevelFilters {
+    pub fn new() -> Self {
+        LevelFilters {
+            default: log::LevelFilter::Trace,
+            processes: HashMap::new(),
+        }
+    }
+
+    /// Set the filter for `pid`, or the default for processes that don't have
+    /// their own if `pid` is `None`.
+    pub fn set(&mut self, pid: Option<u8>, filter: log::LevelFilter) {
+        match pid {
+            Some(pid) => {
+                self.processes.insert(pid, filter);
+            }
+            None => self.default = filter,
+        }
+    }
+
+    /// Whether a record at `level` from `pid` should be kept
+    pub fn allows(&self, pid: u8, level: u32) -> bool {
+        let filter = self.processes.get(&pid).copied().unwrap_or(self.default);
+        level <= filter as u32
+    }
+}
+
+/// Convert a `log::LevelFilter` that was sent as a `usize`
+pub fn level_filter_from_usize(value: usize) -> Option<log::LevelFilter> {
+    match value {
+        0 => Some(log::LevelFilter::Off),
+        1 => Some(log::LevelFilter::Error),
+        2 => Some(log::LevelFilter::Warn),
+        3 => Some(log::LevelFilter::Info),
+        4 => Some(log::LevelFilter::Debug),
+        5 => Some(log::LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(store: &LogStore, query: Query) -> Vec<StoredRecord> {
+        let mut buf = [0u8; crate::api::RECORD_PAGE_LEN];
+        let (count, _) = store.read(&query, &mut buf);
+        let mut records = Vec::new();
+        let mut offset = 0;
+        for _ in 0..count {
+            let (record, len) = StoredRecord::decode(&buf[offset..]).unwrap();
+            records.push(record);
+            offset += len;
+        }
+        records
+    }
+
+    #[test]
+    fn oldest_records_are_evicted() {
+        let size = StoredRecord {
+            seq: 0,
+            timestamp_ms: 0,
+            pid: 0,
+            level: 0,
+            module: "m".to_owned(),
+            args: "0123456789".to_owned(),
+        }
+        .encoded_len();
+        let mut store = LogStore::new(size * 4);
+        for i in 0..10 {
+            store.push(i, 2, log::Level::Info as u32, "m", "0123456789");
+        }
+        let records = read_all(&store, Query::default());
+        let seqs: Vec<u64> = records.iter().map(|r| r.seq).collect();
+        assert_eq!(seqs, vec![7, 8, 9, 10]);
+        assert_eq!(store.dropped(), 6);
+    }
+
+    #[test]
+    fn oversized_records_are_truncated() {
+        let mut store = LogStore::new(64);
+        store.push(0, 2, log::Level::Info as u32, "m", "a");
+        let seq = store.push(0, 2, log::Level::Info as u32, "m", &"é".repeat(100));
+        let records = read_all(&store, Query::default());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].seq, seq);
+        assert!(records[0].encoded_len() <= 64);
+        assert!(records[0].args.chars().all(|c| c == 'é'));
+        assert_eq!(store.dropped(), 1);
+    }
+
+    #[test]
+    fn reads_filter_by_time_process_and_level() {
+        let mut store = LogStore::new(STORE_CAPACITY);
+        store.push(10, 2, log::Level::Info as u32, "a", "first");
+        store.push(20, 3, log::Level::Error as u32, "b", "second");
+        store.push(30, 2, log::Level::Debug as u32, "a", "third");
+        store.push(40, 3, log::Level::Warn as u32, "b", "fourth");
+
+        let args = |query| -> Vec<String> {
+            read_all(&store, query)
+                .into_iter()
+                .map(|r| r.args)
+                .collect()
+        };
+        assert_eq!(
+            args(Query {
+                since_ms: 25,
+                ..Default::default()
+            }),
+            vec!["third", "fourth"]
+        );
+        assert_eq!(
+            args(Query {
+                pid: Some(3),
+                ..Default::default()
+            }),
+            vec!["second", "fourth"]
+        );
+        assert_eq!(
+            args(Query {
+                level: Some(log::Level::Warn as u32),
+                ..Default::default()
+            }),
+            vec!["second", "fourth"]
+        );
+        assert_eq!(
+            args(Query {
+                after_seq: 2,
+                pid: Some(2),
+                ..Default::default()
+            }),
+            vec!["third"]
+        );
+    }
+
+    #[test]
+    fn reads_continue_where_a_full_page_stopped() {
+        let mut store = LogStore::new(STORE_CAPACITY);
+        for i in 0..100 {
+            store.push(
+                i,
+                2,
+                log::Level::Info as u32,
+                "module",
+                &format!("record {}", i),
+            );
+        }
+        let mut buf = [0u8; 256];
+        let mut query = Query::default();
+        let mut seen = Vec::new();
+        loop {
+            let (count, last_seq) = store.read(&query, &mut buf);
+            if count == 0 {
+                break;
+            }
+            let mut offset = 0;
+            for _ in 0..count {
+                let (record, len) = StoredRecord::decode(&buf[offset..]).unwrap();
+                seen.push(record.seq);
+                offset += len;
+            }
+            query.after_seq = last_seq;
+        }
+        assert_eq!(seen, (1..=100).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn records_always_fit_in_a_page() {
+        let mut store = LogStore::new(STORE_CAPACITY);
+        // Invalid UTF-8 from a sender triples in size when it is converted to a `String`
+        let args = String::from_utf8_lossy(&[0xffu8; 3820]).into_owned();
+        assert!(args.len() > RECORD_PAGE_LEN);
+        let big = store.push(0, 2, log::Level::Info as u32, "m", &args);
+        let next = store.push(0, 2, log::Level::Info as u32, "m", "after");
+
+        let mut buf = [0u8; RECORD_PAGE_LEN];
+        let (count, last_seq) = store.read(&Query::default(), &mut buf);
+        assert_eq!((count, last_seq), (1, big));
+        let (count, last_seq) = store.read(
+            &Query {
+                after_seq: last_seq,
+                ..Default::default()
+            },
+            &mut buf,
+        );
+        assert_eq!((count, last_seq), (1, next));
+    }
+
+    #[test]
+    fn reads_skip_records_too_large_for_the_buffer() {
+        let mut store = LogStore::new(STORE_CAPACITY);
+        store.push(0, 2, log::Level::Info as u32, "m", &"x".repeat(512));
+        let next = store.push(0, 2, log::Level::Info as u32, "m", "after");
+
+        let mut buf = [0u8; 256];
+        let (count, last_seq) = store.read(&Query::default(), &mut buf);
+        assert_eq!(count, 1);
+        assert_eq!(last_seq, next);
+        let (record, _) = StoredRecord::decode(&buf).unwrap();
+        assert_eq!(record.args, "after");
+    }
+
+    #[test]
+    fn spills_are_requested_at_half_capacity() {
+        let size = StoredRecord {
+            seq: 0,
+            timestamp_ms: 0,
+            pid: 0,
+            level: 0,
+            module: "m".to_owned(),
+            args: "0123456789".to_owned(),
+        }
+        .encoded_len();
+        let mut store = LogStore::new(size * 4);
+        store.push(0, 2, log::Level::Info as u32, "m", "0123456789");
+        assert!(!store.needs_spill());
+        store.push(0, 2, log::Level::Info as u32, "m", "0123456789");
+        assert!(store.needs_spill());
+        store.mark_spilled();
+        assert!(!store.needs_spill());
+    }
+
+    #[test]
+    fn level_filters_fall_back_to_the_default() {
+        let mut filters = LevelFilters::new();
+        assert!(filters.allows(5, log::Level::Trace as u32));
+        filters.set(None, log::LevelFilter::Info);
+        filters.set(Some(5), log::LevelFilter::Error);
+        assert!(filters.allows(4, log::Level::Info as u32));
+        assert!(!filters.allows(4, log::Level::Debug as u32));
+        assert!(!filters.allows(5, log::Level::Warn as u32));
+        assert!(filters.allows(5, log::Level::Error as u32));
+    }
+}
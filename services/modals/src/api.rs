@@ -43,6 +43,15 @@ pub struct ManagedNotification {
     pub qrtext: Option<xous_ipc::String<3000>>,
 }
 
+/// Longest document that `show_document()` accepts, in bytes
+pub const DOCUMENT_MAX_LEN: usize = 8192;
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedDocument {
+    pub token: [u32; 4],
+    pub title: Option<xous_ipc::String<256>>,
+    pub text: xous_ipc::String<DOCUMENT_MAX_LEN>,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub struct ManagedBip39 {
     pub token: [u32; 4],
@@ -71,6 +80,13 @@ pub struct ManagedProgress {
     pub current_work: u32,
 }
 
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedForm {
+    pub token: [u32; 4],
+    pub prompt: xous_ipc::String<1024>,
+    pub form: FormPayload,
+}
+
 /// This isn't a terribly useful notification -- it's basically read-only, no interactivity,
 /// but you can animate the text. Mainly used for testing routines. Might be modifiable
 /// into something more useful with a bit of thought, but for now, MVP.
@@ -98,6 +114,11 @@ pub(crate) enum Opcode {
     Bip39 = 31, // ---- note op number
     Bip39Input = 32, // ----- note op number
     Bip39Return = 33, // ----- note op number
+    /// a long document in a scrolling viewer
+    Document = 34, // ----- note op number
+    /// several labelled fields of mixed types in one modal, returned together
+    PromptWithForm = 35, // ----- note op number
+    FormReturn = 36,  // ----- note op number
     /// display an image
     #[cfg(feature = "ditherpunk")]
     Image = 3,
@@ -108,7 +129,7 @@ pub(crate) enum Opcode {
 
     /// ask a question, get a free-form answer back
     PromptWithTextResponse = 6,
-    /// must be used by the PromptWithTextResponse caller to acknowledge correct input
+    /// must be used by the PromptWithTextResponse and PromptWithForm callers to acknowledge correct input
     TextResponseValid = 7,
 
     // these are non-blocking calls
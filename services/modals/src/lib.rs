@@ -140,6 +140,207 @@ impl<'a> AlertModalBuilder<'a> {
     }
 }
 
+/// Checks a field of a form other than a text field, which takes a `TextValidationFn` instead.
+pub type FormValidationFn = fn(&FormField) -> Option<ValidatorErr>;
+
+enum FormValidator {
+    Text(TextValidationFn),
+    Field(FormValidationFn),
+}
+
+/// Builds a single modal out of several labelled fields. Fields are numbered in the order they
+/// are added, and that index is used to read them back out of the `FormPayload` returned by `build()`.
+pub struct FormBuilder<'a> {
+    prompt: String,
+    form: FormPayload,
+    validators: Vec<Option<FormValidator>>,
+    /// set if a field or item didn't fit; reported by `build()` so the builder calls can be chained
+    overflow: bool,
+    modals: &'a Modals,
+}
+
+impl<'a> FormBuilder<'a> {
+    fn add(
+        &mut self,
+        label: &str,
+        kind: FormFieldKind,
+        validator: Option<FormValidator>,
+    ) -> &mut Self {
+        if self.form.add(label, kind) {
+            self.validators.push(validator);
+        } else {
+            log::error!("a form can have at most {} fields", MAX_FORM_FIELDS);
+            self.overflow = true;
+        }
+        self
+    }
+
+    fn items(&mut self, names: &[&str]) -> [Option<ItemName>; MAX_ITEMS] {
+        let mut items = [None; MAX_ITEMS];
+        if names.len() > MAX_ITEMS {
+            log::error!("a form field can have at most {} items", MAX_ITEMS);
+            self.overflow = true;
+        }
+        for (item, name) in items.iter_mut().zip(names.iter()) {
+            *item = Some(ItemName::new(name));
+        }
+        items
+    }
+
+    pub fn text(
+        &mut self,
+        label: &str,
+        placeholder: Option<&str>,
+        validator: Option<TextValidationFn>,
+    ) -> &mut Self {
+        let payload = TextEntryPayload::new_with_fields(
+            Default::default(),
+            placeholder.map(xous_ipc::String::from_str),
+        );
+        self.add(
+            label,
+            FormFieldKind::Text(payload),
+            validator.map(FormValidator::Text),
+        )
+    }
+
+    /// `selected` is the index into `items` that is chosen to start with
+    pub fn radio(&mut self, label: &str, items: &[&str], selected: usize) -> &mut Self {
+        let items = self.items(items);
+        self.add(
+            label,
+            FormFieldKind::Radio {
+                items,
+                selected: selected as u32,
+            },
+            None,
+        )
+    }
+
+    /// `checked` lists the items that are checked to start with
+    pub fn checkboxes(&mut self, label: &str, items: &[&str], checked: &[&str]) -> &mut Self {
+        let mut bits = 0u32;
+        for (index, item) in items.iter().enumerate().take(MAX_ITEMS) {
+            if checked.contains(item) {
+                bits |= 1 << index;
+            }
+        }
+        let items = self.items(items);
+        self.add(
+            label,
+            FormFieldKind::CheckBoxes {
+                items,
+                checked: bits,
+            },
+            None,
+        )
+    }
+
+    pub fn slider(
+        &mut self,
+        label: &str,
+        min: u32,
+        max: u32,
+        step: u32,
+        initial: u32,
+    ) -> &mut Self {
+        self.add(
+            label,
+            FormFieldKind::Slider {
+                min,
+                max,
+                step,
+                value: initial.max(min).min(max),
+            },
+            None,
+        )
+    }
+
+    /// Sets the validator of the most recently added field.
+    pub fn validator(&mut self, validator: FormValidationFn) -> &mut Self {
+        if let Some(last) = self.validators.last_mut() {
+            *last = Some(FormValidator::Field(validator));
+        }
+        self
+    }
+
+    /// Shows the form until every field passes its validator. A failed validator's message replaces
+    /// the prompt, and the form comes back with the values as entered and the cursor on that field.
+    /// Returns an error if the form couldn't be shown.
+    pub fn build(&self) -> Result<FormPayload, xous::Error> {
+        if self.form.is_empty() || self.overflow {
+            log::error!(
+                "form must have between 1 and {} fields, each with at most {} items",
+                MAX_FORM_FIELDS,
+                MAX_ITEMS
+            );
+            return Err(xous::Error::UnknownError);
+        }
+        self.modals.lock();
+        let mut spec = ManagedForm {
+            token: self.modals.token,
+            prompt: xous_ipc::String::from_str(&self.prompt),
+            form: self.form,
+        };
+        loop {
+            let mut buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.modals.conn, Opcode::PromptWithForm.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            let response = match buf.to_original::<FormPayload, _>() {
+                Ok(response) => response,
+                _ => {
+                    // acknowledge anyways so the modals server doesn't get stuck on this error
+                    self.modals.text_response_valid();
+                    self.modals.unlock();
+                    return Err(xous::Error::InternalError);
+                }
+            };
+            if response.is_empty() {
+                // the server turned the request away without showing it, e.g. because the token didn't match
+                log::error!("form was not shown");
+                self.modals.unlock();
+                return Err(xous::Error::InternalError);
+            }
+            match self.validate(&response) {
+                Some((index, err_msg)) => {
+                    spec.prompt.clear();
+                    spec.prompt
+                        .append(err_msg.as_str().unwrap_or("UTF-8 error"))
+                        .ok();
+                    spec.form = response;
+                    spec.form.focus = index as u32;
+                }
+                None => {
+                    self.modals.text_response_valid();
+                    self.modals.unlock();
+                    return Ok(response);
+                }
+            }
+        }
+    }
+
+    /// returns the index of the first field that failed, along with its error message
+    fn validate(&self, response: &FormPayload) -> Option<(usize, ValidatorErr)> {
+        for (index, validator) in self.validators.iter().enumerate() {
+            let field = match response.field(index) {
+                Some(field) => field,
+                None => continue,
+            };
+            let err_msg = match (validator, &field.kind) {
+                (Some(FormValidator::Text(validator)), FormFieldKind::Text(payload)) => {
+                    validator(*payload)
+                }
+                (Some(FormValidator::Field(validator)), _) => validator(field),
+                _ => None,
+            };
+            if let Some(err_msg) = err_msg {
+                return Some((index, err_msg));
+            }
+        }
+        None
+    }
+}
+
 pub struct Modals {
     conn: CID,
     token: [u32; 4],
@@ -170,6 +371,16 @@ impl Modals {
         }
     }
 
+    pub fn form_builder(&self, prompt: &str) -> FormBuilder {
+        FormBuilder {
+            prompt: String::from(prompt),
+            form: FormPayload::new(),
+            validators: vec![],
+            overflow: false,
+            modals: self,
+        }
+    }
+
     /// this blocks until the notification has been acknowledged.
     pub fn show_notification(
         &self,
@@ -193,6 +404,26 @@ impl Modals {
         Ok(())
     }
 
+    /// this blocks until the document has been dismissed. The document is shown in a scrolling viewer: the up and
+    /// down keys scroll by a line, left and right by a page, and any other key closes it. Documents longer than
+    /// `DOCUMENT_MAX_LEN` bytes are refused with an `InvalidString` error, without showing any dialog box.
+    pub fn show_document(&self, title: Option<&str>, text: &str) -> Result<(), xous::Error> {
+        if text.len() > DOCUMENT_MAX_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        self.lock();
+        let spec = ManagedDocument {
+            token: self.token,
+            title: title.map(xous_ipc::String::from_str),
+            text: xous_ipc::String::from_str(text),
+        };
+        let buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::Document.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        self.unlock();
+        Ok(())
+    }
+
     /// this blocks until the notification has been acknowledged. It will attempt to render up to 256 bits
     /// of `data` in bip39 format. Data must conform to the codeable lengths by BIP39, or else the routine
     /// will return immediately with an `InvalidString` error without showing any dialog box.
@@ -564,6 +795,20 @@ impl Modals {
     fn unlock(&self) {
         self.have_lock.set(false);
     }
+    /// lets the modals server move on to the next caller after a text entry or form has been validated
+    fn text_response_valid(&self) {
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::TextResponseValid.to_usize().unwrap(),
+                self.token[0] as _,
+                self.token[1] as _,
+                self.token[2] as _,
+                self.token[3] as _,
+            ),
+        )
+        .expect("couldn't acknowledge text entry");
+    }
     pub fn conn(&self) -> CID {
         self.conn
     }
@@ -629,3 +874,90 @@ pub fn dynamic_notification_blocking_listener(
         _ => Err(xous::Error::InternalError),
     }
 }
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+    use core::mem::ManuallyDrop;
+
+    /// A `Modals` that isn't connected to a server, for the parts of a builder that don't talk to it.
+    /// It is never dropped, so it doesn't touch the connection reference count.
+    fn modals() -> ManuallyDrop<Modals> {
+        ManuallyDrop::new(Modals {
+            conn: 0,
+            token: [0; 4],
+            have_lock: Cell::new(false),
+        })
+    }
+    fn not_empty(payload: TextEntryPayload) -> Option<ValidatorErr> {
+        if payload.as_str().is_empty() {
+            Some(xous_ipc::String::from_str("empty"))
+        } else {
+            None
+        }
+    }
+    fn two_checked(field: &FormField) -> Option<ValidatorErr> {
+        if field.checked().len() < 2 {
+            Some(xous_ipc::String::from_str("check two"))
+        } else {
+            None
+        }
+    }
+    fn set_text(form: &mut FormPayload, index: usize, text: &str) {
+        if let Some(FormField {
+            kind: FormFieldKind::Text(payload),
+            ..
+        }) = form.fields[index].as_mut()
+        {
+            payload.content.clear();
+            payload.content.append(text).unwrap();
+        }
+    }
+
+    #[test]
+    fn validate_reports_the_first_failing_field() {
+        let modals = modals();
+        let mut builder = modals.form_builder("new item");
+        builder
+            .text("Name", None, Some(not_empty))
+            .checkboxes("Options", &["upper", "numbers"], &["upper"])
+            .validator(two_checked)
+            .slider("Length", 4, 10, 1, 20);
+        let mut response = builder.form;
+        let (index, err) = builder.validate(&response).unwrap();
+        assert_eq!((index, err.as_str().unwrap()), (0, "empty"));
+
+        set_text(&mut response, 0, "bank");
+        let (index, err) = builder.validate(&response).unwrap();
+        assert_eq!((index, err.as_str().unwrap()), (1, "check two"));
+
+        if let Some(FormField {
+            kind: FormFieldKind::CheckBoxes { checked, .. },
+            ..
+        }) = response.fields[1].as_mut()
+        {
+            *checked = 0b11;
+        }
+        assert!(builder.validate(&response).is_none());
+        // fields are numbered in the order they were added, and sliders are clamped to their range
+        assert_eq!(response.field(2).unwrap().value(), Some(10));
+    }
+
+    #[test]
+    fn build_rejects_forms_that_dont_fit() {
+        let modals = modals();
+        assert!(modals.form_builder("empty").build().is_err());
+
+        let mut builder = modals.form_builder("too many fields");
+        for _ in 0..=MAX_FORM_FIELDS {
+            builder.slider("Length", 0, 10, 1, 5);
+        }
+        assert_eq!(builder.form.len(), MAX_FORM_FIELDS);
+        assert_eq!(builder.validators.len(), MAX_FORM_FIELDS);
+        assert!(builder.build().is_err());
+
+        let mut builder = modals.form_builder("too many items");
+        builder.radio("Kind", &["item"; MAX_ITEMS + 1], 0);
+        assert!(builder.build().is_err());
+    }
+}
@@ -21,7 +21,7 @@
 /// 6. (implicit) the memory_message previously held in the `dr` record is dropped, trigging the caller to unblock
 /// 7. once you are sure you're finished, call `token_lock = next_lock(&mut work_queue);` to pull any waiting work from the work queue
 ///
-/// Between 5 & 7 is where the TextEntry is weird: because you can "fail" on the return,
+/// Between 5 & 7 is where the TextEntry (and the Form) is weird: because you can "fail" on the return,
 /// it doesn't automatically do step 7. It's an extra step that the library implementation
 /// does after it does the text validation on its side, once it validates the caller sends
 /// a `TextResponseValid` message which pumps the work queue.
@@ -52,16 +52,20 @@ enum RendererState {
     RunRadio(ManagedPromptWithFixedResponse),
     RunCheckBox(ManagedPromptWithFixedResponse),
     RunText(ManagedPromptWithTextResponse),
+    RunForm(ManagedForm),
     RunProgress(ManagedProgress),
     RunNotification(ManagedNotification),
     RunBip39(ManagedBip39),
     RunBip39Input(ManagedBip39),
+    RunDocument(ManagedDocument),
     RunDynamicNotification(DynamicNotification),
     #[cfg(feature="ditherpunk")]
     RunImage(ManagedImage),
 }
 
 const DEFAULT_STYLE: GlyphStyle = GlyphStyle::Regular;
+/// lines of text shown at once by `show_document()`
+const DOCUMENT_LINES: i16 = 14;
 
 fn main () -> ! {
     #[cfg(not(feature="ditherpunk"))]
@@ -241,6 +245,45 @@ fn wrapped_main() -> ! {
                 )
                 .expect("couldn't initiate UX op");
             }
+            Some(Opcode::PromptWithForm) => {
+                let spec = {
+                    let mut buffer = unsafe {
+                        Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
+                    };
+                    let spec = buffer.to_original::<ManagedForm, _>().unwrap();
+                    if spec.token != token_lock.unwrap_or(default_nonce) {
+                        log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                        buffer.replace(FormPayload::new()).unwrap();
+                        continue;
+                    }
+                    spec
+                };
+                op = RendererState::RunForm(spec);
+                dr = Some(msg);
+                send_message(
+                    renderer_cid,
+                    Message::new_scalar(Opcode::InitiateOp.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .expect("couldn't initiate UX op");
+            }
+            Some(Opcode::Document) => {
+                let spec = {
+                    let buffer =
+                        unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                    buffer.to_original::<ManagedDocument, _>().unwrap()
+                };
+                if spec.token != token_lock.unwrap_or(default_nonce) {
+                    log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                    continue;
+                }
+                op = RendererState::RunDocument(spec);
+                dr = Some(msg);
+                send_message(
+                    renderer_cid,
+                    Message::new_scalar(Opcode::InitiateOp.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .expect("couldn't initiate UX op");
+            }
             Some(Opcode::Notification) => {
                 let spec = {
                     let buffer =
@@ -484,6 +527,24 @@ fn wrapped_main() -> ! {
                         renderer_modal.activate();
                         log::debug!("should be active!");
                     }
+                    RendererState::RunForm(config) => {
+                        let mut form = gam::modal::Form::new(
+                            renderer_cid,
+                            Opcode::FormReturn.to_u32().unwrap(),
+                        );
+                        form.set_payload(config.form);
+                        #[cfg(feature = "tts")]
+                        tts.tts_simple(config.prompt.as_str().unwrap()).unwrap();
+                        renderer_modal.modify(
+                            Some(ActionType::Form(form)),
+                            Some(config.prompt.as_str().unwrap()),
+                            false,
+                            None,
+                            true,
+                            Some(DEFAULT_STYLE),
+                        );
+                        renderer_modal.activate();
+                    }
                     RendererState::RunNotification(config) => {
                         let mut notification = gam::modal::Notification::new(
                             renderer_cid,
@@ -511,6 +572,26 @@ fn wrapped_main() -> ! {
                         );
                         renderer_modal.activate();
                     }
+                    RendererState::RunDocument(config) => {
+                        let document = gam::modal::ScrollingText::new(
+                            renderer_cid,
+                            Opcode::NotificationReturn.to_u32().unwrap(),
+                            config.text.as_str().unwrap(),
+                            DOCUMENT_LINES,
+                        );
+                        let title = config.title.map(|title| title.to_string());
+                        #[cfg(feature = "tts")]
+                        tts.tts_simple(config.text.as_str().unwrap()).unwrap();
+                        renderer_modal.modify(
+                            Some(ActionType::ScrollingText(document)),
+                            title.as_deref(),
+                            title.is_none(),
+                            None,
+                            true,
+                            Some(DEFAULT_STYLE),
+                        );
+                        renderer_modal.activate();
+                    }
                     RendererState::RunBip39(config) => {
                         let notification = gam::modal::Notification::new(
                             renderer_cid,
@@ -793,9 +874,38 @@ fn wrapped_main() -> ! {
                 }
                 xous::return_scalar(msg.sender, 1).unwrap();
             }),
+            Some(Opcode::FormReturn) => match op {
+                RendererState::RunForm(_config) => {
+                    let buf =
+                        unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                    let form = buf.to_original::<FormPayload, _>().unwrap();
+                    if let Some(mut origin) = dr.take() {
+                        let mut response = unsafe {
+                            Buffer::from_memory_message_mut(
+                                origin.body.memory_message_mut().unwrap(),
+                            )
+                        };
+                        response.replace(form).unwrap();
+                        // like text entry, the caller validates the fields and then pumps the work queue with `TextResponseValid`
+                        op = RendererState::None;
+                    } else {
+                        log::error!("Ux routine returned but no origin was recorded");
+                        panic!("Ux routine returned but no origin was recorded");
+                    }
+                }
+                RendererState::None => {
+                    log::warn!("Form detected a fat finger event, ignoring.")
+                }
+                _ => {
+                    log::error!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
+                    panic!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
+                }
+            },
             Some(Opcode::NotificationReturn) => {
                 match op {
-                    RendererState::RunNotification(_) | RendererState::RunBip39(_) => {
+                    RendererState::RunNotification(_)
+                    | RendererState::RunBip39(_)
+                    | RendererState::RunDocument(_) => {
                         op = RendererState::None;
                         dr.take(); // unblocks the caller, but without any response data
                         token_lock = next_lock(&mut work_queue);
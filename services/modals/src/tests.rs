@@ -37,6 +37,24 @@ pub fn spawn_test() {
                     .build()
             );
 
+            // 0b. form test: one modal, mixed fields
+            match modals
+                .form_builder("A form with one of everything. Select OK to close.")
+                .text("Name", Some("placeholder"), None)
+                .radio("Animal", &RADIO_TEST, 1)
+                .checkboxes("Mood", &CHECKBOX_TEST[..3], &["happy"])
+                .slider("Volume", 0, 10, 1, 5)
+                .build()
+            {
+                Ok(form) => {
+                    log::info!("name: {:?}", form.field(0).and_then(|f| f.text()));
+                    log::info!("animal: {:?}", form.field(1).and_then(|f| f.selected()));
+                    log::info!("mood: {:?}", form.field(2).map(|f| f.checked()));
+                    log::info!("volume: {:?}", form.field(3).and_then(|f| f.value()));
+                }
+                _ => log::error!("form failed"),
+            }
+
             // 1. test progress bar
             // The start and end items are deliberately structured to be not zero-indexed; the use of PDDB_LOC is just a
             // convenient global constant.
@@ -81,6 +99,15 @@ pub fn spawn_test() {
                 .expect("notification failed");
             log::info!("notification test done");
 
+            log::info!("testing document viewer");
+            let document: Vec<String> = (1..=100)
+                .map(|i| format!("Line {}: arrow keys scroll, any other key closes.", i))
+                .collect();
+            modals
+                .show_document(Some("A long document"), &document.join("\n"))
+                .expect("document failed");
+            log::info!("document test done");
+
             // 4. bip39 display test
             let refnum = 0b00000110001101100111100111001010000110110010100010110101110011111101101010011100000110000110101100110110011111100010011100011110u128;
             let refvec = refnum.to_be_bytes().to_vec();
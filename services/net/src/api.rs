@@ -1,7 +1,7 @@
 pub(crate) mod ping;
 pub(crate) use ping::*;
 pub(crate) mod tcp;
-pub use ping::NetPingCallback;
+pub use ping::{NetPingCallback, NET_PING_IPV6};
 // needed to keep hosted mode quiet, since the Tcp implementation is a bodge
 #[allow(unused_imports)]
 pub(crate) use tcp::*;
@@ -9,15 +9,14 @@ pub(crate) use tcp::*;
 use com::SsidRecord;
 use rkyv::{Archive, Deserialize, Serialize};
 use smoltcp::wire::IpAddress;
-use std::convert::TryInto;
 use std::fmt;
 use std::fmt::Debug;
 use std::io::Write;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use xous_semver::SemVer;
 
-// republish this so we can decode the icmpv4 error codes
-pub use smoltcp::wire::Icmpv4DstUnreachable;
+// republish these so we can decode the icmp error codes
+pub use smoltcp::wire::{Icmpv4DstUnreachable, Icmpv6DstUnreachable};
 
 // note: this name cannot be changed, because it is baked into `libstd`
 pub(crate) const SERVER_NAME_NET: &str = "_Middleware Network Server_";
@@ -357,6 +356,9 @@ pub(crate) enum Opcode {
     StdTcpAccept = 45,
 
     StdTcpStreamShutdown = 46,
+
+    /// Link Management: fetch the IPv6 configuration, as an `Ipv6Conf`
+    GetIpv6Config = 47,
 }
 
 #[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, Default)]
@@ -367,6 +369,30 @@ pub(crate) struct SsidList {
     pub(crate) list: [Option<SsidRecord>; 32],
 }
 
+/// Maximum number of global IPv6 addresses that are configured at once
+pub const MAX_IPV6_ADDRS: usize = 4;
+/// Maximum number of IPv6 DNS servers that are learned from router advertisements
+pub const MAX_IPV6_DNS: usize = 2;
+
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, Default, PartialEq)]
+pub struct Ipv6Prefix {
+    pub addr: [u8; 16],
+    pub prefix_len: u8,
+}
+
+/// The IPv6 configuration of the interface. The link-local address is always configured; the rest is
+/// learned from router advertisements (SLAAC).
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct Ipv6Conf {
+    pub mac: [u8; 6],
+    pub link_local: [u8; 16],
+    /// global addresses, in the order they were configured
+    pub addrs: [Option<Ipv6Prefix>; MAX_IPV6_ADDRS],
+    /// link-local address of the default router
+    pub router: Option<[u8; 16]>,
+    pub dns: [Option<[u8; 16]>; MAX_IPV6_DNS],
+}
+
 #[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone)]
 pub enum XousServerId {
     /// A SID that is shared directly with the Net crate; a private, single-use SID for best security
@@ -495,16 +521,7 @@ impl From<NetIpAddr> for IpAddress {
             NetIpAddr::Ipv4([a, b, c, d]) => {
                 IpAddress::Ipv4(smoltcp::wire::Ipv4Address::new(a, b, c, d))
             }
-            NetIpAddr::Ipv6(ipv6) => IpAddress::Ipv6(smoltcp::wire::Ipv6Address::new(
-                u16::from_be_bytes(ipv6[0..1].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[2..3].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[4..5].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[6..7].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[8..9].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[10..11].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[12..13].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[14..15].try_into().unwrap()),
-            )),
+            NetIpAddr::Ipv6(ipv6) => IpAddress::Ipv6(smoltcp::wire::Ipv6Address(ipv6)),
         }
     }
 }
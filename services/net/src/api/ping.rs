@@ -2,10 +2,12 @@ use rkyv::{Archive, Deserialize, Serialize};
 use crate::api::*;
 
 /// Scalar responses to pings have the following format:
-/// arg1: bottom byte = NetPingCallback as below; top byte = DstUnreachable code as u8
+/// arg1: bottom byte = NetPingCallback as below; `NET_PING_IPV6` is set if the remote is an IPv6 address;
+///       top byte = DstUnreachable code as u8 (an `Icmpv4DstUnreachable` or `Icmpv6DstUnreachable`)
 /// arg2: remote IP address hint (IPv4 is full address; IPv6 is just bottom 4 bytes)
-/// arg3: sequence number (if echo response) or top 4 bytes of an IPv6 address (if reporting timeout or drop)
+/// arg3: sequence number (if echo response or timeout) or top 4 bytes of an IPv6 address (if reporting drop)
 /// arg4: elapsed time
+pub const NET_PING_IPV6: usize = 0x100;
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub enum NetPingCallback {
     /// echo response
@@ -72,6 +72,13 @@ impl NetManager {
             None
         }
     }
+    /// Returns the IPv6 configuration of the interface. The link-local address is always present; global
+    /// addresses, the default router and DNS servers show up once a router advertisement has been received.
+    pub fn get_ipv6_config(&self) -> Result<Ipv6Conf, xous::Error> {
+        let mut buf = Buffer::into_buf(Ipv6Conf::default()).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.netconn.conn(), Opcode::GetIpv6Config.to_u32().unwrap())?;
+        buf.to_original::<Ipv6Conf, _>().or(Err(xous::Error::InternalError))
+    }
     pub fn reset(&self) {
         send_message(
             self.netconn.conn(),
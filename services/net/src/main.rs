@@ -17,6 +17,7 @@ use num_traits::*;
 
 mod connection_manager;
 mod device;
+mod slaac;
 
 use std::collections::{BTreeMap, HashMap, BTreeSet};
 use std::convert::TryInto;
@@ -27,8 +28,11 @@ use byteorder::{ByteOrder, NetworkEndian};
 use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Routes};
 use smoltcp::phy::{Device, Medium};
 use smoltcp::socket::{IcmpEndpoint, IcmpPacketMetadata, IcmpSocket, IcmpSocketBuffer};
+use smoltcp::socket::{RawPacketMetadata, RawSocket, RawSocketBuffer};
 use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr, IpEndpoint};
+use smoltcp::wire::{Ipv6Cidr, IpProtocol, IpVersion};
 use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr};
+use smoltcp::phy::ChecksumCapabilities;
 use crate::device::NetPhy;
 
 use core::num::NonZeroU64;
@@ -60,6 +64,67 @@ where
     });
 }
 
+/// Replaces all the IPv6 addresses of the interface, leaving the IPv4 address in place
+fn set_ipv6_addrs<DeviceT>(iface: &mut Interface<'_, DeviceT>, cidrs: &[Ipv6Cidr])
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    iface.update_ip_addrs(|addrs| {
+        let mut updated: Vec<IpCidr> = addrs
+            .iter()
+            .filter(|cidr| !matches!(cidr, IpCidr::Ipv6(_)))
+            .copied()
+            .collect();
+        updated.extend(cidrs.iter().map(|&cidr| IpCidr::Ipv6(cidr)));
+        *addrs = updated.into();
+    });
+}
+
+/// Applies the outcome of SLAAC processing to the interface, and passes newly learned DNS servers on
+fn apply_slaac_update<DeviceT>(
+    iface: &mut Interface<'_, DeviceT>,
+    slaac: &slaac::Slaac,
+    update: slaac::SlaacUpdate,
+    dns_ipv6_hook: &mut XousScalarEndpoint,
+) where
+    DeviceT: for<'d> Device<'d>,
+{
+    if update.addrs_changed {
+        let cidrs = slaac.cidrs();
+        log::info!("IPv6 addresses updated: {:?}", cidrs);
+        set_ipv6_addrs(iface, &cidrs);
+    }
+    if update.router_changed {
+        iface.routes_mut().remove_default_ipv6_route();
+        if let Some(router) = slaac.router() {
+            match iface.routes_mut().add_default_ipv6_route(router) {
+                Ok(_) => log::info!("IPv6 default route set to {}", router),
+                Err(e) => log::error!("IPv6 routing table update error: {}", e),
+            }
+        }
+    }
+    for dns in update.new_dns.iter() {
+        let b = dns.as_bytes();
+        dns_ipv6_hook.notify_custom_args([
+            Some(u32::from_be_bytes(b[0..4].try_into().unwrap())),
+            Some(u32::from_be_bytes(b[4..8].try_into().unwrap())),
+            Some(u32::from_be_bytes(b[8..12].try_into().unwrap())),
+            Some(u32::from_be_bytes(b[12..16].try_into().unwrap())),
+        ]);
+    }
+}
+
+/// The address hint that goes into the arg2 slot of ping callbacks
+fn ping_addr_hint(addr: &IpAddress) -> usize {
+    let bytes = addr.as_bytes();
+    u32::from_be_bytes(bytes[bytes.len() - 4..].try_into().unwrap()) as usize
+}
+
+/// The callback code of a ping response, flagged if it's about an IPv6 remote
+fn ping_callback_code(code: NetPingCallback, addr: &IpAddress) -> usize {
+    code.to_usize().unwrap() | if let IpAddress::Ipv6(_) = addr { NET_PING_IPV6 } else { 0 }
+}
+
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 enum WaitOp {
     WaitMs,
@@ -174,6 +239,23 @@ fn setup_icmp(iface: &mut Interface::<NetPhy>) -> SocketHandle {
     icmp_handle
 }
 
+/// Router advertisements are consumed by smoltcp without being exposed, but a raw ICMPv6 socket
+/// still sees a copy of them. The same socket is used to send router solicitations.
+fn setup_ndisc(iface: &mut Interface::<NetPhy>) -> SocketHandle {
+    let rx_buffer = RawSocketBuffer::new(
+        vec![
+            RawPacketMetadata::EMPTY,
+            RawPacketMetadata::EMPTY,
+            RawPacketMetadata::EMPTY,
+            RawPacketMetadata::EMPTY,
+        ],
+        vec![0; 2048],
+    );
+    let tx_buffer = RawSocketBuffer::new(vec![RawPacketMetadata::EMPTY], vec![0; 256]);
+    let raw_socket = RawSocket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, rx_buffer, tx_buffer);
+    iface.add_socket(raw_socket)
+}
+
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
@@ -247,7 +329,7 @@ fn main() -> ! {
 
     // --------------- other link storage -------------
     let neighbor_cache = NeighborCache::new(BTreeMap::new());
-    let ip_addrs = [IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)];
+    let ip_addrs = vec![IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)];
     let routes = Routes::new(BTreeMap::new());
 
     // build the device
@@ -281,6 +363,11 @@ fn main() -> ! {
     }
     let mut iface = builder.finalize();
 
+    // IPv6: the link-local address is always there; global addresses come from router advertisements
+    let mut slaac = slaac::Slaac::new(&hw_config.mac);
+    set_ipv6_addrs(&mut iface, &slaac.cidrs());
+    let ndisc_handle = setup_ndisc(&mut iface);
+
     // ------------- native variant -----------
     let icmp_handle = setup_icmp(&mut iface);
     let mut seq: u16 = 0;
@@ -394,10 +481,12 @@ fn main() -> ! {
                     Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
                 };
                 let mut pkt = buf.to_original::<NetPingPacket, _>().unwrap();
+                let remote = IpAddress::from(pkt.endpoint);
+                // the ICMPv6 checksum covers the source address, so we need to know which address the ping goes out on
+                let local = source_address(&iface, &remote);
                 let socket = iface.get_socket::<IcmpSocket>(icmp_handle);
-                if socket.can_send() {
+                if socket.can_send() && local.is_some() {
                     log::debug!("sending ping to {:?}", pkt.endpoint);
-                    let remote = IpAddress::from(pkt.endpoint);
                     // we take advantage of the fact that the same CID is always returned for repeated connect requests to the same SID.
                     let cid = match pkt.server {
                         XousServerId::PrivateSid(sid) => {
@@ -457,8 +546,7 @@ fn main() -> ! {
                             icmp_repr.emit(&mut icmp_packet, &device_caps.checksum);
                         }
                         IpAddress::Ipv6(_) => {
-                            // not sure if this is a valid thing to do, to just assign the source some number like this??
-                            let src_ipv6 = IpAddress::v6(0xfdaa, 0, 0, 0, 0, 0, 0, 1);
+                            let src_ipv6 = local.unwrap(); // checked above
                             let icmp_repr = Icmpv6Repr::EchoRequest {
                                 ident: PING_IDENT,
                                 seq_no: seq,
@@ -1058,8 +1146,8 @@ fn main() -> ! {
                                     log::warn!("Battery is critical! TODO: go into SHIP mode");
                                 }
                                 ComIntSources::WlanIpConfigUpdate => {
-                                    // right now the WLAN implementation only does IPV4. So IPV6 compatibility ends here.
-                                    // if IPV6 gets added to the EC/COM bus, ideally this is one of a couple spots in Xous that needs a tweak.
+                                    // the EC only does DHCPv4, so this carries the IPv4 configuration. IPv6 is configured
+                                    // by the Net crate itself, from router advertisements (see `slaac.rs`).
                                     let config = match com
                                     .wlan_get_config() {
                                         Ok(config) => config,
@@ -1103,6 +1191,17 @@ fn main() -> ! {
                                         ),
                                         Err(e) => log::error!("routing table update error: {}", e),
                                     }
+                                    // we may be on a different network now, so IPv6 configuration starts over as well.
+                                    // the router solicitation goes out on the next pump.
+                                    slaac.reset();
+                                    set_ipv6_addrs(&mut iface, &slaac.cidrs());
+                                    iface.routes_mut().remove_default_ipv6_route();
+                                    xous::try_send_message(
+                                        net_conn,
+                                        Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), 0, 0, 0, 0),
+                                    )
+                                    .ok();
+
                                     dns_allclear_hook.notify();
                                     dns_ipv4_hook.notify_custom_args([
                                         Some(u32::from_be_bytes(config.dns1)),
@@ -1487,13 +1586,18 @@ fn main() -> ! {
                     }
 
                     if socket.can_recv() {
-                        let (payload, _) = socket
+                        let (payload, src) = socket
                             .recv()
                             .expect("couldn't receive on socket despite asserting availability");
                         log::trace!("icmp payload: {:x?}", payload);
 
                         for (connection, waiting_queue) in ping_destinations.iter_mut() {
                             let remote_addr = connection.remote;
+                            // the ICMP socket receives both ICMPv4 and ICMPv6; only look at replies of the same family.
+                            // note that the reply doesn't have to come from the remote: routers report unreachable hosts.
+                            if std::mem::discriminant(&remote_addr) != std::mem::discriminant(&src) {
+                                continue;
+                            }
                             match remote_addr {
                                 IpAddress::Ipv4(_) => {
                                     let icmp_packet = Icmpv4Packet::new_checked(&payload).unwrap();
@@ -1576,18 +1680,27 @@ fn main() -> ! {
                                 }
 
                                 IpAddress::Ipv6(_) => {
-                                    // NOTE: actually not sure what src_ipv6 should be. This is just from an example.
-                                    let src_ipv6 = IpAddress::v6(0xfdaa, 0, 0, 0, 0, 0, 0, 1);
+                                    // smoltcp verified the checksum on the way in, and re-verifying it here would
+                                    // need the destination address of the packet, which the ICMP socket doesn't report.
                                     let icmp_packet = Icmpv6Packet::new_checked(&payload).unwrap();
-                                    let icmp_repr = Icmpv6Repr::parse(
+                                    let icmp_repr = match Icmpv6Repr::parse(
+                                        &src,
                                         &remote_addr,
-                                        &src_ipv6,
                                         &icmp_packet,
-                                        &device_caps.checksum,
-                                    )
-                                    .unwrap();
-                                    let ra = remote_addr.as_bytes();
+                                        &ChecksumCapabilities::ignored(),
+                                    ) {
+                                        Ok(repr) => repr,
+                                        Err(e) => {
+                                            log::warn!("couldn't parse ICMPv6 packet from {:?}: {:?}", src, e);
+                                            continue;
+                                        }
+                                    };
                                     if let Icmpv6Repr::EchoReply { seq_no, data, .. } = icmp_repr {
+                                        log::trace!(
+                                            "got icmpv6 seq no {} / data: {:x?}",
+                                            seq_no,
+                                            data
+                                        );
                                         if let Some(_) = waiting_queue.get(&seq_no) {
                                             let packet_timestamp_ms = NetworkEndian::read_i64(data);
                                             waiting_queue.remove(&seq_no);
@@ -1595,11 +1708,9 @@ fn main() -> ! {
                                                 connection.cid,
                                                 Message::new_scalar(
                                                     connection.retop,
-                                                    NetPingCallback::NoErr.to_usize().unwrap(),
-                                                    u32::from_be_bytes(ra[..4].try_into().unwrap())
-                                                        as usize,
-                                                    u32::from_be_bytes(ra[12..].try_into().unwrap())
-                                                        as usize,
+                                                    ping_callback_code(NetPingCallback::NoErr, &remote_addr),
+                                                    ping_addr_hint(&remote_addr),
+                                                    seq_no as usize,
                                                     (now as i64 - packet_timestamp_ms) as usize,
                                                 ),
                                             ) {
@@ -1621,6 +1732,10 @@ fn main() -> ! {
                                         ..
                                     } = icmp_repr
                                     {
+                                        if IpAddress::Ipv6(header.dst_addr) != remote_addr {
+                                            // the unreachable report is about some other connection
+                                            continue;
+                                        }
                                         let reason_code: u8 = From::from(reason);
                                         log::warn!(
                                             "Got dst unreachable {:?}: {:?}",
@@ -1631,14 +1746,11 @@ fn main() -> ! {
                                             connection.cid,
                                             Message::new_scalar(
                                                 connection.retop,
-                                                NetPingCallback::Unreachable.to_usize().unwrap()
+                                                ping_callback_code(NetPingCallback::Unreachable, &remote_addr)
                                                     | (reason_code as usize) << 24,
-                                                u32::from_be_bytes(ra[..4].try_into().unwrap())
-                                                    as usize,
-                                                u32::from_be_bytes(ra[8..12].try_into().unwrap())
-                                                    as usize,
-                                                u32::from_be_bytes(ra[12..].try_into().unwrap())
-                                                    as usize,
+                                                ping_addr_hint(&remote_addr),
+                                                0,
+                                                0,
                                             ),
                                         ) {
                                             Ok(_) => {}
@@ -1650,7 +1762,7 @@ fn main() -> ! {
                                             }
                                         }
                                     } else {
-                                        log::error!("got unhandled ICMP type, ignoring!");
+                                        log::debug!("got unhandled ICMPv6 type, ignoring");
                                     }
                                 }
                                 _ => unimplemented!(),
@@ -1671,9 +1783,9 @@ fn main() -> ! {
                             match xous::send_message(conn.cid,
                                 Message::new_scalar( // we should wait if the queue is full, as the "Drop" message is important
                                     conn.retop,
-                                    NetPingCallback::Drop.to_usize().unwrap(),
-                                    u32::from_be_bytes(ra[..4].try_into().unwrap()) as usize,
-                                    if ra.len() == 16 {u32::from_be_bytes(ra[12..16].try_into().unwrap()) as usize} else {0},
+                                    ping_callback_code(NetPingCallback::Drop, &conn.remote),
+                                    ping_addr_hint(&conn.remote),
+                                    if ra.len() == 16 {u32::from_be_bytes(ra[..4].try_into().unwrap()) as usize} else {0},
                                     0,
                                 )
                             ) {
@@ -1702,15 +1814,14 @@ fn main() -> ! {
 
                     // now: sequence through the waiting_queue and remove entries that have hit our timeout
                     for (conn, waiting_queue) in ping_destinations.iter_mut() {
-                        let ra = conn.remote.as_bytes();
                         waiting_queue.retain(|&seq, &mut start_time|
                             if now - start_time > ping_timeout_ms as u64 {
                                 log::debug!("timeout - removing {:?}, {}", conn.remote, seq);
                                 match xous::try_send_message(conn.cid,
                                     Message::new_scalar( // we should wait if the queue is full, as the "Drop" message is important
                                         conn.retop,
-                                        NetPingCallback::Timeout.to_usize().unwrap(),
-                                        u32::from_be_bytes(ra[..4].try_into().unwrap()) as usize,
+                                        ping_callback_code(NetPingCallback::Timeout, &conn.remote),
+                                        ping_addr_hint(&conn.remote),
                                         seq as usize,
                                         (now - start_time) as usize,
                                     )
@@ -1734,6 +1845,39 @@ fn main() -> ! {
                     }
                 }
 
+                // this block runs IPv6 address autoconfiguration off of router advertisements
+                log::trace!("pump: ndisc");
+                {
+                    let mut update = slaac.expire(now);
+                    let socket = iface.get_socket::<RawSocket>(ndisc_handle);
+                    while socket.can_recv() {
+                        match socket.recv() {
+                            Ok(packet) => {
+                                if let Some(ra) = slaac::parse_router_advert(packet) {
+                                    log::debug!("got router advertisement: {:?}", ra);
+                                    update.merge(slaac.process_router_advert(&ra, now));
+                                }
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    // solicitations are retried opportunistically, whenever the pump runs after the retry interval
+                    if net_config.is_some() && slaac.solicit(now) {
+                        log::debug!("sending router solicitation");
+                        match socket.send_slice(&slaac.router_solicitation()) {
+                            Ok(_) => {
+                                xous::try_send_message(
+                                    net_conn,
+                                    Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), 0, 0, 0, 0),
+                                )
+                                .ok();
+                            }
+                            Err(e) => log::warn!("couldn't queue router solicitation: {:?}", e),
+                        }
+                    }
+                    apply_slaac_update(&mut iface, &slaac, update, &mut dns_ipv6_hook);
+                }
+
                 // establish our next check-up interval
                 log::trace!("pump: checkup");
                 let timestamp = Instant::from_millis(now as i64);
@@ -1802,6 +1946,12 @@ fn main() -> ! {
                 };
                 buffer.replace(ser).expect("couldn't return config");
             }
+            Some(Opcode::GetIpv6Config) => {
+                let mut buffer = unsafe {
+                    Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
+                };
+                buffer.replace(slaac.config()).expect("couldn't return config");
+            }
             Some(Opcode::SubscribeWifiStats) => {
                 msg.forward(
                     cm_cid,
@@ -1879,6 +2029,9 @@ fn main() -> ! {
 
                 // note: ARP cache isn't reset
                 iface.routes_mut().remove_default_ipv4_route();
+                slaac.reset();
+                set_ipv6_addrs(&mut iface, &slaac.cidrs());
+                iface.routes_mut().remove_default_ipv6_route();
                 dns_allclear_hook.notify();
 
                 send_message(
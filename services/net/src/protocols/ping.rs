@@ -197,7 +197,7 @@ impl Ping {
                                             IpAddr::V6(_) => {
                                                 reachable.store(true, Ordering::SeqCst);
                                                 ping_time.store(timestamp as u32, Ordering::SeqCst);
-                                                log::info!("Pong from {:?} received: {} ms", remote, timestamp);
+                                                log::info!("Pong from {:?} seq {} received: {} ms", remote, seq_or_addr, timestamp);
                                             },
                                         }
                                     }
@@ -207,9 +207,14 @@ impl Ping {
                                         log::info!("Ping to {:?} timed out", remote);
                                     }
                                     Some(NetPingCallback::Unreachable) => {
-                                        let code = smoltcp::wire::Icmpv4DstUnreachable::from((op >> 24) as u8);
                                         reachable.store(false, Ordering::SeqCst);
-                                        log::info!("Ping to {:?} unreachable: {:?}", remote, code);
+                                        if (op & NET_PING_IPV6) != 0 {
+                                            let code = smoltcp::wire::Icmpv6DstUnreachable::from((op >> 24) as u8);
+                                            log::info!("Ping to {:?} unreachable: {:?}", remote, code);
+                                        } else {
+                                            let code = smoltcp::wire::Icmpv4DstUnreachable::from((op >> 24) as u8);
+                                            log::info!("Ping to {:?} unreachable: {:?}", remote, code);
+                                        }
                                     }
                                     None => {
                                         log::error!("Unknown opcode received in one-time server: {:?}", op);
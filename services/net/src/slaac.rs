@@ -0,0 +1,495 @@
+use crate::api::*;
+use smoltcp::wire::{Ipv6Address, Ipv6Cidr};
+use std::convert::TryInto;
+
+/// Interface identifiers are always 64 bits on ethernet, so that's the only prefix length SLAAC can use
+const SLAAC_PREFIX_LEN: u8 = 64;
+/// Router solicitations are retried this many times, at least `RTR_SOLICITATION_INTERVAL_MS` apart (RFC 4861 section 10)
+const MAX_RTR_SOLICITATIONS: u32 = 3;
+const RTR_SOLICITATION_INTERVAL_MS: u64 = 4000;
+/// Lifetime below which an advertisement can't shorten the lifetime of an existing address (RFC 4862 section 5.5.3)
+const TWO_HOURS_S: u32 = 2 * 60 * 60;
+const INFINITE_LIFETIME: u32 = 0xffff_ffff;
+
+const IPV6_HEADER_LEN: usize = 40;
+const IP_PROTOCOL_ICMPV6: u8 = 58;
+const ICMPV6_ROUTER_SOLICIT: u8 = 133;
+const ICMPV6_ROUTER_ADVERT: u8 = 134;
+const NDISC_OPT_SOURCE_LLADDR: u8 = 1;
+const NDISC_OPT_PREFIX_INFO: u8 = 3;
+const NDISC_OPT_RDNSS: u8 = 25;
+const PREFIX_FLAG_AUTONOMOUS: u8 = 0x40;
+const ALL_ROUTERS: [u8; 16] = [0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02];
+
+/// Forms a modified EUI-64 interface identifier from a MAC address (RFC 4291 appendix A)
+pub(crate) fn eui64_interface_id(mac: &[u8; 6]) -> [u8; 8] {
+    [mac[0] ^ 0x02, mac[1], mac[2], 0xff, 0xfe, mac[3], mac[4], mac[5]]
+}
+
+/// Forms the address of an interface identifier within a /64 prefix
+fn slaac_addr(prefix: &Ipv6Address, iid: &[u8; 8]) -> Ipv6Address {
+    let mut addr = [0u8; 16];
+    addr[..8].copy_from_slice(&prefix.0[..8]);
+    addr[8..].copy_from_slice(iid);
+    Ipv6Address(addr)
+}
+
+fn is_link_local(addr: &[u8]) -> bool {
+    addr[0] == 0xfe && (addr[1] & 0xc0) == 0x80
+}
+
+/// Computes the ICMPv6 checksum of `icmp`, including the IPv6 pseudo-header. Over a packet with a
+/// valid checksum, this returns 0.
+fn icmpv6_checksum(src: &[u8], dst: &[u8], icmp: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut add = |data: &[u8]| {
+        for chunk in data.chunks(2) {
+            sum += if chunk.len() == 2 {
+                u16::from_be_bytes([chunk[0], chunk[1]]) as u32
+            } else {
+                (chunk[0] as u32) << 8
+            };
+        }
+    };
+    add(src);
+    add(dst);
+    add(&(icmp.len() as u32).to_be_bytes());
+    add(&[0, 0, 0, IP_PROTOCOL_ICMPV6]);
+    add(icmp);
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// An autonomous prefix taken from a router advertisement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SlaacPrefix {
+    /// the prefix, with the interface identifier bits cleared
+    pub(crate) prefix: Ipv6Address,
+    /// seconds
+    pub(crate) valid_lifetime: u32,
+}
+
+/// The parts of a router advertisement that matter to SLAAC
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RouterAdvert {
+    /// link-local address of the router
+    pub(crate) router: Ipv6Address,
+    /// seconds; 0 means the router is not a default router
+    pub(crate) router_lifetime: u16,
+    pub(crate) prefixes: Vec<SlaacPrefix>,
+    /// recursive DNS servers (RFC 8106)
+    pub(crate) dns: Vec<Ipv6Address>,
+}
+
+/// Parses a router advertisement out of a complete IPv6 packet, as handed out by a raw ICMPv6 socket.
+/// Returns `None` for anything that isn't a valid router advertisement (RFC 4861 section 6.1.2).
+/// The checksum is verified here, because raw sockets get their copy before smoltcp checks it.
+pub(crate) fn parse_router_advert(packet: &[u8]) -> Option<RouterAdvert> {
+    if packet.len() < IPV6_HEADER_LEN
+        || packet[0] >> 4 != 6
+        || packet[6] != IP_PROTOCOL_ICMPV6
+        || packet[7] != 255 // advertisements must not have been forwarded
+    {
+        return None;
+    }
+    let payload_len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+    let src = &packet[8..24];
+    let dst = &packet[24..40];
+    let icmp = packet.get(IPV6_HEADER_LEN..IPV6_HEADER_LEN + payload_len)?;
+    if !is_link_local(src)
+        || icmp.len() < 16
+        || icmp[0] != ICMPV6_ROUTER_ADVERT
+        || icmp[1] != 0
+        || icmpv6_checksum(src, dst, icmp) != 0
+    {
+        return None;
+    }
+    let mut ra = RouterAdvert {
+        router: Ipv6Address::from_bytes(src),
+        router_lifetime: u16::from_be_bytes([icmp[6], icmp[7]]),
+        prefixes: Vec::new(),
+        dns: Vec::new(),
+    };
+    let mut options = &icmp[16..];
+    while options.len() >= 2 {
+        let len = options[1] as usize * 8;
+        if len == 0 || len > options.len() {
+            // a zero-length option would loop forever, and a truncated one means the packet is garbage
+            return None;
+        }
+        let option = &options[..len];
+        match option[0] {
+            NDISC_OPT_PREFIX_INFO if len == 32 => {
+                let prefix_len = option[2];
+                let flags = option[3];
+                let valid_lifetime = u32::from_be_bytes(option[4..8].try_into().unwrap());
+                let preferred_lifetime = u32::from_be_bytes(option[8..12].try_into().unwrap());
+                let mut prefix = [0u8; 16];
+                prefix[..8].copy_from_slice(&option[16..24]);
+                if (flags & PREFIX_FLAG_AUTONOMOUS) != 0
+                    && prefix_len == SLAAC_PREFIX_LEN
+                    && preferred_lifetime <= valid_lifetime
+                    && !is_link_local(&prefix)
+                {
+                    ra.prefixes.push(SlaacPrefix {
+                        prefix: Ipv6Address(prefix),
+                        valid_lifetime,
+                    });
+                }
+            }
+            NDISC_OPT_RDNSS if len >= 24 => {
+                let lifetime = u32::from_be_bytes(option[4..8].try_into().unwrap());
+                if lifetime != 0 {
+                    for addr in option[8..].chunks_exact(16) {
+                        ra.dns.push(Ipv6Address::from_bytes(addr));
+                    }
+                }
+            }
+            _ => {} // unknown options must be ignored
+        }
+        options = &options[len..];
+    }
+    Some(ra)
+}
+
+/// A global address formed by SLAAC
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SlaacAddr {
+    addr: Ipv6Address,
+    /// time in ms at which the address expires; `None` if it never does
+    valid_until: Option<u64>,
+}
+
+/// What changed as a result of an advertisement or an expiry, so the caller can update the interface
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SlaacUpdate {
+    pub(crate) addrs_changed: bool,
+    pub(crate) router_changed: bool,
+    /// DNS servers that were not known before
+    pub(crate) new_dns: Vec<Ipv6Address>,
+}
+impl SlaacUpdate {
+    pub(crate) fn merge(&mut self, other: SlaacUpdate) {
+        self.addrs_changed |= other.addrs_changed;
+        self.router_changed |= other.router_changed;
+        self.new_dns.extend(other.new_dns);
+    }
+}
+
+/// IPv6 stateless address autoconfiguration (RFC 4862) state of the (one and only) interface.
+///
+/// smoltcp 0.8 handles neighbor discovery, but drops router advertisements on the floor. However, a raw
+/// ICMPv6 socket still gets a copy of every ICMPv6 packet, so the Net crate picks the advertisements up
+/// there, and this module turns them into addresses, a default router and DNS servers (RFC 8106).
+///
+/// Only the parts of SLAAC that matter for a client on a home or office network are implemented:
+///   - a link-local address is formed from the MAC address when the interface comes up
+///   - a global address is formed for every autonomous /64 prefix that is advertised
+///   - valid lifetimes are honored, including the two-hour rule against spoofed advertisements
+///   - up to `MAX_RTR_SOLICITATIONS` router solicitations are sent after every link configuration
+///
+/// Duplicate address detection is not performed; the interface identifiers are derived from the MAC
+/// address, so collisions should only happen if something is already very wrong on the network.
+/// Preferred lifetimes are checked for consistency, but deprecated addresses stay usable until they expire.
+pub(crate) struct Slaac {
+    mac: [u8; 6],
+    iid: [u8; 8],
+    addrs: Vec<SlaacAddr>,
+    /// default router, and the time in ms at which it expires
+    router: Option<(Ipv6Address, u64)>,
+    dns: Vec<Ipv6Address>,
+    solicits_sent: u32,
+    last_solicit: u64,
+}
+impl Slaac {
+    pub(crate) fn new(mac: &[u8; 6]) -> Self {
+        Slaac {
+            mac: *mac,
+            iid: eui64_interface_id(mac),
+            addrs: Vec::new(),
+            router: None,
+            dns: Vec::new(),
+            solicits_sent: 0,
+            last_solicit: 0,
+        }
+    }
+    pub(crate) fn link_local(&self) -> Ipv6Address {
+        slaac_addr(&Ipv6Address([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), &self.iid)
+    }
+    /// The addresses to configure on the interface. Global addresses come first, because smoltcp
+    /// picks the first address of the right family when a socket doesn't specify one.
+    pub(crate) fn cidrs(&self) -> Vec<Ipv6Cidr> {
+        let mut cidrs: Vec<Ipv6Cidr> = self.addrs.iter()
+            .map(|a| Ipv6Cidr::new(a.addr, SLAAC_PREFIX_LEN))
+            .collect();
+        cidrs.push(Ipv6Cidr::new(self.link_local(), SLAAC_PREFIX_LEN));
+        cidrs
+    }
+    pub(crate) fn router(&self) -> Option<Ipv6Address> {
+        self.router.map(|(router, _)| router)
+    }
+    /// Forgets everything learned from the network and starts soliciting routers again. Call this
+    /// whenever the link is (re)configured, since we may have moved to a different network.
+    pub(crate) fn reset(&mut self) {
+        self.addrs.clear();
+        self.router = None;
+        self.dns.clear();
+        self.solicits_sent = 0;
+    }
+    /// Returns true if a router solicitation should be sent at `now`, and records that it was.
+    pub(crate) fn solicit(&mut self, now: u64) -> bool {
+        if self.router.is_some()
+            || self.solicits_sent >= MAX_RTR_SOLICITATIONS
+            || (self.solicits_sent > 0 && now < self.last_solicit + RTR_SOLICITATION_INTERVAL_MS)
+        {
+            return false;
+        }
+        self.solicits_sent += 1;
+        self.last_solicit = now;
+        true
+    }
+    /// Builds a complete router solicitation packet, for sending through a raw ICMPv6 socket
+    pub(crate) fn router_solicitation(&self) -> Vec<u8> {
+        let src = self.link_local();
+        let mut icmp = vec![ICMPV6_ROUTER_SOLICIT, 0, 0, 0, 0, 0, 0, 0, NDISC_OPT_SOURCE_LLADDR, 1];
+        icmp.extend_from_slice(&self.mac);
+        let checksum = icmpv6_checksum(src.as_bytes(), &ALL_ROUTERS, &icmp);
+        icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+        let mut packet = vec![0x60, 0, 0, 0];
+        packet.extend_from_slice(&(icmp.len() as u16).to_be_bytes());
+        packet.push(IP_PROTOCOL_ICMPV6);
+        packet.push(255);
+        packet.extend_from_slice(src.as_bytes());
+        packet.extend_from_slice(&ALL_ROUTERS);
+        packet.extend_from_slice(&icmp);
+        packet
+    }
+    pub(crate) fn process_router_advert(&mut self, ra: &RouterAdvert, now: u64) -> SlaacUpdate {
+        let mut update = SlaacUpdate::default();
+        for prefix in ra.prefixes.iter() {
+            let addr = slaac_addr(&prefix.prefix, &self.iid);
+            let valid_until = if prefix.valid_lifetime == INFINITE_LIFETIME {
+                None
+            } else {
+                Some(now + prefix.valid_lifetime as u64 * 1000)
+            };
+            if let Some(existing) = self.addrs.iter_mut().find(|a| a.addr == addr) {
+                // an advertisement can always extend a lifetime, but it can only cut one short down to
+                // two hours, so that a spoofed advertisement can't take our address away
+                let remaining = existing.valid_until.map(|t| t.saturating_sub(now));
+                let extends = match (valid_until, existing.valid_until) {
+                    (None, _) => true,
+                    (Some(_), None) => false,
+                    (Some(new), Some(old)) => new > old,
+                };
+                if extends || prefix.valid_lifetime > TWO_HOURS_S {
+                    existing.valid_until = valid_until;
+                } else if remaining.map(|r| r > TWO_HOURS_S as u64 * 1000).unwrap_or(true) {
+                    existing.valid_until = Some(now + TWO_HOURS_S as u64 * 1000);
+                }
+            } else if prefix.valid_lifetime != 0 && self.addrs.len() < MAX_IPV6_ADDRS {
+                log::info!("SLAAC: configured {} from router {}", addr, ra.router);
+                self.addrs.push(SlaacAddr { addr, valid_until });
+                update.addrs_changed = true;
+            }
+        }
+        match self.router {
+            Some((router, _)) if router == ra.router && ra.router_lifetime == 0 => {
+                self.router = None;
+                update.router_changed = true;
+            }
+            _ if ra.router_lifetime != 0 => {
+                if self.router() != Some(ra.router) {
+                    update.router_changed = true;
+                }
+                self.router = Some((ra.router, now + ra.router_lifetime as u64 * 1000));
+            }
+            _ => {}
+        }
+        for dns in ra.dns.iter() {
+            if !self.dns.contains(dns) && self.dns.len() < MAX_IPV6_DNS {
+                self.dns.push(*dns);
+                update.new_dns.push(*dns);
+            }
+        }
+        update
+    }
+    /// Retires addresses and routers whose lifetimes have run out
+    pub(crate) fn expire(&mut self, now: u64) -> SlaacUpdate {
+        let mut update = SlaacUpdate::default();
+        let count = self.addrs.len();
+        self.addrs.retain(|a| a.valid_until.map(|t| t > now).unwrap_or(true));
+        update.addrs_changed = self.addrs.len() != count;
+        if let Some((router, expiry)) = self.router {
+            if expiry <= now {
+                log::info!("SLAAC: default router {} expired", router);
+                self.router = None;
+                update.router_changed = true;
+            }
+        }
+        update
+    }
+    pub(crate) fn config(&self) -> Ipv6Conf {
+        let mut config = Ipv6Conf {
+            mac: self.mac,
+            link_local: self.link_local().0,
+            ..Default::default()
+        };
+        for (dest, src) in config.addrs.iter_mut().zip(self.addrs.iter()) {
+            *dest = Some(Ipv6Prefix { addr: src.addr.0, prefix_len: SLAAC_PREFIX_LEN });
+        }
+        config.router = self.router().map(|r| r.0);
+        for (dest, src) in config.dns.iter_mut().zip(self.dns.iter()) {
+            *dest = Some(src.0);
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const ROUTER: [u8; 16] = [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0x02, 0, 0, 0xff, 0xfe, 0, 0, 0x01];
+
+    /// builds a router advertisement from its options, the way a router would send it
+    fn advert(router_lifetime: u16, options: &[u8]) -> Vec<u8> {
+        let dst = [0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01];
+        let mut icmp = vec![ICMPV6_ROUTER_ADVERT, 0, 0, 0, 64, 0];
+        icmp.extend_from_slice(&router_lifetime.to_be_bytes());
+        icmp.extend_from_slice(&[0; 8]);
+        icmp.extend_from_slice(options);
+        let checksum = icmpv6_checksum(&ROUTER, &dst, &icmp);
+        icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+        let mut packet = vec![0x60, 0, 0, 0];
+        packet.extend_from_slice(&(icmp.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[IP_PROTOCOL_ICMPV6, 255]);
+        packet.extend_from_slice(&ROUTER);
+        packet.extend_from_slice(&dst);
+        packet.extend_from_slice(&icmp);
+        packet
+    }
+    fn prefix_option(prefix: [u8; 8], flags: u8, valid: u32, preferred: u32) -> Vec<u8> {
+        let mut option = vec![NDISC_OPT_PREFIX_INFO, 4, SLAAC_PREFIX_LEN, flags];
+        option.extend_from_slice(&valid.to_be_bytes());
+        option.extend_from_slice(&preferred.to_be_bytes());
+        option.extend_from_slice(&[0; 4]);
+        option.extend_from_slice(&prefix);
+        option.extend_from_slice(&[0; 8]);
+        option
+    }
+    const PREFIX: [u8; 8] = [0x20, 0x01, 0x0d, 0xb8, 0, 0x01, 0, 0];
+
+    #[test]
+    fn test_link_local() {
+        let slaac = Slaac::new(&MAC);
+        assert_eq!(slaac.link_local(), Ipv6Address::new(0xfe80, 0, 0, 0, 0x0011, 0x22ff, 0xfe33, 0x4455));
+        assert_eq!(slaac.cidrs(), vec![Ipv6Cidr::new(slaac.link_local(), 64)]);
+    }
+
+    #[test]
+    fn test_parse_advert() {
+        let mut options = prefix_option(PREFIX, 0xc0, 86400, 14400);
+        // a link-local prefix and a prefix without the autonomous flag can't be used for SLAAC
+        options.extend(prefix_option([0xfe, 0x80, 0, 0, 0, 0, 0, 0], 0xc0, 86400, 14400));
+        options.extend(prefix_option(PREFIX, 0x80, 86400, 14400));
+        options.extend_from_slice(&[NDISC_OPT_RDNSS, 3, 0, 0, 0, 0, 0x0e, 0x10]);
+        options.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x53]);
+        options.extend_from_slice(&[NDISC_OPT_SOURCE_LLADDR, 1, 2, 0, 0, 0, 0, 1]);
+        let packet = advert(1800, &options);
+        let ra = parse_router_advert(&packet).unwrap();
+        assert_eq!(ra.router, Ipv6Address(ROUTER));
+        assert_eq!(ra.router_lifetime, 1800);
+        assert_eq!(ra.prefixes, vec![SlaacPrefix {
+            prefix: Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0),
+            valid_lifetime: 86400,
+        }]);
+        assert_eq!(ra.dns, vec![Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)]);
+
+        // corruption, truncation and forwarded packets are all rejected
+        let mut corrupt = packet.clone();
+        corrupt[60] ^= 1;
+        assert!(parse_router_advert(&corrupt).is_none());
+        assert!(parse_router_advert(&packet[..packet.len() - 1]).is_none());
+        let mut forwarded = packet.clone();
+        forwarded[7] = 254;
+        assert!(parse_router_advert(&forwarded).is_none());
+        // zero-length options are malformed
+        assert!(parse_router_advert(&advert(1800, &[NDISC_OPT_RDNSS, 0, 0, 0, 0, 0, 0, 0])).is_none());
+    }
+
+    #[test]
+    fn test_router_solicitation() {
+        let slaac = Slaac::new(&MAC);
+        let packet = slaac.router_solicitation();
+        assert_eq!(packet.len(), IPV6_HEADER_LEN + 16);
+        assert_eq!(&packet[8..24], slaac.link_local().as_bytes());
+        assert_eq!(&packet[24..40], &ALL_ROUTERS);
+        assert_eq!(icmpv6_checksum(&packet[8..24], &packet[24..40], &packet[40..]), 0);
+        assert_eq!(&packet[50..56], &MAC);
+    }
+
+    #[test]
+    fn test_slaac_lifecycle() {
+        let mut slaac = Slaac::new(&MAC);
+        assert!(slaac.solicit(0));
+        assert!(!slaac.solicit(1000));
+        assert!(slaac.solicit(RTR_SOLICITATION_INTERVAL_MS));
+
+        let ra = parse_router_advert(&advert(1800, &prefix_option(PREFIX, 0xc0, 3 * TWO_HOURS_S, 3600))).unwrap();
+        let update = slaac.process_router_advert(&ra, 10_000);
+        assert!(update.addrs_changed && update.router_changed);
+        let global = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0x0011, 0x22ff, 0xfe33, 0x4455);
+        assert_eq!(slaac.cidrs()[0], Ipv6Cidr::new(global, 64));
+        assert_eq!(slaac.cidrs().len(), 2);
+        assert_eq!(slaac.router(), Some(Ipv6Address(ROUTER)));
+        // once there is a router, there's no need to solicit one
+        assert!(!slaac.solicit(100_000));
+        // the same advertisement again changes nothing
+        assert_eq!(slaac.process_router_advert(&ra, 20_000), SlaacUpdate::default());
+
+        // the router goes away when it says so, or when its lifetime runs out
+        assert!(slaac.process_router_advert(&parse_router_advert(&advert(0, &[])).unwrap(), 25_000).router_changed);
+        assert_eq!(slaac.router(), None);
+        assert!(slaac.process_router_advert(&ra, 26_000).router_changed);
+        let update = slaac.expire(26_000 + 1800 * 1000);
+        assert!(update.router_changed && !update.addrs_changed);
+        assert_eq!(slaac.router(), None);
+
+        // an advertisement can't cut the lifetime of an address below two hours
+        let short = parse_router_advert(&advert(1800, &prefix_option(PREFIX, 0xc0, 60, 60))).unwrap();
+        slaac.process_router_advert(&short, 2_000_000);
+        assert!(!slaac.expire(2_000_000 + 60_000 + 1).addrs_changed);
+        assert!(slaac.expire(2_000_000 + TWO_HOURS_S as u64 * 1000).addrs_changed);
+        assert_eq!(slaac.cidrs().len(), 1);
+
+        slaac.process_router_advert(&ra, 60_000);
+        slaac.reset();
+        assert_eq!(slaac.cidrs().len(), 1);
+        assert_eq!(slaac.router(), None);
+        assert!(slaac.solicit(70_000));
+    }
+
+    #[test]
+    fn test_config() {
+        let mut slaac = Slaac::new(&MAC);
+        let mut options = prefix_option(PREFIX, 0xc0, INFINITE_LIFETIME, INFINITE_LIFETIME);
+        options.extend_from_slice(&[NDISC_OPT_RDNSS, 3, 0, 0, 0, 0, 0x0e, 0x10]);
+        options.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x53]);
+        let update = slaac.process_router_advert(&parse_router_advert(&advert(1800, &options)).unwrap(), 0);
+        assert_eq!(update.new_dns.len(), 1);
+        let config = slaac.config();
+        assert_eq!(config.mac, MAC);
+        assert_eq!(config.link_local, slaac.link_local().0);
+        assert_eq!(config.addrs[0].map(|a| a.prefix_len), Some(64));
+        assert!(config.addrs[1].is_none());
+        assert_eq!(config.router, Some(ROUTER));
+        assert_eq!(config.dns[0], Some([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x53]));
+        // addresses with an infinite lifetime never expire
+        assert!(!slaac.expire(u64::MAX).addrs_changed);
+    }
+}
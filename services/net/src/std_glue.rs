@@ -1,5 +1,6 @@
-use smoltcp::wire::IpAddress;
+use smoltcp::wire::{IpAddress, IpCidr};
 use crate::*;
+use crate::device::NetPhy;
 
 
 pub(crate) fn parse_address(data: &[u8]) -> Option<smoltcp::wire::IpAddress> {
@@ -38,7 +39,7 @@ pub(crate) fn write_address(address: IpAddress, data: &mut [u8]) -> Option<usize
             for (dest, src) in i.zip(a.as_bytes().iter()) {
                 *dest = *src;
             }
-            Some(16)
+            Some(17)
         }
         _ => {
             *i.next()? = 0;
@@ -47,6 +48,35 @@ pub(crate) fn write_address(address: IpAddress, data: &mut [u8]) -> Option<usize
     }
 }
 
+/// Picks the local address to use when talking to `remote`. The underlying smoltcp library can't handle
+/// unspecified source addresses, so sockets are bound to one of our addresses of the same family. For IPv6,
+/// link-local peers get our link-local address, and everything else gets a global address if we have one.
+/// `remote` may be an unspecified address, in which case a global address is preferred.
+pub(crate) fn source_address(iface: &Interface::<NetPhy>, remote: &IpAddress) -> Option<IpAddress> {
+    match remote {
+        IpAddress::Ipv4(_) => iface.ipv4_addr().map(IpAddress::Ipv4),
+        IpAddress::Ipv6(remote) => {
+            let mut link_local = None;
+            let mut global = None;
+            for cidr in iface.ip_addrs().iter() {
+                if let IpCidr::Ipv6(cidr) = cidr {
+                    if cidr.address().is_link_local() {
+                        link_local = link_local.or(Some(cidr.address()));
+                    } else {
+                        global = global.or(Some(cidr.address()));
+                    }
+                }
+            }
+            if remote.is_link_local() {
+                link_local
+            } else {
+                global.or(link_local)
+            }.map(IpAddress::Ipv6)
+        }
+        _ => None,
+    }
+}
+
 pub(crate) fn respond_with_error(mut env: xous::MessageEnvelope, code: NetError) -> Option<()> {
     // If it's not a memory message, don't fill in the return information.
     let body = match env.body.memory_message_mut() {
@@ -7,6 +7,7 @@ use smoltcp::socket::{
     TcpSocket, TcpSocketBuffer,
 };
 use smoltcp::iface::{Interface, SocketHandle};
+use smoltcp::wire::{IpAddress, IpEndpoint};
 use crate::*;
 use crate::device::NetPhy;
 
@@ -39,6 +40,19 @@ pub(crate) fn std_tcp_connect(
         }
     };
 
+    // smoltcp picks the first address of the right family if the local address is left unspecified, which
+    // isn't right for IPv6, where we may have both link-local and global addresses
+    let local_endpoint = match address {
+        IpAddress::Ipv6(_) => match source_address(iface, &address) {
+            Some(local_addr) => IpEndpoint::new(local_addr, local_port),
+            None => {
+                respond_with_error(msg, NetError::Unaddressable);
+                return;
+            }
+        },
+        _ => IpEndpoint::from(local_port),
+    };
+
     // initiates a new connection to a remote server consisting of an (Address:Port) tuple.
     // multiple connections can exist to a server, and they are further differentiated by the return port
     let tcp_socket = TcpSocket::new(
@@ -50,7 +64,7 @@ pub(crate) fn std_tcp_connect(
 
     // Attempt to connect, returning the error if there is one
     if let Err(e) = tcp_socket
-        .connect(cx, (address, remote_port), local_port)
+        .connect(cx, (address, remote_port), local_endpoint)
         .map_err(|e| match e {
             smoltcp::Error::Illegal => NetError::SocketInUse,
             smoltcp::Error::Unaddressable => NetError::Unaddressable,
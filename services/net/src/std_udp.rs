@@ -109,7 +109,8 @@ pub(crate) fn std_udp_rx(
     };
     let do_peek = body.offset.is_some();
     log::debug!("udp rx from fd {}", connection_handle_index);
-    let local_addr = match iface.ipv4_addr() {
+    let bound_addr = iface.get_socket::<UdpSocket>(*handle).endpoint().addr;
+    let local_addr = match source_address(iface, &bound_addr) {
         Some(addr) => addr,
         None => {
             std_failure(msg, NetError::Unaddressable);
@@ -118,15 +119,15 @@ pub(crate) fn std_udp_rx(
     };
     let socket = iface.get_socket::<UdpSocket>(*handle);
     let port = socket.endpoint().port;
-    // force the local address to correspond to our (one and only) IP address
+    // force the local address to correspond to one of our IP addresses, of the same family as the address the socket was bound to
     // the underlying smoltcp library can't handle unspecified source addresses
     // because the library itself works with multiple interfaces and has no default resolution mechanism
     // this may eventually get fixed see https://github.com/smoltcp-rs/smoltcp/issues/599
-    if socket.endpoint().addr != IpAddress::Ipv4(local_addr) {
+    if socket.endpoint().addr != local_addr {
         if socket.is_open() {
             socket.close();
         }
-        if let Err(e) = socket.bind(IpEndpoint{addr: IpAddress::Ipv4(local_addr), port})
+        if let Err(e) = socket.bind(IpEndpoint{addr: local_addr, port})
         .map_err(|e| match e {
             smoltcp::Error::Illegal => NetError::SocketInUse,
             smoltcp::Error::Unaddressable => NetError::Unaddressable,
@@ -219,7 +220,7 @@ pub(crate) fn std_udp_tx(
     let len = u16::from_le_bytes([bytes[19], bytes[20]]);
     // attempt the tx
     log::debug!("udp tx to fd {} -> {:?}:{} {:?}", connection_handle_index, address, remote_port, &bytes[21..21 + len as usize]);
-    let local_addr = match iface.ipv4_addr() {
+    let local_addr = match source_address(iface, &address) {
         Some(addr) => addr,
         None => {
             std_failure(msg, NetError::Unaddressable);
@@ -228,15 +229,15 @@ pub(crate) fn std_udp_tx(
     };
     let socket = iface.get_socket::<UdpSocket>(*handle);
     let port = socket.endpoint().port;
-    // force the local address to correspond to our (one and only) IP address
+    // force the local address to correspond to one of our IP addresses, of the same family as the destination
     // the underlying smoltcp library can't handle unspecified source addresses
     // because the library itself works with multiple interfaces and has no default resolution mechanism
     // this may eventually get fixed see https://github.com/smoltcp-rs/smoltcp/issues/599
-    if socket.endpoint().addr != IpAddress::Ipv4(local_addr) {
+    if socket.endpoint().addr != local_addr {
         if socket.is_open() {
             socket.close();
         }
-        if let Err(e) = socket.bind(IpEndpoint{addr: IpAddress::Ipv4(local_addr), port})
+        if let Err(e) = socket.bind(IpEndpoint{addr: local_addr, port})
         .map_err(|e| match e {
             smoltcp::Error::Illegal => NetError::SocketInUse,
             smoltcp::Error::Unaddressable => NetError::Unaddressable,
@@ -1,5 +1,6 @@
 use bitfield::bitfield;
 use std::num::NonZeroU32;
+use std::collections::BTreeMap;
 
 // on the "[allow(dead_code)]" directives: these constants are used to define the PDDB, and are
 // sometimes used by both `bin` (main.rs) and `lib` (lib.rs) views, but also, sometimes used
@@ -25,9 +26,17 @@ pub(crate) const DICT_NAME_LEN: usize = 127 - 4 - 4 - 4 - 4; // u32: flags, age,
 #[allow(dead_code)]
 pub(crate) const KEY_NAME_LEN: usize = 127 - 8 - 8 - 8 - 4 - 4; // u64: vaddr/len/resvd, u32: flags, age = 95
 #[allow(dead_code)]
-pub(crate) const PASSWORD_LEN: usize = 72; // this is actually set by bcrypt
+pub(crate) const KEY_CONTENT_TYPE_LEN: usize = 32;
+#[allow(dead_code)]
+pub(crate) const KEY_ATTR_MAX: usize = 8; // max number of user attributes per key
+#[allow(dead_code)]
+pub(crate) const KEY_ATTR_NAME_LEN: usize = 32;
+#[allow(dead_code)]
+pub(crate) const KEY_ATTR_VALUE_LEN: usize = 64;
+#[allow(dead_code)]
+pub(crate) const KEY_PAGE_LEN: usize = 32; // max number of key names returned by one `list_keys_page` call
 #[allow(dead_code)]
-pub(crate) const PDDB_MAGIC: [u8; 4] = [0x50, 0x44, 0x44, 0x42];
+pub(crate) const PASSWORD_LEN: usize = 72; // this is actually set by bcrypt
 /// migrateable version pairs
 /// PDDB_MIGRATE_1:
 ///   00.00.01.01 - xous 0.9.7 release (original base release)
@@ -35,8 +44,6 @@ pub(crate) const PDDB_MAGIC: [u8; 4] = [0x50, 0x44, 0x44, 0x42];
 #[allow(dead_code)]
 pub(crate) const PDDB_MIGRATE_1: (u32, u32) = (0x00_00_01_01, 0x00_00_02_01);
 #[allow(dead_code)]
-pub(crate) const PDDB_VERSION: u32 = 0x00_00_02_01;
-#[allow(dead_code)]
 // PDDB_A_LEN may be shorter than xous::PDDB_LEN, to speed up testing.
 #[allow(dead_code)]
 #[cfg(not(any(feature="pddbtest",feature="autobasis",feature="ci")))]
@@ -98,9 +105,6 @@ pub(crate) const FAST_REKEY_CHANCE: u32 = 26;
 
 #[allow(dead_code)]
 pub const PDDB_DEFAULT_SYSTEM_BASIS: &'static str = ".System";
-// this isn't an "official" basis, but it is used for the AAD for encrypting the FastSpace structure
-#[allow(dead_code)]
-pub(crate) const PDDB_FAST_SPACE_SYSTEM_BASIS: &'static str = ".FastSpace";
 
 #[allow(dead_code)]
 // TODO: add hardware acceleration for BCRYPT so we can hit the OWASP target without excessive UX delay
@@ -207,6 +211,62 @@ pub(crate) enum Opcode {
     /// Flush the SpaceUpdate log, restoring deniability
     FlushSpaceUpdate = 44,
 
+    /// Open a multi-key transaction on a basis
+    TxBegin = 45,
+
+    /// Stage a key write or delete into an open transaction
+    TxStage = 46,
+
+    /// Apply all the operations staged in a transaction, all-or-nothing
+    TxCommit = 47,
+
+    /// Discard a transaction without applying any of its operations
+    TxAbort = 48,
+
+    /// Read the metadata of a single key
+    KeyMetadata = 49,
+
+    /// Set the content type and user attributes of a key
+    SetKeyMetadata = 50,
+
+    /// Like `GetKeyNameAtIndex`, but also returns the key's metadata
+    GetKeyMetadataAtIndex = 51,
+
+    /// Wall-clock reference from the time server, used to stamp key metadata
+    SetTimeReference = 52,
+
+    /// List one page of key names in a dictionary, with optional prefix and range filters
+    ListKeyPage = 53,
+
+    /// Register for change notifications on a dictionary
+    Watch = 54,
+
+    /// Cancel a change notification registration
+    Unwatch = 55,
+
+    /// Seal the contents of a basis into an export blob, staged for `TransferRead`
+    ExportBasis = 56,
+    /// Allocate a staging area for an export blob, to be filled with `TransferWrite`
+    ImportBasisStage = 57,
+    /// Read a chunk of a staged export blob
+    TransferRead = 58,
+    /// Write a chunk of a staged export blob
+    TransferWrite = 59,
+    /// Create a new basis from a staged export blob
+    ImportBasis = 60,
+    /// Release a staged export blob
+    TransferDrop = 61,
+
+    /// Compact the small and large key pools of all open bases. The response is deferred until the pass is done.
+    Compact = 62,
+    /// Internal: runs the next step of a compaction pass
+    CompactStep = 63,
+
+    /// Set or clear the quota of a basis or a dictionary
+    SetQuota = 64,
+    /// Query the usage and quota of a basis or a dictionary
+    QuotaUsage = 65,
+
     /// This key type could not be decoded
     InvalidOpcode = u32::MAX as _,
 }
@@ -240,6 +300,14 @@ pub enum PddbRequestCode {
     InternalError = 8,
     AccessDenied = 9,
     Uninit = 10,
+    /// the operation was committed to disk, but will only be completed on the next mount
+    Interrupted = 11,
+    /// the supplied data is malformed, or of an unsupported version
+    InvalidData = 12,
+    /// the write would exceed the quota of the dictionary or the basis
+    QuotaExceeded = 13,
+    /// the process has too many transactions open, or has staged too much into them
+    TxLimit = 14,
 }
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -263,6 +331,86 @@ pub struct PddbBasisRequest {
     pub code: PddbRequestCode,
     pub policy: Option<BasisRetentionPolicy>,
 }
+/// Used to export a basis and to import one. The export blob itself is staged in the PDDB server and
+/// moved in `PddbBuf`-sized chunks with `TransferRead` and `TransferWrite`, referenced by `token`.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbBasisTransferRequest {
+    /// the basis to export, or the name of the basis to create on import
+    pub name: xous_ipc::String::<BASIS_NAME_LEN>,
+    /// the passphrase that protects the export blob
+    pub passphrase: xous_ipc::String::<PASSWORD_LEN>,
+    pub token: ApiToken,
+    /// length of the staged export blob
+    pub len: u64,
+    pub code: PddbRequestCode,
+}
+/// A measure of how fragmented the key pools of the open bases are. See `Pddb::compact()`.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Copy, Clone, Default)]
+pub struct PddbFragStats {
+    /// small pool pages holding key data
+    pub small_pages: u32,
+    /// small pool pages the same keys would need, if they were tightly packed
+    pub small_pages_needed: u32,
+    /// number of large keys
+    pub large_keys: u32,
+    /// 32GiB large pool slots consumed by the allocation pointer, counting slots left behind by deleted keys
+    pub large_slots: u32,
+}
+/// The result of a `Pddb::compact()` call
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PddbCompactReport {
+    pub before: PddbFragStats,
+    pub after: PddbFragStats,
+    /// number of small and large keys that were relocated
+    pub keys_moved: u32,
+    /// number of physical pages returned to the free space
+    pub pages_freed: u32,
+}
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbCompactRequest {
+    pub before: PddbFragStats,
+    pub after: PddbFragStats,
+    pub keys_moved: u32,
+    pub pages_freed: u32,
+    pub code: PddbRequestCode,
+}
+
+/// Usage and quota of a basis or a dictionary, in bytes. See `Pddb::quota_usage()`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PddbQuotaUsage {
+    /// space reserved by the keys, not counting internal bookkeeping
+    pub used: u64,
+    /// the quota, if one is set
+    pub limit: Option<u64>,
+}
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbQuotaRequest {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    /// if not set, the request is about the whole basis
+    pub dict_specified: bool,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    pub limit_specified: bool,
+    pub limit: u64,
+    pub used: u64,
+    pub code: PddbRequestCode,
+}
+/// The inner error of a write that was refused because it would take a dictionary or a basis over its
+/// quota. It is wrapped in an `ErrorKind::OutOfMemory` error; use `is_quota_exceeded()` to tell it apart
+/// from the PDDB running out of space.
+#[derive(Debug, Copy, Clone)]
+pub struct QuotaExceeded;
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PDDB quota exceeded")
+    }
+}
+impl std::error::Error for QuotaExceeded {}
+/// Returns `true` if `e` reports a write that was refused because of a quota
+pub fn is_quota_exceeded(e: &std::io::Error) -> bool {
+    matches!(e.get_ref(), Some(inner) if inner.is::<QuotaExceeded>())
+}
+
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct PddbDictRequest {
     pub basis_specified: bool,
@@ -274,6 +422,144 @@ pub struct PddbDictRequest {
     pub code: PddbRequestCode,
 }
 
+/// Filters for `Pddb::list_keys_page`. Key names are compared lexicographically, byte by byte;
+/// a key must pass every filter that is set to be listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyQuery {
+    /// only list keys that start with this string
+    pub prefix: Option<String>,
+    /// only list keys that are greater than or equal to this
+    pub start: Option<String>,
+    /// only list keys that are less than this
+    pub end: Option<String>,
+}
+impl KeyQuery {
+    pub fn matches(&self, name: &str) -> bool {
+        if let Some(prefix) = &self.prefix {
+            if !name.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        if let Some(start) = &self.start {
+            if name < start.as_str() {
+                return false;
+            }
+        }
+        if let Some(end) = &self.end {
+            if name >= end.as_str() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// One page of a key listing, as returned by `Pddb::list_keys_page`
+#[derive(Debug, Clone, Default)]
+pub struct KeyPage {
+    /// key names, in lexicographic order
+    pub keys: Vec::<String>,
+    /// pass this to the next `list_keys_page` call to continue the listing; `None` if there are no more keys
+    pub cursor: Option<String>,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbKeyPageRequest {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    pub prefix: xous_ipc::String::<KEY_NAME_LEN>,
+    pub start: xous_ipc::String::<KEY_NAME_LEN>,
+    pub end_specified: bool,
+    pub end: xous_ipc::String::<KEY_NAME_LEN>,
+    /// the cursor: only keys strictly after this one are returned
+    pub after_specified: bool,
+    pub after: xous_ipc::String::<KEY_NAME_LEN>,
+    /// max number of names requested, up to `KEY_PAGE_LEN`
+    pub limit: u32,
+    /// number of valid entries in `names`
+    pub count: u32,
+    pub names: [xous_ipc::String::<KEY_NAME_LEN>; KEY_PAGE_LEN],
+    /// set if there are more matching keys after the last one in `names`
+    pub more: bool,
+    pub code: PddbRequestCode,
+}
+impl PddbKeyPageRequest {
+    #[allow(dead_code)]
+    pub fn to_query(&self) -> KeyQuery {
+        let prefix = self.prefix.as_str().unwrap();
+        let start = self.start.as_str().unwrap();
+        KeyQuery {
+            prefix: if prefix.len() > 0 { Some(String::from(prefix)) } else { None },
+            start: if start.len() > 0 { Some(String::from(start)) } else { None },
+            end: if self.end_specified { Some(String::from(self.end.as_str().unwrap())) } else { None },
+        }
+    }
+}
+
+/// The kinds of changes reported to `Pddb::watch` callbacks
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PddbWatchEventKind {
+    /// a key was created
+    Created,
+    /// an existing key was written to
+    Updated,
+    /// a key was deleted, or if the event has no key, the whole dictionary was deleted
+    Deleted,
+    /// a basis was unlocked, which may change the union view of the dictionary
+    BasisUnlocked,
+    /// a basis was locked, which may change the union view of the dictionary
+    BasisLocked,
+}
+
+/// A change delivered to a `Pddb::watch` callback
+#[derive(Debug, Clone)]
+pub struct PddbWatchEvent {
+    pub kind: PddbWatchEventKind,
+    /// the basis in which the change happened
+    pub basis: String,
+    /// the affected dictionary; empty for basis events
+    pub dict: String,
+    /// the affected key; `None` for basis events and for the deletion of a whole dictionary
+    pub key: Option<String>,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbWatchRequest {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    /// only keys that start with this are reported; empty to report every key
+    pub prefix: xous_ipc::String::<KEY_NAME_LEN>,
+    /// chosen by the client, so its callback can be in place before any event arrives
+    pub id: u32,
+    /// server to which the events are sent, as `CbOp::Watch` messages
+    pub cb_sid: [u32; 4],
+    pub code: PddbRequestCode,
+}
+
+/// Sent by the server to a watcher's callback server
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbWatchEventIpc {
+    pub id: u32,
+    pub kind: PddbWatchEventKind,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    pub key_specified: bool,
+    pub key: xous_ipc::String::<KEY_NAME_LEN>,
+}
+impl PddbWatchEventIpc {
+    #[allow(dead_code)]
+    pub fn to_event(&self) -> PddbWatchEvent {
+        PddbWatchEvent {
+            kind: self.kind,
+            basis: String::from(self.basis.as_str().unwrap()),
+            dict: String::from(self.dict.as_str().unwrap()),
+            key: if self.key_specified { Some(String::from(self.key.as_str().unwrap())) } else { None },
+        }
+    }
+}
+
 /// A structure for requesting a token to access a particular key/value pair
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct PddbKeyRequest {
@@ -289,6 +575,47 @@ pub struct PddbKeyRequest {
     pub result: PddbRequestCode,
 }
 
+/// Operations that can be requested through a `PddbTxRequest`
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PddbTxOp {
+    /// no operation; used by begin, commit, and abort
+    None,
+    /// stage a write of the data in the request, replacing the key's contents
+    Write,
+    /// append the data in the request to the write staged just before this one
+    WriteAppend,
+    /// stage a key deletion
+    Delete,
+}
+pub(crate) const PDDB_TX_CHUNK_LEN: usize = 3584;
+/// Staged transactions live on the PDDB's heap until they are committed, so how much a single
+/// transaction, and all of the transactions of one process together, may stage is limited.
+/// Bytes count key data as well as dictionary and key names.
+pub(crate) const PDDB_TX_MAX_OPS: usize = 256;
+pub(crate) const PDDB_TX_MAX_BYTES: usize = 128 * 1024;
+pub(crate) const PDDB_TX_MAX_PROCESS_OPS: usize = 512;
+pub(crate) const PDDB_TX_MAX_PROCESS_BYTES: usize = 256 * 1024;
+/// Number of transactions a process may have open at once
+pub(crate) const PDDB_TX_MAX_PROCESS_OPEN: usize = 4;
+/// A request to manipulate a multi-key transaction. Key data larger than `PDDB_TX_CHUNK_LEN`
+/// is staged as a `Write` followed by as many `WriteAppend` requests as are needed.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbTxRequest {
+    /// transaction ID; assigned by the server on `TxBegin`
+    pub id: u32,
+    /// chosen at random once per client process. Together with the sender's PID, it identifies the
+    /// process that opened the transaction, so a process that is later given the same PID can't use it.
+    pub owner: u32,
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    pub key: xous_ipc::String::<KEY_NAME_LEN>,
+    pub op: PddbTxOp,
+    pub len: u32,
+    pub data: [u8; PDDB_TX_CHUNK_LEN],
+    pub result: PddbRequestCode,
+}
+
 /// Return codes for Read/Write API calls to the main server
 #[repr(u8)]
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
@@ -300,6 +627,7 @@ pub(crate) enum PddbRetcode {
     UnexpectedEof = 4,
     InternalError = 5,
     DiskFull = 6,
+    QuotaExceeded = 7,
 }
 
 pub(crate) const PDDB_BUF_DATA_LEN: usize = 4072;
@@ -417,6 +745,110 @@ impl PddbKeyAttrIpc {
     }
 }
 
+/// Optional metadata that the PDDB keeps alongside a key. Times are in milliseconds since the UNIX epoch
+/// (UTC); 0 means unknown, which is the case for keys written before the time was set, or by older
+/// versions of the PDDB.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMetadata {
+    /// time the key was created
+    pub created: u64,
+    /// time the key's contents were last written
+    pub modified: u64,
+    /// a short, application-defined tag describing the key's contents
+    pub content_type: Option<String>,
+    /// a few application-defined name/value pairs
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl KeyMetadata {
+    /// Checks that the content type and attributes fit within the limits of the PDDB.
+    pub fn validate(&self) -> std::io::Result<()> {
+        use std::io::{Error, ErrorKind};
+        if let Some(ct) = &self.content_type {
+            if ct.len() > KEY_CONTENT_TYPE_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "content type is too long"));
+            }
+        }
+        if self.attributes.len() > KEY_ATTR_MAX {
+            return Err(Error::new(ErrorKind::InvalidInput, "too many attributes"));
+        }
+        for (name, value) in self.attributes.iter() {
+            if name.len() == 0 || name.len() > KEY_ATTR_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "attribute name is empty or too long"));
+            }
+            if value.len() > KEY_ATTR_VALUE_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "attribute value is too long"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+/// serializeable version of the key metadata structure, plus the fields to address the key
+pub struct PddbKeyMetaIpc {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    pub key: xous_ipc::String::<KEY_NAME_LEN>,
+    /// used by `GetKeyMetadataAtIndex`, in place of `key`
+    pub index: u32,
+    pub token: [u32; 4],
+    pub created: u64,
+    pub modified: u64,
+    pub content_type: xous_ipc::String::<KEY_CONTENT_TYPE_LEN>,
+    pub attr_count: u32,
+    pub attr_names: [xous_ipc::String::<KEY_ATTR_NAME_LEN>; KEY_ATTR_MAX],
+    pub attr_values: [xous_ipc::String::<KEY_ATTR_VALUE_LEN>; KEY_ATTR_MAX],
+    pub code: PddbRequestCode,
+}
+impl PddbKeyMetaIpc {
+    #[allow(dead_code)]
+    pub fn new(basis: Option<&str>, dict: &str, key: &str, index: u32, token: [u32; 4]) -> PddbKeyMetaIpc {
+        PddbKeyMetaIpc {
+            basis_specified: basis.is_some(),
+            basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis.unwrap_or("")),
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict),
+            key: xous_ipc::String::<KEY_NAME_LEN>::from_str(key),
+            index,
+            token,
+            created: 0,
+            modified: 0,
+            content_type: xous_ipc::String::<KEY_CONTENT_TYPE_LEN>::new(),
+            attr_count: 0,
+            attr_names: [xous_ipc::String::<KEY_ATTR_NAME_LEN>::new(); KEY_ATTR_MAX],
+            attr_values: [xous_ipc::String::<KEY_ATTR_VALUE_LEN>::new(); KEY_ATTR_MAX],
+            code: PddbRequestCode::Uninit,
+        }
+    }
+    #[allow(dead_code)]
+    pub fn to_metadata(&self) -> KeyMetadata {
+        let mut attributes = BTreeMap::<String, String>::new();
+        for (name, value) in self.attr_names.iter().zip(self.attr_values.iter()).take(self.attr_count as usize) {
+            attributes.insert(String::from(name.as_str().unwrap()), String::from(value.as_str().unwrap()));
+        }
+        let content_type = self.content_type.as_str().unwrap();
+        KeyMetadata {
+            created: self.created,
+            modified: self.modified,
+            content_type: if content_type.len() > 0 { Some(String::from(content_type)) } else { None },
+            attributes,
+        }
+    }
+    /// Copies `meta` into the record. The caller is responsible for checking that it fits.
+    #[allow(dead_code)]
+    pub fn set_metadata(&mut self, meta: &KeyMetadata) {
+        self.created = meta.created;
+        self.modified = meta.modified;
+        self.content_type = xous_ipc::String::<KEY_CONTENT_TYPE_LEN>::from_str(meta.content_type.as_deref().unwrap_or(""));
+        self.attr_count = meta.attributes.len().min(KEY_ATTR_MAX) as u32;
+        for (i, (name, value)) in meta.attributes.iter().take(KEY_ATTR_MAX).enumerate() {
+            self.attr_names[i] = xous_ipc::String::<KEY_ATTR_NAME_LEN>::from_str(name);
+            self.attr_values[i] = xous_ipc::String::<KEY_ATTR_VALUE_LEN>::from_str(value);
+        }
+    }
+}
+
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Eq, PartialEq)]
 pub enum PddbRekeyOp {
     /// rekeys the a restored PDDB to the current device DNA using the "fast" method.
@@ -1,3 +1,5 @@
+mod layout;
+pub use layout::*;
 mod basis;
 pub use basis::*;
 mod dictionary;
@@ -12,6 +14,14 @@ mod types;
 pub use types::*;
 mod bcrypt;
 pub use bcrypt::*;
+mod transaction;
+pub(crate) use transaction::*;
+mod keymeta;
+pub(crate) use keymeta::*;
+mod export;
+pub(crate) use export::*;
+mod quota;
+pub(crate) use quota::*;
 
 // local to the backend
 mod murmur3;
@@ -67,7 +67,7 @@
 /// |                        |    - TBD                                  |
 /// | 0x0000_FE00_0000_0000  |  Large data pool start  (~16mm TiB)       |
 /// |                        |    - Demand-allocated, bump-pointer       |
-/// |                        |      currently no defrag                  |
+/// |                        |      compacted on request                 |
 /// ```
 ///
 /// Note that each Basis has its own memory section, and you can have "many" orthogonal Basis without
@@ -83,8 +83,9 @@
 /// Large keys are the simplest - each key starts at a VPAGE-aligned address, and allocates
 /// up from there. Any unused amount is wasted, but with a ~32k threshold you'll have no worse
 /// than 12.5% unused space, probably closer to ~7%-ish if all your data hovered around the threshold.
-/// The allocation is a simple pointer that just keeps going up. De-allocated space is never defragmented,
-/// and we just rely on the space being "huge" to save us.
+/// The allocation is a simple pointer that just keeps going up. De-allocated space is not reused by the
+/// allocator, and we mostly rely on the space being "huge" to save us; a compaction pass (see
+/// `BasisCache::compact_plan()`) slides the surviving keys down and lowers the pointer again.
 ///
 /// Small keys are kept in VPAGE-sized pools of data, and compacted together in RAM. The initial, naive
 /// implementation simply keeps all small keys in a HashMap in RAM, and when it comes time to sync them
@@ -141,29 +142,25 @@ use aes_gcm_siv::aead::NewAead;
 use aes::Aes256;
 use aes::cipher::{KeyInit, generic_array::GenericArray};
 use std::iter::IntoIterator;
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{BinaryHeap, HashMap, HashSet, BTreeMap, BTreeSet};
 use std::io::{Result, Error, ErrorKind};
 use std::cmp::Reverse;
 use std::cmp::Ordering;
 use core::num::NonZeroU32;
 
-pub(crate) const SMALL_POOL_START: u64 = 0x0000_003F_8000_0000;
-pub(crate) const SMALL_POOL_END: u64 = 0x0000_007E_FF02_0000;
-pub(crate) const SMALL_POOL_STRIDE: u64 = 0xFE_0000;
 /// we don't want this bigger than VPAGE_SIZE, because a key goal of the small pool is to
 /// reduce # of writes to the disk of small data. While we could get some gain in memory efficiency
 /// if we made this larger than a VPAGE_SIZE, we don't get much gain in terms of write reduction,
 /// and it greatly complicates the implementation. So, SMALL_CAPACITY should be less than VPAGE_SIZE.
 pub(crate) const SMALL_CAPACITY: usize = VPAGE_SIZE;
-pub(crate) const LARGE_POOL_START: u64 = 0x0000_FE00_0000_0000;
 pub(crate) const KEY_MAXCOUNT: usize = 131_071; // 2^17 - 1
 /// This is a size limit on the biggest file you can create. It's currently 32GiB. No, this is not
 /// web scale, but it's big enough to hold a typical blu-ray movie as a single file. You can adjust
 /// this constant up or down, and the trade-off is, you get more or less total number of large files
 /// allocated over the life of the filesystem. We simply "increment a pointer" when a new large file
 /// is added to create the next virtual memory spot for the large file. So at 32GiB, you can create
-/// a lifetime total of about 200 million files (this includes files you've previously deleted, until
-/// we create a mechanism for sweeping through the memory space and tracking de-allocations). Note that
+/// a lifetime total of about 200 million files (this includes files you've previously deleted, unless
+/// the PDDB is compacted, which packs the surviving files back down to the bottom of the space). Note that
 /// a "large" file includes anything over 4kiB, so if you create a 5kiB file, it can potentially grow to
 /// 32 GiB without bumping into the next large file. This is a very "lazy" way to deal with large files.
 /// Given that the PDDB is designed for a 32-bit device with only 128MiB of memory and a read/write lifetime
@@ -186,16 +183,6 @@ pub(crate) const KEY_MAXCOUNT: usize = 131_071; // 2^17 - 1
 /// because the usize type isn't big enough. Recompiling for a 64-bit target, however, should give
 /// you access to the 32GiB file size limit.
 pub(crate) const LARGE_FILE_MAX_SIZE: u64 = 0x0000_0008_0000_0000;
-
-/// The chosen "stride" of a dict/key entry. Drives a lot of key parameters in the database's characteristics.
-/// This is chosen such that 32 of these entries fit evenly into a VPAGE.
-pub(crate) const DK_STRIDE: usize = 127;
-//// DK_STRIDES per VPAGE
-pub(crate) const DK_PER_VPAGE: usize = VPAGE_SIZE / DK_STRIDE; // should be 32 - use this for computing modulus on dictionary indices
-/// size of a dictionary region in virtual memory
-pub(crate) const DICT_VSIZE: u64 = 0xFE_0000;
-/// maximum number of dictionaries in a system
-pub(crate) const DICT_MAXCOUNT: usize = 16383;
 /// default alloc hint, if none is given (needs to be non-zero)
 /// this would be the typical "minimum space" reserved for a key
 /// users are of course allowed to specify something smaller, but it should be non-zero
@@ -252,12 +239,61 @@ pub(crate) struct BasisCache {
     cache: Vec::<BasisCacheEntry>,
     /// ticktimer reference, for managing atimes
     pub(crate) tt: ticktimer_server::Ticktimer,
+    /// log of changes to report to watchers; `None` if nobody is watching
+    changes: Option<Vec::<KeyChange>>,
+}
+/// A change to a key, dictionary or basis, as recorded for watchers
+pub(crate) struct KeyChange {
+    pub(crate) kind: PddbWatchEventKind,
+    pub(crate) basis: String,
+    /// empty for basis events
+    pub(crate) dict: String,
+    /// `None` for basis events, and for the removal of a whole dictionary
+    pub(crate) key: Option<String>,
+}
+/// One unit of work of a compaction pass, as planned by `BasisCache::compact_plan()`
+pub(crate) enum CompactJob {
+    /// re-pack the small pool of a dictionary, and release the pages it no longer needs
+    SmallPool { basis: String, dict: String },
+    /// move a large key down to `target`, which is the base of a free slot in the large pool
+    LargeKey { basis: String, dict: String, key: String, target: u64 },
+    /// lower the large pool allocation pointer of a basis to just above its last key
+    LargePoolTrim { basis: String },
 }
+/// number of pages of a large key that are copied between page table syncs when it is moved
+const COMPACT_BATCH_PAGES: usize = 16;
 impl BasisCache {
     pub(crate) fn new() -> Self {
         BasisCache {
             cache: Vec::new(),
             tt: ticktimer_server::Ticktimer::new().unwrap(),
+            changes: None,
+        }
+    }
+    /// Turns the change log on or off. Telling creates from updates costs an extra key lookup per
+    /// write, so it's only done while somebody is watching.
+    pub(crate) fn track_changes(&mut self, enable: bool) {
+        if enable && self.changes.is_none() {
+            self.changes = Some(Vec::new());
+        } else if !enable {
+            self.changes = None;
+        }
+    }
+    /// Returns the changes logged since the last call, and clears the log.
+    pub(crate) fn take_changes(&mut self) -> Vec::<KeyChange> {
+        match &mut self.changes {
+            Some(changes) => std::mem::take(changes),
+            None => Vec::new(),
+        }
+    }
+    fn log_change(&mut self, kind: PddbWatchEventKind, basis: &str, dict: &str, key: Option<&str>) {
+        if let Some(changes) = &mut self.changes {
+            changes.push(KeyChange {
+                kind,
+                basis: basis.to_string(),
+                dict: dict.to_string(),
+                key: key.map(|k| k.to_string()),
+            });
         }
     }
     /// Returns a Vec which is a list of Bases to visit, in order of visitation, to create the union view.
@@ -336,6 +372,11 @@ impl BasisCache {
                 small_pool_free: BinaryHeap::<KeySmallPoolOrd>::new(),
                 aad: my_aad,
                 created: std::time::Instant::now(),
+                key_meta: None,
+                key_meta_dirty: false,
+                key_meta_offsets: HashMap::new(),
+                key_meta_len: 0,
+                key_meta_stale: HashSet::new(),
             };
             log::debug!("adding dictionary {}", name);
             basis.dicts.insert(String::from(name), dict_cache);
@@ -354,7 +395,7 @@ impl BasisCache {
 
     /// Returns a list of all the known dictionaries, across all the basis. A HashSet is returned
     /// because you can have the same-named dictionary in multiple basis, and what we're asking for
-    /// is the union of all the dictionary names, without duplicates.
+    /// is the union of all the dictionary names, without duplicates. Quota tables are not listed.
     pub(crate) fn dict_list(&mut self, hw: &mut PddbOs, basis_name: Option<&str>) -> HashSet::<String> {
         let mut dict_set = HashSet::<String>::new();
         if basis_name.is_some() {
@@ -362,7 +403,7 @@ impl BasisCache {
                 let basis = &mut self.cache[basis_index];
                 basis.populate_caches(hw);
                 for (key, dcache) in basis.dicts.iter() {
-                    if dcache.flags.valid() {
+                    if dcache.flags.valid() && key != QUOTA_DICT {
                         dict_set.insert(String::from(key));
                     }
                 }
@@ -371,7 +412,7 @@ impl BasisCache {
             for basis in self.cache.iter_mut() {
                 basis.populate_caches(hw);
                 for (key, dcache) in basis.dicts.iter() {
-                    if dcache.flags.valid() {
+                    if dcache.flags.valid() && key != QUOTA_DICT {
                         dict_set.insert(String::from(key));
                     }
                 }
@@ -401,12 +442,42 @@ impl BasisCache {
             }
         }
         if found_dict {
+            merge_list.remove(KEY_META_KEY);
             Ok(merge_list)
         } else {
             return Err(Error::new(ErrorKind::NotFound, "dictionary not found"))
         }
     }
 
+    /// Returns up to `limit` key names from a dictionary, in lexicographic order, that match `query` and sort
+    /// strictly after `after`. With no basis specified, the listing is the union of the dictionary across
+    /// all open bases, like `key_list`.
+    pub(crate) fn key_range(&mut self, hw: &mut PddbOs, dict: &str, basis_name: Option<&str>,
+        query: &KeyQuery, after: Option<&str>, limit: usize) -> Result<Vec::<String>> {
+        let mut merge_list = BTreeSet::<String>::new();
+        let mut found_dict = false;
+        let selected = self.select_basis(basis_name);
+        // each basis contributes its own first `limit` names; the first `limit` of the union are the answer
+        for basis_index in 0..self.cache.len() {
+            if basis_name.is_some() && Some(basis_index) != selected {
+                continue;
+            }
+            let basis = &mut self.cache[basis_index];
+            if !basis.ensure_dict_in_cache(hw, dict) {
+                continue;
+            }
+            if let Some(dcache) = basis.dicts.get_mut(dict) {
+                dcache.key_range(hw, &basis.v2p_map, &basis.cipher, query, after, limit, &mut merge_list);
+                found_dict = true;
+            }
+        }
+        if found_dict {
+            Ok(merge_list.into_iter().take(limit).collect())
+        } else {
+            Err(Error::new(ErrorKind::NotFound, "dictionary not found"))
+        }
+    }
+
     /// This version of the call only removes one instance of a dictionary from the specified basis.
     /// Perhaps there also needs to be a `dict_remove_all` call which iterates through every basis
     /// makes sure the dictionary is removed from all the possible known basis. Anyways, that function
@@ -423,6 +494,10 @@ impl BasisCache {
             basis.dict_delete(hw, dict, paranoid)?;
             basis.basis_sync(hw);
             basis.pt_sync(hw);
+            if dict != TX_INTENT_DICT && dict != QUOTA_DICT {
+                let name = basis.name.to_string();
+                self.log_change(PddbWatchEventKind::Deleted, &name, dict, None);
+            }
             Ok(())
         } else {
             Err(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))
@@ -534,6 +609,24 @@ impl BasisCache {
 
     pub(crate) fn key_remove(&mut self,
         hw: &mut PddbOs, dict: &str, key: &str, basis_name: Option<&str>, paranoid: bool
+    ) -> Result<()> {
+        self.key_remove_raw(hw, dict, key, basis_name, paranoid)?;
+        if !key_meta_exempt(dict, key) {
+            if let Some(basis_index) = self.select_basis(basis_name) {
+                let name = self.cache[basis_index].name.to_string();
+                self.log_change(PddbWatchEventKind::Deleted, &name, dict, Some(key));
+                // the table is written back lazily, along with the key removal itself
+                if self.key_meta_table(hw, basis_index, dict).map(|t| t.remove(key).is_some()).unwrap_or(false) {
+                    self.cache[basis_index].dicts.get_mut(dict).unwrap().key_meta_dirty = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a key, without any metadata bookkeeping
+    fn key_remove_raw(&mut self,
+        hw: &mut PddbOs, dict: &str, key: &str, basis_name: Option<&str>, paranoid: bool
     ) -> Result<()> {
         if let Some(basis_index) = self.select_basis(basis_name) {
             let basis = &mut self.cache[basis_index];
@@ -571,9 +664,66 @@ impl BasisCache {
 
     /// Updates a key in a dictionary; if it doesn't exist, creates it. User can specify a basis,
     /// or rely upon the auto-basis select algorithm.
+    ///
+    /// The key's metadata is stamped with the modification time (and the creation time, for new keys).
+    /// Writes made before the time server has given us a wall-clock reference are not stamped.
+    ///
+    /// Writes that would take the dictionary or the basis over its quota are refused with a `QuotaExceeded` error.
     pub(crate) fn key_update(&mut self,
         hw: &mut PddbOs, dict: &str, key: &str, data: &[u8], offset: Option<usize>,
         alloc_hint: Option<usize>, basis_name: Option<&str>, truncate: bool) -> Result<()> {
+        if let Some(basis_index) = self.select_basis(basis_name) {
+            if !key_meta_exempt(dict, key) && !self.quota_table(hw, basis_index).is_empty() {
+                let reserved = self.quota_estimate(hw, basis_index, dict, key, data.len() + offset.unwrap_or(0), alloc_hint);
+                self.quota_check(hw, basis_index, &[(dict, key, Some(reserved))])?;
+            }
+        }
+        self.key_update_stamped(hw, dict, key, data, offset, alloc_hint, basis_name, truncate)
+    }
+
+    /// Updates a key and its metadata, without checking quotas
+    fn key_update_stamped(&mut self,
+        hw: &mut PddbOs, dict: &str, key: &str, data: &[u8], offset: Option<usize>,
+        alloc_hint: Option<usize>, basis_name: Option<&str>, truncate: bool) -> Result<()> {
+        // internal keys are neither stamped nor reported to watchers
+        let basis_index = match self.select_basis(basis_name) {
+            Some(index) if !key_meta_exempt(dict, key) => index,
+            _ => return self.key_update_raw(hw, dict, key, data, offset, alloc_hint, basis_name, truncate),
+        };
+        let name = self.cache[basis_index].name.to_string();
+        let now = hw.utc_now_ms();
+        let has_entry = now != 0 && self.key_meta_table(hw, basis_index, dict).map(|t| t.contains_key(key)).unwrap_or(false);
+        // only look the key up if somebody needs to know whether this write creates it
+        let is_new = !has_entry && (now != 0 || self.changes.is_some()) && match self.key_attributes(hw, dict, key, Some(&name)) {
+            Ok(attr) => !attr.flags.valid(),
+            Err(_) => true,
+        };
+        self.key_update_raw(hw, dict, key, data, offset, alloc_hint, Some(&name), truncate)?;
+        self.log_change(if is_new { PddbWatchEventKind::Created } else { PddbWatchEventKind::Updated }, &name, dict, Some(key));
+        if now == 0 {
+            return Ok(());
+        }
+
+        let table = self.key_meta_table(hw, basis_index, dict).expect("dictionary was just written, but is not there");
+        let entry = table.entry(key.to_string()).or_insert(KeyMetadata {
+            created: if is_new { now } else { 0 },
+            ..Default::default()
+        });
+        entry.modified = now;
+        if has_entry {
+            // modification times of existing entries are written out on the next sync
+            self.cache[basis_index].dicts.get_mut(dict).unwrap().key_meta_stale.insert(key.to_string());
+            Ok(())
+        } else {
+            // new entries are written out right away, along with the key itself
+            self.key_meta_append(hw, basis_index, dict, key)
+        }
+    }
+
+    /// Updates a key, without any metadata bookkeeping
+    fn key_update_raw(&mut self,
+        hw: &mut PddbOs, dict: &str, key: &str, data: &[u8], offset: Option<usize>,
+        alloc_hint: Option<usize>, basis_name: Option<&str>, truncate: bool) -> Result<()> {
 
         // we have to estimate how many pages are needed *before* we do anything, because we can't
         // mutate the page table to allocate data while we're accessing the page table. This huge gob of code
@@ -715,6 +865,411 @@ impl BasisCache {
         }
     }
 
+    /// Applies a list of key writes and deletes to a single basis as one unit. See `transaction.rs` for
+    /// a description of the intent record. The operations are validated before anything is written, so
+    /// an error from validation (e.g. deleting a key that doesn't exist) leaves the basis untouched.
+    ///
+    /// If the intent record is committed but an operation subsequently fails to apply, this returns
+    /// `ErrorKind::Interrupted`. The record stays on disk, and the transaction is rolled forward by the
+    /// next call to `tx_recover` on the basis.
+    pub(crate) fn tx_commit(&mut self, hw: &mut PddbOs, ops: &[TxOp], basis_name: Option<&str>) -> Result<()> {
+        let basis_index = self.select_basis(basis_name)
+            .ok_or(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))?;
+        let name = self.cache[basis_index].name.to_string();
+        for (index, op) in ops.iter().enumerate() {
+            if op.dict() == TX_INTENT_DICT || op.dict() == QUOTA_DICT {
+                return Err(Error::new(ErrorKind::PermissionDenied, "dictionary is reserved for internal records"));
+            }
+            if op.key() == KEY_META_KEY {
+                return Err(Error::new(ErrorKind::PermissionDenied, "key is reserved for metadata"));
+            }
+            if let TxOp::Delete { dict, key } = op {
+                // a key is allowed to be created and deleted within the same transaction
+                let created_earlier = ops[..index].iter().any(|prev|
+                    matches!(prev, TxOp::Write { .. }) && prev.dict() == dict && prev.key() == key
+                );
+                if !created_earlier && !self.key_attributes(hw, dict, key, Some(&name))?.flags.valid() {
+                    return Err(Error::new(ErrorKind::NotFound, "key not found"));
+                }
+            }
+        }
+        if ops.len() == 0 {
+            return Ok(())
+        }
+        // quotas are checked against the end result of the whole transaction, since its operations must not
+        // fail individually once the intent record is committed.
+        if !self.quota_table(hw, basis_index).is_empty() {
+            let mut changes = Vec::<(&str, &str, Option<u64>)>::new();
+            for op in ops.iter() {
+                changes.push((op.dict(), op.key(), match op {
+                    TxOp::Write { dict, key, data } => Some(self.quota_estimate(hw, basis_index, dict, key, data.len(), None)),
+                    TxOp::Delete { .. } => None,
+                }));
+            }
+            self.quota_check(hw, basis_index, &changes)?;
+        }
+        let record = tx_record_encode(ops)?;
+        // this is the commit point: once the intent record is on disk, the transaction is applied in full,
+        // either right now or by the recovery path on the next mount.
+        self.key_update(hw, TX_INTENT_DICT, TX_INTENT_KEY, &record, None, None, Some(&name), true)?;
+        self.tx_apply(hw, ops, &name)
+    }
+
+    /// Checks a freshly mounted basis for an intent record left behind by an interrupted commit, and
+    /// rolls it forward. Returns `true` if an interrupted transaction was found.
+    pub(crate) fn tx_recover(&mut self, hw: &mut PddbOs, basis_name: &str) -> Result<bool> {
+        if self.dict_attributes(hw, TX_INTENT_DICT, Some(basis_name)).is_err() {
+            return Ok(false)
+        }
+        // if the dictionary exists but the record doesn't, we were interrupted while retiring the record;
+        // all the operations were applied, and the dictionary just has to be cleaned up.
+        let ops = match self.key_attributes(hw, TX_INTENT_DICT, TX_INTENT_KEY, Some(basis_name)) {
+            Ok(attr) => {
+                let mut record = vec![0u8; attr.len];
+                self.key_read(hw, TX_INTENT_DICT, TX_INTENT_KEY, &mut record, None, Some(basis_name))?;
+                match tx_record_decode(&record) {
+                    Ok(ops) => ops,
+                    Err(e) => {
+                        // the record is written before any operation is applied, so an unreadable record means nothing was applied.
+                        log::warn!("Discarding unreadable transaction record in basis {}: {:?}", basis_name, e);
+                        Vec::new()
+                    }
+                }
+            }
+            Err(_) => {
+                // a move record is cleaned up by `compact_recover()` instead
+                if self.key_attributes(hw, TX_INTENT_DICT, TX_MOVE_KEY, Some(basis_name)).map(|a| a.flags.valid()).unwrap_or(false) {
+                    return Ok(false)
+                }
+                Vec::new()
+            }
+        };
+        log::info!("Rolling forward interrupted transaction in basis {} ({} operations)", basis_name, ops.len());
+        self.tx_apply(hw, &ops, basis_name)?;
+        Ok(true)
+    }
+
+    /// Applies the operations of a committed transaction, then retires its intent record. Every step is
+    /// idempotent, so this is safe to re-run on a transaction that was partially applied.
+    fn tx_apply(&mut self, hw: &mut PddbOs, ops: &[TxOp], basis_name: &str) -> Result<()> {
+        for op in ops.iter() {
+            let result = match op {
+                TxOp::Write { dict, key, data } => self.key_replace(hw, dict, key, data, basis_name),
+                TxOp::Delete { dict, key } => match self.key_remove(hw, dict, key, Some(basis_name), false) {
+                    // already removed by an earlier, interrupted attempt
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                    result => result,
+                },
+            };
+            if let Err(e) = result {
+                log::error!("Transaction operation on {}:{} failed: {:?}", op.dict(), op.key(), e);
+                return Err(Error::new(ErrorKind::Interrupted, "transaction committed, but not fully applied"));
+            }
+        }
+        // key removals are not synced eagerly, so flush them before retiring the intent record
+        self.sync(hw, Some(basis_name))?;
+        self.dict_remove(hw, TX_INTENT_DICT, Some(basis_name), false)
+    }
+
+    /// Replaces the entire contents of a key, creating it if necessary. Quotas are not checked.
+    fn key_replace(&mut self, hw: &mut PddbOs, dict: &str, key: &str, data: &[u8], basis_name: &str) -> Result<()> {
+        // truncating a large key in place doesn't always shrink its length, so remove any longer prior version first.
+        let longer = match self.key_attributes(hw, dict, key, Some(basis_name)) {
+            Ok(attr) => attr.flags.valid() && attr.len > data.len(),
+            Err(_) => false,
+        };
+        if longer {
+            // bypass the metadata bookkeeping, so the key keeps its creation time
+            self.key_remove_raw(hw, dict, key, Some(basis_name), false)?;
+        }
+        self.key_update_stamped(hw, dict, key, data, None, None, Some(basis_name), true)
+    }
+
+    /// Returns the metadata table of `dict`, reading it in from disk if it isn't cached yet. Returns
+    /// `None` if the dictionary doesn't exist. A table that can't be decoded is treated as empty.
+    fn key_meta_table(&mut self, hw: &mut PddbOs, basis_index: usize, dict: &str) -> Option<&mut BTreeMap<String, KeyMetadata>> {
+        if !self.cache[basis_index].ensure_dict_in_cache(hw, dict) {
+            return None;
+        }
+        if self.cache[basis_index].dicts.get(dict).unwrap().key_meta.is_none() {
+            let name = self.cache[basis_index].name.to_string();
+            let (table, offsets, len) = match self.key_attributes(hw, dict, KEY_META_KEY, Some(&name)) {
+                Ok(attr) if attr.flags.valid() => {
+                    let mut record = vec![0u8; attr.len];
+                    match self.key_read(hw, dict, KEY_META_KEY, &mut record, None, Some(&name)).and_then(|_| key_meta_decode(&record)) {
+                        Ok(table) => (table, key_meta_index(&record), record.len()),
+                        Err(e) => {
+                            log::warn!("Key metadata table for {}:{} is unreadable, discarding: {:?}", name, dict, e);
+                            (BTreeMap::new(), HashMap::new(), 0)
+                        }
+                    }
+                }
+                _ => (BTreeMap::new(), HashMap::new(), 0),
+            };
+            let dict_entry = self.cache[basis_index].dicts.get_mut(dict).unwrap();
+            dict_entry.key_meta = Some(table);
+            dict_entry.key_meta_offsets = offsets;
+            dict_entry.key_meta_len = len;
+        }
+        self.cache[basis_index].dicts.get_mut(dict).unwrap().key_meta.as_mut()
+    }
+
+    /// Adds the entry for `key` to the end of the stored metadata table of `dict`, without rewriting the rest of it.
+    fn key_meta_append(&mut self, hw: &mut PddbOs, basis_index: usize, dict: &str, key: &str) -> Result<()> {
+        let name = self.cache[basis_index].name.to_string();
+        let dict_entry = self.cache[basis_index].dicts.get_mut(dict).expect("dictionary was just written, but is not there");
+        if dict_entry.key_meta_dirty || dict_entry.key_meta_len == 0 {
+            // there's no stored table to add to, or it's going to be rewritten anyway
+            dict_entry.key_meta_dirty = true;
+            return self.key_meta_flush(hw, basis_index, dict);
+        }
+        let table = dict_entry.key_meta.as_ref().expect("metadata table was just updated, but is not there");
+        let entry = key_meta_encode_entry(key, &table[key])?;
+        let count = table.len() as u32;
+        let offset = dict_entry.key_meta_len;
+        dict_entry.key_meta_len += entry.len();
+        dict_entry.key_meta_offsets.insert(key.to_string(), offset);
+        dict_entry.key_meta_stale.remove(key);
+        // leave room to grow, so that the table doesn't have to move on every append
+        let alloc_hint = Some(dict_entry.key_meta_len * 2);
+        self.key_update_raw(hw, dict, KEY_META_KEY, &entry, Some(offset), alloc_hint, Some(&name), false)?;
+        self.key_update_raw(hw, dict, KEY_META_KEY, &count.to_le_bytes(), Some(KEY_META_COUNT_OFFSET), alloc_hint, Some(&name), false)
+    }
+
+    /// Writes the changes to the metadata table of `dict` back to disk: the whole table if entries were removed or
+    /// resized, otherwise just the modification times that changed.
+    fn key_meta_flush(&mut self, hw: &mut PddbOs, basis_index: usize, dict: &str) -> Result<()> {
+        let name = self.cache[basis_index].name.to_string();
+        let dict_entry = match self.cache[basis_index].dicts.get_mut(dict) {
+            Some(dict_entry) => dict_entry,
+            None => return Ok(()),
+        };
+        let stale = std::mem::take(&mut dict_entry.key_meta_stale);
+        if !dict_entry.key_meta_dirty {
+            let patches: Vec<(usize, u64)> = match &dict_entry.key_meta {
+                Some(table) => stale.iter()
+                    .filter_map(|key| Some((dict_entry.key_meta_offsets.get(key)? + key_meta_modified_offset(key), table.get(key)?.modified)))
+                    .collect(),
+                None => Vec::new(),
+            };
+            for (offset, modified) in patches {
+                self.key_update_raw(hw, dict, KEY_META_KEY, &modified.to_le_bytes(), Some(offset), None, Some(&name), false)?;
+            }
+            return Ok(());
+        }
+        dict_entry.key_meta_dirty = false;
+        let record = match &dict_entry.key_meta {
+            Some(table) if table.len() > 0 => Some(key_meta_encode(table)?),
+            _ => None,
+        };
+        dict_entry.key_meta_offsets = record.as_ref().map(|r| key_meta_index(r)).unwrap_or_default();
+        dict_entry.key_meta_len = record.as_ref().map(|r| r.len()).unwrap_or(0);
+        if let Some(record) = record {
+            self.key_replace(hw, dict, KEY_META_KEY, &record, &name)
+        } else {
+            match self.key_remove_raw(hw, dict, KEY_META_KEY, Some(&name), false) {
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                result => result,
+            }
+        }
+    }
+
+    /// Writes out every metadata table in the basis that has changed.
+    fn key_meta_flush_all(&mut self, hw: &mut PddbOs, basis_index: usize) -> Result<()> {
+        let dirty: Vec<String> = self.cache[basis_index].dicts.iter()
+            .filter(|(_, d)| d.key_meta_dirty || !d.key_meta_stale.is_empty())
+            .map(|(name, _)| name.to_string())
+            .collect();
+        for dict in dirty.iter() {
+            self.key_meta_flush(hw, basis_index, dict)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the quota table of a basis, reading it in from disk if it isn't cached yet. A table that
+    /// can't be decoded is treated as empty.
+    fn quota_table(&mut self, hw: &mut PddbOs, basis_index: usize) -> &mut QuotaTable {
+        if self.cache[basis_index].quota.is_none() {
+            let name = self.cache[basis_index].name.to_string();
+            let table = match self.key_attributes(hw, QUOTA_DICT, QUOTA_KEY, Some(&name)) {
+                Ok(attr) if attr.flags.valid() => {
+                    let mut record = vec![0u8; attr.len];
+                    self.key_read(hw, QUOTA_DICT, QUOTA_KEY, &mut record, None, Some(&name))
+                        .and_then(|_| quota_decode(&record))
+                        .unwrap_or_else(|e| {
+                            log::warn!("Quota table for basis {} is unreadable, discarding: {:?}", name, e);
+                            QuotaTable::default()
+                        })
+                }
+                _ => QuotaTable::default(),
+            };
+            self.cache[basis_index].quota = Some(table);
+        }
+        self.cache[basis_index].quota.as_mut().unwrap()
+    }
+
+    /// Sets the quota of a dictionary, or of the whole basis if `dict` is `None`. A `limit` of `None`
+    /// removes the quota. Lowering a quota below the current usage is allowed: nothing is deleted, but
+    /// writes that would grow the usage any further are refused.
+    pub(crate) fn quota_set(&mut self, hw: &mut PddbOs, dict: Option<&str>, limit: Option<u64>, basis_name: Option<&str>) -> Result<()> {
+        let basis_index = self.select_basis(basis_name)
+            .ok_or(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))?;
+        if let Some(dict) = dict {
+            DictName::try_from_str(dict).or(Err(Error::new(ErrorKind::InvalidInput, "dictionary name invalid: invalid utf-8 or length")))?;
+            if dict == TX_INTENT_DICT || dict == QUOTA_DICT {
+                return Err(Error::new(ErrorKind::PermissionDenied, "dictionary is reserved for internal records"));
+            }
+        }
+        let table = self.quota_table(hw, basis_index);
+        match (dict, limit) {
+            (Some(dict), Some(limit)) => { table.dicts.insert(dict.to_string(), limit); }
+            (Some(dict), None) => { table.dicts.remove(dict); }
+            (None, limit) => table.basis = limit,
+        }
+        let record = if table.is_empty() { None } else { Some(quota_encode(table)?) };
+        let name = self.cache[basis_index].name.to_string();
+        if let Some(record) = record {
+            self.key_replace(hw, QUOTA_DICT, QUOTA_KEY, &record, &name)
+        } else {
+            match self.dict_remove(hw, QUOTA_DICT, Some(&name), false) {
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                result => result,
+            }
+        }
+    }
+
+    /// Returns the usage of a dictionary and its quota, or that of the whole basis if `dict` is `None`.
+    pub(crate) fn quota_usage(&mut self, hw: &mut PddbOs, dict: Option<&str>, basis_name: Option<&str>) -> Result<(u64, Option<u64>)> {
+        let basis_index = self.select_basis(basis_name)
+            .ok_or(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))?;
+        let table = self.quota_table(hw, basis_index);
+        let limit = match dict {
+            Some(dict) => table.dicts.get(dict).copied(),
+            None => table.basis,
+        };
+        let used = match dict {
+            Some(dict) => self.dict_usage(hw, basis_index, dict),
+            None => self.basis_usage(hw, basis_index),
+        };
+        Ok((used, limit))
+    }
+
+    /// Returns the space reserved by the keys of a dictionary, not counting its metadata table.
+    fn dict_usage(&mut self, hw: &mut PddbOs, basis_index: usize, dict: &str) -> u64 {
+        let basis = &mut self.cache[basis_index];
+        if !basis.ensure_dict_in_cache(hw, dict) {
+            return 0;
+        }
+        let dict_entry = basis.dicts.get_mut(dict).expect("entry was ensured, but somehow missing");
+        dict_entry.fill(hw, &basis.v2p_map, &basis.cipher);
+        dict_entry.keys.iter()
+            .filter(|(name, kcache)| kcache.flags.valid() && name.as_str() != KEY_META_KEY)
+            .map(|(_, kcache)| kcache.reserved)
+            .sum()
+    }
+
+    /// Returns the space reserved by the keys of a basis, not counting any internal records.
+    fn basis_usage(&mut self, hw: &mut PddbOs, basis_index: usize) -> u64 {
+        let basis = &mut self.cache[basis_index];
+        basis.populate_caches(hw);
+        basis.dicts.iter()
+            .filter(|(name, dcache)| dcache.flags.valid() && name.as_str() != TX_INTENT_DICT && name.as_str() != QUOTA_DICT)
+            .flat_map(|(_, dcache)| dcache.keys.iter())
+            .filter(|(name, kcache)| kcache.flags.valid() && name.as_str() != KEY_META_KEY)
+            .map(|(_, kcache)| kcache.reserved)
+            .sum()
+    }
+
+    /// Estimates the space a key will reserve after a write that extends to `end` bytes. Existing keys
+    /// keep their reservation if the write fits in it.
+    fn quota_estimate(&mut self, hw: &mut PddbOs, basis_index: usize, dict: &str, key: &str, end: usize, alloc_hint: Option<usize>) -> u64 {
+        let name = self.cache[basis_index].name.to_string();
+        match self.key_attributes(hw, dict, key, Some(&name)) {
+            Ok(attr) if attr.flags.valid() => {
+                if end <= attr.reserved {
+                    attr.reserved as u64
+                } else {
+                    quota_reservation(end.max(attr.len), alloc_hint)
+                }
+            }
+            _ => quota_reservation(end, alloc_hint),
+        }
+    }
+
+    /// Checks that a set of changes to the keys of a basis stays within its quotas. Each change is a key
+    /// and its new reservation, or `None` if it is deleted; later changes to the same key supersede
+    /// earlier ones. Changes that don't grow the usage are always allowed, so that a dictionary that is
+    /// over its quota can still be cleaned up.
+    fn quota_check(&mut self, hw: &mut PddbOs, basis_index: usize, changes: &[(&str, &str, Option<u64>)]) -> Result<()> {
+        if self.quota_table(hw, basis_index).is_empty() {
+            return Ok(());
+        }
+        let name = self.cache[basis_index].name.to_string();
+        let mut staged = HashMap::<(&str, &str), u64>::new();
+        let mut growth = BTreeMap::<&str, i64>::new();
+        for &(dict, key, reserved) in changes.iter() {
+            let previous = match staged.get(&(dict, key)) {
+                Some(&previous) => previous,
+                None => match self.key_attributes(hw, dict, key, Some(&name)) {
+                    Ok(attr) if attr.flags.valid() => attr.reserved as u64,
+                    _ => 0,
+                },
+            };
+            staged.insert((dict, key), reserved.unwrap_or(0));
+            *growth.entry(dict).or_insert(0) += reserved.unwrap_or(0) as i64 - previous as i64;
+        }
+        let table = self.quota_table(hw, basis_index).clone();
+        let mut total = 0;
+        for (&dict, &delta) in growth.iter() {
+            total += delta;
+            if let Some(&limit) = table.dicts.get(dict) {
+                if delta > 0 && self.dict_usage(hw, basis_index, dict) + delta as u64 > limit {
+                    log::warn!("Write to {}:{} refused, dictionary quota of {} bytes exceeded", name, dict, limit);
+                    return Err(Error::new(ErrorKind::OutOfMemory, QuotaExceeded));
+                }
+            }
+        }
+        if let Some(limit) = table.basis {
+            if total > 0 && self.basis_usage(hw, basis_index) + total as u64 > limit {
+                log::warn!("Write to basis {} refused, basis quota of {} bytes exceeded", name, limit);
+                return Err(Error::new(ErrorKind::OutOfMemory, QuotaExceeded));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the metadata of a key. Keys that exist but have no metadata return the default
+    /// (all unknown) metadata.
+    pub(crate) fn key_metadata(&mut self, hw: &mut PddbOs, dict: &str, key: &str, basis_name: Option<&str>) -> Result<KeyMetadata> {
+        let attr = self.key_attributes(hw, dict, key, basis_name)?;
+        if !attr.flags.valid() || key_meta_exempt(dict, key) {
+            return Err(Error::new(ErrorKind::NotFound, "key not found"));
+        }
+        // if no basis was specified, this resolves to the basis the key was found in
+        let basis_index = self.select_basis(Some(&attr.basis))
+            .ok_or(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))?;
+        Ok(self.key_meta_table(hw, basis_index, dict)
+            .and_then(|t| t.get(key).cloned())
+            .unwrap_or_default())
+    }
+
+    /// Sets the content type and attributes of a key. The timestamps in `meta` are ignored.
+    pub(crate) fn key_metadata_set(&mut self, hw: &mut PddbOs, dict: &str, key: &str, meta: &KeyMetadata, basis_name: Option<&str>) -> Result<()> {
+        meta.validate()?;
+        let attr = self.key_attributes(hw, dict, key, basis_name)?;
+        if !attr.flags.valid() || key_meta_exempt(dict, key) {
+            return Err(Error::new(ErrorKind::NotFound, "key not found"));
+        }
+        let basis_index = self.select_basis(Some(&attr.basis))
+            .ok_or(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))?;
+        let table = self.key_meta_table(hw, basis_index, dict).expect("key was found, but its dictionary is not there");
+        let entry = table.entry(key.to_string()).or_default();
+        entry.content_type = meta.content_type.clone();
+        entry.attributes = meta.attributes.clone();
+        self.cache[basis_index].dicts.get_mut(dict).unwrap().key_meta_dirty = true;
+        self.key_meta_flush(hw, basis_index, dict)
+    }
+
     pub(crate) fn key_attributes(&mut self, hw: &mut PddbOs, dict: &str, key: &str, basis_name: Option<&str>) -> Result<KeyAttributes> {
         if basis_name.is_none() {
             for basis in self.cache.iter_mut().rev() {
@@ -908,14 +1463,18 @@ impl BasisCache {
     }
 
     pub(crate) fn basis_add(&mut self, basis: BasisCacheEntry) {
+        let name = basis.name.to_string();
         self.cache.push(basis);
+        self.log_change(PddbWatchEventKind::BasisUnlocked, &name, "", None);
     }
 
     pub(crate) fn basis_unmount(&mut self, hw: &mut PddbOs, basis_name: &str) -> Result<()> {
         if let Some(basis_index) = self.select_basis(Some(basis_name)) {
+            self.key_meta_flush_all(hw, basis_index)?;
             let basis = &mut self.cache[basis_index];
             basis.sync(hw)?;
             self.cache.retain(|x| x.name != basis_name);
+            self.log_change(PddbWatchEventKind::BasisLocked, basis_name, "", None);
             Ok(())
         } else {
             Err(Error::new(ErrorKind::NotFound, "Basis not found"))
@@ -942,15 +1501,365 @@ impl BasisCache {
         }
     }
 
+    /// Wipes a mounted basis and forgets it, without writing back anything that was cached for it. Used
+    /// to clean up a basis that could not be set up completely.
+    pub(crate) fn basis_discard(&mut self, hw: &mut PddbOs, basis_name: &str) -> Result<()> {
+        self.basis_delete(hw, basis_name)?;
+        self.cache.retain(|x| x.name != basis_name);
+        self.log_change(PddbWatchEventKind::BasisLocked, basis_name, "", None);
+        Ok(())
+    }
+
+    /// Reads out the entire contents of a mounted basis, for use with `basis_export_seal`. Transaction
+    /// records are left behind, as they are only meaningful to the basis they were written in. Quota
+    /// tables are not listed by `dict_list`, so they are left behind as well.
+    pub(crate) fn basis_export(&mut self, hw: &mut PddbOs, basis_name: &str) -> Result<BasisExport> {
+        let basis_index = self.select_basis(Some(basis_name))
+            .ok_or(Error::new(ErrorKind::NotFound, "Basis not found"))?;
+        let mut dict_names: Vec::<String> = self.dict_list(hw, Some(basis_name))
+            .into_iter().filter(|d| d != TX_INTENT_DICT).collect();
+        dict_names.sort();
+        let mut export = BasisExport { name: basis_name.to_string(), dicts: Vec::new() };
+        for dict_name in dict_names {
+            let mut key_names: Vec::<String> = self.key_list(hw, &dict_name, Some(basis_name))?.into_iter().collect();
+            key_names.sort();
+            let mut dict = ExportDict { name: dict_name, keys: Vec::new(), meta: BTreeMap::new() };
+            for key_name in key_names {
+                let attr = self.key_attributes(hw, &dict.name, &key_name, Some(basis_name))?;
+                let mut data = vec![0u8; attr.len];
+                let readlen = self.key_read(hw, &dict.name, &key_name, &mut data, None, Some(basis_name))?;
+                data.truncate(readlen);
+                dict.keys.push(ExportKey { name: key_name, data });
+            }
+            if let Some(table) = self.key_meta_table(hw, basis_index, &dict.name) {
+                for key in dict.keys.iter() {
+                    if let Some(meta) = table.get(&key.name) {
+                        dict.meta.insert(key.name.to_string(), meta.clone());
+                    }
+                }
+            }
+            export.dicts.push(dict);
+        }
+        Ok(export)
+    }
+
+    /// Writes the contents of an export into a mounted basis, restoring key metadata as it was at the
+    /// time of the export. Meant to be used on a basis that was just created for the import.
+    pub(crate) fn basis_import(&mut self, hw: &mut PddbOs, export: &BasisExport, basis_name: &str) -> Result<()> {
+        let basis_index = self.select_basis(Some(basis_name))
+            .ok_or(Error::new(ErrorKind::NotFound, "Basis not found"))?;
+        for dict in export.dicts.iter() {
+            match self.dict_add(hw, &dict.name, Some(basis_name)) {
+                Err(e) if e.kind() != ErrorKind::AlreadyExists => return Err(e),
+                _ => (),
+            }
+            for key in dict.keys.iter() {
+                self.key_update(hw, &dict.name, &key.name, &key.data, None, None, Some(basis_name), true)?;
+            }
+            if dict.meta.len() > 0 {
+                let table = self.key_meta_table(hw, basis_index, &dict.name)
+                    .expect("dictionary was just written, but is not there");
+                for (key, meta) in dict.meta.iter() {
+                    if dict.keys.iter().any(|k| &k.name == key) {
+                        table.insert(key.to_string(), meta.clone());
+                    }
+                }
+                self.cache[basis_index].dicts.get_mut(&dict.name).unwrap().key_meta_dirty = true;
+                self.key_meta_flush(hw, basis_index, &dict.name)?;
+            }
+        }
+        self.sync(hw, Some(basis_name))
+    }
+
+    /// Measures the fragmentation of the key pools of all the open bases. This reads every dictionary
+    /// into the cache, so it's not a cheap call.
+    pub(crate) fn frag_stats(&mut self, hw: &mut PddbOs) -> PddbFragStats {
+        let mut stats = PddbFragStats::default();
+        for basis in self.cache.iter_mut() {
+            basis.populate_caches(hw);
+            for dict in basis.dicts.values() {
+                if !dict.flags.valid() {
+                    continue;
+                }
+                let (small_pages, small_pages_needed) = dict.small_pool_stats(&basis.v2p_map);
+                stats.small_pages += small_pages as u32;
+                stats.small_pages_needed += small_pages_needed as u32;
+                stats.large_keys += dict.keys.values()
+                    .filter(|k| k.flags.valid() && k.start >= LARGE_POOL_START).count() as u32;
+            }
+            let large_top = basis.large_alloc_ptr.map(|p| p.as_u64()).unwrap_or(LARGE_POOL_START);
+            stats.large_slots += ((large_top - LARGE_POOL_START + LARGE_FILE_MAX_SIZE - 1) / LARGE_FILE_MAX_SIZE) as u32;
+        }
+        stats
+    }
+
+    /// Breaks a compaction pass over all the open bases into a list of jobs, which are to be run one at a
+    /// time with `compact_step()`. Other requests can be served in between the steps: every job re-checks
+    /// its preconditions when it is run, and does nothing if they no longer hold.
+    ///
+    /// Small pools are re-packed one dictionary at a time (see `DictCacheEntry::small_pool_repack()`).
+    /// Large keys are visited in address order and slid down into the lowest free `LARGE_FILE_MAX_SIZE`
+    /// slot, which recovers the virtual space that the bump-pointer allocator leaves behind when keys
+    /// are deleted. A key is never moved up, nor into a slot that overlaps its current location, so the
+    /// old copy stays intact until the new one is committed. Each move is recorded before it starts, so if it
+    /// is interrupted, the copy that the key descriptor doesn't point at can be returned to the free space
+    /// the next time the basis is mounted (see `compact_recover()`).
+    pub(crate) fn compact_plan(&mut self, hw: &mut PddbOs) -> Vec::<CompactJob> {
+        let mut jobs = Vec::<CompactJob>::new();
+        for basis in self.cache.iter_mut() {
+            basis.populate_caches(hw);
+            let mut large_keys = Vec::<(u64, u64, String, String)>::new();
+            let mut dict_names: Vec::<&String> = basis.dicts.keys().collect();
+            dict_names.sort();
+            for dict_name in dict_names {
+                let dict = basis.dicts.get(dict_name).unwrap();
+                if !dict.flags.valid() {
+                    continue;
+                }
+                let (small_pages, small_pages_needed) = dict.small_pool_stats(&basis.v2p_map);
+                if small_pages > small_pages_needed {
+                    jobs.push(CompactJob::SmallPool { basis: basis.name.to_string(), dict: dict_name.to_string() });
+                }
+                for (key_name, kcache) in dict.keys.iter() {
+                    if kcache.flags.valid() && kcache.start >= LARGE_POOL_START {
+                        large_keys.push((kcache.start, kcache.reserved, dict_name.to_string(), key_name.to_string()));
+                    }
+                }
+            }
+            large_keys.sort();
+            let mut next_slot = LARGE_POOL_START;
+            for (start, reserved, dict, key) in large_keys {
+                let placement = if next_slot + reserved <= start {
+                    jobs.push(CompactJob::LargeKey { basis: basis.name.to_string(), dict, key, target: next_slot });
+                    next_slot
+                } else {
+                    start
+                };
+                next_slot = next_slot.max(placement + LARGE_FILE_MAX_SIZE);
+            }
+            jobs.push(CompactJob::LargePoolTrim { basis: basis.name.to_string() });
+        }
+        jobs
+    }
+
+    /// Runs one job of a compaction pass. Everything a job changes is committed to disk before it
+    /// returns, so the pass can be stopped between any two steps. Returns the number of keys that were
+    /// moved, and the number of pages that were returned to the free space.
+    pub(crate) fn compact_step(&mut self, hw: &mut PddbOs, job: &CompactJob) -> Result<(usize, usize)> {
+        match job {
+            CompactJob::SmallPool { basis, dict } => {
+                let basis_index = match self.select_basis(Some(basis)) {
+                    Some(index) => index,
+                    None => return Ok((0, 0)), // the basis was locked since the pass was planned
+                };
+                if !self.cache[basis_index].ensure_dict_in_cache(hw, dict) {
+                    return Ok((0, 0));
+                }
+                let (moved, pages_needed) = {
+                    let basis = &mut self.cache[basis_index];
+                    let dict_entry = basis.dicts.get_mut(dict).expect("entry was ensured, but somehow missing");
+                    dict_entry.fill(hw, &basis.v2p_map, &basis.cipher);
+                    let moved = dict_entry.small_pool_repack(hw, &basis.v2p_map, &basis.cipher);
+                    (moved, dict_entry.small_pool.len() + dict_entry.alloc_estimate_small())
+                };
+                if !hw.ensure_fast_space_alloc(pages_needed, &self.cache) {
+                    return Err(Error::new(ErrorKind::OutOfMemory, "No free space to compact the small pool"));
+                }
+                let basis = &mut self.cache[basis_index];
+                let dict_entry = basis.dicts.get_mut(dict).unwrap();
+                if !dict_entry.sync_small_pool(hw, &mut basis.v2p_map, &basis.cipher) {
+                    return Err(Error::new(ErrorKind::OutOfMemory, "Ran out of memory syncing small pool"));
+                }
+                basis.dict_sync(hw, dict)?;
+                basis.pt_sync(hw);
+                // the new layout is on disk; only now is it safe to let go of the pages that were vacated
+                let dict_entry = basis.dicts.get_mut(dict).unwrap();
+                let freed = dict_entry.small_pool_release_tail(hw, &mut basis.v2p_map);
+                basis.pt_sync(hw);
+                Ok((moved, freed))
+            }
+            CompactJob::LargeKey { basis, dict, key, target } => {
+                if !self.compact_copy(hw, job)? {
+                    return Ok((0, 0));
+                }
+                // commit the new location, then retire the old copy and the move record
+                let basis_index = self.select_basis(Some(basis)).expect("basis was just used, but is not there");
+                let basis_entry = &mut self.cache[basis_index];
+                let dict_entry = basis_entry.dicts.get_mut(dict).unwrap();
+                let kcache = dict_entry.keys.get_mut(key).unwrap();
+                let (start, reserved) = (kcache.start, kcache.reserved);
+                kcache.start = *target;
+                kcache.age = kcache.age.saturating_add(1);
+                kcache.clean = false;
+                dict_entry.age = dict_entry.age.saturating_add(1);
+                dict_entry.clean = false;
+                basis_entry.dict_sync(hw, dict)?;
+                basis_entry.pt_sync(hw);
+                let old_vpages: Vec::<VirtAddr> = (0..reserved).step_by(VPAGE_SIZE)
+                    .map(|offset| VirtAddr::new(start + offset).unwrap()).collect();
+                basis_entry.vpages_release(hw, &old_vpages);
+                self.dict_remove(hw, TX_INTENT_DICT, Some(basis.as_str()), false)?;
+                Ok((1, 0))
+            }
+            CompactJob::LargePoolTrim { basis } => {
+                let basis_index = match self.select_basis(Some(basis)) {
+                    Some(index) => index,
+                    None => return Ok((0, 0)), // the basis was locked since the pass was planned
+                };
+                let basis = &mut self.cache[basis_index];
+                basis.populate_caches(hw);
+                // leave a full slot above the last key, so it can still grow to the maximum file size
+                let mut large_top = LARGE_POOL_START;
+                for dict in basis.dicts.values().filter(|d| d.flags.valid()) {
+                    for kcache in dict.keys.values() {
+                        if kcache.flags.valid() && kcache.start >= LARGE_POOL_START {
+                            large_top = large_top.max(kcache.start + LARGE_FILE_MAX_SIZE);
+                        }
+                    }
+                }
+                // never hand out space that still has pages mapped into it
+                for (va, pp) in basis.v2p_map.iter() {
+                    if pp.valid() && va.get() >= LARGE_POOL_START {
+                        large_top = large_top.max(va.get() + VPAGE_SIZE as u64);
+                    }
+                }
+                basis.large_alloc_ptr = Some(PageAlignedVa::from(large_top));
+                Ok((0, 0))
+            }
+        }
+    }
+
+    /// The first half of a `CompactJob::LargeKey`: records the move, then copies the key to its new location
+    /// without committing it. Returns false if the job no longer applies. This is separate from `compact_step()`
+    /// so that the tests can interrupt a move.
+    pub(crate) fn compact_copy(&mut self, hw: &mut PddbOs, job: &CompactJob) -> Result<bool> {
+        let (basis, dict, key, target) = match job {
+            CompactJob::LargeKey { basis, dict, key, target } => (basis.as_str(), dict.as_str(), key.as_str(), *target),
+            _ => return Ok(false),
+        };
+        let basis_index = match self.select_basis(Some(basis)) {
+            Some(index) => index,
+            None => return Ok(false), // the basis was locked since the pass was planned
+        };
+        if !self.cache[basis_index].ensure_dict_in_cache(hw, dict) {
+            return Ok(false);
+        }
+        let (start, reserved) = match self.cache[basis_index].dicts.get(dict).and_then(|d| d.keys.get(key)) {
+            Some(kcache) if kcache.flags.valid() && kcache.start >= LARGE_POOL_START && target + kcache.reserved <= kcache.start
+                => (kcache.start, kcache.reserved),
+            _ => return Ok(false),
+        };
+        let offsets: Vec::<u64> = (0..reserved).step_by(VPAGE_SIZE).collect();
+        if offsets.iter().any(|&offset| self.cache[basis_index].v2p_map.contains_key(&VirtAddr::new(target + offset).unwrap())) {
+            log::warn!("compaction target for {}:{} at {:x} is not free, leaving the key in place", dict, key, target);
+            return Ok(false);
+        }
+        // the move is on disk before the first page is copied, so an interrupted move can be cleaned up at the next mount
+        let record = tx_move_encode(&TxMove { dict: dict.to_string(), key: key.to_string(), from: start, to: target, reserved })?;
+        self.key_update(hw, TX_INTENT_DICT, TX_MOVE_KEY, &record, None, None, Some(basis), true)?;
+        // make the new copy. The page tables are synced after every batch, so that a full-space sweep
+        // triggered by the allocator can see the pages that have been taken.
+        let mut copied = Vec::<VirtAddr>::new();
+        for batch in offsets.chunks(COMPACT_BATCH_PAGES) {
+            if !hw.ensure_fast_space_alloc(batch.len(), &self.cache) {
+                self.cache[basis_index].vpages_release(hw, &copied);
+                self.dict_remove(hw, TX_INTENT_DICT, Some(basis), false)?;
+                return Err(Error::new(ErrorKind::OutOfMemory, "No free space to move a large key"));
+            }
+            let basis = &mut self.cache[basis_index];
+            for &offset in batch {
+                let old_pp = match basis.v2p_map.get(&VirtAddr::new(start + offset).unwrap()) {
+                    Some(pp) => *pp,
+                    None => continue,
+                };
+                let mut new_pp = hw.try_fast_space_alloc().expect("No free space to move large key");
+                new_pp.set_valid(true);
+                // pages that were reserved but never written don't decrypt; they stay uninitialized in the copy
+                if let Some(mut page) = hw.data_decrypt_page(&basis.cipher, &basis.aad, &old_pp) {
+                    hw.data_encrypt_and_patch_page(&basis.cipher, &basis.aad, &mut page, &new_pp);
+                }
+                let new_va = VirtAddr::new(target + offset).unwrap();
+                basis.v2p_map.insert(new_va, new_pp);
+                copied.push(new_va);
+            }
+            basis.pt_sync(hw);
+        }
+        Ok(true)
+    }
+
+    /// Cleans up after a large key move that was interrupted (see `compact_copy()`): of the two locations in
+    /// the move record, the one that the key descriptor doesn't point at is returned to the free space. Nothing
+    /// is released unless every dictionary in the basis can be read, because a key in a dictionary that can't
+    /// be read looks just like an abandoned copy. Returns `true` if a move record was found and retired.
+    pub(crate) fn compact_recover(&mut self, hw: &mut PddbOs, basis_name: &str) -> Result<bool> {
+        let basis_index = match self.select_basis(Some(basis_name)) {
+            Some(index) => index,
+            None => return Err(Error::new(ErrorKind::NotFound, "basis not found")),
+        };
+        if self.dict_attributes(hw, TX_INTENT_DICT, Some(basis_name)).is_err() {
+            return Ok(false)
+        }
+        let mv = match self.key_attributes(hw, TX_INTENT_DICT, TX_MOVE_KEY, Some(basis_name)) {
+            Ok(attr) if attr.flags.valid() => {
+                let mut record = vec![0u8; attr.len];
+                self.key_read(hw, TX_INTENT_DICT, TX_MOVE_KEY, &mut record, None, Some(basis_name))?;
+                match tx_move_decode(&record) {
+                    Ok(mv) => Some(mv),
+                    Err(e) => {
+                        // the record is written before any page is copied, so an unreadable record means nothing was copied.
+                        log::warn!("Discarding unreadable move record in basis {}: {:?}", basis_name, e);
+                        None
+                    }
+                }
+            }
+            _ => return Ok(false),
+        };
+        if !self.cache[basis_index].populate_caches(hw) {
+            log::warn!("Basis {} has dictionaries that can't be read, leaving an interrupted key move for a later mount", basis_name);
+            return Ok(false);
+        }
+        if let Some(mv) = mv {
+            let basis = &mut self.cache[basis_index];
+            let abandoned = match basis.dicts.get(&mv.dict).and_then(|d| d.keys.get(&mv.key)) {
+                Some(kcache) if kcache.flags.valid() && kcache.start == mv.to => Some(mv.from),
+                Some(kcache) if kcache.flags.valid() && kcache.start == mv.from => Some(mv.to),
+                _ => None,
+            };
+            if let Some(abandoned) = abandoned {
+                // never release a page that a key claims, whatever the record says
+                let mut claimed = Vec::<(u64, u64)>::new();
+                for dict in basis.dicts.values().filter(|d| d.flags.valid()) {
+                    for kcache in dict.keys.values() {
+                        if kcache.flags.valid() && kcache.start >= LARGE_POOL_START {
+                            claimed.push((kcache.start, kcache.start + kcache.reserved));
+                        }
+                    }
+                }
+                let vpages: Vec::<VirtAddr> = (abandoned..abandoned + mv.reserved).step_by(VPAGE_SIZE)
+                    .filter(|&va| !claimed.iter().any(|&(start, end)| va >= start && va < end))
+                    .map(|va| VirtAddr::new(va).unwrap())
+                    .collect();
+                log::info!("Releasing the abandoned copy of {}:{} at {:x} in basis {}", mv.dict, mv.key, abandoned, basis_name);
+                basis.vpages_release(hw, &vpages);
+            } else {
+                log::warn!("{}:{} is at neither end of its interrupted move, leaving both copies", mv.dict, mv.key);
+            }
+        }
+        self.dict_remove(hw, TX_INTENT_DICT, Some(basis_name), false)?;
+        Ok(true)
+    }
+
     pub(crate) fn sync(&mut self, hw: &mut PddbOs, basis_name: Option<&str>) -> Result<()> {
         if basis_name.is_some() {
             if let Some(basis_index) = self.select_basis(basis_name) {
+                self.key_meta_flush_all(hw, basis_index)?;
                 self.cache[basis_index].sync(hw)?
             }
         } else {
-            for basis in self.cache.iter_mut() {
-                log::debug!("syncing {}", basis.name);
-                basis.sync(hw)?;
+            for basis_index in 0..self.cache.len() {
+                log::debug!("syncing {}", self.cache[basis_index].name);
+                self.key_meta_flush_all(hw, basis_index)?;
+                self.cache[basis_index].sync(hw)?;
             }
         }
         Ok(())
@@ -1046,6 +1955,40 @@ impl BasisCache {
         pruned
     }
 }
+
+/// Hooks for the hosted-mode tests in `tests.rs`, which need to damage the disk and inspect the page tables.
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+impl BasisCache {
+    /// Overwrites the page that holds the header of `dict` with noise, as a torn write would. Returns where the
+    /// page is and what it held, so that it can be put back with `dbg_restore_page()`.
+    pub(crate) fn dbg_corrupt_dict(&mut self, hw: &mut PddbOs, dict: &str, basis_name: &str) -> Option<(PhysPage, Vec::<u8>)> {
+        let basis_index = self.select_basis(Some(basis_name))?;
+        let basis = &mut self.cache[basis_index];
+        if !basis.ensure_dict_in_cache(hw, dict) {
+            return None;
+        }
+        let index = basis.dicts.get(dict)?.index.get() as u64;
+        let pp = *basis.v2p_map.get(&VirtAddr::new(index * DICT_VSIZE).unwrap())?;
+        let page = hw.data_decrypt_page(&basis.cipher, &basis.aad, &pp)?;
+        let mut noise = [0u8; PAGE_SIZE];
+        hw.trng_slice(&mut noise);
+        hw.patch_data(&noise, pp.page_number() * PAGE_SIZE as u32);
+        Some((pp, page))
+    }
+    /// Writes back a page that was saved by `dbg_corrupt_dict()`
+    pub(crate) fn dbg_restore_page(&mut self, hw: &mut PddbOs, basis_name: &str, pp: &PhysPage, page: &mut [u8]) {
+        let basis_index = self.select_basis(Some(basis_name)).expect("basis is not mounted");
+        let basis = &self.cache[basis_index];
+        hw.data_encrypt_and_patch_page(&basis.cipher, &basis.aad, page, pp);
+    }
+    /// Counts the pages that are mapped in `start..start + len` of a basis
+    pub(crate) fn dbg_vpages_mapped(&self, basis_name: &str, start: u64, len: u64) -> usize {
+        let basis_index = self.select_basis(Some(basis_name)).expect("basis is not mounted");
+        (start..start + len).step_by(VPAGE_SIZE)
+            .filter(|&va| self.cache[basis_index].v2p_map.get(&VirtAddr::new(va).unwrap()).map(|pp| pp.valid()).unwrap_or(false))
+            .count()
+    }
+}
 // Revise this to use references instead of allocations once we've refactored the interior mutability
 // issues with the PDDB.
 struct KeyAge {
@@ -1113,6 +2056,8 @@ pub(crate) struct BasisCacheEntry {
     pub policy: BasisRetentionPolicy,
     // rention state
     pub policy_state: u32,
+    /// quota limits of the basis, loaded on first use
+    pub quota: Option<QuotaTable>,
 }
 impl BasisCacheEntry {
     /// given a pointer to the hardware, name of the basis, and its cryptographic key, try to derive
@@ -1166,6 +2111,7 @@ impl BasisCacheEntry {
                     large_alloc_ptr: None,
                     policy,
                     policy_state: policy.derive_init_state(),
+                    quota: None,
                 };
                 if !lazy {
                     bcache.populate_caches(hw);
@@ -1190,8 +2136,9 @@ impl BasisCacheEntry {
         }
     }
 
-    /// do a deep scan of all the dictionaries and keys and attempt to populate all the caches
-    pub(crate) fn populate_caches(&mut self, hw: &mut PddbOs) {
+    /// do a deep scan of all the dictionaries and keys and attempt to populate all the caches. Returns false if
+    /// a dictionary couldn't be decrypted, in which case the caches are missing its keys.
+    pub(crate) fn populate_caches(&mut self, hw: &mut PddbOs) -> bool {
         // count number of valid dictionaries
         let mut num_valid = 0;
         for dict in self.dicts.values() {
@@ -1216,9 +2163,11 @@ impl BasisCacheEntry {
                 }
             }
             self.large_pool_update(largest_extent);
+            true
         } else { // scan the full index
             let mut try_entry = 1;
             let mut dict_count = 0;
+            let mut unreadable = 0;
             while try_entry <= DICT_MAXCOUNT && dict_count < self.num_dicts {
                 let dict_vaddr = VirtAddr::new(try_entry as u64 * DICT_VSIZE).unwrap();
                 if let Some(pp) = self.v2p_map.get(&dict_vaddr) {
@@ -1247,6 +2196,8 @@ impl BasisCacheEntry {
                             if self.free_dict_offset.is_none() { self.free_dict_offset = Some(try_entry as u32); }
                         }
                     } else {
+                        log::warn!("dictionary at {:x} in basis {} could not be decrypted", dict_vaddr, self.name);
+                        unreadable += 1;
                         if self.free_dict_offset.is_none() { self.free_dict_offset = Some(try_entry as u32); }
                     }
                 } else {
@@ -1257,6 +2208,7 @@ impl BasisCacheEntry {
             if try_entry <= DICT_MAXCOUNT {
                 if self.free_dict_offset.is_none() { self.free_dict_offset = Some(try_entry as u32); }
             }
+            unreadable == 0 && dict_count == self.num_dicts
         }
     }
 
@@ -1597,6 +2549,22 @@ impl BasisCacheEntry {
         unimplemented!();
     }
 
+    /// Overwrites the pages mapped at `vpages` with noise, returns them to the free space, and syncs the
+    /// page tables. Unmapped addresses are skipped.
+    pub(crate) fn vpages_release(&mut self, hw: &mut PddbOs, vpages: &[VirtAddr]) {
+        for vpage in vpages.iter() {
+            if let Some(pp) = self.v2p_map.get_mut(vpage) {
+                if pp.valid() {
+                    let mut noise = [0u8; PAGE_SIZE];
+                    hw.trng_slice(&mut noise);
+                    hw.patch_data(&noise, pp.page_number() * PAGE_SIZE as u32);
+                    hw.fast_space_free(pp);
+                }
+            }
+        }
+        self.pt_sync(hw);
+    }
+
     /// Syncs *only* the basis header to disk.
     pub(crate) fn basis_sync(&mut self, hw: &mut PddbOs) {
         self.last_sync = Some(hw.timestamp_now());
@@ -6,7 +6,7 @@ use core::ops::{Deref, DerefMut};
 use core::mem::size_of;
 use aes_gcm_siv::AesGcmSiv;
 use aes::Aes256;
-use std::collections::{HashMap, BinaryHeap, HashSet};
+use std::collections::{HashMap, BinaryHeap, HashSet, BTreeMap, BTreeSet};
 use std::io::{Result, Error, ErrorKind};
 use bitfield::bitfield;
 use std::cmp::{Ordering, Reverse};
@@ -15,7 +15,7 @@ bitfield! {
     #[derive(Copy, Clone, PartialEq, Eq)]
     pub struct DictFlags(u32);
     impl Debug;
-    pub valid, set_valid: 0;
+    pub valid, set_valid: DK_VALID_BIT;
 }
 
 /// RAM based copy of the dictionary structures on disk. Most of the methods on this function operate on
@@ -47,8 +47,8 @@ pub(crate) struct DictCacheEntry {
     /// goes completely empty, the entry should still exist but indicate that it's got space. Thus if a key was found allocated
     /// to the Nth index position, but the previous N-1 positions are empty, the only way we could have gotten there was if we
     /// had allocated lots of small data, filled upo the pool to the Nth position, and then deleted all of that prior data.
-    /// This situation could create pathologies in the memory usage overhead of the small_pool, which until we have a "defrag"
-    /// operation for the small pool, we may just have to live with.
+    /// This situation could create pathologies in the memory usage overhead of the small_pool; they are cleaned up by
+    /// `small_pool_repack()`, which is run as part of a compaction pass.
     pub(crate) small_pool: Vec<KeySmallPool>,
     /// free space of each small pool element. It's a collection of free space along with the Vec index of the small_pool.
     /// We don't keep the KeySmallPool itself in the small_pool_free directly because it's presumed to be more common
@@ -61,6 +61,16 @@ pub(crate) struct DictCacheEntry {
     pub(crate) aad: Vec::<u8>,
     /// ticktimer reference, for managing atimes
     pub(crate) created: std::time::Instant,
+    /// per-key metadata table, read in from the dictionary's `KEY_META_KEY` on first use
+    pub(crate) key_meta: Option<BTreeMap<String, KeyMetadata>>,
+    /// set if the stored copy of `key_meta` has to be rewritten as a whole, because entries were removed or resized
+    pub(crate) key_meta_dirty: bool,
+    /// where each entry of the stored copy of `key_meta` starts, so that entries can be updated in place
+    pub(crate) key_meta_offsets: HashMap<String, usize>,
+    /// length of the stored copy of `key_meta`; 0 if there is none, or it couldn't be decoded
+    pub(crate) key_meta_len: usize,
+    /// keys whose modification time has changed since their entry was stored; they're patched in on the next sync
+    pub(crate) key_meta_stale: HashSet<String>,
 }
 impl DictCacheEntry {
     pub fn new(dict: Dictionary, index: usize, aad: &Vec<u8>) -> DictCacheEntry {
@@ -83,6 +93,11 @@ impl DictCacheEntry {
             small_pool_free: BinaryHeap::<KeySmallPoolOrd>::new(),
             aad: my_aad,
             created: std::time::Instant::now(),
+            key_meta: None,
+            key_meta_dirty: false,
+            key_meta_offsets: HashMap::new(),
+            key_meta_len: 0,
+            key_meta_stale: HashSet::new(),
         }
     }
     /// Populates cache entries, reporting the maximum extent of large alloc data seen so far.
@@ -208,6 +223,33 @@ impl DictCacheEntry {
             }
         }
     }
+    /// Adds to `merge_list` the first `limit` valid keys, in lexicographic order, that match `query` and
+    /// sort strictly after `after`. The metadata table is never listed.
+    pub(crate) fn key_range(&mut self, hw: &mut PddbOs, v2p_map: &HashMap::<VirtAddr, PhysPage>, cipher: &AesGcmSiv::<Aes256>,
+        query: &KeyQuery, after: Option<&str>, limit: usize, merge_list: &mut BTreeSet<String>) {
+        if self.keys.len() < self.key_count as usize {
+            self.fill(hw, v2p_map, cipher);
+        }
+        // max-heap of the smallest names seen so far; the largest is evicted when it grows past the limit
+        let mut page = BinaryHeap::<&String>::new();
+        for (key, kcache) in self.keys.iter() {
+            if !kcache.flags.valid() || key == KEY_META_KEY || !query.matches(key) {
+                continue;
+            }
+            if let Some(cursor) = after {
+                if key.as_str() <= cursor {
+                    continue;
+                }
+            }
+            page.push(key);
+            if page.len() > limit {
+                page.pop();
+            }
+        }
+        for key in page.into_iter() {
+            merge_list.insert(key.to_string());
+        }
+    }
     /// Simply ensures we have the description of a key in cache. Only tries to load small key data.
     /// Required by meta-operations on the keys that operate only out of the cache.
     /// This shares a lot of code with the fill() routine -- we should condense the common routines
@@ -295,8 +337,8 @@ impl DictCacheEntry {
                     // fill in the pool with blank entries. In general, we should have a low amount of blank entries, but
                     // one situation where we could get a leak is if we allocate a large amount of small data, and then delete
                     // all but the most recently allocated one, leaving an orphan at a high index, which is then subsequently
-                    // treated as read-only so none of the subsequent write/update ops would have occassion to move it. This would
-                    // need to be remedied with a "defrag" operation, but for now, we don't have that.
+                    // treated as read-only so none of the subsequent write/update ops would have occassion to move it. This is
+                    // remedied by `small_pool_repack()` when the PDDB is compacted.
                     let ksp = KeySmallPool::new();
                     self.small_pool.push(ksp);
                 }
@@ -878,6 +920,148 @@ impl DictCacheEntry {
         true
     }
 
+    /// Returns the number of physical pages mapped into the dictionary's small pool, and the number of pages
+    /// its small keys would occupy if they were tightly packed. Only meaningful after a `fill()`.
+    pub(crate) fn small_pool_stats(&self, v2p_map: &HashMap::<VirtAddr, PhysPage>) -> (usize, usize) {
+        let base = small_storage_base_vaddr_from_indices(self.index, 0);
+        let mapped = v2p_map.iter()
+            .filter(|(va, pp)| pp.valid() && va.get() >= base && va.get() < base + SMALL_POOL_STRIDE)
+            .count();
+        (mapped, self.small_pool_packing().len())
+    }
+    /// number of pools up to and including the last one with any keys in it
+    fn small_pool_extent(&self) -> usize {
+        let mut extent = self.small_pool.len();
+        while extent > 0 && self.small_pool[extent - 1].contents.len() == 0 {
+            extent -= 1;
+        }
+        extent
+    }
+    /// First-fit-decreasing bin packing of the live small keys into fresh pools. Not optimal, but it's
+    /// never worse than 11/9ths of the optimum, which is plenty good for pages of short records.
+    fn small_pool_packing(&self) -> Vec::<KeySmallPool> {
+        let mut entries = Vec::<(u64, &String)>::new();
+        for ksp in self.small_pool.iter() {
+            for key_name in ksp.contents.iter() {
+                let kcache = self.keys.get(key_name).expect("data record without index");
+                entries.push((kcache.reserved, key_name));
+            }
+        }
+        // ties are broken by name so the packing is deterministic
+        entries.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+        let mut pools = Vec::<KeySmallPool>::new();
+        for (reserved, key_name) in entries {
+            let index = match pools.iter().position(|p| p.avail as u64 >= reserved) {
+                Some(index) => index,
+                None => {
+                    pools.push(KeySmallPool::new());
+                    pools.len() - 1
+                }
+            };
+            pools[index].contents.push(key_name.to_string());
+            pools[index].avail -= reserved as u16;
+        }
+        pools
+    }
+    /// Re-packs the small keys of the dictionary into as few pools as possible, starting from index 0. This is
+    /// the small pool "defrag": it recovers pools that are left sparse by deleted keys, and it brings the
+    /// pathological high-index orphans (see the notes on `small_pool`) back down to the bottom of the pool.
+    ///
+    /// Only the RAM copy is touched. The re-packed pools are marked dirty, so `sync_small_pool` + `dict_sync`
+    /// will write them out; pools that are vacated are left in place, empty and clean, so that their old
+    /// contents stay readable until the new layout is on disk. Call `small_pool_release_tail` after the sync
+    /// to recover the vacated pages.
+    ///
+    /// Returns the number of keys that changed pools; 0 if re-packing would not free up any pools, or if the
+    /// data of a key could not be read back (in which case nothing is changed).
+    pub(crate) fn small_pool_repack(&mut self, hw: &mut PddbOs, v2p_map: &HashMap::<VirtAddr, PhysPage>, cipher: &AesGcmSiv::<Aes256>) -> usize {
+        if self.small_pool_packing().len() >= self.small_pool_extent() {
+            return 0;
+        }
+        // sync_small_pool rewrites a pool from the cached data of its keys, so every key has to be in cache.
+        // Pools that are clean skip the data refill, so mark them temporarily dirty to force the refill.
+        let mut data_cache = PlaintextCache { data: None, tag: None };
+        for pool_index in 0..self.small_pool.len() {
+            let missing: Vec::<String> = self.small_pool[pool_index].contents.iter()
+                .filter(|k| self.keys.get(*k).map(|kcache| kcache.data.is_none()).unwrap_or(false))
+                .cloned().collect();
+            if missing.len() > 0 {
+                let was_clean = self.small_pool[pool_index].clean;
+                self.small_pool[pool_index].clean = false;
+                for key_name in missing.iter() {
+                    self.try_fill_small_key(hw, v2p_map, cipher, &mut data_cache, key_name);
+                }
+                self.small_pool[pool_index].clean = was_clean;
+            }
+        }
+        for ksp in self.small_pool.iter() {
+            for key_name in ksp.contents.iter() {
+                if self.keys.get(key_name).map(|kcache| kcache.data.is_none()).unwrap_or(true) {
+                    log::warn!("small pool repack: key {} could not be read back, skipping", key_name);
+                    return 0;
+                }
+            }
+        }
+
+        let packed = self.small_pool_packing();
+        let mut moved = 0;
+        for (index, ksp) in packed.iter().enumerate() {
+            let base = small_storage_base_vaddr_from_indices(self.index, index);
+            for key_name in ksp.contents.iter() {
+                let kcache = self.keys.get_mut(key_name).expect("data record without index");
+                if small_storage_index_from_key(kcache, self.index) != Some(index) {
+                    moved += 1;
+                }
+                // the final offset within the pool is assigned by sync_small_pool
+                kcache.start = base;
+                kcache.clean = false;
+            }
+        }
+        let old_len = self.small_pool.len();
+        self.small_pool = packed;
+        for ksp in self.small_pool.iter_mut() {
+            ksp.clean = false;
+        }
+        while self.small_pool.len() < old_len {
+            let mut ksp = KeySmallPool::new();
+            ksp.clean = true;
+            self.small_pool.push(ksp);
+        }
+        self.rebuild_free_pool();
+        self.age = self.age.saturating_add(1);
+        self.clean = false;
+        moved
+    }
+    /// Drops the empty pools at the end of the small pool, and returns every page mapped into the pool
+    /// region past the last remaining pool to the free space, overwriting it with noise first. This also
+    /// picks up pages left behind by pools that were emptied in an earlier session: once a dictionary
+    /// is read back in, those are no longer tracked by `small_pool`.
+    ///
+    /// The caller must have synced the dictionary first, so that no key descriptor on disk still points
+    /// into the released pages, and should `pt_sync` afterwards. Returns the number of pages freed.
+    pub(crate) fn small_pool_release_tail(&mut self, hw: &mut PddbOs, v2p_map: &mut HashMap::<VirtAddr, PhysPage>) -> usize {
+        while let Some(ksp) = self.small_pool.last() {
+            if ksp.contents.len() > 0 || !ksp.clean {
+                break;
+            }
+            self.small_pool.pop();
+        }
+        self.rebuild_free_pool();
+        let release_start = small_storage_base_vaddr_from_indices(self.index, self.small_pool.len());
+        let release_end = small_storage_base_vaddr_from_indices(self.index, 0) + SMALL_POOL_STRIDE;
+        let mut freed = 0;
+        for (va, pp) in v2p_map.iter_mut() {
+            if pp.valid() && va.get() >= release_start && va.get() < release_end {
+                let mut noise = [0u8; PAGE_SIZE];
+                hw.trng_slice(&mut noise);
+                hw.patch_data(&noise, pp.page_number() * PAGE_SIZE as u32);
+                hw.fast_space_free(pp);
+                freed += 1;
+            }
+        }
+        freed
+    }
+
     /// No data cache to flush yet...large pool caches not implemented!
     pub(crate) fn sync_large_pool(&self) {
     }
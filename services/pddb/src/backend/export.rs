@@ -0,0 +1,267 @@
+/// # Basis Export
+///
+/// A basis can be exported into a self-contained blob, so that its contents can be archived or moved
+/// to another device. The keys that protect a basis on disk are bound to the device that created it, so
+/// an export carries the plaintext contents of the basis, sealed under a key derived from a passphrase
+/// chosen at export time. Importing the blob creates a brand-new basis, with fresh keys derived from a
+/// new basis password on the importing device.
+///
+/// Blob format (all integers little-endian):
+///   - header, sent in the clear but authenticated as the AAD of the payload:
+///     - `BASIS_EXPORT_MAGIC` (4 bytes)
+///     - format version (u32), currently `BASIS_EXPORT_VERSION`
+///     - bcrypt cost (u32)
+///     - bcrypt salt (16 bytes)
+///     - AES-GCM-SIV nonce (12 bytes)
+///   - the payload, encrypted with AES-256-GCM-SIV, followed by its 16-byte tag
+///
+/// Payload format:
+///   - name of the exported basis: length (u8) + bytes
+///   - number of dictionaries (u32)
+///   - for each dictionary:
+///     - dictionary name: length (u8) + bytes
+///     - number of keys (u32)
+///     - for each key: key name length (u8) + bytes, data length (u32) + data bytes
+///     - length (u32) of the dictionary's key metadata table, followed by the table in the format of
+///       `key_meta_encode`; a length of 0 means the dictionary has no metadata
+///
+/// The whole blob is assembled in memory, so a basis can only be exported if it fits in RAM, and the
+/// blob may be no larger than `BASIS_EXPORT_MAX_LEN`.
+
+use crate::api::*;
+use super::bcrypt::bcrypt;
+use super::{key_meta_encode, key_meta_decode};
+
+use aes_gcm_siv::{AesGcmSiv, Nonce, Key};
+use aes_gcm_siv::aead::{Aead, NewAead, Payload};
+use aes::Aes256;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::io::{Result, Error, ErrorKind};
+use zeroize::Zeroize;
+
+const BASIS_EXPORT_MAGIC: [u8; 4] = *b"PDBX";
+/// Bump this if the payload format changes in a way that older importers can't handle
+pub(crate) const BASIS_EXPORT_VERSION: u32 = 1;
+/// The cost is recorded in the blob, so it can be raised later; this bounds what an importer will
+/// attempt, so a corrupt header can't tie up the PDDB for hours.
+const BASIS_EXPORT_MAX_COST: u32 = 12;
+const BASIS_EXPORT_SALT_LEN: usize = 16;
+const BASIS_EXPORT_NONCE_LEN: usize = 12;
+const BASIS_EXPORT_HEADER_LEN: usize = 4 + 4 + 4 + BASIS_EXPORT_SALT_LEN + BASIS_EXPORT_NONCE_LEN;
+/// Exports are staged whole in the PDDB's heap, and importing one needs a few times its size in RAM, so
+/// bigger exports are refused in both directions rather than risk exhausting the server's memory.
+pub(crate) const BASIS_EXPORT_MAX_LEN: usize = 1024 * 1024;
+
+pub(crate) struct ExportKey {
+    pub(crate) name: String,
+    pub(crate) data: Vec::<u8>,
+}
+pub(crate) struct ExportDict {
+    pub(crate) name: String,
+    pub(crate) keys: Vec::<ExportKey>,
+    pub(crate) meta: BTreeMap<String, KeyMetadata>,
+}
+/// The plaintext contents of a basis
+pub(crate) struct BasisExport {
+    pub(crate) name: String,
+    pub(crate) dicts: Vec::<ExportDict>,
+}
+impl Drop for BasisExport {
+    fn drop(&mut self) {
+        for dict in self.dicts.iter_mut() {
+            for key in dict.keys.iter_mut() {
+                key.data.zeroize();
+            }
+        }
+    }
+}
+
+fn basis_export_key(cost: u32, salt: &[u8], passphrase: &str) -> [u8; 32] {
+    let mut hashed_passphrase: [u8; 24] = [0; 24];
+    bcrypt(cost, salt, passphrase, &mut hashed_passphrase);
+    let hk = hkdf::Hkdf::<sha2::Sha256>::new(Some(salt), &hashed_passphrase);
+    let mut okm = [0u8; 32];
+    hk.expand(b"pddb basis export key", &mut okm).expect("invalid length specified for HKDF");
+    hashed_passphrase.zeroize();
+    okm
+}
+
+/// Encrypts `export` under `passphrase`. `salt` and `nonce` must be freshly generated random numbers.
+pub(crate) fn basis_export_seal(export: &BasisExport, passphrase: &str,
+    salt: [u8; BASIS_EXPORT_SALT_LEN], nonce: [u8; BASIS_EXPORT_NONCE_LEN]) -> Result<Vec::<u8>> {
+    fn push_str(payload: &mut Vec::<u8>, s: &str) {
+        payload.push(s.len() as u8);
+        payload.extend_from_slice(s.as_bytes());
+    }
+    let mut payload = Vec::<u8>::new();
+    push_str(&mut payload, &export.name);
+    payload.extend_from_slice(&(export.dicts.len() as u32).to_le_bytes());
+    for dict in export.dicts.iter() {
+        push_str(&mut payload, &dict.name);
+        payload.extend_from_slice(&(dict.keys.len() as u32).to_le_bytes());
+        for key in dict.keys.iter() {
+            push_str(&mut payload, &key.name);
+            payload.extend_from_slice(&(key.data.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&key.data);
+        }
+        if dict.meta.len() > 0 {
+            let table = key_meta_encode(&dict.meta)?;
+            payload.extend_from_slice(&(table.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&table);
+        } else {
+            payload.extend_from_slice(&0u32.to_le_bytes());
+        }
+    }
+
+    if BASIS_EXPORT_HEADER_LEN + payload.len() + 16 > BASIS_EXPORT_MAX_LEN {
+        payload.zeroize();
+        return Err(Error::new(ErrorKind::OutOfMemory, "basis is too large to export"));
+    }
+    let mut blob = Vec::<u8>::new();
+    blob.extend_from_slice(&BASIS_EXPORT_MAGIC);
+    blob.extend_from_slice(&BASIS_EXPORT_VERSION.to_le_bytes());
+    blob.extend_from_slice(&BCRYPT_COST.to_le_bytes());
+    blob.extend_from_slice(&salt);
+    blob.extend_from_slice(&nonce);
+    let mut key = basis_export_key(BCRYPT_COST, &salt, passphrase);
+    let cipher = AesGcmSiv::<Aes256>::new(Key::from_slice(&key));
+    key.zeroize();
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { aad: &blob, msg: &payload });
+    payload.zeroize();
+    blob.append(&mut ciphertext.or(Err(Error::new(ErrorKind::Other, "couldn't encrypt basis export")))?);
+    Ok(blob)
+}
+
+/// Decrypts and parses an export blob. Returns `PermissionDenied` if the passphrase is wrong or the blob
+/// has been tampered with, and `InvalidData` if the blob is not an export or is of an unsupported version.
+pub(crate) fn basis_export_open(blob: &[u8], passphrase: &str) -> Result<BasisExport> {
+    if blob.len() < BASIS_EXPORT_HEADER_LEN || blob[..4] != BASIS_EXPORT_MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "not a basis export"));
+    }
+    let version = u32::from_le_bytes(blob[4..8].try_into().unwrap());
+    if version != BASIS_EXPORT_VERSION {
+        return Err(Error::new(ErrorKind::InvalidData, "unsupported basis export version"));
+    }
+    let cost = u32::from_le_bytes(blob[8..12].try_into().unwrap());
+    if cost > BASIS_EXPORT_MAX_COST {
+        return Err(Error::new(ErrorKind::InvalidData, "basis export has an unsupported bcrypt cost"));
+    }
+    let salt = &blob[12..12 + BASIS_EXPORT_SALT_LEN];
+    let nonce = &blob[12 + BASIS_EXPORT_SALT_LEN..BASIS_EXPORT_HEADER_LEN];
+    let mut key = basis_export_key(cost, salt, passphrase);
+    let cipher = AesGcmSiv::<Aes256>::new(Key::from_slice(&key));
+    key.zeroize();
+    let mut payload = cipher.decrypt(Nonce::from_slice(nonce),
+        Payload { aad: &blob[..BASIS_EXPORT_HEADER_LEN], msg: &blob[BASIS_EXPORT_HEADER_LEN..] })
+        .or(Err(Error::new(ErrorKind::PermissionDenied, "wrong passphrase, or the basis export is corrupt")))?;
+    let export = basis_export_parse(&payload);
+    payload.zeroize();
+    export
+}
+
+fn basis_export_parse(payload: &[u8]) -> Result<BasisExport> {
+    fn take<'a>(payload: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+        if *pos + len > payload.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "basis export is truncated"));
+        }
+        let slice = &payload[*pos..*pos + len];
+        *pos += len;
+        Ok(slice)
+    }
+    fn take_u32(payload: &[u8], pos: &mut usize) -> Result<usize> {
+        Ok(u32::from_le_bytes(take(payload, pos, 4)?.try_into().unwrap()) as usize)
+    }
+    fn take_str(payload: &[u8], pos: &mut usize, max_len: usize) -> Result<String> {
+        let len = take(payload, pos, 1)?[0] as usize;
+        if len > max_len - 1 {
+            return Err(Error::new(ErrorKind::InvalidData, "name in basis export is too long"));
+        }
+        std::str::from_utf8(take(payload, pos, len)?)
+            .map(|s| s.to_string())
+            .or(Err(Error::new(ErrorKind::InvalidData, "name in basis export is not valid utf-8")))
+    }
+    let mut pos = 0;
+    let mut export = BasisExport {
+        name: take_str(payload, &mut pos, BASIS_NAME_LEN)?,
+        dicts: Vec::new(),
+    };
+    let dict_count = take_u32(payload, &mut pos)?;
+    for _ in 0..dict_count {
+        let mut dict = ExportDict {
+            name: take_str(payload, &mut pos, DICT_NAME_LEN)?,
+            keys: Vec::new(),
+            meta: BTreeMap::new(),
+        };
+        let key_count = take_u32(payload, &mut pos)?;
+        for _ in 0..key_count {
+            let name = take_str(payload, &mut pos, KEY_NAME_LEN)?;
+            let len = take_u32(payload, &mut pos)?;
+            dict.keys.push(ExportKey { name, data: take(payload, &mut pos, len)?.to_vec() });
+        }
+        let meta_len = take_u32(payload, &mut pos)?;
+        if meta_len > 0 {
+            dict.meta = key_meta_decode(take(payload, &mut pos, meta_len)?)?;
+        }
+        export.dicts.push(dict);
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_export() -> BasisExport {
+        let mut meta = BTreeMap::<String, KeyMetadata>::new();
+        meta.insert("totp".to_string(), KeyMetadata {
+            created: 1_650_000_000_000,
+            modified: 1_650_000_123_456,
+            content_type: Some("vault/totp".to_string()),
+            attributes: BTreeMap::new(),
+        });
+        BasisExport {
+            name: "secrets".to_string(),
+            dicts: vec![
+                ExportDict {
+                    name: "vault".to_string(),
+                    keys: vec![
+                        ExportKey { name: "totp".to_string(), data: vec![0xA5; 300] },
+                        ExportKey { name: "empty".to_string(), data: Vec::new() },
+                    ],
+                    meta,
+                },
+                ExportDict { name: "nothing here".to_string(), keys: Vec::new(), meta: BTreeMap::new() },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_basis_export_roundtrip() {
+        let export = test_export();
+        let blob = basis_export_seal(&export, "correct horse", [7; 16], [9; 12]).unwrap();
+        let opened = basis_export_open(&blob, "correct horse").unwrap();
+        assert_eq!(opened.name, export.name);
+        assert_eq!(opened.dicts.len(), export.dicts.len());
+        for (a, b) in opened.dicts.iter().zip(export.dicts.iter()) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.meta, b.meta);
+            assert!(a.keys.iter().zip(b.keys.iter()).all(|(x, y)| x.name == y.name && x.data == y.data));
+            assert_eq!(a.keys.len(), b.keys.len());
+        }
+    }
+
+    #[test]
+    fn test_basis_export_rejects() {
+        let blob = basis_export_seal(&test_export(), "correct horse", [7; 16], [9; 12]).unwrap();
+        assert_eq!(basis_export_open(&blob, "battery staple").err().unwrap().kind(), ErrorKind::PermissionDenied);
+        // the header is authenticated along with the payload
+        let mut tampered = blob.clone();
+        tampered[12] ^= 1;
+        assert_eq!(basis_export_open(&tampered, "correct horse").err().unwrap().kind(), ErrorKind::PermissionDenied);
+        let mut future = blob.clone();
+        future[4] = 2;
+        assert_eq!(basis_export_open(&future, "correct horse").err().unwrap().kind(), ErrorKind::InvalidData);
+        assert_eq!(basis_export_open(&blob[..20], "correct horse").err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -6,10 +6,6 @@ use crate::*;
 use core::mem::size_of;
 use aes_gcm_siv::{Nonce, Tag};
 
-/// Each free_pool entry takes about 4 bytes, so give-or-take we have about 1000 free_pool
-/// entries per page of storage for the free_pool, or 4k * 1000 ~ 4MiB per page, when PhysAddr is a u32
-pub(crate) const FASTSPACE_PAGES: usize = 2;
-
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 #[repr(u8)]
 pub enum SpaceState {
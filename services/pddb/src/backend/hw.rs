@@ -30,19 +30,6 @@ use zeroize::Zeroize;
 #[cfg(feature="migration1")]
 use crate::backend::migration1to2::*;
 
-/// Implementation-specific PDDB structures: for Precursor/Xous OS pair
-pub(crate) const MBBB_PAGES: usize = 10;
-pub(crate) const FSCB_PAGES: usize = 16;
-
-/// size of a physical page
-pub const PAGE_SIZE: usize = spinor::SPINOR_ERASE_SIZE as usize;
-/// size of a virtual page -- after the AES encryption and journaling overhead is subtracted
-pub const VPAGE_SIZE: usize = PAGE_SIZE - size_of::<Nonce>() - size_of::<Tag>() - size_of::<JournalType>();
-
-/// length of the ciphertext in an AES-GCM-SIV page with key commitments
-/// equal to the total plaintext to be encrypted, including the journal number
-/// does not include the MAC overhead
-pub const KCOM_CT_LEN: usize = 4004;
 
 pub(crate) const WRAPPED_AES_KEYSIZE: usize = AES_KEYSIZE + 8;
 const SCD_VERSION: u32 = 2;
@@ -152,6 +139,8 @@ pub(crate) struct PddbOs {
     entropy: Rc<RefCell<TrngPool>>,
     /// connection to the password request manager
     pw_cid: xous::CID,
+    /// wall-clock reference pushed to us by the time server, as (UTC ms, ticktimer ms) at the time of the push
+    time_ref: Option<(u64, u64)>,
     #[cfg(all(feature="pddbtest", feature="autobasis"))]
     testnames: HashSet::<String>,
 }
@@ -204,6 +193,7 @@ impl PddbOs {
             dna_mode: DnaMode::Normal,
             entropy: trngpool,
             pw_cid,
+            time_ref: None,
             #[cfg(all(feature="pddbtest", feature="autobasis"))]
             testnames: HashSet::new(),
         };
@@ -232,6 +222,7 @@ impl PddbOs {
                 dna_mode: DnaMode::Normal,
                 entropy: trngpool,
                 pw_cid,
+                time_ref: None,
                 #[cfg(all(feature="pddbtest", feature="autobasis"))]
                 testnames: HashSet::new(),
             }
@@ -300,6 +291,19 @@ impl PddbOs {
         self.entropy.borrow_mut().get_u8()
     }
     pub(crate) fn timestamp_now(&self) -> u64 {self.tt.elapsed_ms()}
+    /// Records the current UTC time. We can't ask the time server for it, because the time server
+    /// stores its own state in the PDDB and may be blocked on us when we need the time.
+    pub(crate) fn set_time_reference(&mut self, utc_ms: u64) {
+        self.time_ref = Some((utc_ms, self.timestamp_now()));
+    }
+    /// Current UTC time in ms since the epoch, or 0 if the time server hasn't given us a reference yet.
+    pub(crate) fn utc_now_ms(&self) -> u64 {
+        if let Some((utc_ms, tt_ms)) = self.time_ref {
+            utc_ms + (self.timestamp_now() - tt_ms)
+        } else {
+            0
+        }
+    }
     /// checks if the root keys are initialized, which is a prerequisite to formatting and mounting
     pub(crate) fn rootkeys_initialized(&self) -> bool {
         self.rootkeys.is_initialized().expect("couldn't query initialization state of the rootkeys server")
@@ -1371,8 +1375,6 @@ impl PddbOs {
     /// of a basis root record with key commitment. The committed key and the nonce both should be indistinguishable
     /// from ciphertext.
     pub(crate) fn data_decrypt_page_with_commit(&self, key: &[u8], aad: &[u8], page: &PhysPage) -> Option<Vec::<u8>> {
-        const KCOM_NONCE_LEN: usize = 32;
-        const KCOM_LEN: usize = 32;
         const MAC_LEN: usize = 16;
         let ct_slice = &self.pddb_mr.as_slice()[
             self.data_phys_base.as_usize() + page.page_number() as usize * PAGE_SIZE ..
@@ -1498,15 +1500,13 @@ impl PddbOs {
 
         let mut h_enc = Sha512Trunc256::new_with_strategy(FallbackStrategy::SoftwareOnly);
         h_enc.update(key);
-        // per https://eprint.iacr.org/2020/1456.pdf Table 4 on page 13 Type I Lenc
-        h_enc.update([0x43, 0x6f, 0x6, 0xd6, 0xd, 0x69, 0x74, 0x01, 0x01]);
+        h_enc.update(KCOM_LABEL_ENC);
         h_enc.update(nonce_com);
         let k_enc = h_enc.finalize();
 
         let mut h_com = Sha512Trunc256::new_with_strategy(FallbackStrategy::SoftwareOnly);
         h_com.update(key);
-        // per https://eprint.iacr.org/2020/1456.pdf Table 4 on page 13 Type I Lcom. Note one-bit difference in last byte.
-        h_com.update([0x43, 0x6f, 0x6, 0xd6, 0xd, 0x69, 0x74, 0x01, 0x02]);
+        h_com.update(KCOM_LABEL_COM);
         h_com.update(nonce_com);
         let k_com = h_com.finalize();
         (k_enc.into(), k_com.into())
@@ -1814,8 +1814,8 @@ impl PddbOs {
             self.tt.sleep_ms(100).unwrap();
         }
         let basis_root = BasisRoot {
-            magic: api::PDDB_MAGIC,
-            version: api::PDDB_VERSION,
+            magic: PDDB_MAGIC,
+            version: PDDB_VERSION,
             name: BasisRootName::try_from_str(PDDB_DEFAULT_SYSTEM_BASIS).unwrap(),
             age: 0,
             num_dictionaries: 0,
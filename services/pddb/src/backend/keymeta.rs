@@ -0,0 +1,215 @@
+/// # Key Metadata
+///
+/// On-disk key descriptors only have room for a name, length and flags, so the optional per-key metadata
+/// (`KeyMetadata`: created/modified times, a content type and a few user attributes) is kept in a
+/// table that is stored as an ordinary key (`KEY_META_KEY`) inside each dictionary that uses it.
+/// Because the table is just another key, it is carried along by the normal sync, rekey and migration
+/// machinery without any changes to the on-disk format, and keys written by older versions of the PDDB
+/// simply have no metadata. The table key itself is hidden from key listings and client access.
+///
+/// Table format (all integers little-endian):
+///   - `KEY_META_MAGIC` (4 bytes)
+///   - number of entries (u32)
+///   - for each entry:
+///     - entry length (u16), counting the bytes that follow; decoders skip any bytes they don't understand,
+///       so fields can be appended in later revisions
+///     - key name length (u8) + key name bytes
+///     - created time (u64), modified time (u64)
+///     - content type length (u8) + content type bytes; a length of 0 means no content type
+///     - number of attributes (u8)
+///     - for each attribute: name length (u8) + name bytes, value length (u8) + value bytes
+///
+/// So that storing a key doesn't rewrite the whole table, new entries are appended to the stored table (and the
+/// entry count patched), and new modification times are patched into their entries in place. The table is only
+/// rewritten as a whole when entries are removed or change size.
+
+use crate::api::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::io::{Result, Error, ErrorKind};
+
+/// Name of the key that holds the metadata table of a dictionary. Client requests that target this
+/// key are rejected, so it can't collide with user data.
+pub(crate) const KEY_META_KEY: &str = "__pddb.meta";
+const KEY_META_MAGIC: [u8; 4] = *b"KMd1";
+/// Offset of the entry count in a stored table
+pub(crate) const KEY_META_COUNT_OFFSET: usize = 4;
+
+/// Keys that don't get metadata of their own: the metadata table itself, transaction records and quota tables.
+pub(crate) fn key_meta_exempt(dict: &str, key: &str) -> bool {
+    key == KEY_META_KEY || dict == super::TX_INTENT_DICT || dict == super::QUOTA_DICT
+}
+
+/// Serializes a metadata table, indexed by key name
+pub(crate) fn key_meta_encode(table: &BTreeMap<String, KeyMetadata>) -> Result<Vec::<u8>> {
+    let mut record = Vec::<u8>::new();
+    record.extend_from_slice(&KEY_META_MAGIC);
+    record.extend_from_slice(&(table.len() as u32).to_le_bytes());
+    for (name, meta) in table.iter() {
+        record.extend_from_slice(&key_meta_encode_entry(name, meta)?);
+    }
+    Ok(record)
+}
+
+/// Serializes one entry of a metadata table, including its length field
+pub(crate) fn key_meta_encode_entry(name: &str, meta: &KeyMetadata) -> Result<Vec::<u8>> {
+    fn push_str(entry: &mut Vec::<u8>, s: &str) {
+        entry.push(s.len() as u8);
+        entry.extend_from_slice(s.as_bytes());
+    }
+    if name.len() > KEY_NAME_LEN - 1 {
+        return Err(Error::new(ErrorKind::InvalidInput, "key name is too long"));
+    }
+    meta.validate()?;
+    let mut entry = Vec::<u8>::new();
+    push_str(&mut entry, name);
+    entry.extend_from_slice(&meta.created.to_le_bytes());
+    entry.extend_from_slice(&meta.modified.to_le_bytes());
+    push_str(&mut entry, meta.content_type.as_deref().unwrap_or(""));
+    entry.push(meta.attributes.len() as u8);
+    for (attr, value) in meta.attributes.iter() {
+        push_str(&mut entry, attr);
+        push_str(&mut entry, value);
+    }
+    let mut record = Vec::<u8>::new();
+    record.extend_from_slice(&(entry.len() as u16).to_le_bytes());
+    record.extend_from_slice(&entry);
+    Ok(record)
+}
+
+/// Offset of the modification time within the stored entry for `name`, counting from the start of its length field
+pub(crate) fn key_meta_modified_offset(name: &str) -> usize {
+    2 + 1 + name.len() + 8
+}
+
+/// Finds where each entry of a stored metadata table starts, indexed by key name. The table must be well-formed,
+/// i.e. it has been accepted by `key_meta_decode`.
+pub(crate) fn key_meta_index(record: &[u8]) -> HashMap<String, usize> {
+    let mut index = HashMap::<String, usize>::new();
+    let count = u32::from_le_bytes(record[KEY_META_COUNT_OFFSET..KEY_META_COUNT_OFFSET + 4].try_into().unwrap());
+    let mut pos = KEY_META_COUNT_OFFSET + 4;
+    for _ in 0..count {
+        let entry_len = u16::from_le_bytes(record[pos..pos + 2].try_into().unwrap()) as usize;
+        let name_len = record[pos + 2] as usize;
+        let name = String::from_utf8_lossy(&record[pos + 3..pos + 3 + name_len]).to_string();
+        index.insert(name, pos);
+        pos += 2 + entry_len;
+    }
+    index
+}
+
+/// Deserializes a metadata table. Returns an `InvalidData` error if the table is truncated or malformed.
+pub(crate) fn key_meta_decode(record: &[u8]) -> Result<BTreeMap<String, KeyMetadata>> {
+    fn take<'a>(record: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+        if *pos + len > record.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "key metadata table is truncated"));
+        }
+        let slice = &record[*pos..*pos + len];
+        *pos += len;
+        Ok(slice)
+    }
+    fn take_str(record: &[u8], pos: &mut usize) -> Result<String> {
+        let len = take(record, pos, 1)?[0] as usize;
+        std::str::from_utf8(take(record, pos, len)?)
+            .map(|s| s.to_string())
+            .or(Err(Error::new(ErrorKind::InvalidData, "key metadata string is not valid utf-8")))
+    }
+    let mut pos = 0;
+    if take(record, &mut pos, 4)? != &KEY_META_MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "key metadata table has the wrong magic number"));
+    }
+    let count = u32::from_le_bytes(take(record, &mut pos, 4)?.try_into().unwrap());
+    let mut table = BTreeMap::<String, KeyMetadata>::new();
+    for _ in 0..count {
+        let entry_len = u16::from_le_bytes(take(record, &mut pos, 2)?.try_into().unwrap()) as usize;
+        let entry = take(record, &mut pos, entry_len)?;
+        let mut epos = 0;
+        let name = take_str(entry, &mut epos)?;
+        let created = u64::from_le_bytes(take(entry, &mut epos, 8)?.try_into().unwrap());
+        let modified = u64::from_le_bytes(take(entry, &mut epos, 8)?.try_into().unwrap());
+        let content_type = take_str(entry, &mut epos)?;
+        let attr_count = take(entry, &mut epos, 1)?[0];
+        let mut attributes = BTreeMap::<String, String>::new();
+        for _ in 0..attr_count {
+            let attr = take_str(entry, &mut epos)?;
+            let value = take_str(entry, &mut epos)?;
+            attributes.insert(attr, value);
+        }
+        table.insert(name, KeyMetadata {
+            created,
+            modified,
+            content_type: if content_type.len() > 0 { Some(content_type) } else { None },
+            attributes,
+        });
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_meta_roundtrip() {
+        let mut table = BTreeMap::<String, KeyMetadata>::new();
+        table.insert("plain".to_string(), KeyMetadata::default());
+        let mut attributes = BTreeMap::<String, String>::new();
+        attributes.insert("issuer".to_string(), "example.com".to_string());
+        attributes.insert("algorithm".to_string(), "SHA1".to_string());
+        table.insert("totp-entry".to_string(), KeyMetadata {
+            created: 1_650_000_000_000,
+            modified: 1_650_000_123_456,
+            content_type: Some("vault/totp".to_string()),
+            attributes,
+        });
+        let record = key_meta_encode(&table).unwrap();
+        assert_eq!(key_meta_decode(&record).unwrap(), table);
+        // any truncation should be detected, rather than producing a partial table
+        for len in 0..record.len() {
+            assert!(key_meta_decode(&record[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_key_meta_in_place() {
+        let mut table = BTreeMap::<String, KeyMetadata>::new();
+        table.insert("a".to_string(), KeyMetadata { created: 1, modified: 2, ..Default::default() });
+        table.insert("bb".to_string(), KeyMetadata {
+            created: 3,
+            modified: 4,
+            content_type: Some("text/plain".to_string()),
+            ..Default::default()
+        });
+        let mut record = key_meta_encode(&table).unwrap();
+
+        // appending an entry and patching the count is the same as encoding the bigger table
+        let new = KeyMetadata { created: 5, modified: 5, ..Default::default() };
+        record.extend_from_slice(&key_meta_encode_entry("c", &new).unwrap());
+        record[KEY_META_COUNT_OFFSET..KEY_META_COUNT_OFFSET + 4].copy_from_slice(&3u32.to_le_bytes());
+        table.insert("c".to_string(), new);
+        assert_eq!(key_meta_decode(&record).unwrap(), table);
+
+        // and so is patching a modification time in place
+        let index = key_meta_index(&record);
+        assert_eq!(index.len(), 3);
+        for (name, meta) in table.iter_mut() {
+            meta.modified = 100 + name.len() as u64;
+            let offset = index[name] + key_meta_modified_offset(name);
+            record[offset..offset + 8].copy_from_slice(&meta.modified.to_le_bytes());
+        }
+        assert_eq!(key_meta_decode(&record).unwrap(), table);
+    }
+
+    #[test]
+    fn test_key_meta_limits() {
+        let mut meta = KeyMetadata::default();
+        meta.content_type = Some("x".repeat(KEY_CONTENT_TYPE_LEN));
+        assert!(meta.validate().is_err());
+        meta.content_type = None;
+        for i in 0..=KEY_ATTR_MAX {
+            meta.attributes.insert(format!("a{}", i), String::new());
+        }
+        assert!(meta.validate().is_err());
+    }
+}
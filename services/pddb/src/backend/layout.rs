@@ -0,0 +1,106 @@
+// Not every user of the layout needs every constant
+#![allow(dead_code)]
+
+//! On-disk layout of the PDDB.
+//!
+//! This module has no dependencies, so host tools that read PDDB images (`tools/src/bin/pddb-inspect.rs`)
+//! include it with `#[path]` instead of keeping their own copy of the numbers. The structures in the rest of
+//! the backend are the authority on the record formats; the tests in `types.rs` and `pagetable.rs` check
+//! that the offsets here agree with them.
+
+/// size of a physical page; must equal the erase size of the FLASH
+pub const PAGE_SIZE: usize = 0x1000;
+/// AES-GCM-SIV nonce at the start of every encrypted page
+pub(crate) const NONCE_LEN: usize = 12;
+/// AES-GCM-SIV tag at the end of every encrypted page
+pub(crate) const TAG_LEN: usize = 16;
+/// journal revision at the start of every decrypted page
+pub(crate) const JOURNAL_LEN: usize = 4;
+/// size of a virtual page -- after the AES encryption and journaling overhead is subtracted
+pub const VPAGE_SIZE: usize = PAGE_SIZE - NONCE_LEN - TAG_LEN - JOURNAL_LEN;
+
+/// Implementation-specific PDDB structures: for Precursor/Xous OS pair
+pub(crate) const MBBB_PAGES: usize = 10;
+pub(crate) const FSCB_PAGES: usize = 16;
+/// Each free_pool entry takes about 4 bytes, so give-or-take we have about 1000 free_pool
+/// entries per page of storage for the free_pool, or 4k * 1000 ~ 4MiB per page, when PhysAddr is a u32
+pub(crate) const FASTSPACE_PAGES: usize = 2;
+
+pub(crate) const PDDB_MAGIC: [u8; 4] = [0x50, 0x44, 0x44, 0x42];
+/// current on-disk version; see `PDDB_MIGRATE_1` in `api.rs` for the versions that can be migrated from
+pub(crate) const PDDB_VERSION: u32 = 0x00_00_02_01;
+// this isn't an "official" basis, but it is used for the AAD for encrypting the FastSpace structure
+pub(crate) const PDDB_FAST_SPACE_SYSTEM_BASIS: &str = ".FastSpace";
+
+/// length of the ciphertext in an AES-GCM-SIV page with key commitments
+/// equal to the total plaintext to be encrypted, including the journal number
+/// does not include the MAC overhead
+pub const KCOM_CT_LEN: usize = 4004;
+/// the key commitment nonce, stored after the ciphertext
+pub(crate) const KCOM_NONCE_LEN: usize = 32;
+/// the key commitment, stored after its nonce
+pub(crate) const KCOM_LEN: usize = 32;
+/// per https://eprint.iacr.org/2020/1456.pdf Table 4 on page 13 Type I Lenc
+pub(crate) const KCOM_LABEL_ENC: [u8; 9] = [0x43, 0x6f, 0x6, 0xd6, 0xd, 0x69, 0x74, 0x01, 0x01];
+/// per https://eprint.iacr.org/2020/1456.pdf Table 4 on page 13 Type I Lcom. Note one-bit difference in last byte.
+pub(crate) const KCOM_LABEL_COM: [u8; 9] = [0x43, 0x6f, 0x6, 0xd6, 0xd, 0x69, 0x74, 0x01, 0x02];
+
+pub(crate) const SMALL_POOL_START: u64 = 0x0000_003F_8000_0000;
+pub(crate) const SMALL_POOL_END: u64 = 0x0000_007E_FF02_0000;
+pub(crate) const SMALL_POOL_STRIDE: u64 = 0xFE_0000;
+pub(crate) const LARGE_POOL_START: u64 = 0x0000_FE00_0000_0000;
+
+/// The chosen "stride" of a dict/key entry. Drives a lot of key parameters in the database's characteristics.
+/// This is chosen such that 32 of these entries fit evenly into a VPAGE.
+pub(crate) const DK_STRIDE: usize = 127;
+/// DK_STRIDES per VPAGE
+pub(crate) const DK_PER_VPAGE: usize = VPAGE_SIZE / DK_STRIDE; // should be 32 - use this for computing modulus on dictionary indices
+/// size of a dictionary region in virtual memory
+pub(crate) const DICT_VSIZE: u64 = 0xFE_0000;
+/// maximum number of dictionaries in a system
+pub(crate) const DICT_MAXCOUNT: usize = 16383;
+
+/// Bit positions within a `PhysPage`. The page number takes the low bits; "12" should be log2(PAGE_SIZE)
+/// but https://github.com/rust-lang/rust/issues/70887
+#[cfg(not(feature = "u64_pa"))]
+pub(crate) const PP_PAGE_WIDTH: usize = 32 - 12;
+#[cfg(feature = "u64_pa")]
+pub(crate) const PP_PAGE_WIDTH: usize = 64 - 12;
+pub(crate) const PP_CLEAN_BIT: usize = PP_PAGE_WIDTH;
+pub(crate) const PP_VALID_BIT: usize = PP_PAGE_WIDTH + 1;
+pub(crate) const PP_STATE_LSB: usize = PP_PAGE_WIDTH + 2;
+pub(crate) const PP_STATE_MSB: usize = PP_PAGE_WIDTH + 3;
+pub(crate) const PP_JOURNAL_LSB: usize = PP_PAGE_WIDTH + 4;
+pub(crate) const PP_JOURNAL_MSB: usize = PP_PAGE_WIDTH + 7;
+
+/// A page table entry: the virtual page number, flags, a nonce and a checksum over the preceding bytes
+pub(crate) const PTE_LEN: usize = 16;
+pub(crate) const PTE_ADDR_LEN: usize = 7;
+pub(crate) const PTE_NONCE_OFFSET: usize = 8;
+pub(crate) const PTE_CHECKSUM_OFFSET: usize = 12;
+
+/// Offsets within a `BasisRoot`, which follows the journal in the basis root page
+pub(crate) const BASIS_ROOT_MAGIC_OFFSET: usize = 0;
+pub(crate) const BASIS_ROOT_VERSION_OFFSET: usize = 4;
+pub(crate) const BASIS_ROOT_AGE_OFFSET: usize = 8;
+pub(crate) const BASIS_ROOT_NUM_DICTS_OFFSET: usize = 12;
+pub(crate) const BASIS_ROOT_NAME_OFFSET: usize = 16;
+pub(crate) const BASIS_ROOT_LEN: usize = 80;
+
+/// Offsets within a `Dictionary` header, which occupies the first DK_STRIDE slot of a dictionary
+pub(crate) const DICT_FLAGS_OFFSET: usize = 0;
+pub(crate) const DICT_AGE_OFFSET: usize = 4;
+pub(crate) const DICT_NUM_KEYS_OFFSET: usize = 8;
+pub(crate) const DICT_FREE_KEY_INDEX_OFFSET: usize = 12;
+pub(crate) const DICT_NAME_OFFSET: usize = 16;
+
+/// Offsets within a `KeyDescriptor`, which occupies one DK_STRIDE slot
+pub(crate) const KEY_START_OFFSET: usize = 0;
+pub(crate) const KEY_LEN_OFFSET: usize = 8;
+pub(crate) const KEY_RESERVED_OFFSET: usize = 16;
+pub(crate) const KEY_FLAGS_OFFSET: usize = 24;
+pub(crate) const KEY_AGE_OFFSET: usize = 28;
+pub(crate) const KEY_NAME_OFFSET: usize = 32;
+
+/// The `valid` bit shared by `DictFlags` and `KeyFlags`
+pub(crate) const DK_VALID_BIT: usize = 0;
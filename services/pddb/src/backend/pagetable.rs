@@ -167,4 +167,19 @@ pub(crate) struct EncryptedPage {
     data: [u8; (PAGE_SIZE - size_of::<Nonce>() - size_of::<Tag>() - size_of::<u32>())],
     /// tag is the authentication tag. If the page decrypts & authenticates, we know it's a valid data block for us.
     p_tag: [u8; size_of::<Tag>()],
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{PTE_ADDR_LEN, PTE_CHECKSUM_OFFSET, PTE_LEN, PTE_NONCE_OFFSET};
+    /// host tools decode page table entries using the offsets in `layout.rs`
+    #[test]
+    fn test_pte_layout() {
+        let pte = Pte { pddb_addr: [1; 7], flags: PtFlags::CLEAN, nonce: [2; 4], checksum: [3; 4] };
+        assert_eq!(pte.len(), PTE_LEN);
+        assert_eq!(&pte[..PTE_ADDR_LEN], &[1; 7]);
+        assert_eq!(pte[PTE_ADDR_LEN], PtFlags::CLEAN.bits());
+        assert_eq!(&pte[PTE_NONCE_OFFSET..PTE_CHECKSUM_OFFSET], &[2; 4]);
+        assert_eq!(&pte[PTE_CHECKSUM_OFFSET..], &[3; 4]);
+    }
+}
@@ -0,0 +1,139 @@
+/// # Quotas
+///
+/// Each basis can carry an optional limit on the total storage of its keys, and optional limits on
+/// individual dictionaries. The limits are kept in a table that is stored as an ordinary key
+/// (`QUOTA_KEY`) in a reserved dictionary (`QUOTA_DICT`) of the basis, so they are protected by the
+/// basis' own encryption and travel with it through sync, rekey and migration. A dictionary limit
+/// may be set before the dictionary exists. The reserved dictionary is hidden from dictionary listings
+/// and client access, and is not carried along by a basis export.
+///
+/// Usage is measured as the sum of the space reserved by the keys, since that's what a key actually
+/// consumes on disk. Internal bookkeeping (key metadata tables, transaction records and the quota
+/// table itself) is not counted.
+///
+/// Table format (all integers little-endian):
+///   - `QUOTA_MAGIC` (4 bytes)
+///   - basis limit (u64); `u64::MAX` means no limit
+///   - number of dictionary limits (u32)
+///   - for each dictionary limit: name length (u8) + name bytes, limit (u64)
+
+use crate::api::*;
+use super::{PageAlignedVa, SMALL_CAPACITY, DEFAULT_ALLOC_HINT};
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::io::{Result, Error, ErrorKind};
+
+/// Name of the dictionary that holds the quota table of a basis. Client requests that target this
+/// dictionary are rejected, so it can't collide with user data.
+pub(crate) const QUOTA_DICT: &str = "__pddb.quota";
+pub(crate) const QUOTA_KEY: &str = "limits";
+const QUOTA_MAGIC: [u8; 4] = *b"Qta1";
+
+/// The quota limits of a single basis, in bytes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct QuotaTable {
+    pub(crate) basis: Option<u64>,
+    pub(crate) dicts: BTreeMap<String, u64>,
+}
+impl QuotaTable {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.basis.is_none() && self.dicts.len() == 0
+    }
+}
+
+/// Estimates the space that a key of `len` bytes reserves when it is created, following the
+/// allocation rules of `DictCacheEntry::key_update()`: keys that fit in the small pool reserve the
+/// larger of their length and the allocation hint, and large keys reserve whole pages.
+pub(crate) fn quota_reservation(len: usize, alloc_hint: Option<usize>) -> u64 {
+    let hint = match alloc_hint.unwrap_or(DEFAULT_ALLOC_HINT) {
+        0 => DEFAULT_ALLOC_HINT,
+        hint => hint,
+    };
+    let reserved = len.max(hint) as u64;
+    if len < SMALL_CAPACITY && hint < SMALL_CAPACITY {
+        reserved
+    } else {
+        PageAlignedVa::from(reserved).as_u64()
+    }
+}
+
+/// Serializes a quota table
+pub(crate) fn quota_encode(table: &QuotaTable) -> Result<Vec::<u8>> {
+    let mut record = Vec::<u8>::new();
+    record.extend_from_slice(&QUOTA_MAGIC);
+    record.extend_from_slice(&table.basis.unwrap_or(u64::MAX).to_le_bytes());
+    record.extend_from_slice(&(table.dicts.len() as u32).to_le_bytes());
+    for (name, &limit) in table.dicts.iter() {
+        if name.len() > DICT_NAME_LEN - 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name is too long"));
+        }
+        record.push(name.len() as u8);
+        record.extend_from_slice(name.as_bytes());
+        record.extend_from_slice(&limit.to_le_bytes());
+    }
+    Ok(record)
+}
+
+/// Deserializes a quota table. Returns an `InvalidData` error if the table is truncated or malformed.
+pub(crate) fn quota_decode(record: &[u8]) -> Result<QuotaTable> {
+    fn take<'a>(record: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+        if *pos + len > record.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "quota table is truncated"));
+        }
+        let slice = &record[*pos..*pos + len];
+        *pos += len;
+        Ok(slice)
+    }
+    let mut pos = 0;
+    if take(record, &mut pos, 4)? != &QUOTA_MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "quota table has the wrong magic number"));
+    }
+    let basis = u64::from_le_bytes(take(record, &mut pos, 8)?.try_into().unwrap());
+    let count = u32::from_le_bytes(take(record, &mut pos, 4)?.try_into().unwrap());
+    let mut table = QuotaTable {
+        basis: if basis == u64::MAX { None } else { Some(basis) },
+        dicts: BTreeMap::new(),
+    };
+    for _ in 0..count {
+        let len = take(record, &mut pos, 1)?[0] as usize;
+        let name = std::str::from_utf8(take(record, &mut pos, len)?)
+            .or(Err(Error::new(ErrorKind::InvalidData, "quota dictionary name is not valid utf-8")))?;
+        let limit = u64::from_le_bytes(take(record, &mut pos, 8)?.try_into().unwrap());
+        table.dicts.insert(name.to_string(), limit);
+    }
+    if pos != record.len() {
+        return Err(Error::new(ErrorKind::InvalidData, "quota table has trailing data"));
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::VPAGE_SIZE;
+
+    #[test]
+    fn test_quota_roundtrip() {
+        let mut table = QuotaTable::default();
+        assert_eq!(quota_decode(&quota_encode(&table).unwrap()).unwrap(), table);
+        table.basis = Some(1024 * 1024);
+        table.dicts.insert("wlan.networks".to_string(), 16384);
+        table.dicts.insert("vault.passwords".to_string(), 0);
+        let record = quota_encode(&table).unwrap();
+        assert_eq!(quota_decode(&record).unwrap(), table);
+        // any truncation should be detected, rather than producing a partial table
+        for len in 0..record.len() {
+            assert!(quota_decode(&record[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_quota_reservation() {
+        assert_eq!(quota_reservation(0, None), DEFAULT_ALLOC_HINT as u64);
+        assert_eq!(quota_reservation(100, Some(0)), 100);
+        assert_eq!(quota_reservation(10, Some(200)), 200);
+        assert_eq!(quota_reservation(SMALL_CAPACITY, None), VPAGE_SIZE as u64);
+        assert_eq!(quota_reservation(10, Some(VPAGE_SIZE + 1)), 2 * VPAGE_SIZE as u64);
+    }
+}
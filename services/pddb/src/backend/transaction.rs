@@ -0,0 +1,236 @@
+/// # Multi-key Transactions
+///
+/// A transaction is a list of key writes and deletes, possibly spanning several dictionaries, that
+/// are applied to a single basis as a unit. The mechanism is a simple redo log:
+///
+///   1. The full list of operations is serialized into an "intent record", which is stored as an ordinary
+///      key (`TX_INTENT_KEY`) inside a reserved dictionary (`TX_INTENT_DICT`) of the target basis. Once
+///      `key_update` returns, the record is on disk and the transaction is considered committed.
+///   2. The operations are applied one at a time to the basis, with the normal sync rules.
+///   3. The reserved dictionary is removed, which retires the intent record.
+///
+/// If power is lost (or the PDDB panics) between steps 1 and 3, the intent record is found the next
+/// time the basis is mounted, and the operations are replayed. Every operation is idempotent -- writes
+/// replace the entire key contents and deletes of missing keys are ignored -- so replaying a partially
+/// applied transaction converges to the same final state. If power is lost before step 1 completes, the
+/// record fails to decrypt or is absent, and none of the transaction's effects are visible.
+///
+/// Intent record format (all integers little-endian):
+///   - `TX_RECORD_MAGIC` (4 bytes)
+///   - number of operations (u32)
+///   - for each operation:
+///     - opcode (u8): 0 = write, 1 = delete
+///     - dict name length (u8) + dict name bytes
+///     - key name length (u8) + key name bytes
+///     - write only: data length (u32) + data bytes
+///
+/// The same dictionary holds the intent record of a large key that is being moved by a compaction pass
+/// (`TX_MOVE_KEY`). It names the key and both of its locations, so that when a move is interrupted, the
+/// copy that the key descriptor doesn't point at can be returned to the free space -- and nothing else.
+/// Moves and transactions each run to completion within a single request, so at most one of the two
+/// records is ever on disk.
+///
+/// Move record format (all integers little-endian):
+///   - `TX_MOVE_MAGIC` (4 bytes)
+///   - dict name length (u8) + dict name bytes
+///   - key name length (u8) + key name bytes
+///   - old start (u64), new start (u64), reserved length (u64)
+
+use crate::api::*;
+
+use std::convert::TryInto;
+use std::io::{Result, Error, ErrorKind};
+
+/// Name of the dictionary that holds in-flight intent records. Client requests that target this
+/// dictionary are rejected, so it can't collide with user data.
+pub(crate) const TX_INTENT_DICT: &str = "__pddb.txn";
+pub(crate) const TX_INTENT_KEY: &str = "intent";
+const TX_RECORD_MAGIC: [u8; 4] = *b"TxI1";
+pub(crate) const TX_MOVE_KEY: &str = "move";
+const TX_MOVE_MAGIC: [u8; 4] = *b"TxM1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TxOp {
+    /// Replace the contents of `key` in `dict` with `data`, creating the dict and key if necessary
+    Write { dict: String, key: String, data: Vec::<u8> },
+    /// Remove `key` from `dict`
+    Delete { dict: String, key: String },
+}
+impl TxOp {
+    pub(crate) fn dict(&self) -> &str {
+        match self {
+            TxOp::Write { dict, .. } => dict,
+            TxOp::Delete { dict, .. } => dict,
+        }
+    }
+    pub(crate) fn key(&self) -> &str {
+        match self {
+            TxOp::Write { key, .. } => key,
+            TxOp::Delete { key, .. } => key,
+        }
+    }
+}
+
+/// Serializes a list of operations into an intent record
+pub(crate) fn tx_record_encode(ops: &[TxOp]) -> Result<Vec::<u8>> {
+    let mut record = Vec::<u8>::new();
+    record.extend_from_slice(&TX_RECORD_MAGIC);
+    record.extend_from_slice(&(ops.len() as u32).to_le_bytes());
+    for op in ops.iter() {
+        if op.dict().len() > DICT_NAME_LEN - 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "dict name is too long"));
+        }
+        if op.key().len() > KEY_NAME_LEN - 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "key name is too long"));
+        }
+        record.push(match op { TxOp::Write { .. } => 0, TxOp::Delete { .. } => 1 });
+        record.push(op.dict().len() as u8);
+        record.extend_from_slice(op.dict().as_bytes());
+        record.push(op.key().len() as u8);
+        record.extend_from_slice(op.key().as_bytes());
+        if let TxOp::Write { data, .. } = op {
+            record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            record.extend_from_slice(data);
+        }
+    }
+    Ok(record)
+}
+
+/// A large key on its way from `from` to `to`; `reserved` bytes are copied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TxMove {
+    pub(crate) dict: String,
+    pub(crate) key: String,
+    pub(crate) from: u64,
+    pub(crate) to: u64,
+    pub(crate) reserved: u64,
+}
+
+/// Serializes the intent record of a key move
+pub(crate) fn tx_move_encode(mv: &TxMove) -> Result<Vec::<u8>> {
+    if mv.dict.len() > DICT_NAME_LEN - 1 {
+        return Err(Error::new(ErrorKind::InvalidInput, "dict name is too long"));
+    }
+    if mv.key.len() > KEY_NAME_LEN - 1 {
+        return Err(Error::new(ErrorKind::InvalidInput, "key name is too long"));
+    }
+    let mut record = Vec::<u8>::new();
+    record.extend_from_slice(&TX_MOVE_MAGIC);
+    record.push(mv.dict.len() as u8);
+    record.extend_from_slice(mv.dict.as_bytes());
+    record.push(mv.key.len() as u8);
+    record.extend_from_slice(mv.key.as_bytes());
+    record.extend_from_slice(&mv.from.to_le_bytes());
+    record.extend_from_slice(&mv.to.to_le_bytes());
+    record.extend_from_slice(&mv.reserved.to_le_bytes());
+    Ok(record)
+}
+
+/// Deserializes the intent record of a key move. Returns an `InvalidData` error if the record is truncated or malformed.
+pub(crate) fn tx_move_decode(record: &[u8]) -> Result<TxMove> {
+    fn take<'a>(record: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+        if *pos + len > record.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "move record is truncated"));
+        }
+        let slice = &record[*pos..*pos + len];
+        *pos += len;
+        Ok(slice)
+    }
+    fn take_str(record: &[u8], pos: &mut usize) -> Result<String> {
+        let len = take(record, pos, 1)?[0] as usize;
+        std::str::from_utf8(take(record, pos, len)?)
+            .map(|s| s.to_string())
+            .or(Err(Error::new(ErrorKind::InvalidData, "move record name is not valid utf-8")))
+    }
+    fn take_u64(record: &[u8], pos: &mut usize) -> Result<u64> {
+        Ok(u64::from_le_bytes(take(record, pos, 8)?.try_into().unwrap()))
+    }
+    let mut pos = 0;
+    if take(record, &mut pos, 4)? != &TX_MOVE_MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "move record has the wrong magic number"));
+    }
+    let dict = take_str(record, &mut pos)?;
+    let key = take_str(record, &mut pos)?;
+    let from = take_u64(record, &mut pos)?;
+    let to = take_u64(record, &mut pos)?;
+    let reserved = take_u64(record, &mut pos)?;
+    Ok(TxMove { dict, key, from, to, reserved })
+}
+
+/// Deserializes an intent record. Returns an `InvalidData` error if the record is truncated or malformed.
+pub(crate) fn tx_record_decode(record: &[u8]) -> Result<Vec::<TxOp>> {
+    fn take<'a>(record: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+        if *pos + len > record.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "transaction record is truncated"));
+        }
+        let slice = &record[*pos..*pos + len];
+        *pos += len;
+        Ok(slice)
+    }
+    fn take_str(record: &[u8], pos: &mut usize) -> Result<String> {
+        let len = take(record, pos, 1)?[0] as usize;
+        std::str::from_utf8(take(record, pos, len)?)
+            .map(|s| s.to_string())
+            .or(Err(Error::new(ErrorKind::InvalidData, "transaction record name is not valid utf-8")))
+    }
+    let mut pos = 0;
+    if take(record, &mut pos, 4)? != &TX_RECORD_MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "transaction record has the wrong magic number"));
+    }
+    let count = u32::from_le_bytes(take(record, &mut pos, 4)?.try_into().unwrap());
+    let mut ops = Vec::<TxOp>::new();
+    for _ in 0..count {
+        let opcode = take(record, &mut pos, 1)?[0];
+        let dict = take_str(record, &mut pos)?;
+        let key = take_str(record, &mut pos)?;
+        match opcode {
+            0 => {
+                let len = u32::from_le_bytes(take(record, &mut pos, 4)?.try_into().unwrap()) as usize;
+                ops.push(TxOp::Write { dict, key, data: take(record, &mut pos, len)?.to_vec() });
+            }
+            1 => ops.push(TxOp::Delete { dict, key }),
+            _ => return Err(Error::new(ErrorKind::InvalidData, "transaction record has an unknown opcode")),
+        }
+    }
+    Ok(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{LARGE_POOL_START, VPAGE_SIZE};
+
+    #[test]
+    fn test_tx_record_roundtrip() {
+        let ops = vec![
+            TxOp::Write { dict: "wallet".to_string(), key: "balance".to_string(), data: vec![1, 2, 3, 4] },
+            TxOp::Delete { dict: "wallet".to_string(), key: "pending".to_string() },
+            TxOp::Write { dict: "log".to_string(), key: "entry".to_string(), data: vec![0xAA; 5000] },
+        ];
+        let record = tx_record_encode(&ops).unwrap();
+        assert_eq!(tx_record_decode(&record).unwrap(), ops);
+        // any truncation should be detected, rather than producing a partial list of operations
+        for len in 0..record.len() {
+            assert!(tx_record_decode(&record[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tx_move_roundtrip() {
+        let mv = TxMove {
+            dict: "photos".to_string(),
+            key: "big".to_string(),
+            from: LARGE_POOL_START + 0x4000_0000,
+            to: LARGE_POOL_START,
+            reserved: 3 * VPAGE_SIZE as u64,
+        };
+        let record = tx_move_encode(&mv).unwrap();
+        assert_eq!(tx_move_decode(&record).unwrap(), mv);
+        for len in 0..record.len() {
+            assert!(tx_move_decode(&record[..len]).is_err());
+        }
+        // the two kinds of record can't be mistaken for each other
+        assert!(tx_record_decode(&record).is_err());
+        assert!(tx_move_decode(&tx_record_encode(&[]).unwrap()).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use core::num::NonZeroU64;
 use core::ops::Add;
 use super::{PAGE_SIZE, VPAGE_SIZE};
+use super::{PP_PAGE_WIDTH, PP_CLEAN_BIT, PP_VALID_BIT, PP_STATE_LSB, PP_STATE_MSB, PP_JOURNAL_LSB, PP_JOURNAL_MSB};
 use crate::SpaceState;
 use bitfield::bitfield;
 use std::hash::{Hash, Hasher};
@@ -24,23 +25,22 @@ pub(crate) const PHYS_PAGE_JOURNAL_MAX: u8 = 15;
 pub type PhysAddr = u32;
 #[cfg(feature = "u64_pa")]
 pub type PhysAddr = u64;
-const BITFIELD_PAGE_WIDTH: usize = core::mem::size_of::<PhysAddr>() * 8 - 12; // "12" should be log2(PAGE_SIZE) but https://github.com/rust-lang/rust/issues/70887
 // Physical page information, coded as a bitfield, because space is a premium!
 bitfield! {
     #[derive(Copy, Clone, Eq)]
     pub struct PhysPage(PhysAddr);
     impl Debug;
-    pub page_number, set_page_number: BITFIELD_PAGE_WIDTH - 1, 0;
+    pub page_number, set_page_number: PP_PAGE_WIDTH - 1, 0;
     // this is only used by the page table mechanism
-    pub clean, set_clean: BITFIELD_PAGE_WIDTH + 0;
+    pub clean, set_clean: PP_CLEAN_BIT;
     // when set, indicates that the record contents are valid and should be used
     // when cleared, the record contents are invalid and should be ignored.
     // valid is used by both FastSpace and the page table mechanism. Note that we rely upon the mapping of 0->not valid.
-    pub valid, set_valid: BITFIELD_PAGE_WIDTH + 1;
+    pub valid, set_valid: PP_VALID_BIT;
     // these are only used by the FastSpace mechanism; they have no meaning in other contexts
-    pub u8, from into SpaceState, space_state, set_space_state: BITFIELD_PAGE_WIDTH + 3, BITFIELD_PAGE_WIDTH + 2;
+    pub u8, from into SpaceState, space_state, set_space_state: PP_STATE_MSB, PP_STATE_LSB;
     // 4 bits for a journal revision. Intended for the FastSpace mechanism
-    pub u8, journal, set_journal: BITFIELD_PAGE_WIDTH + 7, BITFIELD_PAGE_WIDTH + 4;
+    pub u8, journal, set_journal: PP_JOURNAL_MSB, PP_JOURNAL_LSB;
 }
 // hashes should only key off of the page number, not the metadata
 impl Hash for PhysPage {
@@ -219,4 +219,53 @@ mod tests {
         println!("pp.journal(): {}", pp.journal());
         assert!(pp.journal() == PHYS_PAGE_JOURNAL_MAX, "PHYS_PAGE_JOURNAL_MAX is incorrect");
     }
+    /// The sizes in `layout.rs` are plain numbers so host tools can use them; check them against the types
+    /// they stand in for.
+    #[test]
+    fn test_layout_sizes() {
+        use aes_gcm_siv::{Nonce, Tag};
+        use core::mem::size_of;
+        assert_eq!(PAGE_SIZE, spinor::SPINOR_ERASE_SIZE as usize);
+        assert_eq!(super::super::NONCE_LEN, size_of::<Nonce>());
+        assert_eq!(super::super::TAG_LEN, size_of::<Tag>());
+        assert_eq!(super::super::JOURNAL_LEN, size_of::<JournalType>());
+        assert_eq!(PP_PAGE_WIDTH, size_of::<PhysAddr>() * 8 - 12);
+    }
+    #[test]
+    fn test_layout_record_offsets() {
+        use super::super::*;
+        use crate::api::{KeyFlags, BASIS_NAME_LEN, DICT_NAME_LEN, KEY_NAME_LEN};
+        fn offset<T, F>(record: &T, field: &F) -> usize {
+            field as *const F as usize - record as *const T as usize
+        }
+        let root = BasisRoot::default();
+        assert_eq!(offset(&root, &root.magic), BASIS_ROOT_MAGIC_OFFSET);
+        assert_eq!(offset(&root, &root.version), BASIS_ROOT_VERSION_OFFSET);
+        assert_eq!(offset(&root, &root.age), BASIS_ROOT_AGE_OFFSET);
+        assert_eq!(offset(&root, &root.num_dictionaries), BASIS_ROOT_NUM_DICTS_OFFSET);
+        assert_eq!(offset(&root, &root.name), BASIS_ROOT_NAME_OFFSET);
+        assert_eq!(core::mem::size_of::<BasisRoot>(), BASIS_ROOT_LEN);
+        assert_eq!(BASIS_ROOT_NAME_OFFSET + BASIS_NAME_LEN, BASIS_ROOT_LEN);
+
+        let dict = Dictionary::default();
+        assert_eq!(offset(&dict, &dict.flags), DICT_FLAGS_OFFSET);
+        assert_eq!(offset(&dict, &dict.age), DICT_AGE_OFFSET);
+        assert_eq!(offset(&dict, &dict.num_keys), DICT_NUM_KEYS_OFFSET);
+        assert_eq!(offset(&dict, &dict.free_key_index), DICT_FREE_KEY_INDEX_OFFSET);
+        assert_eq!(offset(&dict, &dict.name), DICT_NAME_OFFSET);
+        assert_eq!(DICT_NAME_OFFSET + DICT_NAME_LEN, DK_STRIDE);
+
+        let key = KeyDescriptor::default();
+        assert_eq!(offset(&key, &key.start), KEY_START_OFFSET);
+        assert_eq!(offset(&key, &key.len), KEY_LEN_OFFSET);
+        assert_eq!(offset(&key, &key.reserved), KEY_RESERVED_OFFSET);
+        assert_eq!(offset(&key, &key.flags), KEY_FLAGS_OFFSET);
+        assert_eq!(offset(&key, &key.age), KEY_AGE_OFFSET);
+        assert_eq!(offset(&key, &key.name), KEY_NAME_OFFSET);
+        assert_eq!(KEY_NAME_OFFSET + KEY_NAME_LEN, DK_STRIDE);
+
+        let mut flags = KeyFlags(0);
+        flags.set_valid(true);
+        assert_eq!(flags.0, 1 << DK_VALID_BIT);
+    }
 }
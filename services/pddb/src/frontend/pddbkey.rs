@@ -162,6 +162,8 @@ impl<'a> Write for PddbKey<'a> {
                     }
                     PddbRetcode::BasisLost => Err(Error::new(ErrorKind::BrokenPipe, "Basis lost")),
                     PddbRetcode::AccessDenied => Err(Error::new(ErrorKind::PermissionDenied, "Access denied")),
+                    PddbRetcode::DiskFull => Err(Error::new(ErrorKind::OutOfMemory, "Out of disk space")),
+                    PddbRetcode::QuotaExceeded => Err(Error::new(ErrorKind::OutOfMemory, QuotaExceeded)),
                     _ => Err(Error::new(ErrorKind::Other, "Unhandled error code in PddbKey Write")),
                 }
             }
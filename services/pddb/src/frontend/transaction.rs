@@ -0,0 +1,132 @@
+use crate::*;
+use xous::CID;
+use xous_ipc::Buffer;
+
+use num_traits::*;
+use std::io::{Result, Error, ErrorKind};
+
+/// Sent with every transaction request, see `PddbTxRequest::owner`. 0 until it is first needed.
+static TX_OWNER: AtomicU32 = AtomicU32::new(0);
+fn tx_owner() -> u32 {
+    let owner = TX_OWNER.load(Ordering::Relaxed);
+    if owner != 0 {
+        return owner;
+    }
+    let mut candidate = 0;
+    while candidate == 0 {
+        candidate = xous::create_server_id().expect("couldn't get a random number").to_u32().0;
+    }
+    // another thread may have picked one first, in which case theirs is used
+    match TX_OWNER.compare_exchange(0, candidate, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => candidate,
+        Err(existing) => existing,
+    }
+}
+
+/// A set of key writes and deletes that are applied to a single basis all-or-nothing.
+/// Created by `Pddb::transaction()`. Operations are staged on the server, but nothing is
+/// visible to other readers until `commit()` returns successfully. Dropping the transaction
+/// without committing it aborts it.
+///
+/// Staged operations are held in the PDDB's memory, so how much a transaction, and all of a process's
+/// transactions together, can stage is limited. A `write()` or `delete()` that would go over a limit
+/// fails with `ErrorKind::OutOfMemory`, and the server discards the whole transaction.
+pub struct PddbTransaction {
+    pub(crate) id: u32,
+    pub(crate) conn: CID,
+    pub(crate) finished: bool,
+}
+impl PddbTransaction {
+    /// Stages a write that replaces the entire contents of `key_name` in `dict_name` with `data`.
+    /// The dictionary and key are created at commit time if they don't already exist.
+    pub fn write(&mut self, dict_name: &str, key_name: &str, data: &[u8]) -> Result<()> {
+        let mut chunks = data.chunks(PDDB_TX_CHUNK_LEN);
+        // always send at least one chunk, so that zero-length writes are staged too
+        self.stage(dict_name, key_name, PddbTxOp::Write, chunks.next().unwrap_or(&[]))?;
+        for chunk in chunks {
+            self.stage(dict_name, key_name, PddbTxOp::WriteAppend, chunk)?;
+        }
+        Ok(())
+    }
+    /// Stages the deletion of `key_name` from `dict_name`. The commit fails if the key does not exist.
+    pub fn delete(&mut self, dict_name: &str, key_name: &str) -> Result<()> {
+        self.stage(dict_name, key_name, PddbTxOp::Delete, &[])
+    }
+    /// Applies every staged operation, or none of them. Once this returns `Ok`, all the changes are on disk.
+    ///
+    /// An `ErrorKind::Interrupted` error means the transaction was committed to disk, but could not be
+    /// applied in full right now; it is completed automatically the next time the basis is mounted.
+    /// Any other error means none of the operations were applied.
+    pub fn commit(mut self) -> Result<()> {
+        self.finished = true;
+        self.request(Opcode::TxCommit, PddbTxOp::None, "", "", &[]).map(|_| ())
+    }
+    /// Discards all the staged operations.
+    pub fn abort(mut self) -> Result<()> {
+        self.finished = true;
+        self.request(Opcode::TxAbort, PddbTxOp::None, "", "", &[]).map(|_| ())
+    }
+
+    fn stage(&self, dict_name: &str, key_name: &str, op: PddbTxOp, data: &[u8]) -> Result<()> {
+        if key_name.len() > (KEY_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+        }
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        self.request(Opcode::TxStage, op, dict_name, key_name, data).map(|_| ())
+    }
+    fn request(&self, opcode: Opcode, op: PddbTxOp, dict_name: &str, key_name: &str, data: &[u8]) -> Result<u32> {
+        tx_request(self.conn, opcode, self.id, None, op, dict_name, key_name, data)
+    }
+}
+
+/// Shared by `Pddb::transaction()` and `PddbTransaction` to marshal a request to the server. Returns the
+/// transaction ID echoed (or assigned, in the case of `TxBegin`) by the server.
+pub(crate) fn tx_request(conn: CID, opcode: Opcode, id: u32, basis_name: Option<&str>, op: PddbTxOp,
+    dict_name: &str, key_name: &str, data: &[u8]) -> Result<u32> {
+    let mut request = PddbTxRequest {
+        id,
+        owner: tx_owner(),
+        basis_specified: basis_name.is_some(),
+        basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name.unwrap_or("")),
+        dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict_name),
+        key: xous_ipc::String::<KEY_NAME_LEN>::from_str(key_name),
+        op,
+        len: data.len() as u32,
+        data: [0u8; PDDB_TX_CHUNK_LEN],
+        result: PddbRequestCode::Uninit,
+    };
+    for (&src, dst) in data.iter().zip(request.data.iter_mut()) {
+        *dst = src;
+    }
+    let mut buf = Buffer::into_buf(request)
+        .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+    buf.lend_mut(conn, opcode.to_u32().unwrap())
+        .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+
+    let response = buf.to_original::<PddbTxRequest, _>().unwrap();
+    match response.result {
+        PddbRequestCode::NoErr => Ok(response.id),
+        PddbRequestCode::AccessDenied => Err(Error::new(ErrorKind::PermissionDenied, "Transaction access denied")),
+        PddbRequestCode::NoFreeSpace => Err(Error::new(ErrorKind::OutOfMemory, "No more space on disk")),
+        PddbRequestCode::QuotaExceeded => Err(Error::new(ErrorKind::OutOfMemory, QuotaExceeded)),
+        PddbRequestCode::NotMounted => Err(Error::new(ErrorKind::ConnectionReset, "PDDB was unmounted")),
+        PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Transaction, dictionary or key was not found")),
+        PddbRequestCode::Interrupted => Err(Error::new(ErrorKind::Interrupted, "Transaction committed, but will be completed on the next mount")),
+        PddbRequestCode::TxLimit => Err(Error::new(ErrorKind::OutOfMemory, "Transaction staging limit reached")),
+        _ => Err(Error::new(ErrorKind::Other, "Internal error"))
+    }
+}
+
+impl Drop for PddbTransaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            // the server may have already discarded the transaction (e.g. the basis was locked), so ignore the result
+            self.request(Opcode::TxAbort, PddbTxOp::None, "", "", &[]).ok();
+        }
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe{xous::disconnect(self.conn).unwrap();}
+        }
+    }
+}
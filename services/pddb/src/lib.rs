@@ -23,7 +23,9 @@ use std::cell::RefCell;
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 pub enum CbOp {
     Change,
-    Quit
+    Quit,
+    /// a `PddbWatchEventIpc`, sent as a memory message
+    Watch,
 }
 
 pub struct PddbMountPoller {
@@ -87,6 +89,9 @@ pub struct Pddb {
     /// in the case of a basis change. Basis changes are thought to be rare; so, big changes
     /// like this are probably OK.
     keys: Arc<Mutex<HashMap<ApiToken, Box<dyn Fn() + 'static + Send> >>>,
+    /// Handlers for change notifications registered with `watch()`, by watch ID. These run on the
+    /// callback thread, with the same restrictions as the key change closures.
+    watches: Arc<Mutex<HashMap<u32, Box<dyn Fn(PddbWatchEvent) + 'static + Send> >>>,
     trng: trng::Trng,
 }
 impl Pddb {
@@ -100,6 +105,7 @@ impl Pddb {
             cb: RefCell::new(None),
             cb_handle: RefCell::new(None),
             keys,
+            watches: Arc::new(Mutex::new(HashMap::new())),
             trng: trng::Trng::new(&xns).unwrap(),
         }
     }
@@ -108,6 +114,7 @@ impl Pddb {
             let sid = xous::create_server().unwrap();
             let handle = thread::spawn({
                 let keys = Arc::clone(&self.keys);
+                let watches = Arc::clone(&self.watches);
                 let sid = sid.clone();
                 move || {
                     loop {
@@ -121,6 +128,15 @@ impl Pddb {
                                     log::warn!("Key changed but no callback was hooked to receive it");
                                 }
                             }),
+                            Some(CbOp::Watch) => {
+                                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                                let event = buffer.to_original::<PddbWatchEventIpc, _>().unwrap();
+                                if let Some(cb) = watches.lock().unwrap().get(&event.id) {
+                                    cb(event.to_event());
+                                } else {
+                                    log::debug!("Watch event arrived after the watch was cancelled");
+                                }
+                            }
                             Some(CbOp::Quit) => { // blocking scalar
                                 xous::return_scalar(msg.sender, 0).unwrap();
                                 break;
@@ -293,6 +309,112 @@ impl Pddb {
             }
         }
     }
+    /// Seals the contents of the unlocked basis `basis_name` into a self-describing blob, encrypted under
+    /// `passphrase`. The blob can be archived, or moved to another device and restored with `import_basis()`.
+    pub fn export_basis(&self, basis_name: &str, passphrase: &str) -> Result<Vec::<u8>> {
+        if basis_name.len() > BASIS_NAME_LEN - 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+        }
+        if passphrase.len() > PASSWORD_LEN {
+            return Err(Error::new(ErrorKind::InvalidInput, "passphrase too long"));
+        }
+        let req = self.basis_transfer_request(Opcode::ExportBasis, basis_name, passphrase, [0; 3], 0)?;
+        match req.code {
+            PddbRequestCode::NoErr => (),
+            PddbRequestCode::NotFound => return Err(Error::new(ErrorKind::NotFound, "Basis not found")),
+            PddbRequestCode::NoFreeSpace => return Err(Error::new(ErrorKind::OutOfMemory, "Basis is too large to export")),
+            _ => return Err(Error::new(ErrorKind::Other, "Internal error exporting basis")),
+        }
+        let mut blob = Vec::<u8>::with_capacity(req.len as usize);
+        let mut buf = Buffer::new(core::mem::size_of::<PddbBuf>());
+        let result = loop {
+            if blob.len() as u64 >= req.len {
+                break Ok(());
+            }
+            {
+                let pbuf = PddbBuf::from_slice_mut(buf.as_mut());
+                pbuf.token = req.token;
+                pbuf.len = PDDB_BUF_DATA_LEN as u16;
+                pbuf.position = blob.len() as u64;
+                pbuf.retcode = PddbRetcode::Uninit;
+            }
+            if buf.lend_mut(self.conn, Opcode::TransferRead.to_u32().unwrap()).is_err() {
+                break Err(Error::new(ErrorKind::Other, "Xous internal error"));
+            }
+            let pbuf = PddbBuf::from_slice_mut(buf.as_mut());
+            match pbuf.retcode {
+                PddbRetcode::Ok if pbuf.len > 0 => blob.extend_from_slice(&pbuf.data[..pbuf.len as usize]),
+                _ => break Err(Error::new(ErrorKind::BrokenPipe, "Basis export was lost in transfer")),
+            }
+        };
+        self.basis_transfer_drop(req.token);
+        result.map(|_| blob)
+    }
+
+    /// Creates a new basis called `basis_name` from a blob made by `export_basis()`. The user is prompted for
+    /// the password of the new basis, so it gets keys of its own on this device; the new basis is left unlocked.
+    /// Fails with `PermissionDenied` if `passphrase` doesn't open the blob, or if a basis called `basis_name` is
+    /// already unlocked, and with `InvalidData` if the blob is not a basis export, or is of a version that
+    /// this PDDB does not understand.
+    pub fn import_basis(&self, basis_name: &str, blob: &[u8], passphrase: &str) -> Result<()> {
+        if basis_name.len() > BASIS_NAME_LEN - 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+        }
+        if passphrase.len() > PASSWORD_LEN {
+            return Err(Error::new(ErrorKind::InvalidInput, "passphrase too long"));
+        }
+        let req = self.basis_transfer_request(Opcode::ImportBasisStage, basis_name, "", [0; 3], blob.len() as u64)?;
+        match req.code {
+            PddbRequestCode::NoErr => (),
+            PddbRequestCode::NoFreeSpace => return Err(Error::new(ErrorKind::OutOfMemory, "Basis export is too large")),
+            _ => return Err(Error::new(ErrorKind::Other, "Internal error importing basis")),
+        }
+        let mut buf = Buffer::new(core::mem::size_of::<PddbBuf>());
+        for (index, chunk) in blob.chunks(PDDB_BUF_DATA_LEN).enumerate() {
+            {
+                let pbuf = PddbBuf::from_slice_mut(buf.as_mut());
+                pbuf.token = req.token;
+                pbuf.len = chunk.len() as u16;
+                pbuf.position = (index * PDDB_BUF_DATA_LEN) as u64;
+                pbuf.retcode = PddbRetcode::Uninit;
+                pbuf.data[..chunk.len()].copy_from_slice(chunk);
+            }
+            let sent = buf.lend_mut(self.conn, Opcode::TransferWrite.to_u32().unwrap());
+            if sent.is_err() || !matches!(PddbBuf::from_slice_mut(buf.as_mut()).retcode, PddbRetcode::Ok) {
+                self.basis_transfer_drop(req.token);
+                return Err(Error::new(ErrorKind::BrokenPipe, "Basis export was lost in transfer"));
+            }
+        }
+        let ret = self.basis_transfer_request(Opcode::ImportBasis, basis_name, passphrase, req.token, blob.len() as u64)?;
+        match ret.code {
+            PddbRequestCode::NoErr => Ok(()),
+            PddbRequestCode::AccessDenied => Err(Error::new(ErrorKind::PermissionDenied, "Wrong passphrase, or the basis is already unlocked")),
+            PddbRequestCode::InvalidData => Err(Error::new(ErrorKind::InvalidData, "Not a basis export, or an unsupported version")),
+            PddbRequestCode::NoFreeSpace => Err(Error::new(ErrorKind::OutOfMemory, "No free space to import basis")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error importing basis")),
+        }
+    }
+    fn basis_transfer_request(&self, op: Opcode, basis_name: &str, passphrase: &str, token: ApiToken, len: u64) -> Result<PddbBasisTransferRequest> {
+        let req = PddbBasisTransferRequest {
+            name: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name),
+            passphrase: xous_ipc::String::<PASSWORD_LEN>::from_str(passphrase),
+            token,
+            len,
+            code: PddbRequestCode::Uninit,
+        };
+        let mut buf = Buffer::into_buf(req).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, op.to_u32().unwrap()).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let ret = buf.to_original::<PddbBasisTransferRequest, _>().unwrap();
+        // don't leave the passphrase lying around in the heap
+        buf.volatile_clear();
+        Ok(ret)
+    }
+    fn basis_transfer_drop(&self, token: ApiToken) {
+        send_message(self.conn,
+            Message::new_blocking_scalar(Opcode::TransferDrop.to_usize().unwrap(),
+            token[0] as usize, token[1] as usize, token[2] as usize, 0)
+        ).expect("couldn't release basis export");
+    }
 
     /// If the `create_*` flags are set, creates the asset if they do not exist, otherwise if false, returns
     /// an error if the asset does not exist.
@@ -376,6 +498,7 @@ impl Pddb {
             }
             PddbRequestCode::AccessDenied => Err(Error::new(ErrorKind::PermissionDenied, "Dict/Key access denied")),
             PddbRequestCode::NoFreeSpace => Err(Error::new(ErrorKind::OutOfMemory, "No more space on disk")),
+            PddbRequestCode::QuotaExceeded => Err(Error::new(ErrorKind::OutOfMemory, QuotaExceeded)),
             PddbRequestCode::NotMounted => Err(Error::new(ErrorKind::ConnectionReset, "PDDB was unmounted")),
             PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found")),
             _ => Err(Error::new(ErrorKind::Other, "Internal error"))
@@ -428,6 +551,7 @@ impl Pddb {
         match response.result {
             PddbRequestCode::NoErr => Ok(()),
             PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found")),
+            PddbRequestCode::AccessDenied => Err(Error::new(ErrorKind::PermissionDenied, "Dict/Key access denied")),
             _ => Err(Error::new(ErrorKind::Other, "Internal error"))
         }
     }
@@ -478,6 +602,24 @@ impl Pddb {
         }
     }
 
+    /// Opens a transaction on the specified basis, or the most recently unlocked basis if `None`.
+    /// Writes and deletes staged into the returned `PddbTransaction` may span several dictionaries,
+    /// and are applied all-or-nothing when it is committed.
+    pub fn transaction(&self, basis_name: Option<&str>) -> Result<PddbTransaction> {
+        if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+        }
+        let id = tx_request(self.conn, Opcode::TxBegin, 0, basis_name, PddbTxOp::None, "", "", &[])?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(PddbTransaction {
+            id,
+            conn: self.conn,
+            finished: false,
+        })
+    }
+
     pub fn sync(&self) -> Result<()> {
         let response = send_message(
             self.conn,
@@ -556,6 +698,233 @@ impl Pddb {
         Ok(key_list)
     }
 
+    /// Like `list_keys`, but also returns each key's metadata, without having to open the keys.
+    pub fn list_keys_with_metadata(&self, dict_name: &str, basis_name: Option<&str>) -> Result<Vec::<(String, KeyMetadata)>> {
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+        }
+        // same two-phase query as `list_keys`: the count request snapshots the key list on the server,
+        // and every entry must be fetched, highest index last, to release it.
+        let token = [self.trng.get_u32().unwrap(), self.trng.get_u32().unwrap(), self.trng.get_u32().unwrap(), self.trng.get_u32().unwrap()];
+        let request = PddbDictRequest {
+            basis_specified: basis_name.is_some(),
+            basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name.unwrap_or("")),
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict_name),
+            key: xous_ipc::String::<KEY_NAME_LEN>::new(),
+            index: 0,
+            code: PddbRequestCode::Uninit,
+            token,
+        };
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::KeyCountInDict.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+
+        let response = buf.to_original::<PddbDictRequest, _>().unwrap();
+        let count = match response.code {
+            PddbRequestCode::NoErr => response.index,
+            PddbRequestCode::NotFound => return Err(Error::new(ErrorKind::NotFound, "dictionary not found")),
+            _ => return Err(Error::new(ErrorKind::Other, "Internal error")),
+        };
+        let mut key_list = Vec::<(String, KeyMetadata)>::new();
+        for index in 0..count {
+            let request = PddbKeyMetaIpc::new(basis_name, dict_name, "", index, token);
+            let mut buf = Buffer::into_buf(request)
+                .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+            buf.lend_mut(self.conn, Opcode::GetKeyMetadataAtIndex.to_u32().unwrap())
+                .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+            let response = buf.to_original::<PddbKeyMetaIpc, _>().unwrap();
+            match response.code {
+                PddbRequestCode::NoErr => key_list.push((
+                    String::from(response.key.as_str().expect("utf-8 parse error in key name")),
+                    response.to_metadata()
+                )),
+                _ => return Err(Error::new(ErrorKind::Other, "Internal error")),
+            }
+        }
+        Ok(key_list)
+    }
+
+    /// Registers `cb` to be called whenever a key in `dict_name` whose name starts with `key_prefix`
+    /// is created, updated or deleted, when the dictionary itself is deleted, and when a basis is
+    /// locked or unlocked. If `basis_name` is `None`, changes in every basis are reported; otherwise only
+    /// changes in that basis are. Returns an ID that can be passed to `unwatch()`.
+    ///
+    /// `cb` runs on this object's callback thread, so it should be brief, and it must not call
+    /// `watch()` or `unwatch()` itself. Notifications are best-effort: if the callback thread falls
+    /// too far behind, events are dropped rather than stalling the PDDB.
+    pub fn watch<F>(&self, dict_name: &str, key_prefix: Option<&str>, basis_name: Option<&str>, cb: F) -> Result<u32>
+    where F: Fn(PddbWatchEvent) + 'static + Send {
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        if key_prefix.unwrap_or("").len() > (KEY_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "key prefix too long"));
+        }
+        if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+        }
+        self.ensure_async_responder();
+        // the callback is hooked up before the server knows about the watch, so no event can be missed
+        let mut id = self.trng.get_u32().unwrap();
+        while id == 0 || self.watches.lock().unwrap().contains_key(&id) {
+            id = self.trng.get_u32().unwrap();
+        }
+        self.watches.lock().unwrap().insert(id, Box::new(cb));
+        let request = PddbWatchRequest {
+            basis_specified: basis_name.is_some(),
+            basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name.unwrap_or("")),
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict_name),
+            prefix: xous_ipc::String::<KEY_NAME_LEN>::from_str(key_prefix.unwrap_or("")),
+            id,
+            cb_sid: self.cb.borrow().as_ref().unwrap().to_array(),
+            code: PddbRequestCode::Uninit,
+        };
+        let response = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))
+            .and_then(|mut buf| {
+                buf.lend_mut(self.conn, Opcode::Watch.to_u32().unwrap())
+                    .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+                Ok(buf.to_original::<PddbWatchRequest, _>().unwrap())
+            });
+        let result = match response {
+            Ok(response) => match response.code {
+                PddbRequestCode::NoErr => Ok(id),
+                // another process is using the same ID. Vanishingly unlikely, but the caller can retry.
+                PddbRequestCode::AccessDenied => Err(Error::new(ErrorKind::AlreadyExists, "watch ID collision, please retry")),
+                _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+            },
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            self.watches.lock().unwrap().remove(&id);
+        }
+        result
+    }
+
+    /// Cancels a registration made with `watch()`.
+    pub fn unwatch(&self, id: u32) -> Result<()> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Unwatch.to_usize().unwrap(), id as usize, 0, 0, 0)
+        ).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        self.watches.lock().unwrap().remove(&id);
+        if let xous::Result::Scalar1(rcode) = response {
+            match FromPrimitive::from_u8(rcode as u8) {
+                Some(PddbRetcode::Ok) => Ok(()),
+                _ => Err(Error::new(ErrorKind::NotFound, "No such watch")),
+            }
+        } else {
+            Err(Error::new(ErrorKind::Other, "Xous internal error"))
+        }
+    }
+
+    /// Lists up to `limit` key names from a dictionary (at most 32 per call), in lexicographic order,
+    /// filtered by `query`. Pass `None` as the cursor to start from the beginning, and the `cursor` of the
+    /// returned page to continue from where it left off; the returned cursor is `None` once the listing
+    /// is complete.
+    ///
+    /// Unlike `list_keys`, the server keeps no state between pages, so a listing can be abandoned at any
+    /// point. Keys that are added or removed in the middle of a listing may or may not show up in it.
+    pub fn list_keys_page(&self, dict_name: &str, query: &KeyQuery, cursor: Option<&str>, limit: usize,
+        basis_name: Option<&str>) -> Result<KeyPage> {
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+        }
+        for bound in [query.prefix.as_deref(), query.start.as_deref(), query.end.as_deref(), cursor].iter() {
+            if bound.unwrap_or("").len() > KEY_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+            }
+        }
+        let request = PddbKeyPageRequest {
+            basis_specified: basis_name.is_some(),
+            basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name.unwrap_or("")),
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict_name),
+            prefix: xous_ipc::String::<KEY_NAME_LEN>::from_str(query.prefix.as_deref().unwrap_or("")),
+            start: xous_ipc::String::<KEY_NAME_LEN>::from_str(query.start.as_deref().unwrap_or("")),
+            end_specified: query.end.is_some(),
+            end: xous_ipc::String::<KEY_NAME_LEN>::from_str(query.end.as_deref().unwrap_or("")),
+            after_specified: cursor.is_some(),
+            after: xous_ipc::String::<KEY_NAME_LEN>::from_str(cursor.unwrap_or("")),
+            limit: limit.min(KEY_PAGE_LEN) as u32,
+            count: 0,
+            names: [xous_ipc::String::<KEY_NAME_LEN>::new(); KEY_PAGE_LEN],
+            more: false,
+            code: PddbRequestCode::Uninit,
+        };
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::ListKeyPage.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let response = buf.to_original::<PddbKeyPageRequest, _>().unwrap();
+        match response.code {
+            PddbRequestCode::NoErr => {
+                let keys: Vec::<String> = response.names[..response.count as usize].iter()
+                    .map(|name| String::from(name.as_str().expect("utf-8 parse error in key name")))
+                    .collect();
+                let cursor = if response.more { keys.last().cloned() } else { None };
+                Ok(KeyPage { keys, cursor })
+            }
+            PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "dictionary not found")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+        }
+    }
+
+    /// Returns the metadata of a single key. Keys that have no metadata (for example, keys written by
+    /// older versions of the PDDB) return `KeyMetadata::default()`.
+    pub fn key_metadata(&self, dict_name: &str, key_name: &str, basis_name: Option<&str>) -> Result<KeyMetadata> {
+        let request = key_meta_request(dict_name, key_name, basis_name)?;
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::KeyMetadata.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let response = buf.to_original::<PddbKeyMetaIpc, _>().unwrap();
+        match response.code {
+            PddbRequestCode::NoErr => Ok(response.to_metadata()),
+            PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error"))
+        }
+    }
+
+    /// Sets the content type and attributes of an existing key, replacing any previous ones. The
+    /// timestamps in `meta` are ignored; they are maintained by the PDDB.
+    pub fn set_key_metadata(&self, dict_name: &str, key_name: &str, meta: &KeyMetadata, basis_name: Option<&str>) -> Result<()> {
+        meta.validate()?;
+        let mut request = key_meta_request(dict_name, key_name, basis_name)?;
+        request.set_metadata(meta);
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::SetKeyMetadata.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let response = buf.to_original::<PddbKeyMetaIpc, _>().unwrap();
+        match response.code {
+            PddbRequestCode::NoErr => Ok(()),
+            PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found")),
+            PddbRequestCode::NoFreeSpace => Err(Error::new(ErrorKind::OutOfMemory, "No more space on disk")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error"))
+        }
+    }
+
+    /// Tells the PDDB the current UTC time, in ms since the epoch, for stamping key metadata. This is
+    /// meant to be called by the time server, which can't be called back by the PDDB.
+    pub fn set_time_reference(&self, utc_ms: u64) -> Result<()> {
+        send_message(self.conn,
+            Message::new_scalar(Opcode::SetTimeReference.to_usize().unwrap(),
+            (utc_ms & 0xFFFF_FFFF) as usize, (utc_ms >> 32) as usize, 0, 0)
+        ).map(|_| ()).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))
+    }
 
     pub fn list_dict(&self, basis_name: Option<&str>) -> Result<Vec::<String>> {
         let bname = if let Some(bname) = basis_name {
@@ -660,6 +1029,98 @@ impl Pddb {
             _ => Err(Error::new(ErrorKind::Unsupported, "Return code was never set")),
         }
     }
+    /// Compacts the key pools of all the open bases: small keys are re-packed into as few pages as possible,
+    /// and large keys are moved down into the space left behind by deleted keys. The pass runs inside the
+    /// PDDB server a step at a time, behind a progress bar; other requests are served in between the steps,
+    /// and it is safe to suspend while it runs. Blocks until the pass is done.
+    ///
+    /// Fails with `WouldBlock` if a compaction pass is already running.
+    pub fn compact(&self) -> Result<PddbCompactReport> {
+        let request = PddbCompactRequest {
+            before: PddbFragStats::default(),
+            after: PddbFragStats::default(),
+            keys_moved: 0,
+            pages_freed: 0,
+            code: PddbRequestCode::Uninit,
+        };
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::Compact.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let response = buf.to_original::<PddbCompactRequest, _>().unwrap();
+        match response.code {
+            PddbRequestCode::NoErr => Ok(PddbCompactReport {
+                before: response.before,
+                after: response.after,
+                keys_moved: response.keys_moved,
+                pages_freed: response.pages_freed,
+            }),
+            PddbRequestCode::AccessDenied => Err(Error::new(ErrorKind::WouldBlock, "A compaction pass is already running")),
+            PddbRequestCode::NotMounted => Err(Error::new(ErrorKind::ConnectionReset, "PDDB was unmounted")),
+            PddbRequestCode::NoFreeSpace => Err(Error::new(ErrorKind::OutOfMemory, "No free space to move keys")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error compacting the PDDB")),
+        }
+    }
+    /// Sets the quota of `dict`, or of the whole basis if `dict` is `None`, to `limit` bytes. A `limit` of
+    /// `None` removes the quota. If no basis is specified, the most recently opened basis is used.
+    ///
+    /// Quotas are stored in the basis, and are enforced on every write to it: a write that would take the
+    /// usage past a limit fails with an `ErrorKind::OutOfMemory` error for which `is_quota_exceeded()`
+    /// returns `true`. Usage is the space reserved by the keys, which can be larger than their contents;
+    /// see `quota_usage()`.
+    pub fn set_quota(&self, basis_name: Option<&str>, dict: Option<&str>, limit: Option<u64>) -> Result<()> {
+        let mut request = Self::quota_request(basis_name, dict)?;
+        request.limit_specified = limit.is_some();
+        request.limit = limit.unwrap_or(0);
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::SetQuota.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let response = buf.to_original::<PddbQuotaRequest, _>().unwrap();
+        match response.code {
+            PddbRequestCode::NoErr => Ok(()),
+            PddbRequestCode::NotMounted => Err(Error::new(ErrorKind::NotFound, "Basis not found, or PDDB not mounted")),
+            PddbRequestCode::AccessDenied => Err(Error::new(ErrorKind::PermissionDenied, "Dictionary is reserved")),
+            PddbRequestCode::NoFreeSpace => Err(Error::new(ErrorKind::OutOfMemory, "No more space on disk")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+        }
+    }
+    /// Returns the usage and quota of `dict`, or of the whole basis if `dict` is `None`. If no basis is
+    /// specified, the most recently opened basis is used.
+    pub fn quota_usage(&self, basis_name: Option<&str>, dict: Option<&str>) -> Result<PddbQuotaUsage> {
+        let request = Self::quota_request(basis_name, dict)?;
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::QuotaUsage.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let response = buf.to_original::<PddbQuotaRequest, _>().unwrap();
+        match response.code {
+            PddbRequestCode::NoErr => Ok(PddbQuotaUsage {
+                used: response.used,
+                limit: if response.limit_specified { Some(response.limit) } else { None },
+            }),
+            PddbRequestCode::NotMounted => Err(Error::new(ErrorKind::NotFound, "Basis not found, or PDDB not mounted")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+        }
+    }
+    fn quota_request(basis_name: Option<&str>, dict: Option<&str>) -> Result<PddbQuotaRequest> {
+        if basis_name.map(|b| b.len() > BASIS_NAME_LEN - 1).unwrap_or(false) {
+            return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+        }
+        if dict.map(|d| d.len() > DICT_NAME_LEN - 1).unwrap_or(false) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        Ok(PddbQuotaRequest {
+            basis_specified: basis_name.is_some(),
+            basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name.unwrap_or("")),
+            dict_specified: dict.is_some(),
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict.unwrap_or("")),
+            limit_specified: false,
+            limit: 0,
+            used: 0,
+            code: PddbRequestCode::Uninit,
+        })
+    }
     /// Triggers a dump of the PDDB to host disk
     #[cfg(not(any(target_os = "none", target_os = "xous")))]
     pub fn dbg_dump(&self, name: &str) -> Result<()> {
@@ -704,6 +1165,10 @@ impl Pddb {
 
 impl Drop for Pddb {
     fn drop(&mut self) {
+        let watch_ids: Vec::<u32> = self.watches.lock().unwrap().keys().copied().collect();
+        for id in watch_ids {
+            self.unwatch(id).ok();
+        }
         if let Some(cb_sid) = self.cb.take() {
             let handle = self.cb_handle.take().unwrap(); // we guarantee this is always set when cb is set
             let cid = xous::connect(cb_sid).unwrap();
@@ -717,3 +1182,19 @@ impl Drop for Pddb {
         }
     }
 }
+
+/// Checks the names of a key and packs them into a metadata request
+fn key_meta_request(dict_name: &str, key_name: &str, basis_name: Option<&str>) -> Result<PddbKeyMetaIpc> {
+    if key_name.len() > (KEY_NAME_LEN - 1) {
+        return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+    }
+    if dict_name.len() > (DICT_NAME_LEN - 1) {
+        return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+    }
+    if let Some(bname) = basis_name {
+        if bname.len() > BASIS_NAME_LEN - 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+        }
+    }
+    Ok(PddbKeyMetaIpc::new(basis_name, dict_name, key_name, 0, [0; 4]))
+}
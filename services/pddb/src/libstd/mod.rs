@@ -3,6 +3,7 @@ mod utils;
 
 use crate::backend::BasisCache;
 use crate::backend::PddbOs;
+use crate::backend::{KEY_META_KEY, QUOTA_DICT, TX_INTENT_DICT};
 use crate::FileHandle;
 
 use senres::{Senres, SenresMut};
@@ -335,6 +336,9 @@ pub(crate) fn open_key(
             log::error!("no key was specified");
             crate::PddbRetcode::AccessDenied
         })?;
+    if requested_key == KEY_META_KEY || requested_dict == QUOTA_DICT || requested_dict == TX_INTENT_DICT {
+        return Err(crate::PddbRetcode::AccessDenied);
+    }
 
     let mut writer = backing
         .writer(*b"KyOR")
@@ -404,7 +408,11 @@ pub(crate) fn open_key(
                         bname.unwrap_or("internal_error"),
                         e,
                     );
-                    crate::PddbRetcode::InternalError
+                    if crate::is_quota_exceeded(&e) {
+                        crate::PddbRetcode::QuotaExceeded
+                    } else {
+                        crate::PddbRetcode::InternalError
+                    }
                 })?;
             len = 0;
         } else if create_new {
@@ -437,7 +445,11 @@ pub(crate) fn open_key(
                         bname.unwrap_or("internal_error"),
                         e,
                     );
-                    crate::PddbRetcode::InternalError
+                    if crate::is_quota_exceeded(&e) {
+                        crate::PddbRetcode::QuotaExceeded
+                    } else {
+                        crate::PddbRetcode::InternalError
+                    }
                 })?;
         }
 
@@ -564,6 +576,9 @@ pub(crate) fn delete_key(
     let (dict, key) = path
         .rsplit_once(std::path::MAIN_SEPARATOR)
         .ok_or(crate::PddbRetcode::AccessDenied)?;
+    if key == KEY_META_KEY || dict == QUOTA_DICT || dict == TX_INTENT_DICT {
+        return Err(crate::PddbRetcode::AccessDenied);
+    }
 
     // Perform the actual removal
     basis_cache
@@ -632,6 +647,9 @@ pub(crate) fn write_key(
             .map_err(|e| match e.kind() {
                 std::io::ErrorKind::NotFound => retcode = crate::PddbRetcode::BasisLost,
                 std::io::ErrorKind::UnexpectedEof => retcode = crate::PddbRetcode::UnexpectedEof,
+                std::io::ErrorKind::OutOfMemory if crate::is_quota_exceeded(&e) => {
+                    retcode = crate::PddbRetcode::QuotaExceeded
+                }
                 std::io::ErrorKind::OutOfMemory => retcode = crate::PddbRetcode::DiskFull,
                 _ => retcode = crate::PddbRetcode::InternalError,
             })
@@ -840,6 +858,9 @@ pub(crate) fn delete_dict(
         utils::split_basis_and_dict(path, || basis_cache.basis_latest().map(|m| m.to_owned()))
             .or(Err(crate::PddbRetcode::InternalError))?;
     let dict = dict.ok_or(crate::PddbRetcode::InternalError)?;
+    if dict == QUOTA_DICT || dict == TX_INTENT_DICT {
+        return Err(crate::PddbRetcode::AccessDenied);
+    }
 
     if let Some(key_list) = basis_cache
         .key_list(pddb_os, &dict, bname.as_deref())
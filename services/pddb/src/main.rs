@@ -387,7 +387,7 @@ use xous_ipc::Buffer;
 use core::cell::RefCell;
 use std::rc::Rc;
 use std::thread;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::ErrorKind;
 use core::fmt::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -416,6 +416,74 @@ struct TokenRecord {
     pub conn: Option<xous::CID>, // callback connection, if one was specified
 }
 
+/// A transaction that is being staged by a client, prior to commit
+struct TxRecord {
+    /// only the process that opened the transaction may stage into it, commit it, or abort it
+    pub pid: Option<xous::PID>,
+    /// the `owner` nonce of the process that opened the transaction. PIDs are reused, so the PID
+    /// alone doesn't tell the process that opened the transaction from a later one.
+    pub owner: u32,
+    /// the basis is resolved when the transaction is opened, so that unlocking another basis
+    /// while the transaction is staged doesn't redirect the commit
+    pub basis: String,
+    pub ops: Vec<TxOp>,
+    /// bytes of data and names staged so far, which count against `PDDB_TX_MAX_BYTES`
+    pub bytes: usize,
+}
+impl TxRecord {
+    fn owned_by(&self, pid: Option<xous::PID>, owner: u32) -> bool {
+        self.pid == pid && self.owner == owner
+    }
+}
+
+/// A client's registration for change notifications on a dictionary
+struct WatchRecord {
+    pub pid: Option<xous::PID>,
+    /// `None` watches the dictionary in every basis
+    pub basis: Option<String>,
+    pub dict: String,
+    pub prefix: String,
+    pub conn: xous::CID,
+}
+impl WatchRecord {
+    fn matches(&self, change: &KeyChange) -> bool {
+        if let Some(basis) = &self.basis {
+            if *basis != change.basis {
+                return false;
+            }
+        }
+        match change.kind {
+            PddbWatchEventKind::BasisLocked | PddbWatchEventKind::BasisUnlocked => true,
+            _ => change.dict == self.dict
+                && change.key.as_ref().map(|k| k.starts_with(&self.prefix)).unwrap_or(true),
+        }
+    }
+}
+
+/// An export blob that is staged in the server while it is moved to or from a client
+struct TransferRecord {
+    /// only the process that staged the blob may access it
+    pub pid: Option<xous::PID>,
+    pub data: Vec::<u8>,
+}
+impl Drop for TransferRecord {
+    fn drop(&mut self) {
+        // the blob is encrypted, but there's no reason to leave it lying around in the heap
+        self.data.iter_mut().for_each(|b| *b = 0);
+    }
+}
+
+/// A compaction pass that is being run a step at a time, in between other requests
+struct CompactionRecord {
+    /// the `Compact` request; its response is deferred until the pass is done
+    pub msg: xous::MessageEnvelope,
+    pub jobs: VecDeque::<CompactJob>,
+    pub total: usize,
+    pub before: PddbFragStats,
+    pub keys_moved: usize,
+    pub pages_freed: usize,
+}
+
 struct FileHandle {
     pub dict: String,
     pub key: String,
@@ -478,6 +546,15 @@ fn wrapped_main() -> ! {
     // Process-indexed map of file descriptors to token records
     let mut fd_mapping = HashMap::<Option<xous::PID>, Vec<Option<FileHandle>>>::new();
 
+    // transactions that are being staged, indexed by a transaction ID
+    let mut tx_dict = HashMap::<u32, TxRecord>::new();
+    // change notification registrations, by ID
+    let mut watch_dict = HashMap::<u32, WatchRecord>::new();
+    // basis export blobs in transit; each process may have at most one
+    let mut transfers = HashMap::<ApiToken, TransferRecord>::new();
+    // compaction pass in progress, if any
+    let mut compaction: Option<CompactionRecord> = None;
+
     // mount poller thread
     let is_mounted = Arc::new(AtomicBool::new(false));
     let _ = thread::spawn({
@@ -598,6 +675,11 @@ fn wrapped_main() -> ! {
     let mut susres = susres::Susres::new(Some(susres::SuspendOrder::Early), &xns,
         Opcode::SuspendResume as u32, my_cid).expect("couldn't create suspend/resume object");
     loop {
+        // report the changes made while handling the previous message
+        if !watch_dict.is_empty() {
+            notify_watchers(basis_cache.take_changes(), &mut watch_dict, &token_dict);
+        }
+        basis_cache.track_changes(!watch_dict.is_empty());
         let mut msg = xous::receive_message(pddb_sid).unwrap();
         // log::error!("got msg: {:x?}", msg);
         match FromPrimitive::from_usize(msg.body.id() & 0xffff).unwrap_or(Opcode::InvalidOpcode) {
@@ -796,6 +878,7 @@ fn wrapped_main() -> ! {
                                     mgmt.policy.unwrap_or(BasisRetentionPolicy::Persist)
                                 ) {
                                     basis_cache.basis_add(basis);
+                                    tx_recover_basis(&mut pddb_os, &mut basis_cache, mgmt.name.as_str().unwrap());
                                     finished = true;
                                     log::info!("{}PDDB.UNLOCKOK,{},{}", xous::BOOKEND_START, mgmt.name.as_str().unwrap(), xous::BOOKEND_END);
                                     mgmt.code = PddbRequestCode::NoErr;
@@ -837,6 +920,7 @@ fn wrapped_main() -> ! {
                 notify_of_disconnect(&mut pddb_os, &token_dict, &mut basis_cache);
                 match mgmt.code {
                     PddbRequestCode::Close => {
+                        tx_dict.retain(|_, tx| tx.basis != mgmt.name.as_str().unwrap());
                         match basis_cache.basis_unmount(&mut pddb_os, mgmt.name.as_str().expect("name is not valid utf-8")) {
                             Ok(_) => mgmt.code = PddbRequestCode::NoErr,
                             Err(e) => match e.kind() {
@@ -857,6 +941,7 @@ fn wrapped_main() -> ! {
                 notify_of_disconnect(&mut pddb_os, &token_dict, &mut basis_cache);
                 match mgmt.code {
                     PddbRequestCode::Delete => {
+                        tx_dict.retain(|_, tx| tx.basis != mgmt.name.as_str().unwrap());
                         match basis_cache.basis_delete(&mut pddb_os, mgmt.name.as_str().expect("name is not valid utf-8")) {
                             Ok(_) => mgmt.code = PddbRequestCode::NoErr,
                             Err(e) => match e.kind() {
@@ -883,6 +968,11 @@ fn wrapped_main() -> ! {
                     let dict = req.dict.as_str().expect("dict utf-8 decode error");
                     let key = req.key.as_str().expect("key utf-8 decode error");
                     log::debug!("get: {:?} {}", bname, key);
+                    if key == KEY_META_KEY || dict == QUOTA_DICT || dict == TX_INTENT_DICT {
+                        req.result = PddbRequestCode::AccessDenied;
+                        buffer.replace(req).unwrap();
+                        break;
+                    }
                     if basis_cache.dict_attributes(&mut pddb_os, dict, bname).is_err() {
                         if req.create_dict {
                             match basis_cache.dict_add(&mut pddb_os, dict, bname) {
@@ -918,6 +1008,7 @@ fn wrapped_main() -> ! {
                                     log::error!("Couldn't allocate key: {:?}", e);
                                     match e.kind() {
                                         std::io::ErrorKind::NotFound => req.result = PddbRequestCode::NotMounted,
+                                        std::io::ErrorKind::OutOfMemory if is_quota_exceeded(&e) => req.result = PddbRequestCode::QuotaExceeded,
                                         std::io::ErrorKind::OutOfMemory => req.result = PddbRequestCode::NoFreeSpace,
                                         _ => req.result = PddbRequestCode::InternalError,
                                     }
@@ -962,7 +1053,7 @@ fn wrapped_main() -> ! {
                     // now check if we can safely disconnect and recycle our connection number.
                     // This is important because we can only have 32 outgoing connections...
                     if let Some(conn_to_remove) = rec.conn {
-                        let mut still_needs_cid = false;
+                        let mut still_needs_cid = watch_dict.values().any(|w| w.conn == conn_to_remove);
                         for r in token_dict.values() {
                             // check through the remaining dictionary values to see if they have a connection that is the same as our number
                             if let Some(existing_conn) = r.conn {
@@ -1009,6 +1100,11 @@ fn wrapped_main() -> ! {
                 };
                 let dict = req.dict.as_str().expect("dict utf-8 decode error");
                 let key = req.key.as_str().expect("key utf-8 decode error");
+                if key == KEY_META_KEY || dict == QUOTA_DICT || dict == TX_INTENT_DICT {
+                    req.result = PddbRequestCode::AccessDenied;
+                    buffer.replace(req).unwrap();
+                    continue;
+                }
                 match basis_cache.key_remove(&mut pddb_os, dict, key, bname, false) {
                     Ok(_) => {
                         let mut evict_list = Vec::<ApiToken>::new();
@@ -1067,6 +1163,11 @@ fn wrapped_main() -> ! {
                 };
                 let dict = req.dict.as_str().expect("dict utf-8 decode error");
                 log::debug!("attempting to remove dict {} basis {:?}", dict, bname);
+                if dict == QUOTA_DICT || dict == TX_INTENT_DICT {
+                    req.result = PddbRequestCode::AccessDenied;
+                    buffer.replace(req).unwrap();
+                    continue;
+                }
                 match basis_cache.dict_remove(&mut pddb_os, dict, bname, false) {
                     Ok(_) => {
                         let mut evict_list = Vec::<ApiToken>::new();
@@ -1216,6 +1317,433 @@ fn wrapped_main() -> ! {
                 }
                 buffer.replace(req).unwrap();
             }
+            Opcode::GetKeyMetadataAtIndex => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbKeyMetaIpc, _>().unwrap();
+                if let Some(token) = key_token {
+                    if req.token != token {
+                        req.code = PddbRequestCode::AccessDenied;
+                    } else {
+                        if req.index >= key_list.len() as u32 {
+                            req.code = PddbRequestCode::InternalError;
+                        } else {
+                            let key = key_list[req.index as usize].to_string();
+                            let bname = if req.basis_specified {
+                                Some(req.basis.as_str().unwrap())
+                            } else {
+                                None
+                            };
+                            let dict = req.dict.as_str().expect("dict utf-8 decode error");
+                            // a listing shouldn't fail just because one key's metadata can't be read
+                            let meta = basis_cache.key_metadata(&mut pddb_os, dict, &key, bname).unwrap_or_default();
+                            req.key = xous_ipc::String::<KEY_NAME_LEN>::from_str(&key);
+                            req.set_metadata(&meta);
+                            req.code = PddbRequestCode::NoErr;
+                            // the last index requested must be the highest one!
+                            if req.index == key_list.len() as u32 - 1 {
+                                log::debug!("last key, resetting state");
+                                key_token = None;
+                                key_list.clear();
+                            }
+                        }
+                    }
+                } else {
+                    log::debug!("multiple concurrent requests detected, returning error");
+                    req.code = PddbRequestCode::AccessDenied;
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::KeyMetadata => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbKeyMetaIpc, _>().unwrap();
+                let bname = if req.basis_specified {
+                    Some(req.basis.as_str().unwrap())
+                } else {
+                    None
+                };
+                let dict = req.dict.as_str().expect("dict utf-8 decode error");
+                let key = req.key.as_str().expect("key utf-8 decode error");
+                match basis_cache.key_metadata(&mut pddb_os, dict, key, bname) {
+                    Ok(meta) => {
+                        req.set_metadata(&meta);
+                        req.code = PddbRequestCode::NoErr;
+                    }
+                    Err(e) => match e.kind() {
+                        std::io::ErrorKind::NotFound => req.code = PddbRequestCode::NotFound,
+                        _ => req.code = PddbRequestCode::InternalError,
+                    }
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::SetKeyMetadata => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbKeyMetaIpc, _>().unwrap();
+                let meta = req.to_metadata();
+                let bname = if req.basis_specified {
+                    Some(req.basis.as_str().unwrap())
+                } else {
+                    None
+                };
+                let dict = req.dict.as_str().expect("dict utf-8 decode error");
+                let key = req.key.as_str().expect("key utf-8 decode error");
+                match basis_cache.key_metadata_set(&mut pddb_os, dict, key, &meta, bname) {
+                    Ok(_) => req.code = PddbRequestCode::NoErr,
+                    Err(e) => match e.kind() {
+                        std::io::ErrorKind::NotFound => req.code = PddbRequestCode::NotFound,
+                        std::io::ErrorKind::OutOfMemory => req.code = PddbRequestCode::NoFreeSpace,
+                        _ => req.code = PddbRequestCode::InternalError,
+                    }
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::ListKeyPage => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbKeyPageRequest, _>().unwrap();
+                let query = req.to_query();
+                let limit = (req.limit as usize).max(1).min(KEY_PAGE_LEN);
+                let bname = if req.basis_specified {
+                    Some(req.basis.as_str().unwrap())
+                } else {
+                    None
+                };
+                let dict = req.dict.as_str().expect("dict utf-8 decode error");
+                let after = if req.after_specified {
+                    Some(req.after.as_str().expect("key utf-8 decode error"))
+                } else {
+                    None
+                };
+                // ask for one extra name, to find out if there is another page after this one
+                match basis_cache.key_range(&mut pddb_os, dict, bname, &query, after, limit + 1) {
+                    Ok(list) => {
+                        req.more = list.len() > limit;
+                        req.count = list.len().min(limit) as u32;
+                        for (name, dest) in list.iter().zip(req.names.iter_mut()) {
+                            *dest = xous_ipc::String::<KEY_NAME_LEN>::from_str(name);
+                        }
+                        req.code = PddbRequestCode::NoErr;
+                    }
+                    Err(e) => match e.kind() {
+                        std::io::ErrorKind::NotFound => req.code = PddbRequestCode::NotFound,
+                        _ => req.code = PddbRequestCode::InternalError,
+                    }
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::Watch => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbWatchRequest, _>().unwrap();
+                if watch_dict.contains_key(&req.id) {
+                    req.code = PddbRequestCode::AccessDenied;
+                } else {
+                    match xous::connect(xous::SID::from_array(req.cb_sid)) {
+                        Ok(conn) => {
+                            watch_dict.insert(req.id, WatchRecord {
+                                pid: msg.sender.pid(),
+                                basis: if req.basis_specified { Some(req.basis.as_str().unwrap().to_string()) } else { None },
+                                dict: req.dict.as_str().expect("dict utf-8 decode error").to_string(),
+                                prefix: req.prefix.as_str().expect("key utf-8 decode error").to_string(),
+                                conn,
+                            });
+                            req.code = PddbRequestCode::NoErr;
+                        }
+                        Err(e) => {
+                            log::error!("couldn't connect to watch callback server: {:?}", e);
+                            req.code = PddbRequestCode::InternalError;
+                        }
+                    }
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::Unwatch => msg_blocking_scalar_unpack!(msg, id, _, _, _, {
+                let id = id as u32;
+                let owned = watch_dict.get(&id).map(|w| w.pid == msg.sender.pid()).unwrap_or(false);
+                if owned {
+                    remove_watch(id, &mut watch_dict, &token_dict);
+                    xous::return_scalar(msg.sender, PddbRetcode::Ok as usize).expect("couldn't ack Unwatch");
+                } else {
+                    xous::return_scalar(msg.sender, PddbRetcode::AccessDenied as usize).expect("couldn't ack Unwatch");
+                }
+            }),
+            Opcode::ExportBasis => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbBasisTransferRequest, _>().unwrap();
+                transfers.retain(|_, t| t.pid != msg.sender.pid());
+                let result = basis_cache.basis_export(&mut pddb_os, req.name.as_str().expect("name is not valid utf-8"))
+                    .and_then(|export| {
+                        let mut salt = [0u8; 16];
+                        pddb_os.trng_slice(&mut salt);
+                        let mut nonce = [0u8; 12];
+                        pddb_os.trng_slice(&mut nonce);
+                        basis_export_seal(&export, req.passphrase.as_str().expect("passphrase is not valid utf-8"), salt, nonce)
+                    });
+                req.passphrase.volatile_clear();
+                match result {
+                    Ok(blob) => {
+                        log::info!("exported basis {}: {} bytes", req.name.as_str().unwrap(), blob.len());
+                        let token: ApiToken = [pddb_os.trng_u32(), pddb_os.trng_u32(), pddb_os.trng_u32()];
+                        req.token = token;
+                        req.len = blob.len() as u64;
+                        transfers.insert(token, TransferRecord { pid: msg.sender.pid(), data: blob });
+                        req.code = PddbRequestCode::NoErr;
+                    }
+                    Err(e) => {
+                        log::error!("couldn't export basis {}: {:?}", req.name.as_str().unwrap(), e);
+                        req.code = match e.kind() {
+                            ErrorKind::NotFound => PddbRequestCode::NotFound,
+                            ErrorKind::OutOfMemory => PddbRequestCode::NoFreeSpace,
+                            _ => PddbRequestCode::InternalError,
+                        }
+                    }
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::ImportBasisStage => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbBasisTransferRequest, _>().unwrap();
+                let mut data = Vec::<u8>::new();
+                if req.len > BASIS_EXPORT_MAX_LEN as u64 || data.try_reserve_exact(req.len as usize).is_err() {
+                    req.code = PddbRequestCode::NoFreeSpace;
+                } else {
+                    // a process only gets to stage one transfer at a time
+                    transfers.retain(|_, t| t.pid != msg.sender.pid());
+                    data.resize(req.len as usize, 0);
+                    let token: ApiToken = [pddb_os.trng_u32(), pddb_os.trng_u32(), pddb_os.trng_u32()];
+                    transfers.insert(token, TransferRecord { pid: msg.sender.pid(), data });
+                    req.token = token;
+                    req.code = PddbRequestCode::NoErr;
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::TransferRead => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let pbuf = PddbBuf::from_slice_mut(buffer.as_mut());
+                match transfers.get(&pbuf.token) {
+                    Some(t) if t.pid == msg.sender.pid() => {
+                        let start = (pbuf.position as usize).min(t.data.len());
+                        let end = (start + (pbuf.len as usize).min(PDDB_BUF_DATA_LEN)).min(t.data.len());
+                        pbuf.data[..end - start].copy_from_slice(&t.data[start..end]);
+                        pbuf.len = (end - start) as u16;
+                        pbuf.retcode = PddbRetcode::Ok;
+                    }
+                    _ => pbuf.retcode = PddbRetcode::AccessDenied,
+                }
+            }
+            Opcode::TransferWrite => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let pbuf = PddbBuf::from_slice_mut(buffer.as_mut());
+                match transfers.get_mut(&pbuf.token) {
+                    Some(t) if t.pid == msg.sender.pid() => {
+                        let start = pbuf.position as usize;
+                        let len = (pbuf.len as usize).min(PDDB_BUF_DATA_LEN);
+                        if start + len <= t.data.len() {
+                            t.data[start..start + len].copy_from_slice(&pbuf.data[..len]);
+                            pbuf.retcode = PddbRetcode::Ok;
+                        } else {
+                            pbuf.retcode = PddbRetcode::UnexpectedEof;
+                        }
+                    }
+                    _ => pbuf.retcode = PddbRetcode::AccessDenied,
+                }
+            }
+            Opcode::ImportBasis => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbBasisTransferRequest, _>().unwrap();
+                let name = req.name.as_str().expect("name is not valid utf-8").to_string();
+                let export = match transfers.get(&req.token) {
+                    Some(t) if t.pid == msg.sender.pid() =>
+                        Some(basis_export_open(&t.data, req.passphrase.as_str().expect("passphrase is not valid utf-8"))),
+                    _ => None,
+                };
+                req.passphrase.volatile_clear();
+                transfers.remove(&req.token);
+                req.code = match export {
+                    None => PddbRequestCode::AccessDenied,
+                    // refuse to shadow a basis that is open right now
+                    Some(_) if basis_cache.basis_list().iter().any(|b| *b == name) => PddbRequestCode::AccessDenied,
+                    Some(Err(e)) => {
+                        log::warn!("couldn't open basis export: {:?}", e);
+                        match e.kind() {
+                            ErrorKind::PermissionDenied => PddbRequestCode::AccessDenied,
+                            ErrorKind::InvalidData => PddbRequestCode::InvalidData,
+                            _ => PddbRequestCode::InternalError,
+                        }
+                    }
+                    Some(Ok(export)) => {
+                        // the imported basis gets a new password, and thus new keys, on this device
+                        let request = BasisRequestPassword {
+                            db_name: req.name,
+                            plaintext_pw: None,
+                        };
+                        let mut buf = Buffer::into_buf(request).unwrap();
+                        buf.lend_mut(pw_cid, PwManagerOpcode::RequestPassword.to_u32().unwrap()).unwrap();
+                        let ret = buf.to_original::<BasisRequestPassword, _>().unwrap();
+                        if let Some(pw) = ret.plaintext_pw {
+                            let pw = pw.as_str().expect("password was not valid utf-8");
+                            let created = basis_cache.basis_create(&mut pddb_os, &name, pw);
+                            let created_ok = created.is_ok();
+                            let result = created
+                                .and_then(|_| basis_cache.basis_unlock(&mut pddb_os, &name, pw, BasisRetentionPolicy::Persist)
+                                    .ok_or(std::io::Error::new(ErrorKind::Other, "couldn't mount newly created basis")))
+                                .and_then(|basis| {
+                                    basis_cache.basis_add(basis);
+                                    basis_cache.basis_import(&mut pddb_os, &export, &name)
+                                });
+                            match result {
+                                Ok(_) => {
+                                    log::info!("{}PDDB.IMPORTOK,{},{}", xous::BOOKEND_START, name, xous::BOOKEND_END);
+                                    PddbRequestCode::NoErr
+                                }
+                                Err(e) => {
+                                    log::error!("couldn't import basis {}: {:?}", name, e);
+                                    if created_ok {
+                                        // don't leave a partial copy of the basis behind on disk
+                                        if !basis_cache.basis_list().iter().any(|b| *b == name) {
+                                            if let Some(basis) = basis_cache.basis_unlock(&mut pddb_os, &name, pw, BasisRetentionPolicy::Persist) {
+                                                basis_cache.basis_add(basis);
+                                            }
+                                        }
+                                        if let Err(e) = basis_cache.basis_discard(&mut pddb_os, &name) {
+                                            log::error!("couldn't remove partially imported basis {}: {:?}", name, e);
+                                        }
+                                    }
+                                    match e.kind() {
+                                        ErrorKind::OutOfMemory => PddbRequestCode::NoFreeSpace,
+                                        _ => PddbRequestCode::InternalError,
+                                    }
+                                }
+                            }
+                        } else {
+                            PddbRequestCode::InternalError
+                        }
+                    }
+                };
+                buffer.replace(req).unwrap();
+            }
+            Opcode::TransferDrop => msg_blocking_scalar_unpack!(msg, t0, t1, t2, _, {
+                let token: ApiToken = [t0 as u32, t1 as u32, t2 as u32];
+                if transfers.get(&token).map(|t| t.pid == msg.sender.pid()).unwrap_or(false) {
+                    transfers.remove(&token);
+                }
+                xous::return_scalar(msg.sender, 1).expect("couldn't ack TransferDrop");
+            }),
+            Opcode::Compact => {
+                if compaction.is_some() || basis_cache.basis_count() == 0 {
+                    let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                    let mut req = buffer.to_original::<PddbCompactRequest, _>().unwrap();
+                    req.code = if compaction.is_some() { PddbRequestCode::AccessDenied } else { PddbRequestCode::NotMounted };
+                    buffer.replace(req).unwrap();
+                    continue;
+                }
+                let before = basis_cache.frag_stats(&mut pddb_os);
+                let jobs: VecDeque::<CompactJob> = basis_cache.compact_plan(&mut pddb_os).into();
+                log::info!("compacting PDDB in {} steps: {:?}", jobs.len(), before);
+                modals.start_progress(t!("pddb.compact", xous::LANG), 0, jobs.len() as u32, 0)
+                    .expect("couldn't raise progress bar");
+                compaction = Some(CompactionRecord {
+                    msg,
+                    total: jobs.len(),
+                    jobs,
+                    before,
+                    keys_moved: 0,
+                    pages_freed: 0,
+                });
+                // the pass is run one job per message, so that requests (including suspend) can be served in between
+                send_message(my_cid,
+                    Message::new_scalar(Opcode::CompactStep.to_usize().unwrap(), 0, 0, 0, 0)
+                ).expect("couldn't start compaction");
+            }
+            Opcode::CompactStep => {
+                if let Some(mut record) = compaction.take() {
+                    let result = match record.jobs.pop_front() {
+                        Some(job) => basis_cache.compact_step(&mut pddb_os, &job),
+                        None => Ok((0, 0)),
+                    };
+                    if let Ok((moved, freed)) = result {
+                        record.keys_moved += moved;
+                        record.pages_freed += freed;
+                    }
+                    if result.is_ok() && record.jobs.len() > 0 {
+                        modals.update_progress((record.total - record.jobs.len()) as u32).expect("couldn't update progress bar");
+                        compaction = Some(record);
+                        send_message(my_cid,
+                            Message::new_scalar(Opcode::CompactStep.to_usize().unwrap(), 0, 0, 0, 0)
+                        ).expect("couldn't continue compaction");
+                    } else {
+                        modals.update_progress(record.total as u32).expect("couldn't update progress bar");
+                        modals.finish_progress().expect("couldn't dismiss progress bar");
+                        let after = basis_cache.frag_stats(&mut pddb_os);
+                        log::info!("compaction done, {} keys moved, {} pages freed: {:?}", record.keys_moved, record.pages_freed, after);
+                        let mut buffer = unsafe { Buffer::from_memory_message_mut(record.msg.body.memory_message_mut().unwrap()) };
+                        let mut req = buffer.to_original::<PddbCompactRequest, _>().unwrap();
+                        req.before = record.before;
+                        req.after = after;
+                        req.keys_moved = record.keys_moved as u32;
+                        req.pages_freed = record.pages_freed as u32;
+                        req.code = match result {
+                            Ok(_) => PddbRequestCode::NoErr,
+                            Err(e) => {
+                                log::error!("compaction stopped: {:?}", e);
+                                if e.kind() == ErrorKind::OutOfMemory {
+                                    PddbRequestCode::NoFreeSpace
+                                } else {
+                                    PddbRequestCode::InternalError
+                                }
+                            }
+                        };
+                        buffer.replace(req).unwrap();
+                        // the response goes out when `record.msg` is dropped
+                    }
+                }
+            }
+            Opcode::SetQuota => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbQuotaRequest, _>().unwrap();
+                let bname = if req.basis_specified {
+                    Some(req.basis.as_str().unwrap())
+                } else {
+                    None
+                };
+                let dict = if req.dict_specified {
+                    Some(req.dict.as_str().expect("dict utf-8 decode error"))
+                } else {
+                    None
+                };
+                let limit = if req.limit_specified { Some(req.limit) } else { None };
+                match basis_cache.quota_set(&mut pddb_os, dict, limit, bname) {
+                    Ok(_) => req.code = PddbRequestCode::NoErr,
+                    Err(e) => match e.kind() {
+                        std::io::ErrorKind::NotFound => req.code = PddbRequestCode::NotMounted,
+                        std::io::ErrorKind::PermissionDenied => req.code = PddbRequestCode::AccessDenied,
+                        std::io::ErrorKind::OutOfMemory => req.code = PddbRequestCode::NoFreeSpace,
+                        _ => req.code = PddbRequestCode::InternalError,
+                    }
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::QuotaUsage => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbQuotaRequest, _>().unwrap();
+                let bname = if req.basis_specified {
+                    Some(req.basis.as_str().unwrap())
+                } else {
+                    None
+                };
+                let dict = if req.dict_specified {
+                    Some(req.dict.as_str().expect("dict utf-8 decode error"))
+                } else {
+                    None
+                };
+                match basis_cache.quota_usage(&mut pddb_os, dict, bname) {
+                    Ok((used, limit)) => {
+                        req.used = used;
+                        req.limit_specified = limit.is_some();
+                        req.limit = limit.unwrap_or(0);
+                        req.code = PddbRequestCode::NoErr;
+                    }
+                    Err(_) => req.code = PddbRequestCode::NotMounted,
+                }
+                buffer.replace(req).unwrap();
+            }
             Opcode::ListKeyStd => {
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
@@ -1364,6 +1892,7 @@ fn wrapped_main() -> ! {
                             Err(e) => match e.kind() {
                                 std::io::ErrorKind::NotFound => pbuf.retcode = PddbRetcode::BasisLost,
                                 std::io::ErrorKind::UnexpectedEof => pbuf.retcode = PddbRetcode::UnexpectedEof,
+                                std::io::ErrorKind::OutOfMemory if is_quota_exceeded(&e) => pbuf.retcode = PddbRetcode::QuotaExceeded,
                                 std::io::ErrorKind::OutOfMemory => pbuf.retcode = PddbRetcode::DiskFull,
                                 _ => pbuf.retcode = PddbRetcode::InternalError,
                             }
@@ -1418,6 +1947,137 @@ fn wrapped_main() -> ! {
                 pddb_os.fast_space_flush();
                 xous::return_scalar(msg.sender, 1).ok();
             }
+            Opcode::SetTimeReference => xous::msg_scalar_unpack!(msg, utc_lo_ms, utc_hi_ms, _, _, {
+                pddb_os.set_time_reference((utc_hi_ms as u64) << 32 | (utc_lo_ms as u64));
+            }),
+            Opcode::TxBegin => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbTxRequest, _>().unwrap();
+                let basis = if req.basis_specified {
+                    basis_cache.basis_list().into_iter().find(|b| b == req.basis.as_str().unwrap())
+                } else {
+                    basis_cache.basis_latest().map(|b| b.to_string())
+                };
+                reclaim_orphaned_transactions(&mut tx_dict, msg.sender.pid(), req.owner);
+                if tx_dict.values().filter(|tx| tx.pid == msg.sender.pid()).count() >= PDDB_TX_MAX_PROCESS_OPEN {
+                    req.result = PddbRequestCode::TxLimit;
+                } else if let Some(basis) = basis {
+                    let mut id = pddb_os.trng_u32();
+                    while id == 0 || tx_dict.contains_key(&id) {
+                        id = pddb_os.trng_u32();
+                    }
+                    tx_dict.insert(id, TxRecord { pid: msg.sender.pid(), owner: req.owner, basis, ops: Vec::new(), bytes: 0 });
+                    req.id = id;
+                    req.result = PddbRequestCode::NoErr;
+                } else if req.basis_specified {
+                    req.result = PddbRequestCode::NotFound;
+                } else {
+                    req.result = PddbRequestCode::NotMounted;
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::TxStage => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbTxRequest, _>().unwrap();
+                let dict = req.dict.as_str().expect("dict utf-8 decode error").to_string();
+                let key = req.key.as_str().expect("key utf-8 decode error").to_string();
+                let data = &req.data[..(req.len as usize).min(PDDB_TX_CHUNK_LEN)];
+                let (ops, bytes) = match req.op {
+                    PddbTxOp::Write => (1, dict.len() + key.len() + data.len()),
+                    PddbTxOp::WriteAppend => (0, data.len()),
+                    PddbTxOp::Delete => (1, dict.len() + key.len()),
+                    PddbTxOp::None => (0, 0),
+                };
+                let result = match tx_dict.get(&req.id) {
+                    Some(tx) if tx.owned_by(msg.sender.pid(), req.owner) => {
+                        if dict == TX_INTENT_DICT || dict == QUOTA_DICT || key == KEY_META_KEY {
+                            PddbRequestCode::AccessDenied
+                        } else if !tx_stage_fits(&tx_dict, req.id, ops, bytes) {
+                            // the transaction can't be completed as the client intended, so it's discarded
+                            // rather than left for a commit of whatever part of it was staged
+                            log::warn!("Transaction {:x} reached the staging limits, discarding it", req.id);
+                            tx_dict.remove(&req.id);
+                            PddbRequestCode::TxLimit
+                        } else {
+                            let tx = tx_dict.get_mut(&req.id).unwrap();
+                            tx.bytes += bytes;
+                            match req.op {
+                                PddbTxOp::Write => {
+                                    tx.ops.push(TxOp::Write { dict, key, data: data.to_vec() });
+                                    PddbRequestCode::NoErr
+                                }
+                                PddbTxOp::WriteAppend => match tx.ops.last_mut() {
+                                    Some(TxOp::Write { dict: staged_dict, key: staged_key, data: staged })
+                                    if *staged_dict == dict && *staged_key == key => {
+                                        staged.extend_from_slice(data);
+                                        PddbRequestCode::NoErr
+                                    }
+                                    _ => PddbRequestCode::InternalError,
+                                },
+                                PddbTxOp::Delete => {
+                                    tx.ops.push(TxOp::Delete { dict, key });
+                                    PddbRequestCode::NoErr
+                                }
+                                PddbTxOp::None => PddbRequestCode::InternalError,
+                            }
+                        }
+                    }
+                    Some(_) => PddbRequestCode::AccessDenied,
+                    None => PddbRequestCode::NotFound,
+                };
+                req.result = result;
+                buffer.replace(req).unwrap();
+            }
+            Opcode::TxCommit => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbTxRequest, _>().unwrap();
+                req.result = match tx_dict.get(&req.id) {
+                    Some(tx) if tx.owned_by(msg.sender.pid(), req.owner) => {
+                        let tx = tx_dict.remove(&req.id).unwrap();
+                        match basis_cache.tx_commit(&mut pddb_os, &tx.ops, Some(&tx.basis)) {
+                            Ok(_) => {
+                                // tokens to deleted keys are no longer valid
+                                for op in tx.ops.iter() {
+                                    if let TxOp::Delete { dict, key } = op {
+                                        token_dict.retain(|_, rec|
+                                            !(rec.dict == *dict && rec.key == *key
+                                            && (rec.basis.is_none() || rec.basis.as_deref() == Some(tx.basis.as_str())))
+                                        );
+                                    }
+                                }
+                                PddbRequestCode::NoErr
+                            }
+                            Err(e) => {
+                                log::warn!("Transaction commit failed: {:?}", e);
+                                match e.kind() {
+                                    ErrorKind::NotFound => PddbRequestCode::NotFound,
+                                    ErrorKind::OutOfMemory if is_quota_exceeded(&e) => PddbRequestCode::QuotaExceeded,
+                                    ErrorKind::OutOfMemory => PddbRequestCode::NoFreeSpace,
+                                    ErrorKind::PermissionDenied => PddbRequestCode::AccessDenied,
+                                    ErrorKind::Interrupted => PddbRequestCode::Interrupted,
+                                    _ => PddbRequestCode::InternalError,
+                                }
+                            }
+                        }
+                    }
+                    Some(_) => PddbRequestCode::AccessDenied,
+                    None => PddbRequestCode::NotFound,
+                };
+                buffer.replace(req).unwrap();
+            }
+            Opcode::TxAbort => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbTxRequest, _>().unwrap();
+                req.result = match tx_dict.get(&req.id) {
+                    Some(tx) if tx.owned_by(msg.sender.pid(), req.owner) => {
+                        tx_dict.remove(&req.id);
+                        PddbRequestCode::NoErr
+                    }
+                    Some(_) => PddbRequestCode::AccessDenied,
+                    None => PddbRequestCode::NotFound,
+                };
+                buffer.replace(req).unwrap();
+            }
             Opcode::ResetDontAskInit => {
                 pddb_os.reset_dont_ask_init();
                 xous::return_scalar(msg.sender, 1).ok();
@@ -1440,9 +2100,11 @@ fn wrapped_main() -> ! {
                     DebugRequest::Remount => {
                         log::info!("attempting remount");
                         basis_cache = BasisCache::new(); // this effectively erases the PDDB from memory
+                        tx_dict.clear();
                         if let Some(sys_basis) = pddb_os.pddb_mount() {
                             log::info!("remount successful");
                             basis_cache.basis_add(sys_basis);
+                            tx_recover_basis(&mut pddb_os, &mut basis_cache, PDDB_DEFAULT_SYSTEM_BASIS);
                         } else {
                             log::info!("remount failed");
                         }
@@ -1534,6 +2196,7 @@ fn try_mount_or_format(modals: &modals::Modals, pddb_os: &mut PddbOs, basis_cach
         if let Some(sys_basis) = pddb_os.pddb_mount() {
             log::info!("PDDB mount operation finished successfully");
             basis_cache.basis_add(sys_basis);
+            tx_recover_basis(pddb_os, basis_cache, PDDB_DEFAULT_SYSTEM_BASIS);
             return true
         }
     }
@@ -1596,6 +2259,7 @@ fn try_mount_or_format(modals: &modals::Modals, pddb_os: &mut PddbOs, basis_cach
                 if let Some(sys_basis) = pddb_os.pddb_mount() {
                     log::info!("PDDB mount operation finished successfully");
                     basis_cache.basis_add(sys_basis);
+                    tx_recover_basis(pddb_os, basis_cache, PDDB_DEFAULT_SYSTEM_BASIS);
                     true
                 } else {
                     log::error!("Despite formatting, no PDDB was found!");
@@ -1621,6 +2285,7 @@ fn try_mount_or_format(modals: &modals::Modals, pddb_os: &mut PddbOs, basis_cach
             if let Some(sys_basis) = pddb_os.pddb_mount() {
                 log::info!("PDDB mount operation finished successfully");
                 basis_cache.basis_add(sys_basis);
+                tx_recover_basis(pddb_os, basis_cache, PDDB_DEFAULT_SYSTEM_BASIS);
                 true
             } else {
                 log::error!("Despite formatting, no PDDB was found!");
@@ -1727,6 +2392,21 @@ pub(crate) fn hw_testcase(pddb_os: &mut PddbOs) {
     pddb_os.dbg_dump(Some("manual".to_string()), None);
 }
 
+/// Rolls forward any transaction that was interrupted before it could be fully applied to a freshly mounted basis,
+/// and cleans up after a compaction pass that was interrupted while moving a key.
+fn tx_recover_basis(pddb_os: &mut PddbOs, basis_cache: &mut BasisCache, basis_name: &str) {
+    match basis_cache.compact_recover(pddb_os, basis_name) {
+        Ok(true) => log::info!("Cleaned up an interrupted key move in basis {}", basis_name),
+        Ok(false) => (),
+        Err(e) => log::error!("Couldn't clean up an interrupted key move in basis {}: {:?}", basis_name, e),
+    }
+    match basis_cache.tx_recover(pddb_os, basis_name) {
+        Ok(true) => log::info!("Recovered an interrupted transaction in basis {}", basis_name),
+        Ok(false) => (),
+        Err(e) => log::error!("Couldn't recover an interrupted transaction in basis {}: {:?}", basis_name, e),
+    }
+}
+
 fn notify_of_disconnect(pddb_os: &mut PddbOs, token_dict: &HashMap::<ApiToken, TokenRecord>, basis_cache: &mut BasisCache) {
     // 1. search to see if any of the active tokens are are in our token_dict
     // 2. notify them of the disconnect, if there is a callback set.
@@ -1759,6 +2439,78 @@ fn notify_of_disconnect(pddb_os: &mut PddbOs, token_dict: &HashMap::<ApiToken, T
     }
 }
 
+/// Delivers logged changes to the clients watching them. Events are sent as non-blocking messages, so a
+/// slow watcher can't stall the PDDB; watchers whose callback server has gone away are dropped.
+fn notify_watchers(changes: Vec::<KeyChange>, watch_dict: &mut HashMap::<u32, WatchRecord>, token_dict: &HashMap::<ApiToken, TokenRecord>) {
+    let mut gone = Vec::<u32>::new();
+    for change in changes.iter() {
+        for (&id, watch) in watch_dict.iter() {
+            if !watch.matches(change) || gone.contains(&id) {
+                continue;
+            }
+            let event = PddbWatchEventIpc {
+                id,
+                kind: change.kind,
+                basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(&change.basis),
+                dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(&change.dict),
+                key_specified: change.key.is_some(),
+                key: xous_ipc::String::<KEY_NAME_LEN>::from_str(change.key.as_deref().unwrap_or("")),
+            };
+            let buf = Buffer::into_buf(event).expect("couldn't allocate watch event");
+            match buf.send(watch.conn, pddb::CbOp::Watch.to_u32().unwrap()) {
+                Ok(_) => (),
+                Err(xous::Error::ServerNotFound) => gone.push(id),
+                Err(e) => log::warn!("Watch event on {}:{:?} was dropped: {:?}", &change.dict, &change.key, e),
+            }
+        }
+    }
+    for id in gone {
+        log::info!("Watcher {:x} has gone away, removing it", id);
+        remove_watch(id, watch_dict, token_dict);
+    }
+}
+
+/// Staged transactions only live in RAM, so drop any that were left behind by a process that exited
+/// without committing or aborting them. A PID is only reused once its process has exited, so
+/// transactions under `pid` with an owner nonce other than `owner` belong to an earlier process.
+/// Those of a process whose PID is never reused stay, but are bounded by the staging limits.
+fn reclaim_orphaned_transactions(tx_dict: &mut HashMap::<u32, TxRecord>, pid: Option<xous::PID>, owner: u32) {
+    tx_dict.retain(|id, tx| {
+        let orphaned = tx.pid == pid && tx.owner != owner;
+        if orphaned {
+            log::info!("dropping transaction {:x} left behind by an earlier process with PID {:?}", id, tx.pid);
+        }
+        !orphaned
+    });
+}
+
+/// Whether transaction `id` may stage `ops` more operations and `bytes` more bytes, without it or the
+/// process that owns it going over the staging limits
+fn tx_stage_fits(tx_dict: &HashMap::<u32, TxRecord>, id: u32, ops: usize, bytes: usize) -> bool {
+    let tx = match tx_dict.get(&id) {
+        Some(tx) => tx,
+        None => return false,
+    };
+    let (process_ops, process_bytes) = tx_dict.values()
+        .filter(|other| other.pid == tx.pid)
+        .fold((0, 0), |(ops, bytes), other| (ops + other.ops.len(), bytes + other.bytes));
+    tx.ops.len() + ops <= PDDB_TX_MAX_OPS
+        && tx.bytes + bytes <= PDDB_TX_MAX_BYTES
+        && process_ops + ops <= PDDB_TX_MAX_PROCESS_OPS
+        && process_bytes + bytes <= PDDB_TX_MAX_PROCESS_BYTES
+}
+
+/// Removes a watch registration, and recycles its callback connection if nothing else is using it.
+fn remove_watch(id: u32, watch_dict: &mut HashMap::<u32, WatchRecord>, token_dict: &HashMap::<ApiToken, TokenRecord>) {
+    if let Some(watch) = watch_dict.remove(&id) {
+        let still_needs_cid = watch_dict.values().any(|w| w.conn == watch.conn)
+            || token_dict.values().any(|r| r.conn == Some(watch.conn));
+        if !still_needs_cid {
+            unsafe{xous::disconnect(watch.conn).ok()};
+        }
+    }
+}
+
 pub(crate) fn heap_usage() -> usize {
     match xous::rsyscall(xous::SysCall::IncreaseHeap(0, xous::MemoryFlags::R)).expect("couldn't get heap size") {
         xous::Result::MemoryRange(m) => {
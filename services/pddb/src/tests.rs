@@ -3,7 +3,7 @@ use rand_chacha::rand_core::RngCore;
 use rand_chacha::rand_core::SeedableRng;
 use crate::*;
 use core::sync::atomic::{AtomicU64, Ordering};
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::io::Result;
 
 const UPPER_BOUND: usize = 9000;
@@ -388,6 +388,7 @@ pub(crate) fn list_all(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
         note: for faster stress-testing, we dialed the FSCB_PAGES to 4 and the FASTSPACE_PAGES to 1.
     - [done] basis search: create basis A, populate with general integrity. create basis B, add test entries.
         hide basis B, confirm original A; mount basis B, confirm B overlay.
+    - [done] transactions: commit across dicts, reject a bad commit whole, roll forward an interrupted commit on remount.
 */
 
 #[allow(dead_code)]
@@ -552,6 +553,36 @@ pub(crate) fn ci_tests(pddb_os: &mut PddbOs) -> Result<()> {
         }
         assert!(merge2_list.difference(&merge_list).count() == 0, "merged list is different from the original list after remount");
         list_all(pddb_os, &mut basis_cache);
+        basis_cache.basis_unmount(pddb_os, EXTRA_BASIS).unwrap();
+
+        log::info!("Doing transaction test");
+        transaction_test(pddb_os, &mut basis_cache)?;
+        pddb_os.dbg_dump(Some("txe".to_string()), None);
+
+        log::info!("Doing key range test");
+        key_range_test(pddb_os, &mut basis_cache)?;
+
+        log::info!("Doing key metadata test");
+        key_metadata_test(pddb_os, &mut basis_cache)?;
+        pddb_os.dbg_dump(Some("kme".to_string()), None);
+
+        log::info!("Doing change tracking test");
+        change_tracking_test(pddb_os, &mut basis_cache)?;
+
+        log::info!("Doing basis export test");
+        basis_export_test(pddb_os, &mut basis_cache)?;
+        pddb_os.dbg_dump(Some("exporte".to_string()), None);
+
+        log::info!("Doing compaction test");
+        compaction_test(pddb_os, &mut basis_cache)?;
+        pddb_os.dbg_dump(Some("compacte".to_string()), None);
+
+        log::info!("Doing compaction recovery test");
+        compaction_recovery_test(pddb_os, &mut basis_cache)?;
+
+        log::info!("Doing quota test");
+        quota_test(pddb_os, &mut basis_cache)?;
+        pddb_os.dbg_dump(Some("quotae".to_string()), None);
 
         log::info!("CI done");
 
@@ -569,6 +600,446 @@ pub(crate) fn ci_tests(pddb_os: &mut PddbOs) -> Result<()> {
     }
 }
 
+fn tx_read_key(hw: &mut PddbOs, basis_cache: &mut BasisCache, dict: &str, key: &str) -> Option<Vec::<u8>> {
+    match basis_cache.key_attributes(hw, dict, key, None) {
+        Ok(attr) if attr.flags.valid() => {
+            let mut data = vec![0u8; attr.len];
+            basis_cache.key_read(hw, dict, key, &mut data, None, None).expect("couldn't read key");
+            Some(data)
+        }
+        _ => None,
+    }
+}
+
+/// Exercises multi-key transactions on the system basis: a normal commit, a commit that fails
+/// validation, and recovery of a commit that was interrupted after its intent record was written.
+/// Leaves `basis_cache` holding a freshly remounted system basis.
+pub(crate) fn transaction_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    let large: Vec::<u8> = (0..6000).map(|i| i as u8).collect();
+    basis_cache.key_update(hw, "txtest1", "doomed", &[1, 2, 3], None, None, None, true)?;
+    basis_cache.key_update(hw, "txtest2", "shrink", &large, None, None, None, true)?;
+
+    // 1. a commit that spans dictionaries, mixes small and large keys, and deletes a key
+    let ops = vec![
+        TxOp::Write { dict: "txtest1".to_string(), key: "small".to_string(), data: vec![0xA5; 100] },
+        TxOp::Write { dict: "txtest2".to_string(), key: "large".to_string(), data: large.clone() },
+        TxOp::Write { dict: "txtest2".to_string(), key: "shrink".to_string(), data: vec![7; 10] },
+        TxOp::Delete { dict: "txtest1".to_string(), key: "doomed".to_string() },
+    ];
+    basis_cache.tx_commit(hw, &ops, None)?;
+    assert!(tx_read_key(hw, basis_cache, "txtest1", "small") == Some(vec![0xA5; 100]), "small key not committed");
+    assert!(tx_read_key(hw, basis_cache, "txtest2", "large") == Some(large.clone()), "large key not committed");
+    assert!(tx_read_key(hw, basis_cache, "txtest2", "shrink") == Some(vec![7; 10]), "overwritten key not replaced");
+    assert!(tx_read_key(hw, basis_cache, "txtest1", "doomed").is_none(), "deleted key still exists");
+    assert!(!basis_cache.dict_list(hw, None).contains(TX_INTENT_DICT), "intent record was not retired");
+
+    // 2. a commit that fails validation must not apply any of its operations
+    let ops = vec![
+        TxOp::Write { dict: "txtest1".to_string(), key: "small".to_string(), data: vec![0; 4] },
+        TxOp::Delete { dict: "txtest1".to_string(), key: "does not exist".to_string() },
+    ];
+    assert!(basis_cache.tx_commit(hw, &ops, None).is_err(), "commit with a bad delete should fail");
+    assert!(tx_read_key(hw, basis_cache, "txtest1", "small") == Some(vec![0xA5; 100]), "failed commit was partially applied");
+
+    // 3. simulate a power loss after the intent record was written and one operation was applied
+    let ops = vec![
+        TxOp::Write { dict: "txtest1".to_string(), key: "small".to_string(), data: vec![0x5A; 50] },
+        TxOp::Delete { dict: "txtest2".to_string(), key: "large".to_string() },
+        TxOp::Write { dict: "txtest3".to_string(), key: "new".to_string(), data: vec![3; 3000] },
+    ];
+    let record = tx_record_encode(&ops)?;
+    basis_cache.key_update(hw, TX_INTENT_DICT, TX_INTENT_KEY, &record, None, None, None, true)?;
+    basis_cache.key_update(hw, "txtest1", "small", &[0x5A; 50], None, None, None, true)?;
+    basis_cache.sync(hw, None)?;
+
+    *basis_cache = BasisCache::new();
+    let sys_basis = hw.pddb_mount().expect("couldn't remount system basis");
+    basis_cache.basis_add(sys_basis);
+    assert!(basis_cache.tx_recover(hw, PDDB_DEFAULT_SYSTEM_BASIS)?, "interrupted transaction was not found");
+    assert!(tx_read_key(hw, basis_cache, "txtest1", "small") == Some(vec![0x5A; 50]), "recovered write missing");
+    assert!(tx_read_key(hw, basis_cache, "txtest2", "large").is_none(), "recovered delete missing");
+    assert!(tx_read_key(hw, basis_cache, "txtest3", "new") == Some(vec![3; 3000]), "recovered write to new dict missing");
+    assert!(!basis_cache.dict_list(hw, None).contains(TX_INTENT_DICT), "intent record was not retired after recovery");
+    assert!(!basis_cache.tx_recover(hw, PDDB_DEFAULT_SYSTEM_BASIS)?, "recovery ran twice");
+    Ok(())
+}
+
+/// Walks a dictionary a page at a time, with and without filters, and checks each walk against
+/// a filtered copy of the full key list.
+pub(crate) fn key_range_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const PAGE: usize = 7;
+    for i in 0..100 {
+        let name = format!("{}{:03}", if i % 2 == 0 { "even" } else { "odd" }, i);
+        basis_cache.key_update(hw, "rangetest", &name, &[i as u8], None, None, None, true)?;
+    }
+    let queries = [
+        KeyQuery::default(),
+        KeyQuery { prefix: Some("odd".to_string()), ..Default::default() },
+        KeyQuery { start: Some("even050".to_string()), end: Some("odd011".to_string()), ..Default::default() },
+        KeyQuery { prefix: Some("none".to_string()), ..Default::default() },
+    ];
+    for query in queries.iter() {
+        let mut expected: Vec::<String> = basis_cache.key_list(hw, "rangetest", None)?
+            .into_iter().filter(|k| query.matches(k)).collect();
+        expected.sort();
+        let mut walked = Vec::<String>::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut page = basis_cache.key_range(hw, "rangetest", None, query, cursor.as_deref(), PAGE)?;
+            let done = page.len() < PAGE;
+            cursor = page.last().cloned();
+            walked.append(&mut page);
+            if done {
+                break;
+            }
+        }
+        assert!(walked == expected, "paged listing of {:?} does not match the filtered key list", query);
+    }
+    assert!(basis_cache.key_range(hw, "no such dict", None, &KeyQuery::default(), None, PAGE).is_err(),
+        "listing a missing dictionary should fail");
+    basis_cache.dict_remove(hw, "rangetest", None, false)
+}
+
+/// Exercises key metadata on the system basis: stamping on create and write, client-set content type
+/// and attributes, persistence across a remount, and cleanup as keys are deleted. Leaves `basis_cache`
+/// holding a freshly remounted system basis.
+pub(crate) fn key_metadata_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const T0: u64 = 1_650_000_000_000;
+    // writes made before the time is known aren't stamped
+    basis_cache.key_update(hw, "metatest", "legacy", &[1], None, None, None, true)?;
+    hw.set_time_reference(T0);
+    basis_cache.key_update(hw, "metatest", "fresh", &[2; 10], None, None, None, true)?;
+    basis_cache.key_update(hw, "metatest", "legacy", &[3], None, None, None, true)?;
+    let fresh = basis_cache.key_metadata(hw, "metatest", "fresh", None)?;
+    assert!(fresh.created >= T0 && fresh.modified >= fresh.created, "new key was not stamped");
+    let legacy = basis_cache.key_metadata(hw, "metatest", "legacy", None)?;
+    assert!(legacy.created == 0 && legacy.modified >= T0, "pre-existing key was given a creation time");
+
+    let mut meta = KeyMetadata::default();
+    meta.content_type = Some("test/blob".to_string());
+    meta.attributes.insert("origin".to_string(), "ci".to_string());
+    basis_cache.key_metadata_set(hw, "metatest", "fresh", &meta, None)?;
+    basis_cache.key_update(hw, "metatest", "fresh", &[4; 10], None, None, None, true)?;
+    let expected = basis_cache.key_metadata(hw, "metatest", "fresh", None)?;
+    assert!(expected.content_type == meta.content_type && expected.attributes == meta.attributes, "metadata was not set");
+    assert!(expected.created == fresh.created, "writing a key changed its creation time");
+    let list = basis_cache.key_list(hw, "metatest", None)?;
+    assert!(list.len() == 2 && !list.contains(KEY_META_KEY), "metadata table is visible in the key list");
+    basis_cache.key_remove(hw, "metatest", "legacy", None, false)?;
+    basis_cache.sync(hw, None)?;
+
+    *basis_cache = BasisCache::new();
+    let sys_basis = hw.pddb_mount().expect("couldn't remount system basis");
+    basis_cache.basis_add(sys_basis);
+    assert!(basis_cache.key_metadata(hw, "metatest", "fresh", None)? == expected, "metadata did not persist");
+    assert!(basis_cache.key_metadata(hw, "metatest", "legacy", None).is_err(), "deleted key still has metadata");
+    // the table itself goes away along with the last entry in it
+    basis_cache.key_remove(hw, "metatest", "fresh", None, false)?;
+    basis_cache.sync(hw, None)?;
+    assert!(basis_cache.key_list(hw, "metatest", None)?.is_empty(), "dictionary is not empty");
+    assert!(basis_cache.key_attributes(hw, "metatest", KEY_META_KEY, None).map(|a| !a.flags.valid()).unwrap_or(true),
+        "empty metadata table was not removed");
+    Ok(())
+}
+
+/// Checks the change log that feeds `Pddb::watch` notifications: creates, updates, deletes and
+/// dictionary removals are recorded in order, hidden keys are not, and nothing is recorded while
+/// tracking is off.
+pub(crate) fn change_tracking_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    basis_cache.key_update(hw, "watchtest", "untracked", &[0], None, None, None, true)?;
+    basis_cache.track_changes(true);
+    assert!(basis_cache.take_changes().is_empty(), "changes were recorded while tracking was off");
+    basis_cache.key_update(hw, "watchtest", "a", &[1], None, None, None, true)?;
+    basis_cache.key_update(hw, "watchtest", "a", &[2], None, None, None, true)?;
+    basis_cache.key_remove(hw, "watchtest", "a", None, false)?;
+    basis_cache.dict_remove(hw, "watchtest", None, false)?;
+    let changes: Vec::<(PddbWatchEventKind, Option<String>)> = basis_cache.take_changes()
+        .into_iter()
+        .map(|c| { assert!(c.dict == "watchtest" && c.basis == PDDB_DEFAULT_SYSTEM_BASIS); (c.kind, c.key) })
+        .collect();
+    assert!(changes == vec![
+        (PddbWatchEventKind::Created, Some("a".to_string())),
+        (PddbWatchEventKind::Updated, Some("a".to_string())),
+        (PddbWatchEventKind::Deleted, Some("a".to_string())),
+        (PddbWatchEventKind::Deleted, None),
+    ], "unexpected change log: {:?}", changes);
+    assert!(basis_cache.take_changes().is_empty(), "change log was not drained");
+    basis_cache.track_changes(false);
+    Ok(())
+}
+
+/// Exports a basis, then imports it under a new name and password, and checks that the copy has the same
+/// keys, data and metadata as the original, that it persists across a remount, and that it really is
+/// protected by the new password rather than the original one.
+pub(crate) fn basis_export_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const SRC: &'static str = "ExportSrc";
+    const SRC_PW: &'static str = "export source password";
+    const DST: &'static str = "ExportDst";
+    const DST_PW: &'static str = "a different password";
+    const PASSPHRASE: &'static str = "correct horse battery staple";
+    basis_cache.basis_create(hw, SRC, SRC_PW)?;
+    let src = basis_cache.basis_unlock(hw, SRC, SRC_PW, BasisRetentionPolicy::Persist).expect("couldn't unlock export source");
+    basis_cache.basis_add(src);
+    let mut expected = BTreeMap::<(String, String), Vec::<u8>>::new();
+    for (dict, key, len) in [("exp.small", "a", 1), ("exp.small", "b", 100), ("exp.large", "big", 3 * VPAGE_SIZE + 17)].iter() {
+        let data: Vec::<u8> = (0..*len).map(|i| (i * 7 + key.len()) as u8).collect();
+        basis_cache.key_update(hw, dict, key, &data, None, None, Some(SRC), true)?;
+        expected.insert((dict.to_string(), key.to_string()), data);
+    }
+    basis_cache.dict_add(hw, "exp.empty", Some(SRC))?;
+    let mut meta = KeyMetadata::default();
+    meta.content_type = Some("test/export".to_string());
+    meta.attributes.insert("origin".to_string(), "ci".to_string());
+    basis_cache.key_metadata_set(hw, "exp.small", "b", &meta, Some(SRC))?;
+    let src_meta = basis_cache.key_metadata(hw, "exp.small", "b", Some(SRC))?;
+
+    let mut salt = [0u8; 16];
+    hw.trng_slice(&mut salt);
+    let mut nonce = [0u8; 12];
+    hw.trng_slice(&mut nonce);
+    let blob = basis_export_seal(&basis_cache.basis_export(hw, SRC)?, PASSPHRASE, salt, nonce)?;
+    basis_cache.basis_unmount(hw, SRC)?;
+    assert!(basis_export_open(&blob, "not the passphrase").is_err(), "export opened with the wrong passphrase");
+
+    let export = basis_export_open(&blob, PASSPHRASE)?;
+    assert!(export.name == SRC, "export has the wrong basis name");
+    basis_cache.basis_create(hw, DST, DST_PW)?;
+    let dst = basis_cache.basis_unlock(hw, DST, DST_PW, BasisRetentionPolicy::Persist).expect("couldn't unlock import target");
+    basis_cache.basis_add(dst);
+    basis_cache.basis_import(hw, &export, DST)?;
+    basis_cache.basis_unmount(hw, DST)?;
+    assert!(basis_cache.basis_unlock(hw, DST, SRC_PW, BasisRetentionPolicy::Persist).is_none(),
+        "imported basis can be opened with the password of the original");
+
+    let dst = basis_cache.basis_unlock(hw, DST, DST_PW, BasisRetentionPolicy::Persist).expect("imported basis did not persist");
+    basis_cache.basis_add(dst);
+    let dicts = basis_cache.dict_list(hw, Some(DST));
+    assert!(dicts.len() == 3 && dicts.contains("exp.empty"), "imported basis has the wrong dictionaries: {:?}", dicts);
+    for ((dict, key), data) in expected.iter() {
+        let attr = basis_cache.key_attributes(hw, dict, key, Some(DST))?;
+        let mut readback = vec![0u8; attr.len];
+        basis_cache.key_read(hw, dict, key, &mut readback, None, Some(DST))?;
+        assert!(readback == *data, "imported key {}:{} does not match the original", dict, key);
+    }
+    assert!(basis_cache.key_list(hw, "exp.small", Some(DST))?.len() == 2, "imported dictionary has extra keys");
+    assert!(basis_cache.key_metadata(hw, "exp.small", "b", Some(DST))? == src_meta, "key metadata was not imported");
+    basis_cache.basis_unmount(hw, DST)?;
+    Ok(())
+}
+
+pub(crate) fn compaction_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const BASIS: &'static str = "Compact";
+    const BASIS_PW: &'static str = "compaction password";
+    fn make_key(keynum: usize, len: usize) -> (String, Vec::<u8>) {
+        (format!("key{}", keynum), (0..len).map(|i| (i * 7 + keynum) as u8).collect())
+    }
+    fn readback(hw: &mut PddbOs, basis_cache: &mut BasisCache, expected: &BTreeMap::<(String, String), Vec::<u8>>) -> Result<()> {
+        for ((dict, key), data) in expected.iter() {
+            let attr = basis_cache.key_attributes(hw, dict, key, Some(BASIS))?;
+            let mut readback = vec![0u8; attr.len];
+            basis_cache.key_read(hw, dict, key, &mut readback, None, Some(BASIS))?;
+            assert!(readback == *data, "key {}:{} was corrupted by compaction", dict, key);
+        }
+        Ok(())
+    }
+    basis_cache.basis_create(hw, BASIS, BASIS_PW)?;
+    let basis = basis_cache.basis_unlock(hw, BASIS, BASIS_PW, BasisRetentionPolicy::Persist).expect("couldn't unlock compaction basis");
+    basis_cache.basis_add(basis);
+    // spread small keys over several pools, and large keys over several slots, then delete most of them
+    let mut expected = BTreeMap::<(String, String), Vec::<u8>>::new();
+    for (dict, count, len) in [("cmp.small", 40, 600), ("cmp.large", 4, 3 * VPAGE_SIZE + 17)].iter() {
+        for i in 0..*count {
+            let (key, data) = make_key(i, *len);
+            basis_cache.key_update(hw, dict, &key, &data, None, None, Some(BASIS), true)?;
+            expected.insert((dict.to_string(), key), data);
+        }
+    }
+    // keep a few keys, scattered across the pools
+    for i in 0..40 {
+        if i % 17 != 5 && i != 39 {
+            basis_cache.key_remove(hw, "cmp.small", &format!("key{}", i), Some(BASIS), false)?;
+            expected.remove(&("cmp.small".to_string(), format!("key{}", i)));
+        }
+    }
+    for i in 0..3 {
+        basis_cache.key_remove(hw, "cmp.large", &format!("key{}", i), Some(BASIS), false)?;
+        expected.remove(&("cmp.large".to_string(), format!("key{}", i)));
+    }
+    basis_cache.sync(hw, Some(BASIS))?;
+
+    let before = basis_cache.frag_stats(hw);
+    log::info!("fragmentation before: {:?}", before);
+    let mut keys_moved = 0;
+    let mut pages_freed = 0;
+    for job in basis_cache.compact_plan(hw).iter() {
+        let (moved, freed) = basis_cache.compact_step(hw, job)?;
+        keys_moved += moved;
+        pages_freed += freed;
+    }
+    let after = basis_cache.frag_stats(hw);
+    log::info!("fragmentation after: {:?}, {} keys moved, {} pages freed", after, keys_moved, pages_freed);
+    assert!(after.small_pages < before.small_pages && after.small_pages == after.small_pages_needed,
+        "small pool was not compacted: {:?} -> {:?}", before, after);
+    assert!(after.large_slots < before.large_slots && after.large_keys == before.large_keys,
+        "large pool was not compacted: {:?} -> {:?}", before, after);
+    assert!(pages_freed > 0 && keys_moved > 0, "compaction reports no work done");
+    readback(hw, basis_cache, &expected)?;
+    // a second pass has nothing left to do but re-checking the allocation pointer
+    assert!(basis_cache.compact_plan(hw).len() == 1, "compaction did not reach a fixed point");
+
+    // the new layout must survive a remount, and the space below the allocation pointer must be usable
+    basis_cache.basis_unmount(hw, BASIS)?;
+    let basis = basis_cache.basis_unlock(hw, BASIS, BASIS_PW, BasisRetentionPolicy::Persist).expect("compacted basis did not persist");
+    basis_cache.basis_add(basis);
+    readback(hw, basis_cache, &expected)?;
+    for (dict, i, len) in [("cmp.small", 40, 600), ("cmp.large", 4, 3 * VPAGE_SIZE + 17)].iter() {
+        let (key, data) = make_key(*i, *len);
+        basis_cache.key_update(hw, dict, &key, &data, None, None, Some(BASIS), true)?;
+        expected.insert((dict.to_string(), key), data);
+    }
+    basis_cache.sync(hw, Some(BASIS))?;
+    readback(hw, basis_cache, &expected)?;
+    basis_cache.basis_unmount(hw, BASIS)?;
+    Ok(())
+}
+
+/// Interrupts a compaction pass after it has copied a large key, but before the move is committed, then remounts:
+/// first with the key's dictionary damaged, when nothing may be released, and then with it repaired, when only
+/// the abandoned copy is. Leaves `basis_cache` holding a freshly remounted system basis.
+pub(crate) fn compaction_recovery_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const BASIS: &'static str = "CompactRecover";
+    const BASIS_PW: &'static str = "compaction recovery password";
+    const DICT: &'static str = "rec.large";
+    fn readback(hw: &mut PddbOs, basis_cache: &mut BasisCache, data: &[u8]) -> Result<()> {
+        let attr = basis_cache.key_attributes(hw, DICT, "kept", Some(BASIS))?;
+        let mut readback = vec![0u8; attr.len];
+        basis_cache.key_read(hw, DICT, "kept", &mut readback, None, Some(BASIS))?;
+        assert!(readback == data, "the moved key was corrupted");
+        Ok(())
+    }
+    basis_cache.basis_create(hw, BASIS, BASIS_PW)?;
+    let basis = basis_cache.basis_unlock(hw, BASIS, BASIS_PW, BasisRetentionPolicy::Persist).expect("couldn't unlock recovery basis");
+    basis_cache.basis_add(basis);
+    let data: Vec::<u8> = (0..3 * VPAGE_SIZE + 17).map(|i| (i * 3) as u8).collect();
+    basis_cache.key_update(hw, DICT, "gone", &data, None, None, Some(BASIS), true)?;
+    basis_cache.key_update(hw, DICT, "kept", &data, None, None, Some(BASIS), true)?;
+    basis_cache.key_remove(hw, DICT, "gone", Some(BASIS), false)?;
+    basis_cache.sync(hw, None)?;
+
+    let job = basis_cache.compact_plan(hw).into_iter()
+        .find(|job| matches!(job, CompactJob::LargeKey { basis, key, .. } if basis == BASIS && key == "kept"))
+        .expect("no move was planned for the key");
+    let target = match &job {
+        CompactJob::LargeKey { target, .. } => *target,
+        _ => unreachable!(),
+    };
+    let reserved = basis_cache.key_attributes(hw, DICT, "kept", Some(BASIS))?.reserved as u64;
+    let pages = (reserved as usize + VPAGE_SIZE - 1) / VPAGE_SIZE;
+    assert!(basis_cache.compact_copy(hw, &job)?, "the move did not start");
+    assert!(basis_cache.dbg_vpages_mapped(BASIS, target, reserved) == pages, "the key was not copied");
+
+    // lose power with the dictionary header torn
+    let (pp, mut page) = basis_cache.dbg_corrupt_dict(hw, DICT, BASIS).expect("couldn't damage the dictionary");
+    *basis_cache = BasisCache::new();
+    let sys_basis = hw.pddb_mount().expect("couldn't remount system basis");
+    basis_cache.basis_add(sys_basis);
+    let basis = basis_cache.basis_unlock(hw, BASIS, BASIS_PW, BasisRetentionPolicy::Persist).expect("couldn't remount recovery basis");
+    basis_cache.basis_add(basis);
+    assert!(!basis_cache.compact_recover(hw, BASIS)?, "a move was cleaned up while a dictionary was unreadable");
+    assert!(!basis_cache.tx_recover(hw, BASIS)?, "the move record was taken for a transaction");
+    assert!(basis_cache.dbg_vpages_mapped(BASIS, target, reserved) == pages, "pages were released while a dictionary was unreadable");
+    assert!(basis_cache.dict_list(hw, Some(BASIS)).contains(TX_INTENT_DICT), "the move record was retired");
+
+    // once the dictionary can be read again, only the copy is released
+    basis_cache.dbg_restore_page(hw, BASIS, &pp, &mut page);
+    basis_cache.basis_unmount(hw, BASIS)?;
+    let basis = basis_cache.basis_unlock(hw, BASIS, BASIS_PW, BasisRetentionPolicy::Persist).expect("couldn't remount recovery basis");
+    basis_cache.basis_add(basis);
+    assert!(basis_cache.compact_recover(hw, BASIS)?, "the interrupted move was not found");
+    assert!(basis_cache.dbg_vpages_mapped(BASIS, target, reserved) == 0, "the abandoned copy was not released");
+    assert!(!basis_cache.dict_list(hw, Some(BASIS)).contains(TX_INTENT_DICT), "the move record was not retired");
+    assert!(!basis_cache.compact_recover(hw, BASIS)?, "recovery ran twice");
+    readback(hw, basis_cache, &data)?;
+
+    // and the pass can be run again from the start
+    for job in basis_cache.compact_plan(hw).iter() {
+        basis_cache.compact_step(hw, job)?;
+    }
+    readback(hw, basis_cache, &data)?;
+    basis_cache.basis_unmount(hw, BASIS)?;
+    Ok(())
+}
+
+/// Fills a dictionary up to its quota, then checks that the quota is enforced on plain writes and on
+/// transactions, that deleting keys makes room again, that a basis-wide quota covers all dictionaries,
+/// and that the quotas survive a remount.
+pub(crate) fn quota_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const BASIS: &'static str = "Quota";
+    const BASIS_PW: &'static str = "quota password";
+    const DICT: &'static str = "quota.limited";
+    basis_cache.basis_create(hw, BASIS, BASIS_PW)?;
+    let basis = basis_cache.basis_unlock(hw, BASIS, BASIS_PW, BasisRetentionPolicy::Persist).expect("couldn't unlock quota basis");
+    basis_cache.basis_add(basis);
+
+    // a quota can be set before its dictionary exists
+    basis_cache.quota_set(hw, Some(DICT), Some(2000), Some(BASIS))?;
+    assert!(!basis_cache.dict_list(hw, Some(BASIS)).contains(QUOTA_DICT), "quota table is visible");
+    for i in 0..3 {
+        basis_cache.key_update(hw, DICT, &format!("key{}", i), &[i as u8; 500], None, None, Some(BASIS), true)?;
+    }
+    assert!(basis_cache.quota_usage(hw, Some(DICT), Some(BASIS))? == (1500, Some(2000)), "wrong dictionary usage");
+    let e = basis_cache.key_update(hw, DICT, "key3", &[3; 600], None, None, Some(BASIS), true)
+        .expect_err("write past the dictionary quota was allowed");
+    assert!(is_quota_exceeded(&e), "quota error is not distinguishable: {:?}", e);
+    assert!(basis_cache.key_attributes(hw, DICT, "key3", Some(BASIS)).is_err(), "refused write created a key");
+    // rewriting a key within its reservation doesn't grow the usage
+    basis_cache.key_update(hw, DICT, "key0", &[9; 400], None, None, Some(BASIS), true)?;
+
+    // transactions are checked as a whole, before anything is applied
+    let ops = vec![
+        TxOp::Delete { dict: DICT.to_string(), key: "key1".to_string() },
+        TxOp::Write { dict: DICT.to_string(), key: "key3".to_string(), data: vec![3; 600] },
+    ];
+    basis_cache.tx_commit(hw, &ops, Some(BASIS))?;
+    let ops = vec![
+        TxOp::Write { dict: DICT.to_string(), key: "key4".to_string(), data: vec![4; 1000] },
+    ];
+    let e = basis_cache.tx_commit(hw, &ops, Some(BASIS)).expect_err("transaction past the dictionary quota was allowed");
+    assert!(is_quota_exceeded(&e), "quota error is not distinguishable: {:?}", e);
+    assert!(basis_cache.key_attributes(hw, DICT, "key4", Some(BASIS)).is_err(), "refused transaction was applied");
+    assert!(basis_cache.dict_attributes(hw, TX_INTENT_DICT, Some(BASIS)).is_err(), "refused transaction left an intent record");
+
+    // deleting a key makes room again
+    basis_cache.key_remove(hw, DICT, "key2", Some(BASIS), false)?;
+    basis_cache.key_update(hw, DICT, "key4", &[4; 500], None, None, Some(BASIS), true)?;
+    assert!(basis_cache.quota_usage(hw, Some(DICT), Some(BASIS))?.0 == 1600, "wrong dictionary usage after delete");
+
+    // a basis quota covers every dictionary
+    basis_cache.quota_set(hw, None, Some(3000), Some(BASIS))?;
+    let e = basis_cache.key_update(hw, "quota.other", "big", &[5; 2000], None, None, Some(BASIS), true)
+        .expect_err("write past the basis quota was allowed");
+    assert!(is_quota_exceeded(&e), "quota error is not distinguishable: {:?}", e);
+    basis_cache.key_update(hw, "quota.other", "small", &[5; 1000], None, None, Some(BASIS), true)?;
+    assert!(basis_cache.quota_usage(hw, None, Some(BASIS))? == (2600, Some(3000)), "wrong basis usage");
+    basis_cache.sync(hw, Some(BASIS))?;
+
+    // quotas are stored in the basis
+    basis_cache.basis_unmount(hw, BASIS)?;
+    let basis = basis_cache.basis_unlock(hw, BASIS, BASIS_PW, BasisRetentionPolicy::Persist).expect("quota basis did not persist");
+    basis_cache.basis_add(basis);
+    assert!(basis_cache.quota_usage(hw, Some(DICT), Some(BASIS))? == (1600, Some(2000)), "dictionary quota did not persist");
+    assert!(basis_cache.quota_usage(hw, None, Some(BASIS))? == (2600, Some(3000)), "basis quota did not persist");
+    assert!(basis_cache.key_update(hw, DICT, "key5", &[5; 500], None, None, Some(BASIS), true).is_err(), "quota not enforced after remount");
+
+    // removing the quotas lifts the limits, and retires the table
+    basis_cache.quota_set(hw, Some(DICT), None, Some(BASIS))?;
+    basis_cache.quota_set(hw, None, None, Some(BASIS))?;
+    basis_cache.key_update(hw, DICT, "key5", &[5; 500], None, None, Some(BASIS), true)?;
+    assert!(basis_cache.dict_attributes(hw, QUOTA_DICT, Some(BASIS)).is_err(), "empty quota table was not removed");
+    basis_cache.basis_unmount(hw, BASIS)?;
+    Ok(())
+}
+
 fn test_prune(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
     const TARGET_SIZE: usize = 150*1024;
     let cache_size = basis_cache.cache_size();
@@ -103,6 +103,7 @@ mod jtag_cmd; use jtag_cmd::*;
 mod net_cmd;  use net_cmd::*;
 mod pddb_cmd; use pddb_cmd::*;
 mod usb; use usb::*;
+mod log_cmd;  use log_cmd::*;
 
 #[cfg(feature="tts")]
 mod tts;
@@ -146,6 +147,7 @@ pub struct CmdEnv {
     pddb_cmd: PddbCmd,
     wlan_cmd: Wlan,
     usb_cmd: Usb,
+    log_cmd: LogCmd,
 
     #[cfg(feature="tts")]
     tts_cmd: Tts,
@@ -216,6 +218,7 @@ impl CmdEnv {
             pddb_cmd: PddbCmd::new(&xns),
             wlan_cmd: Wlan::new(),
             usb_cmd: Usb::new(),
+            log_cmd: LogCmd::new(&xns),
 
             #[cfg(feature="tts")]
             tts_cmd: Tts::new(&xns),
@@ -263,6 +266,7 @@ impl CmdEnv {
             &mut self.net_cmd,
             &mut self.pddb_cmd,
             &mut self.usb_cmd,
+            &mut self.log_cmd,
 
             #[cfg(feature="tts")]
             &mut self.tts_cmd,
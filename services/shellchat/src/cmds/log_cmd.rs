@@ -0,0 +1,203 @@
+use crate::{ShellCmdApi, CommonEnv};
+use xous_ipc::String;
+use std::io::{Read, Seek, SeekFrom, Write};
+use core::fmt::Write as FmtWrite;
+
+/// The dictionary that `log export` and the spill thread write to
+const LOG_DICT: &'static str = "sys.log";
+/// The key that records are spilled to as the log server's store fills up
+const SPILL_KEY: &'static str = "spill";
+/// Where the spilled records go once `SPILL_KEY` reaches `SPILL_LIMIT`, replacing
+/// the ones that were there before
+const SPILL_PREV_KEY: &'static str = "spill.prev";
+const SPILL_LIMIT: usize = 64 * 1024;
+
+pub struct LogCmd {
+    pddb: pddb::Pddb,
+}
+impl LogCmd {
+    pub fn new(_xns: &xous_names::XousNames) -> LogCmd {
+        xous::create_thread_0(spill_thread).expect("SHCH: couldn't create log spill thread");
+        LogCmd {
+            pddb: pddb::Pddb::new(),
+        }
+    }
+}
+
+/// Copy records out of the log server's RAM store into the PDDB before they are
+/// evicted. The log server can't use the PDDB itself, since the PDDB logs through it.
+fn spill_thread() {
+    let pddb = pddb::Pddb::new();
+    pddb.is_mounted_blocking();
+    let sid = xous::create_server().expect("SHCH: couldn't create log spill server");
+    log_server::set_spill_listener(sid).expect("SHCH: couldn't listen for log spills");
+    let mut after_seq = 0;
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        if msg.body.id() != log_server::api::SPILL_READY_ID {
+            continue;
+        }
+        let query = log_server::RecordQuery { after_seq, ..Default::default() };
+        let records = match log_server::read_records(&query) {
+            Ok((records, _dropped)) => records,
+            Err(e) => {
+                log::error!("couldn't read log records to spill: {:?}", e);
+                continue;
+            }
+        };
+        if let Some(last) = records.last() {
+            after_seq = last.seq;
+        }
+        if let Err(e) = spill(&pddb, &records) {
+            log::error!("couldn't spill log records: {:?}", e);
+        }
+    }
+}
+
+fn spill(pddb: &pddb::Pddb, records: &[log_server::api::StoredRecord]) -> std::io::Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let mut key = pddb.get(LOG_DICT, SPILL_KEY, None,
+        true, true, Some(SPILL_LIMIT), None::<fn()>)?;
+    if key.seek(SeekFrom::End(0))? as usize >= SPILL_LIMIT {
+        // the PDDB can't rename keys, so copy the full key over the previous one
+        let mut contents = std::vec::Vec::new();
+        key.seek(SeekFrom::Start(0))?;
+        key.read_to_end(&mut contents)?;
+        drop(key);
+        pddb.delete_key(LOG_DICT, SPILL_PREV_KEY, None).ok();
+        pddb.get(LOG_DICT, SPILL_PREV_KEY, None,
+            true, true, Some(SPILL_LIMIT), None::<fn()>)?.write_all(&contents)?;
+        pddb.delete_key(LOG_DICT, SPILL_KEY, None).ok();
+        key = pddb.get(LOG_DICT, SPILL_KEY, None,
+            true, true, Some(SPILL_LIMIT), None::<fn()>)?;
+    }
+    for record in records.iter() {
+        key.write_all(format_record(record).as_bytes())?;
+    }
+    drop(key);
+    pddb.sync()
+}
+
+fn parse_pid(token: &str) -> Option<xous::PID> {
+    token.parse::<u8>().ok().and_then(xous::PID::new)
+}
+
+fn parse_level(token: &str) -> Option<log::LevelFilter> {
+    match token {
+        "off" => Some(log::LevelFilter::Off),
+        "error" => Some(log::LevelFilter::Error),
+        "warn" => Some(log::LevelFilter::Warn),
+        "info" => Some(log::LevelFilter::Info),
+        "debug" => Some(log::LevelFilter::Debug),
+        "trace" => Some(log::LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+fn format_record(record: &log_server::api::StoredRecord) -> std::string::String {
+    let level = record.log_level().map_or("?", |l| l.as_str());
+    format!("{}.{:03} {} {} {}: {}\n",
+        record.timestamp_ms / 1000, record.timestamp_ms % 1000,
+        record.pid, level, record.module, record.args)
+}
+
+impl<'a> ShellCmdApi<'a> for LogCmd {
+    cmd_api!(log); // inserts boilerplate for command API
+
+    fn process(&mut self, args: String::<1024>, _env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "log [dump [pid|all] [level]] [export [key]] [level <pid|all> <level>] [clear]\nlevels: off error warn info debug trace";
+
+        let mut tokens = args.as_str().unwrap().split(' ');
+
+        if let Some(sub_cmd) = tokens.next() {
+            match sub_cmd {
+                "dump" => {
+                    let mut query = log_server::RecordQuery::default();
+                    if let Some(pid) = tokens.next() {
+                        query.pid = parse_pid(pid);
+                    }
+                    if let Some(level) = tokens.next() {
+                        query.level = parse_level(level).and_then(|l| l.to_level());
+                    }
+                    let (records, _dropped) = log_server::read_records(&query)?;
+                    // only the most recent records fit in the reply, so work backwards from the newest
+                    let mut lines = std::vec::Vec::new();
+                    let mut space = 1024;
+                    for record in records.iter().rev() {
+                        let line = format_record(record);
+                        if line.len() > space {
+                            break;
+                        }
+                        space -= line.len();
+                        lines.push(line);
+                    }
+                    for line in lines.iter().rev() {
+                        write!(ret, "{}", line).ok();
+                    }
+                    if lines.len() == 0 {
+                        write!(ret, "No matching records").unwrap();
+                    }
+                }
+                "export" => {
+                    let keyname = tokens.next().unwrap_or("latest");
+                    let (records, dropped) = log_server::read_records(&log_server::RecordQuery::default())?;
+                    // replace any earlier export with the same name
+                    self.pddb.delete_key(LOG_DICT, keyname, None).ok();
+                    match self.pddb.get(LOG_DICT, keyname, None,
+                        true, true, Some(8192), None::<fn()>) {
+                        Ok(mut key) => {
+                            let mut written = 0;
+                            for record in records.iter() {
+                                if let Err(e) = key.write_all(format_record(record).as_bytes()) {
+                                    log::error!("couldn't export log record: {:?}", e);
+                                    break;
+                                }
+                                written += 1;
+                            }
+                            self.pddb.sync().ok();
+                            write!(ret, "Exported {} records to {}:{}", written, LOG_DICT, keyname).unwrap();
+                            if dropped != 0 {
+                                write!(ret, "\n{} older records were already discarded", dropped).unwrap();
+                            }
+                        }
+                        Err(e) => write!(ret, "Couldn't open {}:{}: {:?}", LOG_DICT, keyname, e).unwrap(),
+                    }
+                }
+                "level" => {
+                    let target = tokens.next();
+                    let level = tokens.next().and_then(parse_level);
+                    match (target, level) {
+                        (Some("all"), Some(level)) => {
+                            log_server::set_level_filter(None, level)?;
+                            write!(ret, "Default log level is now {}", level).unwrap();
+                        }
+                        (Some(pid), Some(level)) => {
+                            match parse_pid(pid) {
+                                Some(pid) => {
+                                    log_server::set_level_filter(Some(pid), level)?;
+                                    write!(ret, "Log level for PID {} is now {}", pid, level).unwrap();
+                                }
+                                None => write!(ret, "Invalid PID {}", pid).unwrap(),
+                            }
+                        }
+                        _ => write!(ret, "log level <pid|all> <off|error|warn|info|debug|trace>").unwrap(),
+                    }
+                }
+                "clear" => {
+                    log_server::clear_records()?;
+                    write!(ret, "Log records cleared").unwrap();
+                }
+                _ => {
+                    write!(ret, "{}", helpstring).unwrap();
+                }
+            }
+
+        } else {
+            write!(ret, "{}", helpstring).unwrap();
+        }
+        Ok(Some(ret))
+    }
+}
@@ -57,15 +57,34 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
         use core::fmt::Write;
         let mut ret = String::<1024>::new();
         #[cfg(any(target_os = "none", target_os = "xous"))]
-        let helpstring = "net [udp [rx socket] [tx dest socket]] [ping [host] [count]] [tcpget host/path]";
+        let helpstring = "net [udp [rx socket] [tx dest socket]] [ping [host] [count]] [tcpget host/path] [ipv6] [dns host|cache|flush]";
         // no ping in hosted mode -- why would you need it? we're using the host's network connection.
         #[cfg(not(any(target_os = "none", target_os = "xous")))]
-        let helpstring = "net [udp [port]] [count]] [tcpget host/path]";
+        let helpstring = "net [udp [port]] [count]] [tcpget host/path] [ipv6] [dns host|cache|flush]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
 
         if let Some(sub_cmd) = tokens.next() {
             match sub_cmd {
+                "ipv6" => {
+                    match env.netmgr.get_ipv6_config() {
+                        Ok(config) => {
+                            write!(ret, "link-local: {}", std::net::Ipv6Addr::from(config.link_local)).ok();
+                            for prefix in config.addrs.iter().flatten() {
+                                write!(ret, "\nglobal: {}/{}", std::net::Ipv6Addr::from(prefix.addr), prefix.prefix_len).ok();
+                            }
+                            if let Some(router) = config.router {
+                                write!(ret, "\nrouter: {}", std::net::Ipv6Addr::from(router)).ok();
+                            }
+                            for dns in config.dns.iter().flatten() {
+                                write!(ret, "\ndns: {}", std::net::Ipv6Addr::from(*dns)).ok();
+                            }
+                        }
+                        Err(e) => {
+                            write!(ret, "Couldn't get IPv6 config: {:?}", e).ok();
+                        }
+                    }
+                }
                 "unsub" => {
                     // this is just for testing the unsub call itself. It should result in the connection manager itself breaking.
                     match env.netmgr.wifi_state_unsubscribe() {
@@ -276,15 +295,42 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                     write!(ret, "Started multi-threaded UDP responder").unwrap();
                 }
                 "dns" => {
-                    if let Some(name) = tokens.next() {
-                        match self.dns.lookup(name) {
-                            Ok(ipaddr) => {
-                                write!(ret, "DNS resolved {}->{:?}", name, ipaddr).unwrap();
+                    match tokens.next() {
+                        Some("flush") => {
+                            match self.dns.flush_cache() {
+                                Ok(_) => write!(ret, "DNS cache flushed").unwrap(),
+                                Err(e) => write!(ret, "DNS cache flush error: {:?}", e).unwrap(),
                             }
-                            Err(e) => {
-                                write!(ret, "DNS lookup error: {:?}", e).unwrap();
+                        }
+                        Some("cache") => {
+                            match self.dns.cache_entries() {
+                                Ok(entries) => {
+                                    write!(ret, "{} DNS cache entries", entries.len()).unwrap();
+                                    for entry in entries.iter() {
+                                        if entry.negative {
+                                            write!(ret, "\n{}: NXDOMAIN ttl {}s", entry.name, entry.ttl).unwrap();
+                                        } else if let Some(addr) = entry.addr {
+                                            write!(ret, "\n{}: {:?} ({} total) ttl {}s",
+                                                entry.name, addr, entry.addr_count, entry.ttl).unwrap();
+                                        } else {
+                                            write!(ret, "\n{}: no addresses, ttl {}s", entry.name, entry.ttl).unwrap();
+                                        }
+                                    }
+                                }
+                                Err(e) => write!(ret, "DNS cache error: {:?}", e).unwrap(),
+                            }
+                        }
+                        Some(name) => {
+                            match self.dns.lookup(name) {
+                                Ok(ipaddr) => {
+                                    write!(ret, "DNS resolved {}->{:?}", name, ipaddr).unwrap();
+                                }
+                                Err(e) => {
+                                    write!(ret, "DNS lookup error: {:?}", e).unwrap();
+                                }
                             }
                         }
+                        None => write!(ret, "net dns [host|cache|flush]").unwrap(),
                     }
                 }
                 #[cfg(feature="ditherpunk")]
@@ -530,7 +576,9 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                     None => {
                         // rebind the scalar args to the Ping convention
                         let op = arg1;
+                        // for IPv6 remotes, this is just the bottom 4 bytes of the address
                         let addr = IpAddr::from((*arg2 as u32).to_be_bytes());
+                        let is_ipv6 = (op & net::NET_PING_IPV6) != 0;
                         let seq_or_addr = *arg3;
                         let timestamp = *arg4;
                         match FromPrimitive::from_usize(op & 0xFF) {
@@ -540,31 +588,40 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                                 return Ok(None);
                             }
                             Some(NetPingCallback::NoErr) => {
-                                match addr {
-                                    IpAddr::V4(_) => {
-                                        write!(ret, "Pong from {:?} seq {} received: {} ms",
-                                        addr,
+                                if is_ipv6 {
+                                    write!(ret, "Ipv6 pong from ...:{:x} seq {} received: {} ms",
+                                        *arg2 as u32,
                                         seq_or_addr,
                                         timestamp).unwrap();
-                                        log::info!("{}NET.PONG,{:?},{},{},{}",
-                                            xous::BOOKEND_START,
-                                            addr,
-                                            seq_or_addr,
-                                            timestamp,
-                                            xous::BOOKEND_END
-                                        );
-                                    },
-                                    IpAddr::V6(_) => {
-                                        write!(ret, "Ipv6 pong received: {} ms", timestamp).unwrap();
-                                    },
+                                } else {
+                                    write!(ret, "Pong from {:?} seq {} received: {} ms",
+                                    addr,
+                                    seq_or_addr,
+                                    timestamp).unwrap();
+                                    log::info!("{}NET.PONG,{:?},{},{},{}",
+                                        xous::BOOKEND_START,
+                                        addr,
+                                        seq_or_addr,
+                                        timestamp,
+                                        xous::BOOKEND_END
+                                    );
                                 }
                             }
                             Some(NetPingCallback::Timeout) => {
-                                write!(ret, "Ping to {:?} timed out", addr).unwrap();
+                                if is_ipv6 {
+                                    write!(ret, "Ping to ...:{:x} timed out", *arg2 as u32).unwrap();
+                                } else {
+                                    write!(ret, "Ping to {:?} timed out", addr).unwrap();
+                                }
                             }
                             Some(NetPingCallback::Unreachable) => {
-                                let code = net::Icmpv4DstUnreachable::from((op >> 24) as u8);
-                                write!(ret, "Ping to {:?} unreachable: {:?}", addr, code).unwrap();
+                                if is_ipv6 {
+                                    let code = net::Icmpv6DstUnreachable::from((op >> 24) as u8);
+                                    write!(ret, "Ping to ...:{:x} unreachable: {:?}", *arg2 as u32, code).unwrap();
+                                } else {
+                                    let code = net::Icmpv4DstUnreachable::from((op >> 24) as u8);
+                                    write!(ret, "Ping to {:?} unreachable: {:?}", addr, code).unwrap();
+                                }
                             }
                             None => {
                                 log::error!("Unknown opcode received in NetCmd callback: {:?}", op);
@@ -23,9 +23,9 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
     fn process(&mut self, args: String::<1024>, _env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
         let mut ret = String::<1024>::new();
         #[cfg(not(feature="pddbtest"))]
-        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [query] [dictdelete] [keydelete] [churn]";
+        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [query] [dictdelete] [keydelete] [churn] [defrag] [quota]";
         #[cfg(feature="pddbtest")]
-        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [query] [dictdelete] [keydelete]\n[test]";
+        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [query] [dictdelete] [keydelete] [defrag] [quota]\n[test]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
         if let Some(sub_cmd) = tokens.next() {
@@ -255,6 +255,43 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
                     write!(ret, "Sync result code: {:?}\n", self.pddb.sync()).ok();
                     write!(ret, "Flush result code: {:?}", self.pddb.flush_space_update()).ok();
                 }
+                "defrag" => {
+                    match self.pddb.compact() {
+                        Ok(report) => {
+                            write!(ret, "Small pool pages: {} -> {} (packed: {})\n",
+                                report.before.small_pages, report.after.small_pages, report.after.small_pages_needed).ok();
+                            write!(ret, "Large pool slots: {} -> {} ({} keys)\n",
+                                report.before.large_slots, report.after.large_slots, report.after.large_keys).ok();
+                            write!(ret, "{} keys moved, {} pages freed", report.keys_moved, report.pages_freed).ok();
+                        }
+                        Err(e) => write!(ret, "Defrag failed: {:?}", e).unwrap(),
+                    }
+                }
+                "quota" => {
+                    // `quota [dict]` shows the usage; `quota <dict> <bytes|none>` sets the quota. Use `-` for the whole basis.
+                    let dict = match tokens.next() {
+                        Some("-") | None => None,
+                        Some(dict) => Some(dict),
+                    };
+                    if let Some(limit) = tokens.next() {
+                        let limit = if limit == "none" { Ok(None) } else { limit.parse::<u64>().map(Some) };
+                        match limit {
+                            Ok(limit) => match self.pddb.set_quota(None, dict, limit) {
+                                Ok(_) => write!(ret, "Quota set").unwrap(),
+                                Err(e) => write!(ret, "Couldn't set quota: {:?}", e).unwrap(),
+                            },
+                            Err(_) => write!(ret, "Quota must be a number of bytes, or `none`").unwrap(),
+                        }
+                    } else {
+                        match self.pddb.quota_usage(None, dict) {
+                            Ok(usage) => match usage.limit {
+                                Some(limit) => write!(ret, "{} of {} bytes used", usage.used, limit).unwrap(),
+                                None => write!(ret, "{} bytes used, no quota", usage.used).unwrap(),
+                            },
+                            Err(e) => write!(ret, "Couldn't get quota usage: {:?}", e).unwrap(),
+                        }
+                    }
+                }
                 #[cfg(feature="test-rekey")]
                 "rekey" => {
                     let old_dna = if let Some(dna_str) = tokens.next() {
@@ -288,6 +288,7 @@ pub fn start_time_server() {
             log::debug!("tz_key: {}", tz_offset_ms / 1000);
             log::debug!("start_rtc_secs: {}", start_rtc_secs);
             log::debug!("start_tt_ms: {}", start_tt_ms);
+            pddb_time_reference(&offset_handle, &tt, start_rtc_secs, start_tt_ms, utc_offset_ms);
             loop {
                 let msg = xous::receive_message(pub_sid).unwrap();
                 let opcode: Option<TimeOp> = FromPrimitive::from_usize(msg.body.id());
@@ -310,6 +311,7 @@ pub fn start_time_server() {
                             Ok(val) => {
                                 start_rtc_secs = val;
                                 start_tt_ms = tt.elapsed_ms();
+                                pddb_time_reference(&offset_handle, &tt, start_rtc_secs, start_tt_ms, utc_offset_ms);
                             }
                             Err(e) => {
                                 log::warn!("Error syncing time: {:?}; retrying!", e);
@@ -361,6 +363,7 @@ pub fn start_time_server() {
                         log::info!("setting offset to {} secs", offset / 1000);
                         assert_eq!(offset_key.write(&offset.to_le_bytes()).unwrap_or(0), 8, "couldn't commit UTC time offset to PDDB");
                         offset_key.flush().expect("couldn't flush PDDB");
+                        pddb_time_reference(&offset_handle, &tt, start_rtc_secs, start_tt_ms, utc_offset_ms);
                     }),
                     Some(TimeOp::SetTzOffsetMs) => xous::msg_scalar_unpack!(msg, tz_hi_ms, tz_lo_ms, _, _, {
                         let tz_ms = ((tz_hi_ms as i64) << 32) | (tz_lo_ms as i64);
@@ -482,6 +485,22 @@ pub fn start_time_server() {
     });
 }
 
+/// Gives the PDDB a wall-clock reference for stamping key metadata, once the UTC offset is known. The
+/// reference is pushed rather than pulled, because the PDDB can't call into this server while we are
+/// blocked on a PDDB write.
+fn pddb_time_reference(pddb: &Pddb, tt: &ticktimer_server::Ticktimer, start_rtc_secs: u64, start_tt_ms: u64, utc_offset_ms: i64) {
+    if utc_offset_ms == 0 {
+        return;
+    }
+    let t =
+        start_rtc_secs as i64 * 1000i64
+        + (tt.elapsed_ms() - start_tt_ms) as i64
+        + utc_offset_ms;
+    if t > 0 {
+        pddb.set_time_reference(t as u64).ok();
+    }
+}
+
 #[allow(dead_code)]
 fn is_rtc_invalid(settings: &[u8]) -> bool {
     ((settings[CTL3] & 0xE0) != (Control3::BATT_STD_BL_EN).bits()) // power switchover setting should be initialized
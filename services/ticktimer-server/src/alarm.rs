@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+
+use crate::api::AlarmRequest;
+use crate::TimeoutExpiry;
+
+/// Alarms are kept in milliseconds, because that is the resolution of the timer that
+/// wakes us up.
+pub const RESOLUTION_US: u64 = 1000;
+
+/// Convert a duration in microseconds to whole ticks, rounding up so that an alarm
+/// never expires early.
+fn us_to_ticks(us: u64) -> TimeoutExpiry {
+    ((us + RESOLUTION_US - 1) / RESOLUTION_US) as TimeoutExpiry
+}
+
+/// How alarms reach the servers they are meant for. The ticktimer talks to the kernel,
+/// while the tests keep track of what would have been sent.
+pub trait Postman {
+    /// The process that created the server
+    fn owner(&self, sid: [u32; 4]) -> Option<xous::PID>;
+    fn connect(&mut self, sid: [u32; 4]) -> Option<xous::CID>;
+    fn disconnect(&mut self, cid: xous::CID);
+    fn try_send(&mut self, cid: xous::CID, message: xous::Message) -> Result<(), xous::Error>;
+}
+
+pub struct Kernel;
+
+impl Postman for Kernel {
+    fn owner(&self, sid: [u32; 4]) -> Option<xous::PID> {
+        xous::server_owner(xous::SID::from_array(sid)).ok()
+    }
+
+    fn connect(&mut self, sid: [u32; 4]) -> Option<xous::CID> {
+        // Don't block the ticktimer waiting for a server that doesn't exist yet
+        xous::try_connect(xous::SID::from_array(sid)).ok()
+    }
+
+    fn disconnect(&mut self, cid: xous::CID) {
+        unsafe { xous::disconnect(cid).ok() };
+    }
+
+    fn try_send(&mut self, cid: xous::CID, message: xous::Message) -> Result<(), xous::Error> {
+        xous::try_send_message(cid, message).map(|_| ())
+    }
+}
+
+struct Alarm {
+    owner: Option<xous::PID>,
+    sid: [u32; 4],
+    cid: xous::CID,
+    opcode: usize,
+    data: usize,
+    /// When the alarm next expires, in ms since boot
+    deadline: TimeoutExpiry,
+    period: Option<TimeoutExpiry>,
+    /// Expirations of a periodic alarm that could not be delivered because the
+    /// server's queue was full
+    missed: TimeoutExpiry,
+}
+
+/// Every alarm that clients have started, along with the connections that alarms are
+/// delivered over. Connections are shared between alarms that go to the same server.
+pub struct Alarms<P: Postman = Kernel> {
+    postman: P,
+    alarms: HashMap<u32, Alarm>,
+    connections: HashMap<[u32; 4], (xous::CID, usize)>,
+    next_handle: u32,
+    /// When the kernel asked to be woken up, so that it can expire timeouts
+    kernel_wakeup: Option<TimeoutExpiry>,
+}
+
+impl Alarms {
+    pub fn new() -> Self {
+        Alarms::with_postman(Kernel)
+    }
+}
+
+impl<P: Postman> Alarms<P> {
+    pub fn with_postman(postman: P) -> Self {
+        Alarms {
+            postman,
+            alarms: HashMap::new(),
+            connections: HashMap::new(),
+            next_handle: 1,
+            kernel_wakeup: None,
+        }
+    }
+
+    fn connect(&mut self, sid: [u32; 4]) -> Option<xous::CID> {
+        if let Some((cid, count)) = self.connections.get_mut(&sid) {
+            *count += 1;
+            return Some(*cid);
+        }
+        let cid = self.postman.connect(sid)?;
+        self.connections.insert(sid, (cid, 1));
+        Some(cid)
+    }
+
+    fn release(&mut self, sid: [u32; 4]) {
+        if let Some((cid, count)) = self.connections.get_mut(&sid) {
+            *count -= 1;
+            if *count == 0 {
+                self.postman.disconnect(*cid);
+                self.connections.remove(&sid);
+            }
+        }
+    }
+
+    /// Start a new alarm for `owner`, returning its handle. Alarms may only be sent to a
+    /// server that `owner` created, so that nobody can use the ticktimer to send
+    /// arbitrary messages to someone else's server.
+    pub fn start(
+        &mut self,
+        owner: Option<xous::PID>,
+        request: &AlarmRequest,
+        now: TimeoutExpiry,
+    ) -> Option<u32> {
+        if request.period_us == Some(0) {
+            return None;
+        }
+        if owner.is_none() || self.postman.owner(request.sid) != owner {
+            log::warn!(
+                "{:?} tried to start an alarm for a server it doesn't own",
+                owner
+            );
+            return None;
+        }
+        let cid = self.connect(request.sid)?;
+        let mut handle = self.next_handle;
+        while handle == 0 || self.alarms.contains_key(&handle) {
+            handle = handle.wrapping_add(1);
+        }
+        self.next_handle = handle.wrapping_add(1);
+        self.alarms.insert(
+            handle,
+            Alarm {
+                owner,
+                sid: request.sid,
+                cid,
+                opcode: request.opcode as usize,
+                data: request.data as usize,
+                deadline: now + us_to_ticks(request.delay_us),
+                period: request.period_us.map(us_to_ticks),
+                missed: 0,
+            },
+        );
+        Some(handle)
+    }
+
+    /// Change the schedule of an alarm that belongs to `owner`
+    pub fn reschedule(
+        &mut self,
+        owner: Option<xous::PID>,
+        request: &AlarmRequest,
+        now: TimeoutExpiry,
+    ) -> bool {
+        if request.period_us == Some(0) {
+            return false;
+        }
+        match request
+            .handle
+            .and_then(|handle| self.alarms.get_mut(&handle))
+        {
+            Some(alarm) if alarm.owner == owner => {
+                alarm.deadline = now + us_to_ticks(request.delay_us);
+                alarm.period = request.period_us.map(us_to_ticks);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stop an alarm that belongs to `owner`
+    pub fn cancel(&mut self, owner: Option<xous::PID>, handle: u32) -> bool {
+        match self.alarms.get(&handle) {
+            Some(alarm) if alarm.owner == owner => {
+                let sid = alarm.sid;
+                self.alarms.remove(&handle);
+                self.release(sid);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Make sure the timer goes off by `deadline`. The interrupt alone is what wakes
+    /// the kernel, so nothing is delivered.
+    pub fn wake_kernel_at(&mut self, deadline: TimeoutExpiry) {
+        self.kernel_wakeup = Some(match self.kernel_wakeup {
+            Some(wakeup) => wakeup.min(deadline),
+            None => deadline,
+        });
+    }
+
+    /// The time at which the next alarm expires, or the kernel needs to run
+    pub fn next_deadline(&self) -> Option<TimeoutExpiry> {
+        self.alarms
+            .values()
+            .map(|alarm| alarm.deadline)
+            .chain(self.kernel_wakeup)
+            .min()
+    }
+
+    /// Deliver every alarm that has expired by `now`. One-shot alarms are removed once
+    /// they are delivered, and alarms whose server has gone away are dropped.
+    pub fn deliver(&mut self, now: TimeoutExpiry) {
+        if self.kernel_wakeup.map_or(false, |wakeup| wakeup <= now) {
+            self.kernel_wakeup = None;
+        }
+        let mut finished = Vec::new();
+        for (&handle, alarm) in self.alarms.iter_mut() {
+            if alarm.deadline > now {
+                continue;
+            }
+            // A periodic alarm that is late only gets delivered once, along with the
+            // number of periods that have passed.
+            let expirations = match alarm.period {
+                Some(period) => (now - alarm.deadline) / period + 1,
+                None => 1,
+            };
+            let next_deadline = alarm
+                .period
+                .map(|period| alarm.deadline + expirations * period);
+            let result = self.postman.try_send(
+                alarm.cid,
+                xous::Message::new_scalar(
+                    alarm.opcode,
+                    handle as usize,
+                    alarm.data,
+                    (alarm.missed + expirations) as usize,
+                    0,
+                ),
+            );
+            match result {
+                Ok(_) => match next_deadline {
+                    Some(deadline) => {
+                        alarm.deadline = deadline;
+                        alarm.missed = 0;
+                    }
+                    None => finished.push((handle, alarm.sid)),
+                },
+                Err(xous::Error::ServerQueueFull) => {
+                    // Periodic alarms report the miss with their next delivery, while
+                    // one-shot alarms try again on the next tick.
+                    log::trace!("alarm {} could not be delivered", handle);
+                    match next_deadline {
+                        Some(deadline) => {
+                            alarm.deadline = deadline;
+                            alarm.missed += expirations;
+                        }
+                        None => alarm.deadline = now + 1,
+                    }
+                }
+                Err(e) => {
+                    log::info!(
+                        "dropping alarm {}, which could not be delivered: {:?}",
+                        handle,
+                        e
+                    );
+                    finished.push((handle, alarm.sid));
+                }
+            }
+        }
+        for (handle, sid) in finished {
+            self.alarms.remove(&handle);
+            self.release(sid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: [u32; 4] = [1, 2, 3, 4];
+    const OTHER_SERVER: [u32; 4] = [5, 6, 7, 8];
+
+    /// Delivers alarms into a list, and can pretend that a server's queue is full
+    #[derive(Default)]
+    struct Mailbox {
+        connected: Vec<xous::CID>,
+        sent: Vec<(xous::CID, usize, usize, usize)>,
+        full: bool,
+    }
+
+    impl Postman for Mailbox {
+        fn owner(&self, sid: [u32; 4]) -> Option<xous::PID> {
+            xous::PID::new(sid[0] as u8)
+        }
+
+        fn connect(&mut self, sid: [u32; 4]) -> Option<xous::CID> {
+            self.connected.push(sid[0] as xous::CID);
+            Some(sid[0] as xous::CID)
+        }
+
+        fn disconnect(&mut self, cid: xous::CID) {
+            self.connected.retain(|&connected| connected != cid);
+        }
+
+        fn try_send(&mut self, cid: xous::CID, message: xous::Message) -> Result<(), xous::Error> {
+            if self.full {
+                return Err(xous::Error::ServerQueueFull);
+            }
+            if let xous::Message::Scalar(scalar) = message {
+                self.sent.push((cid, scalar.id, scalar.arg1, scalar.arg3));
+            }
+            Ok(())
+        }
+    }
+
+    fn pid(sid: [u32; 4]) -> Option<xous::PID> {
+        xous::PID::new(sid[0] as u8)
+    }
+
+    fn request(sid: [u32; 4], opcode: u32, delay_us: u64, period_us: Option<u64>) -> AlarmRequest {
+        AlarmRequest {
+            sid,
+            opcode,
+            data: 0,
+            delay_us,
+            period_us,
+            handle: None,
+        }
+    }
+
+    #[test]
+    fn rounds_up_to_ticks() {
+        assert_eq!(us_to_ticks(0), 0);
+        assert_eq!(us_to_ticks(1), 1);
+        assert_eq!(us_to_ticks(RESOLUTION_US), 1);
+        assert_eq!(us_to_ticks(RESOLUTION_US + 1), 2);
+    }
+
+    #[test]
+    fn only_owner_may_target_server() {
+        let mut alarms = Alarms::with_postman(Mailbox::default());
+        assert_eq!(
+            alarms.start(pid(OTHER_SERVER), &request(SERVER, 1, 0, None), 0),
+            None
+        );
+        assert_eq!(alarms.start(None, &request(SERVER, 1, 0, None), 0), None);
+        assert!(alarms
+            .start(pid(SERVER), &request(SERVER, 1, 0, None), 0)
+            .is_some());
+    }
+
+    #[test]
+    fn delivers_in_deadline_order() {
+        let mut alarms = Alarms::with_postman(Mailbox::default());
+        alarms
+            .start(pid(SERVER), &request(SERVER, 3, 3000, None), 0)
+            .unwrap();
+        alarms
+            .start(pid(SERVER), &request(SERVER, 1, 1000, None), 0)
+            .unwrap();
+        alarms
+            .start(pid(SERVER), &request(SERVER, 2, 2000, None), 0)
+            .unwrap();
+        assert_eq!(alarms.postman.connected, vec![1]);
+
+        for now in 0..=3 {
+            assert_eq!(alarms.next_deadline(), Some(now.max(1)));
+            alarms.deliver(now);
+        }
+        let opcodes: Vec<usize> = alarms.postman.sent.iter().map(|sent| sent.1).collect();
+        assert_eq!(opcodes, vec![1, 2, 3]);
+        assert_eq!(alarms.next_deadline(), None);
+        assert!(alarms.postman.connected.is_empty());
+    }
+
+    #[test]
+    fn cancel_only_by_owner() {
+        let mut alarms = Alarms::with_postman(Mailbox::default());
+        let handle = alarms
+            .start(pid(SERVER), &request(SERVER, 1, 1000, None), 0)
+            .unwrap();
+        assert!(!alarms.cancel(pid(OTHER_SERVER), handle));
+        assert!(!alarms.cancel(pid(SERVER), handle + 1));
+        assert!(alarms.cancel(pid(SERVER), handle));
+        assert!(!alarms.cancel(pid(SERVER), handle));
+        assert!(alarms.postman.connected.is_empty());
+
+        alarms.deliver(10);
+        assert!(alarms.postman.sent.is_empty());
+    }
+
+    #[test]
+    fn periodic_alarms_rearm() {
+        let mut alarms = Alarms::with_postman(Mailbox::default());
+        let handle = alarms
+            .start(pid(SERVER), &request(SERVER, 1, 1000, Some(10_000)), 0)
+            .unwrap();
+
+        alarms.deliver(1);
+        assert_eq!(alarms.next_deadline(), Some(11));
+        assert_eq!(alarms.postman.sent, vec![(1, 1, handle as usize, 1)]);
+
+        // A late delivery counts every period that went by, and stays on schedule
+        alarms.deliver(35);
+        assert_eq!(alarms.next_deadline(), Some(41));
+        assert_eq!(alarms.postman.sent[1], (1, 1, handle as usize, 3));
+
+        // Periods that can't be delivered are reported with the next one
+        alarms.postman.full = true;
+        alarms.deliver(41);
+        alarms.postman.full = false;
+        alarms.deliver(51);
+        assert_eq!(alarms.postman.sent[2], (1, 1, handle as usize, 2));
+        assert_eq!(alarms.next_deadline(), Some(61));
+
+        assert!(alarms.reschedule(
+            pid(SERVER),
+            &AlarmRequest {
+                handle: Some(handle),
+                ..request(SERVER, 1, 5000, None)
+            },
+            60
+        ));
+        alarms.deliver(65);
+        assert_eq!(alarms.next_deadline(), None);
+        assert_eq!(alarms.postman.sent.len(), 4);
+    }
+
+    #[test]
+    fn kernel_wakeups_set_the_timer() {
+        let mut alarms = Alarms::with_postman(Mailbox::default());
+        alarms
+            .start(pid(SERVER), &request(SERVER, 1, 20_000, None), 0)
+            .unwrap();
+        alarms.wake_kernel_at(30);
+        assert_eq!(alarms.next_deadline(), Some(20));
+        alarms.wake_kernel_at(10);
+        alarms.wake_kernel_at(15);
+        assert_eq!(alarms.next_deadline(), Some(10));
+
+        // The wakeup is used up without sending anything
+        alarms.deliver(10);
+        assert!(alarms.postman.sent.is_empty());
+        assert_eq!(alarms.next_deadline(), Some(20));
+        alarms.deliver(20);
+        assert_eq!(alarms.postman.sent.len(), 1);
+        assert_eq!(alarms.next_deadline(), None);
+    }
+}
@@ -48,9 +48,78 @@ pub(crate) enum Opcode {
     /// *arg1*: An integer of some sort, such as the address of the Condvar
     /// *arg2*: The number of conditions to notify
     NotifyCondition = 9,
+
+    /// Start an alarm that is delivered as a message to the caller's own server.
+    ///
+    /// # Message Types
+    ///
+    ///     * MutableLend
+    ///
+    /// # Arguments
+    ///
+    /// An `AlarmRequest`. `handle` is ignored, and is overwritten with the new alarm's
+    /// handle, or `None` if the alarm could not be started. `sid` must be a server that
+    /// the calling process created.
+    ///
+    /// # Delivery
+    ///
+    /// Each time the alarm expires, a non-blocking scalar message is sent to `sid`:
+    ///
+    /// *id*: `opcode`
+    /// *arg1*: The alarm handle
+    /// *arg2*: `data`
+    /// *arg3*: The number of times the alarm expired since it was last delivered. This is
+    /// usually 1, but a periodic alarm that could not be delivered on time is only
+    /// delivered once when the server catches up.
+    SetAlarm = 10,
+
+    /// Change the delay and period of an existing alarm. Only the process that started
+    /// the alarm may reschedule it.
+    ///
+    /// # Message Types
+    ///
+    ///     * MutableLend
+    ///
+    /// # Arguments
+    ///
+    /// An `AlarmRequest` whose `handle` names the alarm. Its `delay_us` and `period_us`
+    /// replace the alarm's schedule; the destination is left unchanged. `handle` is set
+    /// to `None` if the alarm does not exist.
+    RescheduleAlarm = 11,
+
+    /// Stop an alarm. Only the process that started the alarm may cancel it.
+    ///
+    /// # Arguments
+    ///
+    /// *arg1*: The alarm handle
+    ///
+    /// # Returns
+    ///
+    /// Scalar1: 1 if the alarm was cancelled, or 0 if it did not exist
+    CancelAlarm = 12,
+
+    /// Return the resolution of alarms, in microseconds. Alarm delays and periods are
+    /// rounded up to a multiple of this.
+    AlarmResolutionUs = 13,
 }
 
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct VersionString {
     pub version: xous_ipc::String::<512>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct AlarmRequest {
+    /// The server that alarm messages are sent to
+    pub sid: [u32; 4],
+    /// The message ID of alarm messages
+    pub opcode: u32,
+    /// Passed back in `arg2` of each alarm message
+    pub data: u32,
+    /// Time until the alarm first expires
+    pub delay_us: u64,
+    /// If present, the alarm repeats with this period after it first expires
+    pub period_us: Option<u64>,
+    /// The alarm that this request refers to
+    pub handle: Option<u32>,
+}
@@ -2,6 +2,7 @@
 
 pub mod api;
 
+use core::time::Duration;
 use num_traits::ToPrimitive;
 use xous::{send_message, Error, CID};
 use xous_semver::SemVer;
@@ -171,8 +172,121 @@ impl Ticktimer {
         .map(|r| r == xous::Result::Scalar1(0))
         .expect("couldn't notify condition");
     }
+
+    /// Start an alarm that sends a scalar message with ID `opcode` to the server `sid`,
+    /// which must belong to this process, once `delay` has passed, and then every `period`
+    /// if one is given. This replaces a thread that loops over `sleep_ms()`, and leaves
+    /// the caller free to do other work.
+    ///
+    /// Alarm messages carry the alarm's ID in `arg1`, `data` in `arg2`, and in `arg3` the
+    /// number of times the alarm expired since it was last delivered, which is more than 1
+    /// if the server fell behind on a periodic alarm.
+    ///
+    /// Times are rounded up to the ticktimer's resolution; see `alarm_resolution()`.
+    ///
+    /// # Errors
+    ///
+    ///     * ServerNotFound: `sid` does not exist or belongs to another process, or
+    ///       `period` is zero
+    pub fn set_alarm(
+        &self,
+        sid: xous::SID,
+        opcode: usize,
+        data: u32,
+        delay: Duration,
+        period: Option<Duration>,
+    ) -> Result<AlarmId, Error> {
+        let request = api::AlarmRequest {
+            sid: sid.to_array(),
+            opcode: opcode as u32,
+            data,
+            delay_us: delay.as_micros() as u64,
+            period_us: period.map(|p| p.as_micros() as u64),
+            handle: None,
+        };
+        let mut buf = xous_ipc::Buffer::into_buf(request).or(Err(Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::SetAlarm.to_u32().unwrap())
+            .or(Err(Error::InternalError))?;
+        let response = buf
+            .to_original::<api::AlarmRequest, _>()
+            .or(Err(Error::InternalError))?;
+        response.handle.map(AlarmId).ok_or(Error::ServerNotFound)
+    }
+
+    /// Change when an alarm next expires and how often it repeats
+    ///
+    /// # Errors
+    ///
+    ///     * ServerNotFound: the alarm has already finished, or belongs to another process
+    pub fn reschedule_alarm(
+        &self,
+        alarm: AlarmId,
+        delay: Duration,
+        period: Option<Duration>,
+    ) -> Result<(), Error> {
+        let request = api::AlarmRequest {
+            sid: [0; 4],
+            opcode: 0,
+            data: 0,
+            delay_us: delay.as_micros() as u64,
+            period_us: period.map(|p| p.as_micros() as u64),
+            handle: Some(alarm.0),
+        };
+        let mut buf = xous_ipc::Buffer::into_buf(request).or(Err(Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::RescheduleAlarm.to_u32().unwrap())
+            .or(Err(Error::InternalError))?;
+        let response = buf
+            .to_original::<api::AlarmRequest, _>()
+            .or(Err(Error::InternalError))?;
+        response.handle.map(|_| ()).ok_or(Error::ServerNotFound)
+    }
+
+    /// Stop an alarm. Returns `false` if the alarm had already finished.
+    pub fn cancel_alarm(&self, alarm: AlarmId) -> bool {
+        send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::CancelAlarm.to_usize().unwrap(),
+                alarm.0 as usize,
+                0,
+                0,
+                0,
+            ),
+        )
+        .map(|r| r == xous::Result::Scalar1(1))
+        .expect("couldn't cancel alarm")
+    }
+
+    /// The granularity of alarm times. Alarms never expire early, so delays and periods
+    /// are rounded up to a multiple of this.
+    pub fn alarm_resolution(&self) -> Duration {
+        let response = send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::AlarmResolutionUs.to_usize().unwrap(),
+                0,
+                0,
+                0,
+                0,
+            ),
+        )
+        .expect("couldn't query alarm resolution");
+        if let xous::Result::Scalar1(us) = response {
+            Duration::from_micros(us as u64)
+        } else {
+            panic!(
+                "Ticktimer alarm_resolution(): unexpected return value: {:#?}",
+                response
+            );
+        }
+    }
 }
 
+/// Identifies an alarm started with `Ticktimer::set_alarm()`. Alarm messages carry this
+/// in `arg1`, so `AlarmId(arg1 as u32)` can be compared against it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AlarmId(pub u32);
+
 use core::sync::atomic::{AtomicU32, Ordering};
 static REFCOUNT: AtomicU32 = AtomicU32::new(0);
 impl Drop for Ticktimer {
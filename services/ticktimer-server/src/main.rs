@@ -2,6 +2,7 @@
 #![cfg_attr(target_os = "none", no_std)]
 #![cfg_attr(target_os = "none", no_main)]
 
+mod alarm;
 mod api;
 mod version;
 
@@ -15,6 +16,8 @@ type TimeoutExpiry = i64;
 pub enum RequestKind {
     Sleep = 0,
     Timeout = 1,
+    /// Wakes the ticktimer to deliver alarms. There is no sender waiting on these.
+    Alarm = 2,
 }
 
 #[derive(Eq)]
@@ -84,8 +87,10 @@ mod implementation {
         // Safe because we're in an interrupt, and this interrupt is only
         // enabled when this value is not None.
         let response = xtt.current_response.take().unwrap();
-        xous::return_scalar(response.sender, response.kind as usize)
-            .expect("couldn't send response");
+        if response.kind != crate::RequestKind::Alarm {
+            xous::return_scalar(response.sender, response.kind as usize)
+                .expect("couldn't send response");
+        }
 
         // Disable the timer
         xtt.csr.wfo(utra::ticktimer::EV_ENABLE_ALARM, 0);
@@ -298,6 +303,8 @@ mod implementation {
             xous::MessageSender,
             i64, /* ms */
             u64, /* elapsed */
+            RequestKind,
+            usize, /* data */
         ),
     }
     pub struct XousTickTimer {
@@ -325,8 +332,10 @@ mod implementation {
                             let response = current_response.take().unwrap();
                             #[cfg(feature = "debug-print")]
                             log::info!("Returning scalar to {}", response.sender);
-                            xous::return_scalar(response.sender, response.kind as usize)
-                                .expect("couldn't send response");
+                            if response.kind != RequestKind::Alarm {
+                                xous::return_scalar(response.sender, response.kind as usize)
+                                    .expect("couldn't send response");
+                            }
 
                             // This is dangerous and may panic if the queue is full.
                             xous::try_send_message(
@@ -349,7 +358,7 @@ mod implementation {
                             timeout = None;
                             time_remaining_sender.send(current_response.take()).unwrap()
                         }
-                        Ok(SleepComms::StartSleep(new_sender, expiry, elapsed)) => {
+                        Ok(SleepComms::StartSleep(new_sender, expiry, elapsed, kind, data)) => {
                             let mut duration = expiry - (elapsed as i64);
                             if duration > 0 {
                                 #[cfg(feature = "debug-print")]
@@ -373,8 +382,8 @@ mod implementation {
                             current_response = Some(TimerRequest {
                                 sender: new_sender,
                                 msec: expiry,
-                                kind: RequestKind::Sleep,
-                                data: 0,
+                                kind,
+                                data,
                             });
                         }
                     }
@@ -415,6 +424,8 @@ mod implementation {
                     request.sender,
                     request.msec as i64,
                     self.elapsed_ms(),
+                    request.kind,
+                    request.data,
                 ))
                 .unwrap();
         }
@@ -516,6 +527,35 @@ fn recalculate_sleep(
     start_sleep(ticktimer, sleep_heap);
 }
 
+/// Deliver any alarms that have expired, then make sure the timer wakes us in time for
+/// the next one. Alarms are represented in the sleep heap by a single `RequestKind::Alarm`
+/// entry at the earliest alarm deadline.
+fn service_alarms(
+    ticktimer: &mut XousTickTimer,
+    sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>,
+    alarms: &mut alarm::Alarms,
+) {
+    alarms.deliver(ticktimer.elapsed_ms() as i64);
+
+    stop_sleep(ticktimer, sleep_heap);
+    sleep_heap.retain(|_, v| v.kind != RequestKind::Alarm);
+    if let Some(mut msec) = alarms.next_deadline() {
+        while sleep_heap.contains_key(&msec) {
+            msec += 1;
+        }
+        sleep_heap.insert(
+            msec,
+            TimerRequest {
+                msec,
+                sender: xous::MessageSender::from_usize(0),
+                kind: RequestKind::Alarm,
+                data: 0,
+            },
+        );
+    }
+    start_sleep(ticktimer, sleep_heap);
+}
+
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
@@ -586,6 +626,9 @@ fn main() -> ! {
     let mut mutex_hash: HashMap<Option<xous::PID>, HashMap<usize, VecDeque<xous::MessageSender>>> =
         HashMap::new();
 
+    // Alarms that clients have started, which are delivered as messages to their servers
+    let mut alarms = alarm::Alarms::new();
+
     loop {
         #[cfg(feature = "watchdog")]
         ticktimer.reset_wdt();
@@ -594,6 +637,25 @@ fn main() -> ! {
 
         let mut msg = xous::receive_message(ticktimer_server).unwrap();
         log::trace!("msg: {:x?}", msg);
+
+        // The interrupt's `RecalculateSleep` message can be lost if our queue is full, so
+        // catch up on any overdue alarms whenever a message arrives.
+        if alarms
+            .next_deadline()
+            .map_or(false, |deadline| deadline <= ticktimer.elapsed_ms() as i64)
+        {
+            service_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms);
+        }
+
+        // The kernel needs to run by a certain time, to expire IPC timeouts
+        if msg.sender.pid().map(|p| p.get()) == Some(1) {
+            if let Some(wakeup) = xous::KernelWakeup::from_message(&msg.body) {
+                alarms.wake_kernel_at(wakeup.deadline as TimeoutExpiry);
+                service_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms);
+                continue;
+            }
+        }
+
         match num_traits::FromPrimitive::from_usize(msg.body.id()) {
             Some(api::Opcode::ElapsedMs) => {
                 let time = ticktimer.elapsed_ms() as i64;
@@ -625,13 +687,22 @@ fn main() -> ! {
                     let request_kind = args.arg2;
                     let condvar = args.arg3;
                     let sender_pid = xous::MessageSender::from_usize(sender).pid();
+                    let from_ticktimer = (msg.sender.pid().map(|p| p.get()).unwrap_or_default()
+                        as u32)
+                        == xous::process::id();
+
+                    // An alarm fired, so deliver it and schedule the next one
+                    if from_ticktimer && (request_kind == RequestKind::Alarm as usize) {
+                        service_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms);
+                        continue;
+                    }
 
                     // If we're being asked to recalculate due to a timeout expiring, drop the sent
                     // message from the `entries` list.
                     // the first check confirms that the origin of the RecalculateSleep message is the Ticktimer,
                     // to prevent third-party servers from issuing the command and thus distorting the sleep
                     // calculations (since this is a public API, anything could happen).
-                    if (msg.sender.pid().map(|p| p.get()).unwrap_or_default() as u32) == xous::process::id()
+                    if from_ticktimer
                         && (request_kind == RequestKind::Timeout as usize)
                         && (sender > 0)
                     {
@@ -845,6 +916,45 @@ fn main() -> ! {
                     );
                 }
             }
+            Some(api::Opcode::SetAlarm) => {
+                let mut buffer = unsafe {
+                    xous_ipc::Buffer::from_memory_message_mut(
+                        msg.body.memory_message_mut().unwrap(),
+                    )
+                };
+                let mut request = buffer.to_original::<api::AlarmRequest, _>().unwrap();
+                request.handle =
+                    alarms.start(msg.sender.pid(), &request, ticktimer.elapsed_ms() as i64);
+                buffer.replace(request).unwrap();
+                service_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms);
+            }
+            Some(api::Opcode::RescheduleAlarm) => {
+                let mut buffer = unsafe {
+                    xous_ipc::Buffer::from_memory_message_mut(
+                        msg.body.memory_message_mut().unwrap(),
+                    )
+                };
+                let mut request = buffer.to_original::<api::AlarmRequest, _>().unwrap();
+                if !alarms.reschedule(msg.sender.pid(), &request, ticktimer.elapsed_ms() as i64) {
+                    request.handle = None;
+                }
+                buffer.replace(request).unwrap();
+                service_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms);
+            }
+            Some(api::Opcode::CancelAlarm) => {
+                xous::msg_blocking_scalar_unpack!(msg, handle, _, _, _, {
+                    let cancelled = alarms.cancel(msg.sender.pid(), handle as u32);
+                    xous::return_scalar(msg.sender, cancelled as usize)
+                        .expect("couldn't return CancelAlarm");
+                    service_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms);
+                })
+            }
+            Some(api::Opcode::AlarmResolutionUs) => {
+                xous::msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                    xous::return_scalar(msg.sender, alarm::RESOLUTION_US as usize)
+                        .expect("couldn't return AlarmResolutionUs");
+                })
+            }
             None => {
                 error!("couldn't convert opcode");
             }
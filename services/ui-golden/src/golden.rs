@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// A greyscale image, one byte per pixel, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Lines `top..bottom` of a frame, which are left out of comparisons
+#[derive(Debug, Clone, Copy)]
+pub struct Band {
+    pub top: usize,
+    pub bottom: usize,
+}
+
+/// Grey level used in diff images for pixels that match
+const DIFF_SAME: u8 = 0xE0;
+/// Grey level used in diff images for pixels that differ
+const DIFF_CHANGED: u8 = 0x00;
+/// Grey level used in diff images for lines that are not compared
+const DIFF_IGNORED: u8 = 0x80;
+
+fn to_io_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, e)
+}
+
+impl Frame {
+    /// Load an 8-bit greyscale PNG, such as those written by `Gfx::screenshot()`
+    pub fn load(path: &Path) -> std::io::Result<Frame> {
+        let decoder = png::Decoder::new(File::open(path)?);
+        let mut reader = decoder.read_info().map_err(to_io_error)?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).map_err(to_io_error)?;
+        if info.color_type != png::ColorType::Grayscale || info.bit_depth != png::BitDepth::Eight {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "expected an 8-bit greyscale image",
+            ));
+        }
+        pixels.truncate(info.buffer_size());
+        Ok(Frame {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut encoder = png::Encoder::new(
+            BufWriter::new(File::create(path)?),
+            self.width as u32,
+            self.height as u32,
+        );
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(to_io_error)?;
+        writer.write_image_data(&self.pixels).map_err(to_io_error)
+    }
+
+    fn line_is_ignored(y: usize, ignore: &[Band]) -> bool {
+        ignore.iter().any(|band| band.top <= y && y < band.bottom)
+    }
+
+    /// Count the pixels that differ from `other`, skipping the lines in `ignore`.
+    /// Frames of different sizes differ in every pixel.
+    pub fn differences(&self, other: &Frame, ignore: &[Band]) -> usize {
+        if self.width != other.width || self.height != other.height {
+            return self.pixels.len().max(other.pixels.len());
+        }
+        self.pixels
+            .chunks(self.width)
+            .zip(other.pixels.chunks(other.width))
+            .enumerate()
+            .filter(|(y, _)| !Self::line_is_ignored(*y, ignore))
+            .map(|(_, (a, b))| a.iter().zip(b).filter(|(a, b)| a != b).count())
+            .sum()
+    }
+
+    /// An image showing where `other` differs from this frame: changed pixels are
+    /// black, matching pixels are pale, and lines that aren't compared are grey.
+    pub fn diff_image(&self, other: &Frame, ignore: &[Band]) -> Frame {
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for (y, (a, b)) in self
+            .pixels
+            .chunks(self.width)
+            .zip(other.pixels.chunks(other.width))
+            .enumerate()
+        {
+            if Self::line_is_ignored(y, ignore) {
+                pixels.resize(pixels.len() + self.width, DIFF_IGNORED);
+            } else {
+                let marks = a.iter().zip(b).map(|(a, b)| match a == b {
+                    true => DIFF_SAME,
+                    false => DIFF_CHANGED,
+                });
+                pixels.extend(marks);
+            }
+        }
+        pixels.resize(self.pixels.len(), DIFF_CHANGED);
+        Frame {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(rows: &[&[u8]]) -> Frame {
+        Frame {
+            width: rows[0].len(),
+            height: rows.len(),
+            pixels: rows.concat(),
+        }
+    }
+
+    #[test]
+    fn differences_skip_ignored_lines() {
+        let expected = frame(&[&[0, 0, 0], &[0xFF, 0xFF, 0xFF], &[0, 0xFF, 0]]);
+        let actual = frame(&[&[0xFF, 0, 0xFF], &[0xFF, 0xFF, 0xFF], &[0, 0, 0]]);
+        assert_eq!(expected.differences(&actual, &[]), 3);
+        let first_line = Band { top: 0, bottom: 1 };
+        assert_eq!(expected.differences(&actual, &[first_line]), 1);
+        assert_eq!(expected.differences(&expected.clone(), &[]), 0);
+    }
+
+    #[test]
+    fn frames_of_different_sizes_never_match() {
+        let small = frame(&[&[0, 0]]);
+        let large = frame(&[&[0, 0], &[0, 0]]);
+        assert_eq!(small.differences(&large, &[]), 4);
+    }
+
+    #[test]
+    fn diff_image_marks_changed_pixels() {
+        let expected = frame(&[&[0, 0], &[0, 0]]);
+        let actual = frame(&[&[0, 0xFF], &[0xFF, 0]]);
+        let diff = expected.diff_image(&actual, &[Band { top: 1, bottom: 2 }]);
+        assert_eq!(
+            diff.pixels,
+            vec![DIFF_SAME, DIFF_CHANGED, DIFF_IGNORED, DIFF_IGNORED]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Golden-image regression tests for the UI.
+//!
+//! This runs as one of the processes of a headless hosted system (`cargo xtask ui-golden`).
+//! It presses keys through the keyboard server, takes screenshots through the graphics
+//! server, and compares them with the reference images in `golden/`. Run
+//! `cargo xtask ui-golden --update` to replace the references with what is drawn now, or
+//! `cargo xtask ui-golden --bless` to also save candidates for the snapshots that have no
+//! reference yet, without failing on them.
+
+#[cfg(any(target_os = "none", target_os = "xous"))]
+compile_error!("ui-golden drives the hosted emulator, and can't run on hardware");
+
+mod golden;
+mod scenarios;
+
+use golden::Frame;
+use scenarios::{Step, IGNORED_LINES, SCENARIOS};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Reference images, checked in alongside this crate
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+/// Screenshots, diffs and the report from the latest run
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/ui-golden");
+/// Set by `cargo xtask ui-golden --update` to save new reference images instead of
+/// comparing against the old ones
+const UPDATE_VAR: &str = "UI_GOLDEN_UPDATE";
+/// Set by `cargo xtask ui-golden --bless` to save snapshots that have no reference yet
+/// under `OUTPUT_DIR/golden`, so they can be checked in, instead of failing on them
+const BLESS_VAR: &str = "UI_GOLDEN_BLESS";
+
+/// Time between injected keys, so that they aren't treated as a chord
+const KEY_INTERVAL_MS: usize = 50;
+/// How often to look at the screen while waiting for it to settle
+const SETTLE_POLL_MS: usize = 200;
+/// How long the screen must stay the same before it counts as settled
+const SETTLE_QUIET_MS: u64 = 1000;
+/// How long to wait for the screen to settle before comparing whatever is there
+const SETTLE_TIMEOUT_MS: u64 = 10_000;
+/// How long to wait for the system to finish booting
+const BOOT_TIMEOUT_MS: u64 = 60_000;
+
+struct Harness {
+    gfx: graphics_server::Gfx,
+    kbd: keyboard::Keyboard,
+    ticktimer: ticktimer_server::Ticktimer,
+    golden_dir: PathBuf,
+    output_dir: PathBuf,
+    update: bool,
+    bless: bool,
+    report: Vec<String>,
+    failures: usize,
+}
+
+impl Harness {
+    fn new(xns: &xous_names::XousNames) -> Harness {
+        let output_dir = PathBuf::from(OUTPUT_DIR);
+        std::fs::create_dir_all(&output_dir).expect("couldn't create the output directory");
+        let update = std::env::var_os(UPDATE_VAR).is_some();
+        if update {
+            std::fs::create_dir_all(GOLDEN_DIR).expect("couldn't create the golden directory");
+        }
+        Harness {
+            gfx: graphics_server::Gfx::new(xns).expect("couldn't connect to graphics server"),
+            kbd: keyboard::Keyboard::new(xns).expect("couldn't connect to keyboard"),
+            ticktimer: ticktimer_server::Ticktimer::new().expect("couldn't connect to ticktimer"),
+            golden_dir: PathBuf::from(GOLDEN_DIR),
+            output_dir,
+            update,
+            bless: std::env::var_os(BLESS_VAR).is_some(),
+            report: Vec::new(),
+            failures: 0,
+        }
+    }
+
+    fn capture(&self, name: &str) -> std::io::Result<Frame> {
+        let path = self.output_dir.join(format!("{}.png", name));
+        self.gfx
+            .screenshot(path.to_str().expect("output path is not UTF-8"))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))?;
+        Frame::load(&path)
+    }
+
+    /// Wait until the screen stops changing, and return what is on it
+    fn settle(&self, timeout_ms: u64) -> std::io::Result<Frame> {
+        let start = self.ticktimer.elapsed_ms();
+        let mut frame = self.capture("settling")?;
+        let mut quiet_since = start;
+        loop {
+            self.ticktimer.sleep_ms(SETTLE_POLL_MS).unwrap();
+            let now = self.ticktimer.elapsed_ms();
+            let next = self.capture("settling")?;
+            if next.differences(&frame, IGNORED_LINES) != 0 {
+                quiet_since = now;
+            }
+            frame = next;
+            if now - quiet_since >= SETTLE_QUIET_MS {
+                return Ok(frame);
+            }
+            if now - start >= timeout_ms {
+                log::warn!("screen is still changing after {} ms", timeout_ms);
+                return Ok(frame);
+            }
+        }
+    }
+
+    fn press(&self, key: char) {
+        self.kbd.hostmode_inject_key(key);
+        self.ticktimer.sleep_ms(KEY_INTERVAL_MS).unwrap();
+    }
+
+    fn pass(&mut self, line: String) {
+        log::info!("{}", line);
+        self.report.push(line);
+    }
+
+    fn fail(&mut self, line: String) {
+        log::error!("{}", line);
+        self.report.push(line);
+        self.failures += 1;
+    }
+
+    fn snapshot(&mut self, name: &str) {
+        let actual = match self.settle(SETTLE_TIMEOUT_MS) {
+            Ok(frame) => frame,
+            Err(e) => {
+                return self.fail(format!("FAIL {}: couldn't take a screenshot: {}", name, e))
+            }
+        };
+        let reference = self.golden_dir.join(format!("{}.png", name));
+        if self.update {
+            match actual.save(&reference) {
+                Ok(()) => self.pass(format!("UPDATED {}", name)),
+                Err(e) => self.fail(format!("FAIL {}: couldn't save reference: {}", name, e)),
+            }
+            return;
+        }
+        let actual_path = self.output_dir.join(format!("{}.png", name));
+        let expected = match Frame::load(&reference) {
+            Ok(frame) => frame,
+            Err(e) if self.bless && e.kind() == std::io::ErrorKind::NotFound => {
+                let candidate = self.output_dir.join("golden").join(format!("{}.png", name));
+                return match std::fs::create_dir_all(self.output_dir.join("golden"))
+                    .and_then(|_| actual.save(&candidate))
+                {
+                    Ok(()) => self.pass(format!(
+                        "NEW {}: no reference yet; check in {} as {}",
+                        name,
+                        candidate.display(),
+                        reference.display()
+                    )),
+                    Err(e) => self.fail(format!("FAIL {}: couldn't save candidate: {}", name, e)),
+                };
+            }
+            Err(e) => {
+                // Keep the screenshot, so that it can be checked and used as the reference
+                actual.save(&actual_path).ok();
+                return self.fail(format!(
+                    "FAIL {}: no usable reference image ({}); see {}, and run \
+                    `cargo xtask ui-golden --update` to use it",
+                    name,
+                    e,
+                    actual_path.display()
+                ));
+            }
+        };
+        let differences = expected.differences(&actual, IGNORED_LINES);
+        if differences == 0 {
+            self.pass(format!("PASS {}", name));
+            return;
+        }
+        let diff_path = self.output_dir.join(format!("{}.diff.png", name));
+        actual.save(&actual_path).ok();
+        expected
+            .diff_image(&actual, IGNORED_LINES)
+            .save(&diff_path)
+            .ok();
+        self.fail(format!(
+            "FAIL {}: {} pixels differ; see {}",
+            name,
+            differences,
+            diff_path.display()
+        ));
+    }
+
+    fn run(&mut self, step: &Step) {
+        match *step {
+            Step::Type(text) => {
+                for key in text.chars() {
+                    self.press(key);
+                }
+            }
+            Step::Key(key) => self.press(key),
+            Step::Repeat(key, count) => {
+                for _ in 0..count {
+                    self.press(key);
+                }
+            }
+            // Modals block until they are dismissed, so they are raised from another
+            // thread while this one carries on pressing keys.
+            Step::Notification(text) => {
+                std::thread::spawn(move || {
+                    let xns = xous_names::XousNames::new().unwrap();
+                    let modals = modals::Modals::new(&xns).expect("couldn't connect to modals");
+                    modals.show_notification(text, None).ok();
+                });
+            }
+            Step::TextEntry(prompt) => {
+                std::thread::spawn(move || {
+                    let xns = xous_names::XousNames::new().unwrap();
+                    let modals = modals::Modals::new(&xns).expect("couldn't connect to modals");
+                    modals.alert_builder(prompt).field(None, None).build().ok();
+                });
+            }
+            Step::Snapshot(name) => self.snapshot(name),
+        }
+    }
+
+    /// Write the report that `cargo xtask ui-golden` reads once the system shuts down
+    fn finish(&self) -> std::io::Result<()> {
+        let mut report = std::fs::File::create(self.output_dir.join("report.txt"))?;
+        for line in &self.report {
+            writeln!(report, "{}", line)?;
+        }
+        if self.failures == 0 {
+            writeln!(report, "RESULT ok")
+        } else {
+            writeln!(report, "RESULT {} failed", self.failures)
+        }
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let mut harness = Harness::new(&xns);
+
+    log::info!("waiting for the system to finish booting");
+    harness.settle(BOOT_TIMEOUT_MS).ok();
+    for scenario in SCENARIOS {
+        log::info!("running scenario '{}'", scenario.name);
+        for step in scenario.steps {
+            harness.run(step);
+        }
+    }
+    harness.finish().expect("couldn't write the report");
+    log::info!("{} snapshots failed", harness.failures);
+
+    xous::rsyscall(xous::SysCall::Shutdown).expect("couldn't shut down");
+    xous::terminate_process(0)
+}
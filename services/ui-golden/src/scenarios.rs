@@ -0,0 +1,76 @@
+use crate::golden::Band;
+
+/// Lines of the screen that are never compared. The status bar at the top shows the
+/// time and battery level, which change from run to run.
+pub const IGNORED_LINES: &[Band] = &[Band { top: 0, bottom: 32 }];
+
+pub enum Step {
+    /// Type each character of the string in turn
+    Type(&'static str),
+    /// Press a single key. The special keys are '∴' (select or menu), '←', '→', '↑',
+    /// '↓' and '\r' (enter).
+    Key(char),
+    /// Press a key several times
+    Repeat(char, usize),
+    /// Pop up a notification modal with this text. It stays up until a key is pressed.
+    Notification(&'static str),
+    /// Pop up a text entry modal with this prompt. It stays up until enter is pressed.
+    TextEntry(&'static str),
+    /// Wait for the screen to stop changing, then compare it with the reference image
+    /// of this name
+    Snapshot(&'static str),
+}
+
+pub struct Scenario {
+    pub name: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// Scenarios run in order, on a single boot, so each one starts from the screen that
+/// the previous one left behind.
+pub const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "boot",
+        steps: &[Step::Snapshot("boot")],
+    },
+    Scenario {
+        name: "chat",
+        steps: &[
+            Step::Type("echo hello world"),
+            Step::Snapshot("chat-typing"),
+            Step::Key('\r'),
+            Step::Snapshot("chat-echo"),
+        ],
+    },
+    Scenario {
+        name: "main menu",
+        steps: &[
+            Step::Key('∴'),
+            Step::Snapshot("main-menu"),
+            Step::Key('↓'),
+            Step::Snapshot("main-menu-second-item"),
+            // the last item closes the menu
+            Step::Repeat('↓', 20),
+            Step::Key('∴'),
+        ],
+    },
+    Scenario {
+        name: "notification",
+        steps: &[
+            Step::Notification("Golden image test notification"),
+            Step::Snapshot("modal-notification"),
+            Step::Key('\r'),
+        ],
+    },
+    Scenario {
+        name: "text entry",
+        steps: &[
+            Step::TextEntry("Golden image text entry"),
+            Step::Snapshot("modal-text-entry"),
+            Step::Type("hunter2"),
+            Step::Snapshot("modal-text-entry-typed"),
+            Step::Key('\r'),
+            Step::Snapshot("modal-text-entry-closed"),
+        ],
+    },
+];
@@ -1,5 +1,11 @@
 #[allow(dead_code)]
 pub const AUTHENTICATE_TIMEOUT: u32 = 10_000; // time in ms that a process has to respond to an authentication request
+/// Separates the components of a hierarchical name, e.g. `_app/vault/ux`
+pub const NAMESPACE_SEPARATOR: char = '/';
+/// Maximum number of process names in the access-control list of a registered name
+pub const ACL_LEN: usize = 8;
+/// Number of names returned by a single `ListNames` call
+pub const NAME_PAGE_LEN: usize = 8;
 
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive)]
 #[non_exhaustive]
@@ -50,6 +56,23 @@ pub(crate) enum Opcode {
     /// }
     /// ```
     BlockingConnect = 6,
+
+    /// Claim a process name for the calling process. Access-control lists refer to processes
+    /// by these names. Each process may claim one name, and each name may only be claimed once.
+    /// Claims are refused once `TrustedInitDone` has succeeded, so only the processes started at
+    /// boot can have names.
+    SetProcessName = 7,
+
+    /// List registered names along with their owner and connection counts, one page at a time.
+    ListNames = 8,
+
+    /// Forget a process that has exited: its process name, the names it registered, and the
+    /// namespaces it owns. Only the supervisor, which the kernel tells about exits, may send this.
+    ProcessExited = 9,
+
+    /// Give a process name that was released by an exit to the process the supervisor restarted
+    /// in its place. Only the supervisor may send this, and it works after `TrustedInitDone`.
+    ReclaimProcessName = 10,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -70,12 +93,24 @@ pub(crate) enum Return {
 
     /// Operation requested was otherwise successful (currently only used by disconnect to ack the disconnect)
     Success,
+
+    /// The server exists, but its access-control list does not include the caller
+    AccessDenied,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub(crate) struct Registration {
     pub name: xous_ipc::String<64>,
     pub conn_limit: Option<u32>,
+    /// If present, only processes with these process names may connect
+    pub acl: Option<[Option<xous_ipc::String<64>>; ACL_LEN]>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct ProcessNameClaim {
+    /// The process to give the name to
+    pub pid: u32,
+    pub name: xous_ipc::String<64>,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -99,6 +134,29 @@ pub(crate) struct AuthenticateRequest {
     pub challenge: [u32; 4],
 }
 
+/// A registered name, as reported by `ListNames`
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct NameInfo {
+    pub name: xous_ipc::String<64>,
+    /// PID of the process that registered the name
+    pub owner_pid: Option<u32>,
+    /// Process name claimed by the owner, if it has claimed one
+    pub owner_name: Option<xous_ipc::String<64>>,
+    pub current_conns: u32,
+    pub max_conns: Option<u32>,
+    /// Whether connections are limited by an access-control list
+    pub restricted: bool,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct NameList {
+    /// Index of the first name to return, in name order
+    pub start: u32,
+    /// Total number of registered names, filled in by the server
+    pub total: u32,
+    pub entries: [Option<NameInfo>; NAME_PAGE_LEN],
+}
+
 //////////////////////////////////////////////////////////////////////////////////////////////
 // We keep XousServerName around because want to be able to index off the server name, without
 // burdening the Kernel String type with the Hash32 methods
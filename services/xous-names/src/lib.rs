@@ -4,7 +4,7 @@
 
 pub mod api;
 
-use api::Disconnect;
+use api::{Disconnect, NameInfo};
 use core::fmt::Write;
 use num_traits::ToPrimitive;
 use xous_ipc::{Buffer, String};
@@ -57,9 +57,41 @@ impl XousNames {
         name: &str,
         max_conns: Option<u32>,
     ) -> Result<xous::SID, xous::Error> {
+        self.register_name_with_acl(name, max_conns, None)
+    }
+
+    /// Register a name that only the processes named in `allowed` may connect to. Processes
+    /// identify themselves with `set_process_name()`. The registering process may always
+    /// connect to its own name.
+    ///
+    /// Names may be grouped into namespaces with `/`, e.g. `_app/vault/ux`. The first process
+    /// to register a name in a namespace owns it, and other processes can't register names
+    /// in it until all of its names are unregistered.
+    pub fn register_name_with_acl(
+        &self,
+        name: &str,
+        max_conns: Option<u32>,
+        allowed: Option<&[&str]>,
+    ) -> Result<xous::SID, xous::Error> {
+        let acl = match allowed {
+            Some(allowed) => {
+                if allowed.len() > api::ACL_LEN {
+                    return Err(xous::Error::InvalidLimit);
+                }
+                let mut acl = [None; api::ACL_LEN];
+                for (dest, process_name) in acl.iter_mut().zip(allowed.iter()) {
+                    let mut entry = String::<64>::new();
+                    write!(entry, "{}", process_name).expect("process name probably too long");
+                    *dest = Some(entry);
+                }
+                Some(acl)
+            }
+            None => None,
+        };
         let mut registration = api::Registration {
             name: String::<64>::new(),
             conn_limit: max_conns,
+            acl,
         };
         // could also do String::from_str() but in this case we want things to fail if the string is too long.
         write!(registration.name, "{}", name).expect("name probably too long");
@@ -93,6 +125,7 @@ impl XousNames {
 
         match buf.to_original().unwrap() {
             api::Return::CID((cid, token)) => Ok((cid, token)),
+            api::Return::AccessDenied => Err(xous::Error::AccessDenied),
             // api::Return::AuthenticateRequest(_) => Err(xous::Error::AccessDenied),
             _ => Err(xous::Error::ServerNotFound),
         }
@@ -123,6 +156,7 @@ impl XousNames {
 
         match buf.to_original().unwrap() {
             api::Return::CID((cid, _)) => Ok(cid),
+            api::Return::AccessDenied => Err(xous::Error::AccessDenied),
             // api::Return::AuthenticateRequest(_) => Err(xous::Error::AccessDenied),
             _ => Err(xous::Error::ServerNotFound),
         }
@@ -164,6 +198,86 @@ impl XousNames {
         }
     }
 
+    /// Claim `name` as this process's name, which is how access-control lists refer to it.
+    /// A process may only claim one name, and a name may only be claimed by one process.
+    /// Names can't be claimed once trusted init is done, so claim it early in boot.
+    pub fn set_process_name(&self, name: &str) -> Result<(), xous::Error> {
+        let mut process_name = String::<64>::new();
+        write!(process_name, "{}", name).expect("name probably too long");
+        let mut buf = Buffer::into_buf(process_name).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::SetProcessName.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::Success => Ok(()),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+
+    /// Tell the name server that `pid` has exited, so that its process name, registrations and
+    /// namespaces are dropped before the PID is given to another process. Only the supervisor,
+    /// which the kernel tells about exits, may call this.
+    pub fn process_exited(&self, pid: xous::PID) -> Result<(), xous::Error> {
+        let response = xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::ProcessExited.to_usize().unwrap(),
+                pid.get() as usize,
+                0,
+                0,
+                0,
+            ),
+        )?;
+        match response {
+            xous::Result::Scalar1(1) => Ok(()),
+            xous::Result::Scalar1(_) => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Give `name` to `pid`, a process that the supervisor restarted in place of one that exited
+    /// holding that name. Only the supervisor may call this. Once trusted init is done, only names
+    /// released by an exit can be handed out.
+    pub fn reclaim_process_name(&self, pid: xous::PID, name: &str) -> Result<(), xous::Error> {
+        let mut claim = api::ProcessNameClaim {
+            pid: pid.get() as u32,
+            name: String::<64>::new(),
+        };
+        write!(claim.name, "{}", name).expect("name probably too long");
+        let mut buf = Buffer::into_buf(claim).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::ReclaimProcessName.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::Success => Ok(()),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+
+    /// List every registered name in name order, along with who registered it and how
+    /// many connections it has.
+    pub fn list_names(&self) -> Result<Vec<NameInfo>, xous::Error> {
+        let mut names = Vec::new();
+        loop {
+            let page = api::NameList {
+                start: names.len() as u32,
+                total: 0,
+                entries: [None; api::NAME_PAGE_LEN],
+            };
+            let mut buf = Buffer::into_buf(page).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.conn, api::Opcode::ListNames.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            let page = buf.to_original::<api::NameList, _>().unwrap();
+
+            let before = names.len();
+            names.extend(page.entries.iter().flatten().copied());
+            // stop once everything has been read, or if names were removed while paging
+            if names.len() >= page.total as usize || names.len() == before {
+                return Ok(names);
+            }
+        }
+    }
+
     // todo:
     // pub fn authenticated_connection(&self, name: &str, key: Authkey)
     // this function will create an authenticated connection, if such are allowed
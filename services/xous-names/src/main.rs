@@ -10,7 +10,7 @@ use xous_ipc::{Buffer, String};
 
 use log::{error, info};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(PartialEq)]
 #[repr(C)]
@@ -26,6 +26,9 @@ enum ConnectError {
 
     /// The message was not a mutable memory message
     InvalidMessageType = 4,
+
+    /// The server's access-control list does not include the caller
+    AccessDenied = 5,
 }
 
 #[derive(PartialEq)]
@@ -110,25 +113,80 @@ struct Connection {
     pub current_conns: u32, // number of unauthenticated (inherentely trusted) connections
     pub max_conns: Option<u32>, // if None, unlimited connections allowed
     pub _allow_authenticate: bool,
-    pub _auth_conns: u32,        // number of authenticated connections
+    pub _auth_conns: u32,         // number of authenticated connections
     pub token: Option<[u32; 4]>, // a random number that must be presented to allow for disconnection
+    pub owner: Option<xous::PID>, // the process that registered the name
+    pub acl: Option<[Option<XousServerName>; ACL_LEN]>, // if set, the process names that may connect
+}
+
+/// The namespace that a name lives in, which is everything before the last separator.
+/// `_app/vault/ux` lives in `_app/vault`, and names without a separator have no namespace.
+fn namespace_of(name: &XousServerName) -> Option<XousServerName> {
+    let name = name.to_str();
+    name.rfind(NAMESPACE_SEPARATOR)
+        .map(|index| XousServerName::from_str(&name[..index]))
 }
+
+/// Names may not have empty components, so `/net`, `net/` and `_app//vault` are all invalid
+fn is_valid_name(name: &XousServerName) -> bool {
+    !name.is_empty()
+        && name
+            .to_str()
+            .split(NAMESPACE_SEPARATOR)
+            .all(|component| !component.is_empty())
+}
+
 #[derive(Debug)]
 struct CheckedHashMap {
     pub map: HashMap<XousServerName, Connection>,
+    /// The process that owns each namespace in use. The first process to register a name directly
+    /// inside a namespace owns it until all of the names in it are unregistered, and no other
+    /// process may register names in it. Sub-namespaces are owned separately, so `_app` can hold
+    /// `_app/vault/...` and `_app/shellchat/...` for two different processes.
+    pub namespaces: HashMap<XousServerName, xous::PID>,
+    /// Process names claimed with `SetProcessName`, which access-control lists refer to
+    pub process_names: HashMap<xous::PID, XousServerName>,
+    /// Set once `TrustedInitDone` has succeeded. From then on, names can no longer be claimed,
+    /// so a process loaded later can't take the name of a trusted process that hadn't claimed it.
+    pub process_names_sealed: bool,
+    /// Process names whose holders have exited. After the seal, only the supervisor can give
+    /// one of these out again, to the process it restarted in place of the one that exited.
+    pub released_names: HashSet<XousServerName>,
 }
 impl CheckedHashMap {
     pub fn new() -> Self {
         CheckedHashMap {
             map: HashMap::new(),
+            namespaces: HashMap::new(),
+            process_names: HashMap::new(),
+            process_names_sealed: false,
+            released_names: HashSet::new(),
         }
     }
+
+    /// Whether `pid` may register `name`: the name must be well-formed, unused, and not inside a
+    /// namespace that belongs to another process.
+    pub fn may_register(&self, name: &XousServerName, pid: xous::PID) -> bool {
+        if !is_valid_name(name) || self.map.contains_key(name) {
+            return false;
+        }
+        match namespace_of(name).and_then(|ns| self.namespaces.get(&ns)) {
+            Some(&owner) => owner == pid,
+            None => true,
+        }
+    }
+
     pub fn insert(
         &mut self,
         name: XousServerName,
         sid: xous::SID,
         max_conns: Option<u32>,
+        owner: xous::PID,
+        acl: Option<[Option<XousServerName>; ACL_LEN]>,
     ) -> Result<(), xous::Error> {
+        if let Some(namespace) = namespace_of(&name) {
+            self.namespaces.entry(namespace).or_insert(owner);
+        }
         let token =
             // for use with 1-connection servers, provision a one-time use token for disconnects
             // it will be returned for multi-connection servers as well, but it doesn't have a clear
@@ -149,6 +207,8 @@ impl CheckedHashMap {
                 _allow_authenticate: false, // for now, we don't support authenticated connections
                 _auth_conns: 0,
                 token,
+                owner: Some(owner),
+                acl,
             },
         );
         Ok(())
@@ -166,11 +226,108 @@ impl CheckedHashMap {
         }
         if let Some(name) = removed_name {
             self.map.remove(&name);
+            // release the namespace once the last name in it is gone
+            if let Some(namespace) = namespace_of(&name) {
+                if !self
+                    .map
+                    .keys()
+                    .any(|other| namespace_of(other) == Some(namespace))
+                {
+                    self.namespaces.remove(&namespace);
+                }
+            }
         }
 
         removed_name
     }
 
+    /// Whether `pid` may connect to `name` under the name's access-control list. The process that
+    /// registered the name may always connect to it.
+    pub fn is_allowed(&self, name: &XousServerName, pid: xous::PID) -> bool {
+        let entry = match self.map.get(name) {
+            Some(entry) => entry,
+            None => return true,
+        };
+        let acl = match &entry.acl {
+            Some(acl) => acl,
+            None => return true,
+        };
+        if entry.owner == Some(pid) {
+            return true;
+        }
+        match self.process_names.get(&pid) {
+            Some(process_name) => acl.iter().flatten().any(|allowed| allowed == process_name),
+            None => false,
+        }
+    }
+
+    /// Claim `name` for `pid`. Fails if the process already has a name, the name is taken, or
+    /// trusted init is over.
+    pub fn set_process_name(&mut self, pid: xous::PID, name: XousServerName) -> bool {
+        if self.process_names_sealed
+            || name.is_empty()
+            || self.process_names.contains_key(&pid)
+            || self
+                .process_names
+                .values()
+                .any(|existing| *existing == name)
+        {
+            return false;
+        }
+        self.process_names.insert(pid, name);
+        self.released_names.remove(&name);
+        true
+    }
+
+    /// Give `name` to `pid` on behalf of the supervisor, which has restarted a process that
+    /// exited. After the seal, only names whose holders have exited can be handed out this way.
+    pub fn reclaim_process_name(&mut self, pid: xous::PID, name: XousServerName) -> bool {
+        if !self.process_names_sealed {
+            return self.set_process_name(pid, name);
+        }
+        if !self.released_names.contains(&name) || self.process_names.contains_key(&pid) {
+            return false;
+        }
+        self.released_names.remove(&name);
+        self.process_names.insert(pid, name);
+        true
+    }
+
+    /// Forget everything that belongs to `pid`, which has exited: its process name, the names it
+    /// registered, and the namespaces it owns. Otherwise a process that is later given the same
+    /// PID would inherit them. Returns the number of names that were unregistered.
+    pub fn process_exited(&mut self, pid: xous::PID) -> usize {
+        if let Some(name) = self.process_names.remove(&pid) {
+            self.released_names.insert(name);
+        }
+        let registered = self.map.len();
+        self.map.retain(|_, entry| entry.owner != Some(pid));
+        self.namespaces.retain(|_, owner| *owner != pid);
+        registered - self.map.len()
+    }
+
+    /// Describe registered names in name order, starting at `start`. Returns the total number of
+    /// registered names.
+    pub fn list(&self, start: usize, entries: &mut [Option<NameInfo>]) -> usize {
+        let mut names: Vec<&XousServerName> = self.map.keys().collect();
+        names.sort_by(|a, b| a.to_str().cmp(b.to_str()));
+        for (slot, name) in entries.iter_mut().zip(names.iter().skip(start)) {
+            let entry = &self.map[*name];
+            *slot = Some(NameInfo {
+                name: String::<64>::from_str(name.to_str()),
+                owner_pid: entry.owner.map(|pid| pid.get() as u32),
+                owner_name: entry
+                    .owner
+                    .and_then(|pid| self.process_names.get(&pid))
+                    .map(|process_name| String::<64>::from_str(process_name.to_str())),
+                current_conns: entry.current_conns,
+                max_conns: entry.max_conns,
+                restricted: entry.acl.is_some(),
+            });
+        }
+        names.len()
+    }
+
     pub fn contains_key(&self, name: &XousServerName) -> bool {
         self.map.contains_key(name)
     }
@@ -273,6 +430,13 @@ impl CheckedHashMap {
     }
 }
 
+fn pid_from_usize(pid: usize) -> Option<xous::PID> {
+    if pid > u8::MAX as usize {
+        return None;
+    }
+    xous::PID::new(pid as u8)
+}
+
 fn name_from_msg(env: &MessageEnvelope) -> Result<XousServerName, ConnectError> {
     let msg = env
         .body
@@ -306,6 +470,15 @@ fn blocking_connect(
         sender_pid
     );
 
+    if !name_table.is_allowed(&name, sender_pid) {
+        log::info!(
+            "process {:?} is not allowed to connect to '{}'",
+            sender_pid,
+            name
+        );
+        return Err(ConnectError::AccessDenied);
+    }
+
     // If the server already exists, attempt to make the connection. The connection can
     // only succeed if the
     if let (Some(server_sid), token) = name_table.connect(&name) {
@@ -399,15 +572,30 @@ fn main() -> ! {
                         .expect("couldn't convert server name to string"),
                 );
 
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on Register");
+                let acl = registration.acl.map(|acl| {
+                    let mut names = [None; ACL_LEN];
+                    for (dest, src) in names.iter_mut().zip(acl.iter()) {
+                        *dest = src
+                            .as_ref()
+                            .and_then(|s| s.as_str().ok())
+                            .map(XousServerName::from_str);
+                    }
+                    names
+                });
+
                 let response: api::Return;
                 let mut should_connect = false;
 
                 log::trace!("registration request for '{}'", name);
-                if !name_table.contains_key(&name) {
+                if name_table.may_register(&name, sender_pid) {
                     let new_sid =
                         xous::create_server_id().expect("create server failed, maybe OOM?");
                     name_table
-                        .insert(name, new_sid, registration.conn_limit)
+                        .insert(name, new_sid, registration.conn_limit, sender_pid, acl)
                         .expect("register name failure, maybe out of HashMap capacity?");
                     log::trace!("request successful, SID is {:?}", new_sid);
                     should_connect = true;
@@ -493,12 +681,19 @@ fn main() -> ! {
                         .expect("couldn't convert server name to string"),
                 );
                 log::trace!("Lookup request for '{}'", name);
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on Lookup");
                 let response: api::Return;
-                if let (Some(server_sid), token) = name_table.connect(&name) {
-                    let sender_pid = msg
-                        .sender
-                        .pid()
-                        .expect("can't extract sender PID on Lookup");
+                if !name_table.is_allowed(&name, sender_pid) {
+                    info!(
+                        "process {:?} is not allowed to connect to '{}'",
+                        sender_pid, name
+                    );
+                    d11ctimeout.deterministic_busy_wait();
+                    response = api::Return::AccessDenied
+                } else if let (Some(server_sid), token) = name_table.connect(&name) {
                     match xous::connect_for_process(sender_pid, server_sid)
                         .expect("can't broker connection")
                     {
@@ -548,6 +743,10 @@ fn main() -> ! {
             }
             Some(api::Opcode::TrustedInitDone) => {
                 if name_table.trusted_init_done() {
+                    if !name_table.process_names_sealed {
+                        info!("trusted init is done, no more process names may be claimed");
+                        name_table.process_names_sealed = true;
+                    }
                     xous::return_scalar(msg.sender, 1).expect("couldn't return trusted_init_done");
                 } else {
                     xous::return_scalar(msg.sender, 0).expect("couldn't return trusted_init_done");
@@ -565,6 +764,84 @@ fn main() -> ! {
                 };
                 buffer.replace(response).expect("Can't return buffer");
             }
+            Some(api::Opcode::SetProcessName) => {
+                let mem = msg.body.memory_message_mut().unwrap();
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let name_string = buffer.to_original::<String<64>, _>().unwrap();
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on SetProcessName");
+                let claimed = match name_string.as_str() {
+                    Ok(name) => {
+                        name_table.set_process_name(sender_pid, XousServerName::from_str(name))
+                    }
+                    Err(_) => false,
+                };
+                let response = if claimed {
+                    info!("process {:?} is now known as '{}'", sender_pid, name_string);
+                    api::Return::Success
+                } else {
+                    api::Return::Failure
+                };
+                buffer.replace(response).expect("Can't return buffer");
+            }
+            Some(api::Opcode::ProcessExited) => msg_blocking_scalar_unpack!(msg, pid, _, _, _, {
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on ProcessExited");
+                match pid_from_usize(pid) {
+                    Some(exited) if xous::role_holder(xous::Role::Supervisor) == Ok(sender_pid) => {
+                        let removed = name_table.process_exited(exited);
+                        // a connection request from a process that no longer exists can never be answered
+                        waiting_connections.retain(|waiting| waiting.sender.pid() != Some(exited));
+                        info!(
+                            "process {:?} exited, {} of its names were unregistered",
+                            exited, removed
+                        );
+                        xous::return_scalar(msg.sender, 1).unwrap();
+                    }
+                    _ => {
+                        info!("process {:?} may not report exits", sender_pid);
+                        xous::return_scalar(msg.sender, 0).unwrap();
+                    }
+                }
+            }),
+            Some(api::Opcode::ReclaimProcessName) => {
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on ReclaimProcessName");
+                let mem = msg.body.memory_message_mut().unwrap();
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let claim = buffer.to_original::<ProcessNameClaim, _>().unwrap();
+                let claimed = xous::role_holder(xous::Role::Supervisor) == Ok(sender_pid)
+                    && match (pid_from_usize(claim.pid as usize), claim.name.as_str()) {
+                        (Some(pid), Ok(name)) => {
+                            name_table.reclaim_process_name(pid, XousServerName::from_str(name))
+                        }
+                        _ => false,
+                    };
+                let response = if claimed {
+                    info!(
+                        "process {} is now known as '{}', on behalf of the supervisor",
+                        claim.pid, claim.name
+                    );
+                    api::Return::Success
+                } else {
+                    api::Return::Failure
+                };
+                buffer.replace(response).expect("Can't return buffer");
+            }
+            Some(api::Opcode::ListNames) => {
+                let mem = msg.body.memory_message_mut().unwrap();
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let mut list = buffer.to_original::<NameList, _>().unwrap();
+                list.entries = [None; NAME_PAGE_LEN];
+                list.total = name_table.list(list.start as usize, &mut list.entries) as u32;
+                buffer.replace(list).expect("Can't return buffer");
+            }
             None => {
                 error!("couldn't decode message: {:?}", msg);
                 break;
@@ -577,3 +854,115 @@ fn main() -> ! {
     log::trace!("quitting");
     xous::terminate_process(0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pid(pid: u8) -> xous::PID {
+        xous::PID::new(pid).unwrap()
+    }
+
+    fn name(name: &str) -> XousServerName {
+        XousServerName::from_str(name)
+    }
+
+    /// A table where `pid(2)` registered `_secret` for `shellchat` only
+    fn table() -> CheckedHashMap {
+        let mut table = CheckedHashMap::new();
+        let mut acl = [None; ACL_LEN];
+        acl[0] = Some(name("shellchat"));
+        table.map.insert(
+            name("_secret"),
+            Connection {
+                sid: xous::SID::from_u32(1, 2, 3, 4),
+                current_conns: 0,
+                max_conns: None,
+                _allow_authenticate: false,
+                _auth_conns: 0,
+                token: None,
+                owner: Some(pid(2)),
+                acl: Some(acl),
+            },
+        );
+        table
+    }
+
+    #[test]
+    fn acl_allows_listed_processes_and_owner() {
+        let mut table = table();
+        assert!(table.set_process_name(pid(3), name("shellchat")));
+        assert!(table.is_allowed(&name("_secret"), pid(3)));
+        assert!(table.is_allowed(&name("_secret"), pid(2)));
+        // Names without an ACL are open to everyone
+        assert!(table.is_allowed(&name("_unlisted"), pid(5)));
+    }
+
+    #[test]
+    fn acl_denies_other_processes() {
+        let mut table = table();
+        assert!(!table.is_allowed(&name("_secret"), pid(4)));
+        assert!(table.set_process_name(pid(4), name("vault")));
+        assert!(!table.is_allowed(&name("_secret"), pid(4)));
+    }
+
+    #[test]
+    fn names_are_claimed_once() {
+        let mut table = table();
+        assert!(table.set_process_name(pid(3), name("shellchat")));
+        // Nobody else may take the name, and a process can't change its own
+        assert!(!table.set_process_name(pid(4), name("shellchat")));
+        assert!(!table.is_allowed(&name("_secret"), pid(4)));
+        assert!(!table.set_process_name(pid(3), name("vault")));
+        assert!(!table.set_process_name(pid(5), name("")));
+    }
+
+    #[test]
+    fn names_cannot_be_claimed_after_trusted_init() {
+        let mut table = table();
+        table.process_names_sealed = true;
+        assert!(!table.set_process_name(pid(4), name("shellchat")));
+        assert!(!table.is_allowed(&name("_secret"), pid(4)));
+    }
+
+    #[test]
+    fn exited_processes_are_forgotten() {
+        let mut table = table();
+        assert!(table.set_process_name(pid(3), name("shellchat")));
+        assert!(table.set_process_name(pid(2), name("vault")));
+        let mut owned = table.map[&name("_secret")];
+        owned.acl = None;
+        table.map.insert(name("_app/vault"), owned);
+        table.namespaces.insert(name("_app"), pid(2));
+        table.process_names_sealed = true;
+
+        assert_eq!(table.process_exited(pid(3)), 0);
+        assert!(!table.process_names.contains_key(&pid(3)));
+        // a new process that is given the PID doesn't pass the ACL
+        assert!(!table.is_allowed(&name("_secret"), pid(3)));
+
+        // the owner's names and namespaces go with it, so another process may use them
+        assert_eq!(table.process_exited(pid(2)), 2);
+        assert!(!table.contains_key(&name("_secret")));
+        assert!(table.namespaces.is_empty());
+        assert!(table.may_register(&name("_app/vault"), pid(4)));
+    }
+
+    #[test]
+    fn supervisor_reclaims_released_names() {
+        let mut table = table();
+        assert!(table.set_process_name(pid(3), name("shellchat")));
+        table.process_names_sealed = true;
+        // a name that is still held, or was never held, can't be handed out
+        assert!(!table.reclaim_process_name(pid(6), name("shellchat")));
+        assert!(!table.reclaim_process_name(pid(6), name("vault")));
+
+        table.process_exited(pid(3));
+        // the restarted process can't take its old name back on its own
+        assert!(!table.set_process_name(pid(6), name("shellchat")));
+        assert!(table.reclaim_process_name(pid(6), name("shellchat")));
+        assert!(table.is_allowed(&name("_secret"), pid(6)));
+        // and the name can only be handed out once
+        assert!(!table.reclaim_process_name(pid(7), name("shellchat")));
+    }
+}
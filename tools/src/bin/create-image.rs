@@ -10,7 +10,9 @@ use tools::elf::{read_minielf, read_program};
 use tools::tags::bflg::Bflg;
 use tools::tags::inie::IniE;
 use tools::tags::memory::{MemoryRegion, MemoryRegions};
+use tools::tags::plim::ProcessLimits;
 use tools::tags::pnam::ProcessNames;
+use tools::tags::prol::ProcessRoles;
 use tools::tags::xkrn::XousKernel;
 use tools::utils::{parse_csr_csv, parse_u32};
 use tools::xous_arguments::XousArguments;
@@ -123,6 +125,31 @@ fn main() {
                 .takes_value(false)
                 .help("Reduce kernel-userspace security and enable debugging programs"),
         )
+        .arg(
+            Arg::with_name("limit")
+                .short("l")
+                .long("limit")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PROCESS:RESOURCE=COUNT")
+                .help(
+                    "Limit a resource of an init program, e.g. shellchat:pages=512. \
+                    Resources are pages, threads, servers and connections",
+                ),
+        )
+        .arg(
+            Arg::with_name("role")
+                .long("role")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PROCESS:ROLE")
+                .help(
+                    "Give a privileged role to an init program, e.g. tracer:trace-reader. \
+                    Only one program may hold each role",
+                ),
+        )
         .arg(
             Arg::with_name("output")
                 .value_name("OUTPUT")
@@ -140,6 +167,8 @@ fn main() {
     };
 
     let mut process_names = ProcessNames::new();
+    let mut process_limits = ProcessLimits::new();
+    let mut process_roles = ProcessRoles::new();
 
     if let Some(val) = matches.value_of("ram") {
         let ram_parts: Vec<&str> = val.split(':').collect();
@@ -264,6 +293,71 @@ fn main() {
     );
     args.add(xkrn);
 
+    if let Some(limits) = matches.values_of("limit") {
+        for limit in limits {
+            let parsed = limit.split_once(':').and_then(|(process, rest)| {
+                rest.split_once('=')
+                    .map(|(resource, count)| (process, resource, count))
+            });
+            let (process, resource, count) = match parsed {
+                Some(p) => p,
+                None => {
+                    eprintln!(
+                        "Error: --limit argument should be of the form [process]:[resource]=[count]"
+                    );
+                    return;
+                }
+            };
+            let pid = match process_names.pid_of(process) {
+                Some(pid) => pid,
+                None => {
+                    eprintln!("Error: no init program named {}", process);
+                    return;
+                }
+            };
+            let count = match parse_u32(count) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("Error: Unable to parse {}: {:?}", count, e);
+                    return;
+                }
+            };
+            if let Err(e) = process_limits.set(pid, resource, count) {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        }
+    }
+    if !process_limits.is_empty() {
+        args.add(process_limits);
+    }
+
+    if let Some(roles) = matches.values_of("role") {
+        for role in roles {
+            let (process, role) = match role.split_once(':') {
+                Some(p) => p,
+                None => {
+                    eprintln!("Error: --role argument should be of the form [process]:[role]");
+                    return;
+                }
+            };
+            let pid = match process_names.pid_of(process) {
+                Some(pid) => pid,
+                None => {
+                    eprintln!("Error: no init program named {}", process);
+                    return;
+                }
+            };
+            if let Err(e) = process_roles.set(pid, role) {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        }
+    }
+    if !process_roles.is_empty() {
+        args.add(process_roles);
+    }
+
     args.add(process_names);
 
     // Add tags for init and kernel.  These point to the actual data, which should
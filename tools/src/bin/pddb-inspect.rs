@@ -0,0 +1,1157 @@
+//! Offline inspector for PDDB disk images.
+//!
+//! Reads a raw PDDB image (as written by `EmuStorage::dump_fs()` in hosted mode, or a backup of the
+//! PDDB region of a device) along with the matching `.key` file written by `EmuStorage::dump_keys()`,
+//! and walks the on-disk structures without going through the PDDB server. This is the Rust
+//! counterpart of `tools/pddbdbg.py`.
+//!
+//! Only the bases whose keys are present in the key file can be decoded. Pages that belong to other
+//! bases are indistinguishable from free space, by design.
+
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::process;
+
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit};
+use aes::{Aes256, Block, BLOCK_SIZE};
+use aes_gcm_siv::aead::{Aead, NewAead, Payload};
+use aes_gcm_siv::{AesGcmSiv, Key, Nonce};
+use sha2::{Digest, Sha512_256};
+
+// The sizes, offsets and checksums of the on-disk structures come straight from the PDDB backend,
+// so that this tool can't drift away from what the PDDB actually writes.
+#[path = "../../../services/pddb/src/backend/layout.rs"]
+mod layout;
+use layout::*;
+#[path = "../../../services/pddb/src/backend/murmur3.rs"]
+mod murmur3;
+use murmur3::murmur3_32;
+
+/// Matches `PDDB_DEFAULT_SYSTEM_BASIS` in the PDDB's API
+const SYSTEM_BASIS: &str = ".System";
+
+struct BasisKeys {
+    name: String,
+    data: [u8; 32],
+    pt: [u8; 32],
+}
+
+/// Parses the key file written by `EmuStorage::dump_keys()`: a u32 count, followed by records of
+/// a 64-byte zero-padded name, the 32-byte data key and the 32-byte page table key.
+fn parse_keys(raw: &[u8]) -> io::Result<Vec<BasisKeys>> {
+    if raw.len() < 4 {
+        return Err(Error::new(ErrorKind::InvalidData, "key file is truncated"));
+    }
+    let count = u32::from_le_bytes(raw[..4].try_into().unwrap()) as usize;
+    let records = &raw[4..];
+    if records.len() < count * 128 {
+        return Err(Error::new(ErrorKind::InvalidData, "key file is truncated"));
+    }
+    let mut keys = Vec::new();
+    for record in records.chunks_exact(128).take(count) {
+        let name_len = record[..64].iter().position(|&b| b == 0).unwrap_or(64);
+        keys.push(BasisKeys {
+            name: String::from_utf8_lossy(&record[..name_len]).to_string(),
+            data: record[64..96].try_into().unwrap(),
+            pt: record[96..128].try_into().unwrap(),
+        });
+    }
+    Ok(keys)
+}
+
+fn read_keys(filename: &str) -> io::Result<Vec<BasisKeys>> {
+    let mut raw = Vec::new();
+    File::open(filename)?.read_to_end(&mut raw)?;
+    parse_keys(&raw)
+}
+
+fn basis_aad(name: &str, dna: u64) -> Vec<u8> {
+    let mut aad = Vec::new();
+    aad.extend_from_slice(name.as_bytes());
+    aad.extend_from_slice(&PDDB_VERSION.to_le_bytes());
+    aad.extend_from_slice(&dna.to_le_bytes());
+    aad
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+/// Names are stored as a length byte followed by the rest of the record
+fn read_name(record: &[u8], offset: usize) -> String {
+    let len = (record[offset] as usize).min(record.len() - offset - 1);
+    String::from_utf8_lossy(&record[offset + 1..offset + 1 + len]).to_string()
+}
+fn is_valid(flags: u32) -> bool {
+    flags & (1 << DK_VALID_BIT) != 0
+}
+
+/// Accessors for a `PhysPage`, as stored in the FSCB
+fn pp_page_number(pp: u32) -> u32 {
+    pp & ((1 << PP_PAGE_WIDTH) - 1)
+}
+fn pp_valid(pp: u32) -> bool {
+    pp & (1 << PP_VALID_BIT) != 0
+}
+fn pp_state(pp: u32) -> SpaceState {
+    SpaceState::from(pp >> PP_STATE_LSB)
+}
+fn pp_journal(pp: u32) -> u32 {
+    (pp >> PP_JOURNAL_LSB) & ((1 << (PP_JOURNAL_MSB + 1 - PP_JOURNAL_LSB)) - 1)
+}
+
+/// Derives the encryption key and the key commitment for a basis root page, per
+/// https://eprint.iacr.org/2020/1456.pdf Table 4, the same way `PddbOs::kcom_func()` does.
+fn kcom_keys(data_key: &[u8; 32], kcom_nonce: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let derive = |label: [u8; 9]| {
+        let mut h = Sha512_256::new();
+        h.update(data_key);
+        h.update(label);
+        h.update(kcom_nonce);
+        h.finalize().into()
+    };
+    (derive(KCOM_LABEL_ENC), derive(KCOM_LABEL_COM))
+}
+
+/// A raw disk image plus the derived locations of its regions
+struct Image {
+    disk: Vec<u8>,
+    dna: u64,
+    pt_len: usize,
+    mbbb_base: usize,
+    fscb_base: usize,
+    data_base: usize,
+}
+impl Image {
+    fn open(filename: &str, dna: u64) -> io::Result<Image> {
+        let mut disk = Vec::new();
+        File::open(filename)?.read_to_end(&mut disk)?;
+        Image::from_bytes(disk, dna)
+    }
+    fn from_bytes(disk: Vec<u8>, dna: u64) -> io::Result<Image> {
+        if disk.len() % PAGE_SIZE != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "image length is not a multiple of the page size",
+            ));
+        }
+        // the page table has one entry for every page in the image, and is followed by one page of
+        // static crypto data, the MBBB, the FSCB and finally the data area.
+        let pt_len = (disk.len() / PAGE_SIZE) * PTE_LEN;
+        let key_base = pt_len + (PAGE_SIZE - pt_len % PAGE_SIZE) % PAGE_SIZE;
+        let mbbb_base = key_base + PAGE_SIZE;
+        let fscb_base = mbbb_base + MBBB_PAGES * PAGE_SIZE;
+        let data_base = fscb_base + FSCB_PAGES * PAGE_SIZE;
+        if data_base >= disk.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "image is too small to contain a PDDB",
+            ));
+        }
+        Ok(Image {
+            disk,
+            dna,
+            pt_len,
+            mbbb_base,
+            fscb_base,
+            data_base,
+        })
+    }
+    fn data_pages(&self) -> u32 {
+        ((self.disk.len() - self.data_base) / PAGE_SIZE) as u32
+    }
+    fn data_page(&self, page_number: u32) -> &[u8] {
+        let start = self.data_base + page_number as usize * PAGE_SIZE;
+        &self.disk[start..start + PAGE_SIZE]
+    }
+    /// Returns the first stashed page in the MBBB, if any
+    fn mbbb_retrieve(&self) -> Option<&[u8]> {
+        self.disk[self.mbbb_base..self.mbbb_base + MBBB_PAGES * PAGE_SIZE]
+            .chunks(PAGE_SIZE)
+            .find(|page| page[..BLOCK_SIZE] != [0xFF; BLOCK_SIZE])
+    }
+    /// Returns the decrypted page, including the journal number at the start
+    fn decrypt_page(&self, data_key: &[u8; 32], aad: &[u8], page_number: u32) -> Option<Vec<u8>> {
+        let page = self.data_page(page_number);
+        let cipher = AesGcmSiv::<Aes256>::new(Key::from_slice(data_key));
+        cipher
+            .decrypt(
+                Nonce::from_slice(&page[..NONCE_LEN]),
+                Payload {
+                    aad,
+                    msg: &page[NONCE_LEN..],
+                },
+            )
+            .ok()
+    }
+    /// Decrypts a basis root page, which is stored with a key commitment. Returns the plaintext only if
+    /// both the AEAD and the commitment check pass.
+    fn decrypt_page_with_commit(
+        &self,
+        data_key: &[u8; 32],
+        aad: &[u8],
+        page_number: u32,
+    ) -> Option<Vec<u8>> {
+        let page = self.data_page(page_number);
+        let nonce = &page[..NONCE_LEN];
+        let (ct, rest) = page[NONCE_LEN..].split_at(KCOM_CT_LEN);
+        let (kcom_nonce, rest) = rest.split_at(KCOM_NONCE_LEN);
+        let kcom_stored = &rest[..KCOM_LEN];
+        let mac = &page[PAGE_SIZE - TAG_LEN..];
+        let mut ct_plus_mac = ct.to_vec();
+        ct_plus_mac.extend_from_slice(mac);
+
+        let (kenc, kcom) = kcom_keys(data_key, kcom_nonce);
+        let cipher = AesGcmSiv::<Aes256>::new(Key::from_slice(&kenc));
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    aad,
+                    msg: &ct_plus_mac,
+                },
+            )
+            .ok()?;
+        if kcom == kcom_stored {
+            Some(plaintext)
+        } else {
+            None
+        }
+    }
+}
+
+/// The page table, decoded for one basis
+struct BasisMap {
+    /// virtual address -> physical page number
+    v2p: BTreeMap<u64, u32>,
+    /// number of checksum-valid entries that were superseded by a newer journal revision
+    superseded: usize,
+}
+
+/// Decodes the page table entries that belong to `keys`, resolving duplicate mappings in favor of
+/// the highest journal revision, the same way `PddbOs::pt_scan_key()` does at mount.
+fn scan_page_table(image: &Image, keys: &BasisKeys, problems: &mut Vec<String>) -> BasisMap {
+    let cipher = Aes256::new(GenericArray::from_slice(&keys.pt));
+    let aad = basis_aad(&keys.name, image.dna);
+    let mut map = BasisMap {
+        v2p: BTreeMap::new(),
+        superseded: 0,
+    };
+    let journal = |vaddr: u64, page_number: u32| -> Option<u32> {
+        let data = if vaddr == VPAGE_SIZE as u64 {
+            image.decrypt_page_with_commit(&keys.data, &aad, page_number)
+        } else {
+            image.decrypt_page(&keys.data, &aad, page_number)
+        };
+        data.map(|d| read_u32(&d, 0))
+    };
+    for (page_index, pt_page) in image.disk[..image.pt_len].chunks(PAGE_SIZE).enumerate() {
+        let pt_page = if pt_page[..BLOCK_SIZE] == [0xFF; BLOCK_SIZE] {
+            image.mbbb_retrieve().unwrap_or(pt_page)
+        } else {
+            pt_page
+        };
+        for (index, candidate) in pt_page.chunks_exact(PTE_LEN).enumerate() {
+            let mut block = Block::clone_from_slice(candidate);
+            cipher.decrypt_block(&mut block);
+            let seed = read_u32(&block, PTE_NONCE_OFFSET);
+            if read_u32(&block, PTE_CHECKSUM_OFFSET)
+                != murmur3_32(&block[..PTE_CHECKSUM_OFFSET], seed)
+            {
+                continue;
+            }
+            let mut addr = [0u8; 8];
+            addr[..PTE_ADDR_LEN].copy_from_slice(&block[..PTE_ADDR_LEN]);
+            let vaddr = u64::from_le_bytes(addr) * VPAGE_SIZE as u64;
+            let page_number = (page_index * PAGE_SIZE / PTE_LEN + index) as u32;
+            if page_number >= image.data_pages() {
+                problems.push(format!(
+                    "{}: PTE for v{:x} points past the end of the data area (p{:x})",
+                    keys.name, vaddr, page_number
+                ));
+                continue;
+            }
+            if let Some(&prev_page) = map.v2p.get(&vaddr) {
+                map.superseded += 1;
+                match (journal(vaddr, prev_page), journal(vaddr, page_number)) {
+                    (Some(prev_j), Some(new_j)) => {
+                        if new_j > prev_j {
+                            map.v2p.insert(vaddr, page_number);
+                        } else if new_j == prev_j {
+                            problems.push(format!(
+                                "{}: v{:x} is mapped to p{:x} and p{:x} with the same journal revision",
+                                keys.name, vaddr, prev_page, page_number
+                            ));
+                        }
+                    }
+                    (None, Some(_)) => {
+                        map.v2p.insert(vaddr, page_number);
+                    }
+                    (_, None) => {}
+                }
+            } else {
+                map.v2p.insert(vaddr, page_number);
+            }
+        }
+    }
+    map
+}
+
+struct KeyEntry {
+    name: String,
+    start: u64,
+    len: u64,
+    reserved: u64,
+    age: u32,
+}
+
+struct DictEntry {
+    name: String,
+    index: u64,
+    age: u32,
+    num_keys: u32,
+    keys: Vec<KeyEntry>,
+}
+
+/// A fully decoded basis: the root record and the dictionaries hanging off of it
+struct Basis {
+    name: String,
+    age: u32,
+    num_dicts: u32,
+    dicts: Vec<DictEntry>,
+    map: BasisMap,
+}
+
+fn decode_basis(image: &Image, keys: &BasisKeys, problems: &mut Vec<String>) -> Option<Basis> {
+    let map = scan_page_table(image, keys, problems);
+    let aad = basis_aad(&keys.name, image.dna);
+    let root_page = match map.v2p.get(&(VPAGE_SIZE as u64)) {
+        Some(&p) => p,
+        None => {
+            problems.push(format!(
+                "{}: basis root is not mapped in the page table",
+                keys.name
+            ));
+            return None;
+        }
+    };
+    let root = match image.decrypt_page_with_commit(&keys.data, &aad, root_page) {
+        Some(root) => root,
+        None => {
+            problems.push(format!(
+                "{}: basis root at p{:x} does not decrypt or fails the key commitment",
+                keys.name, root_page
+            ));
+            return None;
+        }
+    };
+    let root = &root[JOURNAL_LEN..JOURNAL_LEN + BASIS_ROOT_LEN];
+    let magic = &root[BASIS_ROOT_MAGIC_OFFSET..BASIS_ROOT_MAGIC_OFFSET + 4];
+    if magic != PDDB_MAGIC {
+        problems.push(format!(
+            "{}: basis root has bad magic {:x?}",
+            keys.name, magic
+        ));
+    }
+    let version = read_u32(root, BASIS_ROOT_VERSION_OFFSET);
+    if version != PDDB_VERSION {
+        problems.push(format!(
+            "{}: basis root has version {:x}, expected {:x}",
+            keys.name, version, PDDB_VERSION
+        ));
+    }
+    let name = read_name(root, BASIS_ROOT_NAME_OFFSET);
+    if name != keys.name {
+        problems.push(format!("{}: basis root is named '{}'", keys.name, name));
+    }
+    let mut basis = Basis {
+        name,
+        age: read_u32(root, BASIS_ROOT_AGE_OFFSET),
+        num_dicts: read_u32(root, BASIS_ROOT_NUM_DICTS_OFFSET),
+        dicts: Vec::new(),
+        map,
+    };
+
+    let dict_indices: Vec<u64> = basis
+        .map
+        .v2p
+        .keys()
+        .filter(|&&va| {
+            va % DICT_VSIZE == 0 && va / DICT_VSIZE >= 1 && va / DICT_VSIZE <= DICT_MAXCOUNT as u64
+        })
+        .map(|&va| va / DICT_VSIZE)
+        .collect();
+    for index in dict_indices {
+        if let Some(dict) = decode_dict(image, keys, &basis.map, index, problems) {
+            basis.dicts.push(dict);
+        }
+    }
+    if basis.dicts.len() != basis.num_dicts as usize {
+        problems.push(format!(
+            "{}: basis root records {} dictionaries, but {} were found",
+            basis.name,
+            basis.num_dicts,
+            basis.dicts.len()
+        ));
+    }
+    Some(basis)
+}
+
+fn decode_dict(
+    image: &Image,
+    keys: &BasisKeys,
+    map: &BasisMap,
+    index: u64,
+    problems: &mut Vec<String>,
+) -> Option<DictEntry> {
+    let aad = basis_aad(&keys.name, image.dna);
+    let dict_base = index * DICT_VSIZE;
+    let first_page = match image.decrypt_page(&keys.data, &aad, map.v2p[&dict_base]) {
+        Some(page) => page[JOURNAL_LEN..].to_vec(),
+        None => {
+            problems.push(format!(
+                "{}: dictionary header at v{:x} does not decrypt",
+                keys.name, dict_base
+            ));
+            return None;
+        }
+    };
+    let header = &first_page[..DK_STRIDE];
+    if !is_valid(read_u32(header, DICT_FLAGS_OFFSET)) {
+        return None;
+    }
+    let mut dict = DictEntry {
+        name: read_name(header, DICT_NAME_OFFSET),
+        index,
+        age: read_u32(header, DICT_AGE_OFFSET),
+        num_keys: read_u32(header, DICT_NUM_KEYS_OFFSET),
+        keys: Vec::new(),
+    };
+    // the descriptor pages of the dictionary are the mapped vpages in its virtual region
+    for (&vaddr, &page_number) in map.v2p.range(dict_base..dict_base + DICT_VSIZE) {
+        let page = if vaddr == dict_base {
+            first_page.clone()
+        } else {
+            match image.decrypt_page(&keys.data, &aad, page_number) {
+                Some(page) => page[JOURNAL_LEN..].to_vec(),
+                None => {
+                    problems.push(format!(
+                        "{}:{}: key descriptor page at v{:x} does not decrypt",
+                        keys.name, dict.name, vaddr
+                    ));
+                    continue;
+                }
+            }
+        };
+        for slot in 0..DK_PER_VPAGE {
+            // slot 0 of the first vpage is the dictionary header itself
+            if vaddr == dict_base && slot == 0 {
+                continue;
+            }
+            let record = &page[slot * DK_STRIDE..(slot + 1) * DK_STRIDE];
+            if !is_valid(read_u32(record, KEY_FLAGS_OFFSET)) {
+                continue;
+            }
+            let key = KeyEntry {
+                name: read_name(record, KEY_NAME_OFFSET),
+                start: read_u64(record, KEY_START_OFFSET),
+                len: read_u64(record, KEY_LEN_OFFSET),
+                reserved: read_u64(record, KEY_RESERVED_OFFSET),
+                age: read_u32(record, KEY_AGE_OFFSET),
+            };
+            if key.len > key.reserved {
+                problems.push(format!(
+                    "{}:{}:{}: length {} exceeds reservation {}",
+                    keys.name, dict.name, key.name, key.len, key.reserved
+                ));
+            }
+            if key.start < SMALL_POOL_START
+                || (key.start >= SMALL_POOL_END && key.start < LARGE_POOL_START)
+            {
+                problems.push(format!(
+                    "{}:{}:{}: data address {:x} is outside of the key pools",
+                    keys.name, dict.name, key.name, key.start
+                ));
+            }
+            dict.keys.push(key);
+        }
+    }
+    if dict.keys.len() != dict.num_keys as usize {
+        problems.push(format!(
+            "{}:{}: header records {} keys, but {} were found",
+            keys.name,
+            dict.name,
+            dict.num_keys,
+            dict.keys.len()
+        ));
+    }
+    Some(dict)
+}
+
+/// Reads the contents of a key. Missing or undecryptable pages are reported as errors.
+fn read_key(
+    image: &Image,
+    keys: &BasisKeys,
+    map: &BasisMap,
+    key: &KeyEntry,
+) -> io::Result<Vec<u8>> {
+    let aad = basis_aad(&keys.name, image.dna);
+    let mut data = Vec::with_capacity(key.len as usize);
+    let mut vaddr = key.start;
+    while vaddr < key.start + key.len {
+        let vpage = vaddr / VPAGE_SIZE as u64 * VPAGE_SIZE as u64;
+        let offset = (vaddr - vpage) as usize;
+        let len = (VPAGE_SIZE - offset).min((key.start + key.len - vaddr) as usize);
+        let page_number = *map.v2p.get(&vpage).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("missing data allocation at v{:x}", vpage),
+            )
+        })?;
+        let page = image
+            .decrypt_page(&keys.data, &aad, page_number)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("data at v{:x} (p{:x}) does not decrypt", vpage, page_number),
+                )
+            })?;
+        data.extend_from_slice(&page[JOURNAL_LEN + offset..JOURNAL_LEN + offset + len]);
+        vaddr += len as u64;
+    }
+    Ok(data)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpaceState {
+    Free,
+    MaybeUsed,
+    Used,
+    Dirty,
+}
+impl From<u32> for SpaceState {
+    fn from(code: u32) -> Self {
+        match code & 0x3 {
+            0 => SpaceState::Free,
+            1 => SpaceState::MaybeUsed,
+            2 => SpaceState::Used,
+            _ => SpaceState::Dirty,
+        }
+    }
+}
+
+/// Reads the free space tracked by the FSCB: the FastSpace record, patched by any SpaceUpdate records.
+/// Returns a map of physical page number -> (journal, state).
+fn read_fscb(
+    image: &Image,
+    system: &BasisKeys,
+    problems: &mut Vec<String>,
+) -> HashMap<u32, (u32, SpaceState)> {
+    let mut space = HashMap::new();
+    let fscb = &image.disk[image.fscb_base..image.fscb_base + FSCB_PAGES * PAGE_SIZE];
+    let blank = [0xFFu8; BLOCK_SIZE];
+    let mut log_pages = Vec::new();
+    let mut fastspace_found = false;
+    let mut page_start = 0;
+    while page_start < fscb.len() {
+        let page = &fscb[page_start..];
+        if page[..BLOCK_SIZE] == blank && page[BLOCK_SIZE..BLOCK_SIZE * 2] == blank {
+            page_start += PAGE_SIZE;
+        } else if page[..BLOCK_SIZE] == blank {
+            log_pages.push(page_start);
+            page_start += PAGE_SIZE;
+        } else {
+            if fastspace_found {
+                problems.push(format!(
+                    "FSCB: more than one FastSpace record (extra at page {})",
+                    page_start / PAGE_SIZE
+                ));
+            }
+            fastspace_found = true;
+            let end = (page_start + FASTSPACE_PAGES * PAGE_SIZE).min(fscb.len());
+            let aad = basis_aad(PDDB_FAST_SPACE_SYSTEM_BASIS, image.dna);
+            let cipher = AesGcmSiv::<Aes256>::new(Key::from_slice(&system.data));
+            match cipher.decrypt(
+                Nonce::from_slice(&fscb[page_start..page_start + NONCE_LEN]),
+                Payload {
+                    aad: &aad,
+                    msg: &fscb[page_start + NONCE_LEN..end],
+                },
+            ) {
+                Ok(record) => {
+                    for pp in record
+                        .chunks_exact(4)
+                        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                    {
+                        if pp_valid(pp) && pp_state(pp) == SpaceState::Free {
+                            space.insert(pp_page_number(pp), (pp_journal(pp), SpaceState::Free));
+                        }
+                    }
+                }
+                Err(_) => problems.push(format!(
+                    "FSCB: FastSpace record at page {} does not decrypt",
+                    page_start / PAGE_SIZE
+                )),
+            }
+            page_start = end;
+        }
+    }
+    if !fastspace_found {
+        problems.push("FSCB: no FastSpace record found".to_string());
+    }
+    let cipher = Aes256::new(GenericArray::from_slice(&system.pt));
+    for &log_start in log_pages.iter() {
+        for ct_block in fscb[log_start + BLOCK_SIZE..log_start + PAGE_SIZE].chunks_exact(BLOCK_SIZE)
+        {
+            if ct_block == blank {
+                break;
+            }
+            let mut block = Block::clone_from_slice(ct_block);
+            cipher.decrypt_block(&mut block);
+            // note that the seed uses big-endian re-encoding of a portion of the nonce!
+            let seed = u32::from_be_bytes(block[4..8].try_into().unwrap());
+            if read_u32(&block, 12) != murmur3_32(&block[..12], seed) {
+                problems.push(format!(
+                    "FSCB: corrupted SpaceUpdate record in page {}",
+                    log_start / PAGE_SIZE
+                ));
+                continue;
+            }
+            let pp = read_u32(&block, 8);
+            if !pp_valid(pp) {
+                continue;
+            }
+            let journal = pp_journal(pp);
+            match space.get(&pp_page_number(pp)) {
+                Some(&(prev_journal, _)) if prev_journal > journal => {}
+                _ => {
+                    space.insert(pp_page_number(pp), (journal, pp_state(pp)));
+                }
+            }
+        }
+    }
+    space
+}
+
+fn find_keys<'a>(keys: &'a [BasisKeys], name: &str) -> io::Result<&'a BasisKeys> {
+    keys.iter().find(|k| k.name == name).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("no key for basis '{}' in the key file", name),
+        )
+    })
+}
+
+fn list(image: &Image, keys: &[BasisKeys], basis_filter: Option<&str>) -> io::Result<()> {
+    let mut problems = Vec::new();
+    for k in keys
+        .iter()
+        .filter(|k| basis_filter.is_none() || basis_filter == Some(k.name.as_str()))
+    {
+        if let Some(basis) = decode_basis(image, k, &mut problems) {
+            println!(
+                "basis {} (age {}, {} dicts, {} pages)",
+                basis.name,
+                basis.age,
+                basis.num_dicts,
+                basis.map.v2p.len()
+            );
+            for dict in basis.dicts.iter() {
+                println!(
+                    "  dict {} (index {}, age {}, {} keys)",
+                    dict.name, dict.index, dict.age, dict.num_keys
+                );
+                for key in dict.keys.iter() {
+                    println!(
+                        "    {} ({} bytes, reserved {}, age {}, v{:x})",
+                        key.name, key.len, key.reserved, key.age, key.start
+                    );
+                }
+            }
+        }
+    }
+    for problem in problems.iter() {
+        log::warn!("{}", problem);
+    }
+    Ok(())
+}
+
+fn extract(
+    image: &Image,
+    keys: &[BasisKeys],
+    basis_name: &str,
+    dict_name: &str,
+    key_name: &str,
+    output: Option<&str>,
+) -> io::Result<()> {
+    let k = find_keys(keys, basis_name)?;
+    let mut problems = Vec::new();
+    let basis = decode_basis(image, k, &mut problems).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("basis '{}' could not be decoded", basis_name),
+        )
+    })?;
+    let key = basis
+        .dicts
+        .iter()
+        .find(|d| d.name == dict_name)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("dictionary '{}' not found", dict_name),
+            )
+        })?
+        .keys
+        .iter()
+        .find(|key| key.name == key_name)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("key '{}' not found", key_name)))?;
+    let data = read_key(image, k, &basis.map, key)?;
+    match output {
+        Some(filename) => File::create(filename)?.write_all(&data),
+        None => io::stdout().write_all(&data),
+    }
+}
+
+fn free(image: &Image, keys: &[BasisKeys]) -> io::Result<()> {
+    let system = find_keys(keys, SYSTEM_BASIS)?;
+    let mut problems = Vec::new();
+    let space = read_fscb(image, system, &mut problems);
+    let mut used = 0;
+    for k in keys.iter() {
+        let map = scan_page_table(image, k, &mut problems);
+        println!("{:>24}: {} pages", k.name, map.v2p.len());
+        used += map.v2p.len();
+    }
+    let count = |state| space.values().filter(|&&(_, s)| s == state).count();
+    let total = image.data_pages() as usize;
+    println!("{:>24}: {} pages", "total", total);
+    println!("{:>24}: {} pages", "used by known bases", used);
+    println!(
+        "{:>24}: {} pages ({} bytes)",
+        "free (FSCB)",
+        count(SpaceState::Free),
+        count(SpaceState::Free) * VPAGE_SIZE
+    );
+    println!("{:>24}: {} pages", "dirty (FSCB)", count(SpaceState::Dirty));
+    println!(
+        "{:>24}: {} pages",
+        "other",
+        total.saturating_sub(used + count(SpaceState::Free) + count(SpaceState::Dirty))
+    );
+    for problem in problems.iter() {
+        log::warn!("{}", problem);
+    }
+    Ok(())
+}
+
+/// Walks every basis in the key file, reads every key, and cross-checks the page tables against each
+/// other and against the FSCB. Returns the problems found.
+fn check(image: &Image, keys: &[BasisKeys]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut owners: HashMap<u32, (String, u64)> = HashMap::new();
+    for k in keys.iter() {
+        let basis = match decode_basis(image, k, &mut problems) {
+            Some(basis) => basis,
+            None => continue,
+        };
+        for (&vaddr, &page_number) in basis.map.v2p.iter() {
+            if let Some((owner, owner_va)) = owners.insert(page_number, (basis.name.clone(), vaddr))
+            {
+                problems.push(format!(
+                    "p{:x} is mapped by both {}:v{:x} and {}:v{:x}",
+                    page_number, owner, owner_va, basis.name, vaddr
+                ));
+            }
+        }
+        let mut key_count = 0;
+        for dict in basis.dicts.iter() {
+            for key in dict.keys.iter() {
+                key_count += 1;
+                if let Err(e) = read_key(image, k, &basis.map, key) {
+                    problems.push(format!("{}:{}:{}: {}", basis.name, dict.name, key.name, e));
+                }
+            }
+        }
+        println!(
+            "{}: {} dicts, {} keys, {} pages, {} stale PTEs",
+            basis.name,
+            basis.dicts.len(),
+            key_count,
+            basis.map.v2p.len(),
+            basis.map.superseded
+        );
+    }
+    if let Ok(system) = find_keys(keys, SYSTEM_BASIS) {
+        let space = read_fscb(image, system, &mut problems);
+        for (page_number, (_, state)) in space.iter() {
+            if *state == SpaceState::Free {
+                if let Some((owner, vaddr)) = owners.get(page_number) {
+                    problems.push(format!(
+                        "p{:x} is in the free pool, but is mapped by {}:v{:x}",
+                        page_number, owner, vaddr
+                    ));
+                }
+            }
+        }
+    } else {
+        problems.push(format!(
+            "no key for {} in the key file; can't check the FSCB",
+            SYSTEM_BASIS
+        ));
+    }
+    problems
+}
+
+fn run(matches: &ArgMatches) -> io::Result<i32> {
+    let name = matches.value_of("name").unwrap_or("pddb");
+    let image_file = matches
+        .value_of("image")
+        .map(|s| s.to_owned())
+        .unwrap_or_else(|| format!("./tools/pddb-images/{}.bin", name));
+    let key_file = matches
+        .value_of("keys")
+        .map(|s| s.to_owned())
+        .unwrap_or_else(|| format!("./tools/pddb-images/{}.key", name));
+    let dna = match matches.value_of("dna") {
+        Some(dna) => u64::from_str_radix(dna.trim_start_matches("0x"), 16)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "dna must be a hex number"))?,
+        None => 0,
+    };
+    let image = Image::open(&image_file, dna)?;
+    let keys = read_keys(&key_file)?;
+
+    match matches.subcommand() {
+        ("list", Some(sub)) => list(&image, &keys, sub.value_of("basis")).map(|_| 0),
+        ("extract", Some(sub)) => extract(
+            &image,
+            &keys,
+            sub.value_of("basis").unwrap(),
+            sub.value_of("dict").unwrap(),
+            sub.value_of("key").unwrap(),
+            sub.value_of("output"),
+        )
+        .map(|_| 0),
+        ("free", Some(_)) => free(&image, &keys).map(|_| 0),
+        ("check", Some(_)) => {
+            let problems = check(&image, &keys);
+            for problem in problems.iter() {
+                println!("PROBLEM: {}", problem);
+            }
+            println!("{} problem(s) found", problems.len());
+            Ok(if problems.is_empty() { 0 } else { 1 })
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("pddb-inspect")
+        .version(crate_version!())
+        .about("Inspect PDDB disk images offline")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Image root name; reads ./tools/pddb-images/NAME.bin and NAME.key (default: pddb)"),
+        )
+        .arg(
+            Arg::with_name("image")
+                .long("image")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Path to the disk image, overriding --name"),
+        )
+        .arg(
+            Arg::with_name("keys")
+                .long("keys")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Path to the key file, overriding --name"),
+        )
+        .arg(
+            Arg::with_name("dna")
+                .long("dna")
+                .takes_value(true)
+                .value_name("HEX")
+                .help("FPGA DNA of the device that wrote the image (default: 0, as used by hosted mode)"),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List the bases, dictionaries and keys")
+                .arg(Arg::with_name("basis").help("Only list this basis")),
+        )
+        .subcommand(
+            SubCommand::with_name("extract")
+                .about("Extract the contents of a key")
+                .arg(Arg::with_name("basis").required(true))
+                .arg(Arg::with_name("dict").required(true))
+                .arg(Arg::with_name("key").required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Write to FILE instead of stdout"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("free").about("Report free space"))
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Report integrity problems; exits non-zero if any are found"),
+        )
+        .get_matches();
+
+    match run(&matches) {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncrypt;
+
+    const IMAGE_PAGES: usize = 64;
+    const DATA_KEY: [u8; 32] = [0x11; 32];
+    const PT_KEY: [u8; 32] = [0x22; 32];
+
+    fn system_keys() -> BasisKeys {
+        BasisKeys {
+            name: SYSTEM_BASIS.to_string(),
+            data: DATA_KEY,
+            pt: PT_KEY,
+        }
+    }
+
+    /// An image as it comes out of a freshly erased FLASH
+    fn erased_image() -> Image {
+        Image::from_bytes(vec![0xFF; IMAGE_PAGES * PAGE_SIZE], 0).unwrap()
+    }
+
+    fn put_pte(image: &mut Image, vaddr: u64, page_number: u32, nonce: u32) {
+        let mut pte = [0u8; PTE_LEN];
+        pte[..PTE_ADDR_LEN]
+            .copy_from_slice(&(vaddr / VPAGE_SIZE as u64).to_le_bytes()[..PTE_ADDR_LEN]);
+        pte[PTE_NONCE_OFFSET..PTE_CHECKSUM_OFFSET].copy_from_slice(&nonce.to_le_bytes());
+        let checksum = murmur3_32(&pte[..PTE_CHECKSUM_OFFSET], nonce);
+        pte[PTE_CHECKSUM_OFFSET..].copy_from_slice(&checksum.to_le_bytes());
+        let mut block = Block::clone_from_slice(&pte);
+        Aes256::new(GenericArray::from_slice(&PT_KEY)).encrypt_block(&mut block);
+        let start = page_number as usize * PTE_LEN;
+        image.disk[start..start + PTE_LEN].copy_from_slice(&block);
+    }
+
+    fn put_data_page(image: &mut Image, page_number: u32, page: &[u8]) {
+        let start = image.data_base + page_number as usize * PAGE_SIZE;
+        image.disk[start..start + PAGE_SIZE].copy_from_slice(page);
+    }
+
+    fn seal(key: &[u8], aad: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        AesGcmSiv::<Aes256>::new(Key::from_slice(key))
+            .encrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    aad,
+                    msg: plaintext,
+                },
+            )
+            .unwrap()
+    }
+
+    /// A data page: the nonce, then the journal and `data` padded out to a vpage, then the tag
+    fn data_page(journal: u32, data: &[u8]) -> Vec<u8> {
+        let mut plaintext = journal.to_le_bytes().to_vec();
+        plaintext.extend_from_slice(data);
+        plaintext.resize(JOURNAL_LEN + VPAGE_SIZE, 0);
+        let nonce = [journal as u8; NONCE_LEN];
+        let mut page = nonce.to_vec();
+        page.extend(seal(
+            &DATA_KEY,
+            &basis_aad(SYSTEM_BASIS, 0),
+            &nonce,
+            &plaintext,
+        ));
+        page
+    }
+
+    /// A basis root page: the nonce, the ciphertext, the commitment nonce and commitment, then the tag
+    fn root_page(journal: u32, root: &[u8]) -> Vec<u8> {
+        let mut plaintext = journal.to_le_bytes().to_vec();
+        plaintext.extend_from_slice(root);
+        plaintext.resize(KCOM_CT_LEN, 0);
+        let nonce = [journal as u8; NONCE_LEN];
+        let kcom_nonce = [0x33; KCOM_NONCE_LEN];
+        let (kenc, kcom) = kcom_keys(&DATA_KEY, &kcom_nonce);
+        let sealed = seal(&kenc, &basis_aad(SYSTEM_BASIS, 0), &nonce, &plaintext);
+        let mut page = nonce.to_vec();
+        page.extend_from_slice(&sealed[..KCOM_CT_LEN]);
+        page.extend_from_slice(&kcom_nonce);
+        page.extend_from_slice(&kcom);
+        page.extend_from_slice(&sealed[KCOM_CT_LEN..]);
+        page
+    }
+
+    fn put_name(record: &mut [u8], offset: usize, name: &str) {
+        record[offset] = name.len() as u8;
+        record[offset + 1..offset + 1 + name.len()].copy_from_slice(name.as_bytes());
+    }
+
+    /// Writes a system basis holding one dictionary, "dict", with one key, "key", containing `contents`
+    fn put_basis(image: &mut Image, contents: &[u8]) {
+        let mut root = [0u8; BASIS_ROOT_LEN];
+        root[BASIS_ROOT_MAGIC_OFFSET..BASIS_ROOT_MAGIC_OFFSET + 4].copy_from_slice(&PDDB_MAGIC);
+        root[BASIS_ROOT_VERSION_OFFSET..BASIS_ROOT_VERSION_OFFSET + 4]
+            .copy_from_slice(&PDDB_VERSION.to_le_bytes());
+        root[BASIS_ROOT_AGE_OFFSET..BASIS_ROOT_AGE_OFFSET + 4].copy_from_slice(&3u32.to_le_bytes());
+        root[BASIS_ROOT_NUM_DICTS_OFFSET..BASIS_ROOT_NUM_DICTS_OFFSET + 4]
+            .copy_from_slice(&1u32.to_le_bytes());
+        put_name(&mut root, BASIS_ROOT_NAME_OFFSET, SYSTEM_BASIS);
+        put_data_page(image, 0, &root_page(1, &root));
+        put_pte(image, VPAGE_SIZE as u64, 0, 0x1234);
+
+        let mut descriptors = vec![0u8; DK_STRIDE * 2];
+        let (header, key) = descriptors.split_at_mut(DK_STRIDE);
+        header[DICT_FLAGS_OFFSET..DICT_FLAGS_OFFSET + 4].copy_from_slice(&1u32.to_le_bytes());
+        header[DICT_NUM_KEYS_OFFSET..DICT_NUM_KEYS_OFFSET + 4].copy_from_slice(&1u32.to_le_bytes());
+        header[DICT_FREE_KEY_INDEX_OFFSET..DICT_FREE_KEY_INDEX_OFFSET + 4]
+            .copy_from_slice(&2u32.to_le_bytes());
+        put_name(header, DICT_NAME_OFFSET, "dict");
+        key[KEY_START_OFFSET..KEY_START_OFFSET + 8]
+            .copy_from_slice(&SMALL_POOL_START.to_le_bytes());
+        key[KEY_LEN_OFFSET..KEY_LEN_OFFSET + 8]
+            .copy_from_slice(&(contents.len() as u64).to_le_bytes());
+        key[KEY_RESERVED_OFFSET..KEY_RESERVED_OFFSET + 8].copy_from_slice(&64u64.to_le_bytes());
+        key[KEY_FLAGS_OFFSET..KEY_FLAGS_OFFSET + 4].copy_from_slice(&1u32.to_le_bytes());
+        put_name(key, KEY_NAME_OFFSET, "key");
+        put_data_page(image, 1, &data_page(1, &descriptors));
+        put_pte(image, DICT_VSIZE, 1, 0x5678);
+
+        put_data_page(image, 2, &data_page(1, contents));
+        put_pte(image, SMALL_POOL_START, 2, 0x9abc);
+    }
+
+    #[test]
+    fn parses_key_files() {
+        let mut raw = 1u32.to_le_bytes().to_vec();
+        let mut name = [0u8; 64];
+        name[..SYSTEM_BASIS.len()].copy_from_slice(SYSTEM_BASIS.as_bytes());
+        raw.extend_from_slice(&name);
+        raw.extend_from_slice(&DATA_KEY);
+        raw.extend_from_slice(&PT_KEY);
+        let keys = parse_keys(&raw).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].name, SYSTEM_BASIS);
+        assert_eq!(keys[0].data, DATA_KEY);
+        assert_eq!(keys[0].pt, PT_KEY);
+        assert!(parse_keys(&raw[..raw.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn rejects_images_that_cannot_hold_a_pddb() {
+        assert!(Image::from_bytes(vec![0xFF; PAGE_SIZE * 8], 0).is_err());
+        assert!(Image::from_bytes(vec![0xFF; PAGE_SIZE * IMAGE_PAGES + 1], 0).is_err());
+    }
+
+    #[test]
+    fn decodes_a_basis_and_reads_its_keys() {
+        let mut image = erased_image();
+        put_basis(&mut image, b"hello");
+        let keys = system_keys();
+        let mut problems = Vec::new();
+        let basis = decode_basis(&image, &keys, &mut problems).unwrap();
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(basis.name, SYSTEM_BASIS);
+        assert_eq!(basis.age, 3);
+        assert_eq!(basis.dicts.len(), 1);
+        let dict = &basis.dicts[0];
+        assert_eq!(
+            (dict.name.as_str(), dict.index, dict.num_keys),
+            ("dict", 1, 1)
+        );
+        assert_eq!(dict.keys.len(), 1);
+        let key = &dict.keys[0];
+        assert_eq!((key.name.as_str(), key.len, key.reserved), ("key", 5, 64));
+        assert_eq!(read_key(&image, &keys, &basis.map, key).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn newer_journal_revisions_win() {
+        let mut image = erased_image();
+        put_basis(&mut image, b"old");
+        put_data_page(&mut image, 3, &data_page(2, b"new"));
+        put_pte(&mut image, SMALL_POOL_START, 3, 0xdef0);
+        let keys = system_keys();
+        let mut problems = Vec::new();
+        let basis = decode_basis(&image, &keys, &mut problems).unwrap();
+        assert_eq!(basis.map.v2p[&SMALL_POOL_START], 3);
+        assert_eq!(basis.map.superseded, 1);
+        let key = &basis.dicts[0].keys[0];
+        assert_eq!(read_key(&image, &keys, &basis.map, key).unwrap(), b"new");
+    }
+
+    #[test]
+    fn reports_damaged_pages() {
+        let mut image = erased_image();
+        put_basis(&mut image, b"hello");
+        let start = image.data_base + 2 * PAGE_SIZE + NONCE_LEN;
+        image.disk[start] ^= 1;
+        let problems = check(&image, &[system_keys()]);
+        assert!(
+            problems.iter().any(|p| p.contains("does not decrypt")),
+            "{:?}",
+            problems
+        );
+    }
+
+    #[test]
+    fn reads_the_free_space_log() {
+        let mut image = erased_image();
+        let pp = |page: u32, state: u32, journal: u32| {
+            page | 1 << PP_VALID_BIT | state << PP_STATE_LSB | journal << PP_JOURNAL_LSB
+        };
+        // a FastSpace record with two free pages
+        let mut record = Vec::new();
+        for entry in [pp(5, 0, 1), pp(6, 0, 1)].iter() {
+            record.extend_from_slice(&entry.to_le_bytes());
+        }
+        record.resize(FASTSPACE_PAGES * PAGE_SIZE - NONCE_LEN - TAG_LEN, 0);
+        let nonce = [7u8; NONCE_LEN];
+        let aad = basis_aad(PDDB_FAST_SPACE_SYSTEM_BASIS, 0);
+        let fscb = image.fscb_base;
+        image.disk[fscb..fscb + NONCE_LEN].copy_from_slice(&nonce);
+        let sealed = seal(&DATA_KEY, &aad, &nonce, &record);
+        image.disk[fscb + NONCE_LEN..fscb + FASTSPACE_PAGES * PAGE_SIZE].copy_from_slice(&sealed);
+        // followed by a log page that marks page 5 as dirty
+        let mut update = [0u8; BLOCK_SIZE];
+        update[..8].copy_from_slice(&0x0102_0304_0506_0708u64.to_le_bytes());
+        update[8..12].copy_from_slice(&pp(5, 3, 2).to_le_bytes());
+        let seed = u32::from_be_bytes(update[4..8].try_into().unwrap());
+        let checksum = murmur3_32(&update[..12], seed);
+        update[12..].copy_from_slice(&checksum.to_le_bytes());
+        let mut block = Block::clone_from_slice(&update);
+        Aes256::new(GenericArray::from_slice(&PT_KEY)).encrypt_block(&mut block);
+        let log = fscb + FASTSPACE_PAGES * PAGE_SIZE + BLOCK_SIZE;
+        image.disk[log..log + BLOCK_SIZE].copy_from_slice(&block);
+
+        let mut problems = Vec::new();
+        let space = read_fscb(&image, &system_keys(), &mut problems);
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(space.get(&5), Some(&(2, SpaceState::Dirty)));
+        assert_eq!(space.get(&6), Some(&(1, SpaceState::Free)));
+        assert_eq!(space.len(), 2);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::xous_arguments::{XousArgument, XousArgumentCode, XousSize};
+use std::fmt;
+use std::io;
+
+/// The resources that may be limited, by name, along with the number the kernel
+/// uses for each of them.
+const RESOURCES: [(&str, u32); 4] = [
+    ("pages", 1),
+    ("threads", 2),
+    ("servers", 3),
+    ("connections", 4),
+];
+
+#[derive(Debug)]
+pub struct ProcessLimits {
+    /// A vec of (PID, resource, limit) entries
+    limits: Vec<(u32, u32, u32)>,
+}
+
+impl fmt::Display for ProcessLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "    process limits:")?;
+        for (pid, resource, limit) in self.limits.iter() {
+            let name = RESOURCES
+                .iter()
+                .find(|(_, number)| number == resource)
+                .map(|(name, _)| *name)
+                .unwrap_or("unknown");
+            writeln!(f, "        PID {}: {} {}", pid, limit, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl ProcessLimits {
+    pub fn new() -> ProcessLimits {
+        ProcessLimits { limits: vec![] }
+    }
+
+    /// Limit a resource of the given process. Returns an error if the resource
+    /// name is not known.
+    pub fn set(&mut self, pid: u32, resource: &str, limit: u32) -> Result<(), String> {
+        let (_, number) = RESOURCES
+            .iter()
+            .find(|(name, _)| *name == resource)
+            .ok_or_else(|| {
+                format!(
+                    "unknown resource \"{}\", expected one of: {}",
+                    resource,
+                    RESOURCES
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )
+            })?;
+        self.limits.retain(|(p, r, _)| !(*p == pid && r == number));
+        self.limits.push((pid, *number, limit));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+}
+
+impl XousArgument for ProcessLimits {
+    fn code(&self) -> XousArgumentCode {
+        u32::from_le_bytes(*b"PLim")
+    }
+
+    fn length(&self) -> XousSize {
+        (self.limits.len() * 12) as XousSize
+    }
+
+    fn serialize(&self, output: &mut dyn io::Write) -> io::Result<usize> {
+        let mut written = 0;
+        for (pid, resource, limit) in self.limits.iter() {
+            written += output.write(&pid.to_le_bytes())?;
+            written += output.write(&resource.to_le_bytes())?;
+            written += output.write(&limit.to_le_bytes())?;
+        }
+        Ok(written)
+    }
+}
@@ -29,6 +29,14 @@ impl ProcessNames {
     pub fn set(&mut self, pid: u32, name: &str) {
         self.names.insert(pid, name.to_owned());
     }
+
+    /// Find the PID of the process with the given name
+    pub fn pid_of(&self, name: &str) -> Option<u32> {
+        self.names
+            .iter()
+            .find(|(_, n)| n.as_str() == name)
+            .map(|(pid, _)| *pid)
+    }
 }
 
 impl XousArgument for ProcessNames {
@@ -0,0 +1,79 @@
+use crate::xous_arguments::{XousArgument, XousArgumentCode, XousSize};
+use std::fmt;
+use std::io;
+
+/// The roles that may be given to a process, by name, along with the number the
+/// kernel uses for each of them.
+const ROLES: [(&str, u32); 3] = [("trace-reader", 1), ("supervisor", 2), ("log-admin", 3)];
+
+#[derive(Debug)]
+pub struct ProcessRoles {
+    /// A vec of (PID, role) entries
+    roles: Vec<(u32, u32)>,
+}
+
+impl fmt::Display for ProcessRoles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "    process roles:")?;
+        for (pid, role) in self.roles.iter() {
+            let name = ROLES
+                .iter()
+                .find(|(_, number)| number == role)
+                .map(|(name, _)| *name)
+                .unwrap_or("unknown");
+            writeln!(f, "        PID {}: {}", pid, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl ProcessRoles {
+    pub fn new() -> ProcessRoles {
+        ProcessRoles { roles: vec![] }
+    }
+
+    /// Give a role to the given process, taking it away from any process that
+    /// had it before. Returns an error if the role name is not known.
+    pub fn set(&mut self, pid: u32, role: &str) -> Result<(), String> {
+        let (_, number) = ROLES
+            .iter()
+            .find(|(name, _)| *name == role)
+            .ok_or_else(|| {
+                format!(
+                    "unknown role \"{}\", expected one of: {}",
+                    role,
+                    ROLES
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )
+            })?;
+        self.roles.retain(|(_, r)| r != number);
+        self.roles.push((pid, *number));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roles.is_empty()
+    }
+}
+
+impl XousArgument for ProcessRoles {
+    fn code(&self) -> XousArgumentCode {
+        u32::from_le_bytes(*b"PRol")
+    }
+
+    fn length(&self) -> XousSize {
+        (self.roles.len() * 8) as XousSize
+    }
+
+    fn serialize(&self, output: &mut dyn io::Write) -> io::Result<usize> {
+        let mut written = 0;
+        for (pid, role) in self.roles.iter() {
+            written += output.write(&pid.to_le_bytes())?;
+            written += output.write(&role.to_le_bytes())?;
+        }
+        Ok(written)
+    }
+}
@@ -0,0 +1,37 @@
+//! Parks the executor thread on a condition variable, so the executor runs
+//! under `cargo test` on the host without a Xous kernel to talk to.
+
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+pub(crate) struct Parker {
+    notified: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Parker {
+    pub fn new() -> Self {
+        Parker {
+            notified: Mutex::new(false),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Block until `unpark()` is called or the timeout expires. This may also
+    /// return early for no reason.
+    pub fn park(&self, timeout: Option<Duration>) {
+        let mut notified = self.notified.lock().unwrap();
+        if !*notified {
+            notified = match timeout {
+                Some(timeout) => self.condvar.wait_timeout(notified, timeout).unwrap().0,
+                None => self.condvar.wait(notified).unwrap(),
+            };
+        }
+        *notified = false;
+    }
+
+    pub fn unpark(&self) {
+        *self.notified.lock().unwrap() = true;
+        self.condvar.notify_one();
+    }
+}
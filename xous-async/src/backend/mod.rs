@@ -0,0 +1,9 @@
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+mod hosted;
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+pub(crate) use crate::backend::hosted::*;
+
+#[cfg(any(target_os = "none", target_os = "xous"))]
+mod xous;
+#[cfg(any(target_os = "none", target_os = "xous"))]
+pub(crate) use crate::backend::xous::*;
@@ -0,0 +1,77 @@
+//! Parks the executor thread in `ReceiveMessageTimeout` on a private server.
+//! Wakers send that server a scalar, and the kernel's timeout takes care of
+//! sleeping until the next timer, so an idle executor costs no ticktimer
+//! round trips.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+const EMPTY: usize = 0;
+const PARKED: usize = 1;
+const NOTIFIED: usize = 2;
+
+pub(crate) struct Parker {
+    sid: xous::SID,
+    cid: xous::CID,
+    state: AtomicUsize,
+}
+
+impl Parker {
+    pub fn new() -> Self {
+        let sid = xous::create_server().expect("xous-async: couldn't create wake server");
+        let cid = xous::connect(sid).expect("xous-async: couldn't connect to wake server");
+        Parker {
+            sid,
+            cid,
+            state: AtomicUsize::new(EMPTY),
+        }
+    }
+
+    /// Block until `unpark()` is called or the timeout expires. This may also
+    /// return early for no reason.
+    pub fn park(&self, timeout: Option<Duration>) {
+        if self
+            .state
+            .compare_exchange(NOTIFIED, EMPTY, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            return;
+        }
+        if self
+            .state
+            .compare_exchange(EMPTY, PARKED, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            // Notified in between the two checks
+            self.state.store(EMPTY, Ordering::SeqCst);
+            return;
+        }
+
+        // A wakeup that raced with a timeout leaves a message behind, which
+        // makes the next park return early. That's harmless.
+        match timeout {
+            Some(timeout) => {
+                // Round up so a timer that is about to expire doesn't spin
+                let ms = (timeout.as_micros() as usize + 999) / 1000;
+                xous::receive_message_timeout(self.sid, ms.max(1)).ok();
+            }
+            None => {
+                xous::receive_message(self.sid).ok();
+            }
+        }
+        self.state.store(EMPTY, Ordering::SeqCst);
+    }
+
+    pub fn unpark(&self) {
+        if self.state.swap(NOTIFIED, Ordering::SeqCst) == PARKED {
+            xous::try_send_message(self.cid, xous::Message::new_scalar(0, 0, 0, 0, 0)).ok();
+        }
+    }
+}
+
+impl Drop for Parker {
+    fn drop(&mut self) {
+        unsafe { xous::disconnect(self.cid).ok() };
+        xous::destroy_server(self.sid).ok();
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::executor::with_current;
+
+/// The most threads an executor will start for blocking calls. Xous allows a
+/// process only a few dozen threads, so calls beyond this wait their turn.
+pub(crate) const BLOCKING_THREADS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Jobs {
+    queue: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+
+    /// The executor has gone away, so the threads should exit
+    closed: bool,
+}
+
+/// The threads that blocking calls run on. Each executor has one, which starts
+/// threads as they are needed, up to `BLOCKING_THREADS`.
+pub(crate) struct Pool {
+    jobs: Mutex<Jobs>,
+    available: Condvar,
+}
+
+impl Pool {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Pool {
+            jobs: Mutex::new(Jobs {
+                queue: VecDeque::new(),
+                threads: 0,
+                idle: 0,
+                closed: false,
+            }),
+            available: Condvar::new(),
+        })
+    }
+
+    fn submit(self: &Arc<Self>, job: Job) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.idle == 0 && jobs.threads < BLOCKING_THREADS {
+            let pool = self.clone();
+            if std::thread::Builder::new()
+                .spawn(move || pool.work())
+                .is_ok()
+            {
+                jobs.threads += 1;
+            } else if jobs.threads == 0 {
+                // Without any thread to hand it to, the only option left is to
+                // make the call here and block the executor.
+                drop(jobs);
+                job();
+                return;
+            }
+        }
+        jobs.queue.push_back(job);
+        self.available.notify_one();
+    }
+
+    fn work(&self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        loop {
+            if jobs.closed {
+                jobs.threads -= 1;
+                return;
+            }
+            if let Some(job) = jobs.queue.pop_front() {
+                drop(jobs);
+                job();
+                jobs = self.jobs.lock().unwrap();
+                continue;
+            }
+            jobs.idle += 1;
+            jobs = self.available.wait(jobs).unwrap();
+            jobs.idle -= 1;
+        }
+    }
+
+    /// Stop the threads. Calls that haven't started are dropped, and threads
+    /// that are in the middle of a call exit once it returns.
+    pub fn close(&self) {
+        let queue = {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.closed = true;
+            std::mem::take(&mut jobs.queue)
+        };
+        self.available.notify_all();
+        drop(queue);
+    }
+}
+
+struct State<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the result of a blocking call that is running on one of the
+/// executor's helper threads.
+pub struct Unblock<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+/// Run `f` on one of the executor's helper threads and wait for it without
+/// blocking the executor.
+///
+/// The kernel has no asynchronous form of `SendMessage`, so this is how blocking
+/// scalars and memory lends are kept from stalling every other task. There are
+/// at most `BLOCKING_THREADS` helper threads, so prefer a scalar that doesn't
+/// block where the server offers one.
+///
+/// # Panics
+///
+/// Panics if called outside of an executor
+pub fn unblock<F, T>(f: F) -> Unblock<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let state = Arc::new(Mutex::new(State {
+        result: None,
+        waker: None,
+    }));
+    let thread_state = state.clone();
+    let pool = with_current(|local| local.pool());
+    pool.submit(Box::new(move || {
+        let result = f();
+        let mut state = thread_state.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }));
+    Unblock { state }
+}
+
+impl<T> Future for Unblock<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn calls_share_a_few_threads() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let total = crate::block_on({
+            let (running, most) = (running.clone(), most.clone());
+            async move {
+                let calls: Vec<_> = (0..BLOCKING_THREADS * 3)
+                    .map(|i| {
+                        let (running, most) = (running.clone(), most.clone());
+                        unblock(move || {
+                            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                            most.fetch_max(now, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(10));
+                            running.fetch_sub(1, Ordering::SeqCst);
+                            i
+                        })
+                    })
+                    .collect();
+                let mut total = 0;
+                for call in calls {
+                    total += call.await;
+                }
+                total
+            }
+        });
+        assert_eq!(total, (0..BLOCKING_THREADS * 3).sum());
+        assert!(most.load(Ordering::SeqCst) <= BLOCKING_THREADS);
+    }
+
+    #[test]
+    fn closing_stops_the_threads() {
+        let pool = Pool::new();
+        let ran = Arc::new(AtomicUsize::new(0));
+        for _ in 0..BLOCKING_THREADS {
+            let ran = ran.clone();
+            pool.submit(Box::new(move || {
+                ran.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        let start = Instant::now();
+        while ran.load(Ordering::SeqCst) < BLOCKING_THREADS {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::yield_now();
+        }
+
+        // Every thread holds a reference to the pool until it exits
+        pool.close();
+        while Arc::strong_count(&pool) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::yield_now();
+        }
+        assert_eq!(pool.jobs.lock().unwrap().threads, 0);
+    }
+}
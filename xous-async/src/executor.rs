@@ -0,0 +1,381 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Instant;
+
+use crate::backend::Parker;
+use crate::blocking::Pool;
+
+type BoxedTask = Pin<Box<dyn Future<Output = ()>>>;
+
+/// The part of the executor that wakers touch. Wakers may be called from any
+/// thread, so this is the only state that is shared outside the executor thread.
+struct Shared {
+    ready: Mutex<VecDeque<usize>>,
+    parker: Parker,
+}
+
+struct TaskWaker {
+    id: usize,
+
+    /// Set while the task is sitting in the ready queue, so waking a task
+    /// several times before it runs only polls it once
+    queued: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.queued.swap(true, Ordering::SeqCst) {
+            self.shared.ready.lock().unwrap().push_back(self.id);
+            self.shared.parker.unpark();
+        }
+    }
+}
+
+struct Task {
+    future: BoxedTask,
+    waker: Arc<TaskWaker>,
+}
+
+/// Executor state that never leaves the executor thread
+pub(crate) struct Local {
+    shared: Arc<Shared>,
+
+    /// Every task, indexed by ID. A slot is `None` while its task is being
+    /// polled and after the task has finished.
+    tasks: RefCell<Vec<Option<Task>>>,
+    free: RefCell<Vec<usize>>,
+    live: Cell<usize>,
+
+    /// Wakers for pending `Sleep`s, ordered by deadline
+    timers: RefCell<BTreeMap<(Instant, u64), Waker>>,
+    next_timer: Cell<u64>,
+
+    /// Threads for blocking calls
+    pool: Arc<Pool>,
+}
+
+std::thread_local!(static CURRENT: RefCell<Option<Rc<Local>>> = const { RefCell::new(None) });
+
+/// Call `f` with the executor that is running on this thread.
+///
+/// # Panics
+///
+/// Panics if no executor is running on this thread
+pub(crate) fn with_current<F, R>(f: F) -> R
+where
+    F: FnOnce(&Rc<Local>) -> R,
+{
+    CURRENT.with(|current| {
+        let current = current.borrow().clone();
+        f(&current.expect("xous-async: not running inside an executor"))
+    })
+}
+
+impl Local {
+    fn spawn_boxed(&self, future: BoxedTask) {
+        let id = match self.free.borrow_mut().pop() {
+            Some(id) => id,
+            None => {
+                let mut tasks = self.tasks.borrow_mut();
+                tasks.push(None);
+                tasks.len() - 1
+            }
+        };
+        let waker = Arc::new(TaskWaker {
+            id,
+            queued: AtomicBool::new(false),
+            shared: self.shared.clone(),
+        });
+        waker.wake_by_ref();
+        self.tasks.borrow_mut()[id] = Some(Task { future, waker });
+        self.live.set(self.live.get() + 1);
+    }
+
+    fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        let state = Rc::new(RefCell::new(JoinState {
+            result: None,
+            waker: None,
+        }));
+        let task_state = state.clone();
+        self.spawn_boxed(Box::pin(async move {
+            let result = future.await;
+            let mut state = task_state.borrow_mut();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }));
+        JoinHandle { state }
+    }
+
+    pub(crate) fn pool(&self) -> Arc<Pool> {
+        self.pool.clone()
+    }
+
+    pub(crate) fn add_timer(&self, deadline: Instant, waker: Waker) -> u64 {
+        let key = self.next_timer.get();
+        self.next_timer.set(key + 1);
+        self.timers.borrow_mut().insert((deadline, key), waker);
+        key
+    }
+
+    pub(crate) fn update_timer(&self, deadline: Instant, key: u64, waker: &Waker) {
+        if let Some(existing) = self.timers.borrow_mut().get_mut(&(deadline, key)) {
+            if !existing.will_wake(waker) {
+                *existing = waker.clone();
+            }
+        }
+    }
+
+    pub(crate) fn remove_timer(&self, deadline: Instant, key: u64) {
+        self.timers.borrow_mut().remove(&(deadline, key));
+    }
+
+    /// Wake every timer whose deadline has passed, and return the earliest
+    /// deadline that is still pending
+    fn fire_timers(&self) -> Option<Instant> {
+        let now = Instant::now();
+        loop {
+            let mut timers = self.timers.borrow_mut();
+            let next = match timers.keys().next() {
+                Some(&next) => next,
+                None => return None,
+            };
+            if next.0 > now {
+                return Some(next.0);
+            }
+            let waker = timers.remove(&next).unwrap();
+            drop(timers);
+            waker.wake();
+        }
+    }
+
+    fn poll_task(&self, id: usize) {
+        let task = match self
+            .tasks
+            .borrow_mut()
+            .get_mut(id)
+            .and_then(|slot| slot.take())
+        {
+            Some(task) => task,
+            None => return,
+        };
+        let Task { mut future, waker } = task;
+        waker.queued.store(false, Ordering::SeqCst);
+        let task_waker = Waker::from(waker.clone());
+        let mut cx = Context::from_waker(&task_waker);
+        if future.as_mut().poll(&mut cx).is_pending() {
+            self.tasks.borrow_mut()[id] = Some(Task { future, waker });
+        } else {
+            self.free.borrow_mut().push(id);
+            self.live.set(self.live.get() - 1);
+        }
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        // Drop the tasks first, so that servers stop their receiving threads
+        // and nothing is left waiting on the pool.
+        let tasks = std::mem::take(&mut *self.tasks.borrow_mut());
+        drop(tasks);
+        self.pool.close();
+    }
+}
+
+/// A single-threaded executor. Tasks are polled on the thread that calls
+/// `run()`, so they don't need to be `Send` and may share state through `Rc`.
+///
+/// Dropping the executor drops any tasks that haven't finished, and stops the
+/// threads it started for blocking calls and for receiving messages.
+pub struct Executor {
+    local: Rc<Local>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor {
+            local: Rc::new(Local {
+                shared: Arc::new(Shared {
+                    ready: Mutex::new(VecDeque::new()),
+                    parker: Parker::new(),
+                }),
+                tasks: RefCell::new(Vec::new()),
+                free: RefCell::new(Vec::new()),
+                live: Cell::new(0),
+                timers: RefCell::new(BTreeMap::new()),
+                next_timer: Cell::new(0),
+                pool: Pool::new(),
+            }),
+        }
+    }
+
+    /// Add a task to the executor. It starts running once `run()` is called.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        self.local.spawn(future)
+    }
+
+    /// Run tasks until every task has finished. Servers generally never finish,
+    /// in which case this never returns.
+    pub fn run(&self) {
+        let previous = CURRENT.with(|current| current.replace(Some(self.local.clone())));
+        let local = &*self.local;
+        while local.live.get() > 0 {
+            let next_deadline = local.fire_timers();
+
+            let ready: Vec<usize> = local.shared.ready.lock().unwrap().drain(..).collect();
+            if !ready.is_empty() {
+                for id in ready {
+                    local.poll_task(id);
+                }
+                continue;
+            }
+
+            // Nothing is ready, so sleep until a waker fires or a timer expires.
+            let timeout =
+                next_deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            local.shared.parker.park(timeout);
+        }
+        CURRENT.with(|current| current.replace(previous));
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Spawn a task on the executor that is running on this thread.
+///
+/// # Panics
+///
+/// Panics if called outside of an executor
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+{
+    with_current(|local| local.spawn(future))
+}
+
+/// Run `future` to completion on a new executor, along with any tasks it
+/// spawns, and return its output.
+pub fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + 'static,
+{
+    let executor = Executor::new();
+    let handle = executor.spawn(future);
+    executor.run();
+    let result = handle.state.borrow_mut().result.take();
+    result.expect("xous-async: task did not finish")
+}
+
+struct JoinState<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the output of a spawned task. Dropping this does not cancel the
+/// task.
+pub struct JoinHandle<T> {
+    state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.borrow_mut();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn block_on_returns_output() {
+        assert_eq!(block_on(async { 6 * 7 }), 42);
+    }
+
+    #[test]
+    fn spawned_tasks_share_state() {
+        let total = block_on(async {
+            let count = Rc::new(Cell::new(0));
+            let handles: Vec<_> = (0..5)
+                .map(|i| {
+                    let count = count.clone();
+                    spawn(async move {
+                        count.set(count.get() + i);
+                        i * 2
+                    })
+                })
+                .collect();
+            let mut doubled = 0;
+            for handle in handles {
+                doubled += handle.await;
+            }
+            assert_eq!(doubled, 20);
+            count.get()
+        });
+        assert_eq!(total, 10);
+    }
+
+    #[test]
+    fn sleeps_finish_in_deadline_order() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let executor = Executor::new();
+        for &(name, ms) in &[("slow", 60u64), ("fast", 10), ("medium", 30)] {
+            let order = order.clone();
+            executor.spawn(async move {
+                crate::sleep(Duration::from_millis(ms)).await;
+                order.borrow_mut().push(name);
+            });
+        }
+        let start = Instant::now();
+        executor.run();
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert_eq!(*order.borrow(), vec!["fast", "medium", "slow"]);
+    }
+
+    #[test]
+    fn wakers_work_from_other_threads() {
+        let result = block_on(async {
+            let (first, second) = (
+                crate::unblock(|| {
+                    std::thread::sleep(Duration::from_millis(20));
+                    1
+                }),
+                crate::unblock(|| 2),
+            );
+            first.await + second.await
+        });
+        assert_eq!(result, 3);
+    }
+}
@@ -0,0 +1,41 @@
+//! Asynchronous versions of the calls that block a client until the server
+//! replies.
+
+use xous::{Error, Message, CID};
+use xous_ipc::Buffer;
+
+use crate::unblock;
+
+/// Send `message` and wait for the reply without blocking the executor.
+pub async fn send_message(connection: CID, message: Message) -> Result<xous::Result, Error> {
+    unblock(move || xous::send_message(connection, message)).await
+}
+
+/// Lend `buf` to a server, and hand it back along with the result once the
+/// server has returned it.
+pub async fn lend(
+    buf: Buffer<'static>,
+    connection: CID,
+    id: u32,
+) -> (Buffer<'static>, Result<xous::Result, Error>) {
+    unblock(move || {
+        let result = buf.lend(connection, id);
+        (buf, result)
+    })
+    .await
+}
+
+/// Mutably lend `buf` to a server, and hand it back along with the result once
+/// the server has returned it. Any changes the server made are visible in the
+/// returned buffer.
+pub async fn lend_mut(
+    mut buf: Buffer<'static>,
+    connection: CID,
+    id: u32,
+) -> (Buffer<'static>, Result<xous::Result, Error>) {
+    unblock(move || {
+        let result = buf.lend_mut(connection, id);
+        (buf, result)
+    })
+    .await
+}
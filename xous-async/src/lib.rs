@@ -0,0 +1,42 @@
+//! A small async executor for Xous servers.
+//!
+//! Instead of a `loop { receive_message ... }` per server plus helper threads
+//! for anything that has to wait, a service can run one executor and give each
+//! server and each long-running request its own task:
+//!
+//! ```no_run
+//! use xous_async::{sleep_ms, spawn, Executor, Server};
+//!
+//! let executor = Executor::new();
+//! let mut server = Server::new(xous::create_server().unwrap()).unwrap();
+//! executor.spawn(async move {
+//!     while let Some(envelope) = server.receive().await {
+//!         // Answer later without holding up other messages
+//!         spawn(async move {
+//!             sleep_ms(100).await;
+//!             drop(envelope);
+//!         });
+//!     }
+//! });
+//! executor.run();
+//! ```
+//!
+//! Tasks run on the thread that calls `Executor::run()`. Each `Server` has one
+//! thread that receives its messages, and blocking calls share a small, fixed
+//! set of threads, so the number of threads doesn't grow with the number of
+//! requests. Timers are kept by the executor itself. On Xous the executor sleeps in
+//! `ReceiveMessageTimeout`; when hosted it sleeps on a condition variable, so
+//! the executor can be tested on the host without a kernel.
+
+mod backend;
+mod blocking;
+mod executor;
+mod ipc;
+mod server;
+mod time;
+
+pub use blocking::{unblock, Unblock};
+pub use executor::{block_on, spawn, Executor, JoinHandle};
+pub use ipc::{lend, lend_mut, send_message};
+pub use server::{Receive, Server};
+pub use time::{sleep, sleep_ms, sleep_until, Sleep};
@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use xous::{Error, Message, MessageEnvelope, CID, SID};
+
+/// How many messages may be waiting for the executor before the receiving
+/// thread stops taking new ones. Past that point, senders back up in the
+/// kernel's queue just as they would with a blocking server.
+const INBOX_DEPTH: usize = 16;
+
+/// The scalar a `Server` sends itself when it is dropped, so that its receiving
+/// thread gets out of `ReceiveMessage` and exits
+const STOP_ID: usize = usize::MAX;
+
+struct Inbox {
+    messages: VecDeque<MessageEnvelope>,
+    waker: Option<Waker>,
+
+    /// No more messages will be received, either because the receiver ran
+    /// out or because the `Server` was dropped
+    closed: bool,
+}
+
+struct Shared {
+    inbox: Mutex<Inbox>,
+
+    /// Signalled when the executor takes a message out of a full inbox, or
+    /// when the `Server` is dropped
+    space: Condvar,
+}
+
+/// A stream of messages for one server. Each `Server` has a thread that waits
+/// in `ReceiveMessage` and hands messages over to the executor, so a single
+/// executor can serve any number of servers at once. The thread exits when the
+/// `Server` is dropped.
+///
+/// Replying works as it does in a blocking server: use `return_scalar()` on the
+/// sender, or let the envelope drop to return lent memory.
+pub struct Server {
+    shared: Arc<Shared>,
+
+    /// A connection to our own server, used to wake the receiving thread
+    stop: Option<CID>,
+}
+
+impl Server {
+    /// Receive messages sent to `sid`. The stream ends if the server is
+    /// destroyed.
+    ///
+    /// # Errors
+    ///
+    /// * **ThreadNotAvailable**: The receiving thread could not be started
+    pub fn new(sid: SID) -> Result<Self, Error> {
+        let mut server = Self::with_receiver(move || xous::receive_message(sid).ok())?;
+        server.stop = xous::connect(sid).ok();
+        Ok(server)
+    }
+
+    /// Receive messages from `receiver`, which is called on a helper thread
+    /// and may block. The stream ends when it returns `None`. Once the `Server`
+    /// is dropped, the thread exits the next time `receiver` returns.
+    ///
+    /// # Errors
+    ///
+    /// * **ThreadNotAvailable**: The receiving thread could not be started
+    pub fn with_receiver<F>(mut receiver: F) -> Result<Self, Error>
+    where
+        F: FnMut() -> Option<MessageEnvelope> + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            inbox: Mutex::new(Inbox {
+                messages: VecDeque::new(),
+                waker: None,
+                closed: false,
+            }),
+            space: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        std::thread::Builder::new()
+            .spawn(move || loop {
+                let message = receiver();
+                let mut inbox = thread_shared.inbox.lock().unwrap();
+                if inbox.closed {
+                    return;
+                }
+                match message {
+                    Some(message) if is_stop(&message) => continue,
+                    Some(message) => inbox.messages.push_back(message),
+                    None => inbox.closed = true,
+                }
+                if let Some(waker) = inbox.waker.take() {
+                    waker.wake();
+                }
+                while inbox.messages.len() >= INBOX_DEPTH && !inbox.closed {
+                    inbox = thread_shared.space.wait(inbox).unwrap();
+                }
+                if inbox.closed {
+                    return;
+                }
+            })
+            .map_err(|_| Error::ThreadNotAvailable)?;
+        Ok(Server { shared, stop: None })
+    }
+
+    /// Wait for the next message, or `None` once no more messages will arrive.
+    pub fn receive(&mut self) -> Receive<'_> {
+        Receive { server: self }
+    }
+}
+
+/// A stop message that was left behind by an earlier `Server` for the same
+/// SID, because a real message got to its thread first
+fn is_stop(envelope: &MessageEnvelope) -> bool {
+    match &envelope.body {
+        Message::Scalar(scalar) if scalar.id == STOP_ID => {
+            envelope.sender.pid().map(|pid| pid.get() as u32) == Some(xous::process::id())
+        }
+        _ => false,
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shared.inbox.lock().unwrap().closed = true;
+        self.shared.space.notify_one();
+        if let Some(cid) = self.stop {
+            xous::try_send_message(cid, Message::new_scalar(STOP_ID, 0, 0, 0, 0)).ok();
+        }
+    }
+}
+
+/// Resolves to the next message for a `Server`
+pub struct Receive<'a> {
+    server: &'a mut Server,
+}
+
+impl<'a> Future for Receive<'a> {
+    type Output = Option<MessageEnvelope>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let shared = &self.server.shared;
+        let mut inbox = shared.inbox.lock().unwrap();
+        if let Some(message) = inbox.messages.pop_front() {
+            if inbox.messages.len() + 1 == INBOX_DEPTH {
+                shared.space.notify_one();
+            }
+            return Poll::Ready(Some(message));
+        }
+        if inbox.closed {
+            return Poll::Ready(None);
+        }
+        inbox.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+    use xous::MessageSender;
+
+    fn scalar(id: usize, arg1: usize) -> MessageEnvelope {
+        MessageEnvelope {
+            sender: MessageSender::from_usize(0),
+            body: Message::new_scalar(id, arg1, 0, 0, 0),
+        }
+    }
+
+    #[test]
+    fn one_thread_serves_two_servers() {
+        let (first_tx, first_rx) = channel();
+        let (second_tx, second_rx) = channel();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let executor = crate::Executor::new();
+        for (name, rx) in [("first", first_rx), ("second", second_rx)] {
+            let mut server = Server::with_receiver(move || rx.recv().ok()).unwrap();
+            let seen = seen.clone();
+            executor.spawn(async move {
+                while let Some(envelope) = server.receive().await {
+                    if let Message::Scalar(scalar) = &envelope.body {
+                        seen.borrow_mut().push((name, scalar.arg1));
+                    }
+                }
+            });
+        }
+
+        std::thread::spawn(move || {
+            for i in 0..40 {
+                let tx = if i % 2 == 0 { &first_tx } else { &second_tx };
+                tx.send(scalar(1, i)).unwrap();
+            }
+        });
+        executor.run();
+
+        let seen = seen.borrow();
+        assert_eq!(seen.len(), 40);
+        for name in &["first", "second"] {
+            let args: Vec<usize> = seen
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(_, arg)| *arg)
+                .collect();
+            let expected: Vec<usize> = (0..40)
+                .filter(|i| (i % 2 == 0) == (*name == "first"))
+                .collect();
+            assert_eq!(args, expected);
+        }
+    }
+
+    #[test]
+    fn dropping_the_executor_stops_the_thread() {
+        let (tx, rx) = channel();
+        let executor = crate::Executor::new();
+        let mut server = Server::with_receiver(move || rx.recv().ok()).unwrap();
+        executor.spawn(async move {
+            server.receive().await;
+            unreachable!("nothing was sent");
+        });
+        drop(executor);
+
+        // The thread exits after the next message, which drops the receiver
+        tx.send(scalar(1, 0)).unwrap();
+        let start = Instant::now();
+        while tx.send(scalar(1, 0)).is_ok() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
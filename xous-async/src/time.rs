@@ -0,0 +1,86 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::executor::{with_current, Local};
+
+/// Resolves once its deadline has passed. Timers are kept by the executor, so
+/// any number of tasks can sleep at once without tying up the ticktimer.
+pub struct Sleep {
+    deadline: Instant,
+
+    /// The executor this is registered with, and its key there, once it has
+    /// been polled
+    timer: Option<(Weak<Local>, u64)>,
+}
+
+/// Wait for `duration` to pass.
+///
+/// # Panics
+///
+/// The returned future panics if it is polled outside of an executor
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Wait for `ms` milliseconds, just like `Ticktimer::sleep_ms()` but without
+/// blocking the thread.
+pub fn sleep_ms(ms: usize) -> Sleep {
+    sleep(Duration::from_millis(ms as u64))
+}
+
+/// Wait until `deadline`.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        timer: None,
+    }
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    fn cancel(&mut self) {
+        if let Some((local, key)) = self.timer.take() {
+            if let Some(local) = local.upgrade() {
+                local.remove_timer(self.deadline, key);
+            }
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+        let deadline = self.deadline;
+        match &self.timer {
+            Some((local, key)) => {
+                if let Some(local) = local.upgrade() {
+                    local.update_timer(deadline, *key, cx.waker());
+                }
+            }
+            None => {
+                self.timer = Some(with_current(|local| {
+                    let key = local.add_timer(deadline, cx.waker().clone());
+                    (Rc::downgrade(local), key)
+                }));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
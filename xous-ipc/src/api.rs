@@ -0,0 +1,751 @@
+//! Versioned IPC API definitions. The `api!` macro declares every opcode of a
+//! server along with its message kind and payload type, and generates:
+//!
+//! * `Opcode`: an enum of the opcodes
+//! * `Client`: a stub with one method per opcode, which refuses to connect to a
+//!   server that speaks an incompatible version of the API
+//! * `Handler`: a trait with one method per opcode, for the server to implement
+//! * `dispatch()`: decodes a message and calls the matching `Handler` method
+//!
+//! Payload types are declared with `payload!`, which records their fields so
+//! that they become part of the API's schema.
+//!
+//! ```ignore
+//! xous_ipc::payload! {
+//!     #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+//!     pub struct Counters {
+//!         pub values: [u32; 4],
+//!     }
+//! }
+//!
+//! xous_ipc::api! {
+//!     /// Keeps a set of counters
+//!     pub mod counter_api {
+//!         version: (1, 0),
+//!
+//!         /// Add `amount` to the counter at `index`
+//!         scalar Add = 0 => fn add(index, amount);
+//!         /// Return the value of the counter at `index`
+//!         blocking_scalar Get = 1 => fn get(index);
+//!         /// Replace every counter
+//!         lend Load = 2 => fn load(Counters);
+//!         /// Add each counter's value to `Counters`, and zero the counters
+//!         lend_mut Drain = 3 => fn drain(Counters);
+//!         /// Hand over a log entry
+//!         send Log = 4 => fn log(LogEntry);
+//!     }
+//! }
+//! ```
+//!
+//! Scalar opcodes take up to four `usize` arguments, and blocking scalars return
+//! a `usize`. Memory opcodes carry one rkyv-serializable payload: `lend` passes
+//! it to the server, `lend_mut` passes it to the server and returns the server's
+//! replacement, and `send` moves it to the server.
+//!
+//! Bump the minor version when adding opcodes, and the major version for any
+//! other change, including changes to the fields of a payload type. In addition
+//! to the version, the client and server compare a fingerprint of the opcode
+//! list and of the fields of each payload type, which catches a change that was
+//! made without bumping the version. Fields are compared by name and type name,
+//! so a change inside a type that a field refers to is only caught if it changes
+//! the size of the payload.
+//!
+//! A payload that doesn't fit the memory it came in is not handed to the
+//! `Handler`. `dispatch()` returns it to the server as `BadPayload`, and a client
+//! that lent it gets an error back.
+
+use xous::{Error, MemoryMessage, Message, MessageEnvelope, CID};
+
+/// The opcode used to ask a server which version of its API it implements.
+/// This is answered by the generated `dispatch()`, so APIs must not use it.
+pub const API_VERSION_ID: usize = 0xffff_ff00;
+
+/// How long `Client::new()` waits for the server to answer the version query.
+/// Servers that weren't built with `api!` never answer it.
+pub const VERSION_QUERY_TIMEOUT_MS: usize = 1000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ApiVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ApiError {
+    /// The version query itself failed
+    Xous(Error),
+
+    /// The server implements a version of the API that the client can't use
+    Version {
+        client: ApiVersion,
+        server: ApiVersion,
+    },
+
+    /// The client and server claim the same version, but declare different
+    /// opcodes or payloads
+    Schema,
+
+    /// The server didn't answer the version query, so it wasn't built with
+    /// `api!`
+    Unversioned,
+}
+
+/// A message that `dispatch()` did not hand to the `Handler`
+#[derive(Debug)]
+pub enum DispatchError {
+    /// The message is not part of the API, or is a known opcode sent as the
+    /// wrong kind of message
+    Unhandled(MessageEnvelope),
+
+    /// The payload of a memory message doesn't fit the memory it came in. If
+    /// it was lent, the client gets an error once the envelope is dropped.
+    BadPayload(MessageEnvelope),
+}
+
+/// A type that can be the payload of an `api!` memory opcode. Implement it with
+/// `payload!`, which fingerprints the type's fields.
+pub trait Payload {
+    /// Fingerprint of the payload's fields and size
+    const LAYOUT: u32;
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        ApiError::Xous(e)
+    }
+}
+
+impl ApiVersion {
+    pub const fn new(major: u16, minor: u16) -> Self {
+        ApiVersion { major, minor }
+    }
+
+    pub fn to_usize(&self) -> usize {
+        (self.major as usize) << 16 | self.minor as usize
+    }
+
+    pub fn from_usize(val: usize) -> Self {
+        ApiVersion {
+            major: (val >> 16) as u16,
+            minor: val as u16,
+        }
+    }
+
+    /// Whether a client built against this version can talk to a server that
+    /// implements `server`. Servers only ever gain opcodes within a major
+    /// version, so the server may be newer but not older.
+    pub fn is_compatible_with(&self, server: ApiVersion) -> bool {
+        self.major == server.major && self.minor <= server.minor
+    }
+
+    /// Ask the server on `connection` which version it implements, and check it
+    /// against this one. A server that doesn't answer within
+    /// `VERSION_QUERY_TIMEOUT_MS` is taken to have been built without `api!`.
+    pub fn check(&self, connection: CID, schema: u32) -> Result<(), ApiError> {
+        let response = match xous::send_message_timeout(
+            connection,
+            Message::new_blocking_scalar(API_VERSION_ID, self.to_usize(), schema as usize, 0, 0),
+            VERSION_QUERY_TIMEOUT_MS,
+        ) {
+            Err(Error::Timeout) => return Err(ApiError::Unversioned),
+            response => response?,
+        };
+        let (server, server_schema) = match response {
+            xous::Result::Scalar2(version, schema) => {
+                (ApiVersion::from_usize(version), schema as u32)
+            }
+            _ => return Err(ApiError::Unversioned),
+        };
+        if !self.is_compatible_with(server) {
+            return Err(ApiError::Version {
+                client: *self,
+                server,
+            });
+        }
+        if server == *self && server_schema != schema {
+            return Err(ApiError::Schema);
+        }
+        Ok(())
+    }
+
+    /// Reply to a version query from `check()`
+    pub fn answer(&self, envelope: MessageEnvelope, schema: u32) {
+        if let Message::BlockingScalar(_) = envelope.body {
+            xous::return_scalar2(envelope.sender, self.to_usize(), schema as usize).ok();
+        }
+    }
+}
+
+/// FNV-1a hash of an API's opcode list or a payload's fields, as written in the
+/// `api!` or `payload!` invocation
+#[doc(hidden)]
+pub const fn schema_fingerprint(schema: &str) -> u32 {
+    let bytes = schema.as_bytes();
+    let mut hash: u32 = 0x811c_9dc5;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+    hash
+}
+
+/// Fold `value` into the fingerprint `hash`
+#[doc(hidden)]
+pub const fn schema_combine(hash: u32, value: u32) -> u32 {
+    let bytes = value.to_le_bytes();
+    let mut hash = hash;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+    hash
+}
+
+/// Whether the archived `T` that `mem` claims to hold lies inside its memory
+#[doc(hidden)]
+pub fn payload_fits<T: rkyv::Archive>(mem: &MemoryMessage) -> bool {
+    let offset = mem.offset.map(|o| o.get()).unwrap_or(0);
+    offset
+        .checked_add(core::mem::size_of::<T::Archived>())
+        .map(|end| end <= mem.buf.len())
+        .unwrap_or(false)
+}
+
+/// Mark a lent buffer as refused, which the generated client reports as an
+/// error when it gets the buffer back
+#[doc(hidden)]
+pub fn refuse_payload(mem: &mut MemoryMessage) {
+    mem.offset = None;
+    mem.valid = None;
+}
+
+/// Declare a payload type for an `api!`, and implement `Payload` for it. Structs
+/// with named fields and enums with unit or tuple variants are supported.
+/// Attributes and doc comments are passed through, and are not part of the
+/// fingerprint.
+#[macro_export]
+macro_rules! payload {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $( $(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $( $(#[$field_meta])* $field_vis $field: $ty, )*
+        }
+
+        impl $crate::Payload for $name {
+            const LAYOUT: u32 = $crate::schema_combine(
+                $crate::schema_fingerprint(stringify!(struct $name { $( $field: $ty, )* })),
+                core::mem::size_of::<$name>() as u32,
+            );
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $( $(#[$variant_meta:meta])* $variant:ident $( ( $($ty:ty),* $(,)? ) )? ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $( $(#[$variant_meta])* $variant $( ( $($ty),* ) )?, )*
+        }
+
+        impl $crate::Payload for $name {
+            const LAYOUT: u32 = $crate::schema_combine(
+                $crate::schema_fingerprint(stringify!(
+                    enum $name { $( $variant $( ( $($ty),* ) )?, )* }
+                )),
+                core::mem::size_of::<$name>() as u32,
+            );
+        }
+    };
+}
+
+/// Declare a versioned IPC API. See the `api` module documentation.
+#[macro_export]
+macro_rules! api {
+    (
+        $(#[$meta:meta])*
+        $vis:vis mod $name:ident {
+            version: ($major:literal, $minor:literal),
+            $(
+                $(#[$op_meta:meta])*
+                $kind:ident $op:ident = $id:literal => fn $method:ident ( $($arg:tt)* );
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[allow(dead_code)]
+        $vis mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub const VERSION: $crate::ApiVersion = $crate::ApiVersion::new($major, $minor);
+
+            /// Fingerprint of the opcode list and payloads, compared when a
+            /// client connects
+            pub const SCHEMA: u32 = {
+                let hash = $crate::schema_fingerprint(stringify!(
+                    $( $kind $op = $id => fn $method ( $($arg)* ); )*
+                ));
+                $(
+                    let hash = $crate::schema_combine(hash, $crate::api!(@layout $kind ($($arg)*)));
+                )*
+                hash
+            };
+
+            #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+            pub enum Opcode {
+                $( $(#[$op_meta])* $op = $id, )*
+            }
+
+            impl Opcode {
+                pub fn from_usize(id: usize) -> Option<Self> {
+                    $( if id == $id { return Some(Opcode::$op); } )*
+                    None
+                }
+            }
+
+            #[derive(Debug)]
+            pub struct Client {
+                cid: xous::CID,
+            }
+
+            impl Client {
+                /// Wrap a connection, after checking that the server on the other
+                /// end implements a compatible version of this API
+                pub fn new(cid: xous::CID) -> core::result::Result<Self, $crate::ApiError> {
+                    VERSION.check(cid, SCHEMA)?;
+                    Ok(Client { cid })
+                }
+
+                pub fn cid(&self) -> xous::CID {
+                    self.cid
+                }
+
+                $( $crate::api!(@client [$(#[$op_meta])*] $kind $op $method ($($arg)*)); )*
+            }
+
+            pub trait Handler {
+                $( $crate::api!(@handler [$(#[$op_meta])*] $kind $method ($($arg)*)); )*
+            }
+
+            /// Handle `envelope` if it belongs to this API, including version
+            /// queries from `Client::new()`. Anything else, including a known
+            /// opcode sent as the wrong kind of message or with a payload that
+            /// doesn't fit, is handed back so the server can deal with it.
+            #[allow(unused_mut)]
+            pub fn dispatch<H: Handler>(
+                handler: &mut H,
+                mut envelope: xous::MessageEnvelope,
+            ) -> core::result::Result<(), $crate::DispatchError> {
+                let id = envelope.body.id();
+                if id == $crate::API_VERSION_ID {
+                    VERSION.answer(envelope, SCHEMA);
+                    return Ok(());
+                }
+                $(
+                    if id == Opcode::$op as usize {
+                        $crate::api!(@dispatch $kind handler envelope $method ($($arg)*));
+                        return Err($crate::DispatchError::Unhandled(envelope));
+                    }
+                )*
+                Err($crate::DispatchError::Unhandled(envelope))
+            }
+        }
+    };
+
+    (@layout scalar ($($arg:ident),*)) => { 0 };
+    (@layout blocking_scalar ($($arg:ident),*)) => { 0 };
+    (@layout lend ($ty:ty)) => { <$ty as $crate::Payload>::LAYOUT };
+    (@layout lend_mut ($ty:ty)) => { <$ty as $crate::Payload>::LAYOUT };
+    (@layout send ($ty:ty)) => { <$ty as $crate::Payload>::LAYOUT };
+
+    (@pad []) => { [0, 0, 0, 0] };
+    (@pad [$a:ident]) => { [$a, 0, 0, 0] };
+    (@pad [$a:ident, $b:ident]) => { [$a, $b, 0, 0] };
+    (@pad [$a:ident, $b:ident, $c:ident]) => { [$a, $b, $c, 0] };
+    (@pad [$a:ident, $b:ident, $c:ident, $d:ident]) => { [$a, $b, $c, $d] };
+
+    (@client [$(#[$meta:meta])*] scalar $op:ident $method:ident ($($arg:ident),*)) => {
+        $(#[$meta])*
+        pub fn $method(&self, $($arg: usize),*) -> core::result::Result<(), xous::Error> {
+            let args: [usize; 4] = $crate::api!(@pad [$($arg),*]);
+            xous::send_message(
+                self.cid,
+                xous::Message::new_scalar(Opcode::$op as usize, args[0], args[1], args[2], args[3]),
+            )
+            .map(|_| ())
+        }
+    };
+    (@client [$(#[$meta:meta])*] blocking_scalar $op:ident $method:ident ($($arg:ident),*)) => {
+        $(#[$meta])*
+        pub fn $method(&self, $($arg: usize),*) -> core::result::Result<usize, xous::Error> {
+            let args: [usize; 4] = $crate::api!(@pad [$($arg),*]);
+            match xous::send_message(
+                self.cid,
+                xous::Message::new_blocking_scalar(
+                    Opcode::$op as usize,
+                    args[0],
+                    args[1],
+                    args[2],
+                    args[3],
+                ),
+            )? {
+                xous::Result::Scalar1(val) | xous::Result::Scalar2(val, _) => Ok(val),
+                _ => Err(xous::Error::InternalError),
+            }
+        }
+    };
+    (@client [$(#[$meta:meta])*] lend $op:ident $method:ident ($ty:ty)) => {
+        $(#[$meta])*
+        pub fn $method(&self, value: $ty) -> core::result::Result<(), xous::Error> {
+            let buf = $crate::Buffer::into_buf(value).or(Err(xous::Error::InternalError))?;
+            match buf.lend(self.cid, Opcode::$op as u32)? {
+                // The server couldn't decode the payload
+                xous::Result::MemoryReturned(_, None) => Err(xous::Error::InternalError),
+                _ => Ok(()),
+            }
+        }
+    };
+    (@client [$(#[$meta:meta])*] lend_mut $op:ident $method:ident ($ty:ty)) => {
+        $(#[$meta])*
+        pub fn $method(&self, value: $ty) -> core::result::Result<$ty, xous::Error> {
+            let mut buf = $crate::Buffer::into_buf(value).or(Err(xous::Error::InternalError))?;
+            match buf.lend_mut(self.cid, Opcode::$op as u32)? {
+                // The server couldn't decode the payload
+                xous::Result::MemoryReturned(_, None) => return Err(xous::Error::InternalError),
+                _ => (),
+            }
+            buf.to_original::<$ty, _>().or(Err(xous::Error::InternalError))
+        }
+    };
+    (@client [$(#[$meta:meta])*] send $op:ident $method:ident ($ty:ty)) => {
+        $(#[$meta])*
+        pub fn $method(&self, value: $ty) -> core::result::Result<(), xous::Error> {
+            let buf = $crate::Buffer::into_buf(value).or(Err(xous::Error::InternalError))?;
+            buf.send(self.cid, Opcode::$op as u32).map(|_| ())
+        }
+    };
+
+    (@handler [$(#[$meta:meta])*] scalar $method:ident ($($arg:ident),*)) => {
+        $(#[$meta])*
+        fn $method(&mut self, sender: xous::MessageSender, $($arg: usize),*);
+    };
+    (@handler [$(#[$meta:meta])*] blocking_scalar $method:ident ($($arg:ident),*)) => {
+        $(#[$meta])*
+        fn $method(&mut self, sender: xous::MessageSender, $($arg: usize),*) -> usize;
+    };
+    (@handler [$(#[$meta:meta])*] lend $method:ident ($ty:ty)) => {
+        $(#[$meta])*
+        fn $method(&mut self, sender: xous::MessageSender, value: $ty);
+    };
+    (@handler [$(#[$meta:meta])*] lend_mut $method:ident ($ty:ty)) => {
+        $(#[$meta])*
+        fn $method(&mut self, sender: xous::MessageSender, value: $ty) -> $ty;
+    };
+    (@handler [$(#[$meta:meta])*] send $method:ident ($ty:ty)) => {
+        $(#[$meta])*
+        fn $method(&mut self, sender: xous::MessageSender, value: $ty);
+    };
+
+    (@dispatch scalar $handler:ident $envelope:ident $method:ident ($($arg:ident),*)) => {
+        if let xous::Message::Scalar(s) = &$envelope.body {
+            let [$($arg,)* ..] = [s.arg1, s.arg2, s.arg3, s.arg4];
+            $handler.$method($envelope.sender, $($arg),*);
+            return Ok(());
+        }
+    };
+    (@dispatch blocking_scalar $handler:ident $envelope:ident $method:ident ($($arg:ident),*)) => {
+        if let xous::Message::BlockingScalar(s) = &$envelope.body {
+            let [$($arg,)* ..] = [s.arg1, s.arg2, s.arg3, s.arg4];
+            let result = $handler.$method($envelope.sender, $($arg),*);
+            xous::return_scalar($envelope.sender, result).ok();
+            return Ok(());
+        }
+    };
+    (@dispatch lend $handler:ident $envelope:ident $method:ident ($ty:ty)) => {
+        if let xous::Message::Borrow(mem) = &mut $envelope.body {
+            if !$crate::payload_fits::<$ty>(mem) {
+                $crate::refuse_payload(mem);
+                return Err($crate::DispatchError::BadPayload($envelope));
+            }
+            let value = unsafe { $crate::Buffer::from_memory_message(mem) }.to_original::<$ty, _>();
+            match value {
+                Ok(value) => $handler.$method($envelope.sender, value),
+                Err(_) => {
+                    $crate::refuse_payload(mem);
+                    return Err($crate::DispatchError::BadPayload($envelope));
+                }
+            }
+            return Ok(());
+        }
+    };
+    (@dispatch lend_mut $handler:ident $envelope:ident $method:ident ($ty:ty)) => {
+        let sender = $envelope.sender;
+        if let xous::Message::MutableBorrow(mem) = &mut $envelope.body {
+            if !$crate::payload_fits::<$ty>(mem) {
+                $crate::refuse_payload(mem);
+                return Err($crate::DispatchError::BadPayload($envelope));
+            }
+            let mut buffer = unsafe { $crate::Buffer::from_memory_message_mut(mem) };
+            let value = buffer.to_original::<$ty, _>();
+            match value {
+                Ok(value) => {
+                    let reply = $handler.$method(sender, value);
+                    buffer.replace(reply).ok();
+                }
+                Err(_) => {
+                    drop(buffer);
+                    $crate::refuse_payload(mem);
+                    return Err($crate::DispatchError::BadPayload($envelope));
+                }
+            }
+            return Ok(());
+        }
+    };
+    (@dispatch send $handler:ident $envelope:ident $method:ident ($ty:ty)) => {
+        if let xous::Message::Move(mem) = &$envelope.body {
+            if !$crate::payload_fits::<$ty>(mem) {
+                return Err($crate::DispatchError::BadPayload($envelope));
+            }
+            let value = unsafe { $crate::Buffer::from_memory_message(mem) }.to_original::<$ty, _>();
+            match value {
+                Ok(value) => $handler.$method($envelope.sender, value),
+                Err(_) => return Err($crate::DispatchError::BadPayload($envelope)),
+            }
+            return Ok(());
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xous::{MessageSender, ScalarMessage};
+
+    #[derive(Default)]
+    struct Counters {
+        values: [usize; 4],
+    }
+
+    mod v1 {
+        crate::api! {
+            pub mod counter_api {
+                version: (1, 0),
+
+                scalar Add = 0 => fn add(index, amount);
+                blocking_scalar Get = 1 => fn get(index);
+            }
+        }
+    }
+
+    mod v1_renumbered {
+        crate::api! {
+            pub mod counter_api {
+                version: (1, 0),
+
+                scalar Add = 0 => fn add(index, amount);
+                blocking_scalar Get = 2 => fn get(index);
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    mod pair {
+        crate::payload! {
+            pub struct Pair {
+                pub first: u32,
+                pub second: u32,
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    mod pair_documented {
+        crate::payload! {
+            /// The same pair, with comments
+            pub struct Pair {
+                /// Comes first
+                pub first: u32,
+                /// Comes second
+                pub second: u32,
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    mod pair_swapped {
+        crate::payload! {
+            pub struct Pair {
+                pub second: u32,
+                pub first: u32,
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    mod sensor {
+        crate::payload! {
+            #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+            pub struct Reading {
+                pub value: u32,
+            }
+        }
+
+        crate::api! {
+            pub mod sensor_api {
+                version: (1, 0),
+
+                lend_mut Swap = 0 => fn swap(Reading);
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    mod sensor_wider {
+        crate::payload! {
+            #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+            pub struct Reading {
+                pub value: u32,
+                pub scale: u32,
+            }
+        }
+
+        crate::api! {
+            pub mod sensor_api {
+                version: (1, 0),
+
+                lend_mut Swap = 0 => fn swap(Reading);
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Sensor {
+        swaps: usize,
+    }
+
+    impl sensor::sensor_api::Handler for Sensor {
+        fn swap(&mut self, _sender: MessageSender, value: sensor::Reading) -> sensor::Reading {
+            self.swaps += 1;
+            value
+        }
+    }
+
+    impl v1::counter_api::Handler for Counters {
+        fn add(&mut self, _sender: MessageSender, index: usize, amount: usize) {
+            self.values[index] += amount;
+        }
+
+        fn get(&mut self, _sender: MessageSender, index: usize) -> usize {
+            self.values[index]
+        }
+    }
+
+    fn scalar(id: usize, arg1: usize, arg2: usize) -> MessageEnvelope {
+        MessageEnvelope {
+            sender: MessageSender::from_usize(0),
+            body: Message::Scalar(ScalarMessage::from_usize(id, arg1, arg2, 0, 0)),
+        }
+    }
+
+    #[test]
+    fn version_compatibility() {
+        let client = ApiVersion::new(1, 2);
+        assert!(client.is_compatible_with(ApiVersion::new(1, 2)));
+        assert!(client.is_compatible_with(ApiVersion::new(1, 5)));
+        assert!(!client.is_compatible_with(ApiVersion::new(1, 1)));
+        assert!(!client.is_compatible_with(ApiVersion::new(2, 2)));
+        assert_eq!(ApiVersion::from_usize(client.to_usize()), client);
+    }
+
+    #[test]
+    fn schema_covers_opcode_numbers() {
+        assert_eq!(
+            v1::counter_api::VERSION,
+            v1_renumbered::counter_api::VERSION
+        );
+        assert_ne!(v1::counter_api::SCHEMA, v1_renumbered::counter_api::SCHEMA);
+        assert_eq!(
+            v1::counter_api::Opcode::from_usize(1),
+            Some(v1::counter_api::Opcode::Get)
+        );
+        assert_eq!(v1::counter_api::Opcode::from_usize(2), None);
+    }
+
+    #[test]
+    fn dispatch_decodes_scalars() {
+        let mut counters = Counters::default();
+        assert!(v1::counter_api::dispatch(&mut counters, scalar(0, 2, 5)).is_ok());
+        assert!(v1::counter_api::dispatch(&mut counters, scalar(0, 2, 1)).is_ok());
+        assert_eq!(counters.values, [0, 0, 6, 0]);
+
+        // Unknown opcodes, and known ones sent as the wrong kind of message,
+        // are handed back
+        match v1::counter_api::dispatch(&mut counters, scalar(7, 0, 0)) {
+            Err(DispatchError::Unhandled(unknown)) => assert_eq!(unknown.id(), 7),
+            other => panic!("unexpected dispatch result: {:?}", other),
+        }
+        match v1::counter_api::dispatch(&mut counters, scalar(1, 2, 0)) {
+            Err(DispatchError::Unhandled(wrong_kind)) => assert_eq!(wrong_kind.id(), 1),
+            other => panic!("unexpected dispatch result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn schema_covers_payload_fields() {
+        use pair::Pair as Plain;
+        use pair_documented::Pair as Documented;
+        use pair_swapped::Pair as Swapped;
+        assert_eq!(<Plain as Payload>::LAYOUT, <Documented as Payload>::LAYOUT);
+        assert_ne!(<Plain as Payload>::LAYOUT, <Swapped as Payload>::LAYOUT);
+        assert_eq!(
+            sensor::sensor_api::VERSION,
+            sensor_wider::sensor_api::VERSION
+        );
+        assert_ne!(sensor::sensor_api::SCHEMA, sensor_wider::sensor_api::SCHEMA);
+    }
+
+    #[test]
+    fn dispatch_refuses_payloads_that_dont_fit() {
+        let mut sensor = Sensor::default();
+        let envelope = MessageEnvelope {
+            sender: MessageSender::from_usize(0),
+            body: Message::MutableBorrow(MemoryMessage {
+                id: 0,
+                buf: unsafe { xous::MemoryRange::new(0x1000, 0x1000).unwrap() },
+                offset: xous::MemoryAddress::new(0xfff),
+                valid: xous::MemorySize::new(0x1000),
+            }),
+        };
+        let result = sensor::sensor_api::dispatch(&mut sensor, envelope);
+        assert_eq!(sensor.swaps, 0);
+
+        // There's no kernel to give the memory back to, so don't drop the
+        // envelope
+        let message = match result {
+            Err(DispatchError::BadPayload(envelope)) => envelope.take_message(),
+            Err(DispatchError::Unhandled(envelope)) => {
+                envelope.take_message();
+                panic!("payload was not refused");
+            }
+            Ok(()) => panic!("payload was dispatched"),
+        };
+        match message {
+            Message::MutableBorrow(mem) => {
+                assert_eq!(mem.offset, None);
+                assert_eq!(mem.valid, None);
+            }
+            _ => panic!("message changed kind"),
+        }
+    }
+}
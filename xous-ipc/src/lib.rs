@@ -6,3 +6,6 @@ pub use buffer::*;
 
 mod string;
 pub use string::*;
+
+pub mod api;
+pub use api::*;
@@ -96,6 +96,10 @@ impl ProcessStartup {
     pub fn new(pid: crate::PID, connection: crate::CID) -> Self {
         ProcessStartup { pid, connection }
     }
+
+    pub fn pid(&self) -> crate::PID {
+        self.pid
+    }
 }
 
 impl From<&[usize; 7]> for ProcessStartup {
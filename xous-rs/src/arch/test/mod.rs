@@ -104,6 +104,9 @@ where
             set_xous_address(server_address);
             THREAD_ID.with(|tid| *tid.borrow_mut() = 1);
             PROCESS_ID.with(|p| *p.borrow_mut() = pid);
+            // Let the process start processes of its own, such as a supervisor
+            // restarting a service
+            PROCESS_KEY.with(|pk| *pk.borrow_mut() = Some(init.key));
             XOUS_SERVER_CONNECTION.with(|xsc| {
                 let mut xsc = xsc.borrow_mut();
                 match xous_connect_impl(server_address, &init.key) {
@@ -541,12 +544,14 @@ where
     let server_connection =
         XOUS_SERVER_CONNECTION.with(|xsc| xsc.borrow().as_ref().unwrap().clone());
     let process_id = PROCESS_ID.with(|pid| *pid.borrow());
+    let process_key = PROCESS_KEY.with(|pk| *pk.borrow());
     let call_for_thread = CALL_FOR_THREAD.with(|cft| cft.borrow().clone());
     Ok(std::thread::Builder::new()
         .spawn(move || {
             set_xous_address(server_address);
             THREAD_ID.with(|tid| *tid.borrow_mut() = thread_id);
             PROCESS_ID.with(|pid| *pid.borrow_mut() = process_id);
+            PROCESS_KEY.with(|pk| *pk.borrow_mut() = process_key);
             XOUS_SERVER_CONNECTION.with(|xsc| *xsc.borrow_mut() = Some(server_connection));
             CALL_FOR_THREAD.with(|cft| *cft.borrow_mut() = call_for_thread);
             f()
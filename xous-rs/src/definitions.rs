@@ -79,6 +79,21 @@ pub use messages::*;
 pub mod limits;
 pub use limits::*;
 
+pub mod priority;
+pub use priority::*;
+
+pub mod roles;
+pub use roles::*;
+
+pub mod trace;
+pub use trace::*;
+
+pub mod supervisor;
+pub use supervisor::*;
+
+pub mod wakeup;
+pub use wakeup::*;
+
 use crate::arch::ProcessStartup;
 
 /// Server ID
@@ -199,6 +214,8 @@ pub enum Error {
     DoubleFree = 25,
     DebugInProgress = 26,
     InvalidLimit = 27,
+    ResourceLimitExceeded = 28,
+    ServerDisconnected = 29,
 }
 
 impl Error {
@@ -232,6 +249,8 @@ impl Error {
             25 => DoubleFree,
             26 => DebugInProgress,
             27 => InvalidLimit,
+            28 => ResourceLimitExceeded,
+            29 => ServerDisconnected,
             _ => UnknownError,
         }
     }
@@ -265,6 +284,8 @@ impl Error {
             DoubleFree => 25,
             DebugInProgress => 26,
             InvalidLimit => 27,
+            ResourceLimitExceeded => 28,
+            ServerDisconnected => 29,
             UnknownError => usize::MAX,
         }
     }
@@ -460,6 +481,9 @@ pub enum Result {
     /// the caller.
     NewProcess(ProcessStartup),
 
+    /// 20: A scalar with five values
+    Scalar5(usize, usize, usize, usize, usize),
+
     UnknownResult(usize, usize, usize, usize, usize, usize, usize),
 }
 
@@ -523,6 +547,7 @@ impl Result {
                 0,
             ],
             Result::NewProcess(p) => Self::add_opcode(19, p.into()),
+            Result::Scalar5(a, b, c, d, e) => [20, *a, *b, *c, *d, *e, 0, 0],
             Result::UnknownResult(arg1, arg2, arg3, arg4, arg5, arg6, arg7) => {
                 [usize::MAX, *arg1, *arg2, *arg3, *arg4, *arg5, *arg6, *arg7]
             }
@@ -600,6 +625,7 @@ impl Result {
             17 => Result::None,
             18 => Result::MemoryReturned(MemorySize::new(src[1]), MemorySize::new(src[2])),
             19 => Result::NewProcess(src.into()),
+            20 => Result::Scalar5(src[1], src[2], src[3], src[4], src[5]),
             _ => Result::UnknownResult(src[0], src[1], src[2], src[3], src[4], src[5], src[6]),
         }
     }
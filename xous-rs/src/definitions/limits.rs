@@ -2,4 +2,51 @@
 pub enum Limits {
     HeapMaximum = 1,
     HeapSize = 2,
-}
\ No newline at end of file
+}
+/// A kernel resource that is accounted for per process
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Resource {
+    /// Pages of main RAM owned by the process
+    MemoryPages = 1,
+    /// Threads that exist in the process
+    Threads = 2,
+    /// Servers the process has created
+    Servers = 3,
+    /// Connections the process holds to servers
+    Connections = 4,
+    /// Milliseconds the process has spent on the CPU, charged whenever the CPU
+    /// passes to another process. This is counted but cannot be limited.
+    CpuTicks = 5,
+}
+
+/// The limit reported for a resource that has no limit
+pub const RESOURCE_UNLIMITED: usize = usize::MAX;
+
+impl Resource {
+    pub fn from_usize(arg: usize) -> Option<Self> {
+        match arg {
+            1 => Some(Resource::MemoryPages),
+            2 => Some(Resource::Threads),
+            3 => Some(Resource::Servers),
+            4 => Some(Resource::Connections),
+            5 => Some(Resource::CpuTicks),
+            _ => None,
+        }
+    }
+
+    /// Whether a limit may be set on this resource
+    pub fn can_be_limited(&self) -> bool {
+        *self != Resource::CpuTicks
+    }
+}
+
+/// A snapshot of the resources used by a process
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub memory_pages: usize,
+    pub threads: usize,
+    pub servers: usize,
+    pub connections: usize,
+    pub cpu_ticks: usize,
+}
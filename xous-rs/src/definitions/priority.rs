@@ -0,0 +1,35 @@
+/// The scheduling priority of a thread. When more than one thread is ready to
+/// run, the kernel picks the one with the highest priority. A thread that is
+/// ready but keeps getting passed over is gradually promoted, so that low
+/// priority threads still make progress on a busy system.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(usize)]
+pub enum ThreadPriority {
+    /// Only runs when nothing else wants to
+    Idle = 0,
+    /// Background work
+    Low = 1,
+    /// The priority of new threads, and the highest that most processes may use
+    Normal = 2,
+    /// Latency-sensitive work, such as input handling. Only the supervisor may
+    /// raise a thread to this priority.
+    High = 3,
+}
+
+impl ThreadPriority {
+    pub fn from_usize(arg: usize) -> Option<Self> {
+        match arg {
+            0 => Some(ThreadPriority::Idle),
+            1 => Some(ThreadPriority::Low),
+            2 => Some(ThreadPriority::Normal),
+            3 => Some(ThreadPriority::High),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ThreadPriority {
+    fn default() -> Self {
+        ThreadPriority::Normal
+    }
+}
@@ -0,0 +1,27 @@
+/// A privilege that belongs to one process in the system. Roles are handed out
+/// at boot by the loader, and can't be claimed by a process asking for them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Role {
+    /// May read the kernel's IPC trace
+    TraceReader = 1,
+    /// Is told when other processes exit, so it can restart them
+    Supervisor = 2,
+    /// May change which records the log server keeps, clear them, and have them
+    /// spilled to it
+    LogAdmin = 3,
+}
+
+/// The number of roles there are
+pub const ROLE_COUNT: usize = 3;
+
+impl Role {
+    pub fn from_usize(arg: usize) -> Option<Self> {
+        match arg {
+            1 => Some(Role::TraceReader),
+            2 => Some(Role::Supervisor),
+            3 => Some(Role::LogAdmin),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{Message, ScalarMessage, PID};
+
+/// The ID of the scalar message the kernel sends to the supervisor's server
+/// when another process exits. Its sender is PID 1, the kernel.
+pub const PROCESS_EXITED_MESSAGE_ID: usize = 0xffff_0000;
+
+/// Why a process stopped running
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The process called `terminate_process()` with this exit code
+    Exited(u32),
+
+    /// The process was stopped by a CPU exception it did not handle. This is
+    /// the value of the `scause` register.
+    Exception(usize),
+}
+
+/// A death notification, as sent to the supervisor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ProcessExit {
+    pub pid: PID,
+    pub reason: ExitReason,
+}
+
+impl ProcessExit {
+    pub fn to_message(&self) -> Message {
+        let (kind, detail) = match self.reason {
+            ExitReason::Exited(code) => (0, code as usize),
+            ExitReason::Exception(cause) => (1, cause),
+        };
+        Message::Scalar(ScalarMessage {
+            id: PROCESS_EXITED_MESSAGE_ID,
+            arg1: self.pid.get() as usize,
+            arg2: kind,
+            arg3: detail,
+            arg4: 0,
+        })
+    }
+
+    /// Decode a death notification, or return `None` if `message` is not one
+    pub fn from_message(message: &Message) -> Option<Self> {
+        let msg = match message {
+            Message::Scalar(msg) if msg.id == PROCESS_EXITED_MESSAGE_ID => msg,
+            _ => return None,
+        };
+        let reason = match msg.arg2 {
+            0 => ExitReason::Exited(msg.arg3 as u32),
+            1 => ExitReason::Exception(msg.arg3),
+            _ => return None,
+        };
+        Some(ProcessExit {
+            pid: PID::new(msg.arg1 as u8)?,
+            reason,
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{PID, TID};
+
+/// The kind of IPC operation recorded by the kernel's IPC trace
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(usize)]
+pub enum IpcTraceKind {
+    SendMessage = 1,
+    TrySendMessage = 2,
+    ReturnMemory = 3,
+    ReturnScalar1 = 4,
+    ReturnScalar2 = 5,
+}
+
+impl IpcTraceKind {
+    pub fn from_usize(arg: usize) -> Option<Self> {
+        match arg {
+            1 => Some(IpcTraceKind::SendMessage),
+            2 => Some(IpcTraceKind::TrySendMessage),
+            3 => Some(IpcTraceKind::ReturnMemory),
+            4 => Some(IpcTraceKind::ReturnScalar1),
+            5 => Some(IpcTraceKind::ReturnScalar2),
+            _ => None,
+        }
+    }
+}
+
+/// One IPC operation, as recorded by a kernel built with the `ipc-trace` feature.
+/// Operations are recorded when they are attempted, so a send that blocks forever
+/// still shows up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IpcTraceEvent {
+    /// Sequence number, which increases by one for every recorded operation
+    pub seq: usize,
+
+    /// When the operation happened. In hosted mode this is microseconds since
+    /// the kernel started; on hardware the kernel has no clock of its own, so
+    /// this is the number of times the scheduler has run.
+    pub timestamp: usize,
+
+    pub kind: IpcTraceKind,
+
+    /// The process and thread that performed the operation
+    pub pid: PID,
+    pub tid: TID,
+
+    /// The other end: the process that owns the server for sends, and the
+    /// process being replied to for returns. `None` if the connection or
+    /// sender was not valid.
+    pub peer: Option<PID>,
+
+    /// Index of the server in the kernel's server table
+    pub sidx: usize,
+
+    /// The message opcode for sends, and the first returned value for returns
+    pub arg: usize,
+}
+
+impl IpcTraceEvent {
+    /// Pack this event into five words, for returning it from the kernel
+    pub fn to_args(&self) -> [usize; 5] {
+        [
+            self.seq,
+            self.timestamp,
+            (self.kind as usize)
+                | (self.pid.get() as usize) << 8
+                | (self.tid & 0xff) << 16
+                | (self.peer.map(|p| p.get() as usize).unwrap_or(0)) << 24,
+            self.sidx,
+            self.arg,
+        ]
+    }
+
+    pub fn from_args(args: [usize; 5]) -> Option<Self> {
+        Some(IpcTraceEvent {
+            seq: args[0],
+            timestamp: args[1],
+            kind: IpcTraceKind::from_usize(args[2] & 0xff)?,
+            pid: PID::new(((args[2] >> 8) & 0xff) as u8)?,
+            tid: (args[2] >> 16) & 0xff,
+            peer: PID::new(((args[2] >> 24) & 0xff) as u8),
+            sidx: args[3],
+            arg: args[4],
+        })
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{Message, ScalarMessage};
+
+/// The ID of the scalar message the kernel sends to the ticktimer server when
+/// it needs to run again at a given time, for example because a thread's
+/// timeout is about to run out. Its sender is PID 1, the kernel.
+pub const KERNEL_WAKEUP_MESSAGE_ID: usize = 0xffff_0002;
+
+/// A request for the ticktimer to raise an interrupt no later than `deadline`,
+/// in milliseconds since boot
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KernelWakeup {
+    pub deadline: u64,
+}
+
+impl KernelWakeup {
+    pub fn to_message(&self) -> Message {
+        Message::Scalar(ScalarMessage {
+            id: KERNEL_WAKEUP_MESSAGE_ID,
+            arg1: self.deadline as u32 as usize,
+            arg2: (self.deadline >> 32) as usize,
+            arg3: 0,
+            arg4: 0,
+        })
+    }
+
+    /// Decode a wakeup request, or return `None` if `message` is not one
+    pub fn from_message(message: &Message) -> Option<Self> {
+        match message {
+            Message::Scalar(msg) if msg.id == KERNEL_WAKEUP_MESSAGE_ID => Some(KernelWakeup {
+                deadline: (msg.arg1 as u32 as u64) | ((msg.arg2 as u64) << 32),
+            }),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
-    pid_from_usize, CpuID, Error, MemoryAddress, MemoryFlags, MemoryMessage, MemoryRange,
-    MemorySize, MemoryType, Message, MessageEnvelope, MessageSender, ProcessArgs, ProcessInit,
-    Result, ScalarMessage, SysCallResult, ThreadInit, CID, PID, SID, TID,
+    pid_from_usize, CpuID, Error, IpcTraceEvent, MemoryAddress, MemoryFlags, MemoryMessage,
+    MemoryRange, MemorySize, MemoryType, Message, MessageEnvelope, MessageSender, ProcessArgs,
+    ProcessInit, Resource, ResourceUsage, Result, Role, ScalarMessage, SysCallResult, ThreadInit,
+    ThreadPriority, CID, PID, SID, TID,
 };
 use core::convert::{TryFrom, TryInto};
 /* https://github.com/betrusted-io/xous-core/issues/90
@@ -14,6 +15,11 @@ use crate::Exception
 #[cfg(feature = "processes-as-threads")]
 pub use crate::arch::ProcessArgsAsThread;
 
+/// The longest timeout `SendMessageTimeout` can carry, since it shares a word
+/// with the message type. On 32-bit targets this is a little over four and a
+/// half hours.
+pub const SEND_MESSAGE_TIMEOUT_MAX: usize = usize::MAX >> 8;
+
 #[derive(Debug, PartialEq)]
 pub enum SysCall {
     /// Allocates pages of memory, equal to a total of `size` bytes.  A physical
@@ -332,6 +338,7 @@ pub enum SysCall {
     /// # Errors
     ///
     /// * **ServerNotFound**: The server could not be found.
+    /// * **ServerDisconnected**: The process that owned the server has exited
     /// * **ProcessNotFound**: Internal error -- the parent process couldn't be found when blocking
     SendMessage(CID, Message),
 
@@ -347,6 +354,7 @@ pub enum SysCall {
     /// # Errors
     ///
     /// * **ServerNotFound**: The server could not be found.
+    /// * **ServerDisconnected**: The process that owned the server has exited
     /// * **ServerQueueFull**: The server's mailbox is full
     /// * **ProcessNotFound**: Internal error -- the parent process couldn't be found when blocking
     TrySendMessage(CID, Message),
@@ -457,6 +465,144 @@ pub enum SysCall {
         usize, /* proposed new limit */
     ),
 
+    /// Set the scheduling priority of one of the threads in this process.
+    /// Threads start out at `ThreadPriority::Normal`. Any process may lower
+    /// its threads and raise them back up to that, but only the process that
+    /// holds `Role::Supervisor` may raise them above it.
+    ///
+    /// ## Returns
+    ///
+    /// Returns a Scalar1 containing the previous priority of the thread.
+    ///
+    /// ## Errors
+    ///
+    ///     * **InvalidThread**: The thread ID is out of range
+    ///     * **AccessDenied**: The priority is above `ThreadPriority::Normal`,
+    ///                         and this process is not the supervisor
+    SetThreadPriority(TID, ThreadPriority),
+
+    /// Query how much of a resource the given process is using.
+    ///
+    /// ## Returns
+    ///
+    /// Returns a Scalar2 containing `(Usage, Limit)`. The limit is
+    /// `RESOURCE_UNLIMITED` if there is none.
+    ///
+    /// ## Errors
+    ///
+    ///     * **ProcessNotFound**: The process does not exist
+    GetResourceUsage(PID, Resource),
+
+    /// Set the limit on a resource of the given process. A process may lower
+    /// its own limits, and the process that created another process may set
+    /// that process' limits to anything. Other limits come from the loader.
+    /// Pass `RESOURCE_UNLIMITED` to remove a limit.
+    ///
+    /// ## Returns
+    ///
+    /// Returns a Scalar1 containing the previous limit.
+    ///
+    /// ## Errors
+    ///
+    ///     * **ProcessNotFound**: The process does not exist
+    ///     * **AccessDenied**: The caller may not change this limit
+    ///     * **InvalidLimit**: The resource cannot be limited
+    SetResourceLimit(PID, Resource, usize /* new limit */),
+
+    /// Read the oldest event in the kernel's IPC trace whose sequence number is
+    /// at least the given one. Only the process holding `Role::TraceReader` may
+    /// read the trace. Only available if the kernel was built with the
+    /// `ipc-trace` feature.
+    ///
+    /// ## Returns
+    ///
+    /// Returns a Scalar5 containing the packed `IpcTraceEvent`, or None if there
+    /// is no such event yet.
+    ///
+    /// ## Errors
+    ///
+    ///     * **AccessDenied**: The caller is not the trace reader
+    ///     * **UnhandledSyscall**: The kernel does not record an IPC trace
+    ReadIpcTrace(usize /* first sequence number */),
+
+    /// Wait for a message on the given server, like `ReceiveMessage`, but give
+    /// up after the given number of milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// * **Message**: A valid message from the queue
+    ///
+    /// # Errors
+    ///
+    /// * **ServerNotFound**: The given SID is not active or has terminated
+    /// * **Timeout**: No message arrived before the timeout expired
+    /// * **OutOfMemory**: The kernel is tracking too many timeouts already
+    ReceiveMessageTimeout(SID, usize /* timeout in ms */),
+
+    /// Send a message to a server, like `SendMessage`, but give up on a blocking
+    /// message if the server has not replied to it within the given number of
+    /// milliseconds, whether or not it has received it. A reply that comes later
+    /// is discarded. If the server's queue is full, this
+    /// returns immediately rather than waiting for room. The timeout is capped at
+    /// `SEND_MESSAGE_TIMEOUT_MAX`.
+    ///
+    /// # Returns
+    ///
+    /// * **Ok**: The Scalar / Send message was successfully sent, or the Borrow has finished
+    /// * **Scalar1**: The Server returned a `Scalar1` value
+    /// * **Scalar2**: The Server returned a `Scalar2` value
+    ///
+    /// # Errors
+    ///
+    /// * **ServerNotFound**: The server could not be found.
+    /// * **ServerDisconnected**: The process that owned the server has exited
+    /// * **ServerQueueFull**: The server's mailbox is full
+    /// * **Timeout**: The server did not reply in time. Any memory that was
+    ///                lent is returned unchanged.
+    /// * **OutOfMemory**: The kernel is tracking too many timeouts already
+    SendMessageTimeout(CID, Message, usize /* timeout in ms */),
+
+    /// Choose the server that the system's supervisor is told about exits on.
+    /// Only the process the loader gave `Role::Supervisor` may call this.
+    /// Whenever another process exits, the kernel sends a `ProcessExit` scalar
+    /// message to the given server. Clients of the servers that process owned
+    /// get `ServerDisconnected` errors until they reconnect, and the supervisor
+    /// may restart it with `create_process`.
+    ///
+    /// # Returns
+    ///
+    /// * **Ok**: Death notifications will be sent to the server
+    ///
+    /// # Errors
+    ///
+    /// * **ServerNotFound**: The server does not belong to this process
+    /// * **AccessDenied**: This process is not the supervisor
+    SetSupervisor(SID),
+
+    /// Find out which process owns a server. Servers use this to make sure a
+    /// client is only asking for messages to be sent to itself.
+    ///
+    /// # Returns
+    ///
+    /// * **ProcessID**: The process that created the server
+    ///
+    /// # Errors
+    ///
+    /// * **ServerNotFound**: No server with that SID exists
+    GetServerOwner(SID),
+
+    /// Find out which process the loader gave a role to. Servers use this to
+    /// limit requests that only the holder of a role may make.
+    ///
+    /// # Returns
+    ///
+    /// * **ProcessID**: The process that holds the role
+    ///
+    /// # Errors
+    ///
+    /// * **ProcessNotFound**: No running process holds the role
+    GetRoleHolder(Role),
+
     /// This syscall does not exist. It captures all possible
     /// arguments so detailed analysis can be performed.
     Invalid(usize, usize, usize, usize, usize, usize, usize),
@@ -501,6 +647,15 @@ pub enum SysCallNumber {
     JoinThread = 36,
     SetExceptionHandler = 37,
     AdjustProcessLimit = 38,
+    SetThreadPriority = 39,
+    GetResourceUsage = 40,
+    SetResourceLimit = 41,
+    ReadIpcTrace = 42,
+    ReceiveMessageTimeout = 43,
+    SendMessageTimeout = 44,
+    SetSupervisor = 45,
+    GetServerOwner = 46,
+    GetRoleHolder = 47,
     Invalid,
 }
 
@@ -545,6 +700,15 @@ impl SysCallNumber {
             36 => JoinThread,
             37 => SetExceptionHandler,
             38 => AdjustProcessLimit,
+            39 => SetThreadPriority,
+            40 => GetResourceUsage,
+            41 => SetResourceLimit,
+            42 => ReadIpcTrace,
+            43 => ReceiveMessageTimeout,
+            44 => SendMessageTimeout,
+            45 => SetSupervisor,
+            46 => GetServerOwner,
+            47 => GetRoleHolder,
             _ => Invalid,
         }
     }
@@ -909,6 +1073,121 @@ impl SysCall {
                 0,
                 0,
             ],
+            SysCall::SetThreadPriority(tid, priority) => [
+                SysCallNumber::SetThreadPriority as usize,
+                *tid,
+                *priority as usize,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::GetResourceUsage(pid, resource) => [
+                SysCallNumber::GetResourceUsage as usize,
+                pid.get() as usize,
+                *resource as usize,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::SetResourceLimit(pid, resource, limit) => [
+                SysCallNumber::SetResourceLimit as usize,
+                pid.get() as usize,
+                *resource as usize,
+                *limit,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::ReadIpcTrace(seq) => [
+                SysCallNumber::ReadIpcTrace as usize,
+                *seq,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::ReceiveMessageTimeout(sid, timeout) => {
+                let s = sid.to_u32();
+                [
+                    SysCallNumber::ReceiveMessageTimeout as usize,
+                    s.0 as _,
+                    s.1 as _,
+                    s.2 as _,
+                    s.3 as _,
+                    *timeout,
+                    0,
+                    0,
+                ]
+            }
+            SysCall::SendMessageTimeout(a1, ref a2, timeout) => {
+                // The timeout rides along in the upper bits of the message type
+                let kind = a2.message_type() | (*timeout).min(SEND_MESSAGE_TIMEOUT_MAX) << 8;
+                match a2 {
+                    Message::MutableBorrow(mm) | Message::Borrow(mm) | Message::Move(mm) => [
+                        SysCallNumber::SendMessageTimeout as usize,
+                        *a1 as usize,
+                        kind,
+                        mm.id as usize,
+                        mm.buf.as_ptr() as usize,
+                        mm.buf.len(),
+                        mm.offset.map(|x| x.get()).unwrap_or(0) as usize,
+                        mm.valid.map(|x| x.get()).unwrap_or(0) as usize,
+                    ],
+                    Message::Scalar(sc) | Message::BlockingScalar(sc) => [
+                        SysCallNumber::SendMessageTimeout as usize,
+                        *a1 as usize,
+                        kind,
+                        sc.id as usize,
+                        sc.arg1,
+                        sc.arg2,
+                        sc.arg3,
+                        sc.arg4,
+                    ],
+                }
+            }
+            SysCall::SetSupervisor(sid) => {
+                let s = sid.to_u32();
+                [
+                    SysCallNumber::SetSupervisor as usize,
+                    s.0 as _,
+                    s.1 as _,
+                    s.2 as _,
+                    s.3 as _,
+                    0,
+                    0,
+                    0,
+                ]
+            }
+            SysCall::GetServerOwner(sid) => {
+                let s = sid.to_u32();
+                [
+                    SysCallNumber::GetServerOwner as usize,
+                    s.0 as _,
+                    s.1 as _,
+                    s.2 as _,
+                    s.3 as _,
+                    0,
+                    0,
+                    0,
+                ]
+            }
+            SysCall::GetRoleHolder(role) => [
+                SysCallNumber::GetRoleHolder as usize,
+                *role as usize,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
             SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7) => [
                 SysCallNumber::Invalid as usize,
                 *a1,
@@ -1073,6 +1352,36 @@ impl SysCall {
             SysCallNumber::JoinThread => SysCall::JoinThread(a1 as _),
             SysCallNumber::SetExceptionHandler => SysCall::SetExceptionHandler(a1 as _, a2 as _),
             SysCallNumber::AdjustProcessLimit => SysCall::AdjustProcessLimit(a1, a2, a3),
+            SysCallNumber::SetThreadPriority => SysCall::SetThreadPriority(
+                a1 as _,
+                ThreadPriority::from_usize(a2).ok_or(Error::InvalidSyscall)?,
+            ),
+            SysCallNumber::GetResourceUsage => SysCall::GetResourceUsage(
+                pid_from_usize(a1)?,
+                Resource::from_usize(a2).ok_or(Error::InvalidSyscall)?,
+            ),
+            SysCallNumber::SetResourceLimit => SysCall::SetResourceLimit(
+                pid_from_usize(a1)?,
+                Resource::from_usize(a2).ok_or(Error::InvalidSyscall)?,
+                a3,
+            ),
+            SysCallNumber::ReadIpcTrace => SysCall::ReadIpcTrace(a1),
+            SysCallNumber::ReceiveMessageTimeout => SysCall::ReceiveMessageTimeout(
+                SID::from_u32(a1 as _, a2 as _, a3 as _, a4 as _),
+                a5,
+            ),
+            SysCallNumber::SendMessageTimeout => Message::try_from((a2 & 0xff, a3, a4, a5, a6, a7))
+                .map(|m| SysCall::SendMessageTimeout(a1.try_into().unwrap(), m, a2 >> 8))
+                .unwrap_or_else(|_| SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7)),
+            SysCallNumber::SetSupervisor => {
+                SysCall::SetSupervisor(SID::from_u32(a1 as _, a2 as _, a3 as _, a4 as _))
+            }
+            SysCallNumber::GetServerOwner => {
+                SysCall::GetServerOwner(SID::from_u32(a1 as _, a2 as _, a3 as _, a4 as _))
+            }
+            SysCallNumber::GetRoleHolder => {
+                SysCall::GetRoleHolder(Role::from_usize(a1).ok_or(Error::InvalidSyscall)?)
+            }
             SysCallNumber::Invalid => SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7),
         })
     }
@@ -1080,7 +1389,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that has memory attached to it
     pub fn has_memory(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(
                     msg,
                     Message::Move(_) | Message::Borrow(_) | Message::MutableBorrow(_)
@@ -1094,7 +1405,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that is a Move
     pub fn is_move(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(msg, Message::Move(_))
             }
             _ => false,
@@ -1104,7 +1417,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that is a Borrow
     pub fn is_borrow(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(msg, Message::Borrow(_))
             }
             _ => false,
@@ -1114,7 +1429,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that is a MutableBorrow
     pub fn is_mutableborrow(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(msg, Message::MutableBorrow(_))
             }
             _ => false,
@@ -1129,7 +1446,9 @@ impl SysCall {
     /// If the syscall has memory attached to it, return the memory
     pub fn memory(&self) -> Option<MemoryRange> {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => match msg {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => match msg {
                 Message::Move(memory_message)
                 | Message::Borrow(memory_message)
                 | Message::MutableBorrow(memory_message) => Some(memory_message.buf),
@@ -1148,7 +1467,9 @@ impl SysCall {
     /// not be used for any other purpose.
     pub unsafe fn memory_mut(&mut self) -> Option<&mut MemoryRange> {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => match msg {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => match msg {
                 Message::Move(memory_message)
                 | Message::Borrow(memory_message)
                 | Message::MutableBorrow(memory_message) => Some(&mut memory_message.buf),
@@ -1489,6 +1810,8 @@ pub fn try_receive_message(server: SID) -> core::result::Result<Option<MessageEn
 /// # Errors
 ///
 /// * **ServerNotFound**: The server does not exist so the connection is now invalid
+/// * **ServerDisconnected**: The process that owned the server has exited. Disconnect,
+///                            then connect again once the server is back.
 /// * **BadAddress**: The client tried to pass a Memory message using an address it doesn't own
 /// * **ServerQueueFull**: The queue in the server is full, and this call would block
 /// * **Timeout**: The timeout limit has been reached
@@ -1510,6 +1833,8 @@ pub fn try_send_message(connection: CID, message: Message) -> core::result::Resu
 /// # Errors
 ///
 /// * **ServerNotFound**: The server does not exist so the connection is now invalid
+/// * **ServerDisconnected**: The process that owned the server has exited. Disconnect,
+///                            then connect again once the server is back.
 /// * **BadAddress**: The client tried to pass a Memory message using an address it doesn't own
 /// * **ServerQueueFull**: The queue in the server is full, and this call would block
 /// * **Timeout**: The timeout limit has been reached
@@ -1532,6 +1857,8 @@ pub fn connect_for_process(pid: PID, sid: SID) -> core::result::Result<Result, E
 /// # Errors
 ///
 /// * **ServerNotFound**: The server does not exist so the connection is now invalid
+/// * **ServerDisconnected**: The process that owned the server has exited. Disconnect,
+///                            then connect again once the server is back.
 /// * **BadAddress**: The client tried to pass a Memory message using an address it doesn't own
 /// * **Timeout**: The timeout limit has been reached
 pub fn send_message(connection: CID, message: Message) -> core::result::Result<Result, Error> {
@@ -1546,6 +1873,54 @@ pub fn send_message(connection: CID, message: Message) -> core::result::Result<R
     }
 }
 
+/// Suspend the current thread until a message is received, or until `timeout_ms`
+/// milliseconds have passed.
+///
+/// # Errors
+///
+/// * **ServerNotFound**: The server does not exist
+/// * **Timeout**: No message arrived before the timeout expired
+pub fn receive_message_timeout(
+    server: SID,
+    timeout_ms: usize,
+) -> core::result::Result<MessageEnvelope, Error> {
+    let result = rsyscall(SysCall::ReceiveMessageTimeout(server, timeout_ms))?;
+    if let Result::Message(envelope) = result {
+        Ok(envelope)
+    } else if let Result::Error(e) = result {
+        Err(e)
+    } else {
+        Err(Error::InternalError)
+    }
+}
+
+/// Send a message to a server, giving up if the server has not replied to a
+/// blocking message within `timeout_ms` milliseconds.
+///
+/// # Errors
+///
+/// * **ServerNotFound**: The server does not exist so the connection is now invalid
+/// * **ServerDisconnected**: The process that owned the server has exited. Disconnect,
+///                            then connect again once the server is back.
+/// * **BadAddress**: The client tried to pass a Memory message using an address it doesn't own
+/// * **ServerQueueFull**: The queue in the server is full
+/// * **Timeout**: The server did not reply in time
+pub fn send_message_timeout(
+    connection: CID,
+    message: Message,
+    timeout_ms: usize,
+) -> core::result::Result<Result, Error> {
+    let result = rsyscall(SysCall::SendMessageTimeout(connection, message, timeout_ms));
+    match result {
+        Ok(Result::Ok) => Ok(Result::Ok),
+        Ok(Result::Scalar1(a)) => Ok(Result::Scalar1(a)),
+        Ok(Result::Scalar2(a, b)) => Ok(Result::Scalar2(a, b)),
+        Ok(Result::MemoryReturned(offset, valid)) => Ok(Result::MemoryReturned(offset, valid)),
+        Err(e) => Err(e),
+        v => panic!("Unexpected return value: {:?}", v),
+    }
+}
+
 pub fn terminate_process(exit_code: u32) -> ! {
     rsyscall(SysCall::TerminateProcess(exit_code)).expect("terminate_process returned an error");
     panic!("process didn't terminate");
@@ -1557,6 +1932,144 @@ pub fn yield_slice() {
     rsyscall(SysCall::Yield).ok();
 }
 
+/// Set the scheduling priority of the given thread in this process, returning
+/// its previous priority. Only the supervisor may go above `ThreadPriority::Normal`.
+///
+/// # Errors
+///
+/// * **InvalidThread**: The thread ID is out of range
+/// * **AccessDenied**: The priority is too high for this process
+pub fn set_thread_priority(
+    tid: TID,
+    priority: ThreadPriority,
+) -> core::result::Result<ThreadPriority, Error> {
+    rsyscall(SysCall::SetThreadPriority(tid, priority)).and_then(|result| {
+        if let Result::Scalar1(previous) = result {
+            ThreadPriority::from_usize(previous).ok_or(Error::InternalError)
+        } else if let Result::Error(e) = result {
+            Err(e)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Return `(usage, limit)` of a resource of the given process. The limit is
+/// `RESOURCE_UNLIMITED` if there is none.
+///
+/// # Errors
+///
+/// * **ProcessNotFound**: The process does not exist
+pub fn resource_usage(pid: PID, resource: Resource) -> core::result::Result<(usize, usize), Error> {
+    rsyscall(SysCall::GetResourceUsage(pid, resource)).and_then(|result| {
+        if let Result::Scalar2(usage, limit) = result {
+            Ok((usage, limit))
+        } else if let Result::Error(e) = result {
+            Err(e)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Return a snapshot of all resources used by the given process.
+///
+/// # Errors
+///
+/// * **ProcessNotFound**: The process does not exist
+pub fn process_usage(pid: PID) -> core::result::Result<ResourceUsage, Error> {
+    Ok(ResourceUsage {
+        memory_pages: resource_usage(pid, Resource::MemoryPages)?.0,
+        threads: resource_usage(pid, Resource::Threads)?.0,
+        servers: resource_usage(pid, Resource::Servers)?.0,
+        connections: resource_usage(pid, Resource::Connections)?.0,
+        cpu_ticks: resource_usage(pid, Resource::CpuTicks)?.0,
+    })
+}
+
+/// Set the limit on a resource of the given process, returning the previous limit.
+///
+/// # Errors
+///
+/// * **ProcessNotFound**: The process does not exist
+/// * **AccessDenied**: The caller may not change this limit
+/// * **InvalidLimit**: The resource cannot be limited
+pub fn set_resource_limit(
+    pid: PID,
+    resource: Resource,
+    limit: usize,
+) -> core::result::Result<usize, Error> {
+    rsyscall(SysCall::SetResourceLimit(pid, resource, limit)).and_then(|result| {
+        if let Result::Scalar1(previous) = result {
+            Ok(previous)
+        } else if let Result::Error(e) = result {
+            Err(e)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Read the oldest event in the kernel's IPC trace whose sequence number is at
+/// least `seq`, or `None` if there is no such event yet. Events that have been
+/// overwritten are skipped, which shows up as a gap in the sequence numbers.
+///
+/// # Errors
+///
+/// * **AccessDenied**: This process was not made the trace reader at boot
+/// * **UnhandledSyscall**: The kernel does not record an IPC trace
+pub fn read_ipc_trace(seq: usize) -> core::result::Result<Option<IpcTraceEvent>, Error> {
+    rsyscall(SysCall::ReadIpcTrace(seq)).and_then(|result| match result {
+        Result::Scalar5(a, b, c, d, e) => IpcTraceEvent::from_args([a, b, c, d, e])
+            .map(Some)
+            .ok_or(Error::InternalError),
+        Result::None => Ok(None),
+        Result::Error(e) => Err(e),
+        _ => Err(Error::InternalError),
+    })
+}
+
+/// Have every other process that exits reported through a `ProcessExit` message
+/// sent to `server`. Only the process the loader made the supervisor may do this.
+///
+/// # Errors
+///
+/// * **ServerNotFound**: The server does not belong to this process
+/// * **AccessDenied**: This process is not the supervisor
+pub fn set_supervisor(server: SID) -> core::result::Result<(), Error> {
+    rsyscall(SysCall::SetSupervisor(server)).and_then(|result| match result {
+        Result::Ok => Ok(()),
+        Result::Error(e) => Err(e),
+        _ => Err(Error::InternalError),
+    })
+}
+
+/// Return the PID of the process that created `server`.
+///
+/// # Errors
+///
+/// * **ServerNotFound**: No server with that SID exists
+pub fn server_owner(server: SID) -> core::result::Result<PID, Error> {
+    rsyscall(SysCall::GetServerOwner(server)).and_then(|result| match result {
+        Result::ProcessID(pid) => Ok(pid),
+        Result::Error(e) => Err(e),
+        _ => Err(Error::InternalError),
+    })
+}
+
+/// Return the PID of the process that holds `role`.
+///
+/// # Errors
+///
+/// * **ProcessNotFound**: No running process holds the role
+pub fn role_holder(role: Role) -> core::result::Result<PID, Error> {
+    rsyscall(SysCall::GetRoleHolder(role)).and_then(|result| match result {
+        Result::ProcessID(pid) => Ok(pid),
+        Result::Error(e) => Err(e),
+        _ => Err(Error::InternalError),
+    })
+}
+
 /// Return execution to the kernel and wait for a message or an interrupt.
 pub fn wait_event() {
     rsyscall(SysCall::WaitEvent).ok();
@@ -1702,9 +2215,28 @@ pub fn create_process_as_thread<F>(
 where
     F: FnOnce() + Send + 'static,
 {
+    create_process_as_thread_with_limits(args, &[])
+}
+
+/// Create a new process by running it in its own thread, with the given resource
+/// limits already in place when it starts.
+///
+/// # Errors
+///
+/// * **InvalidLimit**: One of the resources cannot be limited
+#[cfg(feature = "processes-as-threads")]
+pub fn create_process_as_thread_with_limits<F>(
+    args: ProcessArgsAsThread<F>,
+    limits: &[(Resource, usize)],
+) -> core::result::Result<crate::arch::ProcessHandleAsThread, Error>
+where
+    F: FnOnce() + Send + 'static,
+{
+    check_limits(limits)?;
     let process_init = crate::arch::create_process_pre_as_thread(&args)?;
     rsyscall(SysCall::CreateProcess(process_init)).and_then(|result| {
         if let Result::NewProcess(startup) = result {
+            apply_limits(startup.pid(), limits)?;
             crate::arch::create_process_post_as_thread(args, process_init, startup)
         } else {
             Err(Error::InternalError)
@@ -1721,9 +2253,24 @@ pub fn wait_process_as_thread(joiner: crate::arch::ProcessHandleAsThread) -> Sys
 pub fn create_process(
     args: ProcessArgs,
 ) -> core::result::Result<crate::arch::ProcessHandle, Error> {
+    create_process_with_limits(args, &[])
+}
+
+/// Create a new process with the given resource limits already in place when it
+/// starts, so it never runs unconstrained.
+///
+/// # Errors
+///
+/// * **InvalidLimit**: One of the resources cannot be limited
+pub fn create_process_with_limits(
+    args: ProcessArgs,
+    limits: &[(Resource, usize)],
+) -> core::result::Result<crate::arch::ProcessHandle, Error> {
+    check_limits(limits)?;
     let process_init = crate::arch::create_process_pre(&args)?;
     rsyscall(SysCall::CreateProcess(process_init)).and_then(|result| {
         if let Result::NewProcess(startup) = result {
+            apply_limits(startup.pid(), limits)?;
             crate::arch::create_process_post(args, process_init, startup)
         } else {
             Err(Error::InternalError)
@@ -1731,6 +2278,23 @@ pub fn create_process(
     })
 }
 
+/// Reject limits on resources that cannot be limited before creating anything
+fn check_limits(limits: &[(Resource, usize)]) -> core::result::Result<(), Error> {
+    if limits.iter().all(|(resource, _)| resource.can_be_limited()) {
+        Ok(())
+    } else {
+        Err(Error::InvalidLimit)
+    }
+}
+
+/// Apply limits to a process that was just created and has not started yet
+fn apply_limits(pid: PID, limits: &[(Resource, usize)]) -> core::result::Result<(), Error> {
+    for &(resource, limit) in limits {
+        set_resource_limit(pid, resource, limit)?;
+    }
+    Ok(())
+}
+
 /// Wait for a thread to finish
 pub fn wait_process(joiner: crate::arch::ProcessHandle) -> SysCallResult {
     crate::arch::wait_process(joiner)
@@ -434,6 +434,51 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     // "--features", "test-rekey",
                 ]), false)?
         }
+        Some("run-headless") => {
+            let mut pkgs = hw_pkgs.to_vec();
+            let mut apps: Vec<String> = get_packages();
+            if apps.len() == 0 {
+                println!("No apps specified, adding default apps...");
+                apps.push("ball".to_string());
+                apps.push("repl".to_string());
+            }
+            for app in &apps {
+                pkgs.push(app);
+            }
+            generate_app_menus(&apps);
+            run(false, &pkgs,
+                Some(&[
+                    "--features", "graphics-server/headless",
+                ]), false)?
+        }
+        Some("ui-golden") => {
+            let update = env::args().any(|x| x == "--update");
+            let bless = env::args().any(|x| x == "--bless");
+            let mut pkgs = hw_pkgs.to_vec();
+            pkgs.push("ui-golden");
+            // the main menu lists the apps, so always build the same ones for the references
+            let apps = vec!["ball".to_string(), "repl".to_string()];
+            for app in &apps {
+                pkgs.push(app);
+            }
+            generate_app_menus(&apps);
+            if update {
+                env::set_var("UI_GOLDEN_UPDATE", "1");
+            }
+            if bless {
+                env::set_var("UI_GOLDEN_BLESS", "1");
+            }
+            let mut report = project_root();
+            report.push("target");
+            report.push("ui-golden");
+            report.push("report.txt");
+            std::fs::remove_file(&report).ok();
+            run(false, &pkgs,
+                Some(&[
+                    "--features", "graphics-server/headless",
+                ]), false)?;
+            check_ui_golden_report(&report)?
+        }
         Some("hosted-ci") => {
             let mut pkgs = hw_pkgs.to_vec();
             let mut apps: Vec<String> = get_packages();
@@ -646,6 +691,11 @@ Hardware images:
 
 Hosted emulation:
  run [app1] [..]         runs a release build using a hosted environment plus specified apps
+ run-headless [app1] [..] like `run`, but draws into memory instead of a window; see Gfx::screenshot()
+ ui-golden [--update|--bless]
+                         compares the UI against the reference images in services/ui-golden/golden,
+                         or replaces the references with `--update`. `--bless` saves snapshots that
+                         have no reference yet to target/ui-golden/golden instead of failing on them
 
 Renode emulation:
  renode-image            builds a functional image for renode
@@ -1535,6 +1585,15 @@ fn create_image(
         args.push(i.to_str().ok_or(BuildError::PathConversionError)?);
     }
 
+    // The shell changes log filters and spills the log to the PDDB
+    if init
+        .iter()
+        .any(|i| i.file_stem().map_or(false, |s| s == "shellchat"))
+    {
+        args.push("--role");
+        args.push("shellchat:log-admin");
+    }
+
     match memory_spec {
         MemorySpec::SvdFile(ref s) => {
             args.push("--svd");
@@ -1557,6 +1616,21 @@ fn cargo() -> String {
     env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
 }
 
+/// Print the report left behind by the `ui-golden` harness, and fail if any snapshot
+/// didn't match its reference
+fn check_ui_golden_report(report: &Path) -> Result<(), DynError> {
+    let mut contents = String::new();
+    File::open(report)
+        .map_err(|_| "ui-golden didn't write a report; did it crash?")?
+        .read_to_string(&mut contents)?;
+    print!("{}", contents);
+    if contents.lines().last() == Some("RESULT ok") {
+        Ok(())
+    } else {
+        Err("UI golden image tests failed".into())
+    }
+}
+
 fn project_root() -> PathBuf {
     Path::new(&env!("CARGO_MANIFEST_DIR"))
         .ancestors()